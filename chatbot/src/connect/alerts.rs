@@ -0,0 +1,188 @@
+use super::{error::ConnectorError, Alert};
+use std::{
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use websocket::{
+    server::{NoTlsAcceptor, WsServer},
+    sync::{Client, Server},
+    OwnedMessage,
+};
+
+// alerts queued for a subscriber, a subscriber with a full queue is too slow and dropped
+const SUBSCRIBER_QUEUE_SIZE: usize = 16;
+// how long sending an alert to a subscriber may take before it is dropped
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Local websocket server for stream overlays (e.g. an OBS browser source).
+/// Every connected client receives the forwarded alerts as json text messages.
+pub struct AlertServer {
+    local_addr: SocketAddr,
+    subscribers: Arc<Mutex<Vec<SyncSender<String>>>>,
+}
+
+impl AlertServer {
+    /// Binds the alert server to the given port on localhost and starts accepting subscribers.
+    pub fn bind(port: u16) -> Result<Self, ConnectorError> {
+        let server = Server::bind(SocketAddr::from(([127, 0, 0, 1], port))).map_err(|err| {
+            ConnectorError::ExternalServerError(format!("Could not bind alert server: {:?}", err))
+        })?;
+        let local_addr = server.local_addr().map_err(|err| {
+            ConnectorError::ExternalServerError(format!("Could not bind alert server: {:?}", err))
+        })?;
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let accepted_subscribers = subscribers.clone();
+        thread::spawn(move || accept_subscribers(server, accepted_subscribers));
+        Ok(Self {
+            local_addr,
+            subscribers,
+        })
+    }
+
+    #[cfg(test)]
+    fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    #[cfg(test)]
+    fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    /// Sends the alert to all subscribers, dropping the ones which are gone or too slow.
+    pub fn broadcast(&self, alert: &Alert) {
+        let payload = alert.to_json().to_string();
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            match subscriber.try_send(payload.to_owned()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    println!("Dropping slow alert subscriber on {}", self.local_addr);
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
+
+fn accept_subscribers(
+    server: WsServer<NoTlsAcceptor, TcpListener>,
+    subscribers: Arc<Mutex<Vec<SyncSender<String>>>>,
+) {
+    for request in server.filter_map(Result::ok) {
+        match request.accept() {
+            Ok(client) => {
+                let (sender, receiver) = mpsc::sync_channel(SUBSCRIBER_QUEUE_SIZE);
+                subscribers.lock().unwrap().push(sender);
+                thread::spawn(move || send_alerts(client, receiver));
+            }
+            Err((_, error)) => println!("Could not accept alert subscriber: {:?}", error),
+        }
+    }
+}
+
+// ends when the subscriber is dropped by the server or the client is gone
+fn send_alerts(mut client: Client<TcpStream>, receiver: mpsc::Receiver<String>) {
+    if let Err(error) = client
+        .stream_ref()
+        .set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT))
+    {
+        println!(
+            "Could not set write timeout of alert subscriber: {:?}",
+            error
+        );
+        return;
+    }
+    for payload in receiver {
+        if client.send_message(&OwnedMessage::Text(payload)).is_err() {
+            break;
+        }
+    }
+    let _ = client.shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ChatBot, ChatBotCommand};
+    use serde_json::{json, Value};
+    use std::{collections::HashSet, time::Instant};
+    use websocket::ClientBuilder;
+
+    fn wait_for_subscribers(server: &AlertServer, count: usize) {
+        let start = Instant::now();
+        while server.subscriber_count() < count {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn receive_json(client: &mut Client<TcpStream>) -> Value {
+        match client.recv_message().unwrap() {
+            OwnedMessage::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn broadcasting_cheers_to_subscribers() {
+        let server = AlertServer::bind(0).unwrap();
+        let url = format!("ws://{}", server.local_addr());
+        let mut clients: Vec<_> = (0..2)
+            .map(|_| {
+                ClientBuilder::new(&url)
+                    .unwrap()
+                    .connect_insecure()
+                    .unwrap()
+            })
+            .collect();
+        wait_for_subscribers(&server, 2);
+
+        let mut bot = ChatBot::builder()
+            .alerts(HashSet::from(["cheer".to_owned()]), 100)
+            .build();
+        let message = "@badges=;bits=500;display-name=chatter :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :Cheer500 great stream";
+        match bot.handle_raw(message) {
+            Some(ChatBotCommand::MultipleCommands(commands)) => {
+                for command in commands {
+                    if let ChatBotCommand::Alert(alert) = command {
+                        server.broadcast(&alert);
+                    }
+                }
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let expected = json!({ "version": 1, "type": "cheer", "user": "chatter", "bits": 500 });
+        for client in clients.iter_mut() {
+            assert_eq!(receive_json(client), expected);
+        }
+    }
+
+    #[test]
+    fn dropping_disconnected_subscribers() {
+        let server = AlertServer::bind(0).unwrap();
+        let url = format!("ws://{}", server.local_addr());
+        let client = ClientBuilder::new(&url)
+            .unwrap()
+            .connect_insecure()
+            .unwrap();
+        wait_for_subscribers(&server, 1);
+        client.shutdown().unwrap();
+
+        let alert = Alert::Test {
+            user: "me".to_owned(),
+        };
+        let start = Instant::now();
+        while server.subscriber_count() > 0 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            server.broadcast(&alert);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::Event;
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{Receiver, TryRecvError},
+};
+
+// chat events waiting for later batches, in batches, beyond which the oldest ones are dropped
+const MAX_BACKLOG_BATCHES: usize = 10;
+
+/// Hands the events of the channel to the main loop in batches of a maximum size, the rest waits
+/// for the next batch. Events of the bot itself (e.g. of its timers, the control interface or the
+/// answers of the api) go ahead of waiting chat events, so that they are not stuck behind a flood.
+/// A flood which outgrows the backlog loses its oldest chat events, own events count towards the
+/// backlog but are always kept.
+pub struct EventBatches {
+    receiver: Receiver<Event>,
+    // events which did not fit into a batch yet, the own events in front of the chat events
+    backlog: VecDeque<Event>,
+    // the number of own events at the front of the backlog
+    own_events: usize,
+    max_batch_size: usize,
+    max_backlog: usize,
+    // chat events dropped since the last call of `take_dropped`
+    dropped: usize,
+}
+
+impl EventBatches {
+    pub fn new(receiver: Receiver<Event>, max_batch_size: usize) -> Self {
+        let max_batch_size = max_batch_size.max(1);
+        Self {
+            receiver,
+            backlog: VecDeque::new(),
+            own_events: 0,
+            max_batch_size,
+            max_backlog: max_batch_size * MAX_BACKLOG_BATCHES,
+            dropped: 0,
+        }
+    }
+
+    /// Waits for events if none are waiting and returns the next batch.
+    /// None once all senders are gone and every event was handed out.
+    pub fn next_batch(&mut self) -> Option<Vec<Event>> {
+        if self.backlog.is_empty() {
+            let event = self.receiver.recv().ok()?;
+            self.push_event(event);
+        }
+        // a flood which keeps coming is taken in portions, the rest waits in the channel
+        for _ in 0..self.max_batch_size + self.max_backlog {
+            match self.receiver.try_recv() {
+                Ok(event) => self.push_event(event),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            }
+        }
+        let batch_size = self.max_batch_size.min(self.backlog.len());
+        self.own_events = self.own_events.saturating_sub(batch_size);
+        Some(self.backlog.drain(..batch_size).collect())
+    }
+
+    fn push_event(&mut self, event: Event) {
+        // the oldest chat event makes room, behind the own events
+        if self.backlog.len() >= self.max_backlog && self.backlog.len() > self.own_events {
+            self.backlog.remove(self.own_events);
+            self.dropped += 1;
+        }
+        if event.raw.is_empty() {
+            self.backlog.insert(self.own_events, event);
+            self.own_events += 1;
+        } else {
+            self.backlog.push_back(event);
+        }
+    }
+
+    /// The number of events waiting for a later batch.
+    pub fn backlog(&self) -> usize {
+        self.backlog.len()
+    }
+
+    /// The number of chat events dropped from the full backlog since the last call.
+    pub fn take_dropped(&mut self) -> usize {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::ChatBotEvent;
+    use chrono::DateTime;
+    use std::sync::mpsc;
+
+    fn chat_event(number: usize) -> Event {
+        Event {
+            sent_at: DateTime::default(),
+            channel: Some("channel".to_owned()),
+            raw: format!(
+                ":viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :spam {}",
+                number
+            ),
+            content: ChatBotEvent::Say(number.to_string()),
+        }
+    }
+
+    #[test]
+    fn capping_the_batches() {
+        let (tx, rx) = mpsc::channel();
+        for number in 0..120 {
+            tx.send(chat_event(number)).unwrap();
+        }
+        tx.send(ChatBotEvent::CheckStream.into()).unwrap();
+        let mut batches = EventBatches::new(rx, 50);
+
+        let batch = batches.next_batch().unwrap();
+        assert_eq!(batch.len(), 50);
+        // the bot's own event is not stuck behind the flood
+        assert_eq!(batch[0].content, ChatBotEvent::CheckStream);
+        assert_eq!(batch[1], chat_event(0));
+        assert_eq!(batch[49], chat_event(48));
+        assert_eq!(batches.backlog(), 71);
+
+        tx.send(ChatBotEvent::Unresponsive.into()).unwrap();
+        let batch = batches.next_batch().unwrap();
+        assert_eq!(batch.len(), 50);
+        assert_eq!(batch[0].content, ChatBotEvent::Unresponsive);
+        assert_eq!(batch[1], chat_event(49));
+
+        drop(tx);
+        let batch = batches.next_batch().unwrap();
+        assert_eq!(batch.len(), 22);
+        assert_eq!(batch[21], chat_event(119));
+        assert_eq!(batches.backlog(), 0);
+        assert_eq!(batches.next_batch(), None);
+    }
+
+    #[test]
+    fn deferring_chat_events_behind_the_waiting_ones() {
+        let (tx, rx) = mpsc::channel();
+        for number in 0..3 {
+            tx.send(chat_event(number)).unwrap();
+        }
+        let mut batches = EventBatches::new(rx, 2);
+        assert_eq!(
+            batches.next_batch(),
+            Some(vec![chat_event(0), chat_event(1)])
+        );
+
+        // events arriving while the batch is handled: the chat waits its turn, the bot does not
+        tx.send(chat_event(3)).unwrap();
+        tx.send(ChatBotEvent::CheckStream.into()).unwrap();
+        let batch = batches.next_batch().unwrap();
+        assert_eq!(batch[0].content, ChatBotEvent::CheckStream);
+        assert_eq!(batch[1], chat_event(2));
+        assert_eq!(batches.backlog(), 1);
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(3)]));
+        assert_eq!(batches.backlog(), 0);
+    }
+
+    #[test]
+    fn dropping_the_oldest_chat_events_of_a_full_backlog() {
+        let (tx, rx) = mpsc::channel();
+        tx.send(chat_event(0)).unwrap();
+        let mut batches = EventBatches::new(rx, 1);
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(0)]));
+
+        for number in 1..=10 {
+            tx.send(chat_event(number)).unwrap();
+        }
+        tx.send(ChatBotEvent::CheckStream.into()).unwrap();
+        tx.send(ChatBotEvent::Unresponsive.into()).unwrap();
+        let batch = batches.next_batch().unwrap();
+        assert_eq!(batch[0].content, ChatBotEvent::CheckStream);
+        // the own events took the room of the two oldest chat events
+        assert_eq!(batches.backlog(), 9);
+        assert_eq!(batches.take_dropped(), 2);
+        assert_eq!(batches.take_dropped(), 0);
+        assert_eq!(
+            batches.next_batch().unwrap()[0].content,
+            ChatBotEvent::Unresponsive
+        );
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(3)]));
+    }
+
+    #[test]
+    fn taking_a_flood_in_portions() {
+        let (tx, rx) = mpsc::channel();
+        for number in 0..30 {
+            tx.send(chat_event(number)).unwrap();
+        }
+        let mut batches = EventBatches::new(rx, 1);
+        // one event waited for and eleven more, which overflow the backlog of ten by two
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(2)]));
+        assert_eq!(batches.backlog(), 9);
+        assert_eq!(batches.take_dropped(), 2);
+        // the next eleven events of the flood are taken with the next batch
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(13)]));
+        assert_eq!(batches.take_dropped(), 10);
+    }
+
+    #[test]
+    fn handing_out_single_events_without_a_flood() {
+        let (tx, rx) = mpsc::channel();
+        let mut batches = EventBatches::new(rx, 0);
+        tx.send(chat_event(1)).unwrap();
+        tx.send(chat_event(2)).unwrap();
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(1)]));
+        assert_eq!(batches.next_batch(), Some(vec![chat_event(2)]));
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, Utc,
+};
+use std::{fs::OpenOptions, io::Write, str::FromStr};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ChatLogError {
+    #[error("Invalid chat log timestamp format [{}]", .0)]
+    InvalidFormat(String),
+    #[error("Could not write chat log [{}]", .0)]
+    Io(#[from] std::io::Error),
+}
+
+/// The timezone of the timestamps in the chat log.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum LogTimezone {
+    #[default]
+    Utc,
+    Local,
+}
+
+impl FromStr for LogTimezone {
+    type Err = String;
+
+    fn from_str(timezone: &str) -> Result<Self, Self::Err> {
+        match timezone.to_lowercase().as_str() {
+            "utc" => Ok(LogTimezone::Utc),
+            "local" => Ok(LogTimezone::Local),
+            _ => Err(format!(
+                "unknown timezone {}, expected utc or local",
+                timezone
+            )),
+        }
+    }
+}
+
+/// Appends the messages of the chat to a file, each line starting with a timestamp.
+#[derive(Debug)]
+pub struct ChatLogger {
+    path: String,
+    // chrono format string, RFC 3339 if None
+    format: Option<String>,
+    timezone: LogTimezone,
+}
+
+impl ChatLogger {
+    /// Creates a logger writing timestamps with the chrono format string (e.g. `%Y-%m-%d %H:%M:%S`
+    /// or `%s` for seconds since the epoch), RFC 3339 if no format is given.
+    pub fn new(
+        path: &str,
+        format: Option<&str>,
+        timezone: LogTimezone,
+    ) -> Result<Self, ChatLogError> {
+        if let Some(format) = format {
+            // invalid formats would only fail when the first message is logged
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                return Err(ChatLogError::InvalidFormat(format.to_owned()));
+            }
+        }
+        Ok(Self {
+            path: path.to_owned(),
+            format: format.map(String::from),
+            timezone,
+        })
+    }
+
+    pub fn timestamp(&self, at: DateTime<Utc>) -> String {
+        match (&self.format, self.timezone) {
+            (None, LogTimezone::Utc) => at.to_rfc3339(),
+            (None, LogTimezone::Local) => at.with_timezone(&Local).to_rfc3339(),
+            (Some(format), LogTimezone::Utc) => at.format(format).to_string(),
+            (Some(format), LogTimezone::Local) => {
+                at.with_timezone(&Local).format(format).to_string()
+            }
+        }
+    }
+
+    /// Appends a line with the time of the event it belongs to, e.g. when the message was sent.
+    pub fn log(&self, at: DateTime<Utc>, line: &str) -> Result<(), ChatLogError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{} {}", self.timestamp(at), line)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn instant() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 11, 22, 20, 46, 42).unwrap()
+    }
+
+    #[test]
+    fn rendering_configured_formats() {
+        let logger = ChatLogger::new("chat.log", Some("%d.%m.%Y %H:%M"), LogTimezone::Utc).unwrap();
+        assert_eq!(logger.timestamp(instant()), "22.11.2021 20:46");
+        let logger = ChatLogger::new("chat.log", Some("%s"), LogTimezone::Utc).unwrap();
+        assert_eq!(logger.timestamp(instant()), "1637614002");
+        let logger = ChatLogger::new("chat.log", None, LogTimezone::Utc).unwrap();
+        assert_eq!(logger.timestamp(instant()), "2021-11-22T20:46:42+00:00");
+    }
+
+    #[test]
+    fn rejecting_invalid_formats() {
+        assert!(matches!(
+            ChatLogger::new("chat.log", Some("%Y-%m-%d %Q"), LogTimezone::Utc),
+            Err(ChatLogError::InvalidFormat(_))
+        ));
+        assert_eq!("Local".parse(), Ok(LogTimezone::Local));
+        assert!("cet".parse::<LogTimezone>().is_err());
+    }
+}
@@ -1,3 +1,8 @@
 pub(crate) mod twitch_chat;
 
-pub use twitch_chat::TwitchChatConnector;
+pub(crate) use twitch_chat::ReceiveEvent;
+pub(crate) use twitch_chat::{
+    poll_device_tokens, request_device_code, restrict_permissions, store_authorization,
+    AccessTokenDispenser, ID_URL,
+};
+pub use twitch_chat::{SeenMessages, TwitchChatConnector};
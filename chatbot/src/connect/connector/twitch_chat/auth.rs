@@ -1,18 +1,40 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use super::retry_manager::ExponentialRetryManager;
-use crate::{app_config::AppConfig, connect::error::ConnectorError};
+use crate::{
+    app_config::AppConfig,
+    connect::{error::ConnectorError, http_client, scopes::authorization_scopes},
+};
 use futures_retry::FutureRetry;
 use kv::*;
 use reqwest::Response;
 use serde_json::{from_str, Value};
 
 const VALIDATION_URL: &str = "https://id.twitch.tv/oauth2/validate";
+pub(crate) const ID_URL: &str = "https://id.twitch.tv/oauth2";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// twitch asks to poll more slowly by this much
+const SLOW_DOWN: Duration = Duration::from_secs(5);
 const REDIRECT_URI: &str = "https://localhost:3030";
 const AUTH_CONFIG_FILE: &str = "./auth_store";
 const AUTH_BUCKET_NAME: &str = "auth_config";
 const ACCESS_TOKEN_PERSISTENCE_KEY: &str = "access_token";
 const REFRESH_TOKEN_PERSISTENCE_KEY: &str = "refresh_token";
+const SCOPES_PERSISTENCE_KEY: &str = "scopes";
+
+/// The code the user enters at the verification uri to authorize the bot, so that no token
+/// is copied by hand and the bot needs no redirect server.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeviceCode {
+    device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: Duration,
+    interval: Duration,
+}
 
 async fn get_json_from_response(response: Response) -> Result<Value, ConnectorError> {
     let response_text = response.text().await?;
@@ -34,16 +56,31 @@ fn create_url_with_query_params(base: &str, query_params: &HashMap<&str, &str>)
     format!("{}?{}", base, query_params_options_strings.join("&"))
 }
 
-async fn access_token_is_valid(access_token: &str) -> Result<bool, ConnectorError> {
-    let client = reqwest::Client::new();
+/// The scopes granted to the access token, None if it is not valid (anymore).
+async fn validate_access_token(
+    client: &reqwest::Client,
+    access_token: &str,
+) -> Result<Option<Vec<String>>, ConnectorError> {
     let validation_response = client
         .get(VALIDATION_URL)
         .bearer_auth(access_token)
         .send()
         .await?;
     match validation_response.status().as_u16() {
-        401 => Ok(false),
-        200 => Ok(true),
+        401 => Ok(None),
+        200 => {
+            let json = get_json_from_response(validation_response).await?;
+            let scopes = json["scopes"]
+                .as_array()
+                .map(|scopes| {
+                    scopes
+                        .iter()
+                        .filter_map(|scope| scope.as_str().map(str::to_owned))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Ok(Some(scopes))
+        }
         status_code => Err(ConnectorError::ExternalServerError(format!(
             "Access token validation server sent bad response with http status code {}",
             status_code
@@ -51,9 +88,12 @@ async fn access_token_is_valid(access_token: &str) -> Result<bool, ConnectorErro
     }
 }
 
-async fn access_token_is_valid_retrying(access_token: &str) -> Result<bool, ConnectorError> {
+async fn validate_access_token_retrying(
+    client: &reqwest::Client,
+    access_token: &str,
+) -> Result<Option<Vec<String>>, ConnectorError> {
     FutureRetry::new(
-        || access_token_is_valid(access_token),
+        || validate_access_token(client, access_token),
         ExponentialRetryManager::new(Some(1), Some(3)),
     )
     .await
@@ -74,9 +114,10 @@ fn retrieve_code(client_id: &str) -> Result<String, ConnectorError> {
         ))
     })?;
     println!(
-            "Open link https://id.twitch.tv/oauth2/authorize?client_id={}&redirect_uri=https://localhost:3030&response_type=code&scope=chat:read%20chat:edit",
-            client_id,
-        );
+        "Open link https://id.twitch.tv/oauth2/authorize?client_id={}&redirect_uri=https://localhost:3030&response_type=code&scope={}",
+        client_id,
+        authorization_scopes().join("%20"),
+    );
     let request: tiny_http::Request = server.recv().map_err(|err| {
         ConnectorError::MessageReceiveFailed(format!(
             "Could not receive request with code: {:?}",
@@ -103,9 +144,14 @@ fn load_saved_access_token() -> Result<(String, String), ConnectorError> {
     }
 }
 
-async fn request_access_token(uri: &str) -> Result<(String, String), ConnectorError> {
-    let client = reqwest::Client::new();
-    let response = client.post(uri).send().await?;
+async fn request_access_token(
+    client: &reqwest::Client,
+    uri: &str,
+) -> Result<(String, String), ConnectorError> {
+    read_tokens(client.post(uri).send().await?).await
+}
+
+async fn read_tokens(response: Response) -> Result<(String, String), ConnectorError> {
     match response.status().as_u16() {
         200 => {
             let json = get_json_from_response(response).await?;
@@ -139,6 +185,7 @@ async fn request_access_token(uri: &str) -> Result<(String, String), ConnectorEr
 
 // https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow
 async fn request_new_access_token(
+    client: &reqwest::Client,
     client_id: &str,
     client_secret: &str,
 ) -> Result<(String, String), ConnectorError> {
@@ -152,15 +199,16 @@ async fn request_new_access_token(
         ("redirect_uri", REDIRECT_URI),
     ]);
     let uri = create_url_with_query_params("https://id.twitch.tv/oauth2/token", &query_params);
-    request_access_token(&uri).await
+    request_access_token(client, &uri).await
 }
 
 async fn request_new_access_token_retrying(
+    client: &reqwest::Client,
     client_id: &str,
     client_secret: &str,
 ) -> Result<(String, String), ConnectorError> {
     FutureRetry::new(
-        || request_new_access_token(client_id, client_secret),
+        || request_new_access_token(client, client_id, client_secret),
         ExponentialRetryManager::new(Some(1), Some(3)),
     )
     .await
@@ -170,6 +218,7 @@ async fn request_new_access_token_retrying(
 
 // https://dev.twitch.tv/docs/authentication#refreshing-access-tokens
 async fn refresh_access_token(
+    client: &reqwest::Client,
     client_id: &str,
     client_secret: &str,
     refresh_token: &str,
@@ -181,16 +230,17 @@ async fn refresh_access_token(
         ("grant_type", "refresh_token"),
     ]);
     let uri = create_url_with_query_params("https://id.twitch.tv/oauth2/token", &query_params);
-    request_access_token(&uri).await
+    request_access_token(client, &uri).await
 }
 
 async fn refresh_access_token_retrying(
+    client: &reqwest::Client,
     client_id: &str,
     client_secret: &str,
     refresh_token: &str,
 ) -> Result<(String, String), ConnectorError> {
     FutureRetry::new(
-        || refresh_access_token(client_id, client_secret, refresh_token),
+        || refresh_access_token(client, client_id, client_secret, refresh_token),
         ExponentialRetryManager::new(Some(1), Some(3)),
     )
     .await
@@ -212,20 +262,149 @@ fn store_tokens(access_token: &str, refresh_token: &str) {
     }
 }
 
-pub struct AccessTokenDispenser<'a> {
-    app_config: &'a AppConfig,
+// https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#device-code-grant-flow
+pub(crate) async fn request_device_code(
+    client: &reqwest::Client,
+    id_url: &str,
+    client_id: &str,
+    scopes: &[String],
+) -> Result<DeviceCode, ConnectorError> {
+    let response = client
+        .post(format!("{}/device", id_url))
+        .form(&[("client_id", client_id), ("scopes", &scopes.join(" "))])
+        .send()
+        .await?;
+    let status = response.status().as_u16();
+    let json = get_json_from_response(response).await?;
+    let text = |key: &str| json[key].as_str().map(str::to_owned);
+    let seconds = |key: &str| json[key].as_u64().map(Duration::from_secs);
+    match (
+        status,
+        text("device_code"),
+        text("user_code"),
+        text("verification_uri"),
+        seconds("expires_in"),
+        seconds("interval"),
+    ) {
+        (
+            200,
+            Some(device_code),
+            Some(user_code),
+            Some(verification_uri),
+            Some(expires_in),
+            Some(interval),
+        ) => Ok(DeviceCode {
+            device_code,
+            user_code,
+            verification_uri,
+            expires_in,
+            interval,
+        }),
+        (200, ..) => Err(ConnectorError::ExternalServerError(
+            "Server did not provide a device code in response".to_owned(),
+        )),
+        (status_code, ..) => Err(ConnectorError::ExternalServerError(format!(
+            "Device code request server sent bad response with http status code {}: {}",
+            status_code,
+            json["message"].as_str().unwrap_or_default()
+        ))),
+    }
+}
+
+/// Waits until the user authorized the device code and returns the access token and refresh token.
+pub(crate) async fn poll_device_tokens(
+    client: &reqwest::Client,
+    id_url: &str,
+    client_id: &str,
+    scopes: &[String],
+    device: &DeviceCode,
+) -> Result<(String, String), ConnectorError> {
+    let expires_at = Instant::now() + device.expires_in;
+    let mut interval = device.interval;
+    let scopes = scopes.join(" ");
+    loop {
+        tokio::time::sleep(interval).await;
+        if Instant::now() >= expires_at {
+            return Err(ConnectorError::DeviceCodeExpired);
+        }
+        let response = client
+            .post(format!("{}/token", id_url))
+            .form(&[
+                ("client_id", client_id),
+                ("scopes", &scopes),
+                ("device_code", &device.device_code),
+                ("grant_type", DEVICE_CODE_GRANT),
+            ])
+            .send()
+            .await?;
+        if response.status().as_u16() != 400 {
+            return read_tokens(response).await;
+        }
+        let json = get_json_from_response(response).await?;
+        match json["message"].as_str().unwrap_or_default() {
+            "authorization_pending" => {}
+            "slow_down" => interval += SLOW_DOWN,
+            "invalid device code" | "expired_token" => {
+                return Err(ConnectorError::DeviceCodeExpired)
+            }
+            "access_denied" | "authorization_declined" => {
+                return Err(ConnectorError::AuthorizationDeclined)
+            }
+            message => {
+                return Err(ConnectorError::ExternalServerError(format!(
+                    "Device token request was rejected: {}",
+                    message
+                )))
+            }
+        }
+    }
+}
+
+/// Stores the tokens of a new authorization with its scopes, readable by the owner only.
+pub(crate) fn store_authorization(
+    access_token: &str,
+    refresh_token: &str,
+    scopes: &[String],
+) -> Result<(), ConnectorError> {
+    let store = Store::new(Config::new(AUTH_CONFIG_FILE))?;
+    let bucket = store.bucket::<String, String>(Some(AUTH_BUCKET_NAME))?;
+    bucket.set(ACCESS_TOKEN_PERSISTENCE_KEY, access_token)?;
+    bucket.set(REFRESH_TOKEN_PERSISTENCE_KEY, refresh_token)?;
+    bucket.set(SCOPES_PERSISTENCE_KEY, scopes.join(" "))?;
+    restrict_permissions(AUTH_CONFIG_FILE, 0o700)?;
+    Ok(())
+}
+
+/// Makes a file or directory accessible by its owner only, e.g. `0o600` for a file.
+#[cfg(unix)]
+pub(crate) fn restrict_permissions(path: &str, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+/// Makes a file or directory accessible by its owner only, e.g. `0o600` for a file.
+#[cfg(not(unix))]
+pub(crate) fn restrict_permissions(_path: &str, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+pub struct AccessTokenDispenser {
+    client_id: String,
+    client_secret: String,
+    // goes through the configured proxy
+    client: reqwest::Client,
     access_token: String,
     refresh_token: String,
 }
 
-impl<'a> AccessTokenDispenser<'a> {
-    pub async fn new(
-        app_config: &'a AppConfig,
-    ) -> Result<AccessTokenDispenser<'a>, ConnectorError> {
+impl AccessTokenDispenser {
+    pub async fn new(app_config: &AppConfig) -> Result<AccessTokenDispenser, ConnectorError> {
+        let client = http_client(app_config.proxy())?;
         let (access_token, refresh_token) = match load_saved_access_token() {
             Ok(val) => val,
             Err(_) => {
                 let (access_token, refresh_token) = request_new_access_token_retrying(
+                    &client,
                     app_config.twitch_client_id(),
                     app_config.twitch_client_secret(),
                 )
@@ -235,20 +414,25 @@ impl<'a> AccessTokenDispenser<'a> {
             }
         };
         Ok(Self {
-            app_config,
+            client_id: app_config.twitch_client_id().to_owned(),
+            client_secret: app_config.twitch_client_secret().to_owned(),
+            client,
             access_token,
             refresh_token,
         })
     }
 
     pub async fn get(&mut self) -> Result<&str, ConnectorError> {
-        let access_token_valid = access_token_is_valid_retrying(&self.access_token).await?;
+        let access_token_valid = validate_access_token_retrying(&self.client, &self.access_token)
+            .await?
+            .is_some();
         let (access_token, refresh_token) = match access_token_valid {
             true => (self.access_token.to_owned(), self.refresh_token.to_owned()),
             false => {
                 refresh_access_token_retrying(
-                    &self.app_config.twitch_client_id(),
-                    &self.app_config.twitch_client_secret(),
+                    &self.client,
+                    &self.client_id,
+                    &self.client_secret,
                     &self.refresh_token,
                 )
                 .await?
@@ -259,11 +443,100 @@ impl<'a> AccessTokenDispenser<'a> {
         self.refresh_token = refresh_token;
         Ok(self.access_token.as_ref())
     }
+
+    /// The scopes granted to the access token, which is refreshed first if it expired.
+    pub async fn scopes(&mut self) -> Result<Vec<String>, ConnectorError> {
+        if let Some(scopes) =
+            validate_access_token_retrying(&self.client, &self.access_token).await?
+        {
+            return Ok(scopes);
+        }
+        self.refresh().await?;
+        validate_access_token_retrying(&self.client, &self.access_token)
+            .await?
+            .ok_or_else(|| {
+                ConnectorError::ExternalServerError(
+                    "Refreshed access token is not valid".to_owned(),
+                )
+            })
+    }
+
+    /// Refreshes the access token without validating it, e.g. after Twitch rejected it anyway.
+    pub async fn refresh(&mut self) -> Result<&str, ConnectorError> {
+        let (access_token, refresh_token) = refresh_access_token_retrying(
+            &self.client,
+            &self.client_id,
+            &self.client_secret,
+            &self.refresh_token,
+        )
+        .await?;
+        store_tokens(&access_token, &refresh_token);
+        self.access_token = access_token;
+        self.refresh_token = refresh_token;
+        Ok(self.access_token.as_ref())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::connect::scripted_server::scripted_server;
+
+    const DEVICE_CODE: &str = r#"{"device_code":"ike3GM8QIdYZs43KdrWPIO36LofILoCyFEzjlQ91","expires_in":1800,"interval":0,"user_code":"ABCDEFGH","verification_uri":"https://www.twitch.tv/activate?public=true&device-code=ABCDEFGH"}"#;
+
+    fn scopes() -> Vec<String> {
+        vec!["chat:read".to_owned(), "chat:edit".to_owned()]
+    }
+
+    #[tokio::test]
+    async fn authorizing_with_a_device_code() {
+        let (url, requests) = scripted_server(&[
+            (200, DEVICE_CODE),
+            (400, r#"{"status":400,"message":"authorization_pending"}"#),
+            (
+                200,
+                r#"{"access_token":"access","refresh_token":"refresh","expires_in":14124,"scope":["chat:read","chat:edit"],"token_type":"bearer"}"#,
+            ),
+        ])
+        .await;
+        let client = reqwest::Client::new();
+        let device = request_device_code(&client, &url, "client", &scopes())
+            .await
+            .unwrap();
+        assert_eq!(device.user_code, "ABCDEFGH");
+        assert_eq!(device.expires_in, Duration::from_secs(1800));
+        let tokens = poll_device_tokens(&client, &url, "client", &scopes(), &device).await;
+        assert_eq!(tokens.unwrap(), ("access".to_owned(), "refresh".to_owned()));
+        let requests = requests.await.unwrap();
+        assert!(requests[0].starts_with("post /device"));
+        assert!(requests[0].contains("scopes=chat%3aread+chat%3aedit"));
+        assert!(requests[2].starts_with("post /token"));
+        assert!(requests[2]
+            .contains("grant_type=urn%3aietf%3aparams%3aoauth%3agrant-type%3adevice_code"));
+    }
+
+    #[tokio::test]
+    async fn failing_on_an_expired_device_code() {
+        let (url, _) = scripted_server(&[
+            (200, DEVICE_CODE),
+            (400, r#"{"status":400,"message":"authorization_pending"}"#),
+            (400, r#"{"status":400,"message":"invalid device code"}"#),
+        ])
+        .await;
+        let client = reqwest::Client::new();
+        let device = request_device_code(&client, &url, "client", &scopes())
+            .await
+            .unwrap();
+        let tokens = poll_device_tokens(&client, &url, "client", &scopes(), &device).await;
+        assert!(matches!(tokens, Err(ConnectorError::DeviceCodeExpired)));
+        // the code also expires while nobody answers
+        let device = DeviceCode {
+            expires_in: Duration::ZERO,
+            ..device
+        };
+        let tokens = poll_device_tokens(&client, &url, "client", &scopes(), &device).await;
+        assert!(matches!(tokens, Err(ConnectorError::DeviceCodeExpired)));
+    }
 
     #[test]
     fn getting_code_out_of_url() {
@@ -0,0 +1,115 @@
+use super::receive::{ConnectorEvent, ReceiveEvent};
+use crate::connect::error::ConnectorError;
+use std::collections::HashSet;
+
+// the bot depends on the tags for badges, cheers and redemptions
+pub const REQUIRED_CAPABILITIES: [&str; 1] = ["tags"];
+// the login replies are few lines, the answers to the capability requests come with them
+const MAX_NEGOTIATION_EVENTS: usize = 50;
+
+/// Waits for the answers of the chat server to the requested capabilities,
+/// each capability is requested separately so that it is acknowledged or rejected on its own.
+/// Returns the acknowledged capabilities, or an error if a required one is rejected.
+pub fn negotiate<F, E>(requested: &[String], mut receive: F) -> Result<Vec<String>, ConnectorError>
+where
+    F: FnMut() -> Result<Vec<ReceiveEvent<E>>, ConnectorError>,
+{
+    let mut pending: HashSet<&str> = requested.iter().map(String::as_str).collect();
+    let mut acknowledged = Vec::new();
+    let mut received = 0;
+    while !pending.is_empty() {
+        if received >= MAX_NEGOTIATION_EVENTS {
+            return Err(ConnectorError::ExternalServerError(format!(
+                "No answer to the capability requests {:?}",
+                pending
+            )));
+        }
+        for event in receive()? {
+            received += 1;
+            if let ReceiveEvent::ConnectorEvent(ConnectorEvent::Capabilities {
+                acknowledged: ack,
+                names,
+            }) = event
+            {
+                for name in names {
+                    pending.remove(name.as_str());
+                    if ack {
+                        acknowledged.push(name);
+                    } else if REQUIRED_CAPABILITIES.contains(&name.as_str()) {
+                        return Err(ConnectorError::CapabilityRejected(name));
+                    } else {
+                        println!("The chat server rejected the capability {}", name);
+                    }
+                }
+            }
+        }
+    }
+    Ok(acknowledged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // plays the given websocket messages (lines separated by newlines) like the chat server
+    fn scripted(messages: &[&str]) -> impl FnMut() -> Result<Vec<ReceiveEvent>, ConnectorError> {
+        let mut messages: VecDeque<String> = messages.iter().map(|m| m.to_string()).collect();
+        move || match messages.pop_front() {
+            Some(message) => Ok(message
+                .lines()
+                .filter_map(ReceiveEvent::parse_from_message)
+                .collect()),
+            None => Err(ConnectorError::MessageReceiveFailed(
+                "connection closed".to_owned(),
+            )),
+        }
+    }
+
+    fn capabilities(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn acknowledging_all_capabilities() {
+        let receive = scripted(&[
+            ":tmi.twitch.tv 001 bot :Welcome, GLHF!\n:tmi.twitch.tv 376 bot :>",
+            ":tmi.twitch.tv CAP * ACK :twitch.tv/tags\n:tmi.twitch.tv CAP * ACK :twitch.tv/commands",
+            ":tmi.twitch.tv CAP * ACK :twitch.tv/membership",
+        ]);
+        let acknowledged =
+            negotiate(&capabilities(&["tags", "commands", "membership"]), receive).unwrap();
+        assert_eq!(
+            acknowledged,
+            capabilities(&["tags", "commands", "membership"])
+        );
+    }
+
+    #[test]
+    fn continuing_without_optional_capabilities() {
+        let receive = scripted(&[
+            ":tmi.twitch.tv CAP * ACK :twitch.tv/tags",
+            ":tmi.twitch.tv CAP * NAK :twitch.tv/membership",
+        ]);
+        let acknowledged = negotiate(&capabilities(&["tags", "membership"]), receive).unwrap();
+        assert_eq!(acknowledged, capabilities(&["tags"]));
+    }
+
+    #[test]
+    fn failing_without_required_capabilities() {
+        let receive = scripted(&[
+            ":tmi.twitch.tv CAP * NAK :twitch.tv/tags",
+            ":tmi.twitch.tv CAP * ACK :twitch.tv/commands",
+        ]);
+        assert!(matches!(
+            negotiate(&capabilities(&["tags", "commands"]), receive),
+            Err(ConnectorError::CapabilityRejected(name)) if name == "tags"
+        ));
+    }
+
+    #[test]
+    fn failing_without_answer() {
+        let receive = scripted(&[":tmi.twitch.tv 001 bot :Welcome, GLHF!"]);
+        assert!(negotiate(&capabilities(&["tags"]), receive).is_err());
+    }
+}
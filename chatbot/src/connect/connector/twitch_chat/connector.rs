@@ -1,93 +1,391 @@
 use super::{
     auth::AccessTokenDispenser,
-    receive::{receive, ReceiveEvent},
-    send::{get_login_tasks, send, send_multiple, SendTask},
+    capabilities::negotiate,
+    duplicate::DuplicateGuard,
+    idle::IdleChannels,
+    join::join_channels,
+    mode_gate::{ModeGate, Poll},
+    ping::PingTracker,
+    quarantine::{Quarantine, QuarantineEntry},
+    rate_limit::{RateLimiter, MESSAGE_BURST, MESSAGE_INTERVAL},
+    receive::{receive, ConnectorEvent, ReceiveEvent},
+    seen::SeenMessages,
+    send::{get_login_tasks, raw_task, sanitize_message, send, send_multiple, SendTask},
+    stream::ChatStream,
 };
 use crate::{
     app_config::AppConfig,
-    connect::{error::ConnectorError, ChatBotEvent},
+    connect::{error::ConnectorError, ChatBotEvent, Event, EventFilter},
 };
 use std::{
-    net::TcpStream,
-    sync::mpsc::{self, Sender, SyncSender},
+    collections::VecDeque,
+    net::{Shutdown, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender, SyncSender},
+        Arc, Mutex,
+    },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
-use websocket::{receiver::Reader, sync::Writer, ClientBuilder};
+use uuid::Uuid;
+use websocket::{
+    sync::{stream::ReadWritePair, Writer},
+    url::Url,
+    ClientBuilder,
+};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+// the connection is probed with a ping in this interval
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+// the connection is considered dead after this many pings without answer in a row
+const MAX_FAILED_PROBES: u32 = 3;
+// idle channels are parted (and joined again) this long after their time at the latest
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// while chat messages are held back, the send thread checks this often whether the chat modes changed
+const HELD_RECHECK: Duration = Duration::from_secs(1);
 
 pub struct TwitchChatConnector<'a> {
-    _receive_thread: ReceiveThread,
+    receive_thread: ReceiveThread,
     send_thread: SendThread,
+    // the tcp stream under the connection of both threads, shutting it down stops them
+    stream: TcpStream,
     app_config: &'a AppConfig,
+    quarantine: Arc<Mutex<Quarantine>>,
+    ping_tracker: Arc<Mutex<PingTracker>>,
+    seen_messages: Arc<Mutex<SeenMessages>>,
+    // stops the probe thread when sent to or dropped
+    stop_probes: Sender<()>,
+    // stops the thread parting idle channels, None unless they are parted
+    stop_idle_checks: Option<Sender<()>>,
+    // kept for the connector which replaces this one when reconnecting
+    chatbot_event_sender: Sender<Event>,
+    channels: Vec<String>,
+    // reconnects since the bot started, the first connector has none
+    reconnect_count: u32,
 }
 
 impl<'a> TwitchChatConnector<'a> {
+    /// Connects to the chat server, logs in with the configured capabilities and joins the channel.
+    /// Fails if the chat server rejects a capability the bot depends on.
+    /// The seen messages are shared with earlier connectors, to drop messages redelivered after reconnecting.
     pub async fn new(
         app_config: &'a AppConfig,
-        chatbot_event_sender: Sender<ChatBotEvent>,
-    ) -> TwitchChatConnector<'a> {
-        let chat_client = ClientBuilder::new("ws://irc-ws.chat.twitch.tv:80")
-            .unwrap()
-            .connect_insecure()
-            .unwrap();
-        let (receiver, mut sender) = chat_client.split().unwrap();
-        let mut access_token_dispenser = AccessTokenDispenser::new(app_config)
-            .await
-            .expect("Could not instantiate Twitch connector");
-        let access_token: String = access_token_dispenser
-            .get()
-            .await
-            .expect("Could not get valid access token")
-            .to_owned();
+        chatbot_event_sender: Sender<Event>,
+        seen_messages: Arc<Mutex<SeenMessages>>,
+    ) -> Result<TwitchChatConnector<'a>, ConnectorError> {
+        let mut access_token_dispenser = AccessTokenDispenser::new(app_config).await?;
+        let access_token: String = access_token_dispenser.get().await?.to_owned();
+        Self::connect(
+            app_config,
+            &access_token,
+            chatbot_event_sender,
+            seen_messages,
+            0,
+        )
+    }
+
+    /// Closes the connection and connects again with the same event sender and seen messages.
+    pub async fn reconnect(self) -> Result<TwitchChatConnector<'a>, ConnectorError> {
+        let mut access_token_dispenser = AccessTokenDispenser::new(self.app_config).await?;
+        let access_token: String = access_token_dispenser.get().await?.to_owned();
+        self.reconnect_with(&access_token)
+    }
+
+    fn reconnect_with(self, access_token: &str) -> Result<TwitchChatConnector<'a>, ConnectorError> {
+        if let Err(error) = self.close() {
+            println!("Could not close the chat connection: {:?}", error);
+        }
+        Self::connect(
+            self.app_config,
+            access_token,
+            self.chatbot_event_sender.clone(),
+            self.seen_messages.clone(),
+            self.reconnect_count + 1,
+        )
+    }
+
+    // the connection itself does not need the Twitch api, so that it can be tested against a local server
+    fn connect(
+        app_config: &'a AppConfig,
+        access_token: &str,
+        chatbot_event_sender: Sender<Event>,
+        seen_messages: Arc<Mutex<SeenMessages>>,
+        reconnect_count: u32,
+    ) -> Result<TwitchChatConnector<'a>, ConnectorError> {
+        let server_url = Url::parse(app_config.server_url())
+            .map_err(|err| ConnectorError::ExternalServerError(err.to_string()))?;
+        let host = server_url.host_str().unwrap_or_default();
+        let port = server_url.port_or_known_default().unwrap_or(80);
+        let tcp_stream = match app_config.proxy() {
+            Some(proxy) => proxy.tunnel(host, port)?,
+            None => TcpStream::connect((host, port))?,
+        };
+        let stream = tcp_stream.try_clone()?;
+        let (reader, writer) = ChatStream::open(tcp_stream, server_url.scheme() == "wss", host)?;
+        let chat_client =
+            ClientBuilder::from_url(&server_url).connect_on(ReadWritePair(reader, writer))?;
+        let (mut receiver, mut sender) = chat_client
+            .split()
+            .map_err(|err| ConnectorError::ExternalServerError(err.to_string()))?;
         send_multiple(
             &mut sender,
             get_login_tasks(
-                &access_token,
+                access_token,
                 app_config.bot_user_name(),
-                app_config.channel_name(),
+                app_config.capabilities(),
             ),
-        )
-        .expect("Could not log in");
-        let send_thread = send_thread(sender);
-        let receive_thread = receive_thread(receiver, chatbot_event_sender, send_thread.tx.clone());
-        Self {
+        )?;
+        let quarantine = Arc::new(Mutex::new(Quarantine::new(
+            app_config.unparsed_ignore(),
+            app_config.unparsed_log().map(String::from),
+        )));
+        let capabilities = negotiate(app_config.capabilities(), || {
+            receive(
+                &mut receiver,
+                &quarantine,
+                &seen_messages,
+                app_config.command_prefixes(),
+                app_config.suppress_membership(),
+            )
+        })?;
+        println!(
+            "Capabilities acknowledged by the chat server: {:?}",
+            capabilities
+        );
+        let channels = vec![app_config.channel_name().to_owned()];
+        join_channels(
+            &channels,
+            app_config.join_delay(),
+            |task| send(&mut sender, task),
+            thread::sleep,
+        )?;
+        chatbot_event_sender
+            .send(ChatBotEvent::Connected.into())
+            .map_err(|err| ConnectorError::MessageReceiveFailed(err.to_string()))?;
+        let ping_tracker = Arc::new(Mutex::new(PingTracker::default()));
+        let send_thread = send_thread(
+            sender,
+            DuplicateGuard::new(app_config.distinct_duplicates()),
+            app_config.channel_name().to_owned(),
+        );
+        let stop_probes = probe_thread(
+            send_thread.queue.clone(),
+            ping_tracker.clone(),
+            chatbot_event_sender.clone(),
+        );
+        let idle_channels = app_config.idle_part().map(|timeout| {
+            Arc::new(Mutex::new(IdleChannels::new(
+                &channels,
+                timeout,
+                app_config.idle_rejoin(),
+                Instant::now(),
+            )))
+        });
+        let stop_idle_checks = idle_channels
+            .clone()
+            .map(|idle_channels| idle_thread(send_thread.queue.clone(), idle_channels));
+        let receive_quarantine = quarantine.clone();
+        let receive_seen_messages = seen_messages.clone();
+        let command_prefixes = app_config.command_prefixes().clone();
+        let suppress_membership = app_config.suppress_membership();
+        let receive_thread = receive_thread(
+            move || {
+                receive(
+                    &mut receiver,
+                    &receive_quarantine,
+                    &receive_seen_messages,
+                    &command_prefixes,
+                    suppress_membership,
+                )
+            },
+            chatbot_event_sender.clone(),
+            send_thread.queue.clone(),
+            EventFilter::ignoring(app_config.ignored_events()),
+            ping_tracker.clone(),
+            idle_channels,
+        );
+        Ok(Self {
             send_thread,
-            _receive_thread: receive_thread,
+            receive_thread,
+            stream,
             app_config,
-        }
+            quarantine,
+            ping_tracker,
+            seen_messages,
+            stop_probes,
+            stop_idle_checks,
+            chatbot_event_sender,
+            channels,
+            reconnect_count,
+        })
+    }
+
+    /// Sends a ping to the chat server. The matching pong is waited for on a thread of its own,
+    /// so that the caller is not blocked, the round trip time (None without a pong in time)
+    /// comes back as ChatBotEvent::Latency.
+    pub fn measure_latency(&self) -> Result<(), ConnectorError> {
+        let token = Uuid::new_v4().to_string();
+        let pong = self
+            .ping_tracker
+            .lock()
+            .unwrap()
+            .start(&token, Instant::now());
+        self.send_thread.queue.push(SendTask::Ping(token.clone()))?;
+        let ping_tracker = self.ping_tracker.clone();
+        let chatbot_event_sender = self.chatbot_event_sender.clone();
+        thread::spawn(move || {
+            let latency = pong.recv_timeout(PING_TIMEOUT).ok();
+            if latency.is_none() {
+                println!("No pong within {:?}", PING_TIMEOUT);
+                ping_tracker.lock().unwrap().fail(&token);
+            }
+            let _ = chatbot_event_sender.send(ChatBotEvent::Latency(latency).into());
+        });
+        Ok(())
+    }
+
+    /// Returns the average round trip time of the last pings, None before the first pong.
+    pub fn latency(&self) -> Option<Duration> {
+        self.ping_tracker.lock().unwrap().average()
+    }
+
+    /// Returns the round trip time of the last answered ping.
+    pub fn last_latency(&self) -> Option<Duration> {
+        self.ping_tracker.lock().unwrap().last()
+    }
+
+    /// Returns the last `count` received lines which could not be parsed
+    /// and the number of all unparsed lines since the connector was created.
+    pub fn unparsed_lines(&self, count: usize) -> (Vec<QuarantineEntry>, u64) {
+        let quarantine = self.quarantine.lock().unwrap();
+        (quarantine.last(count), quarantine.total())
     }
 
+    /// Queues a chat message. In emote-only mode it is held back until the mode ends,
+    /// in slow mode it is sent after the slow mode interval, unless the bot is a moderator.
+    /// After a burst of messages, the rate limiter spaces them out as the chat server requires.
+    /// Control characters are stripped, so that the message cannot inject other commands.
     pub fn send_message(&self, message: &'a str) -> Result<(), ConnectorError> {
-        Ok(self.send_thread.tx.send(SendTask::PrivateMessage(
+        self.send_thread.queue.push(SendTask::PrivateMessage(
             self.app_config.channel_name().to_string(),
-            message.to_string(),
-        ))?)
+            sanitize_message(message),
+        ))
+    }
+
+    /// Queues a chat message to another channel. The bot does not join the channel, so that its
+    /// chat is not handled as the chat of the bot's channel, and the chat modes of the bot's channel
+    /// do not hold the message back.
+    pub fn send_message_to(&self, channel: &str, message: &str) -> Result<(), ConnectorError> {
+        self.send_thread.queue.push(SendTask::PrivateMessage(
+            channel.to_lowercase(),
+            sanitize_message(message),
+        ))
+    }
+
+    /// Queues a raw IRC line (e.g. `CAP REQ :twitch.tv/membership`) without the framing of
+    /// `send_message`, for experiments the connector has no method for. Control characters are
+    /// stripped, so that the line stays one command, but nothing else is checked: a raw `PART`,
+    /// `NICK` or `QUIT` changes the connection behind the back of the connector and the bot,
+    /// and lines the server rejects may get the bot disconnected. Raw chat messages to the
+    /// bot's channel wait for the chat modes like the other chat messages, and all raw lines
+    /// wait for the rate limiter like chat messages do.
+    pub fn send_raw(&self, line: &str) -> Result<(), ConnectorError> {
+        self.send_thread.queue.push(raw_task(line))
+    }
+
+    /// Whether the connection to the chat server is still open.
+    pub fn is_connected(&self) -> bool {
+        !self.receive_thread.handle.is_finished()
+    }
+
+    /// Returns the channels the bot joined, only its own channel for now.
+    pub fn channels(&self) -> &[String] {
+        &self.channels
+    }
+
+    /// Returns the number of reconnects since the bot started.
+    pub fn reconnect_count(&self) -> u32 {
+        self.reconnect_count
+    }
+
+    /// Returns the number of messages (and other tasks) waiting to be sent to the chat server.
+    pub fn queued_messages(&self) -> usize {
+        self.send_thread.queue.len()
+    }
+
+    /// Returns the number of redelivered messages which were dropped since the bot started.
+    pub fn suppressed_duplicates(&self) -> u64 {
+        self.seen_messages.lock().unwrap().suppressed()
+    }
+
+    /// Closes the connection to the chat server, which stops the receive, send and probe threads.
+    pub fn close(&self) -> Result<(), ConnectorError> {
+        // the probe thread might be gone already
+        let _ = self.stop_probes.send(());
+        if let Some(stop_idle_checks) = &self.stop_idle_checks {
+            let _ = stop_idle_checks.send(());
+        }
+        Ok(self.stream.shutdown(Shutdown::Both)?)
     }
 }
 
 struct ReceiveThread {
-    _handle: JoinHandle<()>,
+    handle: JoinHandle<()>,
 }
 
 fn receive_thread(
-    mut receiver: Reader<TcpStream>,
-    send_chat_bot_events: Sender<ChatBotEvent>,
-    send_tasks: SyncSender<SendTask>,
+    mut receive_events: impl FnMut() -> Result<Vec<ReceiveEvent<Event>>, ConnectorError>
+        + Send
+        + 'static,
+    send_chat_bot_events: Sender<Event>,
+    send_tasks: SendQueue,
+    event_filter: EventFilter,
+    ping_tracker: Arc<Mutex<PingTracker>>,
+    idle_channels: Option<Arc<Mutex<IdleChannels>>>,
 ) -> ReceiveThread {
-    use ReceiveEvent::*;
     let handle = thread::spawn(move || 'outer: loop {
-        match receive(&mut receiver) {
+        match receive_events() {
             Ok(events) => {
                 for event in events {
-                    if let ChatBotEvent(event_content) = event {
-                        if let Err(error) = send_chat_bot_events.send(event_content) {
-                            println!("Reader thread stopped with error {:?}", error);
-                            break 'outer;
+                    // the chat modes are tracked even if the bot ignores them
+                    if let ReceiveEvent::ChatBotEvent(event) = &event {
+                        send_tasks.track_modes(&event.content);
+                        // chatting keeps the channel joined, joins and parts of viewers do not
+                        if let (
+                            Some(idle_channels),
+                            Some(channel),
+                            ChatBotEvent::TextMessage(_) | ChatBotEvent::Command(_),
+                        ) = (&idle_channels, &event.channel, &event.content)
+                        {
+                            idle_channels
+                                .lock()
+                                .unwrap()
+                                .record_activity(channel, Instant::now());
+                        }
+                    }
+                    match event {
+                        // events of ignored kinds never reach the bot
+                        ReceiveEvent::ChatBotEvent(event)
+                            if !event_filter.accepts(&event.content) => {}
+                        ReceiveEvent::ChatBotEvent(event) => {
+                            if let Err(error) = send_chat_bot_events.send(event) {
+                                println!("Reader thread stopped with error {:?}", error);
+                                break 'outer;
+                            }
                         }
-                    } else {
-                        if let Err(error) = send_tasks.send(SendTask::Pong) {
-                            println!("Reader thread stopped with error {:?}", error);
-                            break 'outer;
+                        ReceiveEvent::ConnectorEvent(ConnectorEvent::Ping) => {
+                            if let Err(error) = send_tasks.push(SendTask::Pong) {
+                                println!("Reader thread stopped with error {:?}", error);
+                                break 'outer;
+                            }
                         }
+                        ReceiveEvent::ConnectorEvent(ConnectorEvent::Pong(token)) => ping_tracker
+                            .lock()
+                            .unwrap()
+                            .complete(&token, Instant::now()),
+                        // capabilities are only requested when connecting
+                        ReceiveEvent::ConnectorEvent(ConnectorEvent::Capabilities { .. }) => {}
                     }
                 }
             }
@@ -97,28 +395,454 @@ fn receive_thread(
             }
         }
     });
-    ReceiveThread { _handle: handle }
+    ReceiveThread { handle }
+}
+
+/// Pings the chat server regularly to measure the latency. The bot is told when
+/// the server stops answering, so that it can reconnect. Returns the sender which stops the thread.
+fn probe_thread(
+    send_tasks: SendQueue,
+    ping_tracker: Arc<Mutex<PingTracker>>,
+    send_chat_bot_events: Sender<Event>,
+) -> Sender<()> {
+    let (stop, stopped) = mpsc::channel();
+    thread::spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(PROBE_INTERVAL) {
+            let token = Uuid::new_v4().to_string();
+            let pong = ping_tracker.lock().unwrap().start(&token, Instant::now());
+            if send_tasks.push(SendTask::Ping(token.clone())).is_err() {
+                break;
+            }
+            if pong.recv_timeout(PING_TIMEOUT).is_ok() {
+                continue;
+            }
+            let failed = ping_tracker.lock().unwrap().fail(&token);
+            println!("The chat server did not answer {} pings in a row", failed);
+            if failed >= MAX_FAILED_PROBES {
+                let _ = send_chat_bot_events.send(ChatBotEvent::Unresponsive.into());
+                break;
+            }
+        }
+    });
+    stop
+}
+
+/// Parts the channels nobody chatted in for a while and joins them again when their time has come.
+/// Returns the sender which stops the thread.
+fn idle_thread(send_tasks: SendQueue, idle_channels: Arc<Mutex<IdleChannels>>) -> Sender<()> {
+    let (stop, stopped) = mpsc::channel();
+    thread::spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(IDLE_CHECK_INTERVAL) {
+            let tasks = idle_channels.lock().unwrap().due(Instant::now());
+            for task in tasks {
+                println!("Channel activity: {}", task.to_string());
+                if send_tasks.push(task).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    stop
+}
+
+/// The tasks of the send thread, counting the tasks which are not sent yet.
+#[derive(Clone)]
+struct SendQueue {
+    tx: SyncSender<SendTask>,
+    queued: Arc<AtomicUsize>,
+    // the chat messages taken from the channel wait here until the chat modes allow them
+    mode_gate: Arc<Mutex<ModeGate>>,
+}
+
+impl SendQueue {
+    fn push(&self, task: SendTask) -> Result<(), ConnectorError> {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.tx.send(task).map_err(|err| {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            err.into()
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    fn track_modes(&self, event: &ChatBotEvent) {
+        self.mode_gate.lock().unwrap().update(event);
+    }
 }
 
 struct SendThread {
     _handle: JoinHandle<()>,
-    tx: SyncSender<SendTask>,
+    queue: SendQueue,
 }
 
 const SEND_CHAN_CAPACITY: usize = 10;
 
-fn send_thread(mut sender: Writer<TcpStream>) -> SendThread {
+/// Chat messages to the channel wait in the mode gate until the chat modes allow them. They and
+/// the other rate limited tasks (messages to other channels and raw lines) are sent as fast as
+/// the rate limiter allows, in the order they were queued; all other tasks (e.g. pongs) are sent
+/// right away.
+fn send_thread(
+    mut sender: Writer<ChatStream>,
+    mut duplicate_guard: DuplicateGuard,
+    channel: String,
+) -> SendThread {
     let (tx, rx) = mpsc::sync_channel(SEND_CHAN_CAPACITY);
-    let handle = thread::spawn(move || {
-        while let Ok(task) = rx.recv() {
-            if let Err(error) = send(&mut sender, task) {
-                println!("writer thread stopped with error {:?}", error);
-                break;
+    let queue = SendQueue {
+        tx,
+        queued: Arc::new(AtomicUsize::new(0)),
+        mode_gate: Arc::new(Mutex::new(ModeGate::default())),
+    };
+    let queued = queue.queued.clone();
+    let mode_gate = queue.mode_gate.clone();
+    let mut rate_limiter = RateLimiter::new(MESSAGE_BURST, MESSAGE_INTERVAL, Instant::now());
+    // rate limited tasks which do not wait for the chat modes
+    let mut limited: VecDeque<SendTask> = VecDeque::new();
+    let handle = thread::spawn(move || 'outer: loop {
+        let poll = loop {
+            let now = Instant::now();
+            if let Some(wait) = rate_limiter.wait(now) {
+                let waiting = !limited.is_empty() || !mode_gate.lock().unwrap().is_empty();
+                break if waiting {
+                    Poll::Wait(wait)
+                } else {
+                    Poll::Empty
+                };
+            }
+            let next = match limited.pop_front() {
+                Some(task) => Ok(task),
+                None => mode_gate
+                    .lock()
+                    .unwrap()
+                    .take(now)
+                    .map(|message| duplicate_guard.distinct(message)),
+            };
+            match next {
+                Ok(task) => {
+                    rate_limiter.take(now);
+                    let result = send(&mut sender, task);
+                    queued.fetch_sub(1, Ordering::SeqCst);
+                    if let Err(error) = result {
+                        println!("writer thread stopped with error {:?}", error);
+                        break 'outer;
+                    }
+                }
+                Err(poll) => break poll,
+            }
+        };
+        let task = match poll {
+            Poll::Empty => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Poll::Wait(wait) => rx.recv_timeout(wait),
+            Poll::Held => rx.recv_timeout(HELD_RECHECK),
+        };
+        match task {
+            Ok(message) if message.channel() == Some(&channel) => {
+                if let Some(dropped) = mode_gate.lock().unwrap().push(message) {
+                    println!("Dropping held back message {}", dropped.to_string());
+                    queued.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+            Ok(task) if task.is_rate_limited() => limited.push_back(task),
+            Ok(task) => {
+                let result = send(&mut sender, task);
+                queued.fetch_sub(1, Ordering::SeqCst);
+                if let Err(error) = result {
+                    println!("writer thread stopped with error {:?}", error);
+                    break;
+                }
             }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
         }
     });
     SendThread {
         _handle: handle,
-        tx,
+        queue,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::mock_server::MockServer, *};
+    use std::sync::mpsc::Receiver;
+
+    const CAPABILITIES: [&str; 2] = ["tags", "commands"];
+    const WAIT: Duration = Duration::from_secs(5);
+
+    fn chat_line(id: &str, text: &str) -> String {
+        format!(
+            "@badge-info=;badges=;color=;display-name=carkhy;emotes=;first-msg=0;flags=;id={};mod=0;room-id=120630112;subscriber=0;tmi-sent-ts=1637614002702;turbo=0;user-id=70346833;user-type= :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #captaincallback :{}",
+            id, text
+        )
+    }
+
+    fn seen_messages() -> Arc<Mutex<SeenMessages>> {
+        Arc::new(Mutex::new(SeenMessages::new(100, Duration::from_secs(600))))
+    }
+
+    // the next event which is not about the login, e.g. the welcome lines or the join
+    fn next_event(events: &Receiver<Event>) -> ChatBotEvent {
+        loop {
+            let event = events.recv_timeout(WAIT).expect("No event arrived").content;
+            if matches!(
+                event,
+                ChatBotEvent::Connected | ChatBotEvent::TextMessage(_)
+            ) {
+                return event;
+            }
+        }
+    }
+
+    fn next_text(events: &Receiver<Event>) -> String {
+        match next_event(events) {
+            ChatBotEvent::TextMessage(message) => message.text,
+            event => panic!("Expected a chat message, got {:?}", event),
+        }
+    }
+
+    // the receive thread notices the closed connection a moment later
+    fn wait_for_disconnect(connector: &TwitchChatConnector) {
+        let closed_at = Instant::now();
+        while connector.is_connected() {
+            assert!(
+                closed_at.elapsed() < WAIT,
+                "The closed connection was not noticed"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn connecting_and_chatting() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.expect("CAP REQ :twitch.tv/tags");
+            connection.expect("CAP REQ :twitch.tv/commands");
+            connection.expect("PASS oauth:s3cret-token");
+            connection.expect("NICK botanist");
+            connection.send_line(":tmi.twitch.tv CAP * ACK :twitch.tv/tags");
+            connection.send_line(":tmi.twitch.tv CAP * ACK :twitch.tv/commands");
+            connection.expect("JOIN #captaincallback");
+            connection.expect("PRIVMSG #captaincallback :Hello chat");
+            connection.send_line(&chat_line("1", "Hello bot"));
+            connection.expect("PRIVMSG #someone :Hello elsewhere");
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "s3cret-token", tx, seen_messages(), 0)
+                .unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        assert!(connector.is_connected());
+        assert_eq!(connector.channels(), ["captaincallback"]);
+        assert_eq!(connector.reconnect_count(), 0);
+        connector.send_message("Hello chat").unwrap();
+        assert_eq!(next_text(&events), "Hello bot");
+        connector
+            .send_message_to("Someone", "Hello elsewhere")
+            .unwrap();
+        script.join().unwrap();
+        connector.close().unwrap();
+        wait_for_disconnect(&connector);
+    }
+
+    #[test]
+    fn connecting_to_a_custom_server_url() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(&format!("{}/custom/chat", server.url()));
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            connection
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0).unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        let connection = script.join().unwrap();
+        assert_eq!(connection.path(), "/custom/chat");
+        connector.close().unwrap();
+    }
+
+    #[test]
+    fn neutralizing_injected_commands() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            connection.expect("PRIVMSG #captaincallback :echo  PART #captaincallback");
+            // the next line is the next message, not an injected PART
+            connection.expect("PRIVMSG #captaincallback :still here");
+            connection
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0).unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        connector
+            .send_message("echo\r\nPART #captaincallback")
+            .unwrap();
+        connector.send_message("still here").unwrap();
+        script.join().unwrap();
+        connector.close().unwrap();
+    }
+
+    #[test]
+    fn sending_raw_lines() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            // the whole lines are compared below
+            let lines = [
+                connection.expect(""),
+                connection.expect(""),
+                connection.expect(""),
+            ];
+            (connection, lines)
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0).unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        connector
+            .send_raw("CAP REQ :twitch.tv/membership\r\nQUIT")
+            .unwrap();
+        connector
+            .send_raw("@reply-parent-msg-id=42 PRIVMSG #captaincallback :raw reply")
+            .unwrap();
+        connector.send_raw("PRIVMSG #someone :raw hello").unwrap();
+        let (_connection, lines) = script.join().unwrap();
+        assert_eq!(
+            lines,
+            [
+                "CAP REQ :twitch.tv/membership  QUIT",
+                "@reply-parent-msg-id=42 PRIVMSG #captaincallback :raw reply",
+                "PRIVMSG #someone :raw hello",
+            ]
+        );
+        connector.close().unwrap();
+    }
+
+    #[test]
+    fn spacing_out_raw_lines_after_a_burst() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            let started_at = Instant::now();
+            for _ in 0..MESSAGE_BURST {
+                connection.expect("PRIVMSG #someone");
+            }
+            let burst = started_at.elapsed();
+            connection.expect("PRIVMSG #someone :one too many");
+            (connection, burst, started_at.elapsed())
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0).unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        for _ in 0..MESSAGE_BURST {
+            connector.send_raw("PRIVMSG #someone :hello").unwrap();
+        }
+        connector
+            .send_raw("PRIVMSG #someone :one too many")
+            .unwrap();
+        let (_connection, burst, all) = script.join().unwrap();
+        assert!(burst < MESSAGE_INTERVAL / 2, "burst took {:?}", burst);
+        assert!(all >= MESSAGE_INTERVAL / 2, "all lines took {:?}", all);
+        connector.close().unwrap();
+    }
+
+    #[test]
+    fn rejecting_required_capabilities() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.expect("CAP REQ :twitch.tv/tags");
+            connection.expect("CAP REQ :twitch.tv/commands");
+            connection.send_line(":tmi.twitch.tv CAP * NAK :twitch.tv/tags");
+            connection
+        });
+        let (tx, _events) = mpsc::channel();
+        let result = TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0);
+        assert!(matches!(result, Err(ConnectorError::CapabilityRejected(name)) if name == "tags"));
+        script.join().unwrap();
+    }
+
+    #[test]
+    fn answering_and_sending_pings() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            connection.send_line("PING :tmi.twitch.tv");
+            connection.expect("PONG :tmi.twitch.tv");
+            // the bot measures the latency only after its pong, so that the lines do not cross
+            connection.send_line(&chat_line("1", "pong received"));
+            let ping = connection.expect("PING :");
+            let token = ping.trim_start_matches("PING :");
+            connection.send_line(&format!(":tmi.twitch.tv PONG tmi.twitch.tv :{}", token));
+            connection
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0).unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        assert_eq!(next_text(&events), "pong received");
+        assert_eq!(connector.latency(), None);
+        connector.measure_latency().unwrap();
+        let latency = loop {
+            let event = events
+                .recv_timeout(WAIT)
+                .expect("No latency arrived")
+                .content;
+            if let ChatBotEvent::Latency(latency) = event {
+                break latency.expect("No pong arrived");
+            }
+        };
+        assert_eq!(connector.last_latency(), Some(latency));
+        script.join().unwrap();
+        connector.close().unwrap();
+    }
+
+    #[test]
+    fn reconnecting_after_the_server_closed_the_connection() {
+        let mut server = MockServer::start();
+        let app_config = AppConfig::for_server(server.url());
+        let script = thread::spawn(move || {
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            connection.send_line(&chat_line("1", "before the restart"));
+            connection.close();
+            let mut connection = server.accept();
+            connection.login(&CAPABILITIES, "botanist", "captaincallback");
+            // the message is delivered again after the reconnect
+            connection.send_line(&chat_line("1", "before the restart"));
+            connection.send_line(&chat_line("2", "after the restart"));
+            connection
+        });
+        let (tx, events) = mpsc::channel();
+        let connector =
+            TwitchChatConnector::connect(&app_config, "token", tx, seen_messages(), 0).unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        assert_eq!(next_text(&events), "before the restart");
+        wait_for_disconnect(&connector);
+
+        let connector = connector.reconnect_with("token").unwrap();
+        assert_eq!(next_event(&events), ChatBotEvent::Connected);
+        assert!(connector.is_connected());
+        assert_eq!(connector.reconnect_count(), 1);
+        assert_eq!(next_text(&events), "after the restart");
+        assert_eq!(connector.suppressed_duplicates(), 1);
+        script.join().unwrap();
+        connector.close().unwrap();
     }
 }
@@ -0,0 +1,73 @@
+use super::send::SendTask;
+
+// twitch drops a message identical to the previous one, an invisible suffix makes it distinct
+// (a trailing space alone is trimmed by twitch, the braille blank is not)
+const DUPLICATE_SUFFIX: &str = " \u{2800}";
+
+/// Remembers the last chat message sent, to make an identical next message distinct.
+#[derive(Debug, Default)]
+pub struct DuplicateGuard {
+    enabled: bool,
+    last_message: Option<String>,
+}
+
+impl DuplicateGuard {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            last_message: None,
+        }
+    }
+
+    /// Appends the suffix to a chat message identical to the last one sent, other tasks are unchanged.
+    pub fn distinct(&mut self, task: SendTask) -> SendTask {
+        match task {
+            SendTask::PrivateMessage(channel, message) if self.enabled => {
+                let message = if self.last_message.as_ref() == Some(&message) {
+                    format!("{}{}", message, DUPLICATE_SUFFIX)
+                } else {
+                    message
+                };
+                self.last_message = Some(message.to_owned());
+                SendTask::PrivateMessage(channel, message)
+            }
+            task => task,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> SendTask {
+        SendTask::PrivateMessage("channel123".to_owned(), text.to_owned())
+    }
+
+    #[test]
+    fn making_identical_messages_distinct() {
+        let mut guard = DuplicateGuard::new(true);
+        let first = guard.distinct(message("deaths: 1")).to_string();
+        let second = guard.distinct(message("deaths: 1")).to_string();
+        assert_eq!(first, "PRIVMSG #channel123 :deaths: 1");
+        assert_ne!(first, second);
+        assert_eq!(second.trim_end_matches(DUPLICATE_SUFFIX), first);
+        // the third one differs from the second one which was sent with the suffix
+        assert_eq!(guard.distinct(message("deaths: 1")).to_string(), first);
+        assert_eq!(
+            guard.distinct(message("deaths: 2")).to_string(),
+            "PRIVMSG #channel123 :deaths: 2"
+        );
+    }
+
+    #[test]
+    fn keeping_identical_messages_when_disabled() {
+        let mut guard = DuplicateGuard::new(false);
+        let first = guard.distinct(message("deaths: 1")).to_string();
+        assert_eq!(guard.distinct(message("deaths: 1")).to_string(), first);
+        assert_eq!(
+            guard.distinct(SendTask::Pong).to_string(),
+            "PONG :tmi.twitch.tv"
+        );
+    }
+}
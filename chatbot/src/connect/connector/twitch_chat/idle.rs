@@ -0,0 +1,129 @@
+use super::send::SendTask;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Parts the channels without chat messages for a while, to save the resources of channels
+/// nobody chats in, and joins them again after a while if configured.
+#[derive(Debug)]
+pub struct IdleChannels {
+    timeout: Duration,
+    rejoin_after: Option<Duration>,
+    // the last chat message in each joined channel, by channel name without `#`
+    last_activity: HashMap<String, Instant>,
+    // when each idle channel was parted
+    parted: HashMap<String, Instant>,
+}
+
+impl IdleChannels {
+    /// Starts watching the channels, which were just joined.
+    pub fn new(
+        channels: &[String],
+        timeout: Duration,
+        rejoin_after: Option<Duration>,
+        now: Instant,
+    ) -> Self {
+        Self {
+            timeout,
+            rejoin_after,
+            last_activity: channels
+                .iter()
+                .map(|channel| (channel.to_owned(), now))
+                .collect(),
+            parted: HashMap::new(),
+        }
+    }
+
+    /// Notes a chat message in the channel, messages of channels the bot did not join are ignored.
+    pub fn record_activity(&mut self, channel: &str, now: Instant) {
+        if let Some(last_activity) = self.last_activity.get_mut(channel) {
+            *last_activity = now;
+        }
+    }
+
+    /// Parts the channels which were idle for too long and joins the parted ones again
+    /// whose time has come. Returns the tasks to send, ordered by channel.
+    pub fn due(&mut self, now: Instant) -> Vec<SendTask> {
+        let mut idle: Vec<String> = self
+            .last_activity
+            .iter()
+            .filter(|(_, last_activity)| {
+                now.saturating_duration_since(**last_activity) >= self.timeout
+            })
+            .map(|(channel, _)| channel.to_owned())
+            .collect();
+        idle.sort();
+        let mut rejoined: Vec<String> = match self.rejoin_after {
+            Some(rejoin_after) => self
+                .parted
+                .iter()
+                .filter(|(_, parted_at)| now.saturating_duration_since(**parted_at) >= rejoin_after)
+                .map(|(channel, _)| channel.to_owned())
+                .collect(),
+            None => Vec::new(),
+        };
+        rejoined.sort();
+        let mut tasks = Vec::new();
+        for channel in idle {
+            self.last_activity.remove(&channel);
+            self.parted.insert(channel.to_owned(), now);
+            tasks.push(SendTask::PartChannel(channel));
+        }
+        for channel in rejoined {
+            self.parted.remove(&channel);
+            self.last_activity.insert(channel.to_owned(), now);
+            tasks.push(SendTask::JoinChannel(channel));
+        }
+        tasks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(tasks: Vec<SendTask>) -> Vec<String> {
+        tasks.iter().map(SendTask::to_string).collect()
+    }
+
+    #[test]
+    fn parting_idle_channels() {
+        let start = Instant::now();
+        let minutes = |minutes: u64| start + Duration::from_secs(minutes * 60);
+        let mut channels = IdleChannels::new(
+            &["quiet".to_owned(), "busy".to_owned()],
+            Duration::from_secs(30 * 60),
+            None,
+            start,
+        );
+        channels.record_activity("busy", minutes(20));
+        channels.record_activity("elsewhere", minutes(20));
+        assert!(channels.due(minutes(29)).is_empty());
+        assert_eq!(tasks(channels.due(minutes(30))), vec!["PART #quiet"]);
+        // parted once, no more activity is noted
+        channels.record_activity("quiet", minutes(31));
+        assert!(channels.due(minutes(49)).is_empty());
+        assert_eq!(tasks(channels.due(minutes(50))), vec!["PART #busy"]);
+        assert!(channels.due(minutes(600)).is_empty());
+    }
+
+    #[test]
+    fn rejoining_parted_channels() {
+        let start = Instant::now();
+        let minutes = |minutes: u64| start + Duration::from_secs(minutes * 60);
+        let mut channels = IdleChannels::new(
+            &["quiet".to_owned()],
+            Duration::from_secs(30 * 60),
+            Some(Duration::from_secs(60 * 60)),
+            start,
+        );
+        assert_eq!(tasks(channels.due(minutes(30))), vec!["PART #quiet"]);
+        assert!(channels.due(minutes(89)).is_empty());
+        assert_eq!(tasks(channels.due(minutes(90))), vec!["JOIN #quiet"]);
+        // the channel gets the whole time again
+        channels.record_activity("quiet", minutes(100));
+        assert!(channels.due(minutes(129)).is_empty());
+        assert_eq!(tasks(channels.due(minutes(130))), vec!["PART #quiet"]);
+    }
+}
@@ -0,0 +1,89 @@
+use super::send::SendTask;
+use std::time::Duration;
+
+// twitch accepts about 20 joins per 10 seconds, up to this many channels are joined at once
+const JOIN_BURST: usize = 20;
+
+/// Joins the channels, waiting the delay between the joins if there are more of them than
+/// twitch accepts at once, so that the bot is not rate limited while logging in.
+/// Sending and waiting are passed in, so that the pacing can be tested without a connection.
+pub fn join_channels<E>(
+    channels: &[String],
+    delay: Duration,
+    mut send: impl FnMut(SendTask) -> Result<(), E>,
+    mut sleep: impl FnMut(Duration),
+) -> Result<(), E> {
+    let stagger = channels.len() > JOIN_BURST;
+    for (index, channel) in channels.iter().enumerate() {
+        if stagger && index > 0 {
+            sleep(delay);
+        }
+        send(SendTask::JoinChannel(channel.to_owned()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // the joins sent and the time they were sent at, on a clock which only the pauses advance
+    fn play(channel_count: usize, delay: Duration) -> Vec<(String, Duration)> {
+        let channels: Vec<String> = (0..channel_count)
+            .map(|index| format!("channel{}", index))
+            .collect();
+        let clock = Cell::new(Duration::ZERO);
+        let mut sent = Vec::new();
+        join_channels::<()>(
+            &channels,
+            delay,
+            |task| {
+                sent.push((task.to_string(), clock.get()));
+                Ok(())
+            },
+            |pause| clock.set(clock.get() + pause),
+        )
+        .unwrap();
+        sent
+    }
+
+    #[test]
+    fn joining_few_channels_at_once() {
+        let sent = play(3, Duration::from_millis(500));
+        assert_eq!(
+            sent,
+            vec![
+                ("JOIN #channel0".to_owned(), Duration::ZERO),
+                ("JOIN #channel1".to_owned(), Duration::ZERO),
+                ("JOIN #channel2".to_owned(), Duration::ZERO),
+            ]
+        );
+    }
+
+    #[test]
+    fn staggering_many_channels() {
+        let sent = play(25, Duration::from_millis(500));
+        assert_eq!(sent.len(), 25);
+        for (index, (join, sent_at)) in sent.iter().enumerate() {
+            assert_eq!(join, &format!("JOIN #channel{}", index));
+            assert_eq!(*sent_at, Duration::from_millis(500) * index as u32);
+        }
+    }
+
+    #[test]
+    fn stopping_at_the_first_failed_join() {
+        let mut sent = 0;
+        let result = join_channels(
+            &["first".to_owned(), "second".to_owned()],
+            Duration::ZERO,
+            |_| {
+                sent += 1;
+                Err("closed")
+            },
+            |_| {},
+        );
+        assert_eq!(result, Err("closed"));
+        assert_eq!(sent, 1);
+    }
+}
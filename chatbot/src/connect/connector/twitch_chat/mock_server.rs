@@ -0,0 +1,121 @@
+use std::{collections::VecDeque, net::TcpStream, time::Duration};
+use websocket::{
+    server::NoTlsAcceptor,
+    sync::{Client, Server},
+    Message, OwnedMessage,
+};
+
+// a line the connector does not send within this time fails the test instead of blocking it
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A chat server on a local port which speaks enough Twitch IRC for tests of the connector.
+/// Each connection is driven by a script of lines which the connector has to send (`expect`)
+/// and lines the server sends (`send_line`), so that new scenarios are a few lines each.
+pub struct MockServer {
+    server: Server<NoTlsAcceptor>,
+    url: String,
+}
+
+impl MockServer {
+    pub fn start() -> Self {
+        let server = Server::bind("127.0.0.1:0").expect("Could not bind the mock chat server");
+        let url = format!("ws://{}", server.local_addr().unwrap());
+        Self { server, url }
+    }
+
+    /// The url of the server for `TWITCH_SERVER_URL`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Waits for the next connection of a connector, e.g. after a reconnect.
+    pub fn accept(&mut self) -> MockConnection {
+        let upgrade = self
+            .server
+            .accept()
+            .map_err(|err| err.error)
+            .expect("Could not accept the connection");
+        let path = upgrade.uri();
+        let client = upgrade
+            .accept()
+            .map_err(|(_, err)| err)
+            .expect("Could not upgrade the connection");
+        client
+            .stream_ref()
+            .set_read_timeout(Some(EXPECT_TIMEOUT))
+            .unwrap();
+        MockConnection {
+            client,
+            path,
+            received: VecDeque::new(),
+        }
+    }
+}
+
+pub struct MockConnection {
+    client: Client<TcpStream>,
+    // the path of the url the connector asked for, e.g. `/`
+    path: String,
+    // the lines of a websocket message which were not expected yet
+    received: VecDeque<String>,
+}
+
+impl MockConnection {
+    /// The path of the url the connector connected to.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Waits for the next line of the connector, which has to start with the given text.
+    /// Returns the whole line, e.g. to answer a ping with its token.
+    pub fn expect(&mut self, start: &str) -> String {
+        while self.received.is_empty() {
+            match self.client.recv_message() {
+                Ok(OwnedMessage::Text(text)) => {
+                    self.received.extend(text.lines().map(str::to_owned))
+                }
+                Ok(OwnedMessage::Close(_)) => {
+                    panic!("Expected {}, but the connection closed", start)
+                }
+                Ok(_) => {}
+                Err(err) => panic!("Expected {}, but nothing arrived: {:?}", start, err),
+            }
+        }
+        let line = self.received.pop_front().unwrap();
+        assert!(line.starts_with(start), "Expected {}, got {}", start, line);
+        line
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.client
+            .send_message(&Message::text(format!("{}\r\n", line)))
+            .expect("Could not send the line");
+    }
+
+    /// Answers the capability requests, the login and the join of the channel like Twitch does.
+    pub fn login(&mut self, capabilities: &[&str], user: &str, channel: &str) {
+        for capability in capabilities {
+            self.expect(&format!("CAP REQ :twitch.tv/{}", capability));
+        }
+        self.expect("PASS oauth:");
+        self.expect(&format!("NICK {}", user));
+        for capability in capabilities {
+            self.send_line(&format!(
+                ":tmi.twitch.tv CAP * ACK :twitch.tv/{}",
+                capability
+            ));
+        }
+        self.send_line(&format!(":tmi.twitch.tv 001 {} :Welcome, GLHF!", user));
+        self.expect(&format!("JOIN #{}", channel));
+        self.send_line(&format!(
+            ":{user}!{user}@{user}.tmi.twitch.tv JOIN #{channel}",
+            user = user,
+            channel = channel
+        ));
+    }
+
+    /// Closes the connection like the chat server does, e.g. before a restart.
+    pub fn close(self) {
+        let _ = self.client.shutdown();
+    }
+}
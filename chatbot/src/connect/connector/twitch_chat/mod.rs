@@ -1,7 +1,25 @@
 mod auth;
+mod capabilities;
 mod connector;
-mod receive;
+mod duplicate;
+mod idle;
+mod join;
+#[cfg(test)]
+mod mock_server;
+mod mode_gate;
+mod ping;
+mod quarantine;
+mod rate_limit;
+pub(crate) mod receive;
 mod retry_manager;
+mod seen;
 pub(crate) mod send;
+mod stream;
 
+pub(crate) use auth::{
+    poll_device_tokens, request_device_code, restrict_permissions, store_authorization,
+    AccessTokenDispenser, ID_URL,
+};
 pub use connector::TwitchChatConnector;
+pub(crate) use receive::ReceiveEvent;
+pub use seen::SeenMessages;
@@ -0,0 +1,176 @@
+use super::send::SendTask;
+use crate::connect::{Badge, ChatBotEvent, RoomState};
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+// chat messages held back longer (e.g. during a long emote-only mode) are dropped, oldest first
+const MAX_WAITING: usize = 20;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Poll {
+    // no chat message is waiting
+    Empty,
+    // the next chat message may be sent after this time
+    Wait(Duration),
+    // the chat messages are held back until the chat modes change
+    Held,
+}
+
+/// Holds back the chat messages of the bot which the chat server would reject in the current
+/// chat modes: all of them in emote-only mode, and those sent too fast in slow mode.
+/// Moderators are not restricted by either mode.
+#[derive(Default)]
+pub struct ModeGate {
+    room: RoomState,
+    moderator: bool,
+    last_sent: Option<Instant>,
+    waiting: VecDeque<SendTask>,
+}
+
+impl ModeGate {
+    /// Tracks the chat modes (ROOMSTATE) and whether the bot is a moderator (USERSTATE).
+    pub fn update(&mut self, event: &ChatBotEvent) {
+        match event {
+            ChatBotEvent::RoomState(change) => self.room.update(*change),
+            ChatBotEvent::UserState { badges, .. } => self.moderator = is_moderator(badges),
+            _ => {}
+        }
+    }
+
+    /// Queues a chat message behind the ones already waiting.
+    /// Returns the oldest message if it had to be dropped to make room.
+    pub fn push(&mut self, message: SendTask) -> Option<SendTask> {
+        let dropped = if self.waiting.len() == MAX_WAITING {
+            self.waiting.pop_front()
+        } else {
+            None
+        };
+        self.waiting.push_back(message);
+        dropped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+
+    /// Takes the next chat message if it may be sent now.
+    pub fn take(&mut self, now: Instant) -> Result<SendTask, Poll> {
+        if self.waiting.is_empty() {
+            return Err(Poll::Empty);
+        }
+        if !self.moderator {
+            if self.room.emote_only == Some(true) {
+                return Err(Poll::Held);
+            }
+            let wait = self.slow_wait(now);
+            if !wait.is_zero() {
+                return Err(Poll::Wait(wait));
+            }
+        }
+        self.last_sent = Some(now);
+        self.waiting.pop_front().ok_or(Poll::Empty)
+    }
+
+    fn slow_wait(&self, now: Instant) -> Duration {
+        let interval = Duration::from_secs(self.room.slow.unwrap_or_default().into());
+        self.last_sent
+            .map(|last_sent| interval.saturating_sub(now - last_sent))
+            .unwrap_or_default()
+    }
+}
+
+fn is_moderator(badges: &HashSet<Badge>) -> bool {
+    badges
+        .iter()
+        .any(|badge| badge.name == "broadcaster" || badge.name == "moderator")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> SendTask {
+        SendTask::PrivateMessage("channel123".to_owned(), text.to_owned())
+    }
+
+    fn room(emote_only: bool, slow: u32) -> ChatBotEvent {
+        ChatBotEvent::RoomState(RoomState {
+            emote_only: Some(emote_only),
+            slow: Some(slow),
+            ..RoomState::default()
+        })
+    }
+
+    fn sent(result: Result<SendTask, Poll>) -> String {
+        match result {
+            Ok(task) => task.to_string(),
+            Err(poll) => panic!("nothing sent: {:?}", poll),
+        }
+    }
+
+    #[test]
+    fn holding_back_messages_in_emote_only_mode() {
+        let now = Instant::now();
+        let mut gate = ModeGate::default();
+        gate.update(&room(true, 0));
+        gate.push(message("deaths: 1"));
+        assert_eq!(gate.take(now).err(), Some(Poll::Held));
+        assert_eq!(gate.waiting.len(), 1);
+        gate.update(&room(false, 0));
+        assert_eq!(sent(gate.take(now)), "PRIVMSG #channel123 :deaths: 1");
+        assert_eq!(gate.take(now).err(), Some(Poll::Empty));
+    }
+
+    #[test]
+    fn spacing_out_messages_in_slow_mode() {
+        let now = Instant::now();
+        let mut gate = ModeGate::default();
+        gate.update(&room(false, 30));
+        gate.push(message("first"));
+        gate.push(message("second"));
+        assert_eq!(sent(gate.take(now)), "PRIVMSG #channel123 :first");
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            gate.take(later).err(),
+            Some(Poll::Wait(Duration::from_secs(20)))
+        );
+        assert_eq!(
+            sent(gate.take(now + Duration::from_secs(30))),
+            "PRIVMSG #channel123 :second"
+        );
+    }
+
+    #[test]
+    fn sending_freely_as_moderator() {
+        let now = Instant::now();
+        let mut gate = ModeGate::default();
+        gate.update(&room(true, 30));
+        gate.update(&ChatBotEvent::UserState {
+            badges: HashSet::from([Badge {
+                name: "moderator".to_owned(),
+                level: 1,
+            }]),
+            display_name: None,
+        });
+        gate.push(message("first"));
+        gate.push(message("second"));
+        assert!(gate.take(now).is_ok());
+        assert!(gate.take(now).is_ok());
+    }
+
+    #[test]
+    fn dropping_the_oldest_held_back_messages() {
+        let mut gate = ModeGate::default();
+        gate.update(&room(true, 0));
+        for count in 0..MAX_WAITING {
+            assert!(gate.push(message(&count.to_string())).is_none());
+        }
+        let dropped = gate.push(message("last")).map(|task| task.to_string());
+        assert_eq!(dropped.as_deref(), Some("PRIVMSG #channel123 :0"));
+        assert_eq!(gate.waiting.len(), MAX_WAITING);
+        gate.update(&room(false, 0));
+        assert_eq!(sent(gate.take(Instant::now())), "PRIVMSG #channel123 :1");
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+// round trip times of the rolling average
+const LATENCY_SAMPLES: usize = 10;
+
+/// Keeps track of the pings sent to the chat server,
+/// so that the round trip time can be measured when the matching pong arrives.
+/// The round trip times of the last answered pings are kept for a rolling average.
+#[derive(Debug, Default)]
+pub struct PingTracker {
+    outstanding: HashMap<String, (Instant, Sender<Duration>)>,
+    // newest last
+    samples: VecDeque<Duration>,
+    // pings without answer since the last answered one
+    failed: u32,
+}
+
+impl PingTracker {
+    /// Registers a ping with the given token. The round trip time is sent
+    /// to the returned receiver when the pong with the same token arrives.
+    pub fn start(&mut self, token: &str, now: Instant) -> Receiver<Duration> {
+        let (tx, rx) = mpsc::channel();
+        self.outstanding.insert(token.to_owned(), (now, tx));
+        rx
+    }
+
+    /// Completes the ping with the given token. Pongs with unknown tokens are ignored.
+    pub fn complete(&mut self, token: &str, now: Instant) {
+        if let Some((sent_at, tx)) = self.outstanding.remove(token) {
+            let round_trip = now - sent_at;
+            if self.samples.len() == LATENCY_SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(round_trip);
+            self.failed = 0;
+            // the receiver is gone if the measurement timed out already
+            let _ = tx.send(round_trip);
+        }
+    }
+
+    /// Forgets a ping which will not be answered anymore.
+    pub fn cancel(&mut self, token: &str) {
+        self.outstanding.remove(token);
+    }
+
+    /// Forgets a ping which was not answered in time.
+    /// Returns the number of pings without answer since the last answered one.
+    pub fn fail(&mut self, token: &str) -> u32 {
+        self.cancel(token);
+        self.failed += 1;
+        self.failed
+    }
+
+    /// The round trip time of the last answered ping.
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    /// The average round trip time of the last answered pings.
+    pub fn average(&self) -> Option<Duration> {
+        let total: Duration = self.samples.iter().sum();
+        (!self.samples.is_empty()).then(|| total / self.samples.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::connector::twitch_chat::receive::{ConnectorEvent, ReceiveEvent};
+
+    #[test]
+    fn correlating_pongs() {
+        let now = Instant::now();
+        let mut tracker = PingTracker::default();
+        let first = tracker.start("first", now);
+        let second = tracker.start("second", now);
+        tracker.complete("second", now);
+        assert!(second.try_recv().is_ok());
+        assert!(first.try_recv().is_err());
+        tracker.complete("first", now);
+        assert!(first.try_recv().is_ok());
+        assert!(tracker.outstanding.is_empty());
+    }
+
+    #[test]
+    fn ignoring_unknown_pongs() {
+        let now = Instant::now();
+        let mut tracker = PingTracker::default();
+        let ping = tracker.start("token", now);
+        tracker.complete("tmi.twitch.tv", now);
+        assert!(ping.try_recv().is_err());
+        tracker.cancel("token");
+        tracker.complete("token", now);
+        assert!(ping.try_recv().is_err());
+    }
+
+    // plays the lines of the chat server, each arriving the given milliseconds after the pings were sent,
+    // and returns the number of pings of the server
+    fn play(tracker: &mut PingTracker, sent_at: Instant, script: &[(u64, &str)]) -> usize {
+        let mut server_pings = 0;
+        for (millis, line) in script {
+            match ReceiveEvent::parse_from_message(line) {
+                Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Pong(token))) => {
+                    tracker.complete(&token, sent_at + Duration::from_millis(*millis))
+                }
+                Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Ping)) => server_pings += 1,
+                _ => (),
+            }
+        }
+        server_pings
+    }
+
+    #[test]
+    fn measuring_delayed_pongs() {
+        let now = Instant::now();
+        let mut tracker = PingTracker::default();
+        let first = tracker.start("first", now);
+        let second = tracker.start("second", now);
+        let server_pings = play(
+            &mut tracker,
+            now,
+            &[
+                (10, "PING :tmi.twitch.tv"),
+                (20, ":tmi.twitch.tv PONG tmi.twitch.tv :tmi.twitch.tv"),
+                (40, ":tmi.twitch.tv PONG tmi.twitch.tv :second"),
+                (45, "PING:tmi.twitch.tv"),
+                (60, ":tmi.twitch.tv PONG tmi.twitch.tv :first"),
+            ],
+        );
+        assert_eq!(server_pings, 2);
+        assert_eq!(first.try_recv(), Ok(Duration::from_millis(60)));
+        assert_eq!(second.try_recv(), Ok(Duration::from_millis(40)));
+        assert_eq!(tracker.last(), Some(Duration::from_millis(60)));
+        assert_eq!(tracker.average(), Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn averaging_the_last_pongs() {
+        let now = Instant::now();
+        let mut tracker = PingTracker::default();
+        assert_eq!(tracker.average(), None);
+        for millis in [1000, 10, 10, 10, 10, 10, 10, 10, 10, 10, 20] {
+            tracker.start("token", now);
+            tracker.complete("token", now + Duration::from_millis(millis));
+        }
+        // the first round trip is not part of the average anymore
+        assert_eq!(tracker.average(), Some(Duration::from_millis(11)));
+        assert_eq!(tracker.last(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn counting_failed_pings() {
+        let now = Instant::now();
+        let mut tracker = PingTracker::default();
+        tracker.start("first", now);
+        assert_eq!(tracker.fail("first"), 1);
+        tracker.start("second", now);
+        assert_eq!(tracker.fail("second"), 2);
+        // a pong which arrives too late does not count
+        play(
+            &mut tracker,
+            now,
+            &[(6000, ":tmi.twitch.tv PONG tmi.twitch.tv :second")],
+        );
+        tracker.start("third", now);
+        assert_eq!(tracker.fail("third"), 3);
+        tracker.start("fourth", now);
+        play(
+            &mut tracker,
+            now,
+            &[(30, ":tmi.twitch.tv PONG tmi.twitch.tv :fourth")],
+        );
+        tracker.start("fifth", now);
+        assert_eq!(tracker.fail("fifth"), 1);
+    }
+}
@@ -0,0 +1,172 @@
+use super::receive::sent_at;
+use chrono::{DateTime, Utc};
+use std::{collections::VecDeque, fs::OpenOptions, io::Write};
+
+const QUARANTINE_CAPACITY: usize = 100;
+// replies to the login which the bot does not need to handle
+const DEFAULT_IGNORED_PREFIXES: [&str; 7] = [
+    ":tmi.twitch.tv 001",
+    ":tmi.twitch.tv 002",
+    ":tmi.twitch.tv 003",
+    ":tmi.twitch.tv 004",
+    ":tmi.twitch.tv 372",
+    ":tmi.twitch.tv 375",
+    ":tmi.twitch.tv 376",
+];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct QuarantineEntry {
+    pub sent_at: DateTime<Utc>,
+    pub line: String,
+}
+
+/// Keeps the last lines received from the chat server which could not be parsed,
+/// so that unsupported message types can be discovered.
+#[derive(Debug)]
+pub struct Quarantine {
+    entries: VecDeque<QuarantineEntry>,
+    capacity: usize,
+    total: u64,
+    ignored_prefixes: Vec<String>,
+    log_file: Option<String>,
+}
+
+impl Quarantine {
+    /// Creates a quarantine which ignores the default and the given prefixes
+    /// and optionally appends every quarantined line to `log_file`.
+    pub fn new(ignored_prefixes: &[String], log_file: Option<String>) -> Self {
+        let ignored_prefixes = DEFAULT_IGNORED_PREFIXES
+            .iter()
+            .map(|prefix| prefix.to_string())
+            .chain(ignored_prefixes.iter().cloned())
+            .collect();
+        Self::with_capacity(QUARANTINE_CAPACITY, ignored_prefixes, log_file)
+    }
+
+    fn with_capacity(
+        capacity: usize,
+        ignored_prefixes: Vec<String>,
+        log_file: Option<String>,
+    ) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            total: 0,
+            ignored_prefixes,
+            log_file,
+        }
+    }
+
+    fn is_ignored(&self, line: &str) -> bool {
+        // the prefixes are compared without the leading tags of the line
+        let line = match line.strip_prefix('@') {
+            Some(tagged_line) => tagged_line
+                .split_once(' ')
+                .map(|(_, line)| line)
+                .unwrap_or_default(),
+            None => line,
+        };
+        line.trim().is_empty()
+            || line.starts_with("PING")
+            || self
+                .ignored_prefixes
+                .iter()
+                .any(|prefix| line.starts_with(prefix.as_str()))
+    }
+
+    pub fn add(&mut self, line: &str) {
+        if self.is_ignored(line) {
+            return;
+        }
+        let entry = QuarantineEntry {
+            sent_at: sent_at(line).unwrap_or_else(Utc::now),
+            line: line.to_owned(),
+        };
+        if let Some(log_file) = &self.log_file {
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)
+                .and_then(|mut file| {
+                    writeln!(file, "{} {}", entry.sent_at.to_rfc3339(), entry.line)
+                });
+            if let Err(error) = written {
+                println!("Could not write unparsed line to {}: {:?}", log_file, error);
+            }
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.total += 1;
+    }
+
+    /// Returns the last `count` entries, oldest first.
+    pub fn last(&self, count: usize) -> Vec<QuarantineEntry> {
+        self.entries
+            .iter()
+            .skip(self.entries.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of all quarantined lines, including the ones which were dropped from the buffer.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capping_quarantined_lines() {
+        let mut quarantine = Quarantine::with_capacity(3, Vec::default(), None);
+        for i in 0..5 {
+            quarantine.add(&format!(":tmi.twitch.tv UNKNOWN {}", i));
+        }
+        let lines: Vec<String> = quarantine
+            .last(10)
+            .into_iter()
+            .map(|entry| entry.line)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ":tmi.twitch.tv UNKNOWN 2",
+                ":tmi.twitch.tv UNKNOWN 3",
+                ":tmi.twitch.tv UNKNOWN 4"
+            ]
+        );
+        assert_eq!(quarantine.last(1)[0].line, ":tmi.twitch.tv UNKNOWN 4");
+        assert_eq!(quarantine.total(), 5);
+    }
+
+    #[test]
+    fn timestamping_tagged_lines_when_they_were_sent() {
+        let mut quarantine = Quarantine::with_capacity(3, Vec::default(), None);
+        quarantine.add("@msg-id=raid;tmi-sent-ts=1637614002702 :tmi.twitch.tv USERNOTICE #channel");
+        assert_eq!(
+            quarantine.last(1)[0].sent_at.timestamp_millis(),
+            1637614002702
+        );
+    }
+
+    #[test]
+    fn excluding_ping_lines() {
+        let mut quarantine = Quarantine::with_capacity(3, Vec::default(), None);
+        quarantine.add("PING :tmi.twitch.tv");
+        assert_eq!(quarantine.total(), 0);
+    }
+
+    #[test]
+    fn excluding_ignored_prefixes() {
+        let mut quarantine = Quarantine::new(&[":tmi.twitch.tv USERSTATE".to_owned()], None);
+        quarantine.add(":tmi.twitch.tv 001 botanist :Welcome, GLHF!");
+        quarantine.add(":tmi.twitch.tv USERSTATE #channel");
+        quarantine.add("@msg-id=subs_on :tmi.twitch.tv 001 botanist :tagged");
+        quarantine.add("@msg-id=subs_on :tmi.twitch.tv NOTICE #channel :subscribers only");
+        assert_eq!(quarantine.total(), 1);
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+// twitch allows 20 chat messages (and other commands) in 30 seconds to users who are no moderators
+pub const MESSAGE_BURST: u32 = 20;
+pub const MESSAGE_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// A token bucket for the messages of the bot: up to `burst` messages at once, after that one
+/// per `interval`, so that the chat server does not drop messages or the connection.
+#[derive(Debug)]
+pub struct RateLimiter {
+    burst: u32,
+    interval: Duration,
+    tokens: u32,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, interval: Duration, now: Instant) -> Self {
+        Self {
+            burst,
+            interval,
+            tokens: burst,
+            refilled_at: now,
+        }
+    }
+
+    /// How long the next message has to wait, None if it may be sent now.
+    pub fn wait(&mut self, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        (self.tokens == 0).then(|| self.interval.saturating_sub(now - self.refilled_at))
+    }
+
+    /// Counts a sent message.
+    pub fn take(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens = self.tokens.saturating_sub(1);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if self.tokens == self.burst {
+            self.refilled_at = now;
+            return;
+        }
+        let refills = (now - self.refilled_at).as_nanos() / self.interval.as_nanos().max(1);
+        let refills = u32::try_from(refills).unwrap_or(u32::MAX);
+        if refills > 0 {
+            self.tokens = self.tokens.saturating_add(refills).min(self.burst);
+            self.refilled_at += self.interval * refills.min(self.burst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiting_messages_after_a_burst() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(3, Duration::from_secs(2), now);
+        for _ in 0..3 {
+            assert_eq!(limiter.wait(now), None);
+            limiter.take(now);
+        }
+        assert_eq!(limiter.wait(now), Some(Duration::from_secs(2)));
+        let later = now + Duration::from_millis(1500);
+        assert_eq!(limiter.wait(later), Some(Duration::from_millis(500)));
+        // one message per interval
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.wait(later), None);
+        limiter.take(later);
+        assert_eq!(limiter.wait(later), Some(Duration::from_secs(2)));
+        // a quiet time refills the burst, but no more
+        let later = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(limiter.wait(later), None);
+            limiter.take(later);
+        }
+        assert!(limiter.wait(later).is_some());
+    }
+}
@@ -1,23 +1,43 @@
+use super::stream::ChatStream;
+use super::{quarantine::Quarantine, seen::SeenMessages};
 use crate::connect::error::ConnectorError;
-use crate::connect::{types::CommandType, Badge, ChatBotEvent, Command, TextMessage, UserInfo};
+use crate::connect::{
+    types::CommandType, Badge, ChatBotEvent, Command, CommandPrefixes, Event, RoomState,
+    TextMessage, UserInfo,
+};
+use chrono::{DateTime, Utc};
 use std::collections::{HashMap, HashSet};
-use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::Instant;
 use websocket::WebSocketError;
 use websocket::{receiver::Reader, OwnedMessage};
 
-pub fn receive(receiver: &mut Reader<TcpStream>) -> Result<Vec<ReceiveEvent>, ConnectorError> {
+pub fn receive(
+    receiver: &mut Reader<ChatStream>,
+    quarantine: &Mutex<Quarantine>,
+    seen_messages: &Mutex<SeenMessages>,
+    command_prefixes: &CommandPrefixes,
+    suppress_membership: bool,
+) -> Result<Vec<ReceiveEvent<Event>>, ConnectorError> {
     loop {
         match receiver.recv_message() {
-            Err(WebSocketError::NoDataAvailable) => continue,
+            // the stream ended, e.g. because the connector was closed
+            Err(WebSocketError::NoDataAvailable) => {
+                return Err(ConnectorError::MessageReceiveFailed(
+                    "The connection to the chat server was closed".to_owned(),
+                ))
+            }
             response => match response {
                 Ok(owned_message) => match owned_message {
+                    // the chat lines are printed with their events by the main loop
                     OwnedMessage::Text(text) => {
-                        println!("New websocket message: {}", text);
-                        let events = text
-                            .lines()
-                            .filter_map(ReceiveEvent::parse_from_message)
-                            .collect();
-                        return Ok(events);
+                        return Ok(parse_lines(
+                            &text,
+                            quarantine,
+                            seen_messages,
+                            command_prefixes,
+                            suppress_membership,
+                        ));
                     }
                     _ => continue,
                 },
@@ -32,53 +52,155 @@ pub fn receive(receiver: &mut Reader<TcpStream>) -> Result<Vec<ReceiveEvent>, Co
     }
 }
 
+/// Parses the lines of a websocket message, dropping tagged messages which were already received
+/// (e.g. redelivered after a reconnect). Untagged lines like JOIN and PART are always parsed.
+/// Commands are parsed with the prefix of the channel the line is from.
+fn parse_lines(
+    text: &str,
+    quarantine: &Mutex<Quarantine>,
+    seen_messages: &Mutex<SeenMessages>,
+    command_prefixes: &CommandPrefixes,
+    suppress_membership: bool,
+) -> Vec<ReceiveEvent<Event>> {
+    let received_at = Utc::now();
+    let mut events = Vec::new();
+    for line in text.lines() {
+        if let Some(id) = message_id(line) {
+            if !seen_messages
+                .lock()
+                .unwrap()
+                .first_time(&id, Instant::now())
+            {
+                println!("Dropping message {} which was already received", id);
+                continue;
+            }
+        }
+        let command_prefix = command_prefixes.for_channel(line_channel(line).as_deref());
+        match ReceiveEvent::parse_with_prefix(line, command_prefix) {
+            // in big channels they are spam, which is not even logged
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Join(_) | ChatBotEvent::Part(_)))
+                if suppress_membership => {}
+            Some(ReceiveEvent::ChatBotEvent(content)) => {
+                events.push(ReceiveEvent::ChatBotEvent(wrap(line, content, received_at)))
+            }
+            Some(ReceiveEvent::ConnectorEvent(event)) => {
+                events.push(ReceiveEvent::ConnectorEvent(event))
+            }
+            None => quarantine.lock().unwrap().add(line),
+        }
+    }
+    events
+}
+
+/// Wraps an event parsed from a chat line with its channel and the time it was sent,
+/// which is the time it was received if the line is not tagged with it.
+fn wrap(line: &str, content: ChatBotEvent, received_at: DateTime<Utc>) -> Event {
+    Event {
+        sent_at: sent_at(line).unwrap_or(received_at),
+        channel: line_channel(line),
+        raw: line.to_owned(),
+        content,
+    }
+}
+
+/// The time the chat server sent a tagged line (`tmi-sent-ts`, in milliseconds since the epoch).
+pub fn sent_at(line: &str) -> Option<DateTime<Utc>> {
+    let (tags, _) = line.strip_prefix('@')?.split_once(' ')?;
+    let millis = parse_tags(tags).remove("tmi-sent-ts")?.parse().ok()?;
+    DateTime::from_timestamp_millis(millis)
+}
+
+// the channel named by a line like `:user!user@host PRIVMSG #channel :text`, whispers have none
+fn line_channel(line: &str) -> Option<String> {
+    let line = match line.strip_prefix('@') {
+        Some(tagged) => tagged.split_once(' ')?.1,
+        None => line,
+    };
+    line.split(' ')
+        .skip(1)
+        .take_while(|word| !word.starts_with(':'))
+        .find_map(|word| word.strip_prefix('#'))
+        .map(String::from)
+}
+
+// the unique id twitch tags messages with
+fn message_id(line: &str) -> Option<String> {
+    let (tags, _) = line.strip_prefix('@')?.split_once(' ')?;
+    parse_tags(tags).remove("id").filter(|id| !id.is_empty())
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ConnectorEvent {
     Ping,
+    // answer to a ping of the bot, with the token of the ping
+    Pong(String),
+    // answer to a capability request, with the names of the capabilities without `twitch.tv/`
+    Capabilities {
+        acknowledged: bool,
+        names: Vec<String>,
+    },
 }
 
+/// An event parsed from a chat line, the connector wraps the events of the bot as `Event`.
 #[derive(Debug, PartialEq)]
-pub enum ReceiveEvent {
-    ChatBotEvent(ChatBotEvent),
+pub enum ReceiveEvent<E = ChatBotEvent> {
+    ChatBotEvent(E),
     ConnectorEvent(ConnectorEvent),
 }
 
 impl ReceiveEvent {
-    fn parse_command_kind(command_name: &str) -> CommandType {
-        match command_name {
-            "help" => CommandType::Help,
-            "info" => CommandType::Info,
-            "newcommand" => CommandType::NewCommand,
-            "removecommand" => CommandType::RemoveCommand,
-            "slap" => CommandType::Slap,
-            "discord" => CommandType::Discord,
-            "newrepeating" => CommandType::NewRepeating,
-            "removerepeating" => CommandType::RemoveRepeating,
-            _ => CommandType::Dynamic(command_name.to_owned()),
+    pub(crate) fn parse_command_kind(command_name: &str) -> CommandType {
+        match command_name.parse() {
+            Ok(kind) => kind,
+            Err(never) => match never {},
         }
     }
 
-    fn parse_command_from_message(message: &str) -> Option<(CommandType, Vec<String>)> {
-        if message == "!" {
+    fn parse_command_from_message<'a>(
+        message: &'a str,
+        command_prefix: &str,
+    ) -> Option<(CommandType, &'a str)> {
+        if message == command_prefix {
             return None;
         }
-        let mut words = message.split(' ');
-        words.next().map(|name| {
-            (
-                ReceiveEvent::parse_command_kind(&name[1..]),
-                words.map(String::from).collect(),
-            )
-        })
+        let (name, raw_args) = message.split_once(' ').unwrap_or((message, ""));
+        let name = name.strip_prefix(command_prefix)?;
+        Some((
+            ReceiveEvent::parse_command_kind(name),
+            raw_args.trim_start(),
+        ))
     }
 
+    /// The name color of the user who sent a tagged line, e.g. `#1E90FF`.
+    /// It is empty if the user never chose a color, None if the line has no tags.
+    pub(crate) fn name_color(line: &str) -> Option<String> {
+        let (tags, _) = line.strip_prefix('@')?.split_once(' ')?;
+        Some(parse_tags(tags).remove("color").unwrap_or_default())
+    }
+
+    /// The channel (without `#`) a line is from, None for whispers and lines of the server.
+    #[cfg(test)]
+    pub(crate) fn channel(line: &str) -> Option<String> {
+        line_channel(line)
+    }
+
+    /// Parses a line of the chat server, taking messages starting with `!` for commands.
+    #[cfg(test)]
     pub fn parse_from_message(message: &str) -> Option<Self> {
+        Self::parse_with_prefix(message, crate::connect::DEFAULT_COMMAND_PREFIX)
+    }
+
+    /// Parses a line of the chat server, taking messages starting with the prefix for commands.
+    pub fn parse_with_prefix(message: &str, command_prefix: &str) -> Option<Self> {
         enum ParsingState {
             Start,
             Tags,
+            Source,
             UserName,
             AdditionalUserInfo,
             MessageToken,
             Channel,
+            BodyStart,
             MessageBody,
         }
         use ParsingState::*;
@@ -86,12 +208,40 @@ impl ReceiveEvent {
         let mut state = Start;
         let mut user_name = &message[0..0];
         let mut marker = 0;
+        let mut whisper = false;
         let mut tags_map = HashMap::<String, String>::new();
 
-        if message.starts_with("PING:") {
+        // twitch sends `PING :tmi.twitch.tv`
+        if message.starts_with("PING :") || message.starts_with("PING:") {
             return Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Ping));
         }
 
+        if let Some(answer) = message.strip_prefix(":tmi.twitch.tv CAP * ") {
+            let (acknowledged, names) = match answer.split_once(" :")? {
+                ("ACK", names) => (true, names),
+                ("NAK", names) => (false, names),
+                _ => return None,
+            };
+            return Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Capabilities {
+                acknowledged,
+                names: names
+                    .split_whitespace()
+                    .map(|name| name.strip_prefix("twitch.tv/").unwrap_or(name).to_owned())
+                    .collect(),
+            }));
+        }
+
+        if let Some(event) = parse_state(message) {
+            return Some(ReceiveEvent::ChatBotEvent(event));
+        }
+
+        if let Some(pong) = message.strip_prefix(":tmi.twitch.tv PONG ") {
+            let token = pong.split_once(':').map(|(_, token)| token.trim())?;
+            return Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Pong(
+                token.to_owned(),
+            )));
+        }
+
         for (i, codepoint) in message.char_indices() {
             match state {
                 Start => match codepoint {
@@ -106,13 +256,23 @@ impl ReceiveEvent {
                 },
                 Tags => {
                     if codepoint == ' ' {
-                        state = UserName;
+                        state = Source;
                         tags_map = parse_tags(&message[1..i]);
-                        marker = i + 2;
                     }
                 }
+                // the user follows the tags after a `:`, which is checked instead of skipped,
+                // so that the user name is not sliced from the middle of a character
+                Source => match codepoint {
+                    ':' => {
+                        state = UserName;
+                        marker = i + 1;
+                    }
+                    _ => return None,
+                },
                 UserName => match codepoint {
                     ' ' => return None,
+                    // lines without a user name are not from a user
+                    '!' if marker == i => return None,
                     '!' => {
                         user_name = &message[marker..i];
                         state = AdditionalUserInfo;
@@ -130,6 +290,13 @@ impl ReceiveEvent {
                         let token = &message[marker..i];
                         match token {
                             "PRIVMSG" => {
+                                marker = i + 1;
+                                state = Channel;
+                            }
+                            // whispers name the receiving user where messages name the channel
+                            "WHISPER" => {
+                                whisper = true;
+                                marker = i + 1;
                                 state = Channel;
                             }
                             "JOIN" => {
@@ -147,33 +314,70 @@ impl ReceiveEvent {
                     }
                 }
                 Channel => {
-                    if codepoint == ':' {
-                        state = MessageBody;
+                    if codepoint == ' ' {
+                        if !is_valid_channel(&message[marker..i]) {
+                            return None;
+                        }
+                        state = BodyStart;
                     }
                 }
+                BodyStart => match codepoint {
+                    ':' => state = MessageBody,
+                    _ => return None,
+                },
                 MessageBody => {
+                    let bits = tags_map.get("bits").and_then(|bits| bits.parse().ok());
+                    let reward_id = tags_map
+                        .get("custom-reward-id")
+                        .filter(|reward_id| !reward_id.is_empty())
+                        .cloned();
+                    let id = tags_map.get("user-id").cloned();
+                    let room_id = tags_map
+                        .get("room-id")
+                        .and_then(|room_id| room_id.parse().ok());
+                    let message_id = tags_map.get("id").filter(|id| !id.is_empty()).cloned();
+                    let first_message = tags_map.get("first-msg").is_some_and(|first| first == "1");
                     let badges = get_badges(tags_map);
                     let user_info = UserInfo {
                         name: user_name.to_owned(),
+                        id,
                         badges,
                     };
                     let user_message = message[i..].trim();
-                    if codepoint == '!' {
-                        let (command_kind, command_options) =
-                            ReceiveEvent::parse_command_from_message(user_message)?;
-                        return Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
-                            kind: command_kind,
-                            options: command_options,
-                            user: user_info,
-                        })));
-                    } else {
-                        return Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::TextMessage(
-                            TextMessage {
-                                text: message[i..].trim().to_owned(),
-                                user: user_info,
-                            },
-                        )));
+                    if message[i..].starts_with(command_prefix) {
+                        let (command_kind, raw_args) =
+                            ReceiveEvent::parse_command_from_message(user_message, command_prefix)?;
+                        let command = Command::new(command_kind, raw_args, user_info, room_id);
+                        return Some(ReceiveEvent::ChatBotEvent(if whisper {
+                            ChatBotEvent::Whisper(command)
+                        } else {
+                            ChatBotEvent::Command(command)
+                        }));
                     }
+                    // the bot only understands commands in whispers
+                    if whisper {
+                        return None;
+                    }
+                    let text_message = TextMessage {
+                        text: user_message.to_owned(),
+                        user: user_info,
+                        room_id,
+                        id: message_id,
+                        first_message,
+                    };
+                    // cheers and redemptions of rewards with a text are messages with extra tags
+                    let event = match (bits, reward_id) {
+                        (Some(bits), _) => ChatBotEvent::Cheer {
+                            message: text_message,
+                            bits,
+                        },
+                        (None, Some(reward_id)) => ChatBotEvent::Redemption {
+                            message: text_message,
+                            reward_id,
+                        },
+                        (None, None) => ChatBotEvent::TextMessage(text_message),
+                    };
+                    return Some(ReceiveEvent::ChatBotEvent(event));
                 }
             }
         }
@@ -181,6 +385,92 @@ impl ReceiveEvent {
     }
 }
 
+// ROOMSTATE, USERSTATE, CLEARCHAT, CLEARMSG and USERNOTICE are sent by the chat server itself,
+// without a user
+fn parse_state(message: &str) -> Option<ChatBotEvent> {
+    let (tags, rest) = message.strip_prefix('@')?.split_once(' ')?;
+    let tags = parse_tags(tags);
+    let mode = |name: &str| tags.get(name).map(|value| value == "1");
+    let mut words = rest.split(' ');
+    match words.nth(1)? {
+        "ROOMSTATE" => Some(ChatBotEvent::RoomState(RoomState {
+            emote_only: mode("emote-only"),
+            subs_only: mode("subs-only"),
+            slow: tags.get("slow").and_then(|seconds| seconds.parse().ok()),
+            followers_only: tags
+                .get("followers-only")
+                .and_then(|minutes| minutes.parse().ok()),
+            unique_chat: mode("r9k"),
+        })),
+        "USERSTATE" => Some(ChatBotEvent::UserState {
+            display_name: tags
+                .get("display-name")
+                .filter(|name| !name.is_empty())
+                .cloned(),
+            badges: get_badges(tags),
+        }),
+        // the user of a timeout or ban follows the channel, `/clear` has none
+        "CLEARCHAT" => Some(ChatBotEvent::ClearChat(
+            words
+                .nth(1)
+                .and_then(|user| user.strip_prefix(':'))
+                .map(String::from),
+        )),
+        "CLEARMSG" => Some(ChatBotEvent::ClearMessage(
+            tags.get("target-msg-id")
+                .filter(|id| !id.is_empty())?
+                .to_owned(),
+        )),
+        "USERNOTICE" => parse_user_notice(tags, rest),
+        _ => None,
+    }
+}
+
+// announcements and watch streaks, the user is only named by the tags
+// since the chat server sends the notice, e.g. `@...;login=mod;msg-id=announcement :tmi.twitch.tv USERNOTICE #channel :text`
+fn parse_user_notice(tags: HashMap<String, String>, rest: &str) -> Option<ChatBotEvent> {
+    let notice = tags.get("msg-id")?.to_owned();
+    let color = tags.get("msg-param-color").cloned().unwrap_or_default();
+    let streak = tags
+        .get("msg-param-value")
+        .and_then(|streak| streak.parse().ok());
+    let watch_streak = tags
+        .get("msg-param-category")
+        .is_some_and(|category| category == "watch-streak");
+    let message = TextMessage {
+        // milestones may come without a text of the viewer
+        text: rest
+            .split_once(" :")
+            .map(|(_, text)| text.trim().to_owned())
+            .unwrap_or_default(),
+        room_id: tags.get("room-id").and_then(|room_id| room_id.parse().ok()),
+        id: tags.get("id").filter(|id| !id.is_empty()).cloned(),
+        first_message: false,
+        user: UserInfo {
+            name: tags
+                .get("login")
+                .filter(|login| !login.is_empty())?
+                .to_owned(),
+            id: tags.get("user-id").cloned(),
+            badges: get_badges(tags),
+        },
+    };
+    match notice.as_str() {
+        "announcement" => Some(ChatBotEvent::Announcement { message, color }),
+        "viewermilestone" if watch_streak => Some(ChatBotEvent::ViewerMilestone {
+            message,
+            streak: streak?,
+        }),
+        _ => None,
+    }
+}
+
+// channel names are twitch user names, the leading '#' is omitted by some servers
+fn is_valid_channel(channel: &str) -> bool {
+    let channel = channel.strip_prefix('#').unwrap_or(channel);
+    !channel.is_empty() && channel.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
 fn parse_tags(tags_string: &str) -> HashMap<String, String> {
     tags_string
         .split(';')
@@ -218,6 +508,7 @@ fn get_badges(tags: HashMap<String, String>) -> HashSet<Badge> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn parsing_user_messages() {
@@ -227,8 +518,12 @@ mod tests {
                 text: "This is a test message".to_owned(),
                 user: UserInfo {
                     name: "chatter".to_owned(),
+                    id: Some("70346833".to_owned()),
                     badges: HashSet::default(),
                 },
+                room_id: Some(120630112),
+                id: Some("60904094-3684-4871-9e8c-1400648a804d".to_owned()),
+                first_message: false,
             },
         )));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
@@ -242,8 +537,12 @@ mod tests {
                 text: "This is a test message".to_owned(),
                 user: UserInfo {
                     name: "chatter".to_owned(),
+                    id: Some("70346833".to_owned()),
                     badges: HashSet::default(),
                 },
+                room_id: Some(120630112),
+                id: Some("60904094-3684-4871-9e8c-1400648a804d".to_owned()),
+                first_message: false,
             },
         )));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
@@ -257,6 +556,7 @@ mod tests {
                 text: "This is a test message".to_owned(),
                 user: UserInfo {
                     name: "chatter".to_owned(),
+                    id: Some("70346833".to_owned()),
                     badges: HashSet::from([
                         Badge {
                             name: "badge1".to_owned(),
@@ -268,6 +568,9 @@ mod tests {
                         },
                     ]),
                 },
+                room_id: Some(120630112),
+                id: Some("60904094-3684-4871-9e8c-1400648a804d".to_owned()),
+                first_message: false,
             },
         )));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
@@ -279,24 +582,50 @@ mod tests {
         let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
             kind: CommandType::Help,
             options: Vec::default(),
+            raw_args: String::new(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
 
+    #[test]
+    fn parsing_whispered_commands() {
+        let message = "@badges=;color=;display-name=Carkhy;emotes=;message-id=3;thread-id=70346833_120630112;turbo=0;user-id=70346833;user-type= :carkhy!carkhy@carkhy.tmi.twitch.tv WHISPER chatbot :!say hello chat";
+        let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Whisper(Command {
+            kind: CommandType::Say,
+            options: vec!["hello".to_owned(), "chat".to_owned()],
+            raw_args: "hello chat".to_owned(),
+            user: UserInfo {
+                name: "carkhy".to_owned(),
+                id: Some("70346833".to_owned()),
+                badges: HashSet::default(),
+            },
+            room_id: None,
+        })));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+        let text =
+            "@badges=;user-id=70346833 :carkhy!carkhy@carkhy.tmi.twitch.tv WHISPER chatbot :hello";
+        assert_eq!(ReceiveEvent::parse_from_message(text), None);
+    }
+
     #[test]
     fn parsing_info_command() {
         let message = "@badge-info=;badges=;client-nonce=1e51cee7513a4516545bbc36a22f27eb;color=;display-name=carkhy;emotes=;first-msg=0;flags=;id=60904094-3684-4871-9e8c-1400648a804d;mod=0;room-id=120630112;subscriber=0;tmi-sent-ts=1637614002702;turbo=0;user-id=70346833;user-type= :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!info";
         let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
             kind: CommandType::Info,
             options: Vec::default(),
+            raw_args: String::new(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
@@ -325,10 +654,13 @@ mod tests {
         let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
             kind: CommandType::Slap,
             options: vec!["anotheruser".to_owned()],
+            raw_args: "anotheruser".to_owned(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
@@ -344,10 +676,13 @@ mod tests {
                 "to".to_owned(),
                 "output".to_owned(),
             ],
+            raw_args: "command Text to output".to_owned(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
@@ -358,10 +693,13 @@ mod tests {
         let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
             kind: CommandType::RemoveCommand,
             options: vec!["command".to_owned()],
+            raw_args: "command".to_owned(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
@@ -372,10 +710,13 @@ mod tests {
         let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
             kind: CommandType::Discord,
             options: Vec::default(),
+            raw_args: String::new(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
@@ -386,10 +727,13 @@ mod tests {
         let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
             kind: CommandType::Dynamic("unknown".to_owned()),
             options: vec!["command".to_owned()],
+            raw_args: "command".to_owned(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
@@ -406,11 +750,551 @@ mod tests {
                 "to".to_owned(),
                 "output".to_owned(),
             ],
+            raw_args: "command 60 Text to output".to_owned(),
+            user: UserInfo {
+                name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
+                badges: HashSet::default(),
+            },
+            room_id: Some(120630112),
+        })));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+    }
+
+    #[test]
+    fn parsing_counter_command() {
+        let message = "@badge-info=;badges=;client-nonce=1e51cee7513a4516545bbc36a22f27eb;color=;display-name=carkhy;emotes=;first-msg=0;flags=;id=60904094-3684-4871-9e8c-1400648a804d;mod=0;room-id=120630112;subscriber=0;tmi-sent-ts=1637614002702;turbo=0;user-id=70346833;user-type= :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!counter deaths +1";
+        let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(Command {
+            kind: CommandType::Counter,
+            options: vec!["deaths".to_owned(), "+1".to_owned()],
+            raw_args: "deaths +1".to_owned(),
             user: UserInfo {
                 name: "chatter".to_owned(),
+                id: Some("70346833".to_owned()),
                 badges: HashSet::default(),
             },
+            room_id: Some(120630112),
         })));
         assert_eq!(ReceiveEvent::parse_from_message(message), expected);
     }
+
+    #[test]
+    fn keeping_the_spacing_of_raw_args() {
+        let message = ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!newcommand  greet  Hello,   @{user}!";
+        match ReceiveEvent::parse_from_message(message) {
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Command(command))) => {
+                assert_eq!(command.options, vec!["greet", "Hello,", "@{user}!"]);
+                assert_eq!(command.raw_args, "greet  Hello,   @{user}!");
+            }
+            event => panic!("not a command: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn parsing_pong_messages() {
+        let message = ":tmi.twitch.tv PONG tmi.twitch.tv :token123";
+        let expected = Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Pong(
+            "token123".to_owned(),
+        )));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+    }
+
+    #[test]
+    fn parsing_capability_answers() {
+        let message = ":tmi.twitch.tv CAP * ACK :twitch.tv/tags twitch.tv/commands";
+        let expected = Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Capabilities {
+            acknowledged: true,
+            names: vec!["tags".to_owned(), "commands".to_owned()],
+        }));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+        let message = ":tmi.twitch.tv CAP * NAK :twitch.tv/membership";
+        let expected = Some(ReceiveEvent::ConnectorEvent(ConnectorEvent::Capabilities {
+            acknowledged: false,
+            names: vec!["membership".to_owned()],
+        }));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+        assert_eq!(
+            ReceiveEvent::parse_from_message(":tmi.twitch.tv CAP * LS :twitch.tv/tags"),
+            None
+        );
+    }
+
+    #[test]
+    fn parsing_cheers() {
+        let message = "@badge-info=;badges=;bits=500;display-name=chatter;id=1 :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :Cheer500 great stream";
+        let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Cheer {
+            message: TextMessage {
+                text: "Cheer500 great stream".to_owned(),
+                user: UserInfo {
+                    name: "chatter".to_owned(),
+                    id: None,
+                    badges: HashSet::default(),
+                },
+                room_id: None,
+                id: Some("1".to_owned()),
+                first_message: false,
+            },
+            bits: 500,
+        }));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+    }
+
+    #[test]
+    fn parsing_redemptions() {
+        let message = "@badges=;custom-reward-id=f3a1c0de;display-name=chatter;id=1 :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :play some jazz";
+        let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Redemption {
+            message: TextMessage {
+                text: "play some jazz".to_owned(),
+                user: UserInfo {
+                    name: "chatter".to_owned(),
+                    id: None,
+                    badges: HashSet::default(),
+                },
+                room_id: None,
+                id: Some("1".to_owned()),
+                first_message: false,
+            },
+            reward_id: "f3a1c0de".to_owned(),
+        }));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+    }
+
+    #[test]
+    fn parsing_messages_without_channel_hash() {
+        let message =
+            ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG channel123 :This is a test message";
+        let expected = Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::TextMessage(
+            TextMessage {
+                text: "This is a test message".to_owned(),
+                user: UserInfo {
+                    name: "chatter".to_owned(),
+                    id: None,
+                    badges: HashSet::default(),
+                },
+                room_id: None,
+                id: None,
+                first_message: false,
+            },
+        )));
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+        let message =
+            ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :This is a test message";
+        assert_eq!(ReceiveEvent::parse_from_message(message), expected);
+    }
+
+    #[test]
+    fn parsing_deleted_messages() {
+        let message = "@ban-duration=350;room-id=12345678;target-user-id=87654321;tmi-sent-ts=1642715756806 :tmi.twitch.tv CLEARCHAT #channel123 :ronni";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::ClearChat(Some(
+                "ronni".to_owned()
+            ))))
+        );
+        let message =
+            "@room-id=12345678;tmi-sent-ts=1642715695392 :tmi.twitch.tv CLEARCHAT #channel123";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::ClearChat(None)))
+        );
+        let message = "@login=ronni;room-id=;target-msg-id=abc-123-def;tmi-sent-ts=1642720582342 :tmi.twitch.tv CLEARMSG #channel123 :HeyGuys";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::ClearMessage(
+                "abc-123-def".to_owned()
+            )))
+        );
+        let message = "@login=ronni;room-id= :tmi.twitch.tv CLEARMSG #channel123 :HeyGuys";
+        assert_eq!(ReceiveEvent::parse_from_message(message), None);
+    }
+
+    #[test]
+    fn parsing_announcements_and_viewer_milestones() {
+        let message = "@badge-info=;badges=moderator/1;color=#1E90FF;display-name=Ronni;emotes=;flags=;id=d5a1d2b3-5a4c-4e0a-9c1e-1b2c3d4e5f60;login=ronni;mod=1;msg-id=announcement;msg-param-color=PRIMARY;room-id=12345678;subscriber=0;system-msg=;tmi-sent-ts=1700000000000;user-id=87654321;user-type=mod :tmi.twitch.tv USERNOTICE #channel123 :Giveaway at the end of the stream!";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::Announcement {
+                message: TextMessage {
+                    text: "Giveaway at the end of the stream!".to_owned(),
+                    user: UserInfo {
+                        name: "ronni".to_owned(),
+                        id: Some("87654321".to_owned()),
+                        badges: HashSet::from([Badge {
+                            name: "moderator".to_owned(),
+                            level: 1,
+                        }]),
+                    },
+                    room_id: Some(12345678),
+                    id: Some("d5a1d2b3-5a4c-4e0a-9c1e-1b2c3d4e5f60".to_owned()),
+                    first_message: false,
+                },
+                color: "PRIMARY".to_owned(),
+            }))
+        );
+
+        let message = "@badge-info=;badges=;color=;display-name=Carkhy;emotes=;flags=;id=0e8b4b8c-6f2a-4c3e-8d5b-2a1b3c4d5e6f;login=carkhy;mod=0;msg-id=viewermilestone;msg-param-category=watch-streak;msg-param-copoReward=450;msg-param-id=7c1d2e3f-4a5b-6c7d-8e9f-0a1b2c3d4e5f;msg-param-value=7;room-id=12345678;subscriber=0;system-msg=Carkhy\\swatched\\s7\\sconsecutive\\sstreams\\sthis\\smonth\\sand\\ssparked\\sa\\swatch\\sstreak!;tmi-sent-ts=1700000000000;user-id=70346833;user-type= :tmi.twitch.tv USERNOTICE #channel123";
+        match ReceiveEvent::parse_from_message(message) {
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::ViewerMilestone { message, streak })) => {
+                assert_eq!(streak, 7);
+                assert_eq!(message.user.name, "carkhy");
+                assert_eq!(message.text, "");
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        let with_text = format!("{} :Love this stream", message);
+        assert!(matches!(
+            ReceiveEvent::parse_from_message(&with_text),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::ViewerMilestone { message, .. }))
+                if message.text == "Love this stream"
+        ));
+
+        // other notices (e.g. subs) are not used yet
+        let message = "@badges=;login=carkhy;msg-id=sub;msg-param-cumulative-months=1;room-id=12345678 :tmi.twitch.tv USERNOTICE #channel123";
+        assert_eq!(ReceiveEvent::parse_from_message(message), None);
+        let message =
+            "@badges=;login=;msg-id=announcement :tmi.twitch.tv USERNOTICE #channel123 :hi";
+        assert_eq!(ReceiveEvent::parse_from_message(message), None);
+    }
+
+    #[test]
+    fn parsing_room_states() {
+        let message = "@emote-only=0;followers-only=-1;r9k=0;room-id=120630112;slow=0;subs-only=1 :tmi.twitch.tv ROOMSTATE #channel123";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::RoomState(
+                RoomState {
+                    emote_only: Some(false),
+                    subs_only: Some(true),
+                    slow: Some(0),
+                    followers_only: Some(-1),
+                    unique_chat: Some(false),
+                }
+            )))
+        );
+        // changes only contain the changed mode
+        let message = "@emote-only=1;room-id=120630112 :tmi.twitch.tv ROOMSTATE #channel123";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::RoomState(
+                RoomState {
+                    emote_only: Some(true),
+                    ..RoomState::default()
+                }
+            )))
+        );
+        let message = "@room-id=120630112;slow=30 :tmi.twitch.tv ROOMSTATE #channel123";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::RoomState(
+                RoomState {
+                    slow: Some(30),
+                    ..RoomState::default()
+                }
+            )))
+        );
+        let message = "@followers-only=10;room-id=120630112 :tmi.twitch.tv ROOMSTATE #channel123";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::RoomState(
+                RoomState {
+                    followers_only: Some(10),
+                    ..RoomState::default()
+                }
+            )))
+        );
+        let message = "@badge-info=;badges=moderator/1;color=;display-name=ChatBot;emote-sets=0;mod=1;subscriber=0;user-type=mod :tmi.twitch.tv USERSTATE #channel123";
+        assert_eq!(
+            ReceiveEvent::parse_from_message(message),
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::UserState {
+                badges: HashSet::from([Badge {
+                    name: "moderator".to_owned(),
+                    level: 1,
+                }]),
+                display_name: Some("ChatBot".to_owned()),
+            }))
+        );
+    }
+
+    #[test]
+    fn parsing_room_ids() {
+        let message = "@badge-info=;badges=;client-nonce=1e51cee7513a4516545bbc36a22f27eb;color=;display-name=carkhy;emotes=;first-msg=0;flags=;id=60904094-3684-4871-9e8c-1400648a804d;mod=0;room-id=120630112;subscriber=0;tmi-sent-ts=1637614002702;turbo=0;user-id=70346833;user-type= :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :This is a test message";
+        let room_id = match ReceiveEvent::parse_from_message(message) {
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::TextMessage(message))) => message.room_id,
+            _ => None,
+        };
+        assert_eq!(room_id, Some(120630112));
+        let message = message.replace("room-id=120630112", "room-id=channel123");
+        let room_id = match ReceiveEvent::parse_from_message(&message) {
+            Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::TextMessage(message))) => message.room_id,
+            _ => Some(0),
+        };
+        assert_eq!(room_id, None);
+    }
+
+    #[test]
+    fn rejecting_messages_without_channel_or_body() {
+        let missing_body = ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 ";
+        assert_eq!(ReceiveEvent::parse_from_message(missing_body), None);
+        let missing_channel =
+            ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG :This is a test message";
+        assert_eq!(ReceiveEvent::parse_from_message(missing_channel), None);
+        let missing_both = ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123";
+        assert_eq!(ReceiveEvent::parse_from_message(missing_both), None);
+    }
+
+    #[test]
+    fn parsing_commands_with_the_prefix_of_their_channel() {
+        let quarantine = Mutex::new(Quarantine::new(&[], None));
+        let seen_messages = Mutex::new(SeenMessages::new(500, std::time::Duration::from_secs(600)));
+        let prefixes = CommandPrefixes::new(
+            "!",
+            HashMap::from([("channel123".to_owned(), "?".to_owned())]),
+        );
+        let text = ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :?help\r\n:chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!help\r\n:chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #other :!help\r\n:chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #other :?help";
+        let kinds: Vec<(Option<String>, bool)> = parse_lines(text, &quarantine, &seen_messages, &prefixes, false)
+            .into_iter()
+            .filter_map(|event| match event {
+                ReceiveEvent::ChatBotEvent(event) => Some((
+                    event.channel,
+                    matches!(event.content, ChatBotEvent::Command(command) if command.kind == CommandType::Help),
+                )),
+                ReceiveEvent::ConnectorEvent(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Some("channel123".to_owned()), true),
+                (Some("channel123".to_owned()), false),
+                (Some("other".to_owned()), true),
+                (Some("other".to_owned()), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn suppressing_joins_and_parts() {
+        let quarantine = Mutex::new(Quarantine::new(&[], None));
+        let seen_messages = Mutex::new(SeenMessages::new(500, std::time::Duration::from_secs(600)));
+        let text = ":chatter!chatter@chatter.tmi.twitch.tv JOIN #channel123\r\n:chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :hello\r\n:lurker!lurker@lurker.tmi.twitch.tv PART #channel123\r\nPING :tmi.twitch.tv";
+        let events = parse_lines(
+            text,
+            &quarantine,
+            &seen_messages,
+            &CommandPrefixes::default(),
+            true,
+        );
+        assert!(matches!(
+            &events[..],
+            [
+                ReceiveEvent::ChatBotEvent(Event {
+                    content: ChatBotEvent::TextMessage(_),
+                    ..
+                }),
+                ReceiveEvent::ConnectorEvent(ConnectorEvent::Ping)
+            ]
+        ));
+        // the lines were understood, they are not quarantined as unparsed
+        assert_eq!(quarantine.lock().unwrap().total(), 0);
+        let events = parse_lines(
+            text,
+            &quarantine,
+            &seen_messages,
+            &CommandPrefixes::default(),
+            false,
+        );
+        assert_eq!(events.len(), 4);
+    }
+
+    #[test]
+    fn wrapping_events_with_their_circumstances() {
+        let quarantine = Mutex::new(Quarantine::new(&[], None));
+        let seen_messages = Mutex::new(SeenMessages::new(500, std::time::Duration::from_secs(600)));
+        let before = Utc::now();
+        let text = "@badges=;tmi-sent-ts=1637614002702 :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :hello\r\n:chatter!chatter@chatter.tmi.twitch.tv JOIN #channel123\r\n:chatter!chatter@chatter.tmi.twitch.tv WHISPER bot :!help";
+        let events = parse_lines(
+            text,
+            &quarantine,
+            &seen_messages,
+            &CommandPrefixes::default(),
+            false,
+        );
+        let events: Vec<&Event> = events
+            .iter()
+            .filter_map(|event| match event {
+                ReceiveEvent::ChatBotEvent(event) => Some(event),
+                ReceiveEvent::ConnectorEvent(_) => None,
+            })
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].channel.as_deref(), Some("channel123"));
+        assert!(events[0].raw.ends_with("PRIVMSG #channel123 :hello"));
+        assert!(
+            matches!(&events[0].content, ChatBotEvent::TextMessage(message) if message.text == "hello")
+        );
+        // untagged lines are timestamped when they are received
+        assert_eq!(events[1].channel.as_deref(), Some("channel123"));
+        assert!(events[1].sent_at >= before);
+        assert_eq!(events[1].content, ChatBotEvent::Join("chatter".to_owned()));
+        // whispers name the bot instead of a channel
+        assert_eq!(events[2].channel, None);
+    }
+
+    #[test]
+    fn timestamping_events_when_they_were_sent() {
+        let line = "@badges=;room-id=120630112;tmi-sent-ts=1637614002702;user-id=70346833 :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :hello";
+        let sent = DateTime::parse_from_rfc3339("2021-11-22T20:46:42.702Z").unwrap();
+        assert_eq!(sent_at(line), Some(sent.with_timezone(&Utc)));
+        assert_eq!(sent_at(line).unwrap().timestamp_millis(), 1637614002702);
+        let received_at = Utc::now();
+        let event = wrap(line, ChatBotEvent::Connected, received_at);
+        assert_eq!(event.sent_at, sent);
+        // lines without a valid tag are timestamped when they are received
+        let line = line.replace("tmi-sent-ts=1637614002702", "tmi-sent-ts=");
+        assert_eq!(sent_at(&line), None);
+        let event = wrap(&line, ChatBotEvent::Connected, received_at);
+        assert_eq!(event.sent_at, received_at);
+        assert_eq!(
+            sent_at(":chatter!chatter@chatter.tmi.twitch.tv JOIN #channel123"),
+            None
+        );
+    }
+
+    #[test]
+    fn dropping_redelivered_messages() {
+        use crate::core::{ChatBot, ChatBotCommand};
+        use std::time::Duration;
+
+        let quarantine = Mutex::new(Quarantine::new(&[], None));
+        let seen_messages = Mutex::new(SeenMessages::new(500, Duration::from_secs(600)));
+        let text = "@badges=;id=60904094-3684-4871-9e8c-1400648a804d;user-id=70346833 :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!help\r\n:chatter!chatter@chatter.tmi.twitch.tv JOIN #channel123";
+        let mut events = parse_lines(
+            text,
+            &quarantine,
+            &seen_messages,
+            &CommandPrefixes::default(),
+            false,
+        );
+        // the chat server delivers the message again after a reconnect, the join is kept
+        events.extend(parse_lines(
+            text,
+            &quarantine,
+            &seen_messages,
+            &CommandPrefixes::default(),
+            false,
+        ));
+        assert_eq!(events.len(), 3);
+        assert_eq!(seen_messages.lock().unwrap().suppressed(), 1);
+
+        let mut bot = ChatBot::new();
+        let replies: Vec<ChatBotCommand> = events
+            .into_iter()
+            .filter_map(|event| match event {
+                ReceiveEvent::ChatBotEvent(event) => bot.handle_event(event),
+                ReceiveEvent::ConnectorEvent(_) => None,
+            })
+            .collect();
+        assert_eq!(replies.len(), 1);
+        assert!(
+            matches!(bot.take_pending_usage(), Some(ChatBotCommand::MultipleCommands(usage))
+                         if matches!(&usage[..], [ChatBotCommand::StoreUsage { counts, .. }]
+                                     if counts == &vec![("messages".to_owned(), 1), ("help".to_owned(), 1)]))
+        );
+    }
+
+    #[test]
+    fn rejecting_malformed_lines_found_by_the_property_tests() {
+        // the user name was sliced from the middle of the character after the tags
+        assert_eq!(ReceiveEvent::parse_from_message("@ ¡!"), None);
+        // the user name ended before it started
+        assert_eq!(ReceiveEvent::parse_from_message("@badges= !"), None);
+        assert_eq!(
+            ReceiveEvent::parse_from_message("@badges= :!chatter PRIVMSG #channel123 :hello"),
+            None
+        );
+        assert_eq!(
+            ReceiveEvent::parse_from_message(":!chatter@chatter.tmi.twitch.tv JOIN #channel123"),
+            None
+        );
+        // the trimmed command was shorter than the prefix
+        assert_eq!(
+            ReceiveEvent::parse_with_prefix(
+                ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :! ",
+                "! "
+            ),
+            None
+        );
+    }
+
+    // chat lines whose parts look like twitch's, so that the parser gets past its first states
+    const CHAT_LINE: &str =
+        "(@[^ ]{0,30} )?(:[^ ]{0,12}(![^ ]{0,12})? )?(PRIVMSG|WHISPER|JOIN|PART|CLEARCHAT|ROOMSTATE)? ?#?[^ ]{0,12} ?:?.{0,20}";
+
+    fn parse_everything(line: &str) {
+        for prefix in ["!", "?", "!!"] {
+            let _ = ReceiveEvent::parse_with_prefix(line, prefix);
+        }
+        let _ = line_channel(line);
+        let _ = message_id(line);
+        let _ = sent_at(line);
+        let _ = ReceiveEvent::name_color(line);
+    }
+
+    proptest! {
+        #[test]
+        fn parsing_arbitrary_lines_without_panicking(line in any::<String>()) {
+            parse_everything(&line);
+        }
+
+        #[test]
+        fn parsing_chat_like_lines_without_panicking(line in CHAT_LINE) {
+            parse_everything(&line);
+        }
+
+        #[test]
+        fn parsing_generated_messages(
+            user in "[a-z0-9_]{1,25}",
+            channel in "[a-z0-9_]{1,25}",
+            // commands start with the prefix, the parser trims the text
+            text in "[^!\\s\\p{C}][^\\p{C}]{0,40}",
+        ) {
+            let line = format!(
+                "@badges=;display-name={user};id=1;user-id=70346833 :{user}!{user}@{user}.tmi.twitch.tv PRIVMSG #{channel} :{text}",
+                user = user,
+                channel = channel,
+                text = text
+            );
+            match ReceiveEvent::parse_from_message(&line) {
+                Some(ReceiveEvent::ChatBotEvent(ChatBotEvent::TextMessage(message))) => {
+                    prop_assert_eq!(message.user.name, user);
+                    prop_assert_eq!(message.text, text.trim());
+                }
+                event => prop_assert!(false, "{} was parsed as {:?}", line, event),
+            }
+            prop_assert_eq!(line_channel(&line), Some(channel));
+        }
+
+        #[test]
+        fn parsing_generated_joins_and_parts(
+            user in "[a-z0-9_]{1,25}",
+            channel in "[a-z0-9_]{1,25}",
+            join in any::<bool>(),
+        ) {
+            let line = format!(
+                ":{user}!{user}@{user}.tmi.twitch.tv {command} #{channel}",
+                user = user,
+                command = if join { "JOIN" } else { "PART" },
+                channel = channel
+            );
+            let expected = if join {
+                ChatBotEvent::Join(user)
+            } else {
+                ChatBotEvent::Part(user)
+            };
+            prop_assert_eq!(
+                ReceiveEvent::parse_from_message(&line),
+                Some(ReceiveEvent::ChatBotEvent(expected))
+            );
+        }
+    }
 }
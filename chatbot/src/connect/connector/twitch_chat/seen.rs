@@ -0,0 +1,84 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Remembers the ids of the last tagged messages received from the chat server,
+/// so that messages redelivered after a reconnect are not handled twice.
+/// It outlives the connector, which is replaced when the bot reconnects.
+#[derive(Debug)]
+pub struct SeenMessages {
+    capacity: usize,
+    retention: Duration,
+    // oldest first
+    order: VecDeque<(String, Instant)>,
+    ids: HashSet<String>,
+    suppressed: u64,
+}
+
+impl SeenMessages {
+    /// Keeps at most `capacity` ids, each for at most `retention`.
+    pub fn new(capacity: usize, retention: Duration) -> Self {
+        Self {
+            capacity,
+            retention,
+            order: VecDeque::with_capacity(capacity),
+            ids: HashSet::with_capacity(capacity),
+            suppressed: 0,
+        }
+    }
+
+    /// Whether the message with the id is seen for the first time. Duplicates are counted.
+    pub fn first_time(&mut self, id: &str, now: Instant) -> bool {
+        while let Some((oldest, seen_at)) = self.order.front() {
+            if self.order.len() < self.capacity && now - *seen_at < self.retention {
+                break;
+            }
+            self.ids.remove(oldest);
+            self.order.pop_front();
+        }
+        if self.ids.contains(id) {
+            self.suppressed += 1;
+            return false;
+        }
+        if self.capacity > 0 {
+            self.ids.insert(id.to_owned());
+            self.order.push_back((id.to_owned(), now));
+        }
+        true
+    }
+
+    /// The number of duplicates dropped since the bot started.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppressing_duplicates() {
+        let now = Instant::now();
+        let mut seen = SeenMessages::new(500, Duration::from_secs(600));
+        assert!(seen.first_time("a", now));
+        assert!(seen.first_time("b", now));
+        assert!(!seen.first_time("a", now));
+        assert_eq!(seen.suppressed(), 1);
+    }
+
+    #[test]
+    fn forgetting_old_and_surplus_ids() {
+        let now = Instant::now();
+        let mut seen = SeenMessages::new(2, Duration::from_secs(60));
+        assert!(seen.first_time("a", now));
+        assert!(seen.first_time("b", now));
+        assert!(seen.first_time("c", now));
+        // "a" was dropped to make room for "c"
+        assert!(seen.first_time("a", now));
+        assert!(!seen.first_time("a", now));
+        assert!(seen.first_time("a", now + Duration::from_secs(60)));
+        assert_eq!(seen.suppressed(), 1);
+    }
+}
@@ -1,8 +1,8 @@
+use super::stream::ChatStream;
 use crate::connect::error::ConnectorError;
-use std::net::TcpStream;
 use websocket::{sync::Writer, Message};
 
-pub fn send(sender: &mut Writer<TcpStream>, task: SendTask) -> Result<(), ConnectorError> {
+pub fn send(sender: &mut Writer<ChatStream>, task: SendTask) -> Result<(), ConnectorError> {
     let message = Message::text(task.to_string());
     sender.send_message(&message).map_err(|err| {
         ConnectorError::MessageSendFailed(format!("Could not send message: {:?}", err))
@@ -10,7 +10,7 @@ pub fn send(sender: &mut Writer<TcpStream>, task: SendTask) -> Result<(), Connec
 }
 
 pub fn send_multiple(
-    sender: &mut Writer<TcpStream>,
+    sender: &mut Writer<ChatStream>,
     tasks: Vec<SendTask>,
 ) -> Result<(), ConnectorError> {
     for task in tasks {
@@ -19,40 +19,95 @@ pub fn send_multiple(
     Ok(())
 }
 
-pub fn get_login_tasks<'a>(
-    password: &'a str,
-    user_name: &'a str,
-    channel: &'a str,
-) -> Vec<SendTask> {
-    return vec![
-        SendTask::ProvideLoginPassword(password.to_string()),
-        SendTask::ProvideLoginUserName(user_name.to_string()),
-        SendTask::JoinChannel(channel.to_string()),
-        SendTask::RequestCapabilities("membership".to_string()),
-        SendTask::RequestCapabilities("tags".to_string()),
-    ];
+/// The capabilities are requested one by one, so that each is acknowledged or rejected on its own.
+pub fn get_login_tasks(password: &str, user_name: &str, capabilities: &[String]) -> Vec<SendTask> {
+    capabilities
+        .iter()
+        .map(|capability| SendTask::RequestCapabilities(capability.to_string()))
+        .chain([
+            SendTask::ProvideLoginPassword(password.to_string()),
+            SendTask::ProvideLoginUserName(user_name.to_string()),
+        ])
+        .collect()
+}
+
+/// Makes a chat message a single line, so that e.g. a line break and `PART` in an echoed text
+/// cannot send another command to the chat server. Line breaks and tabs become spaces,
+/// other control characters (e.g. null bytes) are removed.
+pub fn sanitize_message(message: &str) -> String {
+    message
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_control() && c.is_whitespace() => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// A raw line for the chat server, sanitized like a chat message so that it stays one line.
+/// A chat message to a channel (e.g. `PRIVMSG #channel :text`, with or without tags) keeps
+/// its channel, so that raw messages to the bot's channel wait for the chat modes like others.
+pub fn raw_task(line: &str) -> SendTask {
+    let line = sanitize_message(line);
+    let command = match line.strip_prefix('@') {
+        Some(tagged) => tagged.split_once(' ').map_or("", |(_, command)| command),
+        None => &line,
+    };
+    let channel = command
+        .strip_prefix("PRIVMSG #")
+        .and_then(|rest| rest.split(' ').next())
+        .filter(|channel| !channel.is_empty())
+        .map(str::to_lowercase);
+    SendTask::Raw { line, channel }
 }
 
 pub enum SendTask {
     PrivateMessage(String, String),
+    // sent as it is, see `raw_task`
+    Raw {
+        line: String,
+        channel: Option<String>,
+    },
     ProvideLoginPassword(String),
     ProvideLoginUserName(String),
     JoinChannel(String),
+    PartChannel(String),
     RequestCapabilities(String),
     Pong,
+    Ping(String),
+}
+
+impl SendTask {
+    /// The channel a chat message goes to, None for other tasks.
+    pub fn channel(&self) -> Option<&String> {
+        match self {
+            Self::PrivateMessage(channel, _) => Some(channel),
+            Self::Raw { channel, .. } => channel.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the task counts towards the message rate limit of the chat server.
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, Self::PrivateMessage(..) | Self::Raw { .. })
+    }
 }
 
 impl ToString for SendTask {
     fn to_string(&self) -> String {
         match self {
             Self::PrivateMessage(channel, message) => format!("PRIVMSG #{} :{}", channel, message),
+            Self::Raw { line, .. } => line.to_owned(),
             Self::ProvideLoginPassword(password) => format!("PASS oauth:{}", password),
             Self::ProvideLoginUserName(user_name) => format!("NICK {}", user_name),
             Self::JoinChannel(channel) => format!("JOIN #{}", channel),
+            Self::PartChannel(channel) => format!("PART #{}", channel),
             Self::RequestCapabilities(capability_name) => {
                 format!("CAP REQ :twitch.tv/{}", capability_name)
             }
             Self::Pong => format!("PONG :tmi.twitch.tv"),
+            Self::Ping(token) => format!("PING :{}", token),
         }
     }
 }
@@ -61,10 +116,55 @@ impl ToString for SendTask {
 mod tests {
     use super::*;
 
+    #[test]
+    fn requesting_capabilities_before_login() {
+        let tasks: Vec<String> = get_login_tasks(
+            "admin123",
+            "user123",
+            &["tags".to_string(), "commands".to_string()],
+        )
+        .iter()
+        .map(SendTask::to_string)
+        .collect();
+        assert_eq!(
+            tasks,
+            vec![
+                "CAP REQ :twitch.tv/tags",
+                "CAP REQ :twitch.tv/commands",
+                "PASS oauth:admin123",
+                "NICK user123"
+            ]
+        );
+    }
+
     #[test]
     fn prints_private_messages_correctly() {
         let task = SendTask::PrivateMessage("channelname".to_string(), "Message".to_string());
         assert_eq!(task.to_string(), "PRIVMSG #channelname :Message");
+        assert_eq!(task.channel().map(String::as_str), Some("channelname"));
+        assert_eq!(SendTask::Pong.channel(), None);
+    }
+
+    #[test]
+    fn sanitizing_messages() {
+        assert_eq!(sanitize_message("hi\r\nPART #channel"), "hi  PART #channel");
+        assert_eq!(sanitize_message("a\tb\0c\u{7}d"), "a bcd");
+        assert_eq!(sanitize_message("Grüße ✨"), "Grüße ✨");
+    }
+
+    #[test]
+    fn sending_raw_lines() {
+        let task = raw_task("CAP REQ :twitch.tv/membership\r\nPART #channel");
+        assert_eq!(
+            task.to_string(),
+            "CAP REQ :twitch.tv/membership  PART #channel"
+        );
+        assert_eq!(task.channel(), None);
+        let task = raw_task("PRIVMSG #Channel123 :hi");
+        assert_eq!(task.to_string(), "PRIVMSG #Channel123 :hi");
+        assert_eq!(task.channel().map(String::as_str), Some("channel123"));
+        let task = raw_task("@reply-parent-msg-id=42 PRIVMSG #channel123 :reply");
+        assert_eq!(task.channel().map(String::as_str), Some("channel123"));
     }
 
     #[test]
@@ -96,4 +196,10 @@ mod tests {
         let task = SendTask::Pong;
         assert_eq!(task.to_string(), "PONG :tmi.twitch.tv");
     }
+
+    #[test]
+    fn prints_ping_messages_correctly() {
+        let task = SendTask::Ping("token123".to_string());
+        assert_eq!(task.to_string(), "PING :token123");
+    }
 }
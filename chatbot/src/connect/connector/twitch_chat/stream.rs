@@ -0,0 +1,156 @@
+use crate::connect::error::ConnectorError;
+use native_tls::{TlsConnector, TlsStream};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+// a read of a tls stream gives the stream back to a waiting writer after this long at the latest
+const TLS_READ_SLICE: Duration = Duration::from_millis(50);
+
+/// The connection to the chat server, read by the receive thread and written by the send thread.
+/// A plain tcp stream (`ws://`) is cloned for both threads, a tls stream (`wss://`) cannot be
+/// cloned and is shared instead.
+pub enum ChatStream {
+    Plain(TcpStream),
+    Tls(Arc<SharedStream<TlsStream<TcpStream>>>),
+}
+
+impl ChatStream {
+    /// Returns the reading and the writing half of the connection, with a tls handshake
+    /// with `host` first if `secure` is set.
+    pub fn open(
+        stream: TcpStream,
+        secure: bool,
+        host: &str,
+    ) -> Result<(ChatStream, ChatStream), ConnectorError> {
+        if !secure {
+            let writer = stream.try_clone()?;
+            return Ok((ChatStream::Plain(stream), ChatStream::Plain(writer)));
+        }
+        let connector = TlsConnector::new()
+            .map_err(|err| ConnectorError::ExternalServerError(err.to_string()))?;
+        let tls_stream = connector
+            .connect(host, stream)
+            .map_err(|err| ConnectorError::ExternalServerError(err.to_string()))?;
+        tls_stream
+            .get_ref()
+            .set_read_timeout(Some(TLS_READ_SLICE))?;
+        let shared = Arc::new(SharedStream::new(tls_stream));
+        Ok((ChatStream::Tls(shared.clone()), ChatStream::Tls(shared)))
+    }
+}
+
+impl Read for ChatStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ChatStream::Plain(stream) => stream.read(buf),
+            ChatStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ChatStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ChatStream::Plain(stream) => stream.write(buf),
+            ChatStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ChatStream::Plain(stream) => stream.flush(),
+            ChatStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// A stream used by a reading and a writing thread at once. The underlying socket needs a read
+/// timeout: a read which times out releases the stream to waiting writers and is tried again,
+/// so that the reader never sees the timeout.
+pub struct SharedStream<S> {
+    stream: Mutex<S>,
+    waiting_writers: AtomicUsize,
+}
+
+impl<S: Read + Write> SharedStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream: Mutex::new(stream),
+            waiting_writers: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            while self.waiting_writers.load(Ordering::SeqCst) > 0 {
+                thread::yield_now();
+            }
+            match self.stream.lock().unwrap().read(buf) {
+                Err(err) if is_timeout(&err) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    pub fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        let mut stream = self.lock_for_writing();
+        loop {
+            match stream.write(buf) {
+                Err(err) if is_timeout(&err) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.lock_for_writing().flush()
+    }
+
+    fn lock_for_writing(&self) -> std::sync::MutexGuard<'_, S> {
+        self.waiting_writers.fetch_add(1, Ordering::SeqCst);
+        let stream = self.stream.lock().unwrap();
+        self.waiting_writers.fetch_sub(1, Ordering::SeqCst);
+        stream
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn writing_while_the_reader_waits() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let shared = Arc::new(SharedStream::new(client));
+        let reader = shared.clone();
+        let reading = thread::spawn(move || {
+            let mut buf = [0; 4];
+            reader.read(&mut buf).map(|len| buf[..len].to_vec())
+        });
+        thread::sleep(Duration::from_millis(50));
+        shared.write(b"PING").unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"PING");
+        server.write_all(b"PONG").unwrap();
+        assert_eq!(reading.join().unwrap().unwrap(), b"PONG");
+    }
+}
@@ -0,0 +1,320 @@
+use super::{
+    connector::twitch_chat::receive::ReceiveEvent, error::ConnectorError, Badge, ChatBotEvent,
+    Command, Event, UserInfo,
+};
+use serde_json::{json, Value};
+use std::{collections::HashSet, net::SocketAddr, sync::mpsc::Sender};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+/// Local control interface for external tools (e.g. a stream deck).
+/// Accepts newline delimited json requests on a localhost port, like
+/// `{"secret":"...","action":"say","text":"Starting soon!"}` or
+/// `{"secret":"...","action":"run_command","command":"so","args":["friend"]}` or
+/// `{"secret":"...","action":"raw","line":"CAP REQ :twitch.tv/membership"}`,
+/// and feeds them to the chat bot as events.
+pub struct ControlServer {
+    listener: TcpListener,
+    secret: String,
+    channel_name: String,
+    chatbot_event_sender: Sender<Event>,
+}
+
+#[derive(Debug, PartialEq)]
+enum ControlRequest {
+    Say(String),
+    RunCommand { command: String, args: Vec<String> },
+    Raw(String),
+}
+
+impl ControlServer {
+    /// Binds the control interface to the given port on localhost.
+    pub async fn bind(
+        port: u16,
+        secret: &str,
+        channel_name: &str,
+        chatbot_event_sender: Sender<Event>,
+    ) -> Result<Self, ConnectorError> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port)))
+            .await
+            .map_err(|err| {
+                ConnectorError::ExternalServerError(format!(
+                    "Could not bind control interface: {:?}",
+                    err
+                ))
+            })?;
+        Ok(Self {
+            listener,
+            secret: secret.to_owned(),
+            channel_name: channel_name.to_owned(),
+            chatbot_event_sender,
+        })
+    }
+
+    #[cfg(test)]
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    /// Accepts connections until the chat bot stops.
+    pub async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    let secret = self.secret.to_owned();
+                    let channel_name = self.channel_name.to_owned();
+                    let chatbot_event_sender = self.chatbot_event_sender.clone();
+                    tokio::spawn(async move {
+                        if let Err(error) =
+                            handle_connection(stream, &secret, &channel_name, chatbot_event_sender)
+                                .await
+                        {
+                            println!("Control connection closed with error {:?}", error);
+                        }
+                    });
+                }
+                Err(error) => println!("Could not accept control connection: {:?}", error),
+            }
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    secret: &str,
+    channel_name: &str,
+    chatbot_event_sender: Sender<Event>,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match parse_request(&line, secret, channel_name) {
+            Ok(request) => {
+                let event = to_event(request, channel_name);
+                match chatbot_event_sender.send(event.into()) {
+                    Ok(()) => json!({ "ok": true }),
+                    Err(_) => json!({ "ok": false, "error": "chat bot stopped" }),
+                }
+            }
+            Err(error) => json!({ "ok": false, "error": error }),
+        };
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await?;
+    }
+    Ok(())
+}
+
+fn parse_request(line: &str, secret: &str, channel_name: &str) -> Result<ControlRequest, String> {
+    let request: Value = serde_json::from_str(line).map_err(|_| "invalid json".to_owned())?;
+    if !request["secret"]
+        .as_str()
+        .is_some_and(|given| secrets_match(given, secret))
+    {
+        return Err("invalid secret".to_owned());
+    }
+    if let Some(channel) = request["channel"].as_str() {
+        if channel.trim_start_matches('#') != channel_name {
+            return Err(format!("unknown channel {}", channel));
+        }
+    }
+    match request["action"].as_str() {
+        Some("say") => request["text"]
+            .as_str()
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| ControlRequest::Say(text.to_owned()))
+            .ok_or_else(|| "say needs a text".to_owned()),
+        Some("run_command") => {
+            let command = request["command"]
+                .as_str()
+                .map(|command| command.trim_start_matches('!'))
+                .filter(|command| !command.is_empty())
+                .ok_or_else(|| "run_command needs a command".to_owned())?;
+            let args = match &request["args"] {
+                Value::Null => Vec::new(),
+                Value::Array(args) => args
+                    .iter()
+                    .map(|arg| arg.as_str().map(String::from))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| "args must be strings".to_owned())?,
+                _ => return Err("args must be a list".to_owned()),
+            };
+            Ok(ControlRequest::RunCommand {
+                command: command.to_owned(),
+                args,
+            })
+        }
+        Some("raw") => request["line"]
+            .as_str()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| ControlRequest::Raw(line.to_owned()))
+            .ok_or_else(|| "raw needs a line".to_owned()),
+        Some(action) => Err(format!("unknown action {}", action)),
+        None => Err("missing action".to_owned()),
+    }
+}
+
+// commands from the control interface are run on behalf of the broadcaster
+// compares all bytes, so that the time taken does not tell how much of a guess was right
+fn secrets_match(given: &str, secret: &str) -> bool {
+    given.len() == secret.len()
+        && given
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn to_event(request: ControlRequest, channel_name: &str) -> ChatBotEvent {
+    match request {
+        ControlRequest::Say(text) => ChatBotEvent::Say(text),
+        ControlRequest::Raw(line) => ChatBotEvent::Raw(line),
+        ControlRequest::RunCommand { command, args } => ChatBotEvent::Command(Command {
+            kind: ReceiveEvent::parse_command_kind(&command),
+            // the args are passed as given, even with spaces in them
+            raw_args: args.join(" "),
+            options: args,
+            user: UserInfo {
+                name: channel_name.to_owned(),
+                id: None,
+                badges: HashSet::from([Badge {
+                    name: "broadcaster".to_owned(),
+                    level: 1,
+                }]),
+            },
+            room_id: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ChatBot, ChatBotCommand};
+    use std::sync::mpsc;
+
+    #[test]
+    fn parsing_control_requests() {
+        assert_eq!(
+            parse_request(
+                r##"{"secret":"s3cret","action":"say","channel":"#me","text":"Starting soon!"}"##,
+                "s3cret",
+                "me"
+            ),
+            Ok(ControlRequest::Say("Starting soon!".to_owned()))
+        );
+        assert_eq!(
+            parse_request(
+                r#"{"secret":"s3cret","action":"run_command","command":"so","args":["friend"]}"#,
+                "s3cret",
+                "me"
+            ),
+            Ok(ControlRequest::RunCommand {
+                command: "so".to_owned(),
+                args: vec!["friend".to_owned()]
+            })
+        );
+        assert_eq!(
+            parse_request(
+                r#"{"secret":"s3cret","action":"raw","line":"CAP REQ :twitch.tv/membership"}"#,
+                "s3cret",
+                "me"
+            ),
+            Ok(ControlRequest::Raw(
+                "CAP REQ :twitch.tv/membership".to_owned()
+            ))
+        );
+        assert!(parse_request(r#"{"secret":"s3cret","action":"raw"}"#, "s3cret", "me").is_err());
+    }
+
+    #[test]
+    fn rejecting_invalid_control_requests() {
+        assert!(parse_request(r#"{"action":"say","text":"hi"}"#, "s3cret", "me").is_err());
+        assert!(parse_request(
+            r##"{"secret":"s3cret","action":"say","channel":"#other","text":"hi"}"##,
+            "s3cret",
+            "me"
+        )
+        .is_err());
+        assert!(parse_request(r#"{"secret":"s3cret","action":"dance"}"#, "s3cret", "me").is_err());
+        assert!(parse_request("say hi", "s3cret", "me").is_err());
+        assert!(parse_request(
+            r#"{"secret":"s3cre","action":"say","text":"hi"}"#,
+            "s3cret",
+            "me"
+        )
+        .is_err());
+        assert!(parse_request(
+            r#"{"secret":"s3crex","action":"say","text":"hi"}"#,
+            "s3cret",
+            "me"
+        )
+        .is_err());
+        assert!(secrets_match("s3cret", "s3cret"));
+        assert!(!secrets_match("", "s3cret"));
+    }
+
+    async fn send_line(stream: &mut BufReader<TcpStream>, line: &str) -> Value {
+        stream
+            .get_mut()
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_line(&mut response).await.unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn saying_messages_through_the_control_interface() {
+        let (tx, rx) = mpsc::channel();
+        let server = ControlServer::bind(0, "s3cret", "me", tx).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = BufReader::new(TcpStream::connect(addr).await.unwrap());
+        let response = send_line(
+            &mut stream,
+            r#"{"secret":"wrong","action":"say","text":"Starting soon!"}"#,
+        )
+        .await;
+        assert_eq!(response, json!({ "ok": false, "error": "invalid secret" }));
+        let response = send_line(
+            &mut stream,
+            r#"{"secret":"s3cret","action":"say","text":"Starting soon!"}"#,
+        )
+        .await;
+        assert_eq!(response, json!({ "ok": true }));
+
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(rx.recv().unwrap());
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Starting soon!"));
+    }
+
+    #[tokio::test]
+    async fn running_commands_through_the_control_interface() {
+        let (tx, rx) = mpsc::channel();
+        let server = ControlServer::bind(0, "s3cret", "me", tx).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = BufReader::new(TcpStream::connect(addr).await.unwrap());
+        let response = send_line(
+            &mut stream,
+            r#"{"secret":"s3cret","action":"run_command","command":"counter","args":["deaths","+1"]}"#,
+        )
+        .await;
+        assert_eq!(response, json!({ "ok": true }));
+
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(rx.recv().unwrap());
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message), _]
+                                     if message == "deaths: 1"))
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use super::error::ConnectorError;
+use serde_json::Value;
+
+/// Posts messages to a discord channel through a webhook.
+#[derive(Debug, Clone)]
+pub struct DiscordNotifier {
+    webhook_url: String,
+    client: reqwest::Client,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            webhook_url: webhook_url.to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Sends a webhook message, e.g. `{"content": "...", "embeds": [...]}`
+    pub async fn send(&self, message: &Value) -> Result<(), ConnectorError> {
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(message)
+            .send()
+            .await
+            .map_err(|err| ConnectorError::ExternalServerError(err.to_string()))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(ConnectorError::ExternalServerError(format!(
+                "Discord answered with {}",
+                response.status()
+            )))
+        }
+    }
+}
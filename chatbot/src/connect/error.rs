@@ -15,8 +15,18 @@ pub enum ConnectorError {
     HTTP404,
     #[error("Http status 403: forbidden: {0:?}")]
     HTTP403(String),
+    #[error("The chat server rejected the required capability twitch.tv/{0}")]
+    CapabilityRejected(String),
+    #[error("The device code expired before the bot was authorized")]
+    DeviceCodeExpired,
+    #[error("The authorization of the bot was declined")]
+    AuthorizationDeclined,
     #[error("No stored value available: {0}")]
     StoredValueNotAvailable(String),
+    #[error("{0}")]
+    Proxy(#[from] super::ProxyError),
+    #[error("Io error: {0:?}")]
+    IoError(#[from] std::io::Error),
     // Errors for other crates
     #[error("Send error {0:?}")]
     MPSCSendError(#[from] mpsc::SendError<SendTask>),
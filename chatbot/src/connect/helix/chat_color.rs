@@ -0,0 +1,51 @@
+use super::{super::types::*, user::parse_user, HelixClient};
+use reqwest::Method;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#update-user-chat-color
+    pub(super) async fn update_chat_color(&self, color: &str) -> Result<(), HelixError> {
+        // without a login the user of the access token, who the bot chats as
+        let users = self.send(Method::GET, "users", &[], None).await?;
+        let (user_id, _) = parse_user(&users)?;
+        self.send(
+            Method::PUT,
+            "chat/color",
+            &chat_color_query(&user_id, color),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn chat_color_query<'q>(user_id: &'q str, color: &'q str) -> [(&'static str, &'q str); 2] {
+    [("user_id", user_id), ("color", color)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded_query(color: &str) -> Option<String> {
+        reqwest::Url::parse_with_params(
+            "https://api.twitch.tv/helix/chat/color",
+            chat_color_query("141981764", color),
+        )
+        .unwrap()
+        .query()
+        .map(str::to_owned)
+    }
+
+    #[test]
+    fn encoding_chat_colors() {
+        assert_eq!(
+            encoded_query("blue_violet").as_deref(),
+            Some("user_id=141981764&color=blue_violet")
+        );
+        // the # of hex colors has to be escaped
+        assert_eq!(
+            encoded_query("#9146FF").as_deref(),
+            Some("user_id=141981764&color=%239146FF")
+        );
+    }
+}
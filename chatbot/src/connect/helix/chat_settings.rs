@@ -0,0 +1,133 @@
+use super::{super::types::*, HelixClient};
+use reqwest::Method;
+use serde_json::{Map, Value};
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#update-chat-settings
+    pub(super) async fn update_chat_settings(
+        &self,
+        settings: ChatSettings,
+    ) -> Result<(), HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        // the access token belongs to the broadcaster, who moderates their own channel
+        self.send(
+            Method::PATCH,
+            "chat/settings",
+            &[
+                ("broadcaster_id", &broadcaster_id),
+                ("moderator_id", &broadcaster_id),
+            ],
+            Some(chat_settings_body(settings)),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn chat_settings_body(settings: ChatSettings) -> Value {
+    let mut body = Map::new();
+    if let Some(follower_mode) = settings.follower_mode {
+        body.insert("follower_mode".to_owned(), follower_mode.into());
+        if follower_mode {
+            body.insert(
+                "follower_mode_duration".to_owned(),
+                settings.follower_minutes().into(),
+            );
+        }
+    }
+    if let Some(slow_mode) = settings.slow_mode {
+        body.insert("slow_mode".to_owned(), slow_mode.into());
+        if slow_mode {
+            body.insert(
+                "slow_mode_wait_time".to_owned(),
+                settings.slow_seconds().into(),
+            );
+        }
+    }
+    for (name, mode) in [
+        ("subscriber_mode", settings.subscriber_mode),
+        ("emote_mode", settings.emote_mode),
+        ("unique_chat_mode", settings.unique_chat_mode),
+    ] {
+        if let Some(mode) = mode {
+            body.insert(name.to_owned(), mode.into());
+        }
+    }
+    Value::Object(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn building_chat_settings_bodies() {
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                follower_mode: Some(true),
+                slow_mode: Some(true),
+                ..ChatSettings::default()
+            }),
+            json!({
+                "follower_mode": true,
+                "follower_mode_duration": 10,
+                "slow_mode": true,
+                "slow_mode_wait_time": 30,
+            })
+        );
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                slow_mode: Some(false),
+                ..ChatSettings::default()
+            }),
+            json!({ "slow_mode": false })
+        );
+        // the chat mode commands of the moderators
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                slow_mode: Some(true),
+                slow_mode_wait_time: Some(45),
+                ..ChatSettings::default()
+            }),
+            json!({ "slow_mode": true, "slow_mode_wait_time": 45 })
+        );
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                follower_mode: Some(true),
+                follower_mode_duration: Some(0),
+                ..ChatSettings::default()
+            }),
+            json!({ "follower_mode": true, "follower_mode_duration": 0 })
+        );
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                follower_mode: Some(false),
+                follower_mode_duration: Some(90),
+                ..ChatSettings::default()
+            }),
+            json!({ "follower_mode": false })
+        );
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                subscriber_mode: Some(true),
+                ..ChatSettings::default()
+            }),
+            json!({ "subscriber_mode": true })
+        );
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                emote_mode: Some(false),
+                ..ChatSettings::default()
+            }),
+            json!({ "emote_mode": false })
+        );
+        assert_eq!(
+            chat_settings_body(ChatSettings {
+                unique_chat_mode: Some(true),
+                ..ChatSettings::default()
+            }),
+            json!({ "unique_chat_mode": true })
+        );
+    }
+}
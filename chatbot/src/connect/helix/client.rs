@@ -0,0 +1,177 @@
+use super::{
+    super::{
+        connector::AccessTokenDispenser,
+        error::ConnectorError,
+        proxy::http_client,
+        types::{HelixError, HelixMetrics, HelixRequest, HelixResponse},
+    },
+    transport::{HelixTransport, TokenSource},
+};
+use crate::app_config::AppConfig;
+use reqwest::Method;
+use serde_json::Value;
+
+const HELIX_URL: &str = "https://api.twitch.tv/helix";
+
+// the dispenser is only locked while it gets or refreshes the token
+impl TokenSource for tokio::sync::Mutex<AccessTokenDispenser> {
+    async fn token(&self) -> Result<String, HelixError> {
+        self.lock()
+            .await
+            .get()
+            .await
+            .map(str::to_owned)
+            .map_err(|err| HelixError::Unavailable(err.to_string()))
+    }
+
+    async fn refreshed_token(&self) -> Result<String, HelixError> {
+        self.lock()
+            .await
+            .refresh()
+            .await
+            .map(str::to_owned)
+            .map_err(|err| HelixError::Unavailable(err.to_string()))
+    }
+}
+
+/// Client for the Twitch Helix API acting on behalf of the configured channel.
+/// It is shared by the requests running at once.
+pub struct HelixClient {
+    access_token_dispenser: tokio::sync::Mutex<AccessTokenDispenser>,
+    transport: HelixTransport,
+    broadcaster_id: String,
+}
+
+impl HelixClient {
+    pub async fn new(app_config: &AppConfig) -> Result<HelixClient, ConnectorError> {
+        let mut helix_client = Self {
+            access_token_dispenser: tokio::sync::Mutex::new(
+                AccessTokenDispenser::new(app_config).await?,
+            ),
+            transport: HelixTransport::new(
+                http_client(app_config.proxy())?,
+                HELIX_URL,
+                app_config.twitch_client_id(),
+            ),
+            broadcaster_id: String::new(),
+        };
+        let users = helix_client
+            .send(
+                Method::GET,
+                "users",
+                &[("login", app_config.channel_name())],
+                None,
+            )
+            .await
+            .map_err(|err| {
+                ConnectorError::ExternalServerError(format!(
+                    "Could not get user id of channel: {:?}",
+                    err
+                ))
+            })?;
+        helix_client.broadcaster_id = users["data"][0]["id"]
+            .as_str()
+            .ok_or_else(|| {
+                ConnectorError::ExternalServerError("Channel user not found".to_owned())
+            })?
+            .to_owned();
+        Ok(helix_client)
+    }
+
+    pub(super) fn broadcaster_id(&self) -> &str {
+        self.broadcaster_id.as_ref()
+    }
+
+    /// The numbers of requests sent since the bot started, reported by `!botstatus`.
+    pub fn metrics(&self) -> HelixMetrics {
+        self.transport.metrics()
+    }
+
+    /// The scopes granted to the access token, checked against the enabled features at startup.
+    pub async fn scopes(&self) -> Result<Vec<String>, HelixError> {
+        self.access_token_dispenser
+            .lock()
+            .await
+            .scopes()
+            .await
+            .map_err(|err| HelixError::Unavailable(err.to_string()))
+    }
+
+    pub(super) async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> Result<Value, HelixError> {
+        self.transport
+            .send(&self.access_token_dispenser, method, path, query, body)
+            .await
+    }
+
+    pub async fn execute(&self, request: HelixRequest) -> HelixResponse {
+        match request {
+            HelixRequest::CreatePrediction {
+                title,
+                outcomes,
+                window,
+            } => HelixResponse::Prediction(self.create_prediction(&title, &outcomes, window).await),
+            HelixRequest::LockPrediction { id } => {
+                HelixResponse::Prediction(self.end_prediction(&id, "LOCKED", None).await)
+            }
+            HelixRequest::ResolvePrediction {
+                id,
+                winning_outcome_id,
+            } => HelixResponse::Prediction(
+                self.end_prediction(&id, "RESOLVED", Some(&winning_outcome_id))
+                    .await,
+            ),
+            HelixRequest::FollowerTotal => {
+                HelixResponse::FollowerTotal(self.follower_total().await)
+            }
+            HelixRequest::SubPointTotal => {
+                HelixResponse::SubPointTotal(self.sub_point_total().await)
+            }
+            HelixRequest::FollowerCount => {
+                HelixResponse::FollowerCount(self.follower_total().await)
+            }
+            HelixRequest::Subscriptions => HelixResponse::Subscriptions(self.subscriptions().await),
+            HelixRequest::UserAge { login } => {
+                let result = self.user_age(&login).await;
+                HelixResponse::UserAge { login, result }
+            }
+            HelixRequest::StreamInfo => HelixResponse::StreamInfo(self.stream_info().await),
+            HelixRequest::Shoutout { login } => {
+                let result = self.shoutout(&login).await;
+                HelixResponse::Shoutout { login, result }
+            }
+            HelixRequest::Whisper { login, text } => {
+                let result = self.whisper(&login, &text).await;
+                HelixResponse::Whisper {
+                    login,
+                    text,
+                    result,
+                }
+            }
+            HelixRequest::StartRaid { login } => {
+                let result = self.start_raid(&login).await;
+                HelixResponse::Raid { login, result }
+            }
+            HelixRequest::CancelRaid => HelixResponse::RaidCanceled(self.cancel_raid().await),
+            HelixRequest::Emotes => HelixResponse::Emotes(self.emotes().await),
+            HelixRequest::LatestVod => HelixResponse::LatestVod(self.latest_vod().await),
+            HelixRequest::Clip { window } => HelixResponse::Clip {
+                window,
+                result: self.clip(window).await,
+            },
+            HelixRequest::UpdateChatSettings(settings) => {
+                HelixResponse::ChatSettings(self.update_chat_settings(settings).await)
+            }
+            HelixRequest::TokenScopes => HelixResponse::TokenScopes(self.scopes().await),
+            HelixRequest::UpdateChatColor { color } => {
+                let result = self.update_chat_color(&color).await;
+                HelixResponse::ChatColor { color, result }
+            }
+        }
+    }
+}
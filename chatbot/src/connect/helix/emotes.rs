@@ -0,0 +1,102 @@
+use super::{super::types::*, HelixClient};
+use reqwest::Method;
+use serde_json::Value;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#get-channel-emotes
+    // https://dev.twitch.tv/docs/api/reference#get-global-emotes
+    pub(super) async fn emotes(&self) -> Result<Emotes, HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let channel = self
+            .send(
+                Method::GET,
+                "chat/emotes",
+                &[("broadcaster_id", &broadcaster_id)],
+                None,
+            )
+            .await?;
+        let global = self
+            .send(Method::GET, "chat/emotes/global", &[], None)
+            .await?;
+        Ok(Emotes {
+            channel: parse_channel_emotes(&channel)?,
+            global: parse_global_emotes(&global)?,
+        })
+    }
+}
+
+fn parse_channel_emotes(response: &Value) -> Result<Vec<ChannelEmote>, HelixError> {
+    let invalid_response = || HelixError::Unavailable("Invalid emote in response".to_owned());
+    response["data"]
+        .as_array()
+        .ok_or_else(invalid_response)?
+        .iter()
+        .map(|emote| {
+            Ok(ChannelEmote {
+                code: emote["name"]
+                    .as_str()
+                    .ok_or_else(invalid_response)?
+                    .to_owned(),
+                emote_type: emote["emote_type"].as_str().unwrap_or_default().to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn parse_global_emotes(response: &Value) -> Result<Vec<String>, HelixError> {
+    let invalid_response = || HelixError::Unavailable("Invalid emote in response".to_owned());
+    response["data"]
+        .as_array()
+        .ok_or_else(invalid_response)?
+        .iter()
+        .map(|emote| {
+            Ok(emote["name"]
+                .as_str()
+                .ok_or_else(invalid_response)?
+                .to_owned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_channel_emotes_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"id":"304456832","name":"twitchdevPitchfork","images":{"url_1x":"https://static-cdn.jtvnw.net/emoticons/v2/304456832/static/light/1.0"},"tier":"1000","emote_type":"subscriptions","emote_set_id":"301590448","format":["static"],"scale":["1.0","2.0","3.0"],"theme_mode":["light","dark"]},{"id":"emotesv2_4c3b4ed516de493bbcd2df2f5d450f49","name":"twitchdevHyperPitchfork","images":{},"tier":"","emote_type":"follower","emote_set_id":"0","format":["static","animated"]}],"template":"https://static-cdn.jtvnw.net/emoticons/v2/{{id}}/{{format}}/{{theme_mode}}/{{scale}}"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_channel_emotes(&response),
+            Ok(vec![
+                ChannelEmote {
+                    code: "twitchdevPitchfork".to_owned(),
+                    emote_type: "subscriptions".to_owned(),
+                },
+                ChannelEmote {
+                    code: "twitchdevHyperPitchfork".to_owned(),
+                    emote_type: "follower".to_owned(),
+                },
+            ])
+        );
+        assert!(parse_channel_emotes(&serde_json::from_str(r#"{"error":"x"}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parsing_global_emotes_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"id":"196892","name":"TwitchUnity","images":{},"format":["static"]},{"id":"25","name":"Kappa","images":{},"format":["static"]}],"template":""}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_global_emotes(&response),
+            Ok(vec!["TwitchUnity".to_owned(), "Kappa".to_owned()])
+        );
+        assert_eq!(
+            parse_global_emotes(&serde_json::from_str(r#"{"data":[]}"#).unwrap()),
+            Ok(Vec::new())
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use super::{super::types::*, HelixClient};
+use reqwest::Method;
+use serde_json::Value;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#get-channel-followers
+    // without the moderator:read:followers scope only the total is returned, which is all we need
+    pub(super) async fn follower_total(&self) -> Result<u64, HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let response = self
+            .send(
+                Method::GET,
+                "channels/followers",
+                &[("broadcaster_id", &broadcaster_id), ("first", "1")],
+                None,
+            )
+            .await?;
+        parse_total(&response, "total")
+    }
+
+    pub(super) async fn sub_point_total(&self) -> Result<u64, HelixError> {
+        Ok(self.subscriptions().await?.points)
+    }
+
+    // https://dev.twitch.tv/docs/api/reference#get-broadcaster-subscriptions
+    pub(super) async fn subscriptions(&self) -> Result<Subscriptions, HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let response = self
+            .send(
+                Method::GET,
+                "subscriptions",
+                &[("broadcaster_id", &broadcaster_id), ("first", "1")],
+                None,
+            )
+            .await?;
+        Ok(Subscriptions {
+            total: parse_total(&response, "total")?,
+            points: parse_total(&response, "points")?,
+        })
+    }
+}
+
+fn parse_total(response: &Value, field: &str) -> Result<u64, HelixError> {
+    response[field]
+        .as_u64()
+        .ok_or_else(|| HelixError::Unavailable(format!("Missing {} in response", field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_follower_response() {
+        let response = serde_json::from_str(
+            r#"{"total":1234,"data":[{"user_id":"11111","user_name":"UserDisplayName","user_login":"userloginname","followed_at":"2022-05-24T22:22:08Z"}],"pagination":{"cursor":"eyJiIjpudWxsLCJhIjp7Ik9mZnNldCI6NX19"}}"#,
+        )
+        .unwrap();
+        assert_eq!(parse_total(&response, "total"), Ok(1234));
+    }
+
+    #[test]
+    fn parsing_subscription_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"broadcaster_id":"141981764","broadcaster_login":"twitchdev","broadcaster_name":"TwitchDev","gifter_id":"","gifter_login":"","gifter_name":"","is_gift":false,"tier":"1000","plan_name":"Channel Subscription (twitchdev)","user_id":"527115020","user_name":"twitchgaming","user_login":"twitchgaming"}],"pagination":{"cursor":"xxxx"},"total":13,"points":13}"#,
+        )
+        .unwrap();
+        assert_eq!(parse_total(&response, "points"), Ok(13));
+        assert_eq!(parse_total(&response, "total"), Ok(13));
+        assert!(parse_total(&serde_json::from_str(r#"{"data":[]}"#).unwrap(), "points").is_err());
+    }
+}
@@ -0,0 +1,15 @@
+mod chat_color;
+mod chat_settings;
+mod client;
+mod emotes;
+mod goal;
+mod prediction;
+mod raid;
+mod shoutout;
+mod stream;
+mod transport;
+mod user;
+mod videos;
+mod whisper;
+
+pub use client::HelixClient;
@@ -0,0 +1,104 @@
+use super::{super::types::*, HelixClient};
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+// https://dev.twitch.tv/docs/api/reference#create-prediction
+impl HelixClient {
+    pub(super) async fn create_prediction(
+        &self,
+        title: &str,
+        outcomes: &[String],
+        window: Duration,
+    ) -> Result<Prediction, HelixError> {
+        let body = json!({
+            "broadcaster_id": self.broadcaster_id(),
+            "title": title,
+            "outcomes": outcomes
+                .iter()
+                .map(|outcome| json!({ "title": outcome }))
+                .collect::<Vec<Value>>(),
+            "prediction_window": window.as_secs(),
+        });
+        let response = self
+            .send(Method::POST, "predictions", &[], Some(body))
+            .await?;
+        parse_prediction(&response)
+    }
+
+    // https://dev.twitch.tv/docs/api/reference#end-prediction
+    pub(super) async fn end_prediction(
+        &self,
+        id: &str,
+        status: &str,
+        winning_outcome_id: Option<&str>,
+    ) -> Result<Prediction, HelixError> {
+        let mut body = json!({
+            "broadcaster_id": self.broadcaster_id(),
+            "id": id,
+            "status": status,
+        });
+        if let Some(winning_outcome_id) = winning_outcome_id {
+            body["winning_outcome_id"] = json!(winning_outcome_id);
+        }
+        let response = self
+            .send(Method::PATCH, "predictions", &[], Some(body))
+            .await?;
+        parse_prediction(&response)
+    }
+}
+
+fn parse_prediction(response: &Value) -> Result<Prediction, HelixError> {
+    let prediction = &response["data"][0];
+    let invalid_response = || HelixError::Unavailable("Invalid prediction in response".to_owned());
+    let status = match prediction["status"].as_str() {
+        Some("ACTIVE") => PredictionStatus::Active,
+        Some("LOCKED") => PredictionStatus::Locked,
+        Some("RESOLVED") => PredictionStatus::Resolved,
+        Some("CANCELED") => PredictionStatus::Canceled,
+        _ => return Err(invalid_response()),
+    };
+    Ok(Prediction {
+        id: prediction["id"]
+            .as_str()
+            .ok_or_else(invalid_response)?
+            .to_owned(),
+        status,
+        outcome_ids: prediction["outcomes"]
+            .as_array()
+            .ok_or_else(invalid_response)?
+            .iter()
+            .filter_map(|outcome| outcome["id"].as_str().map(String::from))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_prediction_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"id":"d6676d5c-c86e-44d2-bfc4-100fb48f0656","broadcaster_id":"55696719","title":"Will there be any leaks today?","winning_outcome_id":null,"outcomes":[{"id":"021e9234-5893-49b4-982e-cfe9a0aaddd9","title":"Yes","users":0,"channel_points":0,"top_predictors":null,"color":"BLUE"},{"id":"ded84c26-13cb-4b48-8cb5-5bae3ec3a66e","title":"No","users":0,"channel_points":0,"top_predictors":null,"color":"PINK"}],"prediction_window":600,"status":"ACTIVE","created_at":"2021-04-28T16:03:06.320848689Z","ended_at":null,"locked_at":null}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_prediction(&response),
+            Ok(Prediction {
+                id: "d6676d5c-c86e-44d2-bfc4-100fb48f0656".to_owned(),
+                status: PredictionStatus::Active,
+                outcome_ids: vec![
+                    "021e9234-5893-49b4-982e-cfe9a0aaddd9".to_owned(),
+                    "ded84c26-13cb-4b48-8cb5-5bae3ec3a66e".to_owned(),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_prediction_response() {
+        let response = serde_json::from_str(r#"{"data":[]}"#).unwrap();
+        assert!(parse_prediction(&response).is_err());
+    }
+}
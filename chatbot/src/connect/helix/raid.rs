@@ -0,0 +1,37 @@
+use super::{super::types::*, user::parse_user, HelixClient};
+use reqwest::Method;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#start-a-raid
+    pub(super) async fn start_raid(&self, login: &str) -> Result<(), HelixError> {
+        let users = self
+            .send(Method::GET, "users", &[("login", login)], None)
+            .await?;
+        let (user_id, _) = parse_user(&users)?;
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        self.send(
+            Method::POST,
+            "raids",
+            &[
+                ("from_broadcaster_id", &broadcaster_id),
+                ("to_broadcaster_id", &user_id),
+            ],
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // https://dev.twitch.tv/docs/api/reference#cancel-a-raid
+    pub(super) async fn cancel_raid(&self) -> Result<(), HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        self.send(
+            Method::DELETE,
+            "raids",
+            &[("broadcaster_id", &broadcaster_id)],
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use super::{super::types::*, user::parse_user, HelixClient};
+use reqwest::Method;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#send-a-shoutout
+    pub(super) async fn shoutout(&self, login: &str) -> Result<(), HelixError> {
+        let users = self
+            .send(Method::GET, "users", &[("login", login)], None)
+            .await?;
+        let (user_id, _) = parse_user(&users)?;
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        // the access token belongs to the broadcaster, who moderates their own channel
+        self.send(
+            Method::POST,
+            "chat/shoutouts",
+            &[
+                ("from_broadcaster_id", &broadcaster_id),
+                ("to_broadcaster_id", &user_id),
+                ("moderator_id", &broadcaster_id),
+            ],
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+}
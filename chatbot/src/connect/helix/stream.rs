@@ -0,0 +1,119 @@
+use super::{super::types::*, HelixClient};
+use reqwest::Method;
+use serde_json::Value;
+
+const THUMBNAIL_SIZE: (&str, &str) = ("1280", "720");
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#get-streams
+    // https://dev.twitch.tv/docs/api/reference#get-channel-information
+    // the streams only contain the channel while it is live, otherwise the channel information is used
+    pub(super) async fn stream_info(&self) -> Result<StreamInfo, HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let streams = self
+            .send(
+                Method::GET,
+                "streams",
+                &[("user_id", &broadcaster_id)],
+                None,
+            )
+            .await?;
+        if let Some(stream_info) = parse_stream(&streams) {
+            return Ok(stream_info);
+        }
+        let channels = self
+            .send(
+                Method::GET,
+                "channels",
+                &[("broadcaster_id", &broadcaster_id)],
+                None,
+            )
+            .await?;
+        parse_channel(&channels)
+    }
+}
+
+fn parse_stream(response: &Value) -> Option<StreamInfo> {
+    let stream = &response["data"][0];
+    if stream["type"].as_str() != Some("live") {
+        return None;
+    }
+    Some(StreamInfo {
+        live: true,
+        title: stream["title"].as_str().unwrap_or_default().to_owned(),
+        game: stream["game_name"].as_str().unwrap_or_default().to_owned(),
+        game_id: stream["game_id"].as_str().unwrap_or_default().to_owned(),
+        // the url contains placeholders for the size of the thumbnail
+        thumbnail_url: stream["thumbnail_url"].as_str().map(|url| {
+            url.replace("{width}", THUMBNAIL_SIZE.0)
+                .replace("{height}", THUMBNAIL_SIZE.1)
+        }),
+        stream_id: stream["id"].as_str().map(str::to_owned),
+    })
+}
+
+fn parse_channel(response: &Value) -> Result<StreamInfo, HelixError> {
+    let channel = &response["data"][0];
+    let title = channel["title"]
+        .as_str()
+        .ok_or_else(|| HelixError::Unavailable("Invalid channel in response".to_owned()))?;
+    Ok(StreamInfo {
+        live: false,
+        title: title.to_owned(),
+        game: channel["game_name"].as_str().unwrap_or_default().to_owned(),
+        game_id: channel["game_id"].as_str().unwrap_or_default().to_owned(),
+        thumbnail_url: None,
+        stream_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_stream_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"id":"40952121085","user_id":"101051819","user_login":"afro","user_name":"Afro","game_id":"32982","game_name":"Grand Theft Auto V","type":"live","title":"Jacob: Digital Den Laptops & Tablets","viewer_count":1490,"started_at":"2021-03-10T03:18:11Z","language":"en","thumbnail_url":"https://static-cdn.jtvnw.net/previews-ttv/live_user_afro-{width}x{height}.jpg","tag_ids":[],"is_mature":false}],"pagination":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_stream(&response),
+            Some(StreamInfo {
+                live: true,
+                title: "Jacob: Digital Den Laptops & Tablets".to_owned(),
+                game: "Grand Theft Auto V".to_owned(),
+                game_id: "32982".to_owned(),
+                thumbnail_url: Some(
+                    "https://static-cdn.jtvnw.net/previews-ttv/live_user_afro-1280x720.jpg"
+                        .to_owned()
+                ),
+                stream_id: Some("40952121085".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse_stream(&serde_json::from_str(r#"{"data":[],"pagination":{}}"#).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn parsing_channel_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"broadcaster_id":"141981764","broadcaster_login":"twitchdev","broadcaster_name":"TwitchDev","broadcaster_language":"en","game_id":"509670","game_name":"Science & Technology","title":"TwitchDev Monthly Update // May 6, 2021","delay":0}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_channel(&response),
+            Ok(StreamInfo {
+                live: false,
+                title: "TwitchDev Monthly Update // May 6, 2021".to_owned(),
+                game: "Science & Technology".to_owned(),
+                game_id: "509670".to_owned(),
+                thumbnail_url: None,
+                stream_id: None,
+            })
+        );
+        assert!(parse_channel(&serde_json::from_str(r#"{"data":[]}"#).unwrap()).is_err());
+    }
+}
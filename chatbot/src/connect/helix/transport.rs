@@ -0,0 +1,330 @@
+use super::super::types::{HelixError, HelixMetrics};
+use chrono::{DateTime, Utc};
+use reqwest::{header::HeaderMap, Method};
+use serde_json::Value;
+use std::{sync::Mutex, time::Duration};
+
+// requests answered with 429 or a server error are retried this often
+const MAX_RETRIES: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+// below this many requests left in the rate limit window, the next request waits for the reset
+const RATE_LIMIT_RESERVE: u64 = 5;
+// Twitch refills the bucket continuously, so a reset should never be further away than this
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Where the Helix client gets its access tokens from, shared by the requests running at once.
+pub(super) trait TokenSource {
+    /// The current access token.
+    async fn token(&self) -> Result<String, HelixError>;
+
+    /// A new access token, after Twitch rejected the current one.
+    async fn refreshed_token(&self) -> Result<String, HelixError>;
+}
+
+/// The rate limit of the Helix api as told by the headers of the last response.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct RateLimit {
+    remaining: Option<u64>,
+    reset_at: Option<DateTime<Utc>>,
+}
+
+impl RateLimit {
+    fn update(&mut self, headers: &HeaderMap) {
+        let header = |name| headers.get(name)?.to_str().ok()?.parse::<i64>().ok();
+        self.remaining =
+            header("ratelimit-remaining").and_then(|remaining| remaining.try_into().ok());
+        self.reset_at =
+            header("ratelimit-reset").and_then(|reset| DateTime::from_timestamp(reset, 0));
+    }
+
+    /// How long the next request should wait, None if enough requests are left.
+    fn delay(&self, now: DateTime<Utc>) -> Option<Duration> {
+        match (self.remaining, self.reset_at) {
+            (Some(remaining), Some(reset_at)) if remaining < RATE_LIMIT_RESERVE => (reset_at - now)
+                .to_std()
+                .ok()
+                .map(|delay| delay.min(MAX_RATE_LIMIT_WAIT)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Retry {
+    // once with a refreshed access token
+    Refresh,
+    After(Duration),
+    No,
+}
+
+/// Whether a failed request is sent again, `jitter` is a random number in `0.0..1.0`.
+fn retry(status: u16, retries: u32, refreshed: bool, jitter: f64) -> Retry {
+    match status {
+        401 if !refreshed => Retry::Refresh,
+        429 | 500..=599 if retries < MAX_RETRIES => Retry::After(backoff(retries, jitter)),
+        _ => Retry::No,
+    }
+}
+
+/// Doubles the wait with every retry up to the cap, up to half of it is left out at random
+/// so that several waiting requests do not hit the api at once.
+fn backoff(retries: u32, jitter: f64) -> Duration {
+    let backoff = INITIAL_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(retries))
+        .min(MAX_BACKOFF);
+    backoff.mul_f64(1.0 - jitter / 2.0)
+}
+
+// Helix error responses look like {"error":"Bad Request","status":400,"message":"..."}
+fn error_from_response(status: u16, json: &Value) -> HelixError {
+    HelixError::Status(
+        status,
+        json["message"].as_str().unwrap_or_default().to_owned(),
+    )
+}
+
+/// Sends the requests of the Helix client with its client id and an access token,
+/// keeping to the rate limit and retrying requests which failed for the time being.
+/// Several requests may be sent at once, a waiting one does not hold up the others.
+pub(super) struct HelixTransport {
+    client: reqwest::Client,
+    base_url: String,
+    client_id: String,
+    // only locked to read or update them, never while waiting
+    rate_limit: Mutex<RateLimit>,
+    metrics: Mutex<HelixMetrics>,
+}
+
+impl HelixTransport {
+    pub fn new(client: reqwest::Client, base_url: &str, client_id: &str) -> Self {
+        Self {
+            client,
+            base_url: base_url.to_owned(),
+            client_id: client_id.to_owned(),
+            rate_limit: Mutex::new(RateLimit::default()),
+            metrics: Mutex::new(HelixMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> HelixMetrics {
+        *self.metrics.lock().unwrap()
+    }
+
+    fn count(&self, count: impl FnOnce(&mut HelixMetrics)) {
+        count(&mut self.metrics.lock().unwrap());
+    }
+
+    // the lock is released before the caller waits
+    fn rate_limit_delay(&self) -> Option<Duration> {
+        self.rate_limit.lock().unwrap().delay(Utc::now())
+    }
+
+    pub async fn send(
+        &self,
+        tokens: &impl TokenSource,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> Result<Value, HelixError> {
+        let mut access_token = tokens.token().await?;
+        let mut refreshed = false;
+        let mut retries = 0;
+        loop {
+            if let Some(delay) = self.rate_limit_delay() {
+                println!("Helix rate limit almost used up, waiting {:?}", delay);
+                self.count(|metrics| metrics.throttled += 1);
+                tokio::time::sleep(delay).await;
+            }
+            self.count(|metrics| metrics.requests += 1);
+            let mut request = self
+                .client
+                .request(method.clone(), format!("{}/{}", self.base_url, path))
+                .bearer_auth(&access_token)
+                .header("Client-Id", &self.client_id)
+                .query(query);
+            if let Some(body) = &body {
+                request = request.json(body);
+            }
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    self.count(|metrics| metrics.failed += 1);
+                    return Err(HelixError::Unavailable(err.to_string()));
+                }
+            };
+            self.rate_limit.lock().unwrap().update(response.headers());
+            let status = response.status();
+            let text = response
+                .text()
+                .await
+                .map_err(|err| HelixError::Unavailable(err.to_string()))?;
+            let json: Value = serde_json::from_str(&text).unwrap_or_default();
+            if status.is_success() {
+                return Ok(json);
+            }
+            match retry(status.as_u16(), retries, refreshed, rand::random()) {
+                Retry::Refresh => {
+                    self.count(|metrics| metrics.refreshes += 1);
+                    access_token = tokens.refreshed_token().await?;
+                    refreshed = true;
+                }
+                Retry::After(backoff) => {
+                    self.count(|metrics| metrics.retries += 1);
+                    retries += 1;
+                    // a rate limited request waits at least until the rate limit is reset
+                    let wait = match self.rate_limit_delay() {
+                        Some(delay) => delay.max(backoff),
+                        None => backoff,
+                    };
+                    println!(
+                        "Helix answered {} to {}, retrying in {:?}",
+                        status, path, wait
+                    );
+                    tokio::time::sleep(wait).await;
+                }
+                Retry::No => {
+                    self.count(|metrics| metrics.failed += 1);
+                    return Err(error_from_response(status.as_u16(), &json));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::scripted_server::scripted_server;
+    use reqwest::header::HeaderValue;
+
+    struct TestTokens;
+
+    impl TokenSource for TestTokens {
+        async fn token(&self) -> Result<String, HelixError> {
+            Ok("old".to_owned())
+        }
+
+        async fn refreshed_token(&self) -> Result<String, HelixError> {
+            Ok("new".to_owned())
+        }
+    }
+
+    #[tokio::test]
+    async fn retrying_rate_limited_requests() {
+        let (url, requests) =
+            scripted_server(&[(429, r#"{"message":"slow down"}"#), (200, r#"{"data":[]}"#)]).await;
+        let transport = HelixTransport::new(reqwest::Client::new(), &url, "client");
+        let result = transport
+            .send(
+                &TestTokens,
+                Method::GET,
+                "users",
+                &[("login", "carkhy")],
+                None,
+            )
+            .await;
+        assert_eq!(result, Ok(serde_json::json!({ "data": [] })));
+        let requests = requests.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("get /users?login=carkhy"));
+        assert!(requests[1].contains("client-id: client"));
+        let metrics = transport.metrics();
+        assert_eq!(
+            (metrics.requests, metrics.retries, metrics.failed),
+            (2, 1, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn refreshing_a_rejected_token_once() {
+        let (url, requests) = scripted_server(&[(401, "{}"), (200, "{}")]).await;
+        let transport = HelixTransport::new(reqwest::Client::new(), &url, "client");
+        let result = transport
+            .send(&TestTokens, Method::GET, "users", &[], None)
+            .await;
+        assert!(result.is_ok());
+        let requests = requests.await.unwrap();
+        assert!(requests[0].contains("authorization: bearer old"));
+        assert!(requests[1].contains("authorization: bearer new"));
+
+        let (url, _) = scripted_server(&[(401, "{}"), (401, r#"{"message":"invalid"}"#)]).await;
+        let transport = HelixTransport::new(reqwest::Client::new(), &url, "client");
+        let result = transport
+            .send(&TestTokens, Method::GET, "users", &[], None)
+            .await;
+        assert_eq!(result, Err(HelixError::Status(401, "invalid".to_owned())));
+        let metrics = transport.metrics();
+        assert_eq!((metrics.refreshes, metrics.failed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn sending_while_another_request_backs_off() {
+        let (url, _) = scripted_server(&[
+            (429, "{}"),
+            (200, r#"{"request":"second"}"#),
+            (200, r#"{"request":"first"}"#),
+        ])
+        .await;
+        let transport = HelixTransport::new(reqwest::Client::new(), &url, "client");
+        let first = transport.send(&TestTokens, Method::GET, "users", &[], None);
+        let second = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            transport
+                .send(&TestTokens, Method::GET, "users", &[], None)
+                .await
+        };
+        let (first, second) = tokio::join!(first, second);
+        assert_eq!(first, Ok(serde_json::json!({ "request": "first" })));
+        assert_eq!(second, Ok(serde_json::json!({ "request": "second" })));
+    }
+
+    #[test]
+    fn backing_off_with_jitter_up_to_the_cap() {
+        assert_eq!(retry(429, 0, false, 0.0), Retry::After(INITIAL_BACKOFF));
+        assert_eq!(
+            retry(503, 2, false, 0.5),
+            Retry::After(Duration::from_millis(1500))
+        );
+        assert_eq!(retry(500, MAX_RETRIES, false, 0.0), Retry::No);
+        assert_eq!(retry(401, 0, false, 0.0), Retry::Refresh);
+        assert_eq!(retry(401, 0, true, 0.0), Retry::No);
+        assert_eq!(retry(400, 0, false, 0.0), Retry::No);
+        assert_eq!(backoff(10, 0.0), MAX_BACKOFF);
+        assert!(backoff(30, 0.999) >= MAX_BACKOFF / 2);
+    }
+
+    #[test]
+    fn waiting_for_the_rate_limit_reset() {
+        let now = Utc::now();
+        let mut headers = HeaderMap::new();
+        headers.insert("Ratelimit-Remaining", HeaderValue::from_static("2"));
+        let reset = (now.timestamp() + 10).to_string();
+        headers.insert("Ratelimit-Reset", HeaderValue::from_str(&reset).unwrap());
+        let mut rate_limit = RateLimit::default();
+        assert_eq!(rate_limit.delay(now), None);
+        rate_limit.update(&headers);
+        let delay = rate_limit.delay(now).unwrap();
+        assert!(delay > Duration::from_secs(9) && delay <= Duration::from_secs(10));
+        headers.insert("Ratelimit-Remaining", HeaderValue::from_static("700"));
+        rate_limit.update(&headers);
+        assert_eq!(rate_limit.delay(now), None);
+    }
+
+    #[test]
+    fn reading_error_responses() {
+        let json = serde_json::from_str(
+            r#"{"error":"Forbidden","status":403,"message":"channel is not eligible"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            error_from_response(403, &json),
+            HelixError::Status(403, "channel is not eligible".to_owned())
+        );
+        assert_eq!(
+            error_from_response(500, &Value::Null),
+            HelixError::Status(500, String::new())
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use super::{super::types::*, HelixClient};
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde_json::Value;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#get-users
+    // https://dev.twitch.tv/docs/api/reference#get-channel-followers
+    pub(super) async fn user_age(&self, login: &str) -> Result<UserAge, HelixError> {
+        let users = self
+            .send(Method::GET, "users", &[("login", login)], None)
+            .await?;
+        let (user_id, created_at) = parse_user(&users)?;
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let followers = self
+            .send(
+                Method::GET,
+                "channels/followers",
+                &[("broadcaster_id", &broadcaster_id), ("user_id", &user_id)],
+                None,
+            )
+            .await?;
+        Ok(UserAge {
+            created_at,
+            followed_at: parse_followed_at(&followers),
+        })
+    }
+}
+
+fn parse_date(date: &Value) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(date.as_str()?)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+pub(super) fn parse_user(response: &Value) -> Result<(String, DateTime<Utc>), HelixError> {
+    let user = &response["data"][0];
+    let invalid_response = || HelixError::Unavailable("Invalid user in response".to_owned());
+    Ok((
+        user["id"].as_str().ok_or_else(invalid_response)?.to_owned(),
+        parse_date(&user["created_at"]).ok_or_else(invalid_response)?,
+    ))
+}
+
+// the followers of the channel filtered by the user, empty if the user does not follow
+fn parse_followed_at(response: &Value) -> Option<DateTime<Utc>> {
+    parse_date(&response["data"][0]["followed_at"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parsing_user_response() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"id":"141981764","login":"twitchdev","display_name":"TwitchDev","type":"","broadcaster_type":"partner","description":"Supporting third-party developers building Twitch integrations from chatbots to game integrations.","profile_image_url":"https://static-cdn.jtvnw.net/jtv_user_pictures/8a6381c7-d0c0-4576-b179-38bd5ce1d6af-profile_image-300x300.png","offline_image_url":"https://static-cdn.jtvnw.net/jtv_user_pictures/3f13ab61-ec78-4fe6-8481-8682cb3b0ac2-channel_offline_image-1920x1080.png","view_count":5980557,"created_at":"2016-12-14T20:32:28Z"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_user(&response),
+            Ok((
+                "141981764".to_owned(),
+                Utc.with_ymd_and_hms(2016, 12, 14, 20, 32, 28).unwrap()
+            ))
+        );
+        assert!(parse_user(&serde_json::from_str(r#"{"data":[]}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parsing_follower_response() {
+        let response = serde_json::from_str(
+            r#"{"total":8,"data":[{"user_id":"11111","user_name":"UserDisplayName","user_login":"userloginname","followed_at":"2022-05-24T22:22:08Z"}],"pagination":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_followed_at(&response),
+            Some(Utc.with_ymd_and_hms(2022, 5, 24, 22, 22, 8).unwrap())
+        );
+        let not_following =
+            serde_json::from_str(r#"{"total":8,"data":[],"pagination":{}}"#).unwrap();
+        assert_eq!(parse_followed_at(&not_following), None);
+    }
+}
@@ -0,0 +1,191 @@
+use super::{super::types::*, HelixClient};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Method;
+use serde_json::Value;
+use std::time::Duration;
+
+// twitch returns the most viewed clips first, the latest one is looked for among this many
+const CLIPS_PAGE_SIZE: &str = "100";
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#get-videos
+    pub(super) async fn latest_vod(&self) -> Result<Option<Video>, HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let videos = self
+            .send(
+                Method::GET,
+                "videos",
+                &[
+                    ("user_id", &broadcaster_id),
+                    ("type", "archive"),
+                    ("first", "1"),
+                ],
+                None,
+            )
+            .await?;
+        parse_latest_video(&videos)
+    }
+
+    // https://dev.twitch.tv/docs/api/reference#get-clips
+    pub(super) async fn clip(&self, window: ClipWindow) -> Result<Option<Clip>, HelixError> {
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        let now = Utc::now();
+        let timestamp = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let (started_at, ended_at) = match window.since(now) {
+            // twitch ends the window a week after its start unless told otherwise
+            Some(since) => (timestamp(since), timestamp(now)),
+            None => (String::new(), String::new()),
+        };
+        let mut query = vec![
+            ("broadcaster_id", broadcaster_id.as_str()),
+            ("first", CLIPS_PAGE_SIZE),
+        ];
+        if !started_at.is_empty() {
+            query.push(("started_at", &started_at));
+            query.push(("ended_at", &ended_at));
+        }
+        let clips = self.send(Method::GET, "clips", &query, None).await?;
+        Ok(select_clip(window, parse_clips(&clips)?))
+    }
+}
+
+fn invalid_response(kind: &str) -> HelixError {
+    HelixError::Unavailable(format!("Invalid {} in response", kind))
+}
+
+fn parse_latest_video(response: &Value) -> Result<Option<Video>, HelixError> {
+    let videos = response["data"]
+        .as_array()
+        .ok_or_else(|| invalid_response("videos"))?;
+    videos
+        .first()
+        .map(|video| {
+            Ok(Video {
+                title: video["title"].as_str().unwrap_or_default().to_owned(),
+                duration: video["duration"]
+                    .as_str()
+                    .and_then(parse_video_duration)
+                    .ok_or_else(|| invalid_response("video duration"))?,
+                url: video["url"]
+                    .as_str()
+                    .ok_or_else(|| invalid_response("video"))?
+                    .to_owned(),
+            })
+        })
+        .transpose()
+}
+
+/// Parses the durations of videos, e.g. `3h8m33s`.
+fn parse_video_duration(duration: &str) -> Option<Duration> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    for char in duration.chars() {
+        if char.is_ascii_digit() {
+            number.push(char);
+            continue;
+        }
+        let unit = match char {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        seconds += number.parse::<u64>().ok()? * unit;
+        number.clear();
+    }
+    number.is_empty().then_some(Duration::from_secs(seconds))
+}
+
+fn parse_clips(response: &Value) -> Result<Vec<Clip>, HelixError> {
+    response["data"]
+        .as_array()
+        .ok_or_else(|| invalid_response("clips"))?
+        .iter()
+        .map(|clip| {
+            Ok(Clip {
+                title: clip["title"].as_str().unwrap_or_default().to_owned(),
+                creator: clip["creator_name"].as_str().unwrap_or_default().to_owned(),
+                views: clip["view_count"].as_u64().unwrap_or_default(),
+                created_at: clip["created_at"]
+                    .as_str()
+                    .and_then(|created_at| DateTime::parse_from_rfc3339(created_at).ok())
+                    .ok_or_else(|| invalid_response("clip"))?
+                    .with_timezone(&Utc),
+                url: clip["url"]
+                    .as_str()
+                    .ok_or_else(|| invalid_response("clip"))?
+                    .to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn select_clip(window: ClipWindow, clips: Vec<Clip>) -> Option<Clip> {
+    match window {
+        ClipWindow::Latest => clips.into_iter().max_by_key(|clip| clip.created_at),
+        _ => clips.into_iter().max_by_key(|clip| clip.views),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIPS: &str = r#"{"data":[{"id":"AwkwardHelplessSalamanderSwiftRage","url":"https://clips.twitch.tv/AwkwardHelplessSalamanderSwiftRage","embed_url":"https://clips.twitch.tv/embed?clip=AwkwardHelplessSalamanderSwiftRage","broadcaster_id":"67955580","broadcaster_name":"ChewieMelodies","creator_id":"53834192","creator_name":"BlackNova03","video_id":"205586603","game_id":"488191","language":"en","title":"babymetal","view_count":10,"created_at":"2017-11-30T22:34:18Z","thumbnail_url":"https://clips-media-assets.twitch.tv/157589949-preview-480x272.jpg","duration":60,"vod_offset":480},{"id":"SpicyTiredClipTwitchRaid","url":"https://clips.twitch.tv/SpicyTiredClipTwitchRaid","creator_name":"Viewer","title":"the raid","view_count":3,"created_at":"2017-12-02T10:00:00Z"}],"pagination":{}}"#;
+
+    #[test]
+    fn parsing_the_latest_video() {
+        let response = serde_json::from_str(
+            r#"{"data":[{"id":"335921245","stream_id":null,"user_id":"141981764","user_login":"twitchdev","user_name":"TwitchDev","title":"Twitch Developers 101","description":"Welcome to Twitch development!","created_at":"2018-11-14T21:30:18Z","published_at":"2018-11-14T22:04:30Z","url":"https://www.twitch.tv/videos/335921245","thumbnail_url":"https://static-cdn.jtvnw.net/cf_vods/d2nvs31859zcd8/twitchdev/335921245/ce0f3a7f-57a3-4152-bc06-0c6610189fb3/thumb/index-0000000000-%{width}x%{height}.jpg","viewable":"public","view_count":1863062,"language":"en","type":"upload","duration":"3h8m33s","muted_segments":[]}],"pagination":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_latest_video(&response),
+            Ok(Some(Video {
+                title: "Twitch Developers 101".to_owned(),
+                duration: Duration::from_secs(3 * 3600 + 8 * 60 + 33),
+                url: "https://www.twitch.tv/videos/335921245".to_owned(),
+            }))
+        );
+        // the vods of the channel are turned off
+        assert_eq!(
+            parse_latest_video(&serde_json::from_str(r#"{"data":[],"pagination":{}}"#).unwrap()),
+            Ok(None)
+        );
+        assert!(parse_latest_video(&serde_json::from_str(r#"{"error":"x"}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parsing_video_durations() {
+        assert_eq!(parse_video_duration("33s"), Some(Duration::from_secs(33)));
+        assert_eq!(
+            parse_video_duration("1h0m5s"),
+            Some(Duration::from_secs(3605))
+        );
+        assert_eq!(parse_video_duration("12m"), Some(Duration::from_secs(720)));
+        assert_eq!(parse_video_duration("5"), None);
+        assert_eq!(parse_video_duration("1d"), None);
+    }
+
+    #[test]
+    fn selecting_clips() {
+        let clips = parse_clips(&serde_json::from_str(CLIPS).unwrap()).unwrap();
+        assert_eq!(clips.len(), 2);
+        let latest = select_clip(ClipWindow::Latest, clips.clone()).unwrap();
+        assert_eq!(latest.title, "the raid");
+        assert_eq!(latest.creator, "Viewer");
+        let top = select_clip(ClipWindow::Week, clips).unwrap();
+        assert_eq!(
+            top,
+            Clip {
+                title: "babymetal".to_owned(),
+                creator: "BlackNova03".to_owned(),
+                views: 10,
+                created_at: "2017-11-30T22:34:18Z".parse().unwrap(),
+                url: "https://clips.twitch.tv/AwkwardHelplessSalamanderSwiftRage".to_owned(),
+            }
+        );
+        let no_clips = parse_clips(&serde_json::from_str(r#"{"data":[]}"#).unwrap()).unwrap();
+        assert_eq!(select_clip(ClipWindow::All, no_clips), None);
+    }
+}
@@ -0,0 +1,23 @@
+use super::{super::types::*, user::parse_user, HelixClient};
+use reqwest::Method;
+use serde_json::json;
+
+impl HelixClient {
+    // https://dev.twitch.tv/docs/api/reference#send-whisper
+    pub(super) async fn whisper(&self, login: &str, text: &str) -> Result<(), HelixError> {
+        let users = self
+            .send(Method::GET, "users", &[("login", login)], None)
+            .await?;
+        let (user_id, _) = parse_user(&users)?;
+        // whispers are sent by the broadcaster, who authorized the bot
+        let broadcaster_id = self.broadcaster_id().to_owned();
+        self.send(
+            Method::POST,
+            "whispers",
+            &[("from_user_id", &broadcaster_id), ("to_user_id", &user_id)],
+            Some(json!({ "message": text })),
+        )
+        .await?;
+        Ok(())
+    }
+}
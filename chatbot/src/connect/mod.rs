@@ -1,6 +1,35 @@
+mod alerts;
+mod batches;
+mod chat_log;
 mod connector;
+mod control;
+mod discord;
 mod error;
+mod helix;
+mod proxy;
+mod scopes;
+#[cfg(test)]
+mod scripted_server;
 mod types;
 
-pub use connector::TwitchChatConnector;
-pub use types::{Badge, ChatBotEvent, Command, CommandType, TextMessage, UserInfo};
+pub use alerts::AlertServer;
+pub use batches::EventBatches;
+pub use chat_log::{ChatLogger, LogTimezone};
+pub(crate) use connector::{
+    poll_device_tokens, request_device_code, restrict_permissions, store_authorization,
+    ReceiveEvent, ID_URL,
+};
+pub use connector::{SeenMessages, TwitchChatConnector};
+pub use control::ControlServer;
+pub use discord::DiscordNotifier;
+pub use error::ConnectorError;
+pub use helix::HelixClient;
+pub use proxy::{http_client, Proxy, ProxyError};
+pub(crate) use scopes::authorization_scopes;
+pub use scopes::{enabled_features, missing_scopes, ScopedFeature};
+pub use types::{
+    Alert, Badge, BotStatus, ChannelEmote, ChatBotEvent, ChatSettings, Clip, ClipWindow, Command,
+    CommandPrefixes, CommandType, Emotes, Event, EventFilter, EventKind, HelixError, HelixMetrics,
+    HelixRequest, HelixResponse, Permission, Prediction, PredictionStatus, RoomState, StreamInfo,
+    Subscriptions, TextMessage, UserAge, UserInfo, Video, DEFAULT_COMMAND_PREFIX,
+};
@@ -0,0 +1,174 @@
+use super::ChatSettings;
+use crate::{app_config::AppConfig, core::ResponseTarget};
+
+/// A feature of the bot which needs a scope of the access token.
+#[derive(Debug)]
+pub struct ScopedFeature {
+    pub feature: &'static str,
+    pub scope: &'static str,
+    enabled: fn(&AppConfig) -> bool,
+}
+
+impl ScopedFeature {
+    /// The warning logged at startup if the access token lacks the scope of the feature.
+    pub fn warning(&self) -> String {
+        format!(
+            "Warning: the access token lacks the {} scope for {}",
+            self.scope, self.feature
+        )
+    }
+}
+
+fn always(_: &AppConfig) -> bool {
+    true
+}
+
+fn whispered_replies(app_config: &AppConfig) -> bool {
+    app_config.respond_via() == ResponseTarget::Whisper
+        || app_config
+            .respond_via_commands()
+            .values()
+            .any(|target| *target == ResponseTarget::Whisper)
+}
+
+fn protection_modes(app_config: &AppConfig) -> bool {
+    app_config
+        .raid_protection()
+        .is_some_and(|protection| protection.modes != ChatSettings::default())
+}
+
+// the bot is authorized with every scope in here, a feature using a new scope is added here
+const SCOPED_FEATURES: &[ScopedFeature] = &[
+    ScopedFeature {
+        feature: "the chat",
+        scope: "chat:read",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "the chat",
+        scope: "chat:edit",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "!prediction",
+        scope: "channel:manage:predictions",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "!subgoal",
+        scope: "channel:read:subscriptions",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "!subs",
+        scope: "channel:read:subscriptions",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "!followgoal",
+        scope: "moderator:read:followers",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "TWITCH_NATIVE_SHOUTOUT",
+        scope: "moderator:manage:shoutouts",
+        enabled: AppConfig::native_shoutout,
+    },
+    ScopedFeature {
+        feature: "!context",
+        scope: "user:manage:whispers",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "TWITCH_RESPOND_VIA",
+        scope: "user:manage:whispers",
+        enabled: whispered_replies,
+    },
+    ScopedFeature {
+        feature: "!raid",
+        scope: "channel:manage:raids",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "the chat mode commands",
+        scope: "moderator:manage:chat_settings",
+        enabled: always,
+    },
+    ScopedFeature {
+        feature: "TWITCH_RAID_PROTECTION_MODES",
+        scope: "moderator:manage:chat_settings",
+        enabled: protection_modes,
+    },
+    ScopedFeature {
+        feature: "!botcolor",
+        scope: "user:manage:chat_color",
+        enabled: always,
+    },
+];
+
+/// The scopes the bot asks for when the broadcaster authorizes it.
+pub(crate) fn authorization_scopes() -> Vec<&'static str> {
+    let mut scopes: Vec<&str> = Vec::new();
+    for feature in SCOPED_FEATURES {
+        if !scopes.contains(&feature.scope) {
+            scopes.push(feature.scope);
+        }
+    }
+    scopes
+}
+
+/// The features enabled by the config which need a scope.
+pub fn enabled_features(app_config: &AppConfig) -> Vec<&'static ScopedFeature> {
+    SCOPED_FEATURES
+        .iter()
+        .filter(|feature| (feature.enabled)(app_config))
+        .collect()
+}
+
+/// The features whose scope was not granted to the access token.
+pub fn missing_scopes(
+    features: &[&'static ScopedFeature],
+    granted: &[String],
+) -> Vec<&'static ScopedFeature> {
+    features
+        .iter()
+        .filter(|feature| !granted.iter().any(|scope| scope == feature.scope))
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warning_about_missing_scopes() {
+        let config = AppConfig::for_server("ws://localhost:6667");
+        let all_scopes: Vec<String> = authorization_scopes()
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        assert!(missing_scopes(&enabled_features(&config), &all_scopes).is_empty());
+        assert!(!enabled_features(&config)
+            .iter()
+            .any(|feature| feature.scope == "moderator:manage:shoutouts"));
+
+        let config = config.with_native_shoutout();
+        let granted = ["chat:read".to_owned(), "chat:edit".to_owned()];
+        let warnings: Vec<String> = missing_scopes(&enabled_features(&config), &granted)
+            .iter()
+            .map(|feature| feature.warning())
+            .collect();
+        assert!(warnings.contains(
+            &"Warning: the access token lacks the moderator:manage:shoutouts scope for TWITCH_NATIVE_SHOUTOUT"
+                .to_owned()
+        ));
+        assert!(!warnings.iter().any(|warning| warning.contains("chat:read")));
+        assert_eq!(
+            authorization_scopes().join(" "),
+            "chat:read chat:edit channel:manage:predictions channel:read:subscriptions \
+             moderator:read:followers moderator:manage:shoutouts user:manage:whispers \
+             channel:manage:raids moderator:manage:chat_settings user:manage:chat_color"
+        );
+    }
+}
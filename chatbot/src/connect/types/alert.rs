@@ -0,0 +1,75 @@
+use serde_json::{json, Value};
+
+// version of the json payloads, increased when fields change incompatibly
+pub const ALERT_VERSION: u64 = 1;
+
+/// Event for stream overlays, e.g. a browser source in OBS playing a sound.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Alert {
+    Cheer {
+        user: String,
+        bits: u64,
+    },
+    Redemption {
+        user: String,
+        reward_id: String,
+        text: String,
+    },
+    // triggered with `!alert test` to check the overlay setup
+    Test {
+        user: String,
+    },
+}
+
+impl Alert {
+    /// Name of the alert type, used in the payload and to select the forwarded alerts.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Alert::Cheer { .. } => "cheer",
+            Alert::Redemption { .. } => "redemption",
+            Alert::Test { .. } => "test",
+        }
+    }
+
+    /// The payload sent to the overlays, e.g. `{"version":1,"type":"cheer","user":"x","bits":500}`
+    pub fn to_json(&self) -> Value {
+        let mut payload = match self {
+            Alert::Cheer { user, bits } => json!({ "user": user, "bits": bits }),
+            Alert::Redemption {
+                user,
+                reward_id,
+                text,
+            } => json!({ "user": user, "reward_id": reward_id, "text": text }),
+            Alert::Test { user } => json!({ "user": user }),
+        };
+        payload["version"] = json!(ALERT_VERSION);
+        payload["type"] = json!(self.kind());
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializing_alerts() {
+        let alert = Alert::Cheer {
+            user: "chatter".to_owned(),
+            bits: 500,
+        };
+        assert_eq!(
+            alert.to_json(),
+            json!({ "version": 1, "type": "cheer", "user": "chatter", "bits": 500 })
+        );
+        let alert = Alert::Redemption {
+            user: "chatter".to_owned(),
+            reward_id: "f3a1c0de".to_owned(),
+            text: "play some jazz".to_owned(),
+        };
+        assert_eq!(
+            alert.to_json(),
+            json!({ "version": 1, "type": "redemption", "user": "chatter", "reward_id": "f3a1c0de", "text": "play some jazz" })
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use super::HelixMetrics;
+use std::time::Duration;
+
+/// State of the bot process and its chat connection, reported by `!botstatus`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BotStatus {
+    pub uptime: Duration,
+    pub connected: bool,
+    // reconnects to the chat since the bot started
+    pub reconnects: u32,
+    // messages waiting to be sent to the chat
+    pub queued_messages: usize,
+    // resident memory, None where it cannot be read
+    pub memory_kb: Option<u64>,
+    // messages redelivered by the chat server which were dropped
+    pub suppressed_duplicates: u64,
+    // average round trip time to the chat server, None before the first measurement
+    pub latency: Option<Duration>,
+    // round trip time of the last measurement
+    pub last_latency: Option<Duration>,
+    // requests to the helix api, None if it is not available
+    pub helix: Option<HelixMetrics>,
+}
@@ -1,6 +1,49 @@
 use super::user_info::UserInfo;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::{self, Display},
+    str::FromStr,
+};
 
-#[derive(Debug, PartialEq, Eq)]
+/// The prefix of chat commands unless another one is configured.
+pub const DEFAULT_COMMAND_PREFIX: &str = "!";
+
+/// The prefixes of chat commands by channel, e.g. `?` in one channel and `!` in the others.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CommandPrefixes {
+    default: String,
+    // by lowercase channel name without `#`
+    channels: HashMap<String, String>,
+}
+
+impl CommandPrefixes {
+    pub fn new(default: &str, channels: HashMap<String, String>) -> Self {
+        Self {
+            default: default.to_owned(),
+            channels: channels
+                .into_iter()
+                .map(|(channel, prefix)| (channel.to_lowercase(), prefix))
+                .collect(),
+        }
+    }
+
+    /// The prefix of the commands in the channel, the default one if the channel has none
+    /// or the line is not from a channel (e.g. a whisper).
+    pub fn for_channel(&self, channel: Option<&str>) -> &str {
+        channel
+            .and_then(|channel| self.channels.get(&channel.to_lowercase()))
+            .unwrap_or(&self.default)
+    }
+}
+
+impl Default for CommandPrefixes {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMMAND_PREFIX, HashMap::new())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum CommandType {
     Help,
     Info,
@@ -11,11 +54,499 @@ pub enum CommandType {
     Dynamic(String),
     NewRepeating,
     RemoveRepeating,
+    Counter,
+    Prediction,
+    Debug,
+    FollowGoal,
+    SubGoal,
+    Ping,
+    Emote,
+    Stats,
+    Alert,
+    GoLive,
+    Roll,
+    Quiz,
+    Duel,
+    Accept,
+    Raffle,
+    Enter,
+    First,
+    Shoutout,
+    BotReset,
+    Say,
+    Shutdown,
+    RestartConn,
+    BotStatus,
+    Enable,
+    Disable,
+    Commands,
+    Timers,
+    AccountAge,
+    NameColor,
+    MyRolls,
+    Raid,
+    Unraid,
+    EmoteList,
+    RefreshEmotes,
+    Vod,
+    LastClip,
+    Deaths,
+    Config,
+    AllClear,
+    Context,
+    Define,
+    Perf,
+    Viewers,
+    Slow,
+    SlowOff,
+    FollowersOnly,
+    SubOnly,
+    EmoteOnly,
+    UniqueChat,
+    Scopes,
+    BotColor,
+    Trending,
+    RollStats,
+    Audit,
+    Followers,
+    Subs,
+}
+
+/// Who may use the side effects of a command, from lowest to highest.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Permission {
+    Everyone,
+    Moderator,
+    Broadcaster,
+    // the owners of the bot
+    Owner,
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "everyone" => Ok(Permission::Everyone),
+            "moderator" => Ok(Permission::Moderator),
+            "broadcaster" => Ok(Permission::Broadcaster),
+            "owner" => Ok(Permission::Owner),
+            _ => Err(format!(
+                "unknown permission {}, expected everyone, moderator, broadcaster or owner",
+                name
+            )),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Command {
     pub kind: CommandType,
     pub options: Vec<String>,
+    // everything after the command name as written, e.g. for a reply with its own spacing
+    pub raw_args: String,
     pub user: UserInfo,
+    // the user id of the channel, from the room-id tag
+    pub room_id: Option<u64>,
+}
+
+impl CommandType {
+    /// The commands of the bot itself, without the custom ones.
+    pub const BUILT_IN: &'static [CommandType] = &[
+        CommandType::Help,
+        CommandType::Info,
+        CommandType::NewCommand,
+        CommandType::RemoveCommand,
+        CommandType::Slap,
+        CommandType::Discord,
+        CommandType::NewRepeating,
+        CommandType::RemoveRepeating,
+        CommandType::Counter,
+        CommandType::Prediction,
+        CommandType::Debug,
+        CommandType::FollowGoal,
+        CommandType::SubGoal,
+        CommandType::Ping,
+        CommandType::Emote,
+        CommandType::Stats,
+        CommandType::Alert,
+        CommandType::GoLive,
+        CommandType::Roll,
+        CommandType::Quiz,
+        CommandType::Duel,
+        CommandType::Accept,
+        CommandType::Raffle,
+        CommandType::Enter,
+        CommandType::First,
+        CommandType::Shoutout,
+        CommandType::BotReset,
+        CommandType::Say,
+        CommandType::Shutdown,
+        CommandType::RestartConn,
+        CommandType::BotStatus,
+        CommandType::Enable,
+        CommandType::Disable,
+        CommandType::Commands,
+        CommandType::Timers,
+        CommandType::AccountAge,
+        CommandType::NameColor,
+        CommandType::MyRolls,
+        CommandType::Raid,
+        CommandType::Unraid,
+        CommandType::EmoteList,
+        CommandType::RefreshEmotes,
+        CommandType::Vod,
+        CommandType::LastClip,
+        CommandType::Deaths,
+        CommandType::Config,
+        CommandType::AllClear,
+        CommandType::Context,
+        CommandType::Define,
+        CommandType::Perf,
+        CommandType::Viewers,
+        CommandType::Slow,
+        CommandType::SlowOff,
+        CommandType::FollowersOnly,
+        CommandType::SubOnly,
+        CommandType::EmoteOnly,
+        CommandType::UniqueChat,
+        CommandType::Scopes,
+        CommandType::BotColor,
+        CommandType::Trending,
+        CommandType::RollStats,
+        CommandType::Audit,
+        CommandType::Followers,
+        CommandType::Subs,
+    ];
+
+    /// The name the command is used with in the chat, without the `!`.
+    pub fn name(&self) -> &str {
+        match self {
+            CommandType::Help => "help",
+            CommandType::Info => "info",
+            CommandType::NewCommand => "newcommand",
+            CommandType::RemoveCommand => "removecommand",
+            CommandType::Slap => "slap",
+            CommandType::Discord => "discord",
+            CommandType::Dynamic(name) => name,
+            CommandType::NewRepeating => "newrepeating",
+            CommandType::RemoveRepeating => "removerepeating",
+            CommandType::Counter => "counter",
+            CommandType::Prediction => "prediction",
+            CommandType::Debug => "debug",
+            CommandType::FollowGoal => "followgoal",
+            CommandType::SubGoal => "subgoal",
+            CommandType::Ping => "ping",
+            CommandType::Emote => "emote",
+            CommandType::Stats => "stats",
+            CommandType::Alert => "alert",
+            CommandType::GoLive => "golive",
+            CommandType::Roll => "roll",
+            CommandType::Quiz => "quiz",
+            CommandType::Duel => "duel",
+            CommandType::Accept => "accept",
+            CommandType::Raffle => "raffle",
+            CommandType::Enter => "enter",
+            CommandType::First => "first",
+            CommandType::Shoutout => "so",
+            CommandType::BotReset => "botreset",
+            CommandType::Say => "say",
+            CommandType::Shutdown => "shutdown",
+            CommandType::RestartConn => "restartconn",
+            CommandType::BotStatus => "botstatus",
+            CommandType::Enable => "enable",
+            CommandType::Disable => "disable",
+            CommandType::Commands => "commands",
+            CommandType::Timers => "timers",
+            CommandType::AccountAge => "accountage",
+            CommandType::NameColor => "namecolor",
+            CommandType::MyRolls => "myrolls",
+            CommandType::Raid => "raid",
+            CommandType::Unraid => "unraid",
+            CommandType::EmoteList => "emotes",
+            CommandType::RefreshEmotes => "refreshemotes",
+            CommandType::Vod => "vod",
+            CommandType::LastClip => "lastclip",
+            CommandType::Deaths => "deaths",
+            CommandType::Config => "config",
+            CommandType::AllClear => "allclear",
+            CommandType::Context => "context",
+            CommandType::Define => "define",
+            CommandType::Perf => "perf",
+            CommandType::Viewers => "viewers",
+            CommandType::Slow => "slow",
+            CommandType::SlowOff => "slowoff",
+            CommandType::FollowersOnly => "followersonly",
+            CommandType::SubOnly => "subonly",
+            CommandType::EmoteOnly => "emoteonly",
+            CommandType::UniqueChat => "uniquechat",
+            CommandType::Scopes => "scopes",
+            CommandType::BotColor => "botcolor",
+            CommandType::Trending => "trending",
+            CommandType::RollStats => "rollstats",
+            CommandType::Audit => "audit",
+            CommandType::Followers => "followers",
+            CommandType::Subs => "subs",
+        }
+    }
+
+    /// The permission needed for the side effects of the command, e.g. changing a counter.
+    /// Commands which only read (e.g. `!counter deaths`) may still be used by everyone.
+    pub fn permission(&self) -> Permission {
+        match self {
+            CommandType::Help
+            | CommandType::Info
+            | CommandType::Slap
+            | CommandType::Discord
+            | CommandType::Dynamic(_)
+            | CommandType::Ping
+            | CommandType::Emote
+            | CommandType::Stats
+            | CommandType::Roll
+            | CommandType::Duel
+            | CommandType::Accept
+            | CommandType::Enter
+            | CommandType::First
+            | CommandType::Commands
+            | CommandType::AccountAge
+            | CommandType::NameColor
+            | CommandType::MyRolls
+            | CommandType::RollStats
+            | CommandType::EmoteList
+            | CommandType::Vod
+            | CommandType::LastClip
+            | CommandType::Define
+            | CommandType::Followers => Permission::Everyone,
+            CommandType::NewCommand
+            | CommandType::RemoveCommand
+            | CommandType::NewRepeating
+            | CommandType::RemoveRepeating
+            | CommandType::Counter
+            | CommandType::Deaths
+            | CommandType::Prediction
+            | CommandType::FollowGoal
+            | CommandType::SubGoal
+            | CommandType::Alert
+            | CommandType::Quiz
+            | CommandType::Raffle
+            | CommandType::Shoutout
+            | CommandType::Enable
+            | CommandType::Disable
+            | CommandType::RefreshEmotes
+            | CommandType::AllClear
+            | CommandType::Context
+            | CommandType::Viewers
+            | CommandType::Slow
+            | CommandType::SlowOff
+            | CommandType::FollowersOnly
+            | CommandType::SubOnly
+            | CommandType::EmoteOnly
+            | CommandType::UniqueChat
+            | CommandType::Trending
+            | CommandType::Subs => Permission::Moderator,
+            CommandType::Debug
+            | CommandType::GoLive
+            | CommandType::BotReset
+            | CommandType::Timers
+            | CommandType::Raid
+            | CommandType::Unraid
+            | CommandType::Config
+            | CommandType::Perf
+            | CommandType::Audit => Permission::Broadcaster,
+            CommandType::Say
+            | CommandType::Shutdown
+            | CommandType::RestartConn
+            | CommandType::BotStatus
+            | CommandType::Scopes
+            | CommandType::BotColor => Permission::Owner,
+        }
+    }
+
+    /// Whether the command is restricted to the owners of the bot.
+    pub fn is_admin(&self) -> bool {
+        self.permission() == Permission::Owner
+    }
+}
+
+impl FromStr for CommandType {
+    type Err = Infallible;
+
+    /// Parses the name of a command without the `!`, names which are no built-in command
+    /// are custom commands.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if name == "shoutout" {
+            return Ok(CommandType::Shoutout);
+        }
+        Ok(Self::BUILT_IN
+            .iter()
+            .find(|kind| kind.name() == name)
+            .cloned()
+            .unwrap_or_else(|| CommandType::Dynamic(name.to_owned())))
+    }
+}
+
+impl Display for CommandType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Command {
+    /// A command with the options split at whitespace out of the text after its name.
+    pub fn new(kind: CommandType, raw_args: &str, user: UserInfo, room_id: Option<u64>) -> Self {
+        Self {
+            kind,
+            options: raw_args.split_whitespace().map(String::from).collect(),
+            raw_args: raw_args.to_owned(),
+            user,
+            room_id,
+        }
+    }
+
+    /// Returns the option at `idx` parsed as a number.
+    pub fn arg_u32(&self, idx: usize) -> Option<u32> {
+        self.options.get(idx).and_then(|option| option.parse().ok())
+    }
+
+    /// Returns the option at `idx` as user name, without a leading `@`.
+    pub fn arg_user(&self, idx: usize) -> Option<&str> {
+        self.options
+            .get(idx)
+            .map(|option| option.strip_prefix('@').unwrap_or(option))
+            .filter(|user| !user.is_empty())
+    }
+
+    /// Returns all options starting at `from` joined by spaces.
+    pub fn rest(&self, from: usize) -> String {
+        self.options.get(from..).unwrap_or_default().join(" ")
+    }
+
+    /// Returns the options with quoted options (e.g. `"Will we win?"`) combined into one.
+    pub fn quoted_options(&self) -> Vec<String> {
+        let mut quoted_options = Vec::new();
+        let mut quote: Option<Vec<&str>> = None;
+        for option in &self.options {
+            match quote.as_mut() {
+                Some(words) => words.push(option),
+                None if option.starts_with('"') => quote = Some(vec![&option[1..]]),
+                None => quoted_options.push(option.to_owned()),
+            }
+            if let Some(words) = quote.as_mut() {
+                if let Some(last_word) = words.last_mut().and_then(|word| word.strip_suffix('"')) {
+                    *words.last_mut().unwrap() = last_word;
+                    quoted_options.push(words.join(" "));
+                    quote = None;
+                }
+            }
+        }
+        // an unterminated quote covers the rest of the options
+        if let Some(words) = quote {
+            quoted_options.push(words.join(" "));
+        }
+        quoted_options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn command(options: &[&str]) -> Command {
+        Command {
+            kind: CommandType::Prediction,
+            options: options.iter().map(|option| option.to_string()).collect(),
+            raw_args: options.join(" "),
+            user: UserInfo {
+                name: "chatter".to_owned(),
+                id: None,
+                badges: HashSet::default(),
+            },
+            room_id: None,
+        }
+    }
+
+    #[test]
+    fn ordering_permissions() {
+        assert!(CommandType::Roll.permission() < CommandType::Shoutout.permission());
+        assert_eq!(CommandType::BotReset.permission(), Permission::Broadcaster);
+        assert!(CommandType::Say.is_admin());
+        assert_eq!(CommandType::Say.permission(), Permission::Owner);
+        assert_eq!("Moderator".parse(), Ok(Permission::Moderator));
+        assert!("vip".parse::<Permission>().is_err());
+    }
+
+    #[test]
+    fn getting_number_arguments() {
+        let command = command(&["60", "-1", "abc"]);
+        assert_eq!(command.arg_u32(0), Some(60));
+        assert_eq!(command.arg_u32(1), None);
+        assert_eq!(command.arg_u32(2), None);
+        assert_eq!(command.arg_u32(3), None);
+    }
+
+    #[test]
+    fn getting_user_arguments() {
+        let command = command(&["@carkhy", "chatter", "@"]);
+        assert_eq!(command.arg_user(0), Some("carkhy"));
+        assert_eq!(command.arg_user(1), Some("chatter"));
+        assert_eq!(command.arg_user(2), None);
+        assert_eq!(command.arg_user(3), None);
+    }
+
+    #[test]
+    fn parsing_and_displaying_command_names() {
+        for kind in CommandType::BUILT_IN {
+            let name = kind.to_string();
+            assert_eq!(&name.parse::<CommandType>().unwrap(), kind, "{}", name);
+        }
+        let names: HashSet<String> = CommandType::BUILT_IN
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(names.len(), CommandType::BUILT_IN.len());
+        assert_eq!(
+            "shoutout".parse::<CommandType>().unwrap(),
+            CommandType::Shoutout
+        );
+        assert_eq!(CommandType::Shoutout.to_string(), "so");
+        let custom = "clip".parse::<CommandType>().unwrap();
+        assert_eq!(custom, CommandType::Dynamic("clip".to_owned()));
+        assert_eq!(custom.to_string(), "clip");
+    }
+
+    #[test]
+    fn getting_remaining_arguments() {
+        let command = command(&["name", "Text", "to", "output"]);
+        assert_eq!(command.rest(1), "Text to output");
+        assert_eq!(command.rest(4), "");
+        assert_eq!(command.rest(5), "");
+    }
+
+    #[test]
+    fn combining_quoted_options() {
+        let command = command(&["start", "\"Will", "we", "win?\"", "Yes", "\"No\"", "120"]);
+        assert_eq!(
+            command.quoted_options(),
+            vec!["start", "Will we win?", "Yes", "No", "120"]
+        );
+    }
+
+    #[test]
+    fn resolving_command_prefixes_by_channel() {
+        let prefixes = CommandPrefixes::new(
+            "!",
+            HashMap::from([("CaptainCallback".to_owned(), "?".to_owned())]),
+        );
+        assert_eq!(prefixes.for_channel(Some("captaincallback")), "?");
+        assert_eq!(prefixes.for_channel(Some("carkhy")), "!");
+        assert_eq!(prefixes.for_channel(None), "!");
+        assert_eq!(CommandPrefixes::default().for_channel(Some("carkhy")), "!");
+    }
+
+    #[test]
+    fn combining_unterminated_quoted_options() {
+        let command = command(&["\"Will", "we", "win?"]);
+        assert_eq!(command.quoted_options(), vec!["Will we win?"]);
+    }
 }
@@ -1,15 +1,103 @@
+use chrono::{DateTime, Utc};
+use std::{collections::HashSet, time::Duration};
 use uuid::Uuid;
 
-use super::{text_message::TextMessage, Command};
+use super::{text_message::TextMessage, Badge, BotStatus, Command, HelixResponse, RoomState};
 
 #[derive(Debug, PartialEq)]
 pub enum ChatBotEvent {
     TextMessage(TextMessage),
     Command(Command),
+    // command whispered to the bot instead of sent to the chat
+    Whisper(Command),
     Part(String),
     Join(String),
     // timer sends a message to the bot, String is the name of the message.
     // uuid is the message id, used to deduplicate
     // messages when a command is redefined
     TimedMessage(String, Uuid),
+    // result of a helix request the bot asked for
+    HelixResponse(HelixResponse),
+    // round trip time to the chat server the bot asked for, None if there was no answer
+    Latency(Option<Duration>),
+    // message to send, requested through the control interface
+    Say(String),
+    // raw IRC line to send, requested through the control interface
+    Raw(String),
+    // status of the bot process the bot asked for
+    Status(BotStatus),
+    // connector (re)connected to the chat, the state of an earlier connection is stale
+    Connected,
+    // chat modes of the channel changed (ROOMSTATE)
+    RoomState(RoomState),
+    // the messages of the user were deleted by a timeout or ban, all messages if None (CLEARCHAT)
+    ClearChat(Option<String>),
+    // a moderator deleted the message with the id (CLEARMSG)
+    ClearMessage(String),
+    // badges and display name of the bot in the channel, sent when it joins and after it chatted
+    // (USERSTATE)
+    UserState {
+        badges: HashSet<Badge>,
+        display_name: Option<String>,
+    },
+    // the chat server stopped answering the pings of the connector, the connection is probably dead
+    Unresponsive,
+    // timer ends the quiz question with the uuid
+    QuizEnd(Uuid),
+    // timer asks the bot to check whether the stream went live
+    CheckStream,
+    // timer asks the bot to send the next native shoutout after the cooldown
+    ShoutoutDue,
+    // timer tells the chat how many seconds are left until the raid with the uuid, 0 when it starts
+    RaidCountdown(Uuid, u64),
+    // timer ends the hate raid protection with the uuid
+    ProtectionExpired(Uuid),
+    // timer asks the bot to welcome the users whose greetings were batched while the chat was busy
+    GreetingBatchDue,
+    // asks the bot to fetch the emotes of the channel, sent when the bot starts
+    RefreshEmotes,
+    // chat message with bits
+    Cheer {
+        message: TextMessage,
+        bits: u64,
+    },
+    // redemption of a channel point reward which asks the user for a text
+    Redemption {
+        message: TextMessage,
+        reward_id: String,
+    },
+    // a moderator used `/announce`, the color is e.g. `PRIMARY` (USERNOTICE)
+    Announcement {
+        message: TextMessage,
+        color: String,
+    },
+    // a viewer shared their watch streak, the number of consecutive streams they watched (USERNOTICE)
+    ViewerMilestone {
+        message: TextMessage,
+        streak: u64,
+    },
+}
+
+/// An event with the circumstances it was received in.
+#[derive(Debug, PartialEq)]
+pub struct Event {
+    // when the chat server sent the line (`tmi-sent-ts`), else when the event was received
+    pub sent_at: DateTime<Utc>,
+    // channel of the chat line without `#`, None if the event did not come from the chat
+    pub channel: Option<String>,
+    // the chat line the event was parsed from, empty if the event did not come from the chat
+    pub raw: String,
+    pub content: ChatBotEvent,
+}
+
+// events of the bot itself, e.g. of its timers, did not come from the chat
+impl From<ChatBotEvent> for Event {
+    fn from(content: ChatBotEvent) -> Self {
+        Self {
+            sent_at: Utc::now(),
+            channel: None,
+            raw: String::new(),
+            content,
+        }
+    }
 }
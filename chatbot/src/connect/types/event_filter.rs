@@ -0,0 +1,102 @@
+use super::ChatBotEvent;
+use std::{collections::HashSet, str::FromStr};
+
+/// Kinds of the events the connector receives from the chat.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum EventKind {
+    TextMessage,
+    Command,
+    Join,
+    Part,
+    Cheer,
+    Redemption,
+}
+
+impl EventKind {
+    /// The kind of an event received from the chat,
+    /// None for the events the bot gets from elsewhere (e.g. timers).
+    pub fn of(event: &ChatBotEvent) -> Option<Self> {
+        match event {
+            ChatBotEvent::TextMessage(_) => Some(EventKind::TextMessage),
+            ChatBotEvent::Command(_) => Some(EventKind::Command),
+            ChatBotEvent::Join(_) => Some(EventKind::Join),
+            ChatBotEvent::Part(_) => Some(EventKind::Part),
+            ChatBotEvent::Cheer { .. } => Some(EventKind::Cheer),
+            ChatBotEvent::Redemption { .. } => Some(EventKind::Redemption),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "message" => Ok(EventKind::TextMessage),
+            "command" => Ok(EventKind::Command),
+            "join" => Ok(EventKind::Join),
+            "part" => Ok(EventKind::Part),
+            "cheer" => Ok(EventKind::Cheer),
+            "redemption" => Ok(EventKind::Redemption),
+            _ => Err(format!("unknown event kind {}", name)),
+        }
+    }
+}
+
+/// The kinds of chat events the connector forwards to the bot, all by default.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct EventFilter {
+    ignored: HashSet<EventKind>,
+}
+
+impl EventFilter {
+    /// Forwards all events except the ones of the given kinds.
+    pub fn ignoring(kinds: &[EventKind]) -> Self {
+        Self {
+            ignored: kinds.iter().copied().collect(),
+        }
+    }
+
+    pub fn accepts(&self, event: &ChatBotEvent) -> bool {
+        EventKind::of(event).is_none_or(|kind| !self.ignored.contains(&kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::ReceiveEvent;
+
+    fn chat_event(line: &str) -> ChatBotEvent {
+        match ReceiveEvent::parse_from_message(line) {
+            Some(ReceiveEvent::ChatBotEvent(event)) => event,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn filtering_join_events() {
+        let filter = EventFilter::ignoring(&[EventKind::Join, EventKind::Part]);
+        assert!(!filter.accepts(&chat_event(
+            ":chatter!chatter@chatter.tmi.twitch.tv JOIN #channel123"
+        )));
+        assert!(!filter.accepts(&chat_event(
+            ":chatter!chatter@chatter.tmi.twitch.tv PART #channel123"
+        )));
+        assert!(filter.accepts(&chat_event(
+            ":chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!help"
+        )));
+        assert!(filter.accepts(&ChatBotEvent::Say("hi".to_owned())));
+        assert!(EventFilter::default().accepts(&chat_event(
+            ":chatter!chatter@chatter.tmi.twitch.tv JOIN #channel123"
+        )));
+    }
+
+    #[test]
+    fn parsing_event_kinds() {
+        assert_eq!("Join".parse(), Ok(EventKind::Join));
+        assert_eq!("message".parse(), Ok(EventKind::TextMessage));
+        assert!("whisper".parse::<EventKind>().is_err());
+    }
+}
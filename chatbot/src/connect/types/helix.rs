@@ -0,0 +1,374 @@
+use chrono::{DateTime, Utc};
+use std::{str::FromStr, time::Duration};
+
+/// Requests to the Twitch Helix API which the bot cannot do itself.
+/// The result is sent back to the bot as `ChatBotEvent::HelixResponse`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HelixRequest {
+    CreatePrediction {
+        title: String,
+        outcomes: Vec<String>,
+        window: Duration,
+    },
+    LockPrediction {
+        id: String,
+    },
+    ResolvePrediction {
+        id: String,
+        winning_outcome_id: String,
+    },
+    FollowerTotal,
+    SubPointTotal,
+    // the follower total for `!followers` and the snapshot at the start of a stream
+    FollowerCount,
+    // the subscriber total with its sub points for `!subs` and the snapshot at the start of a stream
+    Subscriptions,
+    UserAge {
+        login: String,
+    },
+    StreamInfo,
+    // native shoutout with a banner in the chat
+    Shoutout {
+        login: String,
+    },
+    Whisper {
+        login: String,
+        text: String,
+    },
+    StartRaid {
+        login: String,
+    },
+    // cancels the pending raid of the channel
+    CancelRaid,
+    // the emotes of the channel and the global ones
+    Emotes,
+    // the latest past broadcast of the channel
+    LatestVod,
+    Clip {
+        window: ClipWindow,
+    },
+    // turns the chat modes on or off, e.g. while a hate raid is going on
+    UpdateChatSettings(ChatSettings),
+    // the scopes granted to the access token, for `!scopes`
+    TokenScopes,
+    // the color of the bot's name in the chat, a name like `blue_violet` or a hex color
+    UpdateChatColor {
+        color: String,
+    },
+}
+
+impl HelixRequest {
+    /// The response to this request if it could not be executed.
+    pub fn failed(&self, error: HelixError) -> HelixResponse {
+        match self {
+            HelixRequest::CreatePrediction { .. }
+            | HelixRequest::LockPrediction { .. }
+            | HelixRequest::ResolvePrediction { .. } => HelixResponse::Prediction(Err(error)),
+            HelixRequest::FollowerTotal => HelixResponse::FollowerTotal(Err(error)),
+            HelixRequest::SubPointTotal => HelixResponse::SubPointTotal(Err(error)),
+            HelixRequest::FollowerCount => HelixResponse::FollowerCount(Err(error)),
+            HelixRequest::Subscriptions => HelixResponse::Subscriptions(Err(error)),
+            HelixRequest::UserAge { login } => HelixResponse::UserAge {
+                login: login.to_owned(),
+                result: Err(error),
+            },
+            HelixRequest::StreamInfo => HelixResponse::StreamInfo(Err(error)),
+            HelixRequest::Shoutout { login } => HelixResponse::Shoutout {
+                login: login.to_owned(),
+                result: Err(error),
+            },
+            HelixRequest::Whisper { login, text } => HelixResponse::Whisper {
+                login: login.to_owned(),
+                text: text.to_owned(),
+                result: Err(error),
+            },
+            HelixRequest::StartRaid { login } => HelixResponse::Raid {
+                login: login.to_owned(),
+                result: Err(error),
+            },
+            HelixRequest::CancelRaid => HelixResponse::RaidCanceled(Err(error)),
+            HelixRequest::Emotes => HelixResponse::Emotes(Err(error)),
+            HelixRequest::LatestVod => HelixResponse::LatestVod(Err(error)),
+            HelixRequest::Clip { window } => HelixResponse::Clip {
+                window: *window,
+                result: Err(error),
+            },
+            HelixRequest::UpdateChatSettings(_) => HelixResponse::ChatSettings(Err(error)),
+            HelixRequest::TokenScopes => HelixResponse::TokenScopes(Err(error)),
+            HelixRequest::UpdateChatColor { color } => HelixResponse::ChatColor {
+                color: color.to_owned(),
+                result: Err(error),
+            },
+        }
+    }
+
+    /// Whether the request changes something on Twitch (e.g. starts a raid or sends a whisper)
+    /// rather than only reading from it.
+    pub fn has_side_effects(&self) -> bool {
+        !matches!(
+            self,
+            HelixRequest::FollowerTotal
+                | HelixRequest::SubPointTotal
+                | HelixRequest::FollowerCount
+                | HelixRequest::Subscriptions
+                | HelixRequest::UserAge { .. }
+                | HelixRequest::StreamInfo
+                | HelixRequest::Emotes
+                | HelixRequest::LatestVod
+                | HelixRequest::Clip { .. }
+                | HelixRequest::TokenScopes
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HelixResponse {
+    Prediction(Result<Prediction, HelixError>),
+    FollowerTotal(Result<u64, HelixError>),
+    SubPointTotal(Result<u64, HelixError>),
+    FollowerCount(Result<u64, HelixError>),
+    Subscriptions(Result<Subscriptions, HelixError>),
+    UserAge {
+        login: String,
+        result: Result<UserAge, HelixError>,
+    },
+    StreamInfo(Result<StreamInfo, HelixError>),
+    Shoutout {
+        login: String,
+        result: Result<(), HelixError>,
+    },
+    // the text is sent to the chat instead if the whisper failed
+    Whisper {
+        login: String,
+        text: String,
+        result: Result<(), HelixError>,
+    },
+    Raid {
+        login: String,
+        result: Result<(), HelixError>,
+    },
+    RaidCanceled(Result<(), HelixError>),
+    Emotes(Result<Emotes, HelixError>),
+    // None if the channel has no past broadcasts, e.g. because they are turned off
+    LatestVod(Result<Option<Video>, HelixError>),
+    Clip {
+        window: ClipWindow,
+        result: Result<Option<Clip>, HelixError>,
+    },
+    ChatSettings(Result<(), HelixError>),
+    TokenScopes(Result<Vec<String>, HelixError>),
+    ChatColor {
+        color: String,
+        result: Result<(), HelixError>,
+    },
+}
+
+/// Numbers of the requests to the Helix api since the bot started.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct HelixMetrics {
+    pub requests: u64,
+    // requests sent again after a rate limit or server error
+    pub retries: u64,
+    // requests sent again with a refreshed access token
+    pub refreshes: u64,
+    // requests which waited because the rate limit was almost used up
+    pub throttled: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HelixError {
+    // request could not be sent or the response could not be read
+    Unavailable(String),
+    // Twitch answered with an error status code and message
+    Status(u16, String),
+}
+
+impl HelixError {
+    /// Whether the access token lacks the scope needed for the request.
+    pub fn is_missing_scope(&self) -> bool {
+        matches!(self, HelixError::Status(401 | 403, message) if message.to_lowercase().contains("scope"))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PredictionStatus {
+    Active,
+    Locked,
+    Resolved,
+    Canceled,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Prediction {
+    pub id: String,
+    pub status: PredictionStatus,
+    pub outcome_ids: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UserAge {
+    pub created_at: DateTime<Utc>,
+    // None if the user does not follow the channel
+    pub followed_at: Option<DateTime<Utc>>,
+}
+
+/// Title and category of the channel, with a thumbnail if the stream is live.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StreamInfo {
+    pub live: bool,
+    pub title: String,
+    pub game: String,
+    // the id of the category, empty if the channel has none
+    pub game_id: String,
+    pub thumbnail_url: Option<String>,
+    // the id of the live stream, None while the channel is offline
+    pub stream_id: Option<String>,
+}
+
+/// The subscribers of the channel, the broadcaster included.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Subscriptions {
+    pub total: u64,
+    // tier 2 and 3 subscriptions are worth more than one point
+    pub points: u64,
+}
+
+/// An emote of the channel, e.g. a sub emote.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ChannelEmote {
+    // what is typed in the chat, e.g. `captai7Hype`
+    pub code: String,
+    // `subscriptions`, `bitstier` or `follower`
+    pub emote_type: String,
+}
+
+/// The emotes which can be used in the channel.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Emotes {
+    pub channel: Vec<ChannelEmote>,
+    // the codes of the emotes everyone can use everywhere
+    pub global: Vec<String>,
+}
+
+/// A past broadcast of the channel.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Video {
+    pub title: String,
+    pub duration: Duration,
+    pub url: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Clip {
+    pub title: String,
+    // display name of the user who clipped it
+    pub creator: String,
+    pub views: u64,
+    pub created_at: DateTime<Utc>,
+    pub url: String,
+}
+
+// the modes turned on by the bot ask for a follow of ten minutes and thirty seconds between messages
+const FOLLOWER_MODE_MINUTES: u32 = 10;
+const SLOW_MODE_SECONDS: u32 = 30;
+
+/// Chat modes to turn on or off, the ones which are None are left as they are.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ChatSettings {
+    pub follower_mode: Option<bool>,
+    // minutes a user has to follow while follower mode is on, None for ten minutes
+    pub follower_mode_duration: Option<u32>,
+    pub slow_mode: Option<bool>,
+    // seconds a user has to wait between messages while slow mode is on, None for thirty seconds
+    pub slow_mode_wait_time: Option<u32>,
+    pub subscriber_mode: Option<bool>,
+    pub emote_mode: Option<bool>,
+    pub unique_chat_mode: Option<bool>,
+}
+
+impl ChatSettings {
+    pub fn follower_minutes(&self) -> u32 {
+        self.follower_mode_duration.unwrap_or(FOLLOWER_MODE_MINUTES)
+    }
+
+    pub fn slow_seconds(&self) -> u32 {
+        self.slow_mode_wait_time.unwrap_or(SLOW_MODE_SECONDS)
+    }
+}
+
+/// Which clip `!lastclip` asks for: the latest one, or the most viewed one created within a time span.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ClipWindow {
+    Latest,
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl ClipWindow {
+    /// The earliest creation time of the clips considered, None for all clips.
+    /// The latest clip is looked for among the clips of the last month only.
+    pub fn since(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = match self {
+            ClipWindow::Day => 1,
+            ClipWindow::Week => 7,
+            ClipWindow::Latest | ClipWindow::Month => 30,
+            ClipWindow::All => return None,
+        };
+        Some(now - chrono::Duration::days(days))
+    }
+}
+
+impl FromStr for ClipWindow {
+    type Err = String;
+
+    // the latest clip is asked for without a window
+    fn from_str(window: &str) -> Result<Self, Self::Err> {
+        match window.to_lowercase().as_str() {
+            "day" | "today" | "24h" => Ok(ClipWindow::Day),
+            "week" | "7d" => Ok(ClipWindow::Week),
+            "month" | "30d" => Ok(ClipWindow::Month),
+            "all" | "alltime" => Ok(ClipWindow::All),
+            _ => Err(window.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_clip_windows() {
+        assert_eq!("week".parse(), Ok(ClipWindow::Week));
+        assert_eq!("Today".parse(), Ok(ClipWindow::Day));
+        assert_eq!("30d".parse(), Ok(ClipWindow::Month));
+        assert_eq!("all".parse(), Ok(ClipWindow::All));
+        assert_eq!("latest".parse::<ClipWindow>(), Err("latest".to_owned()));
+        let now = Utc::now();
+        assert_eq!(
+            ClipWindow::Week.since(now),
+            Some(now - chrono::Duration::days(7))
+        );
+        assert_eq!(ClipWindow::Latest.since(now), ClipWindow::Month.since(now));
+        assert_eq!(ClipWindow::All.since(now), None);
+    }
+
+    #[test]
+    fn telling_requests_with_side_effects() {
+        assert!(HelixRequest::CancelRaid.has_side_effects());
+        assert!(HelixRequest::UpdateChatSettings(ChatSettings::default()).has_side_effects());
+        assert!(HelixRequest::Whisper {
+            login: "carkhy".to_owned(),
+            text: "Hi".to_owned(),
+        }
+        .has_side_effects());
+        assert!(!HelixRequest::StreamInfo.has_side_effects());
+        assert!(!HelixRequest::Clip {
+            window: ClipWindow::Week
+        }
+        .has_side_effects());
+    }
+}
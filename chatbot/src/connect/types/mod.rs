@@ -1,9 +1,22 @@
+mod alert;
+mod bot_status;
 mod command;
 mod event;
+mod event_filter;
+mod helix;
+mod room_state;
 mod text_message;
 mod user_info;
 
-pub use command::{Command, CommandType};
-pub use event::ChatBotEvent;
+pub use alert::Alert;
+pub use bot_status::BotStatus;
+pub use command::{Command, CommandPrefixes, CommandType, Permission, DEFAULT_COMMAND_PREFIX};
+pub use event::{ChatBotEvent, Event};
+pub use event_filter::{EventFilter, EventKind};
+pub use helix::{
+    ChannelEmote, ChatSettings, Clip, ClipWindow, Emotes, HelixError, HelixMetrics, HelixRequest,
+    HelixResponse, Prediction, PredictionStatus, StreamInfo, Subscriptions, UserAge, Video,
+};
+pub use room_state::RoomState;
 pub use text_message::TextMessage;
 pub use user_info::{Badge, UserInfo};
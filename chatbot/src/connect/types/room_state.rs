@@ -0,0 +1,70 @@
+use super::UserInfo;
+
+/// The chat modes of the channel. The chat server sends all modes when the bot joins
+/// and only the changed ones afterwards, modes which were not sent are None.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct RoomState {
+    pub emote_only: Option<bool>,
+    pub subs_only: Option<bool>,
+    // seconds a user has to wait between messages, 0 if slow mode is off
+    pub slow: Option<u32>,
+    // minutes a user has to follow before chatting, -1 if followers-only mode is off
+    pub followers_only: Option<i32>,
+    // whether messages have to differ from the user's previous ones (r9k)
+    pub unique_chat: Option<bool>,
+}
+
+impl RoomState {
+    /// Applies the modes sent in a change, keeping the others.
+    pub fn update(&mut self, change: RoomState) {
+        self.emote_only = change.emote_only.or(self.emote_only);
+        self.subs_only = change.subs_only.or(self.subs_only);
+        self.slow = change.slow.or(self.slow);
+        self.followers_only = change.followers_only.or(self.followers_only);
+        self.unique_chat = change.unique_chat.or(self.unique_chat);
+    }
+
+    /// Whether only some users may chat, e.g. during a hostile raid.
+    pub fn is_restrictive(&self) -> bool {
+        self.emote_only == Some(true) || self.subs_only == Some(true)
+    }
+
+    /// Whether the user may send text messages, moderators may always chat.
+    pub fn allows(&self, user: &UserInfo) -> bool {
+        if user.has_elevated_rights() {
+            return true;
+        }
+        self.emote_only != Some(true) && (self.subs_only != Some(true) || user.is_subscriber())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::user;
+
+    #[test]
+    fn restricting_the_chat() {
+        let mut room = RoomState {
+            emote_only: Some(false),
+            subs_only: Some(false),
+            ..RoomState::default()
+        };
+        assert!(!room.is_restrictive());
+        room.update(RoomState {
+            subs_only: Some(true),
+            ..RoomState::default()
+        });
+        assert_eq!(room.emote_only, Some(false));
+        assert!(room.is_restrictive());
+        assert!(room.allows(&user(&["subscriber"])));
+        assert!(!room.allows(&user(&["vip"])));
+        room.update(RoomState {
+            emote_only: Some(true),
+            subs_only: Some(false),
+            ..RoomState::default()
+        });
+        assert!(!room.allows(&user(&["subscriber"])));
+        assert!(room.allows(&user(&["moderator"])));
+    }
+}
@@ -4,4 +4,10 @@ use super::UserInfo;
 pub struct TextMessage {
     pub text: String,
     pub user: UserInfo,
+    // the user id of the channel, from the room-id tag
+    pub room_id: Option<u64>,
+    // the unique id of the message, from the id tag
+    pub id: Option<String>,
+    // whether this is the first message of the user in the channel, from the first-msg tag
+    pub first_message: bool,
 }
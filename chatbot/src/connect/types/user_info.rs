@@ -9,6 +9,8 @@ pub struct Badge {
 #[derive(Debug, PartialEq, Eq)]
 pub struct UserInfo {
     pub name: String,
+    // the user id from the tags, which stays the same when the user is renamed
+    pub id: Option<String>,
     pub badges: HashSet<Badge>,
 }
 
@@ -18,4 +20,15 @@ impl UserInfo {
             .iter()
             .any(|badge| badge.name == "broadcaster" || badge.name == "moderator")
     }
+
+    pub fn is_broadcaster(&self) -> bool {
+        self.badges.iter().any(|badge| badge.name == "broadcaster")
+    }
+
+    // founders are the first subscribers of a channel and have their own badge
+    pub fn is_subscriber(&self) -> bool {
+        self.badges
+            .iter()
+            .any(|badge| badge.name == "subscriber" || badge.name == "founder")
+    }
 }
@@ -0,0 +1,364 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+};
+use thiserror::Error;
+
+use crate::connect::{Command, CommandType, HelixError};
+
+// written next to the store unless another file is configured
+pub const DEFAULT_AUDIT_LOG: &str = "audit.jsonl";
+// the entries `!audit` can look through, the file keeps all of them
+const TRAIL_SIZE: usize = 1000;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Could not access the audit log [{}]", .0)]
+    Io(#[from] io::Error),
+    #[error("Invalid audit log entry in line {} [{}]", .0, .1)]
+    Invalid(usize, String),
+}
+
+/// How a privileged command ended.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AuditOutcome {
+    Success,
+    // the user lacks the permission for the command
+    Denied,
+    // e.g. the twitch api refused the change
+    Failed(String),
+}
+
+impl From<Result<(), HelixError>> for AuditOutcome {
+    fn from(result: Result<(), HelixError>) -> Self {
+        match result {
+            Ok(()) => AuditOutcome::Success,
+            Err(HelixError::Status(status, message)) => {
+                AuditOutcome::Failed(format!("{} {}", status, message))
+            }
+            Err(HelixError::Unavailable(message)) => AuditOutcome::Failed(message),
+        }
+    }
+}
+
+/// Who used a privileged command, when and how it ended.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub user: String,
+    pub user_id: Option<String>,
+    // without the prefix, e.g. `slow`
+    pub command: String,
+    pub args: String,
+    // the user, channel or command the command acted on, if any
+    pub target: Option<String>,
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEntry {
+    /// An entry for the command, which succeeded until it is told otherwise.
+    pub fn new(command: &Command, at: DateTime<Utc>) -> Self {
+        let target = match command.kind {
+            CommandType::Raid | CommandType::Shoutout | CommandType::Context => {
+                command.arg_user(0).map(str::to_lowercase)
+            }
+            CommandType::NewCommand
+            | CommandType::RemoveCommand
+            | CommandType::NewRepeating
+            | CommandType::RemoveRepeating
+            | CommandType::Enable
+            | CommandType::Disable
+            | CommandType::Counter => command
+                .options
+                .first()
+                .map(|name| name.trim_start_matches('!').to_lowercase()),
+            _ => None,
+        };
+        Self {
+            at,
+            user: command.user.name.to_lowercase(),
+            user_id: command.user.id.clone(),
+            command: command.kind.name().to_owned(),
+            args: command.raw_args.trim().to_owned(),
+            target,
+            outcome: AuditOutcome::Success,
+        }
+    }
+
+    pub fn with_outcome(self, outcome: AuditOutcome) -> Self {
+        Self { outcome, ..self }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (outcome, error) = match &self.outcome {
+            AuditOutcome::Success => ("success", None),
+            AuditOutcome::Denied => ("denied", None),
+            AuditOutcome::Failed(error) => ("failure", Some(error)),
+        };
+        json!({
+            "timestamp": self.at.to_rfc3339(),
+            "user": self.user,
+            "user_id": self.user_id,
+            "command": self.command,
+            "args": self.args,
+            "target": self.target,
+            "outcome": outcome,
+            "error": error,
+        })
+    }
+
+    pub fn from_json(entry: &Value) -> Result<Self, String> {
+        let text = |name: &str| {
+            entry[name]
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| format!("{} is missing", name))
+        };
+        let at = DateTime::parse_from_rfc3339(&text("timestamp")?)
+            .map_err(|err| err.to_string())?
+            .with_timezone(&Utc);
+        let outcome = match text("outcome")?.as_str() {
+            "success" => AuditOutcome::Success,
+            "denied" => AuditOutcome::Denied,
+            "failure" => AuditOutcome::Failed(text("error").unwrap_or_default()),
+            outcome => return Err(format!("unknown outcome {}", outcome)),
+        };
+        Ok(Self {
+            at,
+            user: text("user")?,
+            user_id: text("user_id").ok(),
+            command: text("command")?,
+            args: text("args")?,
+            target: text("target").ok(),
+            outcome,
+        })
+    }
+
+    /// One line for `!audit`, e.g. `2024-05-01 20:15 UTC alice !slow 30: success`.
+    pub fn summary(&self) -> String {
+        let outcome = match &self.outcome {
+            AuditOutcome::Success => "success".to_owned(),
+            AuditOutcome::Denied => "denied".to_owned(),
+            AuditOutcome::Failed(error) => format!("failure ({})", error),
+        };
+        let command = match self.args.as_str() {
+            "" => format!("!{}", self.command),
+            args => format!("!{} {}", self.command, args),
+        };
+        format!(
+            "{} {} {}: {}",
+            self.at.format("%Y-%m-%d %H:%M UTC"),
+            self.user,
+            command,
+            outcome
+        )
+    }
+}
+
+/// Appends the entries to a json lines file, which is never truncated by the bot.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: String,
+}
+
+impl AuditLog {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), AuditError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", entry.to_json())?;
+        Ok(())
+    }
+
+    /// The latest entries of the file, the oldest first, none if there is no file yet.
+    pub fn load(&self) -> Result<Vec<AuditEntry>, AuditError> {
+        let log = match fs::read_to_string(&self.path) {
+            Ok(log) => log,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut entries = VecDeque::new();
+        for (index, line) in log.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(line)
+                .map_err(|err| err.to_string())
+                .and_then(|entry| AuditEntry::from_json(&entry))
+                .map_err(|err| AuditError::Invalid(index + 1, err))?;
+            if entries.len() == TRAIL_SIZE {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+        Ok(entries.into())
+    }
+}
+
+/// The latest entries of the audit log for `!audit`, and the entries waiting for the twitch api
+/// to tell how their command ended.
+#[derive(Debug, Default)]
+pub struct AuditTrail {
+    // the oldest first
+    entries: VecDeque<AuditEntry>,
+    // one per chat settings update in the order of the requests, None if no command asked for it
+    // (e.g. the raid protection), the responses come back in the same order
+    pending_chat_settings: VecDeque<Option<AuditEntry>>,
+    // the channel of the raid being started with its entry, there is one raid at a time
+    pending_raid: Option<(String, AuditEntry)>,
+    // the finished entries which were not written to the log yet
+    unwritten: Vec<AuditEntry>,
+}
+
+impl AuditTrail {
+    pub fn new(entries: Vec<AuditEntry>) -> Self {
+        Self {
+            entries: entries.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Keeps the finished entry for `!audit` and until it is written to the log.
+    pub fn record(&mut self, entry: AuditEntry) {
+        if self.entries.len() == TRAIL_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry.clone());
+        self.unwritten.push(entry);
+    }
+
+    pub fn take_unwritten(&mut self) -> Vec<AuditEntry> {
+        std::mem::take(&mut self.unwritten)
+    }
+
+    pub fn pending_chat_settings(&self) -> usize {
+        self.pending_chat_settings.len()
+    }
+
+    /// Notes a chat settings update, whose response finishes no entry.
+    pub fn await_chat_settings(&mut self) {
+        self.pending_chat_settings.push_back(None);
+    }
+
+    /// Lets the response to the update with the index (counted among the pending ones)
+    /// finish the entry.
+    pub fn attach_chat_settings(&mut self, index: usize, entry: AuditEntry) {
+        if let Some(pending) = self.pending_chat_settings.get_mut(index) {
+            *pending = Some(entry);
+        }
+    }
+
+    /// Finishes the entry of the oldest chat settings update, if a command asked for it.
+    pub fn chat_settings_updated(&mut self, result: Result<(), HelixError>) {
+        if let Some(Some(entry)) = self.pending_chat_settings.pop_front() {
+            self.record(entry.with_outcome(result.into()));
+        }
+    }
+
+    pub fn await_raid(&mut self, login: &str, entry: AuditEntry) {
+        self.pending_raid = Some((login.to_owned(), entry));
+    }
+
+    /// Finishes the entry of the raid on the channel, if it is the one being started.
+    pub fn raid_started(&mut self, login: &str, result: Result<(), HelixError>) {
+        if let Some((_, entry)) = self.pending_raid.take_if(|(channel, _)| channel == login) {
+            self.record(entry.with_outcome(result.into()));
+        }
+    }
+
+    /// The latest entries of the user or command (without the prefix), the latest first.
+    pub fn matching(&self, user_or_command: &str, count: usize) -> Vec<&AuditEntry> {
+        let filter = user_or_command
+            .trim_start_matches(['@', '!'])
+            .to_lowercase();
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.user == filter || entry.command == filter)
+            .take(count)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(user: &str, command: &str, outcome: AuditOutcome) -> AuditEntry {
+        AuditEntry {
+            at: Utc.with_ymd_and_hms(2024, 5, 1, 20, 15, 0).unwrap(),
+            user: user.to_owned(),
+            user_id: Some("1337".to_owned()),
+            command: command.to_owned(),
+            args: "30".to_owned(),
+            target: None,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn writing_and_loading_entries() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AuditLog::new(path.to_str().unwrap());
+        assert!(log.load().unwrap().is_empty());
+        let entries = vec![
+            entry("alice", "slow", AuditOutcome::Success),
+            AuditEntry {
+                target: Some("carkhy".to_owned()),
+                user_id: None,
+                ..entry(
+                    "bob",
+                    "raid",
+                    AuditOutcome::Failed("400 offline".to_owned()),
+                )
+            },
+        ];
+        for entry in &entries {
+            log.append(entry).unwrap();
+        }
+        assert_eq!(log.load().unwrap(), entries);
+        let line = fs::read_to_string(&path).unwrap();
+        assert!(line.starts_with(
+            r#"{"args":"30","command":"slow","error":null,"outcome":"success","target":null,"#
+        ));
+        fs::write(&path, "{\"user\": \"alice\"}\n").unwrap();
+        assert!(matches!(log.load(), Err(AuditError::Invalid(1, _))));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn finding_entries_of_users_and_commands() {
+        let mut trail = AuditTrail::new(vec![
+            entry("alice", "slow", AuditOutcome::Success),
+            entry("bob", "slow", AuditOutcome::Denied),
+        ]);
+        trail.record(entry("alice", "raid", AuditOutcome::Success));
+        let commands = |entries: Vec<&AuditEntry>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|entry| format!("{} {}", entry.user, entry.command))
+                .collect()
+        };
+        assert_eq!(
+            commands(trail.matching("@Alice", 5)),
+            vec!["alice raid", "alice slow"]
+        );
+        assert_eq!(commands(trail.matching("!slow", 1)), vec!["bob slow"]);
+        assert!(trail.matching("carol", 5).is_empty());
+        assert_eq!(
+            trail.matching("bob", 1)[0].summary(),
+            "2024-05-01 20:15 UTC bob !slow 30: denied"
+        );
+    }
+}
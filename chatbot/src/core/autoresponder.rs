@@ -0,0 +1,176 @@
+use super::template::Template;
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+// a pattern answers at most once in this time unless its own cooldown is configured
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum AutoresponderError {
+    #[error("Could not read autoresponses [{}]", .0)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid autoresponses [{}]", .0)]
+    Invalid(String),
+}
+
+#[derive(Debug)]
+struct Autoresponse {
+    pattern: Regex,
+    response: Template,
+    cooldown: Duration,
+    last_response: Option<Instant>,
+}
+
+/// Replies to chat messages matching a regular expression, e.g. a link with "Links are only for subs!".
+/// Each pattern has its own cooldown, shared by all chatters.
+#[derive(Debug, Default)]
+pub struct Autoresponder {
+    responses: Vec<Autoresponse>,
+}
+
+impl Autoresponder {
+    /// Loads the patterns from a json file like
+    /// `[{"pattern": "(?i)https?://", "response": "Links are only for subs!", "cooldown": 120}]`,
+    /// the cooldown in seconds is optional.
+    pub fn load(path: &str) -> Result<Self, AutoresponderError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AutoresponderError> {
+        let config: Value = serde_json::from_str(json)
+            .map_err(|err| AutoresponderError::Invalid(err.to_string()))?;
+        let entries = match config {
+            Value::Array(entries) => entries,
+            _ => {
+                return Err(AutoresponderError::Invalid(
+                    "expected a list of patterns".to_owned(),
+                ))
+            }
+        };
+        let responses = entries
+            .iter()
+            .map(|entry| {
+                let text = |key: &str| {
+                    entry[key].as_str().ok_or_else(|| {
+                        AutoresponderError::Invalid(format!("{} must be a text: {}", key, entry))
+                    })
+                };
+                let pattern = Regex::new(text("pattern")?)
+                    .map_err(|err| AutoresponderError::Invalid(err.to_string()))?;
+                let response = Template::parse(text("response")?)
+                    .map_err(|err| AutoresponderError::Invalid(err.to_string()))?;
+                let cooldown = match &entry["cooldown"] {
+                    Value::Null => DEFAULT_COOLDOWN,
+                    cooldown => cooldown.as_u64().map(Duration::from_secs).ok_or_else(|| {
+                        AutoresponderError::Invalid(format!("cooldown must be seconds: {}", entry))
+                    })?,
+                };
+                Ok(Autoresponse {
+                    pattern,
+                    response,
+                    cooldown,
+                    last_response: None,
+                })
+            })
+            .collect::<Result<_, AutoresponderError>>()?;
+        Ok(Self { responses })
+    }
+
+    /// The response of the first pattern matching the text which is not cooling down,
+    /// which then starts its cooldown.
+    pub fn respond(&mut self, text: &str, now: Instant) -> Option<&Template> {
+        let response = self.responses.iter_mut().find(|response| {
+            response.pattern.is_match(text)
+                && response.last_response.is_none_or(|last_response| {
+                    now.saturating_duration_since(last_response) >= response.cooldown
+                })
+        })?;
+        response.last_response = Some(now);
+        Some(&response.response)
+    }
+
+    /// Ends the cooldowns of all patterns.
+    pub fn reset(&mut self) {
+        for response in &mut self.responses {
+            response.last_response = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder() -> Autoresponder {
+        Autoresponder::from_json(
+            r#"[
+                {"pattern": "(?i)https?://", "response": "Links are only for subs!"},
+                {"pattern": "\\bfirst\\b", "response": "Welcome!", "cooldown": 5}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn rendered(template: Option<&Template>) -> Option<String> {
+        let mut rng = rand::thread_rng();
+        template.map(|template| template.render(&Default::default(), &mut rng))
+    }
+
+    #[test]
+    fn responding_to_matching_messages() {
+        let mut responder = responder();
+        let now = Instant::now();
+        assert_eq!(
+            rendered(responder.respond("look at HTTPS://example.com", now)),
+            Some("Links are only for subs!".to_owned())
+        );
+        assert_eq!(
+            rendered(responder.respond("am I first?", now)),
+            Some("Welcome!".to_owned())
+        );
+        assert_eq!(rendered(responder.respond("firstly, hello", now)), None);
+    }
+
+    #[test]
+    fn cooling_down_each_pattern() {
+        let mut responder = responder();
+        let now = Instant::now();
+        assert!(responder.respond("first", now).is_some());
+        assert!(responder
+            .respond("first", now + Duration::from_secs(4))
+            .is_none());
+        // the other pattern is not cooling down
+        assert!(responder
+            .respond("http://example.com", now + Duration::from_secs(4))
+            .is_some());
+        assert!(responder
+            .respond("first", now + Duration::from_secs(5))
+            .is_some());
+        assert!(responder
+            .respond("https://example.com", now + Duration::from_secs(63))
+            .is_none());
+        assert!(responder
+            .respond("https://example.com", now + Duration::from_secs(64))
+            .is_some());
+    }
+
+    #[test]
+    fn rejecting_invalid_patterns() {
+        for json in [
+            r#"{"pattern": "a", "response": "b"}"#,
+            r#"[{"pattern": "(", "response": "b"}]"#,
+            r#"[{"pattern": "a"}]"#,
+            r#"[{"pattern": "a", "response": "b", "cooldown": "1m"}]"#,
+        ] {
+            assert!(matches!(
+                Autoresponder::from_json(json),
+                Err(AutoresponderError::Invalid(_))
+            ));
+        }
+    }
+}
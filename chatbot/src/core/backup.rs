@@ -0,0 +1,396 @@
+use super::{
+    deaths::GameDeaths,
+    dice::RollRecord,
+    roles::Role,
+    store::{
+        Store, COUNTER_BUCKET_NAME, DISABLED_COMMANDS_BUCKET_NAME, GAME_DEATHS_BUCKET_NAME,
+        GOAL_BUCKET_NAME, MILESTONES_BUCKET_NAME, QUIZ_POINTS_BUCKET_NAME, RECORDS_BUCKET_NAME,
+        ROLES_BUCKET_NAME, USAGE_BUCKET_NAME,
+    },
+};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fs};
+use thiserror::Error;
+
+/// The version of the backups written by this bot, backups of older versions are migrated.
+/// Version 1 was a plain dump of the buckets of the store with all values as text.
+pub const BACKUP_VERSION: u64 = 2;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Could not read or write the backup [{}]", .0)]
+    Io(#[from] std::io::Error),
+    #[error("Could not access the store [{}]", .0)]
+    Store(#[from] kv::Error),
+    #[error("Invalid backup [{}]", .0)]
+    Invalid(String),
+    #[error("Backup version {} is newer than the supported version {}", .0, BACKUP_VERSION)]
+    UnsupportedVersion(u64),
+}
+
+/// How the values of a section are written to and read from the backup, which also tells the valid values.
+#[derive(Debug, Clone, Copy)]
+enum ValueKind {
+    Integer,
+    Count,
+    Flag,
+    // any other value is a string in the form the store keeps it, e.g. `42 Elden Ring`
+    Text(fn(&str) -> bool),
+}
+
+impl ValueKind {
+    fn write(self, text: &str) -> Value {
+        let value = match self {
+            ValueKind::Integer => text.parse::<i64>().ok().map(Value::from),
+            ValueKind::Count => text.parse::<u64>().ok().map(Value::from),
+            ValueKind::Flag => text.parse::<bool>().ok().map(Value::from),
+            ValueKind::Text(_) => None,
+        };
+        value.unwrap_or_else(|| Value::from(text))
+    }
+
+    fn read(self, value: &Value) -> Option<String> {
+        match self {
+            ValueKind::Integer => value.as_i64().map(|value| value.to_string()),
+            ValueKind::Count => value.as_u64().map(|value| value.to_string()),
+            ValueKind::Flag => value.as_bool().map(|value| value.to_string()),
+            ValueKind::Text(valid) => value.as_str().filter(|text| valid(text)).map(str::to_owned),
+        }
+    }
+
+    fn is_valid(self, text: &str) -> bool {
+        self.read(&self.write(text)).is_some()
+    }
+}
+
+fn valid_roll_record(record: &str) -> bool {
+    record.parse::<RollRecord>().is_ok()
+}
+
+fn valid_role(role: &str) -> bool {
+    role.parse::<Role>().is_ok()
+}
+
+fn valid_game_deaths(game: &str) -> bool {
+    game.parse::<GameDeaths>().is_ok()
+}
+
+// the sections of a backup are the buckets of the store
+const SECTIONS: [(&str, ValueKind); 9] = [
+    (COUNTER_BUCKET_NAME, ValueKind::Integer),
+    (GOAL_BUCKET_NAME, ValueKind::Count),
+    (USAGE_BUCKET_NAME, ValueKind::Count),
+    (QUIZ_POINTS_BUCKET_NAME, ValueKind::Count),
+    (RECORDS_BUCKET_NAME, ValueKind::Text(valid_roll_record)),
+    (DISABLED_COMMANDS_BUCKET_NAME, ValueKind::Flag),
+    (ROLES_BUCKET_NAME, ValueKind::Text(valid_role)),
+    (GAME_DEATHS_BUCKET_NAME, ValueKind::Text(valid_game_deaths)),
+    (MILESTONES_BUCKET_NAME, ValueKind::Count),
+];
+
+fn section_kind(section: &str) -> Option<ValueKind> {
+    SECTIONS
+        .iter()
+        .find(|(name, _)| *name == section)
+        .map(|(_, kind)| *kind)
+}
+
+/// Whether a restore keeps the entries which are not in the backup.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RestoreMode {
+    // entries of the backup win over the stored ones, other stored entries are kept
+    Merge,
+    // the store holds exactly the entries of the backup afterwards
+    Replace,
+}
+
+/// The entries a restore changed, as `<section>/<key>`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub added: Vec<String>,
+    // with the old and the new value, e.g. `counters/deaths: 4 -> 7`
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// The persisted data of the bot (counters, goals, usage statistics, quiz points, records,
+/// disabled commands, roles, deaths and message milestones) as one versioned json document,
+/// e.g. to move the bot to another machine.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Backup {
+    // the stored values by key by section
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Backup {
+    /// Backs up all sections of the store. Values the bot could not load are left out.
+    pub fn create(store: &dyn Store) -> Result<Self, BackupError> {
+        let mut sections = BTreeMap::new();
+        for (section, kind) in SECTIONS {
+            let entries: BTreeMap<String, String> = store
+                .entries(section)?
+                .into_iter()
+                .filter(|(_, value)| kind.is_valid(value))
+                .collect();
+            if !entries.is_empty() {
+                sections.insert(section.to_owned(), entries);
+            }
+        }
+        Ok(Self { sections })
+    }
+
+    pub fn load(path: &str) -> Result<Self, BackupError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), BackupError> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+
+    /// Reads a backup of this or an older version. All sections are validated, so that a backup
+    /// with a single invalid entry is refused as a whole.
+    pub fn from_json(json: &str) -> Result<Self, BackupError> {
+        let document: Value =
+            serde_json::from_str(json).map_err(|err| BackupError::Invalid(err.to_string()))?;
+        let mut document = match document {
+            Value::Object(document) => document,
+            _ => return Err(BackupError::Invalid("expected an object".to_owned())),
+        };
+        let version = document
+            .remove("version")
+            .and_then(|version| version.as_u64())
+            .ok_or_else(|| BackupError::Invalid("missing version".to_owned()))?;
+        match version {
+            1 => document = migrate_from_version_1(document)?,
+            BACKUP_VERSION => {}
+            0 => return Err(BackupError::Invalid("unknown version 0".to_owned())),
+            _ => return Err(BackupError::UnsupportedVersion(version)),
+        }
+
+        let mut sections = BTreeMap::new();
+        let mut errors = Vec::new();
+        for (section, entries) in document {
+            let (kind, entries) = match (section_kind(&section), entries) {
+                (Some(kind), Value::Object(entries)) => (kind, entries),
+                (Some(_), _) => {
+                    errors.push(format!("section {} is not an object", section));
+                    continue;
+                }
+                (None, _) => {
+                    errors.push(format!("unknown section {}", section));
+                    continue;
+                }
+            };
+            let mut values = BTreeMap::new();
+            for (key, value) in entries {
+                match kind.read(&value) {
+                    Some(value) => {
+                        values.insert(key, value);
+                    }
+                    None => errors.push(format!("invalid value {} of {}/{}", value, section, key)),
+                }
+            }
+            if !values.is_empty() {
+                sections.insert(section, values);
+            }
+        }
+        if !errors.is_empty() {
+            return Err(BackupError::Invalid(errors.join(", ")));
+        }
+        Ok(Self { sections })
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut document = Map::new();
+        document.insert("version".to_owned(), Value::from(BACKUP_VERSION));
+        for (section, kind) in SECTIONS {
+            if let Some(entries) = self.sections.get(section) {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| (key.to_owned(), kind.write(value)))
+                    .collect();
+                document.insert(section.to_owned(), Value::Object(entries));
+            }
+        }
+        serde_json::to_string_pretty(&Value::Object(document)).unwrap()
+    }
+
+    /// Writes the backup to the store. Replacing also removes the entries of the sections
+    /// missing in the backup.
+    pub fn restore(
+        &self,
+        store: &dyn Store,
+        mode: RestoreMode,
+    ) -> Result<RestoreReport, BackupError> {
+        let mut report = RestoreReport::default();
+        let no_entries = BTreeMap::new();
+        for (section, _) in SECTIONS {
+            let stored: BTreeMap<String, String> = store.entries(section)?.into_iter().collect();
+            let entries = self.sections.get(section).unwrap_or(&no_entries);
+            for (key, value) in entries {
+                match stored.get(key) {
+                    Some(old) if old == value => continue,
+                    Some(old) => report
+                        .changed
+                        .push(format!("{}/{}: {} -> {}", section, key, old, value)),
+                    None => report.added.push(format!("{}/{}", section, key)),
+                }
+                store.set(section, key, value)?;
+            }
+            if mode == RestoreMode::Replace {
+                for key in stored.keys().filter(|key| !entries.contains_key(*key)) {
+                    store.remove(section, key)?;
+                    report.removed.push(format!("{}/{}", section, key));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+// version 1 kept the buckets under `buckets` with all values as strings
+fn migrate_from_version_1(
+    mut document: Map<String, Value>,
+) -> Result<Map<String, Value>, BackupError> {
+    let buckets = match document.remove("buckets") {
+        Some(Value::Object(buckets)) => buckets,
+        _ => return Err(BackupError::Invalid("missing buckets".to_owned())),
+    };
+    Ok(buckets
+        .into_iter()
+        .map(|(section, entries)| {
+            let entries = match (section_kind(&section), entries) {
+                (Some(kind), Value::Object(entries)) => Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| match value {
+                            Value::String(text) => (key, kind.write(&text)),
+                            value => (key, value),
+                        })
+                        .collect(),
+                ),
+                // unknown sections are reported by the validation
+                (_, entries) => entries,
+            };
+            (section, entries)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::store::{load_counters, store_counter, store_role, InMemoryStore};
+    use std::collections::HashMap;
+
+    fn filled_store() -> InMemoryStore {
+        let store = InMemoryStore::default();
+        store_counter(&store, "deaths", 4).unwrap();
+        store_counter(&store, "wins", -1).unwrap();
+        store_role(&store, "12345", Role::Vip).unwrap();
+        store
+            .set(GAME_DEATHS_BUCKET_NAME, "512953", "42 Elden Ring")
+            .unwrap();
+        store
+            .set(DISABLED_COMMANDS_BUCKET_NAME, "slap", "true")
+            .unwrap();
+        // entries the bot cannot load are not backed up
+        store.set(COUNTER_BUCKET_NAME, "broken", "many").unwrap();
+        store
+    }
+
+    #[test]
+    fn round_tripping_a_backup() {
+        let backup = Backup::create(&filled_store()).unwrap();
+        let json = backup.to_json();
+        assert!(json.contains("\"version\": 2"));
+        assert!(json.contains("\"deaths\": 4"));
+        assert!(json.contains("\"512953\": \"42 Elden Ring\""));
+        assert!(json.contains("\"slap\": true"));
+        assert!(!json.contains("broken"));
+        assert_eq!(Backup::from_json(&json).unwrap(), backup);
+
+        let store = InMemoryStore::default();
+        let report = backup.restore(&store, RestoreMode::Merge).unwrap();
+        assert_eq!(report.added.len(), 5);
+        assert_eq!(Backup::create(&store).unwrap(), backup);
+    }
+
+    #[test]
+    fn merging_and_replacing() {
+        let backup = Backup::from_json(
+            r#"{"version": 2, "counters": {"deaths": 7, "jumps": 1}, "roles": {"12345": "vip"}}"#,
+        )
+        .unwrap();
+
+        let store = filled_store();
+        let report = backup.restore(&store, RestoreMode::Merge).unwrap();
+        assert_eq!(
+            report,
+            RestoreReport {
+                added: vec!["counters/jumps".to_owned()],
+                changed: vec!["counters/deaths: 4 -> 7".to_owned()],
+                removed: Vec::new(),
+            }
+        );
+        assert_eq!(
+            load_counters(&store).unwrap(),
+            HashMap::from([
+                ("deaths".to_owned(), 7),
+                ("jumps".to_owned(), 1),
+                ("wins".to_owned(), -1),
+            ])
+        );
+
+        let store = filled_store();
+        let report = backup.restore(&store, RestoreMode::Replace).unwrap();
+        assert_eq!(
+            report.removed,
+            vec![
+                "counters/broken",
+                "counters/wins",
+                "disabled_commands/slap",
+                "game_deaths/512953",
+            ]
+        );
+        assert_eq!(Backup::create(&store).unwrap(), backup);
+    }
+
+    #[test]
+    fn refusing_invalid_backups_without_changes() {
+        let store = filled_store();
+        let result = Backup::from_json(
+            r#"{"version": 2, "counters": {"deaths": 7}, "roles": {"12345": "admin"}, "quotes": {}}"#,
+        );
+        assert!(matches!(result, Err(BackupError::Invalid(errors))
+                         if errors == r#"unknown section quotes, invalid value "admin" of roles/12345"#));
+        assert_eq!(load_counters(&store).unwrap()["deaths"], 4);
+        assert!(matches!(
+            Backup::from_json(r#"{"version": 3}"#),
+            Err(BackupError::UnsupportedVersion(3))
+        ));
+        assert!(matches!(
+            Backup::from_json(r#"{"counters": {}}"#),
+            Err(BackupError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn migrating_backups_of_version_1() {
+        let backup = Backup::from_json(
+            r#"{"version": 1, "buckets": {"counters": {"deaths": "4"}, "disabled_commands": {"slap": "true"}}}"#,
+        )
+        .unwrap();
+        let store = InMemoryStore::default();
+        backup.restore(&store, RestoreMode::Replace).unwrap();
+        assert_eq!(load_counters(&store).unwrap()["deaths"], 4);
+        assert_eq!(
+            store.entries(DISABLED_COMMANDS_BUCKET_NAME).unwrap(),
+            vec![("slap".to_owned(), "true".to_owned())]
+        );
+        assert!(Backup::from_json(
+            r#"{"version": 1, "buckets": {"counters": {"deaths": "many"}}}"#
+        )
+        .is_err());
+    }
+}
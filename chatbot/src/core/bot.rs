@@ -2,328 +2,8970 @@
 
 use uuid::Uuid;
 
-use super::ChatBotCommand;
-use crate::connect::{ChatBotEvent, Command, CommandType};
+use super::{
+    audit::{AuditEntry, AuditOutcome, AuditTrail},
+    autoresponder::Autoresponder,
+    chat_mode::{
+        follower_mode_minutes, format_follow_duration, parse_switch, room_change,
+        slow_mode_seconds, DEFAULT_SLOW_SECONDS,
+    },
+    color::{chat_color_names, nearest_color_name, parse_chat_color},
+    confirmation::{Confirmations, CONFIRMATION_WINDOW},
+    counter::CounterAction,
+    counts::{CountKind, StreamCounts, StreamSnapshot},
+    deaths::{DeathCounters, DeathsAction, GameDeaths},
+    dice::{RollAction, RollRecord, RollSummary},
+    duel::{duel_rounds, DuelError, Duels, DUEL_EXPIRY, MAX_DUEL_ROUNDS},
+    emote_set::EmoteSet,
+    gate::{contains_link, Gate, GateConfig, UnavailablePolicy},
+    glossary::Glossary,
+    goal::{self, CachedTotal, GoalAction, GoalKind},
+    golive::{GoLiveAnnouncement, GoLiveState, STREAM_CHECK_INTERVAL},
+    greeting_throttle::{
+        GreetingDecision, GreetingThrottle, ThrottleConfig, ThrottleMode, Transition,
+    },
+    history::{HistoryEntry, MessageHistory, DEFAULT_HISTORY_SIZE},
+    info::{paginate, split_message, InfoConfig, GAME_SECTION, MAX_MESSAGE_LENGTH},
+    mention::{mentions, MentionReply},
+    messages::MessageCatalog,
+    milestone::{MessageMilestones, Milestone},
+    mode_policy::{ModePolicy, ProactiveMessage},
+    perf::{format_duration, CommandTimings, DEFAULT_SLOW_COMMAND},
+    prediction::PredictionAction,
+    protection::{Observation, Offender, ProtectionConfig, RaidProtection},
+    quiz::{QuizQuestion, QuizRound},
+    raffle::{Raffle, RaffleRules},
+    raid::{Raid, RaidRefusal, RAID_DELAY},
+    response::{ResponseRouting, ResponseTarget},
+    roles::{Role, RoleChange, RoleTracker},
+    shoutout::{ShoutoutQueue, ShoutoutSlot, SHOUTOUT_COOLDOWN},
+    stats::{UserStats, MESSAGES_COUNTER},
+    template::{Template, TemplateContext, TemplateError},
+    trending::{PhraseTracker, TRENDING_WINDOW},
+    vod::{fit_title, CachedVideo},
+    ChatBotCommand,
+};
+use crate::connect::{
+    missing_scopes, Alert, BotStatus, ChatBotEvent, ChatSettings, Clip, ClipWindow, Command,
+    CommandPrefixes, CommandType, Emotes, Event, HelixError, HelixMetrics, HelixRequest,
+    HelixResponse, Permission, Prediction, PredictionStatus, ReceiveEvent, RoomState,
+    ScopedFeature, StreamInfo, Subscriptions, TextMessage, UserAge, UserInfo, Video,
+};
+use chrono::{DateTime, Datelike, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::{
-    collections::{HashMap, HashSet},
-    time::Duration,
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
 };
 
+const DEFAULT_UNPARSED_LINES: u32 = 5;
+const DEFAULT_QUIZ_SECONDS: u32 = 30;
+// the number of recent results of `!roll` shown by `!myrolls`
+const ROLL_HISTORY: usize = 5;
+const QUIZ_SECONDS: std::ops::RangeInclusive<u32> = 10..=300;
+// at most one reply to unknown commands in this time, so that they cannot be used to spam the chat
+const UNKNOWN_COMMAND_COOLDOWN: Duration = Duration::from_secs(30);
+// `!ping` blocks the bot until the chat server answers, so it is answered rarely
+const PING_COOLDOWN: Duration = Duration::from_secs(60);
+// the connection is reported as degraded when the round trips to the chat server take longer
+const DEGRADED_LATENCY: Duration = Duration::from_millis(500);
+// the number of recent messages of a user shown by `!context`
+const CONTEXT_MESSAGES: usize = 5;
+// the entries `!audit` shows unless another number is asked for, and at most
+const DEFAULT_AUDIT_ENTRIES: usize = 5;
+const MAX_AUDIT_ENTRIES: usize = 20;
+
+/// The state of the chat bot is kept when the bot reconnects to the chat,
+/// except for the state of the connection and (if configured) the cooldowns.
 #[derive(Debug)]
 pub struct ChatBot {
-    chatters: HashSet<String>, // NOTE: probably replace String with a User struct when we need it.
-    dynamic_commands: HashMap<String, String>,
+    connection: ConnectionState,
+    cooldowns: Cooldowns,
+    session: SessionState,
+    reset_cooldowns_on_connect: bool,
+    dynamic_commands: HashMap<String, Template>,
     repeating_messages: HashMap<String, RepeatingMessage>,
+    counters: HashMap<String, i64>,
+    game_deaths: DeathCounters,
+    messages: MessageCatalog,
+    goals: HashMap<GoalKind, u64>,
+    // the totals for `!followers` and `!subs` with their snapshot at the start of the stream
+    counts: StreamCounts,
+    mention_user: bool,
+    emotes: HashMap<String, String>,
+    usage_stats: HashMap<String, UserStats>,
+    gates: GateConfig,
+    unknown_command_reply: bool,
+    // types of the alerts forwarded to the stream overlays, empty if there are no overlays
+    alert_events: HashSet<String>,
+    alert_min_bits: u64,
+    channel_name: String,
+    // replaces the go live message of the catalog
+    go_live_template: Option<Template>,
+    // sent once when the bot joined the chat after it started, not after reconnects
+    connect_greeting: Option<Template>,
+    // None unless the bot replies to chatters mentioning it
+    mention_reply: Option<MentionReply>,
+    // replies to chat messages matching the patterns
+    autoresponder: Autoresponder,
+    // None unless chatters reaching a number of messages are celebrated
+    message_milestones: Option<MessageMilestones>,
+    // whether announcements of the moderators are handled like chat messages, e.g. for `!commands` in them
+    announcement_triggers: bool,
+    // sent when a viewer shares their watch streak, None to only log it
+    viewer_milestone_reply: Option<Template>,
+    rng: StdRng,
+    quiz_questions: Vec<QuizQuestion>,
+    // by lowercase name
+    quiz_points: HashMap<String, u64>,
+    // highest d20 of `!roll` since the records began
+    roll_record: Option<RollRecord>,
+    // `!so` also sends a native shoutout through the helix api
+    native_shoutout: bool,
+    // the chat colors the bot cycles through, one per stream
+    bot_colors: Vec<String>,
+    // the enabled features which need a scope of the access token, audited by `!scopes`
+    scoped_features: Vec<&'static ScopedFeature>,
+    info: InfoConfig,
+    // lowercase name of the bot user, whose messages are not counted as chatting
+    bot_name: String,
+    // the display name of the bot from USERSTATE, which may differ from the login (e.g. in
+    // another script), None until the chat server sent it
+    bot_display_name: Option<String>,
+    // the channel (without `#`) of the event being handled, the bot's own for events which did
+    // not come from a chat
+    event_channel: String,
+    // user ids of the users who may use the admin commands
+    owners: HashSet<String>,
+    // lowercase names of the users greeted when they first chat
+    regulars: HashSet<String>,
+    // None unless greetings are batched or held back while the chat is busy
+    greeting_throttle: Option<GreetingThrottle>,
+    // None unless the role changes of the chatters are tracked
+    roles: Option<RoleTracker>,
+    mode_policy: ModePolicy,
+    routing: ResponseRouting,
+    // commands written inside chat messages are used up to this permission, None if they are not
+    commands_anywhere: Option<Permission>,
+    // false once a whisper failed because the bot may not whisper, e.g. its account is not verified
+    whispers_available: bool,
+    // cooldowns of single commands by name, shared by all users
+    command_cooldowns: HashMap<String, CommandCooldown>,
+    // names of the commands turned off with `!disable`
+    disabled_commands: HashSet<String>,
+    // commands which only run when they are repeated, e.g. `!removecommand`
+    confirmations: Confirmations,
+    // who used the privileged commands, for `!audit`
+    audit: AuditTrail,
+    // true while the broadcaster paused the timed messages with `!timers off`
+    timers_paused: bool,
+    // emote codes sent instead of the replies to commands in emote-only mode
+    emote_only_fallback: Option<String>,
+    // the non-secret settings, reported by !config
+    config_summary: Option<String>,
+    // messages are logged instead of sent, except those owners asked for
+    dry_run: bool,
+    // the prefixes of commands written in chat messages by channel, which the connector parses
+    // commands with
+    command_prefixes: CommandPrefixes,
+    // None unless the chat is watched for hate raids
+    protection: Option<RaidProtection>,
+    // the last chat messages of each channel, for `!context`
+    history: MessageHistory,
+    glossary: Glossary,
+    // how long the command handlers took, for `!perf`
+    timings: CommandTimings,
+    // the phrases repeated in the chat lately, for `!trending`
+    trending: PhraseTracker,
+}
+
+/// Transient state of the current chat connection, which is stale after a reconnect.
+#[derive(Debug, Default)]
+struct ConnectionState {
+    chatters: HashSet<String>, // NOTE: probably replace String with a User struct when we need it.
+    // chat messages sent since connecting by lowercase name
+    messages: HashMap<String, u32>,
+    room: RoomState,
+    // the bot with its badges in the channel, None until the chat server sent them
+    bot_user: Option<UserInfo>,
+    // the last timer message held back while the chat was restricted
+    suppressed_timer: Option<String>,
+    // the raid started with `!raid`, its countdown ends with the connection
+    raid: Option<Raid>,
+}
+
+/// Transient state since the bot started, which is kept when the bot reconnects but not
+/// persisted: what is going on in the chat, the requests waiting for the helix api and what it
+/// answered.
+#[derive(Debug, Default)]
+struct SessionState {
+    prediction: PredictionState,
+    quiz: Option<QuizRound>,
+    // index of the question asked next, the questions are asked in turn
+    next_quiz_question: usize,
+    duels: Duels,
+    raffle: Option<Raffle>,
+    shoutouts: ShoutoutQueue,
+    // the first user who chatted since the bot started, set only once
+    first_chatter: Option<String>,
+    // lowercase names of the regulars who were already greeted since the bot started
+    greeted: HashSet<String>,
+    // the recent results of `!roll` since the bot started by lowercase name, the oldest first
+    recent_rolls: HashMap<String, VecDeque<i64>>,
+    // the results of `!roll` since the bot started by kind of roll, for `!rollstats`
+    roll_summaries: HashMap<RollAction, RollSummary>,
+    go_live: GoLiveState,
+    // the next stream info is announced, even if the stream did not just go live
+    go_live_forced: bool,
+    // the next stream info is checked for the stream going live, other requests (e.g. `!info`) only want the game
+    go_live_pending: bool,
+    next_bot_color: usize,
+    // changes of the deaths waiting for the category to be looked up
+    pending_deaths: Vec<CounterAction>,
+    // changed usage counters waiting to be persisted by `take_pending_usage`
+    pending_usage: Vec<ChatBotCommand>,
+    // `!info` requests (with the addressed section and the user asking) waiting for the current game
+    pending_info: Vec<(Option<String>, String)>,
+    // gated events (with their channel) waiting for the age of their user
+    pending_gated: HashMap<String, Vec<Event>>,
+    // the users who asked with `!accountage` by the lowercase name of the account asked about
+    pending_account_ages: HashMap<String, Vec<String>>,
+    // the users waiting for the totals of the goals, the latest vod and the top clips
+    pending_goals: HashMap<GoalKind, Vec<String>>,
+    pending_vod: Vec<String>,
+    pending_clips: HashMap<ClipWindow, Vec<String>>,
+    // the users who asked for the native shoutouts by lowercase login, told if a shoutout fails
+    shoutout_invokers: HashMap<String, String>,
+    // a moderator asked for the emotes with `!refreshemotes` and is told the result
+    emotes_refresh_requested: bool,
+    // ages of the users checked by a gate, by lowercase name
+    user_ages: HashMap<String, UserAge>,
+    // name colors of the chatters by lowercase name, empty if they never chose one
+    name_colors: HashMap<String, String>,
+    // the emotes of the channel and the global ones, None until they were fetched
+    emote_set: Option<EmoteSet>,
+    goal_totals: HashMap<GoalKind, CachedTotal>,
+    latest_vod: Option<CachedVideo<Video>>,
+    clips: HashMap<ClipWindow, CachedVideo<Clip>>,
+}
+
+#[derive(Debug, Default)]
+struct Cooldowns {
+    last_unknown_command_reply: Option<Instant>,
+    last_ping: Option<Instant>,
+    // last uses of the commands with a cooldown by name
+    commands: HashMap<String, Instant>,
+}
+
+/// The cooldown of a command, shared by all users.
+#[derive(Debug, Clone, Copy)]
+struct CommandCooldown {
+    duration: Duration,
+    // moderators and the broadcaster may use the command while it is cooling down
+    mods_bypass_cooldown: bool,
 }
 
 #[derive(Debug)]
 struct RepeatingMessage {
     name: String,
-    text: String,
+    text: Template,
     interval: Duration,
     timer_id: Uuid,
 }
 
-const HELP_MESSAGE: &str =
-    "!help: Show this help | !info: Show some information about the chat bot";
-const INFO_MESSAGE: &str =
-    "Hello, my name is TwitchBotanist. I am a twitch chat bot written in Rust. My source code is on GitHub (https://github.com/CaptainCallback/TwitchBotanist). If you want to know what you can ask me, write '!help' into the chat!";
-const NEW_COMMAND_SUCCESSFUL_MESSAGE: &str = "The new command has been defined successfully.";
-const NEW_COMMAND_NO_OPTION_MESSAGE: &str =
-    "newcommand requires at least two options but less were given.";
-const REMOVE_COMMAND_NO_OPTION_MESSAGE: &str =
-    "removecommand requires at least one option but none was given.";
-const REMOVE_COMMAND_SUCCESSFUL_MESSAGE: &str = "The command has been removed successfully.";
-const DENIED_MESSAGE: &str = "Denied: i ought to !slap you...";
-const DISCORD_MESSAGE: &str =
-    "You can join me on discord for news and updates here: https://discord.gg/qM6DTTQxDV";
-
-fn str_msg(string: &str) -> Option<ChatBotCommand> {
-    Some(ChatBotCommand::SendMessage(string.to_string()))
+#[derive(Debug, Default, PartialEq)]
+enum PredictionState {
+    #[default]
+    Idle,
+    // waiting for twitch to create the prediction
+    Starting,
+    Running(Prediction),
 }
 
 impl ChatBot {
     pub fn new() -> Self {
         Self {
-            chatters: HashSet::default(),
+            connection: ConnectionState::default(),
+            cooldowns: Cooldowns::default(),
+            session: SessionState::default(),
+            reset_cooldowns_on_connect: false,
             dynamic_commands: HashMap::default(),
             repeating_messages: HashMap::default(),
+            counters: HashMap::default(),
+            game_deaths: DeathCounters::default(),
+            messages: MessageCatalog::default(),
+            goals: HashMap::default(),
+            counts: StreamCounts::default(),
+            mention_user: false,
+            emotes: HashMap::default(),
+            usage_stats: HashMap::default(),
+            gates: GateConfig::default(),
+            unknown_command_reply: false,
+            alert_events: HashSet::default(),
+            alert_min_bits: 0,
+            channel_name: String::new(),
+            go_live_template: None,
+            connect_greeting: None,
+            mention_reply: None,
+            autoresponder: Autoresponder::default(),
+            message_milestones: None,
+            announcement_triggers: false,
+            viewer_milestone_reply: None,
+            rng: StdRng::from_entropy(),
+            quiz_questions: Vec::default(),
+            quiz_points: HashMap::default(),
+            roll_record: None,
+            native_shoutout: false,
+            scoped_features: Vec::new(),
+            bot_colors: Vec::new(),
+            info: InfoConfig::default(),
+            bot_name: String::new(),
+            bot_display_name: None,
+            event_channel: String::new(),
+            owners: HashSet::default(),
+            regulars: HashSet::default(),
+            greeting_throttle: None,
+            roles: None,
+            mode_policy: ModePolicy::default(),
+            routing: ResponseRouting::default(),
+            commands_anywhere: None,
+            whispers_available: true,
+            command_cooldowns: HashMap::default(),
+            disabled_commands: HashSet::default(),
+            confirmations: Confirmations::default(),
+            audit: AuditTrail::default(),
+            timers_paused: false,
+            emote_only_fallback: None,
+            config_summary: None,
+            dry_run: false,
+            command_prefixes: CommandPrefixes::default(),
+            protection: None,
+            history: MessageHistory::new(DEFAULT_HISTORY_SIZE),
+            glossary: Glossary::default(),
+            timings: CommandTimings::new(DEFAULT_SLOW_COMMAND),
+            trending: PhraseTracker::default(),
         }
     }
 
-    fn handle_command(&mut self, command: Command) -> Option<ChatBotCommand> {
-        println!("Executing this command: {:#?}", command);
+    /// Starts configuring a chat bot with the defaults of `new()`.
+    pub fn builder() -> ChatBotBuilder {
+        ChatBotBuilder { bot: Self::new() }
+    }
+
+    // only the transient state is reset, commands, counters and all other registries are kept
+    fn handle_connected(&mut self) -> Option<ChatBotCommand> {
+        self.connection = ConnectionState::default();
+        if self.reset_cooldowns_on_connect {
+            self.cooldowns = Cooldowns::default();
+        }
+        let greeting = self.connect_greeting.take()?;
+        let context = TemplateContext {
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        Some(ChatBotCommand::SendMessage(
+            greeting.render(&context, &mut self.rng),
+        ))
+    }
+
+    /// Clears the transient state (chatters, cooldowns, pending confirmations, the greeting
+    /// throttle, raffle, quiz, duels and queued shoutouts) for a clean slate,
+    /// keeping the connection and all persistent data.
+    /// A prediction starting or running on twitch is kept, so that it can still be resolved.
+    pub fn reset_transient_state(&mut self) {
+        self.connection.chatters.clear();
+        self.connection.messages.clear();
+        self.connection.suppressed_timer = None;
+        self.cooldowns = Cooldowns::default();
+        self.session.raffle = None;
+        self.session.quiz = None;
+        self.session.duels = Duels::default();
+        self.session.shoutouts = ShoutoutQueue::default();
+        if let Some(mention_reply) = &mut self.mention_reply {
+            mention_reply.reset();
+        }
+        self.autoresponder.reset();
+        self.confirmations.reset();
+        if let Some(greeting_throttle) = &mut self.greeting_throttle {
+            greeting_throttle.reset();
+        }
+    }
+
+    fn handle_bot_reset(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        self.reset_transient_state();
+        self.msg("botreset_done")
+    }
+
+    fn msg(&self, key: &str) -> Option<ChatBotCommand> {
+        Some(ChatBotCommand::SendMessage(self.messages.text(key, &[])))
+    }
+
+    fn invalid_template(&self, err: TemplateError) -> Option<ChatBotCommand> {
+        Some(ChatBotCommand::SendMessage(
+            self.messages
+                .text("template_invalid", &[("error", &err.to_string())]),
+        ))
+    }
+
+    fn counter_message(&self, counter_name: &str, value: i64) -> String {
+        self.messages
+            .plural("counter_value", value, &[("name", counter_name)])
+    }
+
+    fn handle_counter(&mut self, command: Command) -> Option<ChatBotCommand> {
         use ChatBotCommand::*;
-        match command.kind {
-            CommandType::Discord => str_msg(DISCORD_MESSAGE),
-            CommandType::Help => str_msg(HELP_MESSAGE),
-            CommandType::Info => str_msg(INFO_MESSAGE),
-            CommandType::Slap => {
-                println!("Slapping one of these guys \n{:#?}", self.chatters);
-                // Notice how we can now do everything in a single expression
-                // because we removed the IO from this place
-                let slapping_user = command.user.name;
-                println!("This guy specifically : {}", &slapping_user);
-                command
-                    .options
-                    .get(0)
-                    .and_then(|slapped_user| self.chatters.get(slapped_user))
-                    .map(|slapped_user| {
-                        SendMessage(format!(
-                            "{} slaps {} around a bit with a large trout",
-                            slapping_user, slapped_user
-                        ))
-                    })
+        let counter_name = match command.options.first() {
+            Some(name) => name.to_owned(),
+            None => return self.msg("counter_no_option"),
+        };
+        let action = match CounterAction::parse(&command.options[1..]) {
+            Some(action) => action,
+            None => return self.msg("counter_usage"),
+        };
+        if action.is_mutation() && !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let current_value = self.counters.get(&counter_name).copied().unwrap_or(0);
+        let new_value = match action {
+            CounterAction::Show => {
+                return Some(SendMessage(
+                    self.counter_message(&counter_name, current_value),
+                ))
             }
-            CommandType::NewCommand => {
-                if command.user.has_elevated_rights() {
-                    if command.options.len() < 2 {
-                        str_msg(NEW_COMMAND_NO_OPTION_MESSAGE)
-                    } else {
-                        let new_command_name = &command.options[0];
-                        let new_command_message = command.options[1..].join(" ");
-                        self.dynamic_commands
-                            .insert(new_command_name.to_owned(), new_command_message);
-                        str_msg(NEW_COMMAND_SUCCESSFUL_MESSAGE)
-                    }
-                } else {
-                    str_msg(DENIED_MESSAGE)
-                }
+            CounterAction::Add(change) => current_value.saturating_add(change),
+            CounterAction::Set(value) => value,
+        };
+        self.counters.insert(counter_name.to_owned(), new_value);
+        Some(MultipleCommands(vec![
+            SendMessage(self.counter_message(&counter_name, new_value)),
+            StoreCounter {
+                name: counter_name,
+                value: new_value,
+            },
+        ]))
+    }
+
+    fn handle_deaths(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let action = match DeathsAction::parse(&command.options) {
+            Some(DeathsAction::Game(name)) => {
+                return Some(ChatBotCommand::SendMessage(
+                    match self.game_deaths.find(&name) {
+                        Some(game) => self.messages.plural(
+                            "deaths_game",
+                            game.deaths,
+                            &[("game", &game.name)],
+                        ),
+                        None => self
+                            .messages
+                            .text("deaths_game_unknown", &[("game", &name)]),
+                    },
+                ))
             }
-            CommandType::RemoveCommand => {
-                if command.user.has_elevated_rights() {
-                    if command.options.is_empty() {
-                        str_msg(REMOVE_COMMAND_NO_OPTION_MESSAGE)
-                    } else {
-                        let command_name = &command.options[0];
-                        self.dynamic_commands.remove(command_name);
-                        str_msg(REMOVE_COMMAND_SUCCESSFUL_MESSAGE)
-                    }
-                } else {
-                    str_msg(DENIED_MESSAGE)
+            Some(DeathsAction::Counter(action)) => action,
+            None => return self.msg("deaths_usage"),
+        };
+        if action.is_mutation() && !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        if !self.game_deaths.is_stale(Instant::now()) {
+            return self.count_deaths(action);
+        }
+        self.session.pending_deaths.push(action);
+        // the category is looked up only once for all waiting changes
+        (self.session.pending_deaths.len() == 1)
+            .then_some(ChatBotCommand::HelixRequest(HelixRequest::StreamInfo))
+    }
+
+    fn count_deaths(&mut self, action: CounterAction) -> Option<ChatBotCommand> {
+        let current = match self.game_deaths.current() {
+            Some(game) => game.deaths,
+            None => return self.msg("deaths_no_game"),
+        };
+        let deaths = match action {
+            CounterAction::Show => current,
+            CounterAction::Add(change) => current.saturating_add(change),
+            CounterAction::Set(value) => value,
+        };
+        let (game_id, game) = self.game_deaths.set_current(deaths)?;
+        let message = ChatBotCommand::SendMessage(self.messages.plural(
+            "deaths",
+            game.deaths,
+            &[
+                ("game", &game.name),
+                ("total", &self.game_deaths.total().to_string()),
+            ],
+        ));
+        if !action.is_mutation() {
+            return Some(message);
+        }
+        Some(ChatBotCommand::MultipleCommands(vec![
+            message,
+            ChatBotCommand::StoreGameDeaths { game_id, game },
+        ]))
+    }
+
+    fn handle_prediction(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let action = match PredictionAction::parse(&command.quoted_options()) {
+            Some(action) => action,
+            None => return self.msg("prediction_usage"),
+        };
+        match (action, &self.session.prediction) {
+            (
+                PredictionAction::Start {
+                    title,
+                    outcomes,
+                    window,
+                },
+                PredictionState::Idle,
+            ) => {
+                self.session.prediction = PredictionState::Starting;
+                Some(ChatBotCommand::HelixRequest(
+                    HelixRequest::CreatePrediction {
+                        title,
+                        outcomes,
+                        window,
+                    },
+                ))
+            }
+            (PredictionAction::Start { .. }, _) => self.msg("prediction_already_active"),
+            (PredictionAction::Lock, PredictionState::Running(prediction)) => {
+                Some(ChatBotCommand::HelixRequest(HelixRequest::LockPrediction {
+                    id: prediction.id.to_owned(),
+                }))
+            }
+            (PredictionAction::Resolve(outcome), PredictionState::Running(prediction)) => {
+                match prediction.outcome_ids.get(outcome - 1) {
+                    Some(outcome_id) => Some(ChatBotCommand::HelixRequest(
+                        HelixRequest::ResolvePrediction {
+                            id: prediction.id.to_owned(),
+                            winning_outcome_id: outcome_id.to_owned(),
+                        },
+                    )),
+                    None => self.msg("prediction_invalid_outcome"),
                 }
             }
+            (_, _) => self.msg("prediction_not_active"),
+        }
+    }
 
-            CommandType::NewRepeating => {
-                if command.user.has_elevated_rights() {
-                    if command.options.len() < 2 {
-                        // TODO: set the correct message here
-                        str_msg(NEW_COMMAND_NO_OPTION_MESSAGE)
-                    } else {
-                        let message_name = &command.options[0];
-                        if let Ok(seconds) = &command.options[1].parse() {
-                            let interval = Duration::from_secs(*seconds);
-                            let id = Uuid::new_v4();
-                            self.repeating_messages.insert(
-                                message_name.to_string(),
-                                RepeatingMessage {
-                                    name: message_name.to_string(),
-                                    text: command.options[2..].join(" "),
-                                    interval,
-                                    timer_id: id,
-                                },
-                            );
-                            // TODO: set the correct message here
-                            Some(MultipleCommands(vec![
-                                ChatBotCommand::SendMessage(
-                                    NEW_COMMAND_SUCCESSFUL_MESSAGE.to_string(),
-                                ),
-                                TimedCallback {
-                                    duration: interval,
-                                    event: ChatBotEvent::TimedMessage(message_name.to_string(), id),
-                                },
-                            ]))
-                        } else {
-                            // TODO: set the correct message here
-                            str_msg(NEW_COMMAND_NO_OPTION_MESSAGE)
-                        }
+    fn handle_prediction_response(
+        &mut self,
+        response: Result<Prediction, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        match response {
+            Ok(prediction) => {
+                let message = match prediction.status {
+                    PredictionStatus::Active => "prediction_started",
+                    PredictionStatus::Locked => "prediction_locked",
+                    PredictionStatus::Resolved => "prediction_resolved",
+                    PredictionStatus::Canceled => "prediction_canceled",
+                };
+                self.session.prediction = match prediction.status {
+                    PredictionStatus::Active | PredictionStatus::Locked => {
+                        PredictionState::Running(prediction)
                     }
-                } else {
-                    str_msg(DENIED_MESSAGE)
+                    PredictionStatus::Resolved | PredictionStatus::Canceled => {
+                        PredictionState::Idle
+                    }
+                };
+                self.msg(message)
+            }
+            Err(error) => {
+                if self.session.prediction == PredictionState::Starting {
+                    self.session.prediction = PredictionState::Idle;
+                }
+                match error {
+                    HelixError::Status(403, _) => self.msg("prediction_not_eligible"),
+                    HelixError::Status(400, message)
+                        if message.to_lowercase().contains("resolved")
+                            || message.to_lowercase().contains("canceled") =>
+                    {
+                        self.session.prediction = PredictionState::Idle;
+                        self.msg("prediction_already_ended")
+                    }
+                    error => self.helix_error_message(error),
                 }
             }
+        }
+    }
 
-            CommandType::RemoveRepeating => {
-                if command.user.has_elevated_rights() {
-                    if command.options.is_empty() {
-                        // TODO: set the correct message here
-                        str_msg(REMOVE_COMMAND_NO_OPTION_MESSAGE)
-                    } else {
-                        let command_name = &command.options[0];
-                        self.repeating_messages.remove(command_name);
-                        // TODO: set the correct message here
-                        str_msg(REMOVE_COMMAND_SUCCESSFUL_MESSAGE)
-                    }
-                } else {
-                    str_msg(DENIED_MESSAGE)
+    fn format_number(&self, number: u64) -> String {
+        goal::format_number(number, &self.messages.text("thousands_separator", &[]))
+    }
+
+    fn goal_message(&self, kind: GoalKind, current: u64) -> Option<ChatBotCommand> {
+        let target = *self.goals.get(&kind)?;
+        let remaining = target.saturating_sub(current);
+        let state = if remaining == 0 {
+            "reached"
+        } else {
+            "progress"
+        };
+        Some(ChatBotCommand::SendMessage(self.messages.text(
+            &format!("{}_{}", kind.name(), state),
+            &[
+                ("current", &self.format_number(current)),
+                ("target", &self.format_number(target)),
+                (
+                    "percent",
+                    &(current.saturating_mul(100) / target).to_string(),
+                ),
+                ("remaining", &self.format_number(remaining)),
+            ],
+        )))
+    }
+
+    fn handle_goal(&mut self, kind: GoalKind, command: Command) -> Option<ChatBotCommand> {
+        let action = match GoalAction::parse(&command.options) {
+            Some(action) => action,
+            None => return self.msg("goal_usage"),
+        };
+        match action {
+            GoalAction::Set(target) => {
+                if !command.user.has_elevated_rights() {
+                    return self.msg("denied");
                 }
+                self.goals.insert(kind, target);
+                Some(ChatBotCommand::MultipleCommands(vec![
+                    ChatBotCommand::SendMessage(self.messages.text(
+                        &format!("{}_set", kind.name()),
+                        &[("target", &self.format_number(target))],
+                    )),
+                    ChatBotCommand::StoreGoal { kind, target },
+                ]))
+            }
+            GoalAction::Show if !self.goals.contains_key(&kind) => {
+                self.msg(&format!("{}_no_target", kind.name()))
+            }
+            GoalAction::Show => match self.session.goal_totals.get(&kind) {
+                Some(cached) if cached.is_fresh() => self.goal_message(kind, cached.total),
+                _ => {
+                    let users = self.session.pending_goals.entry(kind).or_default();
+                    await_reply(users, &command.user.name).then(|| {
+                        ChatBotCommand::HelixRequest(match kind {
+                            GoalKind::Followers => HelixRequest::FollowerTotal,
+                            GoalKind::SubPoints => HelixRequest::SubPointTotal,
+                        })
+                    })
+                }
+            },
+        }
+    }
+
+    fn handle_goal_response(
+        &mut self,
+        kind: GoalKind,
+        response: Result<u64, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let users = self.session.pending_goals.remove(&kind).unwrap_or_default();
+        let reply = match response {
+            Ok(total) => {
+                self.session
+                    .goal_totals
+                    .insert(kind, CachedTotal::new(total));
+                self.goal_message(kind, total)
             }
+            Err(error) if kind == GoalKind::SubPoints && error.is_missing_scope() => {
+                self.msg("subgoal_missing_scope")
+            }
+            Err(error) => self.helix_error_message(error),
+        };
+        self.mention_waiting(&users, reply)
+    }
+
+    fn count_request(kind: CountKind) -> ChatBotCommand {
+        ChatBotCommand::HelixRequest(match kind {
+            CountKind::Followers => HelixRequest::FollowerCount,
+            CountKind::Subscribers => HelixRequest::Subscriptions,
+        })
+    }
 
-            CommandType::Dynamic(command_name) => self
-                .dynamic_commands
-                .get(&command_name)
-                .map(String::from)
-                .map(SendMessage),
+    // a cached total is answered right away, otherwise the response answers everyone asking
+    fn handle_count(&mut self, kind: CountKind, command: Command) -> Option<ChatBotCommand> {
+        if let Some(total) = self.counts.cached(kind) {
+            return self.count_message(kind, total);
         }
+        self.counts
+            .await_reply(kind, &command.user.name)
+            .then(|| Self::count_request(kind))
     }
 
-    pub fn handle_event(&mut self, event: ChatBotEvent) -> Option<ChatBotCommand> {
-        use ChatBotCommand::*;
-        match event {
-            ChatBotEvent::Command(command) => self.handle_command(command),
-            ChatBotEvent::Join(user) => {
-                println!("{:?} joined", &user);
-                self.chatters.insert(user);
-                None
+    fn handle_subs(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        self.handle_count(CountKind::Subscribers, command)
+    }
+
+    fn count_message(&self, kind: CountKind, total: u64) -> Option<ChatBotCommand> {
+        let since_start = self.counts.since_start(kind, total);
+        let key = match (kind, since_start) {
+            (CountKind::Followers, None) => "followers",
+            (CountKind::Followers, Some(_)) => "followers_today",
+            (CountKind::Subscribers, None) => "subs",
+            (CountKind::Subscribers, Some(_)) => "subs_today",
+        };
+        let delta = since_start.map_or(String::new(), |delta| {
+            let sign = if delta < 0 { "-" } else { "+" };
+            format!("{}{}", sign, self.format_number(delta.unsigned_abs()))
+        });
+        Some(ChatBotCommand::SendMessage(self.messages.plural(
+            key,
+            total as i64,
+            &[
+                ("total", &self.format_number(total)),
+                ("points", &self.format_number(self.counts.sub_points())),
+                ("delta", &delta),
+            ],
+        )))
+    }
+
+    // the response is only answered if the total was asked for, it may be for the snapshot
+    fn handle_count_response(
+        &mut self,
+        kind: CountKind,
+        response: Result<u64, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let users = self.counts.take_reply(kind);
+        match response {
+            Ok(total) => {
+                let mut commands = Vec::new();
+                if let Some((stream_id, snapshot)) = self.counts.record(kind, total) {
+                    commands.push(ChatBotCommand::StoreStreamSnapshot {
+                        stream_id,
+                        snapshot,
+                    });
+                }
+                if !users.is_empty() {
+                    let message = self.count_message(kind, total);
+                    commands.extend(self.mention_waiting(&users, message));
+                }
+                match commands.len() {
+                    0 => None,
+                    1 => commands.pop(),
+                    _ => Some(ChatBotCommand::MultipleCommands(commands)),
+                }
             }
-            ChatBotEvent::Part(user) => {
-                println!("{:?} parted", &user);
-                self.chatters.remove(&user);
+            Err(error) if users.is_empty() => {
+                println!("Could not fetch the {:?} total: {:?}", kind, error);
                 None
             }
-            ChatBotEvent::TextMessage(tm) => {
-                Some(LogTextMessage(format!("{}: {}", &tm.user.name, &tm.text)))
+            Err(error) if kind == CountKind::Subscribers && error.is_missing_scope() => {
+                self.mention_waiting(&users, self.msg("subgoal_missing_scope"))
             }
-            ChatBotEvent::TimedMessage(message_name, id) => {
-                self.repeating_messages.get(&message_name).and_then(|msg| {
-                    if id == msg.timer_id {
-                        Some(MultipleCommands(vec![
-                            ChatBotCommand::SendMessage(msg.text.to_owned()),
-                            TimedCallback {
-                                duration: msg.interval,
-                                event: ChatBotEvent::TimedMessage(msg.name.to_owned(), id),
-                            },
-                        ]))
-                    } else {
-                        None
-                    }
-                })
+            Err(error) => self.mention_waiting(&users, self.helix_error_message(error)),
+        }
+    }
+
+    fn handle_subscriptions(
+        &mut self,
+        response: Result<Subscriptions, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        if let Ok(subscriptions) = &response {
+            self.counts.record_sub_points(subscriptions.points);
+        }
+        self.handle_count_response(
+            CountKind::Subscribers,
+            response.map(|subscriptions| subscriptions.total),
+        )
+    }
+
+    /// Counts a chat message of the user and the command used with it, if any.
+    /// Returns the celebration of the message milestone the user reached with it.
+    fn record_usage(
+        &mut self,
+        user_name: &str,
+        command_name: Option<&str>,
+    ) -> Option<ChatBotCommand> {
+        let user = user_name.to_lowercase();
+        let user_stats = self.usage_stats.entry(user.to_owned()).or_default();
+        user_stats.messages += 1;
+        let messages = user_stats.messages;
+        let mut counts = vec![(MESSAGES_COUNTER.to_owned(), user_stats.messages)];
+        if let Some(command_name) = command_name {
+            let count = user_stats
+                .commands
+                .entry(command_name.to_owned())
+                .or_default();
+            *count += 1;
+            counts.push((command_name.to_owned(), *count));
+        }
+        self.session.pending_usage.push(ChatBotCommand::StoreUsage {
+            channel: self.event_channel.to_owned(),
+            user,
+            counts,
+        });
+        self.celebrate_milestone(user_name, messages)
+    }
+
+    // a hate raid must not be celebrated, its chatters reach their milestones after it
+    fn celebrate_milestone(&mut self, user_name: &str, messages: u64) -> Option<ChatBotCommand> {
+        if self.is_protected() {
+            return None;
+        }
+        let milestone = self
+            .message_milestones
+            .as_mut()?
+            .reach(user_name, messages)?;
+        let user = user_name.to_lowercase();
+        let milestone = match milestone {
+            Milestone::Celebrated(milestone) => milestone,
+            Milestone::Skipped(milestone) => {
+                println!(
+                    "Not celebrating message {} of {}, the celebrations of this stream are used up",
+                    milestone, user_name
+                );
+                return Some(ChatBotCommand::StoreMessageMilestone {
+                    channel: self.event_channel.to_owned(),
+                    user,
+                    milestone,
+                });
             }
+        };
+        let formatted_milestone = self.format_number(milestone);
+        let formatted_messages = self.format_number(messages);
+        let context = TemplateContext {
+            values: &[
+                ("user", user_name),
+                ("milestone", &formatted_milestone),
+                ("messages", &formatted_messages),
+            ],
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        let text = self
+            .message_milestones
+            .as_ref()?
+            .template()
+            .render(&context, &mut self.rng);
+        Some(ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::StoreMessageMilestone {
+                channel: self.event_channel.to_owned(),
+                user,
+                milestone,
+            },
+            ChatBotCommand::SendMessage(text),
+        ]))
+    }
+
+    /// Returns the audit entries finished by the last events, which have to be written.
+    /// They are not part of the replies, so that dropped replies (e.g. in restricted modes)
+    /// never drop an entry.
+    pub fn take_audit_entries(&mut self) -> Option<ChatBotCommand> {
+        let entries = self.audit.take_unwritten();
+        if entries.is_empty() {
+            None
+        } else {
+            Some(ChatBotCommand::MultipleCommands(
+                entries.into_iter().map(ChatBotCommand::Audit).collect(),
+            ))
         }
     }
-}
 
-#[cfg(test)]
-mod testing {
-    use super::*;
-    use crate::connect::{Badge, TextMessage, UserInfo};
+    /// Returns the usage counters changed by the last events, which have to be persisted.
+    /// They are not part of the replies, so that storing them never delays a reply.
+    pub fn take_pending_usage(&mut self) -> Option<ChatBotCommand> {
+        if self.session.pending_usage.is_empty() {
+            None
+        } else {
+            Some(ChatBotCommand::MultipleCommands(
+                self.session.pending_usage.drain(..).collect(),
+            ))
+        }
+    }
 
-    // It's now easy to test without connecting
-    #[test]
-    fn test_join() {
-        let mut bot = ChatBot::new();
-        let result = bot.handle_event(ChatBotEvent::Join(String::from("Carkhy")));
-        assert!(matches!(result, None));
-        assert_eq!(bot.chatters.len(), 1);
-        assert_eq!(bot.chatters.get("Carkhy").unwrap(), "Carkhy");
+    fn handle_stats(&self, command: Command) -> Option<ChatBotCommand> {
+        let (user_name, own_stats) = match command.arg_user(0) {
+            Some(_) if !command.user.has_elevated_rights() => return self.msg("denied"),
+            Some(user_name) => (user_name, false),
+            None => (command.user.name.as_str(), true),
+        };
+        let user_stats = match self.usage_stats.get(&user_name.to_lowercase()) {
+            Some(user_stats) => user_stats,
+            None => {
+                return Some(ChatBotCommand::SendMessage(
+                    self.messages
+                        .text("stats_unknown_user", &[("user", user_name)]),
+                ))
+            }
+        };
+        let messages = self.format_number(user_stats.messages);
+        let (key, favorite, count) = match user_stats.favorite() {
+            Some((favorite, count)) => ("stats_summary", favorite, self.format_number(count)),
+            None => ("stats_summary_no_commands", "", String::new()),
+        };
+        let key = if own_stats {
+            key.to_owned()
+        } else {
+            key.replacen("stats_", "stats_other_", 1)
+        };
+        Some(ChatBotCommand::SendMessage(self.messages.text(
+            &key,
+            &[
+                ("user", user_name),
+                ("messages", &messages),
+                ("command", favorite),
+                ("count", &count),
+            ],
+        )))
     }
 
-    #[test]
-    fn test_part() {
-        let mut bot = ChatBot::new();
-        bot.handle_event(ChatBotEvent::Join(String::from("Carkhy")));
-        let result = bot.handle_event(ChatBotEvent::Part(String::from("Carkhy")));
-        assert!(matches!(result, None));
-        assert_eq!(bot.chatters.len(), 0);
-        assert!(matches!(bot.chatters.get("Carkhy"), None));
+    // moderators are exempt from all gates
+    fn command_gate(&self, command: &Command) -> Option<Gate> {
+        if command.user.has_elevated_rights() {
+            return None;
+        }
+        let gate = self.gates.commands.get(command.kind.name()).copied();
+        // a raffle for followers gates entering it like a follow age of 0 minutes
+        match &self.session.raffle {
+            Some(raffle) if command.kind == CommandType::Enter && raffle.rules.followers_only => {
+                let gate = gate.unwrap_or_default();
+                Some(Gate {
+                    min_follow_minutes: Some(gate.min_follow_minutes.unwrap_or_default()),
+                    ..gate
+                })
+            }
+            _ => gate,
+        }
     }
 
-    #[test]
-    fn test_text_message() {
-        let mut bot = ChatBot::new();
-        let result = bot.handle_event(ChatBotEvent::TextMessage(TextMessage {
-            text: "Hello".to_string(),
-            user: UserInfo {
-                name: "Carkhy".to_owned(),
-                badges: HashSet::default(),
-            },
-        }));
-        assert!(
-            matches!(result, Some(ChatBotCommand::LogTextMessage(message)) if message == "Carkhy: Hello")
-        );
+    fn link_gate(&self, tm: &TextMessage) -> Option<Gate> {
+        if tm.user.has_elevated_rights() || !contains_link(&tm.text) {
+            return None;
+        }
+        self.gates.links
     }
 
-    #[test]
-    fn invalid_slapping() {
-        let mut bot = ChatBot::new();
-        let result = bot.handle_event(ChatBotEvent::Command(Command {
-            user: UserInfo {
-                name: "CaptainCallback".to_owned(),
-                badges: HashSet::default(),
-            },
-            kind: CommandType::Slap,
-            options: vec!["Carkhy".to_string()],
-        }));
-        assert!(matches!(result, None));
+    fn gate_for(&self, event: &ChatBotEvent) -> Option<Gate> {
+        match event {
+            ChatBotEvent::Command(command) => self.command_gate(command),
+            ChatBotEvent::TextMessage(tm) => self.link_gate(tm),
+            _ => None,
+        }
     }
 
-    #[test]
-    fn valid_slapping_when_abstraction_detected() {
-        let mut bot = ChatBot::new();
-        bot.handle_event(ChatBotEvent::Join(String::from("CaptainCallback")));
-        let result = bot.handle_event(ChatBotEvent::Command(Command {
-            user: UserInfo {
-                name: "Carkhy".to_owned(),
-                badges: HashSet::default(),
-            },
-            kind: CommandType::Slap,
-            options: vec!["CaptainCallback".to_string()],
-        }));
-        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
-                         if message == format!("{} slaps {} around a bit with a large trout", "Carkhy", "CaptainCallback")));
+    /// Checks a gated event against the age of its user.
+    /// If the age is unknown, the event waits until it has been fetched.
+    fn check_gate(&mut self, gate: Gate, user_name: &str, event: Event) -> Option<ChatBotCommand> {
+        let login = user_name.to_lowercase();
+        match self.session.user_ages.get(&login) {
+            Some(age) => {
+                let passed = gate.passes(age, Utc::now());
+                self.finish_gated(event, passed)
+            }
+            None => {
+                let pending = self
+                    .session
+                    .pending_gated
+                    .entry(login.to_owned())
+                    .or_default();
+                pending.push(event);
+                // the age is requested only once for all waiting events of a user
+                (pending.len() == 1 && !self.session.pending_account_ages.contains_key(&login))
+                    .then(|| ChatBotCommand::HelixRequest(HelixRequest::UserAge { login }))
+            }
+        }
     }
 
-    #[test]
-    fn nonmods_cannot_newcommand() {
-        let mut bot = ChatBot::new();
-        let result = bot.handle_event(ChatBotEvent::Command(Command {
-            user: UserInfo {
-                name: "CaptainCallback".to_owned(),
-                badges: HashSet::default(),
-            },
-            kind: CommandType::NewCommand,
-            options: vec!["test".to_string(), "testing".to_string()],
-        }));
-        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
-                         if message == DENIED_MESSAGE));
-        assert!(!bot.dynamic_commands.contains_key("test"));
+    fn finish_gated(&mut self, event: Event, passed: bool) -> Option<ChatBotCommand> {
+        // the event may have waited while events of other channels were handled
+        if let Some(channel) = event.channel {
+            self.event_channel = channel;
+        }
+        match (event.content, passed) {
+            (ChatBotEvent::Command(command), true) => self.handle_command_event(command),
+            (ChatBotEvent::TextMessage(tm), true) => self.handle_passed_message(tm),
+            (ChatBotEvent::Command(command), false) if command.kind == CommandType::Enter => {
+                self.raffle_ineligible(&command.user.name, "raffle_not_follower")
+            }
+            (ChatBotEvent::Command(command), false) => Some(ChatBotCommand::SendMessage(
+                self.messages
+                    .text("gate_blocked", &[("user", &command.user.name)]),
+            )),
+            (ChatBotEvent::TextMessage(tm), false) => Some(ChatBotCommand::SendMessage(
+                self.messages
+                    .text("link_warning", &[("user", &tm.user.name)]),
+            )),
+            _ => None,
+        }
     }
 
-    #[test]
-    fn broadcaster_can_newcommand() {
-        let mut bot = ChatBot::new();
-        let result = bot.handle_event(ChatBotEvent::Command(Command {
-            user: UserInfo {
-                name: "CaptainCallback".to_owned(),
-                badges: HashSet::from([Badge {
-                    name: "broadcaster".to_owned(),
-                    level: 1,
-                }]),
-            },
-            kind: CommandType::NewCommand,
-            options: vec!["test".to_string(), "testing".to_string()],
-        }));
-        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
-                         if message != DENIED_MESSAGE));
-        assert!(bot.dynamic_commands.contains_key("test"));
+    /// Answers a chat message which waited for its gate like a message without a gate: with the
+    /// command written in it, or else with a quip or an automatic response. Everything else
+    /// (e.g. counting the message) was done when it arrived.
+    fn handle_passed_message(&mut self, tm: TextMessage) -> Option<ChatBotCommand> {
+        // a hate raid must not trigger the bot
+        let embedded = match self.is_protected() {
+            true => None,
+            false => self.embedded_command(&tm.text),
+        };
+        if let Some((kind, raw_args)) = embedded {
+            return self.handle_event(Event {
+                channel: Some(self.event_channel.to_owned()),
+                ..ChatBotEvent::Command(Command::new(kind, &raw_args, tm.user, tm.room_id)).into()
+            });
+        }
+        let mut replies: Vec<ChatBotCommand> = self
+            .reply_to_mention(&tm.user, &tm.text)
+            .into_iter()
+            .chain(self.autorespond(&tm.user, &tm.text))
+            .collect();
+        match replies.len() {
+            0 | 1 => replies.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(replies)),
+        }
     }
 
-    #[test]
-    fn mods_can_newcommand() {
-        let mut bot = ChatBot::new();
-        let result = bot.handle_event(ChatBotEvent::Command(Command {
-            user: UserInfo {
+    fn handle_user_age(
+        &mut self,
+        login: String,
+        result: Result<UserAge, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let events = self
+            .session
+            .pending_gated
+            .remove(&login)
+            .unwrap_or_default();
+        let asking = self
+            .session
+            .pending_account_ages
+            .remove(&login)
+            .unwrap_or_default();
+        let mut account_age_reply = None;
+        let unavailable_decision = match result {
+            Ok(age) => {
+                if !asking.is_empty() {
+                    account_age_reply = Some(ChatBotCommand::SendMessage(
+                        self.account_age_message(&login, age.created_at, Utc::now()),
+                    ));
+                }
+                self.session.user_ages.insert(login.to_owned(), age);
+                None
+            }
+            Err(error) => {
+                println!("Could not get age of user {}: {:?}", login, error);
+                if !asking.is_empty() {
+                    account_age_reply = self.helix_error_message(error);
+                }
+                Some(self.gates.unavailable == UnavailablePolicy::Open)
+            }
+        };
+        let mut commands: Vec<ChatBotCommand> = events
+            .into_iter()
+            .filter_map(|event| {
+                let passed = match (unavailable_decision, self.session.user_ages.get(&login)) {
+                    (Some(passed), _) => passed,
+                    (None, Some(age)) => self
+                        .gate_for(&event.content)
+                        .is_none_or(|gate| gate.passes(age, Utc::now())),
+                    (None, None) => false,
+                };
+                self.finish_gated(event, passed)
+            })
+            .collect();
+        commands.extend(self.mention_waiting(&asking, account_age_reply));
+        (!commands.is_empty()).then(|| ChatBotCommand::MultipleCommands(commands))
+    }
+
+    /// The user asked about with `!accountage` and `!namecolor`, None if the user may not ask about
+    /// others. Only moderators and the broadcaster may ask about other users.
+    fn lookup_target(&self, command: &Command) -> Option<String> {
+        let invoker = command.user.name.to_lowercase();
+        match command.options.first() {
+            Some(target) => {
+                let target = target.trim_start_matches('@').to_lowercase();
+                (target == invoker || command.user.has_elevated_rights()).then_some(target)
+            }
+            None => Some(invoker),
+        }
+    }
+
+    fn handle_account_age(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let login = match self.lookup_target(&command) {
+            Some(login) => login,
+            None => return self.msg("denied"),
+        };
+        // the ages of the accounts are kept, they are also used by the gates
+        if let Some(age) = self.session.user_ages.get(&login) {
+            return Some(ChatBotCommand::SendMessage(self.account_age_message(
+                &login,
+                age.created_at,
+                Utc::now(),
+            )));
+        }
+        let asking = self
+            .session
+            .pending_account_ages
+            .entry(login.to_owned())
+            .or_default();
+        let requested = !await_reply(asking, &command.user.name)
+            || self.session.pending_gated.contains_key(&login);
+        (!requested).then(|| ChatBotCommand::HelixRequest(HelixRequest::UserAge { login }))
+    }
+
+    /// Tells how old an account is in years and months, or in days if it is younger than a month.
+    fn account_age_message(
+        &self,
+        user: &str,
+        created_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> String {
+        let months = (now.year() - created_at.year()) * 12 + now.month() as i32
+            - created_at.month() as i32
+            - i32::from(now.day() < created_at.day());
+        let age = match (months / 12, months % 12) {
+            (0, 0) => self
+                .messages
+                .plural("age_days", (now - created_at).num_days(), &[]),
+            (0, months) => self.messages.plural("age_months", months.into(), &[]),
+            (years, 0) => self.messages.plural("age_years", years.into(), &[]),
+            (years, months) => self.messages.text(
+                "age_years_months",
+                &[
+                    (
+                        "years",
+                        &self.messages.plural("age_years", years.into(), &[]),
+                    ),
+                    (
+                        "months",
+                        &self.messages.plural("age_months", months.into(), &[]),
+                    ),
+                ],
+            ),
+        };
+        self.messages
+            .text("account_age", &[("user", user), ("age", &age)])
+    }
+
+    fn handle_name_color(&self, command: Command) -> Option<ChatBotCommand> {
+        let name = match self.lookup_target(&command) {
+            Some(name) => name,
+            None => return self.msg("denied"),
+        };
+        let text = match self.session.name_colors.get(&name) {
+            Some(color) => match nearest_color_name(color) {
+                Some(color_name) => self.messages.text(
+                    "namecolor",
+                    &[("user", &name), ("color", color), ("name", color_name)],
+                ),
+                // twitch has not assigned a color yet, the chat clients pick one themselves
+                None => self.messages.text("namecolor_none", &[("user", &name)]),
+            },
+            None => self.messages.text("namecolor_unknown", &[("user", &name)]),
+        };
+        Some(ChatBotCommand::SendMessage(text))
+    }
+
+    fn record_name_color(&mut self, user_name: &str, color: Option<String>) {
+        if let Some(color) = color {
+            self.session
+                .name_colors
+                .insert(user_name.to_lowercase(), color);
+        }
+    }
+
+    fn helix_error_message(&self, error: HelixError) -> Option<ChatBotCommand> {
+        match error {
+            HelixError::Status(401, _) => self.msg("helix_unauthorized"),
+            HelixError::Status(_, message) => Some(ChatBotCommand::SendMessage(
+                self.messages.text("helix_error", &[("message", &message)]),
+            )),
+            HelixError::Unavailable(_) => self.msg("helix_unavailable"),
+        }
+    }
+
+    /// Mentions the users who waited for a reply from twitch, like in the direct replies to
+    /// their commands.
+    fn mention_waiting(
+        &self,
+        users: &[String],
+        reply: Option<ChatBotCommand>,
+    ) -> Option<ChatBotCommand> {
+        match reply {
+            Some(reply) if self.mention_user && !users.is_empty() => {
+                Some(mention(&users.join(" @"), reply))
+            }
+            reply => reply,
+        }
+    }
+
+    fn handle_debug(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        match command.options.first().map(String::as_str) {
+            Some("unparsed") if command.options.len() == 1 || command.arg_u32(1).is_some() => {
+                let count = command.arg_u32(1).unwrap_or(DEFAULT_UNPARSED_LINES) as usize;
+                Some(ChatBotCommand::MultipleCommands(vec![
+                    ChatBotCommand::LogUnparsedLines(count),
+                    ChatBotCommand::SendMessage(
+                        self.messages
+                            .text("debug_unparsed_logged", &[("count", &count.to_string())]),
+                    ),
+                ]))
+            }
+            _ => self.msg("debug_usage"),
+        }
+    }
+
+    fn handle_alert(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        match command.options.first().map(String::as_str) {
+            Some("test") if command.options.len() == 1 => {
+                let alert = Alert::Test {
+                    user: command.user.name,
+                };
+                match self.forwarded_alert(alert) {
+                    Some(alert) => Some(ChatBotCommand::MultipleCommands(vec![
+                        alert,
+                        ChatBotCommand::SendMessage(self.messages.text("alert_test_sent", &[])),
+                    ])),
+                    None => self.msg("alert_test_disabled"),
+                }
+            }
+            _ => self.msg("alert_usage"),
+        }
+    }
+
+    fn forwarded_alert(&self, alert: Alert) -> Option<ChatBotCommand> {
+        let big_enough = match alert {
+            Alert::Cheer { bits, .. } => bits >= self.alert_min_bits,
+            _ => true,
+        };
+        if big_enough && self.alert_events.contains(alert.kind()) {
+            Some(ChatBotCommand::Alert(alert))
+        } else {
+            None
+        }
+    }
+
+    // cheers and redemptions are chat messages as well, the alert is sent in addition
+    fn handle_alert_message(
+        &mut self,
+        message: TextMessage,
+        alert: Alert,
+    ) -> Option<ChatBotCommand> {
+        let reply = self.handle_event(ChatBotEvent::TextMessage(message));
+        match (reply, self.forwarded_alert(alert)) {
+            (Some(ChatBotCommand::MultipleCommands(mut commands)), Some(alert)) => {
+                commands.push(alert);
+                Some(ChatBotCommand::MultipleCommands(commands))
+            }
+            (Some(reply), Some(alert)) => {
+                Some(ChatBotCommand::MultipleCommands(vec![reply, alert]))
+            }
+            (reply, alert) => reply.or(alert),
+        }
+    }
+
+    // the bot's own announcements never trigger it, so that it cannot answer itself in a loop
+    fn handle_announcement(&mut self, message: TextMessage, color: &str) -> Option<ChatBotCommand> {
+        let mut commands = vec![ChatBotCommand::LogTextMessage(format!(
+            "[announcement {}] {}: {}",
+            color.to_lowercase(),
+            message.user.name,
+            message.text
+        ))];
+        if !self.announcement_triggers || message.user.name.eq_ignore_ascii_case(&self.bot_name) {
+            return commands.pop();
+        }
+        // the announcement was logged already, so the log of the chat message is dropped
+        if let Some(ChatBotCommand::MultipleCommands(replies)) =
+            self.handle_event(ChatBotEvent::TextMessage(message))
+        {
+            commands.extend(replies.into_iter().skip(1));
+        }
+        match commands.len() {
+            1 => commands.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(commands)),
+        }
+    }
+
+    fn handle_viewer_milestone(&mut self, user: &UserInfo, streak: u64) -> Option<ChatBotCommand> {
+        let log = ChatBotCommand::LogTextMessage(format!(
+            "[milestone] {} watched {} consecutive streams",
+            user.name, streak
+        ));
+        let template = match &self.viewer_milestone_reply {
+            Some(template) if !user.name.eq_ignore_ascii_case(&self.bot_name) => template,
+            _ => return Some(log),
+        };
+        let streak = streak.to_string();
+        let context = TemplateContext {
+            user: Some(user),
+            values: &[("user", &user.name), ("streak", &streak)],
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        let reply = template.render(&context, &mut self.rng);
+        Some(ChatBotCommand::MultipleCommands(vec![
+            log,
+            ChatBotCommand::SendMessage(reply),
+        ]))
+    }
+
+    fn handle_go_live(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        self.session.go_live_forced = true;
+        self.session.go_live_pending = true;
+        Some(ChatBotCommand::HelixRequest(HelixRequest::StreamInfo))
+    }
+
+    // checks the stream now and again after the interval
+    fn check_stream(&mut self) -> ChatBotCommand {
+        self.session.go_live_pending = true;
+        ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::HelixRequest(HelixRequest::StreamInfo),
+            ChatBotCommand::TimedCallback {
+                duration: STREAM_CHECK_INTERVAL,
+                event: ChatBotEvent::CheckStream,
+            },
+        ])
+    }
+
+    fn handle_stream_info(
+        &mut self,
+        response: Result<StreamInfo, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        // the same stream info answers waiting info requests and the go live check
+        let stream = response.as_ref().ok();
+        let mut commands: Vec<ChatBotCommand> = std::mem::take(&mut self.session.pending_info)
+            .into_iter()
+            .filter_map(|(section, user_name)| {
+                let reply = self.info_reply(section.as_deref(), stream);
+                self.mention_waiting(&[user_name], reply)
+            })
+            .collect();
+        // the deaths are counted in the game of the category
+        if let Some(stream) = stream {
+            self.game_deaths
+                .switch(&stream.game_id, &stream.game, Instant::now());
+        }
+        // the totals at the start of a new stream are fetched for the change since then
+        if let Some(stream_id) = stream.and_then(|stream| stream.stream_id.as_ref()) {
+            for kind in self.counts.stream_live(stream_id) {
+                commands.push(Self::count_request(kind));
+            }
+        }
+        // without an answer the deaths are counted in the last known game
+        for action in std::mem::take(&mut self.session.pending_deaths) {
+            commands.extend(self.count_deaths(action));
+        }
+        if std::mem::take(&mut self.session.go_live_pending) {
+            commands.extend(self.handle_go_live_check(response));
+        }
+        match commands.len() {
+            0 => None,
+            1 => commands.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(commands)),
+        }
+    }
+
+    fn handle_go_live_check(
+        &mut self,
+        response: Result<StreamInfo, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let forced = std::mem::take(&mut self.session.go_live_forced);
+        match response {
+            Ok(stream) => {
+                let went_live = self.session.go_live.update(stream.live, Instant::now());
+                if let Some(milestones) = self.message_milestones.as_mut().filter(|_| went_live) {
+                    milestones.new_stream();
+                }
+                let rotation = if went_live {
+                    self.rotate_bot_color()
+                } else {
+                    None
+                };
+                match (forced || went_live, rotation) {
+                    (true, Some(rotation)) => Some(ChatBotCommand::MultipleCommands(vec![
+                        self.announce_go_live(stream),
+                        rotation,
+                    ])),
+                    (true, None) => Some(self.announce_go_live(stream)),
+                    (false, rotation) => rotation,
+                }
+            }
+            Err(error) => {
+                println!("Could not check the stream: {:?}", error);
+                if forced {
+                    self.msg("golive_unavailable")
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Changes the chat color of the bot to the next configured one, if any.
+    fn rotate_bot_color(&mut self) -> Option<ChatBotCommand> {
+        let color = self.bot_colors.get(self.session.next_bot_color)?.to_owned();
+        self.session.next_bot_color = (self.session.next_bot_color + 1) % self.bot_colors.len();
+        Some(ChatBotCommand::HelixRequest(
+            HelixRequest::UpdateChatColor { color },
+        ))
+    }
+
+    fn announce_go_live(&mut self, stream: StreamInfo) -> ChatBotCommand {
+        let url = format!("https://twitch.tv/{}", self.channel_name);
+        let args = [
+            ("title", stream.title.as_str()),
+            ("game", stream.game.as_str()),
+            ("url", url.as_str()),
+        ];
+        let context = TemplateContext {
+            values: &args,
+            ..TemplateContext::default()
+        };
+        let text = match &self.go_live_template {
+            Some(template) => template.render(&context, &mut self.rng),
+            None => self.messages.text("golive_announcement", &args),
+        };
+        ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::SendMessage(text.to_owned()),
+            ChatBotCommand::AnnounceGoLive(GoLiveAnnouncement { text, stream, url }),
+        ])
+    }
+
+    fn handle_quiz(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if command.options.first().map(String::as_str) == Some("points") {
+            let user = command.arg_user(1).unwrap_or(&command.user.name);
+            let points = self
+                .quiz_points
+                .get(&user.to_lowercase())
+                .copied()
+                .unwrap_or_default();
+            return Some(ChatBotCommand::SendMessage(self.messages.text(
+                "quiz_points",
+                &[("user", user), ("points", &points.to_string())],
+            )));
+        }
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let seconds = match command.options.len() {
+            0 => DEFAULT_QUIZ_SECONDS,
+            1 => match command
+                .arg_u32(0)
+                .filter(|seconds| QUIZ_SECONDS.contains(seconds))
+            {
+                Some(seconds) => seconds,
+                None => return self.msg("quiz_usage"),
+            },
+            _ => return self.msg("quiz_usage"),
+        };
+        if self.session.quiz.is_some() {
+            return self.msg("quiz_running");
+        }
+        let question = match self.quiz_questions.get(self.session.next_quiz_question) {
+            Some(question) => question.clone(),
+            None => return self.msg("quiz_no_questions"),
+        };
+        self.session.next_quiz_question =
+            (self.session.next_quiz_question + 1) % self.quiz_questions.len();
+        let round = QuizRound::new(question);
+        let reply = ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::SendMessage(self.messages.text(
+                "quiz_question",
+                &[
+                    ("question", &round.question.question),
+                    ("options", &round.question.options_text()),
+                    ("seconds", &seconds.to_string()),
+                ],
+            )),
+            ChatBotCommand::TimedCallback {
+                duration: Duration::from_secs(seconds as u64),
+                event: ChatBotEvent::QuizEnd(round.id),
+            },
+        ]);
+        self.session.quiz = Some(round);
+        Some(reply)
+    }
+
+    fn handle_quiz_end(&mut self, id: Uuid) -> Option<ChatBotCommand> {
+        let round = self.session.quiz.take_if(|round| round.id == id)?;
+        let result = round.result();
+        let args = [
+            ("number", round.question.answer.to_string()),
+            ("answer", round.question.correct_option().to_owned()),
+            ("correct", result.correct_users.len().to_string()),
+            ("total", result.answer_count.to_string()),
+            ("percent", result.percent_correct().to_string()),
+        ];
+        let args: Vec<(&str, &str)> = args
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        if result.answer_count == 0 {
+            return Some(ChatBotCommand::SendMessage(
+                self.messages.text("quiz_no_answers", &args),
+            ));
+        }
+        let points = result
+            .correct_users
+            .into_iter()
+            .map(|user| {
+                let points = self.quiz_points.entry(user.to_owned()).or_default();
+                *points += 1;
+                (user, *points)
+            })
+            .collect();
+        Some(ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::SendMessage(self.messages.text("quiz_result", &args)),
+            ChatBotCommand::StoreQuizPoints(points),
+        ]))
+    }
+
+    fn handle_roll(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let action = match RollAction::parse(&command.options) {
+            Some(action) => action,
+            None => return self.msg("roll_usage"),
+        };
+        let roll = action.roll(&mut self.rng);
+        // a fixed result is no roll worth remembering
+        if !matches!(action, RollAction::Degenerate { .. }) {
+            let recent = self
+                .session
+                .recent_rolls
+                .entry(command.user.name.to_lowercase())
+                .or_default();
+            if recent.len() == ROLL_HISTORY {
+                recent.pop_front();
+            }
+            recent.push_back(roll.result);
+            self.session
+                .roll_summaries
+                .entry(action)
+                .or_default()
+                .add(roll.result);
+        }
+        let (key, modifier) = match action {
+            RollAction::Degenerate { sides: 0, .. } => ("roll_d0", 0),
+            RollAction::Degenerate { .. } => ("roll_d1", 0),
+            RollAction::Advantage => ("roll_advantage", 0),
+            RollAction::Disadvantage => ("roll_disadvantage", 0),
+            RollAction::Dice { modifier: 0, .. } => ("roll_result", 0),
+            RollAction::Dice { modifier, .. } => ("roll_result_modifier", modifier),
+            RollAction::Exploding { modifier: 0, .. } => ("roll_exploding", 0),
+            RollAction::Exploding { modifier, .. } => ("roll_exploding_modifier", modifier),
+            RollAction::Fate { modifier: 0, .. } => ("roll_fate", 0),
+            RollAction::Fate { modifier, .. } => ("roll_fate_modifier", modifier),
+        };
+        let result = ChatBotCommand::SendMessage(self.messages.text(
+            key,
+            &[
+                ("dice", &roll.dice_text()),
+                ("modifier", &format!("{:+}", modifier)),
+                ("result", &roll.result.to_string()),
+                ("signed_result", &format!("{:+}", roll.result)),
+            ],
+        ));
+        let unbeaten = self
+            .roll_record
+            .as_ref()
+            .is_some_and(|record| roll.result <= record.result);
+        if !action.is_record_roll() || unbeaten {
+            return Some(result);
+        }
+        let record = RollRecord {
+            user: command.user.name.to_owned(),
+            result: roll.result,
+        };
+        let beaten = self.roll_record.replace(record.clone());
+        let announcement = match beaten {
+            Some(beaten) => self.messages.text(
+                "roll_record",
+                &[
+                    ("user", &record.user),
+                    ("result", &record.result.to_string()),
+                    ("old_user", &beaten.user),
+                    ("old_result", &beaten.result.to_string()),
+                ],
+            ),
+            None => self.messages.text(
+                "roll_first_record",
+                &[
+                    ("user", &record.user),
+                    ("result", &record.result.to_string()),
+                ],
+            ),
+        };
+        Some(ChatBotCommand::MultipleCommands(vec![
+            result,
+            ChatBotCommand::SendMessage(announcement),
+            ChatBotCommand::StoreRollRecord(record),
+        ]))
+    }
+
+    fn handle_my_rolls(&self, command: Command) -> Option<ChatBotCommand> {
+        let text = match self
+            .session
+            .recent_rolls
+            .get(&command.user.name.to_lowercase())
+        {
+            Some(rolls) => {
+                let rolls: Vec<String> = rolls.iter().map(i64::to_string).collect();
+                self.messages.text(
+                    "my_rolls",
+                    &[("user", &command.user.name), ("rolls", &rolls.join(", "))],
+                )
+            }
+            None => self
+                .messages
+                .text("my_rolls_none", &[("user", &command.user.name)]),
+        };
+        Some(ChatBotCommand::SendMessage(text))
+    }
+
+    /// Summarizes the results of a kind of roll since the bot started, d20 unless another is given.
+    fn handle_roll_stats(&self, command: Command) -> Option<ChatBotCommand> {
+        let action = match RollAction::parse(&command.options) {
+            Some(action) => action,
+            None => return self.msg("rollstats_usage"),
+        };
+        let dice = command.options.first().map_or("d20", String::as_str);
+        let text = match self.session.roll_summaries.get(&action) {
+            Some(summary) => self.messages.plural(
+                "rollstats",
+                summary.count as i64,
+                &[
+                    ("dice", dice),
+                    ("min", &summary.min.to_string()),
+                    ("max", &summary.max.to_string()),
+                    ("average", &format!("{:.2}", summary.average())),
+                ],
+            ),
+            None => self.messages.text("rollstats_none", &[("dice", dice)]),
+        };
+        Some(ChatBotCommand::SendMessage(text))
+    }
+
+    fn handle_duel(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let target = match command.arg_user(0) {
+            Some(target) if command.options.len() == 1 => target,
+            _ => return self.msg("duel_usage"),
+        };
+        match self
+            .session
+            .duels
+            .challenge(&command.user.name, target, Instant::now())
+        {
+            Ok(()) => Some(ChatBotCommand::SendMessage(self.messages.text(
+                "duel_challenge",
+                &[
+                    ("challenger", &command.user.name),
+                    ("target", target),
+                    ("seconds", &DUEL_EXPIRY.as_secs().to_string()),
+                ],
+            ))),
+            Err(_) => self.msg("duel_self"),
+        }
+    }
+
+    // both users roll a d20, the higher roll wins, a tie is rolled again
+    fn handle_accept(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let challenger =
+            match self
+                .session
+                .duels
+                .accept(&command.user.name, command.arg_user(0), Instant::now())
+            {
+                Ok(challenger) => challenger,
+                Err(DuelError::Expired(challenger)) => {
+                    return Some(ChatBotCommand::SendMessage(
+                        self.messages
+                            .text("duel_expired", &[("challenger", &challenger)]),
+                    ))
+                }
+                Err(_) => return self.msg("duel_no_challenge"),
+            };
+        let rounds = duel_rounds(|| self.rng.gen_range(1..=20u32), MAX_DUEL_ROUNDS);
+        let mut announcements: Vec<ChatBotCommand> = rounds
+            .iter()
+            .enumerate()
+            .map(|(round, (challenger_roll, target_roll))| {
+                let (key, winner) = match challenger_roll.cmp(target_roll) {
+                    Ordering::Greater => ("duel_result", challenger.as_str()),
+                    Ordering::Less => ("duel_result", command.user.name.as_str()),
+                    Ordering::Equal if round + 1 < rounds.len() => ("duel_tie", ""),
+                    Ordering::Equal => ("duel_draw", ""),
+                };
+                ChatBotCommand::SendMessage(self.messages.text(
+                    key,
+                    &[
+                        ("challenger", &challenger),
+                        ("challenger_roll", &challenger_roll.to_string()),
+                        ("target", &command.user.name),
+                        ("target_roll", &target_roll.to_string()),
+                        ("winner", winner),
+                    ],
+                ))
+            })
+            .collect();
+        if announcements.len() == 1 {
+            return announcements.pop();
+        }
+        Some(ChatBotCommand::MultipleCommands(announcements))
+    }
+
+    fn handle_raffle(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        match command.options.first().map(String::as_str) {
+            Some("open") => {
+                if self.session.raffle.is_some() {
+                    return self.msg("raffle_running");
+                }
+                match RaffleRules::parse(&command.options[1..]) {
+                    Some(rules) => {
+                        self.session.raffle = Some(Raffle::new(rules));
+                        self.msg("raffle_opened")
+                    }
+                    None => self.msg("raffle_usage"),
+                }
+            }
+            Some("draw") if command.options.len() == 1 => match self.session.raffle.take() {
+                Some(raffle) => match raffle.draw(&mut self.rng) {
+                    Some(winner) => Some(ChatBotCommand::SendMessage(self.messages.text(
+                        "raffle_winner",
+                        &[
+                            ("user", winner),
+                            ("entrants", &raffle.entrant_count().to_string()),
+                        ],
+                    ))),
+                    None => self.msg("raffle_no_entries"),
+                },
+                None => self.msg("raffle_not_running"),
+            },
+            Some("close") if command.options.len() == 1 => match self.session.raffle.take() {
+                Some(_) => self.msg("raffle_closed"),
+                None => self.msg("raffle_not_running"),
+            },
+            _ => self.msg("raffle_usage"),
+        }
+    }
+
+    // followers only raffles are checked by the gate of the command before
+    fn handle_enter(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let raffle = match self.session.raffle.as_mut() {
+            Some(raffle) => raffle,
+            None => return self.msg("raffle_not_running"),
+        };
+        let messages = self
+            .connection
+            .messages
+            .get(&command.user.name.to_lowercase())
+            .copied()
+            .unwrap_or_default();
+        if messages < raffle.rules.min_messages {
+            return self.raffle_ineligible(&command.user.name, "raffle_too_quiet");
+        }
+        raffle.enter(&command.user.name, command.user.is_subscriber());
+        None
+    }
+
+    // users are told only once per raffle why they cannot enter, so that they cannot spam the chat
+    fn raffle_ineligible(&mut self, user_name: &str, key: &str) -> Option<ChatBotCommand> {
+        let raffle = self.session.raffle.as_mut()?;
+        let min_messages = raffle.rules.min_messages.to_string();
+        raffle.explain_once(user_name).then(|| {
+            ChatBotCommand::SendMessage(
+                self.messages
+                    .text(key, &[("user", user_name), ("messages", &min_messages)]),
+            )
+        })
+    }
+
+    fn handle_info(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if self.info.is_empty() {
+            return self.msg("info");
+        }
+        let section = command.options.first().cloned();
+        let needs_game = self
+            .info
+            .select(section.as_deref())
+            .iter()
+            .any(|section| section.name == GAME_SECTION);
+        if !needs_game {
+            return self.info_reply(section.as_deref(), None);
+        }
+        self.session
+            .pending_info
+            .push((section, command.user.name.to_owned()));
+        // the stream info is requested only once for all waiting info requests
+        (self.session.pending_info.len() == 1)
+            .then_some(ChatBotCommand::HelixRequest(HelixRequest::StreamInfo))
+    }
+
+    // unknown sections and the game without stream info are left out,
+    // messages longer than twitch allows are split
+    fn info_reply(
+        &self,
+        section: Option<&str>,
+        stream: Option<&StreamInfo>,
+    ) -> Option<ChatBotCommand> {
+        let parts: Vec<String> = self
+            .info
+            .select(section)
+            .into_iter()
+            .filter_map(|section| match (&section.text, stream) {
+                (Some(text), _) => Some(text.to_owned()),
+                (None, Some(stream)) if !stream.game.is_empty() => Some(self.messages.text(
+                    "info_game",
+                    &[("game", &stream.game), ("title", &stream.title)],
+                )),
+                (None, _) => None,
+            })
+            .collect();
+        let mut messages: Vec<ChatBotCommand> = split_message(&parts, MAX_MESSAGE_LENGTH)
+            .into_iter()
+            .map(ChatBotCommand::SendMessage)
+            .collect();
+        match messages.len() {
+            0 => None,
+            1 => messages.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(messages)),
+        }
+    }
+
+    fn record_first_chatter(&mut self, user_name: &str) {
+        if self.session.first_chatter.is_none() && user_name.to_lowercase() != self.bot_name {
+            self.session.first_chatter = Some(user_name.to_owned());
+        }
+    }
+
+    /// Turns a command off with `!disable <command>` or on again with `!enable <command>`.
+    fn handle_toggle(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let name = match command.options.first() {
+            Some(name) => name.strip_prefix(self.command_prefix()).unwrap_or(name),
+            None => return self.msg("toggle_usage"),
+        };
+        let kind = ReceiveEvent::parse_command_kind(&name.to_lowercase());
+        let disable = command.kind == CommandType::Disable;
+        if disable {
+            match &kind {
+                // otherwise there would be no way back
+                CommandType::Enable | CommandType::Disable => return self.msg("toggle_refused"),
+                CommandType::Dynamic(name) if !self.dynamic_commands.contains_key(name) => {
+                    return Some(ChatBotCommand::SendMessage(
+                        self.messages.text("toggle_unknown", &[("command", name)]),
+                    ))
+                }
+                _ => (),
+            }
+        }
+        let name = kind.name().to_owned();
+        let changed = if disable {
+            self.disabled_commands.insert(name.clone())
+        } else {
+            self.disabled_commands.remove(&name)
+        };
+        let key = if disable {
+            "command_disabled"
+        } else {
+            "command_enabled"
+        };
+        let reply = ChatBotCommand::SendMessage(self.messages.text(key, &[("command", &name)]));
+        if !changed {
+            return Some(reply);
+        }
+        Some(ChatBotCommand::MultipleCommands(vec![
+            reply,
+            ChatBotCommand::StoreCommandDisabled {
+                name,
+                disabled: disable,
+            },
+        ]))
+    }
+
+    /// The prefix of commands in the channel of the event being handled.
+    fn command_prefix(&self) -> &str {
+        self.command_prefixes.for_channel(Some(&self.event_channel))
+    }
+
+    /// Lists the custom commands, and the commands which are turned off.
+    fn handle_commands(&self) -> Option<ChatBotCommand> {
+        let list = |names: Vec<&String>| {
+            let mut names: Vec<String> = names
+                .into_iter()
+                .map(|name| format!("{}{}", self.command_prefix(), name))
+                .collect();
+            names.sort();
+            names.join(", ")
+        };
+        let custom: Vec<&String> = self
+            .dynamic_commands
+            .keys()
+            .filter(|name| !self.disabled_commands.contains(*name))
+            .collect();
+        let mut text = if custom.is_empty() {
+            self.messages.text("commands_none", &[])
+        } else {
+            self.messages
+                .text("commands_list", &[("commands", &list(custom))])
+        };
+        if !self.disabled_commands.is_empty() {
+            let disabled = list(self.disabled_commands.iter().collect());
+            text = format!(
+                "{} | {}",
+                text,
+                self.messages
+                    .text("commands_disabled", &[("commands", &disabled)])
+            );
+        }
+        Some(ChatBotCommand::SendMessage(text))
+    }
+
+    /// Pauses or resumes the timed messages, whose timers keep running in the meantime.
+    fn handle_timers(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        match command.options.first().map(|option| option.to_lowercase()) {
+            Some(option) if option == "off" => {
+                self.timers_paused = true;
+                self.msg("timers_paused")
+            }
+            Some(option) if option == "on" => {
+                self.timers_paused = false;
+                self.msg("timers_resumed")
+            }
+            _ => self.msg("timers_usage"),
+        }
+    }
+
+    fn handle_first(&self) -> Option<ChatBotCommand> {
+        match &self.session.first_chatter {
+            Some(user) => Some(ChatBotCommand::SendMessage(
+                self.messages.text("first_chatter", &[("user", user)]),
+            )),
+            None => self.msg("first_nobody"),
+        }
+    }
+
+    fn handle_shoutout(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let target = match command.arg_user(0) {
+            Some(target) => target,
+            None => return self.msg("shoutout_usage"),
+        };
+        if target.eq_ignore_ascii_case(&self.channel_name) {
+            return self.msg("shoutout_self");
+        }
+        let text = ChatBotCommand::SendMessage(self.messages.text("shoutout", &[("user", target)]));
+        if !self.native_shoutout {
+            return Some(text);
+        }
+        let mut commands = vec![text];
+        self.session
+            .shoutout_invokers
+            .entry(target.to_lowercase())
+            .or_insert_with(|| command.user.name.to_owned());
+        match self.session.shoutouts.request(target, Instant::now()) {
+            ShoutoutSlot::Now => {
+                commands.push(ChatBotCommand::HelixRequest(HelixRequest::Shoutout {
+                    login: target.to_lowercase(),
+                }))
+            }
+            ShoutoutSlot::Queued { wait, first } => {
+                let seconds = wait.as_secs().to_string();
+                commands.push(ChatBotCommand::SendMessage(self.messages.text(
+                    "shoutout_deferred",
+                    &[
+                        ("invoker", &command.user.name),
+                        ("user", target),
+                        ("seconds", &seconds),
+                    ],
+                )));
+                if first {
+                    commands.push(ChatBotCommand::TimedCallback {
+                        duration: wait,
+                        event: ChatBotEvent::ShoutoutDue,
+                    });
+                }
+            }
+            ShoutoutSlot::AlreadyQueued => println!("The shoutout of {} is already queued", target),
+        }
+        Some(ChatBotCommand::MultipleCommands(commands))
+    }
+
+    fn handle_shoutout_due(&mut self) -> Option<ChatBotCommand> {
+        let login = self.session.shoutouts.next_due(Instant::now())?;
+        let shoutout = ChatBotCommand::HelixRequest(HelixRequest::Shoutout { login });
+        if !self.session.shoutouts.is_waiting() {
+            return Some(shoutout);
+        }
+        Some(ChatBotCommand::MultipleCommands(vec![
+            shoutout,
+            ChatBotCommand::TimedCallback {
+                duration: SHOUTOUT_COOLDOWN,
+                event: ChatBotEvent::ShoutoutDue,
+            },
+        ]))
+    }
+
+    // the chat message of `!so` was already sent, so a failed native shoutout is only reported
+    fn handle_shoutout_response(
+        &mut self,
+        login: String,
+        result: Result<(), HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let invoker = self.session.shoutout_invokers.remove(&login);
+        let reply = match result {
+            Ok(()) => {
+                println!("Sent the native shoutout of {}", login);
+                None
+            }
+            Err(HelixError::Status(status, message)) if status != 401 => {
+                Some(ChatBotCommand::SendMessage(self.messages.text(
+                    "shoutout_failed",
+                    &[("user", &login), ("message", &message)],
+                )))
+            }
+            Err(error) => self.helix_error_message(error),
+        };
+        self.mention_waiting(invoker.as_slice(), reply)
+    }
+
+    fn handle_raid(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        let target = match command.arg_user(0) {
+            Some(target) => target,
+            None => return self.msg("raid_usage"),
+        };
+        if target.eq_ignore_ascii_case(&self.channel_name) {
+            return self.msg("raid_self");
+        }
+        if let Some(raid) = &self.connection.raid {
+            return Some(ChatBotCommand::SendMessage(
+                self.messages
+                    .text("raid_pending", &[("channel", &raid.target)]),
+            ));
+        }
+        let raid = Raid::new(target);
+        let login = raid.target.to_owned();
+        self.connection.raid = Some(raid);
+        Some(ChatBotCommand::HelixRequest(HelixRequest::StartRaid {
+            login,
+        }))
+    }
+
+    fn handle_raid_response(
+        &mut self,
+        login: String,
+        result: Result<(), HelixError>,
+    ) -> Option<ChatBotCommand> {
+        // the raid was canceled or the bot reconnected while twitch was asked
+        let raid = self
+            .connection
+            .raid
+            .as_ref()
+            .filter(|raid| raid.target == login)?;
+        if let Err(error) = result {
+            self.connection.raid = None;
+            let key = match RaidRefusal::from(error) {
+                RaidRefusal::NotAllowed => "raid_not_allowed",
+                RaidRefusal::Offline => "raid_offline",
+                RaidRefusal::UnknownChannel => "raid_unknown_channel",
+                RaidRefusal::AlreadyRaiding => "raid_already_raiding",
+                RaidRefusal::Other(error) => return self.helix_error_message(error),
+            };
+            return Some(ChatBotCommand::SendMessage(
+                self.messages.text(key, &[("channel", &login)]),
+            ));
+        }
+        let mut commands = vec![ChatBotCommand::SendMessage(self.messages.text(
+            "raid_started",
+            &[
+                ("channel", &login),
+                ("seconds", &RAID_DELAY.as_secs().to_string()),
+            ],
+        ))];
+        commands.extend(raid.countdown().into_iter().map(|(duration, seconds)| {
+            ChatBotCommand::TimedCallback {
+                duration,
+                event: ChatBotEvent::RaidCountdown(raid.id, seconds),
+            }
+        }));
+        Some(ChatBotCommand::MultipleCommands(commands))
+    }
+
+    fn handle_raid_countdown(&mut self, id: Uuid, seconds: u64) -> Option<ChatBotCommand> {
+        let raid = self.connection.raid.as_ref().filter(|raid| raid.id == id)?;
+        if seconds > 0 {
+            return Some(ChatBotCommand::SendMessage(self.messages.text(
+                "raid_countdown",
+                &[("channel", &raid.target), ("seconds", &seconds.to_string())],
+            )));
+        }
+        let raid = self.connection.raid.take()?;
+        Some(ChatBotCommand::SendMessage(
+            self.messages.text("raid_go", &[("channel", &raid.target)]),
+        ))
+    }
+
+    fn is_protected(&self) -> bool {
+        self.protection
+            .as_ref()
+            .is_some_and(|protection| protection.is_active())
+    }
+
+    // the first messages of new chatters are compared, a hate raid comes from fresh accounts
+    fn watch_first_message(&mut self, message: &TextMessage) -> Option<ChatBotCommand> {
+        if !message.first_message || message.user.has_elevated_rights() {
+            return None;
+        }
+        let offender = Offender {
+            user: message.user.name.to_owned(),
+            message_id: message.id.clone(),
+        };
+        match self
+            .protection
+            .as_mut()?
+            .observe(offender, &message.text, Instant::now())
+        {
+            Observation::Calm => None,
+            Observation::Offending(offender) => {
+                Some(ChatBotCommand::LogTextMessage(offending_message(&offender)))
+            }
+            Observation::Burst(offenders) => self.start_protection(offenders),
+        }
+    }
+
+    fn start_protection(&mut self, offenders: Vec<Offender>) -> Option<ChatBotCommand> {
+        let protection = self.protection.as_mut()?;
+        let duration = protection.duration();
+        let (id, enabled) = protection.protect(&self.connection.room)?;
+        let mut commands: Vec<ChatBotCommand> = offenders
+            .iter()
+            .map(|offender| ChatBotCommand::LogTextMessage(offending_message(offender)))
+            .collect();
+        let users: Vec<&str> = offenders
+            .iter()
+            .map(|offender| offender.user.as_str())
+            .collect();
+        commands.push(ChatBotCommand::NotifyOwners(self.messages.text(
+            "protection_notice",
+            &[
+                ("channel", &self.channel_name),
+                ("count", &offenders.len().to_string()),
+                ("users", &users.join(", ")),
+            ],
+        )));
+        commands.push(ChatBotCommand::SendMessage(self.messages.text(
+            "protection_started",
+            &[("minutes", &(duration.as_secs() / 60).max(1).to_string())],
+        )));
+        if enabled != ChatSettings::default() {
+            commands.push(self.update_chat_settings(enabled));
+        }
+        commands.push(ChatBotCommand::TimedCallback {
+            duration,
+            event: ChatBotEvent::ProtectionExpired(id),
+        });
+        Some(ChatBotCommand::MultipleCommands(commands))
+    }
+
+    // ends the protection with the id when it expired, or any protection with `!allclear`
+    fn end_protection(&mut self, id: Option<Uuid>) -> Option<ChatBotCommand> {
+        let restored = self.protection.as_mut()?.lift(id)?;
+        let ended = ChatBotCommand::SendMessage(self.messages.text("protection_ended", &[]));
+        if restored == ChatSettings::default() {
+            return Some(ended);
+        }
+        Some(ChatBotCommand::MultipleCommands(vec![
+            ended,
+            self.update_chat_settings(restored),
+        ]))
+    }
+
+    fn handle_all_clear(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        if !self.is_protected() {
+            return self.msg("allclear_inactive");
+        }
+        self.end_protection(None)
+    }
+
+    fn handle_chat_mode(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let option = command.options.first().map(String::as_str);
+        let switch = |name: &str, on: bool| {
+            let state = if on { "on" } else { "off" };
+            self.messages.text(&format!("{}_{}", name, state), &[])
+        };
+        let change = match command.kind {
+            CommandType::Slow => option
+                .map_or(Some(DEFAULT_SLOW_SECONDS), slow_mode_seconds)
+                .map(|seconds| {
+                    (
+                        ChatSettings {
+                            slow_mode: Some(true),
+                            slow_mode_wait_time: Some(seconds),
+                            ..ChatSettings::default()
+                        },
+                        self.messages
+                            .text("slow_on", &[("seconds", &seconds.to_string())]),
+                    )
+                }),
+            CommandType::SlowOff => Some((
+                ChatSettings {
+                    slow_mode: Some(false),
+                    ..ChatSettings::default()
+                },
+                switch("slow", false),
+            )),
+            CommandType::FollowersOnly if option == Some("off") => Some((
+                ChatSettings {
+                    follower_mode: Some(false),
+                    ..ChatSettings::default()
+                },
+                switch("followersonly", false),
+            )),
+            // like Twitch's own `/followers`, all followers may chat if no duration is given
+            CommandType::FollowersOnly => {
+                option
+                    .map_or(Some(0), follower_mode_minutes)
+                    .map(|minutes| {
+                        let confirmation = match minutes {
+                            0 => switch("followersonly", true),
+                            minutes => self.messages.text(
+                                "followersonly_on_duration",
+                                &[("duration", &format_follow_duration(minutes))],
+                            ),
+                        };
+                        (
+                            ChatSettings {
+                                follower_mode: Some(true),
+                                follower_mode_duration: Some(minutes),
+                                ..ChatSettings::default()
+                            },
+                            confirmation,
+                        )
+                    })
+            }
+            CommandType::SubOnly => option.and_then(parse_switch).map(|on| {
+                (
+                    ChatSettings {
+                        subscriber_mode: Some(on),
+                        ..ChatSettings::default()
+                    },
+                    switch("subonly", on),
+                )
+            }),
+            CommandType::EmoteOnly => option.and_then(parse_switch).map(|on| {
+                (
+                    ChatSettings {
+                        emote_mode: Some(on),
+                        ..ChatSettings::default()
+                    },
+                    switch("emoteonly", on),
+                )
+            }),
+            CommandType::UniqueChat => option.and_then(parse_switch).map(|on| {
+                (
+                    ChatSettings {
+                        unique_chat_mode: Some(on),
+                        ..ChatSettings::default()
+                    },
+                    switch("uniquechat", on),
+                )
+            }),
+            _ => None,
+        };
+        let (settings, confirmation) = match change {
+            Some(change) => change,
+            None => {
+                let usage = match command.kind {
+                    CommandType::Slow => "slow_usage",
+                    CommandType::FollowersOnly => "followersonly_usage",
+                    _ => "chat_mode_usage",
+                };
+                return Some(ChatBotCommand::SendMessage(
+                    self.messages
+                        .text(usage, &[("command", command.kind.name())]),
+                ));
+            }
+        };
+        // the chat server confirms the change with a ROOMSTATE a moment later
+        self.connection.room.update(room_change(&settings));
+        Some(ChatBotCommand::MultipleCommands(vec![
+            self.update_chat_settings(settings),
+            ChatBotCommand::SendMessage(confirmation),
+        ]))
+    }
+
+    // every update is noted, so that its response finishes the audit entry of the command
+    fn update_chat_settings(&mut self, settings: ChatSettings) -> ChatBotCommand {
+        self.audit.await_chat_settings();
+        ChatBotCommand::HelixRequest(HelixRequest::UpdateChatSettings(settings))
+    }
+
+    fn handle_chat_settings_response(
+        &self,
+        result: Result<(), HelixError>,
+    ) -> Option<ChatBotCommand> {
+        match result {
+            Ok(()) => {
+                println!("Updated the chat settings");
+                None
+            }
+            // the broadcaster has to authorize the bot to manage the chat settings
+            Err(HelixError::Status(401 | 403, _)) => self.msg("chat_settings_denied"),
+            Err(error) => self.helix_error_message(error),
+        }
+    }
+
+    fn handle_token_scopes(
+        &self,
+        result: Result<Vec<String>, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let granted = match result {
+            Ok(granted) => granted,
+            Err(error) => return self.helix_error_message(error),
+        };
+        let missing: Vec<String> = missing_scopes(&self.scoped_features, &granted)
+            .iter()
+            .map(|feature| format!("{} ({})", feature.scope, feature.feature))
+            .collect();
+        if missing.is_empty() {
+            return self.msg("scopes_complete");
+        }
+        Some(ChatBotCommand::SendMessage(
+            self.messages
+                .text("scopes_missing", &[("missing", &missing.join(", "))]),
+        ))
+    }
+
+    fn handle_chat_color_response(
+        &self,
+        color: String,
+        result: Result<(), HelixError>,
+    ) -> Option<ChatBotCommand> {
+        match result {
+            Ok(()) => Some(ChatBotCommand::SendMessage(
+                self.messages.text("botcolor_changed", &[("color", &color)]),
+            )),
+            // e.g. a hex color for an account without Turbo or Prime
+            Err(HelixError::Status(400, message)) => {
+                Some(ChatBotCommand::SendMessage(self.messages.text(
+                    "botcolor_rejected",
+                    &[("color", &color), ("message", &message)],
+                )))
+            }
+            Err(error) => self.helix_error_message(error),
+        }
+    }
+
+    fn handle_unraid(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        // twitch is asked even without a known raid, which may have been started before a reconnect
+        self.connection.raid = None;
+        Some(ChatBotCommand::HelixRequest(HelixRequest::CancelRaid))
+    }
+
+    fn handle_raid_canceled(&self, result: Result<(), HelixError>) -> Option<ChatBotCommand> {
+        match result {
+            Ok(()) => self.msg("raid_canceled"),
+            // twitch answers 404 if the channel is not raiding
+            Err(HelixError::Status(404, _)) => self.msg("raid_none"),
+            Err(error) => self.helix_error_message(error),
+        }
+    }
+
+    fn handle_vod(&mut self, command: Command) -> Option<ChatBotCommand> {
+        match &self.session.latest_vod {
+            Some(cached) if cached.is_fresh() => self.vod_message(cached.video.as_ref()),
+            _ => await_reply(&mut self.session.pending_vod, &command.user.name)
+                .then_some(ChatBotCommand::HelixRequest(HelixRequest::LatestVod)),
+        }
+    }
+
+    fn handle_vod_response(
+        &mut self,
+        result: Result<Option<Video>, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let users = std::mem::take(&mut self.session.pending_vod);
+        let reply = match result {
+            Ok(video) => {
+                let message = self.vod_message(video.as_ref());
+                self.session.latest_vod = Some(CachedVideo::new(video));
+                message
+            }
+            Err(error) => self.helix_error_message(error),
+        };
+        self.mention_waiting(&users, reply)
+    }
+
+    fn vod_message(&self, video: Option<&Video>) -> Option<ChatBotCommand> {
+        let video = match video {
+            Some(video) => video,
+            None => return self.msg("vod_none"),
+        };
+        let duration = format_uptime(video.duration);
+        Some(ChatBotCommand::SendMessage(fit_title(
+            &video.title,
+            MAX_MESSAGE_LENGTH,
+            |title| {
+                self.messages.text(
+                    "vod",
+                    &[
+                        ("title", title),
+                        ("duration", &duration),
+                        ("url", &video.url),
+                    ],
+                )
+            },
+        )))
+    }
+
+    fn handle_last_clip(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let window = match command.options.as_slice() {
+            [] => ClipWindow::Latest,
+            [window] => match window.parse() {
+                Ok(window) => window,
+                Err(_) => return self.msg("lastclip_usage"),
+            },
+            _ => return self.msg("lastclip_usage"),
+        };
+        match self.session.clips.get(&window) {
+            Some(cached) if cached.is_fresh() => self.clip_message(window, cached.video.as_ref()),
+            _ => {
+                let users = self.session.pending_clips.entry(window).or_default();
+                await_reply(users, &command.user.name)
+                    .then_some(ChatBotCommand::HelixRequest(HelixRequest::Clip { window }))
+            }
+        }
+    }
+
+    fn handle_clip_response(
+        &mut self,
+        window: ClipWindow,
+        result: Result<Option<Clip>, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let users = self
+            .session
+            .pending_clips
+            .remove(&window)
+            .unwrap_or_default();
+        let reply = match result {
+            Ok(clip) => {
+                let message = self.clip_message(window, clip.as_ref());
+                self.session.clips.insert(window, CachedVideo::new(clip));
+                message
+            }
+            Err(error) => self.helix_error_message(error),
+        };
+        self.mention_waiting(&users, reply)
+    }
+
+    fn clip_message(&self, window: ClipWindow, clip: Option<&Clip>) -> Option<ChatBotCommand> {
+        let clip = match clip {
+            Some(clip) => clip,
+            None => return self.msg("lastclip_none"),
+        };
+        let views = self.format_number(clip.views);
+        let window_name = match window {
+            ClipWindow::Latest => None,
+            ClipWindow::Day => Some("clip_window_day"),
+            ClipWindow::Week => Some("clip_window_week"),
+            ClipWindow::Month => Some("clip_window_month"),
+            ClipWindow::All => Some("clip_window_all"),
+        }
+        .map(|key| self.messages.text(key, &[]));
+        Some(ChatBotCommand::SendMessage(fit_title(
+            &clip.title,
+            MAX_MESSAGE_LENGTH,
+            |title| {
+                let mut args = vec![
+                    ("title", title),
+                    ("creator", clip.creator.as_str()),
+                    ("views", views.as_str()),
+                    ("url", clip.url.as_str()),
+                ];
+                match &window_name {
+                    Some(window_name) => {
+                        args.push(("window", window_name));
+                        self.messages.text("lastclip_top", &args)
+                    }
+                    None => self.messages.text("lastclip", &args),
+                }
+            },
+        )))
+    }
+
+    fn handle_config(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        let summary = match &self.config_summary {
+            Some(summary) => summary,
+            None => return self.msg("config_unavailable"),
+        };
+        let mut parts = vec![self.messages.text("config", &[("summary", summary)])];
+        if !self.disabled_commands.is_empty() {
+            let mut disabled: Vec<String> = self
+                .disabled_commands
+                .iter()
+                .map(|name| format!("{}{}", self.command_prefix(), name))
+                .collect();
+            disabled.sort();
+            parts.push(
+                self.messages
+                    .text("commands_disabled", &[("commands", &disabled.join(", "))]),
+            );
+        }
+        let mut messages: Vec<ChatBotCommand> = split_message(&parts, MAX_MESSAGE_LENGTH)
+            .into_iter()
+            .map(ChatBotCommand::SendMessage)
+            .collect();
+        match messages.len() {
+            1 => messages.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(messages)),
+        }
+    }
+
+    fn handle_emote_list(&self) -> Option<ChatBotCommand> {
+        let emote_set = match &self.session.emote_set {
+            Some(emote_set) => emote_set,
+            None => return self.msg("emotes_unavailable"),
+        };
+        let sub_emotes = emote_set.sub_emotes();
+        if sub_emotes.is_empty() {
+            return self.msg("emotes_none");
+        }
+        let text = self
+            .messages
+            .text("emotes_list", &[("emotes", &sub_emotes.join(" "))]);
+        let mut messages: Vec<ChatBotCommand> = split_message(&[text], MAX_MESSAGE_LENGTH)
+            .into_iter()
+            .map(ChatBotCommand::SendMessage)
+            .collect();
+        match messages.len() {
+            1 => messages.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(messages)),
+        }
+    }
+
+    fn handle_perf(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        let commands: Vec<String> = self
+            .timings
+            .summary()
+            .iter()
+            .map(|perf| {
+                self.messages.text(
+                    "perf_command",
+                    &[
+                        ("command", &perf.name),
+                        ("count", &perf.count.to_string()),
+                        ("slow", &perf.slow.to_string()),
+                        ("p50", &format_duration(perf.p50)),
+                        ("p95", &format_duration(perf.p95)),
+                    ],
+                )
+            })
+            .collect();
+        if commands.is_empty() {
+            return self.msg("perf_none");
+        }
+        Some(ChatBotCommand::SendMessage(
+            self.messages
+                .text("perf", &[("commands", &commands.join(" | "))]),
+        ))
+    }
+
+    /// Counts the chatters the bot saw joining with `!viewers`, names them with `!viewers list [<page>]`.
+    fn handle_viewers(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let count = self.connection.chatters.len();
+        let page = match command.options.as_slice() {
+            [] => {
+                return Some(ChatBotCommand::SendMessage(self.messages.plural(
+                    "viewers",
+                    count as i64,
+                    &[],
+                )))
+            }
+            [list] if list == "list" => Some(1),
+            [list, page] if list == "list" => page.parse::<usize>().ok().filter(|page| *page > 0),
+            _ => None,
+        };
+        let page = match page {
+            Some(page) => page,
+            None => return self.msg("viewers_usage"),
+        };
+        if count == 0 {
+            return self.msg("viewers_none");
+        }
+        let mut viewers: Vec<String> = self.connection.chatters.iter().cloned().collect();
+        viewers.sort_by_key(|viewer| viewer.to_lowercase());
+        // leaves room for the page numbers of a long list
+        let overhead = self
+            .messages
+            .text(
+                "viewers_page",
+                &[("page", "999"), ("pages", "999"), ("viewers", "")],
+            )
+            .chars()
+            .count();
+        let pages = paginate(&viewers, MAX_MESSAGE_LENGTH.saturating_sub(overhead));
+        let page_count = pages.len().to_string();
+        Some(ChatBotCommand::SendMessage(match pages.get(page - 1) {
+            Some(names) => self.messages.text(
+                "viewers_page",
+                &[
+                    ("page", &page.to_string()),
+                    ("pages", &page_count),
+                    ("viewers", names),
+                ],
+            ),
+            None => self
+                .messages
+                .plural("viewers_no_page", pages.len() as i64, &[]),
+        }))
+    }
+
+    /// Names the phrases repeated most in the chat lately with `!trending`.
+    fn handle_trending(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let phrases = self.trending.top(Instant::now());
+        let minutes = (TRENDING_WINDOW.as_secs() / 60).to_string();
+        if phrases.is_empty() {
+            return Some(ChatBotCommand::SendMessage(
+                self.messages
+                    .text("trending_none", &[("minutes", &minutes)]),
+            ));
+        }
+        let phrases: Vec<String> = phrases
+            .iter()
+            .map(|(phrase, count)| format!("\"{}\" ({})", phrase, count))
+            .collect();
+        Some(ChatBotCommand::SendMessage(self.messages.text(
+            "trending",
+            &[("minutes", &minutes), ("phrases", &phrases.join(", "))],
+        )))
+    }
+
+    fn handle_define(&self, command: Command) -> Option<ChatBotCommand> {
+        let term = command.rest(0);
+        if term.is_empty() {
+            return self.msg("define_usage");
+        }
+        Some(ChatBotCommand::SendMessage(
+            match self.glossary.define(&term) {
+                Some(definition) => self
+                    .messages
+                    .text("define", &[("term", &term), ("definition", definition)]),
+                None => self.messages.text("define_unknown", &[("term", &term)]),
+            },
+        ))
+    }
+
+    // the recent messages of a user go to the moderator who asked, not to the chat
+    fn handle_context(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        let user = match command.arg_user(0) {
+            Some(user) => user,
+            None => return self.msg("context_usage"),
+        };
+        let messages = self
+            .history
+            .last_messages_of(&self.channel_name, user, CONTEXT_MESSAGES);
+        let text = if messages.is_empty() {
+            self.messages.text("context_none", &[("user", user)])
+        } else {
+            let deleted = self.messages.text("context_deleted", &[]);
+            let messages: Vec<String> = messages
+                .iter()
+                .map(|entry| {
+                    let time = entry.sent_at.format("%H:%M:%S UTC");
+                    if entry.deleted {
+                        format!("[{}] {} {}", time, entry.text, deleted)
+                    } else {
+                        format!("[{}] {}", time, entry.text)
+                    }
+                })
+                .collect();
+            self.messages.text(
+                "context",
+                &[("user", user), ("messages", &messages.join(" | "))],
+            )
+        };
+        Some(if self.whispers_available {
+            ChatBotCommand::SendWhisper {
+                user: command.user.name,
+                text,
+            }
+        } else {
+            ChatBotCommand::LogTextMessage(format!("Context for {}: {}", command.user.name, text))
+        })
+    }
+
+    // the entries go to the broadcaster, the audit log is no business of the chat
+    fn handle_audit(&self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.is_broadcaster() {
+            return self.msg("denied");
+        }
+        let filter = match command.options.first() {
+            Some(filter) => filter,
+            None => return self.msg("audit_usage"),
+        };
+        let count = match command.options.get(1) {
+            Some(_) => match command.arg_u32(1) {
+                Some(count @ 1..) => (count as usize).min(MAX_AUDIT_ENTRIES),
+                _ => return self.msg("audit_usage"),
+            },
+            None => DEFAULT_AUDIT_ENTRIES,
+        };
+        let entries = self.audit.matching(filter, count);
+        let text = if entries.is_empty() {
+            self.messages.text("audit_none", &[("filter", filter)])
+        } else {
+            let entries: Vec<String> = entries.iter().map(|entry| entry.summary()).collect();
+            self.messages.text(
+                "audit",
+                &[("filter", filter), ("entries", &entries.join(" | "))],
+            )
+        };
+        Some(if self.whispers_available {
+            ChatBotCommand::SendWhisper {
+                user: command.user.name,
+                text,
+            }
+        } else {
+            ChatBotCommand::LogTextMessage(format!("Audit for {}: {}", command.user.name, text))
+        })
+    }
+
+    fn handle_refresh_emotes(&mut self, command: Command) -> Option<ChatBotCommand> {
+        if !command.user.has_elevated_rights() {
+            return self.msg("denied");
+        }
+        self.session.emotes_refresh_requested = true;
+        Some(ChatBotCommand::HelixRequest(HelixRequest::Emotes))
+    }
+
+    fn handle_emotes_response(
+        &mut self,
+        result: Result<Emotes, HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let requested = std::mem::take(&mut self.session.emotes_refresh_requested);
+        let emote_set = match result {
+            Ok(emotes) => EmoteSet::new(emotes),
+            Err(error) => {
+                println!("Could not fetch the emotes: {:?}", error);
+                return if requested {
+                    self.helix_error_message(error)
+                } else {
+                    None
+                };
+            }
+        };
+        if let Some(fallback) = &self.emote_only_fallback {
+            let unknown = emote_set.unknown_codes(fallback);
+            if !unknown.is_empty() {
+                println!(
+                    "TWITCH_EMOTE_ONLY_FALLBACK contains unknown emotes (typos?): {}",
+                    unknown.join(" ")
+                );
+            }
+        }
+        let (channel, global) = emote_set.counts();
+        println!("Fetched {} channel and {} global emotes", channel, global);
+        self.session.emote_set = Some(emote_set);
+        if !requested {
+            return None;
+        }
+        Some(ChatBotCommand::SendMessage(self.messages.text(
+            "emotes_refreshed",
+            &[
+                ("channel", &channel.to_string()),
+                ("global", &global.to_string()),
+            ],
+        )))
+    }
+
+    // regulars are greeted the first time they chat after the bot started
+    fn greet(&mut self, user: &UserInfo) -> Option<ChatBotCommand> {
+        let name = user.name.to_lowercase();
+        if !self.regulars.contains(&name) || self.session.greeted.contains(&name) {
+            return None;
+        }
+        if self.is_protected() {
+            println!("Not greeting {} while the chat is protected", user.name);
+            return None;
+        }
+        // the regular is greeted when they chat again after the chat is not restricted anymore
+        if self
+            .mode_policy
+            .suppresses(ProactiveMessage::Greeting, &self.connection.room)
+        {
+            println!("Not greeting {} while the chat is restricted", user.name);
+            return None;
+        }
+        let decision = match self.greeting_throttle.as_mut() {
+            Some(throttle) => throttle.greet(&user.name),
+            None => GreetingDecision::Individual,
+        };
+        match decision {
+            // the regular is greeted when they chat again after the chat calmed down
+            GreetingDecision::Suppressed => {
+                println!("Not greeting {} while the chat is busy", user.name);
+                return None;
+            }
+            GreetingDecision::Batched { first } => {
+                self.session.greeted.insert(name);
+                let batch_interval = self.greeting_throttle.as_ref()?.batch_interval();
+                return first.then_some(ChatBotCommand::TimedCallback {
+                    duration: batch_interval,
+                    event: ChatBotEvent::GreetingBatchDue,
+                });
+            }
+            GreetingDecision::Individual => {}
+        }
+        self.session.greeted.insert(name);
+        let context = TemplateContext {
+            user: Some(user),
+            values: &[("user", &user.name)],
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        Some(ChatBotCommand::SendMessage(
+            self.messages
+                .template("greeting")
+                .render(&context, &mut self.rng),
+        ))
+    }
+
+    // counts the chat message towards the chat rate, the batch waiting for its timer is welcomed
+    // when the chat calms down
+    fn watch_chat_rate(&mut self, now: Instant) -> Option<ChatBotCommand> {
+        let throttle = self.greeting_throttle.as_mut()?;
+        match throttle.record_message(now)? {
+            Transition::Busy => {
+                let greetings = match throttle.mode() {
+                    ThrottleMode::Aggregate => "batched",
+                    ThrottleMode::Suppress => "held back",
+                };
+                println!(
+                    "The chat is busy with {} messages per minute, greetings are {}",
+                    throttle.rate(),
+                    greetings
+                );
+                None
+            }
+            Transition::Calm(batch) => {
+                println!(
+                    "The chat calmed down to {} messages per minute, greetings are sent again",
+                    throttle.rate()
+                );
+                self.welcome_batch(batch)
+            }
+        }
+    }
+
+    fn welcome_batch(&self, users: Vec<String>) -> Option<ChatBotCommand> {
+        if users.is_empty() {
+            return None;
+        }
+        let names: Vec<String> = users.iter().map(|user| format!("@{}", user)).collect();
+        Some(ChatBotCommand::SendMessage(self.messages.plural(
+            "greeting_batch",
+            users.len() as i64,
+            &[("users", &names.join(", "))],
+        )))
+    }
+
+    fn handle_greeting_batch_due(&mut self) -> Option<ChatBotCommand> {
+        let batch = self.greeting_throttle.as_mut()?.take_batch();
+        self.welcome_batch(batch)
+    }
+
+    // the bot's own messages do not count, so that a quip mentioning the bot does not answer itself
+    fn reply_to_mention(&mut self, user: &UserInfo, text: &str) -> Option<ChatBotCommand> {
+        let mentioned = mentions(text, &self.bot_name)
+            || self
+                .bot_display_name
+                .as_deref()
+                .is_some_and(|display_name| mentions(text, display_name));
+        if user.name.eq_ignore_ascii_case(&self.bot_name) || !mentioned {
+            return None;
+        }
+        if self.is_protected() {
+            return None;
+        }
+        let template = self
+            .mention_reply
+            .as_mut()?
+            .reply(&user.name, Instant::now())?;
+        let context = TemplateContext {
+            user: Some(user),
+            values: &[("user", &user.name)],
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        Some(ChatBotCommand::SendMessage(
+            template.render(&context, &mut self.rng),
+        ))
+    }
+
+    // the bot's own messages could match a pattern again, so they are never answered
+    fn autorespond(&mut self, user: &UserInfo, text: &str) -> Option<ChatBotCommand> {
+        if user.name.eq_ignore_ascii_case(&self.bot_name) || self.is_protected() {
+            return None;
+        }
+        let template = self.autoresponder.respond(text, Instant::now())?;
+        let context = TemplateContext {
+            user: Some(user),
+            values: &[("user", &user.name)],
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        Some(ChatBotCommand::SendMessage(
+            template.render(&context, &mut self.rng),
+        ))
+    }
+
+    // promotions are congratulated, demotions are only logged
+    fn notice_role_change(&mut self, user: &UserInfo) -> Vec<ChatBotCommand> {
+        let (
+            RoleChange {
+                user: key,
+                from,
+                to,
+            },
+            announce,
+        ) = match self.roles.as_mut() {
+            Some(roles) => match roles.observe(user) {
+                Some(change) => (change, roles.announce),
+                None => return Vec::new(),
+            },
+            None => return Vec::new(),
+        };
+        let mut commands = vec![ChatBotCommand::StoreRole {
+            user: key,
+            role: to,
+        }];
+        match from {
+            Some(from) if from > to => {
+                println!(
+                    "{} is not {} anymore but {}",
+                    user.name,
+                    from.name(),
+                    to.name()
+                );
+            }
+            Some(from) if announce => {
+                let context = TemplateContext {
+                    user: Some(user),
+                    values: &[("user", &user.name)],
+                    counters: Some(&self.counters),
+                    ..TemplateContext::default()
+                };
+                let key = format!("role_{}_to_{}", from.name(), to.name());
+                commands.push(ChatBotCommand::SendMessage(
+                    self.messages.template(&key).render(&context, &mut self.rng),
+                ));
+            }
+            _ => (),
+        }
+        commands
+    }
+
+    // admin commands are checked by user id, names can be changed by the users
+    fn handle_admin(&self, command: Command) -> Option<ChatBotCommand> {
+        let authorized = command
+            .user
+            .id
+            .as_ref()
+            .is_some_and(|id| self.owners.contains(id));
+        if !authorized {
+            return Some(ChatBotCommand::LogTextMessage(format!(
+                "Unauthorized !{} by {} ({})",
+                command.kind.name(),
+                command.user.name,
+                command.user.id.as_deref().unwrap_or("unknown id")
+            )));
+        }
+        match command.kind {
+            CommandType::Say if command.options.is_empty() => self.msg("say_usage"),
+            CommandType::Say => match command.options[0].strip_prefix('#') {
+                Some(channel) if is_channel_login(channel) => {
+                    let channel = channel.to_lowercase();
+                    let text = command.rest(1);
+                    if text.is_empty() {
+                        self.msg("say_usage")
+                    } else if channel.eq_ignore_ascii_case(&self.channel_name) {
+                        Some(ChatBotCommand::SendMessage(text))
+                    } else {
+                        Some(ChatBotCommand::SendMessageTo { channel, text })
+                    }
+                }
+                _ => Some(ChatBotCommand::SendMessage(command.rest(0))),
+            },
+            CommandType::Shutdown => Some(ChatBotCommand::MultipleCommands(vec![
+                ChatBotCommand::SendMessage(self.messages.text("shutdown", &[])),
+                ChatBotCommand::Shutdown,
+            ])),
+            CommandType::RestartConn => Some(ChatBotCommand::Reconnect),
+            CommandType::BotStatus => Some(ChatBotCommand::ReportStatus),
+            CommandType::Scopes => Some(ChatBotCommand::HelixRequest(HelixRequest::TokenScopes)),
+            CommandType::BotColor => match command.options.first() {
+                None => self.msg("botcolor_usage"),
+                Some(color) => match parse_chat_color(color) {
+                    Some(color) => Some(ChatBotCommand::HelixRequest(
+                        HelixRequest::UpdateChatColor { color },
+                    )),
+                    None => Some(ChatBotCommand::SendMessage(self.messages.text(
+                        "botcolor_invalid",
+                        &[("color", color), ("colors", &chat_color_names())],
+                    ))),
+                },
+            },
+            _ => None,
+        }
+    }
+
+    fn handle_status(&self, status: BotStatus) -> Option<ChatBotCommand> {
+        let mut connection = if status.connected {
+            self.messages.text("botstatus_connected", &[])
+        } else {
+            self.messages.text("botstatus_disconnected", &[])
+        };
+        if status.reconnects > 0 {
+            connection = format!(
+                "{}, {}",
+                connection,
+                self.messages
+                    .plural("botstatus_reconnects", status.reconnects.into(), &[])
+            );
+        }
+        let memory = match status.memory_kb {
+            Some(memory_kb) => format!("{} MB", memory_kb / 1024),
+            None => self.messages.text("botstatus_memory_unknown", &[]),
+        };
+        let latency = match (status.latency, status.last_latency) {
+            (Some(latency), Some(last_latency)) => self.messages.text(
+                if latency > DEGRADED_LATENCY {
+                    "botstatus_latency_degraded"
+                } else {
+                    "botstatus_latency"
+                },
+                &[
+                    ("milliseconds", &latency.as_millis().to_string()),
+                    ("last", &last_latency.as_millis().to_string()),
+                ],
+            ),
+            _ => self.messages.text("botstatus_latency_unknown", &[]),
+        };
+        let mut text = self.messages.text(
+            "botstatus",
+            &[
+                ("uptime", &format_uptime(status.uptime)),
+                ("connection", &connection),
+                ("queued", &status.queued_messages.to_string()),
+                ("memory", &memory),
+                ("duplicates", &status.suppressed_duplicates.to_string()),
+                ("latency", &latency),
+            ],
+        );
+        if self.dry_run {
+            text = format!(
+                "{} | {}",
+                text,
+                self.messages.text("botstatus_dry_run", &[])
+            );
+        }
+        if let Some(throttle) = self.greeting_throttle.as_ref() {
+            let greetings = match (throttle.is_busy(), throttle.mode()) {
+                (false, _) => "botstatus_greetings_individual",
+                (true, ThrottleMode::Aggregate) => "botstatus_greetings_batched",
+                (true, ThrottleMode::Suppress) => "botstatus_greetings_held_back",
+            };
+            text = format!(
+                "{} | {}",
+                text,
+                self.messages.text(
+                    "botstatus_greetings",
+                    &[
+                        ("greetings", &self.messages.text(greetings, &[])),
+                        ("rate", &throttle.rate().to_string()),
+                        ("switches", &throttle.switches().to_string()),
+                    ],
+                )
+            );
+        }
+        if let Some(HelixMetrics {
+            requests,
+            retries,
+            failed,
+            ..
+        }) = status.helix
+        {
+            text = format!(
+                "{} | {}",
+                text,
+                self.messages.text(
+                    "botstatus_helix",
+                    &[
+                        ("requests", &requests.to_string()),
+                        ("retries", &retries.to_string()),
+                        ("failed", &failed.to_string()),
+                    ],
+                )
+            );
+        }
+        Some(ChatBotCommand::SendMessage(text))
+    }
+
+    fn handle_ping(&mut self) -> Option<ChatBotCommand> {
+        let cooling_down = self
+            .cooldowns
+            .last_ping
+            .is_some_and(|last_ping| last_ping.elapsed() < PING_COOLDOWN);
+        if cooling_down {
+            return None;
+        }
+        self.cooldowns.last_ping = Some(Instant::now());
+        Some(ChatBotCommand::MeasureLatency)
+    }
+
+    fn handle_unknown_command(&mut self, command_name: &str) -> Option<ChatBotCommand> {
+        if !self.unknown_command_reply {
+            return None;
+        }
+        let cooling_down = self
+            .cooldowns
+            .last_unknown_command_reply
+            .is_some_and(|last_reply| last_reply.elapsed() < UNKNOWN_COMMAND_COOLDOWN);
+        if cooling_down {
+            return None;
+        }
+        self.cooldowns.last_unknown_command_reply = Some(Instant::now());
+        Some(ChatBotCommand::SendMessage(
+            self.messages
+                .text("unknown_command", &[("command", command_name)]),
+        ))
+    }
+
+    fn handle_command(&mut self, command: Command) -> Option<ChatBotCommand> {
+        println!("Executing this command: {:#?}", command);
+        use ChatBotCommand::*;
+        match command.kind {
+            CommandType::Discord => self.msg("discord"),
+            CommandType::Help => self.msg("help"),
+            CommandType::Info => self.handle_info(command),
+            CommandType::Slap => {
+                println!(
+                    "Slapping one of these guys \n{:#?}",
+                    self.connection.chatters
+                );
+                // Notice how we can now do everything in a single expression
+                // because we removed the IO from this place
+                let slapping_user = &command.user.name;
+                println!("This guy specifically : {}", slapping_user);
+                command
+                    .arg_user(0)
+                    .and_then(|slapped_user| self.connection.chatters.get(slapped_user))
+                    .map(|slapped_user| {
+                        let context = TemplateContext {
+                            user: Some(&command.user),
+                            args: &command.options,
+                            values: &[("user", slapping_user), ("target", slapped_user)],
+                            counters: Some(&self.counters),
+                        };
+                        SendMessage(
+                            self.messages
+                                .template("slap")
+                                .render(&context, &mut self.rng),
+                        )
+                    })
+            }
+            CommandType::NewCommand => {
+                if command.user.has_elevated_rights() {
+                    if command.options.len() < 2 {
+                        self.msg("new_command_no_option")
+                    } else {
+                        let new_command_name = &command.options[0];
+                        if !valid_command_name(new_command_name, self.command_prefix()) {
+                            return Some(SendMessage(self.messages.text(
+                                "new_command_invalid_name",
+                                &[("prefix", self.command_prefix())],
+                            )));
+                        }
+                        match Template::parse(&command.rest(1)) {
+                            Ok(new_command_message) => {
+                                // the reply is cut when the command is used, the tags make its
+                                // length vary, so the moderator is told if it is too long already
+                                let context = TemplateContext {
+                                    user: Some(&command.user),
+                                    args: &[],
+                                    values: &[("user", &command.user.name)],
+                                    counters: Some(&self.counters),
+                                };
+                                let too_long = new_command_message
+                                    .render(&context, &mut self.rng)
+                                    .chars()
+                                    .count()
+                                    > MAX_MESSAGE_LENGTH;
+                                self.dynamic_commands
+                                    .insert(new_command_name.to_owned(), new_command_message);
+                                if too_long {
+                                    Some(SendMessage(self.messages.text(
+                                        "new_command_truncated",
+                                        &[("length", &MAX_MESSAGE_LENGTH.to_string())],
+                                    )))
+                                } else {
+                                    self.msg("new_command_successful")
+                                }
+                            }
+                            Err(err) => self.invalid_template(err),
+                        }
+                    }
+                } else {
+                    self.msg("denied")
+                }
+            }
+            CommandType::RemoveCommand => {
+                if command.user.has_elevated_rights() {
+                    if command.options.is_empty() {
+                        self.msg("remove_command_no_option")
+                    } else {
+                        let command_name = &command.options[0];
+                        self.dynamic_commands.remove(command_name);
+                        self.msg("remove_command_successful")
+                    }
+                } else {
+                    self.msg("denied")
+                }
+            }
+
+            CommandType::NewRepeating => {
+                if command.user.has_elevated_rights() {
+                    if command.options.len() < 2 {
+                        // TODO: set the correct message here
+                        self.msg("new_command_no_option")
+                    } else {
+                        let message_name = &command.options[0];
+                        let text = match Template::parse(&command.rest(2)) {
+                            Ok(text) => text,
+                            Err(err) => return self.invalid_template(err),
+                        };
+                        if let Some(seconds) = command.arg_u32(1) {
+                            let interval = Duration::from_secs(seconds.into());
+                            let id = Uuid::new_v4();
+                            self.repeating_messages.insert(
+                                message_name.to_string(),
+                                RepeatingMessage {
+                                    name: message_name.to_string(),
+                                    text,
+                                    interval,
+                                    timer_id: id,
+                                },
+                            );
+                            // TODO: set the correct message here
+                            Some(MultipleCommands(vec![
+                                ChatBotCommand::SendMessage(
+                                    self.messages.text("new_command_successful", &[]),
+                                ),
+                                TimedCallback {
+                                    duration: interval,
+                                    event: ChatBotEvent::TimedMessage(message_name.to_string(), id),
+                                },
+                            ]))
+                        } else {
+                            // TODO: set the correct message here
+                            self.msg("new_command_no_option")
+                        }
+                    }
+                } else {
+                    self.msg("denied")
+                }
+            }
+
+            CommandType::RemoveRepeating => {
+                if command.user.has_elevated_rights() {
+                    if command.options.is_empty() {
+                        // TODO: set the correct message here
+                        self.msg("remove_command_no_option")
+                    } else {
+                        let command_name = &command.options[0];
+                        self.repeating_messages.remove(command_name);
+                        // TODO: set the correct message here
+                        self.msg("remove_command_successful")
+                    }
+                } else {
+                    self.msg("denied")
+                }
+            }
+
+            CommandType::Counter => self.handle_counter(command),
+
+            CommandType::Deaths => self.handle_deaths(command),
+
+            CommandType::Prediction => self.handle_prediction(command),
+
+            CommandType::Debug => self.handle_debug(command),
+
+            CommandType::Ping => self.handle_ping(),
+
+            CommandType::Stats => self.handle_stats(command),
+
+            CommandType::Alert => self.handle_alert(command),
+
+            CommandType::GoLive => self.handle_go_live(command),
+
+            CommandType::Roll => self.handle_roll(command),
+
+            CommandType::Quiz => self.handle_quiz(command),
+
+            CommandType::Duel => self.handle_duel(command),
+
+            CommandType::Accept => self.handle_accept(command),
+
+            CommandType::Raffle => self.handle_raffle(command),
+
+            CommandType::Enter => self.handle_enter(command),
+
+            CommandType::First => self.handle_first(),
+
+            CommandType::Shoutout => self.handle_shoutout(command),
+
+            CommandType::BotReset => self.handle_bot_reset(command),
+
+            CommandType::Enable | CommandType::Disable => self.handle_toggle(command),
+
+            CommandType::Commands => self.handle_commands(),
+
+            CommandType::Timers => self.handle_timers(command),
+
+            CommandType::AccountAge => self.handle_account_age(command),
+
+            CommandType::NameColor => self.handle_name_color(command),
+
+            CommandType::MyRolls => self.handle_my_rolls(command),
+            CommandType::RollStats => self.handle_roll_stats(command),
+            CommandType::Raid => self.handle_raid(command),
+            CommandType::Unraid => self.handle_unraid(command),
+            CommandType::EmoteList => self.handle_emote_list(),
+            CommandType::RefreshEmotes => self.handle_refresh_emotes(command),
+            CommandType::Vod => self.handle_vod(command),
+            CommandType::LastClip => self.handle_last_clip(command),
+            CommandType::Config => self.handle_config(command),
+            CommandType::AllClear => self.handle_all_clear(command),
+            CommandType::Context => self.handle_context(command),
+            CommandType::Define => self.handle_define(command),
+            CommandType::Perf => self.handle_perf(command),
+            CommandType::Viewers => self.handle_viewers(command),
+            CommandType::Trending => self.handle_trending(command),
+            CommandType::Followers => self.handle_count(CountKind::Followers, command),
+            CommandType::Subs => self.handle_subs(command),
+            CommandType::Audit => self.handle_audit(command),
+            CommandType::Slow
+            | CommandType::SlowOff
+            | CommandType::FollowersOnly
+            | CommandType::SubOnly
+            | CommandType::EmoteOnly
+            | CommandType::UniqueChat => self.handle_chat_mode(command),
+
+            CommandType::Say
+            | CommandType::Shutdown
+            | CommandType::RestartConn
+            | CommandType::BotStatus
+            | CommandType::Scopes
+            | CommandType::BotColor => self.handle_admin(command),
+
+            CommandType::Emote => match command.options.first() {
+                Some(name) => match self.emotes.get(name) {
+                    Some(text) => Some(SendMessage(text.to_owned())),
+                    None => Some(SendMessage(
+                        self.messages.text("emote_unknown", &[("name", name)]),
+                    )),
+                },
+                None => self.msg("emote_usage"),
+            },
+
+            CommandType::FollowGoal => self.handle_goal(GoalKind::Followers, command),
+
+            CommandType::SubGoal => self.handle_goal(GoalKind::SubPoints, command),
+
+            CommandType::Dynamic(command_name) => match self.dynamic_commands.get(&command_name) {
+                Some(template) => {
+                    let context = TemplateContext {
+                        user: Some(&command.user),
+                        args: &command.options,
+                        values: &[("user", &command.user.name)],
+                        counters: Some(&self.counters),
+                    };
+                    // the reply would be rejected by twitch
+                    let reply = template.render(&context, &mut self.rng);
+                    Some(SendMessage(
+                        reply.chars().take(MAX_MESSAGE_LENGTH).collect(),
+                    ))
+                }
+                None => self.handle_unknown_command(&command_name),
+            },
+        }
+    }
+
+    fn handle_command_event(&mut self, command: Command) -> Option<ChatBotCommand> {
+        let user_name = command.user.name.to_owned();
+        let command_name = command.kind.name().to_owned();
+        if self.disabled_commands.contains(&command_name) {
+            println!(
+                "Ignoring !{} of {}, the command is disabled",
+                command_name, user_name
+            );
+            return self.record_usage(&user_name, None);
+        }
+        // only users who may use the command are asked to confirm it, the others are denied as usual
+        if self.permits(&command.user, command.kind.permission())
+            && !self.confirmations.confirm(
+                &user_name,
+                &command_name,
+                &command.raw_args,
+                Instant::now(),
+            )
+        {
+            return Some(ChatBotCommand::SendMessage(self.messages.text(
+                "confirm_command",
+                &[
+                    ("command", &command_name),
+                    ("seconds", &CONFIRMATION_WINDOW.as_secs().to_string()),
+                ],
+            )));
+        }
+        if self.command_cooling_down(&command_name, &command.user) {
+            println!(
+                "Ignoring !{} of {}, the command is cooling down",
+                command_name, user_name
+            );
+            return self.record_usage(&user_name, None);
+        }
+        // privileged commands are audited, also when they are denied or fail
+        let audit_entry = (command.kind.permission() != Permission::Everyone).then(|| {
+            (
+                AuditEntry::new(&command, Utc::now()),
+                self.permits(&command.user, command.kind.permission()),
+            )
+        });
+        let pending_chat_settings = self.audit.pending_chat_settings();
+        let raiding = self.connection.raid.is_some();
+        let reply = self.timed(command, Self::handle_command);
+        if let Some((entry, permitted)) = audit_entry {
+            self.audit_command(entry, permitted, &reply, pending_chat_settings, raiding);
+        }
+        let reply = reply.map(|reply| self.route(&command_name, &user_name, reply));
+        let fallback = self
+            .emote_only_fallback
+            .clone()
+            .filter(|_| self.connection.room.emote_only == Some(true));
+        let reply = match &fallback {
+            // text replies would be rejected or pointless, emotes are sent instead
+            Some(fallback) => reply.and_then(|reply| emotes_instead(fallback, reply)),
+            None if self.mention_user => reply.map(|reply| mention(&user_name, reply)),
+            None => reply,
+        };
+        let reply = match (reply, self.record_usage(&user_name, Some(&command_name))) {
+            (Some(reply), Some(celebration)) => {
+                Some(ChatBotCommand::MultipleCommands(vec![reply, celebration]))
+            }
+            (reply, celebration) => reply.or(celebration),
+        };
+        if self.may_chat() || (fallback.is_some() && self.may_chat_emotes()) {
+            reply
+        } else {
+            println!(
+                "Dropping the reply to !{} of {}, the bot may not chat in {:?}",
+                command_name, user_name, self.connection.room
+            );
+            reply.and_then(without_messages)
+        }
+    }
+
+    /// Audits a privileged command once it is known how it ended, the twitch api tells whether
+    /// its chat settings update or raid worked. Commands which only read (e.g. `!counter deaths`)
+    /// are not audited for users who are denied their side effects.
+    fn audit_command(
+        &mut self,
+        entry: AuditEntry,
+        permitted: bool,
+        reply: &Option<ChatBotCommand>,
+        pending_chat_settings: usize,
+        raiding: bool,
+    ) {
+        if !permitted {
+            let denied = self.messages.text("denied", &[]);
+            if matches!(reply, Some(ChatBotCommand::SendMessage(message)) if *message == denied) {
+                self.audit.record(entry.with_outcome(AuditOutcome::Denied));
+            }
+        } else if let Some(raid) = self.connection.raid.as_ref().filter(|_| !raiding) {
+            self.audit.await_raid(&raid.target, entry);
+        } else if self.audit.pending_chat_settings() > pending_chat_settings {
+            self.audit
+                .attach_chat_settings(pending_chat_settings, entry);
+        } else {
+            self.audit.record(entry);
+        }
+    }
+
+    /// Whether the user has the permission, e.g. for the side effects of a command.
+    fn permits(&self, user: &UserInfo, permission: Permission) -> bool {
+        match permission {
+            Permission::Everyone => true,
+            Permission::Moderator => user.has_elevated_rights(),
+            Permission::Broadcaster => user.is_broadcaster(),
+            Permission::Owner => user.id.as_ref().is_some_and(|id| self.owners.contains(id)),
+        }
+    }
+
+    // every handler is timed here, so that the handlers themselves do not have to
+    fn timed(
+        &mut self,
+        command: Command,
+        handler: impl FnOnce(&mut Self, Command) -> Option<ChatBotCommand>,
+    ) -> Option<ChatBotCommand> {
+        let command_name = command.kind.name().to_owned();
+        let user_name = command.user.name.to_owned();
+        let started_at = Instant::now();
+        let reply = handler(self, command);
+        let duration = started_at.elapsed();
+        if self.timings.record(&command_name, duration) {
+            println!(
+                "Warning: !{} of {} took {}",
+                command_name,
+                user_name,
+                format_duration(duration)
+            );
+        }
+        reply
+    }
+
+    /// Whether the command was used within its cooldown, starting the cooldown again if not.
+    /// Moderators do not start the cooldown if they bypass it.
+    fn command_cooling_down(&mut self, command_name: &str, user: &UserInfo) -> bool {
+        let cooldown = match self.command_cooldowns.get(command_name) {
+            Some(cooldown) if !(cooldown.mods_bypass_cooldown && user.has_elevated_rights()) => {
+                cooldown.duration
+            }
+            _ => return false,
+        };
+        let now = Instant::now();
+        let cooling_down = self
+            .cooldowns
+            .commands
+            .get(command_name)
+            .is_some_and(|last_use| now.duration_since(*last_use) < cooldown);
+        if !cooling_down {
+            self.cooldowns.commands.insert(command_name.to_owned(), now);
+        }
+        cooling_down
+    }
+
+    /// Sends the messages of a reply to the target configured for the command.
+    fn route(&self, command_name: &str, user_name: &str, reply: ChatBotCommand) -> ChatBotCommand {
+        match reply {
+            ChatBotCommand::SendMessage(text) => {
+                let response = self.routing.respond(command_name, text);
+                match response.preferred_target {
+                    ResponseTarget::Whisper if self.whispers_available => {
+                        ChatBotCommand::SendWhisper {
+                            user: user_name.to_owned(),
+                            text: response.text,
+                        }
+                    }
+                    // the chat was told once that whispers are unavailable
+                    ResponseTarget::Chat | ResponseTarget::Whisper => {
+                        ChatBotCommand::SendMessage(response.text)
+                    }
+                    ResponseTarget::Log => ChatBotCommand::LogTextMessage(format!(
+                        "Reply to !{} of {}: {}",
+                        command_name, user_name, response.text
+                    )),
+                }
+            }
+            ChatBotCommand::MultipleCommands(commands) => ChatBotCommand::MultipleCommands(
+                commands
+                    .into_iter()
+                    .map(|command| self.route(command_name, user_name, command))
+                    .collect(),
+            ),
+            command => command,
+        }
+    }
+
+    // a failed whisper is sent to the chat instead
+    fn handle_whisper_response(
+        &mut self,
+        login: String,
+        text: String,
+        result: Result<(), HelixError>,
+    ) -> Option<ChatBotCommand> {
+        let error = result.err()?;
+        println!("Could not whisper to {}: {:?}", login, error);
+        let message = ChatBotCommand::SendMessage(format!("@{} {}", login, text));
+        // other errors (e.g. the user does not accept whispers) only affect this whisper
+        let unavailable = matches!(
+            error,
+            HelixError::Unavailable(_) | HelixError::Status(401 | 403, _)
+        );
+        if unavailable && self.whispers_available {
+            self.whispers_available = false;
+            return Some(ChatBotCommand::MultipleCommands(vec![
+                ChatBotCommand::SendMessage(self.messages.text("whisper_unavailable", &[])),
+                message,
+            ]));
+        }
+        Some(message)
+    }
+
+    /// The first known command written inside a chat message with the text after it as arguments,
+    /// if commands are used anywhere and the side effects of the command are permitted there.
+    fn embedded_command(&self, text: &str) -> Option<(CommandType, String)> {
+        let max_permission = self.commands_anywhere?;
+        let mut rest = text.trim_start();
+        let kind = loop {
+            let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if word.is_empty() {
+                return None;
+            }
+            rest = after.trim_start();
+            let kind = match embedded_command_name(word, self.command_prefix()) {
+                Some(name) => ReceiveEvent::parse_command_kind(name),
+                None => continue,
+            };
+            match &kind {
+                CommandType::Dynamic(name) if !self.dynamic_commands.contains_key(name) => {}
+                _ => break kind,
+            }
+        };
+        if kind.permission() > max_permission {
+            println!("Not using !{} written inside a chat message", kind);
+            return None;
+        }
+        Some((kind, rest.to_owned()))
+    }
+
+    // as long as the badges of the bot are unknown, it tries to chat
+    fn may_chat(&self) -> bool {
+        self.connection
+            .bot_user
+            .as_ref()
+            .is_none_or(|bot_user| self.connection.room.allows(bot_user))
+    }
+
+    /// Whether the bot may send messages consisting of emotes only.
+    fn may_chat_emotes(&self) -> bool {
+        let room = RoomState {
+            emote_only: None,
+            ..self.connection.room
+        };
+        self.connection
+            .bot_user
+            .as_ref()
+            .is_none_or(|bot_user| room.allows(bot_user))
+    }
+
+    fn handle_timed_message(&mut self, name: &str, id: Uuid) -> Option<ChatBotCommand> {
+        let msg = self
+            .repeating_messages
+            .get(name)
+            .filter(|msg| msg.timer_id == id)?;
+        let context = TemplateContext {
+            counters: Some(&self.counters),
+            ..TemplateContext::default()
+        };
+        let text = msg.text.render(&context, &mut self.rng);
+        let next = ChatBotCommand::TimedCallback {
+            duration: msg.interval,
+            event: ChatBotEvent::TimedMessage(msg.name.to_owned(), id),
+        };
+        if self.timers_paused {
+            println!("Skipping the timer {} while the timers are paused", name);
+            return Some(next);
+        }
+        if self.is_protected() {
+            println!("Skipping the timer {} while the chat is protected", name);
+            return Some(next);
+        }
+        if self
+            .mode_policy
+            .suppresses(ProactiveMessage::Timer, &self.connection.room)
+        {
+            println!(
+                "Suppressing the timer {} while the chat is restricted",
+                name
+            );
+            self.connection.suppressed_timer = Some(text);
+            return Some(next);
+        }
+        Some(ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::SendMessage(text),
+            next,
+        ]))
+    }
+
+    fn handle_room_state(&mut self, change: RoomState) -> Option<ChatBotCommand> {
+        let was_restrictive = self.connection.room.is_restrictive();
+        self.connection.room.update(change);
+        println!("The chat modes changed to {:?}", self.connection.room);
+        if !was_restrictive || self.connection.room.is_restrictive() {
+            return None;
+        }
+        let suppressed_timer = self.connection.suppressed_timer.take();
+        if self.mode_policy.resend_timer {
+            suppressed_timer.map(ChatBotCommand::SendMessage)
+        } else {
+            None
+        }
+    }
+
+    /// Parses a raw chat line like the connector does and handles the resulting event.
+    #[cfg(test)]
+    pub(crate) fn handle_raw(&mut self, line: &str) -> Option<ChatBotCommand> {
+        let channel = ReceiveEvent::channel(line);
+        let prefix = self.command_prefixes.for_channel(channel.as_deref());
+        match ReceiveEvent::parse_with_prefix(line, prefix)? {
+            ReceiveEvent::ChatBotEvent(content) => self.handle_event(Event {
+                channel,
+                raw: line.to_owned(),
+                ..Event::from(content)
+            }),
+            ReceiveEvent::ConnectorEvent(_) => None,
+        }
+    }
+
+    /// Handles an event received by the connector, or a bare event of the bot itself (e.g. a timer).
+    /// In dry-run mode the messages and the Helix requests with side effects of the reply are
+    /// logged instead, unless an owner asked for them.
+    pub fn handle_event(&mut self, event: impl Into<Event>) -> Option<ChatBotCommand> {
+        let event = event.into();
+        let asked_by_owner = self.asked_by_owner(&event.content);
+        let reply = self.dispatch_event(event)?;
+        if !self.dry_run || asked_by_owner {
+            return Some(reply);
+        }
+        let mut skipped = Vec::new();
+        let mut commands = vec![logged_instead(&self.channel_name, reply, &mut skipped)];
+        // the requests which were not sent fail, so that the bot does not wait for their answers
+        for request in skipped {
+            let error = HelixError::Unavailable("dry run".to_owned());
+            let event = ChatBotEvent::HelixResponse(request.failed(error));
+            if let Some(answer) = self.dispatch_event(event.into()) {
+                commands.push(logged_instead(&self.channel_name, answer, &mut Vec::new()));
+            }
+        }
+        match commands.len() {
+            1 => commands.pop(),
+            _ => Some(ChatBotCommand::MultipleCommands(commands)),
+        }
+    }
+
+    fn asked_by_owner(&self, event: &ChatBotEvent) -> bool {
+        match event {
+            ChatBotEvent::Command(command) | ChatBotEvent::Whisper(command) => {
+                command.kind.is_admin()
+                    && command
+                        .user
+                        .id
+                        .as_ref()
+                        .is_some_and(|id| self.owners.contains(id))
+            }
+            // the control interface needs the secret, the status is only reported on !botstatus
+            ChatBotEvent::Say(_) | ChatBotEvent::Raw(_) | ChatBotEvent::Status(_) => true,
+            _ => false,
+        }
+    }
+
+    fn dispatch_event(&mut self, event: Event) -> Option<ChatBotCommand> {
+        use ChatBotCommand::*;
+        let color = ReceiveEvent::name_color(&event.raw);
+        // events of the bot itself are about its own channel
+        let channel = event
+            .channel
+            .unwrap_or_else(|| self.channel_name.to_owned());
+        self.event_channel = channel.to_owned();
+        match event.content {
+            ChatBotEvent::Command(command) => {
+                self.record_name_color(&command.user.name, color);
+                // asking who was first does not make you first
+                if command.kind != CommandType::First {
+                    self.record_first_chatter(&command.user.name);
+                }
+                match self.command_gate(&command) {
+                    Some(gate) => {
+                        let user_name = command.user.name.to_owned();
+                        let event = Event {
+                            channel: Some(channel),
+                            ..ChatBotEvent::Command(command).into()
+                        };
+                        self.check_gate(gate, &user_name, event)
+                    }
+                    None => self.handle_command_event(command),
+                }
+            }
+            ChatBotEvent::Join(user) => {
+                println!("{:?} joined", &user);
+                self.connection.chatters.insert(user);
+                None
+            }
+            ChatBotEvent::Part(user) => {
+                println!("{:?} parted", &user);
+                self.connection.chatters.remove(&user);
+                None
+            }
+            ChatBotEvent::TextMessage(tm) => {
+                self.record_name_color(&tm.user.name, color);
+                self.record_first_chatter(&tm.user.name);
+                self.history.record(
+                    &channel,
+                    HistoryEntry {
+                        user: tm.user.name.to_owned(),
+                        text: tm.text.to_owned(),
+                        sent_at: event.sent_at,
+                        message_id: tm.id.clone(),
+                        deleted: false,
+                    },
+                );
+                let protection = self.watch_first_message(&tm);
+                // messages with links wait for their gate, the commands in them are not used
+                let embedded = match self.link_gate(&tm) {
+                    Some(_) => None,
+                    // a hate raid must not trigger the bot
+                    None if self.is_protected() => None,
+                    None => self.embedded_command(&tm.text),
+                };
+                // commands and the bot's own messages are no phrases of the chat
+                if embedded.is_none() && !tm.user.name.eq_ignore_ascii_case(&self.bot_name) {
+                    let emote_set = self.session.emote_set.as_ref();
+                    self.trending.track(
+                        &tm.text,
+                        |word| emote_set.is_some_and(|emote_set| emote_set.contains(word)),
+                        Instant::now(),
+                    );
+                }
+                // the embedded command counts the message
+                let celebration = if embedded.is_none() {
+                    self.record_usage(&tm.user.name, None)
+                } else {
+                    None
+                };
+                *self
+                    .connection
+                    .messages
+                    .entry(tm.user.name.to_lowercase())
+                    .or_default() += 1;
+                // while a quiz is running, messages which are just a number are answers
+                if let Some(quiz) = self.session.quiz.as_mut() {
+                    quiz.answer(&tm.user.name, &tm.text);
+                }
+                let mut commands = vec![LogTextMessage(format!("{}: {}", &tm.user.name, &tm.text))];
+                commands.extend(protection);
+                if !tm.user.name.eq_ignore_ascii_case(&self.bot_name) {
+                    commands.extend(self.watch_chat_rate(Instant::now()));
+                }
+                commands.extend(self.greet(&tm.user));
+                commands.extend(self.notice_role_change(&tm.user));
+                commands.extend(celebration);
+                // a message with a command or waiting for its gate is not answered with a quip
+                if embedded.is_none() && self.link_gate(&tm).is_none() {
+                    commands.extend(self.reply_to_mention(&tm.user, &tm.text));
+                    commands.extend(self.autorespond(&tm.user, &tm.text));
+                }
+                if let Some(gate) = self.link_gate(&tm) {
+                    let user_name = tm.user.name.to_owned();
+                    let event = Event {
+                        channel: Some(channel),
+                        ..ChatBotEvent::TextMessage(tm).into()
+                    };
+                    commands.extend(self.check_gate(gate, &user_name, event));
+                } else if let Some((kind, raw_args)) = embedded {
+                    commands.extend(
+                        self.handle_event(Event {
+                            channel: Some(channel),
+                            ..ChatBotEvent::Command(Command::new(
+                                kind, &raw_args, tm.user, tm.room_id,
+                            ))
+                            .into()
+                        }),
+                    );
+                }
+                match commands.len() {
+                    1 => commands.pop(),
+                    _ => Some(MultipleCommands(commands)),
+                }
+            }
+            ChatBotEvent::TimedMessage(message_name, id) => {
+                self.handle_timed_message(&message_name, id)
+            }
+            ChatBotEvent::RoomState(change) => self.handle_room_state(change),
+            ChatBotEvent::ClearChat(user) => {
+                self.history.delete_messages_of(&channel, user.as_deref());
+                None
+            }
+            ChatBotEvent::ClearMessage(message_id) => {
+                self.history.delete_message(&channel, &message_id);
+                None
+            }
+            ChatBotEvent::UserState {
+                badges,
+                display_name,
+            } => {
+                if display_name.is_some() {
+                    self.bot_display_name = display_name;
+                }
+                self.connection.bot_user = Some(UserInfo {
+                    name: self.bot_name.to_owned(),
+                    id: None,
+                    badges,
+                });
+                None
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::Prediction(response)) => {
+                self.handle_prediction_response(response)
+            }
+            ChatBotEvent::Latency(Some(latency)) => Some(SendMessage(self.messages.text(
+                "ping_latency",
+                &[("milliseconds", &latency.as_millis().to_string())],
+            ))),
+            ChatBotEvent::Latency(None) => self.msg("ping_timeout"),
+            ChatBotEvent::Say(text) => Some(SendMessage(text)),
+            ChatBotEvent::Raw(line) => Some(SendRaw(line)),
+            // only the admin commands can be whispered, other whispers are ignored
+            ChatBotEvent::Whisper(command) if command.kind.is_admin() => self.handle_admin(command),
+            ChatBotEvent::Whisper(_) => None,
+            ChatBotEvent::Status(status) => self.handle_status(status),
+            ChatBotEvent::QuizEnd(id) => self.handle_quiz_end(id),
+            ChatBotEvent::Connected => self.handle_connected(),
+            ChatBotEvent::Unresponsive => {
+                println!("The chat server stopped answering, reconnecting");
+                Some(Reconnect)
+            }
+            ChatBotEvent::CheckStream => Some(self.check_stream()),
+            ChatBotEvent::ShoutoutDue => self.handle_shoutout_due(),
+            ChatBotEvent::HelixResponse(HelixResponse::Shoutout { login, result }) => {
+                self.handle_shoutout_response(login, result)
+            }
+            ChatBotEvent::RaidCountdown(id, seconds) => self.handle_raid_countdown(id, seconds),
+            ChatBotEvent::ProtectionExpired(id) => self.end_protection(Some(id)),
+            ChatBotEvent::GreetingBatchDue => self.handle_greeting_batch_due(),
+            ChatBotEvent::HelixResponse(HelixResponse::ChatSettings(result)) => {
+                self.audit.chat_settings_updated(result.clone());
+                self.handle_chat_settings_response(result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::TokenScopes(result)) => {
+                self.handle_token_scopes(result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::ChatColor { color, result }) => {
+                self.handle_chat_color_response(color, result)
+            }
+            ChatBotEvent::RefreshEmotes => Some(HelixRequest(crate::connect::HelixRequest::Emotes)),
+            ChatBotEvent::HelixResponse(HelixResponse::Emotes(result)) => {
+                self.handle_emotes_response(result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::LatestVod(result)) => {
+                self.handle_vod_response(result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::Clip { window, result }) => {
+                self.handle_clip_response(window, result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::Raid { login, result }) => {
+                self.audit.raid_started(&login, result.clone());
+                self.handle_raid_response(login, result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::RaidCanceled(result)) => {
+                self.handle_raid_canceled(result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::Whisper {
+                login,
+                text,
+                result,
+            }) => self.handle_whisper_response(login, text, result),
+            ChatBotEvent::HelixResponse(HelixResponse::StreamInfo(response)) => {
+                self.handle_stream_info(response)
+            }
+            ChatBotEvent::Cheer { message, bits } => {
+                let alert = crate::connect::Alert::Cheer {
+                    user: message.user.name.to_owned(),
+                    bits,
+                };
+                self.handle_alert_message(message, alert)
+            }
+            ChatBotEvent::Redemption { message, reward_id } => {
+                let alert = crate::connect::Alert::Redemption {
+                    user: message.user.name.to_owned(),
+                    reward_id,
+                    text: message.text.to_owned(),
+                };
+                self.handle_alert_message(message, alert)
+            }
+            ChatBotEvent::Announcement { message, color } => {
+                self.handle_announcement(message, &color)
+            }
+            ChatBotEvent::ViewerMilestone { message, streak } => {
+                self.handle_viewer_milestone(&message.user, streak)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::UserAge { login, result }) => {
+                self.handle_user_age(login, result)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::FollowerTotal(response)) => {
+                self.handle_goal_response(GoalKind::Followers, response)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::SubPointTotal(response)) => {
+                self.handle_goal_response(GoalKind::SubPoints, response)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::FollowerCount(response)) => {
+                self.handle_count_response(CountKind::Followers, response)
+            }
+            ChatBotEvent::HelixResponse(HelixResponse::Subscriptions(response)) => {
+                self.handle_subscriptions(response)
+            }
+        }
+    }
+}
+
+/// Configures the settings of a chat bot which are known before it is started.
+#[derive(Debug)]
+pub struct ChatBotBuilder {
+    bot: ChatBot,
+}
+
+impl ChatBotBuilder {
+    /// Sets the (e.g. persisted) counter values the bot starts with.
+    pub fn counters(mut self, counters: HashMap<String, i64>) -> Self {
+        self.bot.counters = counters;
+        self
+    }
+
+    /// Sets the name of the bot user, so that the messages of the bot are not taken for chatters.
+    pub fn nickname(mut self, nickname: &str) -> Self {
+        self.bot.bot_name = nickname.to_lowercase();
+        self
+    }
+
+    /// Lets all users together use a command (by name or alias) only once in the given time,
+    /// moderators bypass the cooldown unless `mods_bypass_cooldown` turns that off.
+    pub fn cooldown(mut self, command_name: &str, cooldown: Duration) -> Self {
+        let kind = ReceiveEvent::parse_command_kind(&command_name.to_lowercase());
+        self.bot.command_cooldowns.insert(
+            kind.name().to_owned(),
+            CommandCooldown {
+                duration: cooldown,
+                mods_bypass_cooldown: true,
+            },
+        );
+        self
+    }
+
+    /// Sets whether moderators bypass the cooldown of a command (by name or alias).
+    pub fn mods_bypass_cooldown(mut self, command_name: &str, mods_bypass_cooldown: bool) -> Self {
+        let kind = ReceiveEvent::parse_command_kind(&command_name.to_lowercase());
+        if let Some(cooldown) = self.bot.command_cooldowns.get_mut(kind.name()) {
+            cooldown.mods_bypass_cooldown = mods_bypass_cooldown;
+        }
+        self
+    }
+
+    /// Replaces the random number generator, e.g. with a seeded one.
+    #[cfg(test)]
+    pub fn with_rng(mut self, rng: StdRng) -> Self {
+        self.bot.rng = rng;
+        self
+    }
+
+    /// Sets the prefixes of commands written in chat messages by channel (`!` by default),
+    /// which have to match the prefixes the connector parses commands with.
+    pub fn command_prefixes(mut self, command_prefixes: CommandPrefixes) -> Self {
+        self.bot.command_prefixes = command_prefixes;
+        self
+    }
+
+    /// Replaces the (english) default messages of the chat bot.
+    pub fn messages(mut self, messages: MessageCatalog) -> Self {
+        self.bot.messages = messages;
+        self
+    }
+
+    /// Sets the targets of the follower and sub goals.
+    pub fn goals(mut self, goals: HashMap<GoalKind, u64>) -> Self {
+        self.bot.goals = goals;
+        self
+    }
+
+    /// Makes the bot start every reply to a command with `@user`.
+    pub fn mention_user(mut self, mention_user: bool) -> Self {
+        self.bot.mention_user = mention_user;
+        self
+    }
+
+    /// Sets the texts of the `!emote` command by name.
+    pub fn emotes(mut self, emotes: HashMap<String, String>) -> Self {
+        self.bot.emotes = emotes;
+        self
+    }
+
+    /// Sets the (e.g. persisted) usage statistics of the users by lowercase name.
+    pub fn usage_stats(mut self, usage_stats: HashMap<String, UserStats>) -> Self {
+        self.bot.usage_stats = usage_stats;
+        self
+    }
+
+    /// Restricts commands and links to users whose accounts and follows are old enough.
+    pub fn gates(mut self, gates: GateConfig) -> Self {
+        self.bot.gates = gates;
+        self
+    }
+
+    /// Makes the bot answer unknown commands (rate limited) instead of ignoring them.
+    pub fn unknown_command_reply(mut self, unknown_command_reply: bool) -> Self {
+        self.bot.unknown_command_reply = unknown_command_reply;
+        self
+    }
+
+    /// Forwards alerts of the given types (e.g. `cheer`) to the stream overlays,
+    /// cheers only from `min_bits` on.
+    pub fn alerts(mut self, alert_events: HashSet<String>, alert_min_bits: u64) -> Self {
+        self.bot.alert_events = alert_events;
+        self.bot.alert_min_bits = alert_min_bits;
+        self
+    }
+
+    /// Sets the channel announced when the stream goes live,
+    /// with a template for the announcement using `{title}`, `{game}` and `{url}`.
+    pub fn go_live(mut self, channel_name: &str, go_live_template: Option<Template>) -> Self {
+        self.bot.channel_name = channel_name.to_owned();
+        self.bot.go_live_template = go_live_template;
+        self
+    }
+
+    /// Sets the message sent when the bot joined the chat, None to join silently.
+    pub fn connect_greeting(mut self, connect_greeting: Option<Template>) -> Self {
+        self.bot.connect_greeting = connect_greeting;
+        self
+    }
+
+    /// Sets the reply to chatters mentioning the bot and how long each chatter waits for the next one,
+    /// None to not reply.
+    pub fn mention_reply(mut self, template: Option<Template>, cooldown: Duration) -> Self {
+        self.bot.mention_reply = template.map(|template| MentionReply::new(template, cooldown));
+        self
+    }
+
+    /// Sets the numbers of messages at which chatters are celebrated, None to not celebrate them.
+    pub fn message_milestones(mut self, message_milestones: Option<MessageMilestones>) -> Self {
+        self.bot.message_milestones = message_milestones;
+        self
+    }
+
+    /// Sets the patterns of chat messages the bot replies to.
+    pub fn autoresponder(mut self, autoresponder: Autoresponder) -> Self {
+        self.bot.autoresponder = autoresponder;
+        self
+    }
+
+    /// Sets whether announcements are handled like chat messages, the bot's own never are.
+    pub fn announcement_triggers(mut self, announcement_triggers: bool) -> Self {
+        self.bot.announcement_triggers = announcement_triggers;
+        self
+    }
+
+    /// Sets the reply to viewers sharing their watch streak, None to not reply.
+    pub fn viewer_milestone_reply(mut self, viewer_milestone_reply: Option<Template>) -> Self {
+        self.bot.viewer_milestone_reply = viewer_milestone_reply;
+        self
+    }
+
+    /// Sets the summary of the settings the broadcaster gets with `!config`, it must not contain secrets.
+    pub fn config_summary(mut self, config_summary: String) -> Self {
+        self.bot.config_summary = Some(config_summary);
+        self
+    }
+
+    /// Sets the persisted deaths of `!deaths` by the id of the category of the game.
+    pub fn game_deaths(mut self, games: HashMap<String, GameDeaths>) -> Self {
+        self.bot.game_deaths = DeathCounters::new(games);
+        self
+    }
+
+    /// Sets when bursts of first messages count as a hate raid, None to not watch for them.
+    pub fn raid_protection(mut self, config: Option<ProtectionConfig>) -> Self {
+        self.bot.protection = config.map(RaidProtection::new);
+        self
+    }
+
+    /// Sets when the chat counts as busy and what happens to greetings then, None to always
+    /// greet individually.
+    pub fn greeting_throttle(mut self, config: Option<ThrottleConfig>) -> Self {
+        self.bot.greeting_throttle = config.map(GreetingThrottle::new);
+        self
+    }
+
+    /// Sets how many chat messages of each channel are remembered for `!context`, 0 to remember none.
+    pub fn history_size(mut self, size: usize) -> Self {
+        self.bot.history = MessageHistory::new(size);
+        self
+    }
+
+    /// Sets whether the messages of the bot are only logged, except those of the owner commands.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.bot.dry_run = dry_run;
+        self
+    }
+
+    /// Sets the emote codes sent instead of the replies to commands in emote-only mode.
+    pub fn emote_only_fallback(mut self, emote_only_fallback: Option<String>) -> Self {
+        self.bot.emote_only_fallback = emote_only_fallback;
+        self
+    }
+
+    /// Sets the question bank of `!quiz` and the (e.g. persisted) quiz points by lowercase name.
+    pub fn quiz(
+        mut self,
+        quiz_questions: Vec<QuizQuestion>,
+        quiz_points: HashMap<String, u64>,
+    ) -> Self {
+        self.bot.quiz_questions = quiz_questions;
+        self.bot.quiz_points = quiz_points;
+        self
+    }
+
+    /// Makes the bot forget the cooldowns (e.g. of the unknown command reply) when it reconnects.
+    pub fn reset_cooldowns_on_connect(mut self, reset_cooldowns_on_connect: bool) -> Self {
+        self.bot.reset_cooldowns_on_connect = reset_cooldowns_on_connect;
+        self
+    }
+
+    /// Replaces the info message of the catalog with the given sections.
+    pub fn info(mut self, info: InfoConfig) -> Self {
+        self.bot.info = info;
+        self
+    }
+
+    /// Sets the duration above which a command handler is logged as slow.
+    pub fn slow_command(mut self, threshold: Duration) -> Self {
+        self.bot.timings = CommandTimings::new(threshold);
+        self
+    }
+
+    /// Sets the terms explained by `!define`.
+    pub fn glossary(mut self, glossary: Glossary) -> Self {
+        self.bot.glossary = glossary;
+        self
+    }
+
+    /// Sets the (e.g. persisted) names of the commands which were turned off with `!disable`.
+    pub fn disabled_commands(mut self, disabled_commands: HashSet<String>) -> Self {
+        self.bot.disabled_commands = disabled_commands;
+        self
+    }
+
+    /// Sets the commands (by name or alias) which have to be repeated within a few seconds to run.
+    pub fn confirmations(mut self, command_names: &[String]) -> Self {
+        let commands = command_names
+            .iter()
+            .map(|name| {
+                ReceiveEvent::parse_command_kind(&name.to_lowercase())
+                    .name()
+                    .to_owned()
+            })
+            .collect();
+        self.bot.confirmations = Confirmations::new(commands);
+        self
+    }
+
+    /// Sets the latest entries of the audit log, e.g. those written before the bot restarted.
+    pub fn audit_trail(mut self, entries: Vec<AuditEntry>) -> Self {
+        self.bot.audit = AuditTrail::new(entries);
+        self
+    }
+
+    /// Sets which messages are held back while the chat is in emote-only or sub-only mode.
+    pub fn mode_policy(mut self, mode_policy: ModePolicy) -> Self {
+        self.bot.mode_policy = mode_policy;
+        self
+    }
+
+    /// Sets whether the replies to commands are sent to the chat, whispered or only logged.
+    pub fn response_routing(mut self, routing: ResponseRouting) -> Self {
+        self.bot.routing = routing;
+        self
+    }
+
+    /// Sets whether commands written inside chat messages (e.g. `can someone do !clip please`)
+    /// are used, up to the given permission so that e.g. `please don't !timeout me` does nothing.
+    pub fn commands_anywhere(mut self, commands_anywhere: Option<Permission>) -> Self {
+        self.bot.commands_anywhere = commands_anywhere;
+        self
+    }
+
+    /// Sets the (e.g. persisted) all-time record of `!roll`.
+    pub fn roll_record(mut self, roll_record: Option<RollRecord>) -> Self {
+        self.bot.roll_record = roll_record;
+        self
+    }
+
+    /// Sets the totals at the start of earlier streams by stream id, the bot continues with the
+    /// snapshot of the live stream after a restart.
+    pub fn stream_snapshots(mut self, snapshots: HashMap<String, StreamSnapshot>) -> Self {
+        self.bot.counts = StreamCounts::new(snapshots);
+        self
+    }
+
+    /// Allows the users with the given ids to use the admin commands (e.g. `!shutdown`).
+    pub fn owners(mut self, owners: HashSet<String>) -> Self {
+        self.bot.owners = owners;
+        self
+    }
+
+    /// Sets whether `!so` also sends a native shoutout, which shows a banner in the chat.
+    pub fn native_shoutout(mut self, native_shoutout: bool) -> Self {
+        self.bot.native_shoutout = native_shoutout;
+        self
+    }
+
+    /// Sets the chat colors the bot changes to in turn whenever the stream goes live.
+    pub fn bot_colors(mut self, bot_colors: Vec<String>) -> Self {
+        self.bot.bot_colors = bot_colors;
+        self
+    }
+
+    /// Sets the enabled features which need a scope, `!scopes` lists those the access token lacks.
+    pub fn scoped_features(mut self, scoped_features: Vec<&'static ScopedFeature>) -> Self {
+        self.bot.scoped_features = scoped_features;
+        self
+    }
+
+    /// Sets the names of the regulars, who are greeted the first time they chat.
+    pub fn regulars(mut self, regulars: &[String]) -> Self {
+        self.bot.regulars = regulars.iter().map(|name| name.to_lowercase()).collect();
+        self
+    }
+
+    /// Tracks the role changes of the chatters, starting from their last known roles.
+    pub fn roles(mut self, known: HashMap<String, Role>) -> Self {
+        self.bot.roles = Some(RoleTracker::new(known));
+        self
+    }
+
+    pub fn build(self) -> ChatBot {
+        self.bot
+    }
+}
+
+/// Formats an uptime like `2d 3h 4m`, leaving out the days and hours while they are 0.
+fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, hours) => format!("{}h {}m", hours, minutes),
+        (days, hours) => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// The name of a command written inside a chat message as a word of its own, e.g. `!clip,`.
+/// Words which only contain the prefix (e.g. `example.com/!clip` or `wow!clip`) are not commands.
+fn embedded_command_name<'a>(word: &'a str, command_prefix: &str) -> Option<&'a str> {
+    let name = word
+        .strip_prefix(command_prefix)?
+        .trim_end_matches([',', '.', '!', '?', ';', ':', ')']);
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    valid.then_some(name)
+}
+
+/// Whether a custom command can be used with this name, i.e. it has no whitespace or prefix in it.
+fn valid_command_name(name: &str, command_prefix: &str) -> bool {
+    !name.is_empty() && !name.contains(char::is_whitespace) && !name.contains(command_prefix)
+}
+
+/// Drops all messages of a reply, keeping the other commands (e.g. storing a counter).
+fn without_messages(reply: ChatBotCommand) -> Option<ChatBotCommand> {
+    match reply {
+        ChatBotCommand::SendMessage(_) => None,
+        ChatBotCommand::MultipleCommands(commands) => {
+            let mut commands: Vec<ChatBotCommand> =
+                commands.into_iter().filter_map(without_messages).collect();
+            match commands.len() {
+                0 => None,
+                1 => commands.pop(),
+                _ => Some(ChatBotCommand::MultipleCommands(commands)),
+            }
+        }
+        command => Some(command),
+    }
+}
+
+/// Replaces the messages of a reply with the emotes, which are sent once.
+fn emotes_instead(emotes: &str, reply: ChatBotCommand) -> Option<ChatBotCommand> {
+    let mut sent = false;
+    let mut replace = |command| match command {
+        ChatBotCommand::SendMessage(_) if sent => None,
+        ChatBotCommand::SendMessage(_) => {
+            sent = true;
+            Some(ChatBotCommand::SendMessage(emotes.to_owned()))
+        }
+        command => Some(command),
+    };
+    match reply {
+        ChatBotCommand::MultipleCommands(commands) => {
+            let mut commands: Vec<ChatBotCommand> =
+                commands.into_iter().filter_map(replace).collect();
+            match commands.len() {
+                0 => None,
+                1 => commands.pop(),
+                _ => Some(ChatBotCommand::MultipleCommands(commands)),
+            }
+        }
+        command => replace(command),
+    }
+}
+
+/// Logs the messages, whispers and announcements of a reply instead of sending them. The Helix
+/// requests with side effects (e.g. a raid or the chat settings of the raid protection) are
+/// logged and collected in `skipped`, reading ones are still sent.
+fn logged_instead(
+    channel_name: &str,
+    reply: ChatBotCommand,
+    skipped: &mut Vec<HelixRequest>,
+) -> ChatBotCommand {
+    match reply {
+        ChatBotCommand::SendMessage(message) => {
+            ChatBotCommand::LogTextMessage(format!("[DRY-RUN] #{}: {}", channel_name, message))
+        }
+        ChatBotCommand::SendMessageTo { channel, text } => {
+            ChatBotCommand::LogTextMessage(format!("[DRY-RUN] #{}: {}", channel, text))
+        }
+        ChatBotCommand::SendWhisper { user, text } => {
+            ChatBotCommand::LogTextMessage(format!("[DRY-RUN] whisper to {}: {}", user, text))
+        }
+        ChatBotCommand::AnnounceGoLive(announcement) => ChatBotCommand::LogTextMessage(format!(
+            "[DRY-RUN] discord: {} {}",
+            announcement.text, announcement.url
+        )),
+        ChatBotCommand::NotifyOwners(text) => {
+            ChatBotCommand::LogTextMessage(format!("[DRY-RUN] discord: {}", text))
+        }
+        ChatBotCommand::HelixRequest(request) if request.has_side_effects() => {
+            let message = format!("[DRY-RUN] helix: {:?}", request);
+            skipped.push(request);
+            ChatBotCommand::LogTextMessage(message)
+        }
+        ChatBotCommand::MultipleCommands(commands) => ChatBotCommand::MultipleCommands(
+            commands
+                .into_iter()
+                .map(|command| logged_instead(channel_name, command, skipped))
+                .collect(),
+        ),
+        command => command,
+    }
+}
+
+/// The log line of a message which was part of a hate raid, with its id for the review.
+fn offending_message(offender: &Offender) -> String {
+    format!(
+        "Hate raid: first message of {} (id {})",
+        offender.user,
+        offender.message_id.as_deref().unwrap_or("unknown")
+    )
+}
+
+/// Whether the text is a possible twitch login, i.e. the name of a channel.
+fn is_channel_login(text: &str) -> bool {
+    (4..=25).contains(&text.len())
+        && text
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+/// Adds a user to the users waiting for a reply, returns whether the reply has to be requested.
+fn await_reply(users: &mut Vec<String>, user_name: &str) -> bool {
+    let first = users.is_empty();
+    if !users.iter().any(|user| user == user_name) {
+        users.push(user_name.to_owned());
+    }
+    first
+}
+
+/// Prefixes all messages of a reply with `@user`.
+fn mention(user_name: &str, reply: ChatBotCommand) -> ChatBotCommand {
+    match reply {
+        ChatBotCommand::SendMessage(message) => {
+            ChatBotCommand::SendMessage(format!("@{} {}", user_name, message))
+        }
+        ChatBotCommand::MultipleCommands(commands) => ChatBotCommand::MultipleCommands(
+            commands
+                .into_iter()
+                .map(|command| mention(user_name, command))
+                .collect(),
+        ),
+        command => command,
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::connect::{Badge, ChannelEmote, UserInfo};
+    use crate::core::{vod::VIDEO_CACHE_DURATION, InfoConfig};
+
+    // It's now easy to test without connecting
+    #[test]
+    fn test_join() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(ChatBotEvent::Join(String::from("Carkhy")));
+        assert!(matches!(result, None));
+        assert_eq!(bot.connection.chatters.len(), 1);
+        assert_eq!(bot.connection.chatters.get("Carkhy").unwrap(), "Carkhy");
+    }
+
+    #[test]
+    fn test_part() {
+        let mut bot = ChatBot::new();
+        bot.handle_event(ChatBotEvent::Join(String::from("Carkhy")));
+        let result = bot.handle_event(ChatBotEvent::Part(String::from("Carkhy")));
+        assert!(matches!(result, None));
+        assert_eq!(bot.connection.chatters.len(), 0);
+        assert!(matches!(bot.connection.chatters.get("Carkhy"), None));
+    }
+
+    #[test]
+    fn test_text_message() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(ChatBotEvent::TextMessage(TextMessage {
+            text: "Hello".to_string(),
+            user: UserInfo {
+                name: "Carkhy".to_owned(),
+                id: None,
+                badges: HashSet::default(),
+            },
+            room_id: None,
+            id: None,
+            first_message: false,
+        }));
+        assert!(
+            matches!(result, Some(ChatBotCommand::LogTextMessage(message)) if message == "Carkhy: Hello")
+        );
+    }
+
+    #[test]
+    fn invalid_slapping() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Slap,
+            "CaptainCallback",
+            HashSet::default(),
+            &["Carkhy"],
+        ));
+        assert!(matches!(result, None));
+    }
+
+    #[test]
+    fn valid_slapping_when_abstraction_detected() {
+        let mut bot = ChatBot::new();
+        bot.handle_event(ChatBotEvent::Join(String::from("CaptainCallback")));
+        let result = bot.handle_event(command(
+            CommandType::Slap,
+            "Carkhy",
+            HashSet::default(),
+            &["CaptainCallback"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == format!("{} slaps {} around a bit with a large trout", "Carkhy", "CaptainCallback")));
+    }
+
+    #[test]
+    fn nonmods_cannot_newcommand() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::NewCommand,
+            "CaptainCallback",
+            HashSet::default(),
+            &["test", "testing"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        assert!(!bot.dynamic_commands.contains_key("test"));
+    }
+
+    #[test]
+    fn broadcaster_can_newcommand() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::NewCommand,
+            "CaptainCallback",
+            badge("broadcaster"),
+            &["test", "testing"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message != bot.messages.text("denied", &[])));
+        assert!(bot.dynamic_commands.contains_key("test"));
+    }
+
+    #[test]
+    fn mods_can_newcommand() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::NewCommand,
+            "CaptainCallback",
+            badge("moderator"),
+            &["test2", "testing2"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message != bot.messages.text("denied", &[])));
+        assert!(bot.dynamic_commands.contains_key("test2"));
+    }
+
+    #[test]
+    fn rendering_dynamic_command_templates() {
+        let mut bot = ChatBot::builder()
+            .counters(HashMap::from([("hugs".to_owned(), 41)]))
+            .build();
+        bot.handle_event(command(
+            CommandType::NewCommand,
+            "CaptainCallback",
+            badge("broadcaster"),
+            &[
+                "hug",
+                "{user} hugs {args:1}{if broadcaster} royally{end}, hug #{count:hugs}",
+            ],
+        ));
+        let result = bot.handle_event(command(
+            CommandType::Dynamic("hug".to_owned()),
+            "CaptainCallback",
+            badge("broadcaster"),
+            &["@chatter"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "CaptainCallback hugs @chatter royally, hug #41"));
+    }
+
+    #[test]
+    fn rejecting_malformed_command_templates() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::NewCommand,
+            "CaptainCallback",
+            badge("broadcaster"),
+            &["hug", "{user} hugs {args:1"],
+        ));
+        let expected = bot.messages.text(
+            "template_invalid",
+            &[("error", "Unclosed tag starting at character 12")],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        assert!(!bot.dynamic_commands.contains_key("hug"));
+    }
+
+    #[test]
+    fn handling_raw_help_command() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw("@badge-info=;badges=;color=;display-name=carkhy;emotes=;mod=0;room-id=120630112;subscriber=0;turbo=0;user-id=70346833;user-type= :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!help");
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("help", &[])));
+    }
+
+    fn command(
+        kind: CommandType,
+        user: &str,
+        badges: HashSet<Badge>,
+        options: &[&str],
+    ) -> ChatBotEvent {
+        ChatBotEvent::Command(Command {
+            user: UserInfo {
+                name: user.to_owned(),
+                id: None,
+                badges,
+            },
+            kind,
+            options: options.iter().map(|option| option.to_string()).collect(),
+            raw_args: options.join(" "),
+            room_id: None,
+        })
+    }
+
+    fn badge(name: &str) -> HashSet<Badge> {
+        HashSet::from([Badge {
+            name: name.to_owned(),
+            level: 1,
+        }])
+    }
+
+    fn moderator_badges() -> HashSet<Badge> {
+        HashSet::from([Badge {
+            name: "moderator".to_owned(),
+            level: 1,
+        }])
+    }
+
+    fn bot_user_state(badges: HashSet<Badge>) -> ChatBotEvent {
+        ChatBotEvent::UserState {
+            badges,
+            display_name: None,
+        }
+    }
+
+    #[test]
+    fn reading_unset_counter() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "deaths: 0"));
+    }
+
+    #[test]
+    fn incrementing_counter() {
+        let mut bot = ChatBot::new();
+        bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "+1"],
+        ));
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "+2"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::SendMessage(message),
+                             ChatBotCommand::StoreCounter { name, value: 3 },
+                         ] if message == "deaths: 3" && name == "deaths"))
+        );
+        assert_eq!(bot.counters.get("deaths"), Some(&3));
+    }
+
+    #[test]
+    fn decrementing_counter() {
+        let mut bot = ChatBot::builder()
+            .counters(HashMap::from([("deaths".to_owned(), 5)]))
+            .build();
+        bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "-2"],
+        ));
+        assert_eq!(bot.counters.get("deaths"), Some(&3));
+    }
+
+    #[test]
+    fn setting_counter() {
+        let mut bot = ChatBot::builder()
+            .counters(HashMap::from([("deaths".to_owned(), 5)]))
+            .build();
+        bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "set", "0"],
+        ));
+        assert_eq!(bot.counters.get("deaths"), Some(&0));
+    }
+
+    #[test]
+    fn nonmods_cannot_change_counter() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths", "+1"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        assert!(!bot.counters.contains_key("deaths"));
+    }
+
+    fn prediction_response(status: PredictionStatus) -> ChatBotEvent {
+        ChatBotEvent::HelixResponse(HelixResponse::Prediction(Ok(Prediction {
+            id: "prediction1".to_owned(),
+            status,
+            outcome_ids: vec!["outcome1".to_owned(), "outcome2".to_owned()],
+        })))
+    }
+
+    #[test]
+    fn running_a_prediction() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &["start", "\"Will", "we", "win?\"", "Yes", "No", "120"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::CreatePrediction {
+            title, outcomes, window
+        })) if title == "Will we win?" && outcomes == ["Yes", "No"] && window == Duration::from_secs(120))
+        );
+        bot.handle_event(prediction_response(PredictionStatus::Active));
+
+        let result = bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &["lock"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::LockPrediction { id }))
+                         if id == "prediction1")
+        );
+        bot.handle_event(prediction_response(PredictionStatus::Locked));
+
+        let result = bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &["resolve", "2"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::ResolvePrediction {
+            id, winning_outcome_id
+        })) if id == "prediction1" && winning_outcome_id == "outcome2")
+        );
+        let result = bot.handle_event(prediction_response(PredictionStatus::Resolved));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("prediction_resolved", &[])));
+        assert_eq!(bot.session.prediction, PredictionState::Idle);
+    }
+
+    #[test]
+    fn rejecting_second_prediction() {
+        let mut bot = ChatBot::new();
+        let start = ["start", "Title", "Yes", "No", "120"];
+        bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &start,
+        ));
+        let result = bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &start,
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("prediction_already_active", &[])));
+        bot.handle_event(prediction_response(PredictionStatus::Active));
+        let result = bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &start,
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("prediction_already_active", &[])));
+    }
+
+    #[test]
+    fn failing_prediction_start_on_ineligible_channel() {
+        let mut bot = ChatBot::new();
+        bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &["start", "Title", "Yes", "No", "120"],
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Prediction(Err(
+            HelixError::Status(403, "channel not eligible".to_owned()),
+        ))));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("prediction_not_eligible", &[])));
+        assert_eq!(bot.session.prediction, PredictionState::Idle);
+    }
+
+    #[test]
+    fn clearing_already_resolved_prediction() {
+        let mut bot = ChatBot::new();
+        bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &["start", "Title", "Yes", "No", "120"],
+        ));
+        bot.handle_event(prediction_response(PredictionStatus::Active));
+        bot.handle_event(command(
+            CommandType::Prediction,
+            "CaptainCallback",
+            moderator_badges(),
+            &["resolve", "1"],
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Prediction(Err(
+            HelixError::Status(400, "prediction has already been resolved".to_owned()),
+        ))));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("prediction_already_ended", &[])));
+        assert_eq!(bot.session.prediction, PredictionState::Idle);
+    }
+
+    #[test]
+    fn logging_unparsed_lines() {
+        let mut bot = ChatBot::new();
+        let broadcaster_badges = HashSet::from([Badge {
+            name: "broadcaster".to_owned(),
+            level: 1,
+        }]);
+        let result = bot.handle_event(command(
+            CommandType::Debug,
+            "CaptainCallback",
+            broadcaster_badges,
+            &["unparsed"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+            if matches!(&commands[..], [
+                ChatBotCommand::LogUnparsedLines(5),
+                ChatBotCommand::SendMessage(_),
+            ]))
+        );
+    }
+
+    #[test]
+    fn only_broadcaster_can_log_unparsed_lines() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Debug,
+            "CaptainCallback",
+            moderator_badges(),
+            &["unparsed", "10"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+    }
+
+    #[test]
+    fn showing_follower_goal() {
+        let mut bot = ChatBot::builder()
+            .goals(HashMap::from([(GoalKind::Followers, 1500)]))
+            .build();
+        let result = bot.handle_event(command(
+            CommandType::FollowGoal,
+            "CaptainCallback",
+            HashSet::default(),
+            &[],
+        ));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::FollowerTotal))
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::FollowerTotal(
+            Ok(1234),
+        )));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "1,234 / 1,500 followers (82%) — 266 to go!"));
+    }
+
+    #[test]
+    fn caching_goal_totals() {
+        let mut bot = ChatBot::builder()
+            .goals(HashMap::from([(GoalKind::Followers, 1500)]))
+            .build();
+        bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::FollowerTotal(
+            Ok(1500),
+        )));
+        let result = bot.handle_event(command(
+            CommandType::FollowGoal,
+            "CaptainCallback",
+            HashSet::default(),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "1,500 / 1,500 followers (100%) — goal reached!"));
+
+        bot.session
+            .goal_totals
+            .get_mut(&GoalKind::Followers)
+            .unwrap()
+            .fetched_at -= goal::GOAL_CACHE_DURATION;
+        let result = bot.handle_event(command(
+            CommandType::FollowGoal,
+            "CaptainCallback",
+            HashSet::default(),
+            &[],
+        ));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::FollowerTotal))
+        ));
+    }
+
+    #[test]
+    fn setting_sub_goal() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::SubGoal,
+            "CaptainCallback",
+            HashSet::default(),
+            &["set", "1000"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_event(command(
+            CommandType::SubGoal,
+            "CaptainCallback",
+            moderator_badges(),
+            &["set", "1000"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+            if matches!(&commands[..], [
+                ChatBotCommand::SendMessage(_),
+                ChatBotCommand::StoreGoal { kind: GoalKind::SubPoints, target: 1000 },
+            ]))
+        );
+        assert_eq!(bot.goals.get(&GoalKind::SubPoints), Some(&1000));
+    }
+
+    #[test]
+    fn explaining_missing_subscription_scope() {
+        let mut bot = ChatBot::builder()
+            .goals(HashMap::from([(GoalKind::SubPoints, 1000)]))
+            .build();
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::SubPointTotal(
+            Err(HelixError::Status(
+                401,
+                "Missing scope: channel:read:subscriptions".to_owned(),
+            )),
+        )));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("subgoal_missing_scope", &[])));
+    }
+
+    fn live_stream(stream_id: &str) -> ChatBotEvent {
+        ChatBotEvent::HelixResponse(HelixResponse::StreamInfo(Ok(StreamInfo {
+            live: true,
+            title: "Writing a chat bot".to_owned(),
+            game: "Software and Game Development".to_owned(),
+            game_id: "1469308723".to_owned(),
+            thumbnail_url: None,
+            stream_id: Some(stream_id.to_owned()),
+        })))
+    }
+
+    #[test]
+    fn counting_followers_and_subs_since_the_stream_started() {
+        // the bot restarted during the stream, whose follower total was fetched already
+        let mut bot = ChatBot::builder()
+            .stream_snapshots(HashMap::from([(
+                "41".to_owned(),
+                StreamSnapshot {
+                    followers: Some(1000),
+                    subscribers: None,
+                },
+            )]))
+            .build();
+        let result = bot.handle_raw(&chat_line("viewer", "!followers"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::FollowerCount))
+        ));
+        // one response answers everyone asking
+        assert!(bot.handle_raw(&chat_line("other", "!followers")).is_none());
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::FollowerCount(
+            Ok(1234),
+        )));
+        assert_eq!(replies(result), vec!["The channel has 1,234 followers."]);
+
+        let result = bot.handle_event(live_stream("41"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::Subscriptions))
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Subscriptions(
+            Ok(Subscriptions {
+                total: 52,
+                points: 60,
+            }),
+        )));
+        assert!(
+            matches!(result, Some(ChatBotCommand::StoreStreamSnapshot { stream_id, snapshot })
+                         if stream_id == "41" && snapshot == StreamSnapshot { followers: Some(1000), subscribers: Some(52) })
+        );
+        // the totals are cached
+        let result = bot.handle_raw(&chat_line("viewer", "!followers"));
+        assert_eq!(
+            replies(result),
+            vec!["The channel has 1,234 followers (+234 today)."]
+        );
+        let result = bot.handle_raw(&moderator_line("alice", "!subs"));
+        assert_eq!(
+            replies(result),
+            vec!["The channel has 52 subscribers worth 60 sub points (+0 today)."]
+        );
+        let result = bot.handle_raw(&chat_line("viewer", "!subs"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+
+        // the next stream gets a new snapshot
+        let result = flattened(bot.handle_event(live_stream("42")));
+        assert!(matches!(
+            &result[..],
+            [
+                ChatBotCommand::HelixRequest(HelixRequest::FollowerCount),
+                ChatBotCommand::HelixRequest(HelixRequest::Subscriptions)
+            ]
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::FollowerCount(
+            Ok(1240),
+        )));
+        assert!(
+            matches!(result, Some(ChatBotCommand::StoreStreamSnapshot { stream_id, snapshot })
+                         if stream_id == "42" && snapshot.followers == Some(1240))
+        );
+        assert!(bot.handle_event(live_stream("42")).is_none());
+        assert_eq!(bot.counts.since_start(CountKind::Followers, 1237), Some(-3));
+    }
+
+    #[test]
+    fn explaining_missing_scope_of_subs() {
+        let mut bot = ChatBot::new();
+        bot.handle_raw(&moderator_line("alice", "!subs"));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Subscriptions(
+            Err(HelixError::Status(
+                401,
+                "Missing scope: channel:read:subscriptions".to_owned(),
+            )),
+        )));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("subgoal_missing_scope", &[])]
+        );
+        // nobody asked for the snapshot's totals
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Subscriptions(
+            Err(HelixError::Unavailable("timeout".to_owned())),
+        )));
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn measuring_latency() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw("@badge-info=;badges=;color=;display-name=carkhy;emotes=;mod=0;room-id=120630112;subscriber=0;turbo=0;user-id=70346833;user-type= :chatter!chatter@chatter.tmi.twitch.tv PRIVMSG #channel123 :!ping");
+        assert!(matches!(result, Some(ChatBotCommand::MeasureLatency)));
+        let result = bot.handle_event(ChatBotEvent::Latency(Some(Duration::from_millis(42))));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Pong! IRC round-trip 42 ms"));
+        // everyone shares the cooldown
+        assert!(bot.handle_raw(&chat_line("alice", "!ping")).is_none());
+        bot.cooldowns.last_ping = Some(Instant::now() - PING_COOLDOWN);
+        let result = bot.handle_raw(&chat_line("alice", "!ping"));
+        assert!(matches!(result, Some(ChatBotCommand::MeasureLatency)));
+    }
+
+    #[test]
+    fn reconnecting_when_unresponsive() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(ChatBotEvent::Unresponsive);
+        assert!(matches!(result, Some(ChatBotCommand::Reconnect)));
+    }
+
+    #[test]
+    fn mentioning_user_in_replies() {
+        let mut bot = ChatBot::builder().mention_user(true).build();
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "+1"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::SendMessage(message),
+                             ChatBotCommand::StoreCounter { .. },
+                         ] if message == "@CaptainCallback deaths: 1"))
+        );
+    }
+
+    #[test]
+    fn mentioning_users_in_replies_from_twitch() {
+        let mut bot = ChatBot::builder().mention_user(true).build();
+        assert!(matches!(
+            bot.handle_raw(&chat_line("alice", "!vod")),
+            Some(ChatBotCommand::HelixRequest(HelixRequest::LatestVod))
+        ));
+        // the latest vod is requested once for everyone asking
+        assert!(bot.handle_raw(&chat_line("bob", "!vod")).is_none());
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::LatestVod(Ok(
+            None,
+        ))));
+        assert_eq!(
+            replies(result),
+            vec![format!(
+                "@alice @bob {}",
+                bot.messages.text("vod_none", &[])
+            )]
+        );
+
+        bot.handle_raw(&chat_line("carkhy", "!accountage"));
+        let result = bot.handle_event(user_age(30, 60));
+        assert!(matches!(&replies(result)[..], [message]
+                         if message.starts_with("@carkhy The account of @carkhy")));
+
+        bot.handle_raw(&chat_line("alice", "!followers"));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::FollowerCount(
+            Err(HelixError::Unavailable("timeout".to_owned())),
+        )));
+        assert_eq!(
+            replies(result),
+            vec![format!(
+                "@alice {}",
+                bot.messages.text("helix_unavailable", &[])
+            )]
+        );
+
+        let mut bot = shoutout_bot();
+        bot.mention_user = true;
+        bot.handle_raw(&moderator_line("modbot", "!so carkhy"));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Shoutout {
+            login: "carkhy".to_owned(),
+            result: Err(HelixError::Status(400, "Not live".to_owned())),
+        }));
+        assert!(matches!(&replies(result)[..], [message]
+                         if message.starts_with("@modbot The Twitch shoutout of @carkhy failed")));
+
+        // the reply to a command waiting for its gate
+        let mut bot = gated_bot(UnavailablePolicy::Closed);
+        bot.mention_user = true;
+        bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        let result = bot.handle_event(user_age(30, 60));
+        assert_eq!(
+            replies(result),
+            vec![format!(
+                "@Carkhy {}",
+                bot.messages.text("emote_unknown", &[("name", "shrug")])
+            )]
+        );
+    }
+
+    #[test]
+    fn replying_without_mention() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "deaths: 0"));
+    }
+
+    fn routing_bot() -> ChatBot {
+        ChatBot::builder()
+            .mention_user(true)
+            .response_routing(ResponseRouting::new(
+                ResponseTarget::Log,
+                HashMap::from([("counter".to_owned(), ResponseTarget::Whisper)]),
+            ))
+            .build()
+    }
+
+    #[test]
+    fn routing_replies_by_command() {
+        let mut bot = routing_bot();
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "+1"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::SendWhisper { user, text },
+                             ChatBotCommand::StoreCounter { .. },
+                         ] if user == "CaptainCallback" && text == "deaths: 1"))
+        );
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["hype"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::LogTextMessage(message))
+                         if message.starts_with("Reply to !emote of Carkhy: "))
+        );
+    }
+
+    #[test]
+    fn falling_back_to_the_chat_without_whispers() {
+        let mut bot = routing_bot();
+        let failed_whisper = || {
+            ChatBotEvent::HelixResponse(HelixResponse::Whisper {
+                login: "CaptainCallback".to_owned(),
+                text: "deaths: 0".to_owned(),
+                result: Err(HelixError::Status(
+                    403,
+                    "the sender does not have a verified phone number".to_owned(),
+                )),
+            })
+        };
+        let result = bot.handle_event(failed_whisper());
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(notice), ChatBotCommand::SendMessage(message)]
+                                     if notice == &bot.messages.text("whisper_unavailable", &[])
+                                         && message == "@CaptainCallback deaths: 0"))
+        );
+        // the notice is sent only once
+        let result = bot.handle_event(failed_whisper());
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "@CaptainCallback deaths: 0"));
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "@CaptainCallback deaths: 0"));
+        // other commands keep their target
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["hype"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    #[test]
+    fn echoing_emotes() {
+        let mut bot = ChatBot::builder()
+            .emotes(HashMap::from([(
+                "shrug".to_owned(),
+                "¯\\_(ツ)_/¯".to_owned(),
+            )]))
+            .build();
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "¯\\_(ツ)_/¯"));
+    }
+
+    #[test]
+    fn echoing_unknown_emotes() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("emote_unknown", &[("name", "shrug")])));
+    }
+
+    fn seeded_usage_stats() -> HashMap<String, UserStats> {
+        let mut carkhy = UserStats::default();
+        carkhy.set(MESSAGES_COUNTER, 412);
+        carkhy.set("slap", 37);
+        carkhy.set("help", 37);
+        carkhy.set("discord", 3);
+        HashMap::from([("carkhy".to_owned(), carkhy)])
+    }
+
+    #[test]
+    fn showing_own_stats() {
+        let mut bot = ChatBot::builder().usage_stats(seeded_usage_stats()).build();
+        let result = bot.handle_event(command(
+            CommandType::Stats,
+            "Carkhy",
+            HashSet::default(),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Carkhy, you've sent 412 messages and used !help 37 times — your favorite."));
+    }
+
+    #[test]
+    fn showing_stats_of_other_users() {
+        let mut bot = ChatBot::builder().usage_stats(seeded_usage_stats()).build();
+        let result = bot.handle_event(command(
+            CommandType::Stats,
+            "Carkhy",
+            HashSet::default(),
+            &["@carkhy"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_event(command(
+            CommandType::Stats,
+            "Carkhy",
+            moderator_badges(),
+            &["@carkhy"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "carkhy has sent 413 messages and used !help 37 times — their favorite."));
+    }
+
+    #[test]
+    fn counting_usage() {
+        let mut bot = ChatBot::builder().go_live("captaincallback", None).build();
+        bot.handle_event(ChatBotEvent::TextMessage(TextMessage {
+            text: "Hello".to_string(),
+            user: UserInfo {
+                name: "Carkhy".to_owned(),
+                id: None,
+                badges: HashSet::default(),
+            },
+            room_id: None,
+            id: None,
+            first_message: false,
+        }));
+        bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        let pending = bot.take_pending_usage();
+        assert!(
+            matches!(pending, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::StoreUsage { .. },
+                             ChatBotCommand::StoreUsage { channel, user, counts },
+                         ] if channel == "captaincallback" && user == "carkhy" && counts[..] == [("messages".to_owned(), 2), ("emote".to_owned(), 1)]))
+        );
+        assert!(bot.take_pending_usage().is_none());
+        // the usage is stored for the channel of the message
+        bot.handle_raw(&chat_line("carkhy", "hi"));
+        assert!(
+            matches!(bot.take_pending_usage(), Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::StoreUsage { channel, .. }]
+                                     if channel == "channel123"))
+        );
+    }
+
+    fn gated_bot(unavailable: UnavailablePolicy) -> ChatBot {
+        ChatBot::builder()
+            .gates(GateConfig {
+                commands: HashMap::from([(
+                    "emote".to_owned(),
+                    Gate {
+                        min_account_days: Some(7),
+                        min_follow_minutes: Some(10),
+                    },
+                )]),
+                links: Some(Gate {
+                    min_account_days: Some(7),
+                    min_follow_minutes: None,
+                }),
+                unavailable,
+            })
+            .build()
+    }
+
+    fn user_age(account_days: i64, follow_minutes: i64) -> ChatBotEvent {
+        let now = Utc::now();
+        ChatBotEvent::HelixResponse(HelixResponse::UserAge {
+            login: "carkhy".to_owned(),
+            result: Ok(UserAge {
+                created_at: now - chrono::Duration::days(account_days),
+                followed_at: Some(now - chrono::Duration::minutes(follow_minutes)),
+            }),
+        })
+    }
+
+    #[test]
+    fn passing_command_gate() {
+        let mut bot = gated_bot(UnavailablePolicy::Closed);
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::UserAge { login }))
+                         if login == "carkhy")
+        );
+        let result = bot.handle_event(user_age(30, 60));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message)]
+                                     if *message == bot.messages.text("emote_unknown", &[("name", "shrug")])))
+        );
+        // the age is cached for the session
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("emote_unknown", &[("name", "shrug")])));
+    }
+
+    #[test]
+    fn blocking_command_gate() {
+        let mut bot = gated_bot(UnavailablePolicy::Open);
+        bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        // a second command while waiting does not request the age again
+        let result = bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["hype"],
+        ));
+        assert!(result.is_none());
+        let result = bot.handle_event(user_age(30, 5));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+            if commands.len() == 2 && commands.iter().all(|command| matches!(command,
+                ChatBotCommand::SendMessage(message) if message == &bot.messages.text("gate_blocked", &[("user", "Carkhy")])
+            )))
+        );
+    }
+
+    #[test]
+    fn warning_about_links_of_new_accounts() {
+        let mut bot = gated_bot(UnavailablePolicy::Open);
+        bot.handle_event(user_age(1, 60));
+        let result = bot.handle_event(ChatBotEvent::TextMessage(TextMessage {
+            text: "cheap viewers at www.example.xyz".to_string(),
+            user: UserInfo {
+                name: "Carkhy".to_owned(),
+                id: None,
+                badges: HashSet::default(),
+            },
+            room_id: None,
+            id: None,
+            first_message: false,
+        }));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::SendMessage(message)]
+                                     if message == &bot.messages.text("link_warning", &[("user", "Carkhy")])))
+        );
+    }
+
+    #[test]
+    fn answering_links_which_passed_the_gate() {
+        let mut bot = ChatBot::builder()
+            .gates(GateConfig {
+                links: Some(Gate {
+                    min_account_days: Some(7),
+                    min_follow_minutes: None,
+                }),
+                ..GateConfig::default()
+            })
+            .autoresponder(
+                Autoresponder::from_json(
+                    r#"[{"pattern": "clips\\.twitch", "response": "Nice clip!"}]"#,
+                )
+                .unwrap(),
+            )
+            .build();
+        let result = bot.handle_raw(&chat_line("carkhy", "look https://clips.twitch.tv/abc"));
+        assert!(flattened(result).iter().any(|command| matches!(command,
+            ChatBotCommand::HelixRequest(HelixRequest::UserAge { login }) if login == "carkhy")));
+        let result = bot.handle_event(user_age(30, 60));
+        assert_eq!(replies(result), vec!["Nice clip!"]);
+    }
+
+    #[test]
+    fn applying_unavailable_policy() {
+        let unavailable = || {
+            ChatBotEvent::HelixResponse(HelixResponse::UserAge {
+                login: "carkhy".to_owned(),
+                result: Err(HelixError::Unavailable("offline".to_owned())),
+            })
+        };
+        let mut bot = gated_bot(UnavailablePolicy::Closed);
+        bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        let result = bot.handle_event(unavailable());
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message)]
+                                     if message == &bot.messages.text("gate_blocked", &[("user", "Carkhy")])))
+        );
+        let mut bot = gated_bot(UnavailablePolicy::Open);
+        bot.handle_event(command(
+            CommandType::Emote,
+            "Carkhy",
+            HashSet::default(),
+            &["shrug"],
+        ));
+        let result = bot.handle_event(unavailable());
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message)]
+                                     if message == &bot.messages.text("emote_unknown", &[("name", "shrug")])))
+        );
+    }
+
+    #[test]
+    fn ignoring_unknown_commands() {
+        let mut bot = ChatBot::new();
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_none());
+    }
+
+    #[test]
+    fn replying_to_unknown_commands() {
+        let mut bot = ChatBot::builder().unknown_command_reply(true).build();
+        let result = bot.handle_event(command(
+            CommandType::Dynamic("thisisnotacommand".to_owned()),
+            "Carkhy",
+            HashSet::default(),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("unknown_command", &[("command", "thisisnotacommand")])));
+        // rate limited
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_none());
+        bot.cooldowns.last_unknown_command_reply = Some(Instant::now() - UNKNOWN_COMMAND_COOLDOWN);
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_some());
+    }
+
+    fn cheer(bits: u64) -> ChatBotEvent {
+        ChatBotEvent::Cheer {
+            message: TextMessage {
+                text: format!("Cheer{} great stream", bits),
+                user: UserInfo {
+                    name: "Carkhy".to_owned(),
+                    id: None,
+                    badges: HashSet::default(),
+                },
+                room_id: None,
+                id: None,
+                first_message: false,
+            },
+            bits,
+        }
+    }
+
+    #[test]
+    fn sending_alerts_for_big_cheers() {
+        let mut bot = ChatBot::new();
+        assert!(matches!(
+            bot.handle_event(cheer(500)),
+            Some(ChatBotCommand::LogTextMessage(_))
+        ));
+        let mut bot = ChatBot::builder()
+            .alerts(HashSet::from(["cheer".to_owned()]), 100)
+            .build();
+        assert!(matches!(
+            bot.handle_event(cheer(99)),
+            Some(ChatBotCommand::LogTextMessage(_))
+        ));
+        let result = bot.handle_event(cheer(500));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::Alert(alert)]
+                                     if alert == &Alert::Cheer { user: "Carkhy".to_owned(), bits: 500 }))
+        );
+    }
+
+    #[test]
+    fn sending_test_alerts() {
+        let mut bot = ChatBot::builder()
+            .alerts(HashSet::from(["test".to_owned()]), 100)
+            .build();
+        let result = bot.handle_event(command(
+            CommandType::Alert,
+            "Carkhy",
+            badge("subscriber"),
+            &["test"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_event(command(
+            CommandType::Alert,
+            "Carkhy",
+            badge("moderator"),
+            &["test"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::Alert(Alert::Test { user }), ChatBotCommand::SendMessage(_)]
+                                     if user == "Carkhy"))
+        );
+        let mut bot = ChatBot::builder()
+            .alerts(HashSet::from(["cheer".to_owned()]), 100)
+            .build();
+        let result = bot.handle_event(command(
+            CommandType::Alert,
+            "Carkhy",
+            badge("moderator"),
+            &["test"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("alert_test_disabled", &[])));
+    }
+
+    fn stream_info(live: bool) -> ChatBotEvent {
+        ChatBotEvent::HelixResponse(HelixResponse::StreamInfo(Ok(StreamInfo {
+            live,
+            title: "Writing a chat bot".to_owned(),
+            game: "Software and Game Development".to_owned(),
+            game_id: "1469308723".to_owned(),
+            thumbnail_url: None,
+            stream_id: None,
+        })))
+    }
+
+    #[test]
+    fn announcing_going_live() {
+        let mut bot = ChatBot::builder().go_live("captaincallback", None).build();
+        // every response answers a periodic check
+        bot.handle_event(ChatBotEvent::CheckStream);
+        assert!(bot.handle_event(stream_info(false)).is_none());
+        bot.handle_event(ChatBotEvent::CheckStream);
+        let result = bot.handle_event(stream_info(true));
+        let expected = bot.messages.text(
+            "golive_announcement",
+            &[
+                ("title", "Writing a chat bot"),
+                ("game", "Software and Game Development"),
+                ("url", "https://twitch.tv/captaincallback"),
+            ],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message), ChatBotCommand::AnnounceGoLive(announcement)]
+                                     if message == &expected && announcement.url == "https://twitch.tv/captaincallback"))
+        );
+        bot.handle_event(ChatBotEvent::CheckStream);
+        assert!(bot.handle_event(stream_info(true)).is_none());
+    }
+
+    #[test]
+    fn forcing_go_live_announcements() {
+        let mut bot = ChatBot::builder()
+            .go_live(
+                "captaincallback",
+                Some(Template::parse("Live: {title} ({game}) {url}").unwrap()),
+            )
+            .build();
+        let result = bot.handle_event(command(
+            CommandType::GoLive,
+            "Carkhy",
+            badge("moderator"),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_event(command(
+            CommandType::GoLive,
+            "Carkhy",
+            badge("broadcaster"),
+            &[],
+        ));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::StreamInfo))
+        ));
+        let result = bot.handle_event(stream_info(true));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message), _]
+                                     if message == "Live: Writing a chat bot (Software and Game Development) https://twitch.tv/captaincallback"))
+        );
+        bot.handle_event(command(
+            CommandType::GoLive,
+            "Carkhy",
+            badge("broadcaster"),
+            &[],
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::StreamInfo(Err(
+            HelixError::Unavailable("offline".to_owned()),
+        ))));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("golive_unavailable", &[])));
+    }
+
+    #[test]
+    fn timing_command_handlers() {
+        let mut bot = ChatBot::builder()
+            .slow_command(Duration::from_millis(10))
+            .build();
+        let roll = match command(CommandType::Roll, "Carkhy", HashSet::default(), &[]) {
+            ChatBotEvent::Command(command) => command,
+            _ => unreachable!(),
+        };
+        // a handler doing slow I/O, e.g. waiting for the Twitch api
+        let reply = bot.timed(roll, |_, _| {
+            std::thread::sleep(Duration::from_millis(20));
+            None
+        });
+        assert!(reply.is_none());
+        let summary = bot.timings.summary();
+        assert_eq!((summary[0].count, summary[0].slow), (1, 1));
+        assert!(summary[0].p50 >= Duration::from_millis(20));
+
+        assert!(bot
+            .handle_event(command(
+                CommandType::Roll,
+                "Carkhy",
+                HashSet::default(),
+                &["2d6"]
+            ))
+            .is_some());
+        let result = bot.handle_raw(&chat_line("carkhy", "!perf"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_raw(&broadcaster_line("!perf"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.starts_with("Command timings: !roll p50 ")
+                             && message.contains(" (2 uses, 1 slow)")));
+        let result = ChatBot::new().handle_raw(&broadcaster_line("!perf"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "No commands were used since the bot started."));
+    }
+
+    fn expected_roll(bot: &ChatBot, key: &str, action: RollAction, seed: u64) -> String {
+        let roll = action.roll(&mut StdRng::seed_from_u64(seed));
+        bot.messages.text(
+            key,
+            &[
+                ("dice", &roll.dice_text()),
+                ("result", &roll.result.to_string()),
+                ("modifier", "+3"),
+            ],
+        )
+    }
+
+    #[test]
+    fn rolling_dice() {
+        let mut bot = ChatBot::new();
+        bot.rng = StdRng::seed_from_u64(42);
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["2d6+3"],
+        ));
+        let action = RollAction::Dice {
+            count: 2,
+            sides: 6,
+            modifier: 3,
+        };
+        let expected = expected_roll(&bot, "roll_result_modifier", action, 42);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["2d"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("roll_usage", &[])));
+        // the sum would overflow
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["1d6+9223372036854775807"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("roll_usage", &[])));
+    }
+
+    #[test]
+    fn rolling_exploding_dice() {
+        let mut bot = ChatBot::new();
+        bot.rng = StdRng::seed_from_u64(39);
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["1d6!+1"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "[6 → 3] +1 = 10"));
+    }
+
+    #[test]
+    fn joking_about_degenerate_dice() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["1"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "A d1? Bold. You rolled a 1."));
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["3d1"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "A d1? Bold. You rolled a 3."));
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["0"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("roll_d0", &[])));
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["d0"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("roll_d0", &[])));
+        // neither the recent rolls nor the record are touched
+        assert!(bot.session.recent_rolls.is_empty());
+        assert!(bot.roll_record.is_none());
+    }
+
+    #[test]
+    fn rolling_with_advantage() {
+        let mut bot = ChatBot::new();
+        bot.rng = StdRng::seed_from_u64(42);
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["adv"],
+        ));
+        let expected = expected_roll(&bot, "roll_advantage", RollAction::Advantage, 42);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected && message.ends_with("(advantage)")));
+    }
+
+    #[test]
+    fn rolling_with_disadvantage() {
+        let mut bot = ChatBot::new();
+        bot.rng = StdRng::seed_from_u64(42);
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &["dis"],
+        ));
+        let expected = expected_roll(&bot, "roll_disadvantage", RollAction::Disadvantage, 42);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected && message.ends_with("(disadvantage)")));
+    }
+
+    #[test]
+    fn remembering_recent_rolls() {
+        let mut bot = ChatBot::builder()
+            .with_rng(StdRng::seed_from_u64(7))
+            .build();
+        let result = bot.handle_raw(&chat_line("carkhy", "!myrolls"));
+        let expected = bot.messages.text("my_rolls_none", &[("user", "carkhy")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        let mut results = Vec::new();
+        for _ in 0..ROLL_HISTORY + 2 {
+            bot.handle_raw(&chat_line("carkhy", "!roll"));
+            results.push(*bot.session.recent_rolls["carkhy"].back().unwrap());
+        }
+        // only the last rolls are kept
+        let recent: Vec<String> = results[2..].iter().map(i64::to_string).collect();
+        let expected = bot.messages.text(
+            "my_rolls",
+            &[("user", "Carkhy"), ("rolls", &recent.join(", "))],
+        );
+        let result = bot.handle_raw(&chat_line("Carkhy", "!myrolls"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!myrolls"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("my_rolls_none", &[("user", "alice")])));
+    }
+
+    #[test]
+    fn summarizing_the_rolls_of_the_session() {
+        let mut bot = ChatBot::builder()
+            .with_rng(StdRng::seed_from_u64(7))
+            .build();
+        let result = bot.handle_raw(&chat_line("carkhy", "!rollstats 2d6"));
+        let expected = bot.messages.text("rollstats_none", &[("dice", "2d6")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        // the same rolls with the same rng
+        let mut rng = StdRng::seed_from_u64(7);
+        let two_d6 = RollAction::parse(&["2d6".to_owned()]).unwrap();
+        let d20 = RollAction::parse(&[]).unwrap();
+        let mut results = Vec::new();
+        for (user, text, action) in [
+            ("carkhy", "!roll 2d6", two_d6),
+            ("alice", "!roll", d20),
+            ("alice", "!roll 2D6", two_d6),
+            ("bob", "!roll 2d6", two_d6),
+            ("carkhy", "!roll 2d6", two_d6),
+        ] {
+            bot.handle_raw(&chat_line(user, text));
+            let result = action.roll(&mut rng).result;
+            if action == two_d6 {
+                results.push(result);
+            }
+        }
+        let average = results.iter().sum::<i64>() as f64 / results.len() as f64;
+        let expected = bot.messages.plural(
+            "rollstats",
+            4,
+            &[
+                ("dice", "2d6"),
+                ("min", &results.iter().min().unwrap().to_string()),
+                ("max", &results.iter().max().unwrap().to_string()),
+                ("average", &format!("{:.2}", average)),
+            ],
+        );
+        let result = bot.handle_raw(&chat_line("bob", "!rollstats 2d6"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        let result = bot.handle_raw(&chat_line("bob", "!rollstats"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.starts_with("1 roll of d20")));
+        let result = bot.handle_raw(&chat_line("bob", "!rollstats lots"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("rollstats_usage", &[])));
+    }
+
+    #[test]
+    fn rolling_fate_dice_without_records() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("carkhy", "!roll 4dF"));
+        let result = match result {
+            Some(ChatBotCommand::SendMessage(message)) => message,
+            result => panic!("unexpected result {:?}", result),
+        };
+        let total = bot.session.recent_rolls["carkhy"][0];
+        assert!(result.ends_with(&format!("] = {:+}", total)), "{}", result);
+        // four symbols like [+ - 0 +]
+        assert_eq!(result.split_once(']').unwrap().0.len(), "[+ - 0 +".len());
+        assert!(bot.roll_record.is_none());
+    }
+
+    #[test]
+    fn beating_the_roll_record() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(command(
+            CommandType::Roll,
+            "Carkhy",
+            HashSet::default(),
+            &[],
+        ));
+        let first = bot.roll_record.clone().unwrap();
+        let expected = bot.messages.text(
+            "roll_first_record",
+            &[("user", "Carkhy"), ("result", &first.result.to_string())],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(_), ChatBotCommand::SendMessage(message), ChatBotCommand::StoreRollRecord(record)]
+                                     if message == &expected && record == &first))
+        );
+
+        // the persisted record is kept after a restart
+        let mut bot = ChatBot::builder()
+            .roll_record(Some(RollRecord {
+                user: "alice".to_owned(),
+                result: 19,
+            }))
+            .build();
+        let mut rolls = 0;
+        let result = loop {
+            let result = bot.handle_event(command(
+                CommandType::Roll,
+                "Carkhy",
+                HashSet::default(),
+                &[],
+            ));
+            if matches!(result, Some(ChatBotCommand::MultipleCommands(_))) {
+                break result;
+            }
+            rolls += 1;
+            assert!(rolls < 1000, "no 20 in {} rolls", rolls);
+        };
+        let expected = bot.messages.text(
+            "roll_record",
+            &[
+                ("user", "Carkhy"),
+                ("result", "20"),
+                ("old_user", "alice"),
+                ("old_result", "19"),
+            ],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [_, ChatBotCommand::SendMessage(message), _] if message == &expected))
+        );
+        // only a single d20 counts
+        bot.roll_record = None;
+        assert!(matches!(
+            bot.handle_event(command(
+                CommandType::Roll,
+                "Carkhy",
+                HashSet::default(),
+                &["2d20"]
+            )),
+            Some(ChatBotCommand::SendMessage(_))
+        ));
+    }
+
+    fn quiz_bot() -> ChatBot {
+        let questions = QuizQuestion::from_json(
+            r#"[{"question": "Which planet is the largest?", "options": ["Mars", "Jupiter", "Venus", "Earth"], "answer": 2}]"#,
+        )
+        .unwrap();
+        ChatBot::builder()
+            .quiz(questions, HashMap::from([("alice".to_owned(), 4)]))
+            .build()
+    }
+
+    fn chat_line(user: &str, text: &str) -> String {
+        format!(
+            ":{user}!{user}@{user}.tmi.twitch.tv PRIVMSG #channel123 :{text}",
+            user = user,
+            text = text
+        )
+    }
+
+    #[test]
+    fn running_a_quiz_round() {
+        let mut bot = quiz_bot();
+        let result = bot.handle_event(command(
+            CommandType::Quiz,
+            "Carkhy",
+            badge("subscriber"),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        // numbers are normal messages without a quiz
+        bot.handle_raw(&chat_line("alice", "2"));
+
+        let result = bot.handle_event(command(
+            CommandType::Quiz,
+            "Carkhy",
+            badge("moderator"),
+            &["20"],
+        ));
+        let id = match result {
+            Some(ChatBotCommand::MultipleCommands(commands)) => match &commands[..] {
+                [ChatBotCommand::SendMessage(message), ChatBotCommand::TimedCallback {
+                    duration,
+                    event: ChatBotEvent::QuizEnd(id),
+                }] => {
+                    assert!(message.contains("1) Mars 2) Jupiter 3) Venus 4) Earth"));
+                    assert_eq!(*duration, Duration::from_secs(20));
+                    *id
+                }
+                commands => panic!("unexpected commands {:?}", commands),
+            },
+            result => panic!("unexpected result {:?}", result),
+        };
+        let result = bot.handle_event(command(
+            CommandType::Quiz,
+            "Carkhy",
+            badge("moderator"),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("quiz_running", &[])));
+
+        bot.handle_raw(&chat_line("alice", "2"));
+        bot.handle_raw(&chat_line("bob", "1"));
+        bot.handle_raw(&chat_line("carol", "Jupiter"));
+        bot.handle_raw(&chat_line("carol", "5"));
+        bot.handle_raw(&chat_line("carol", "2"));
+        bot.handle_raw(&chat_line("alice", "3"));
+        bot.handle_raw(&chat_line("bob", "2"));
+
+        // the timer of an older quiz does not end the round
+        assert!(bot
+            .handle_event(ChatBotEvent::QuizEnd(Uuid::new_v4()))
+            .is_none());
+        let result = bot.handle_event(ChatBotEvent::QuizEnd(id));
+        let expected = bot.messages.text(
+            "quiz_result",
+            &[
+                ("number", "2"),
+                ("answer", "Jupiter"),
+                ("correct", "2"),
+                ("total", "3"),
+                ("percent", "66"),
+            ],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message), ChatBotCommand::StoreQuizPoints(points)]
+                                     if message == &expected
+                                     && points == &vec![("alice".to_owned(), 5), ("carol".to_owned(), 1)]))
+        );
+        let result = bot.handle_event(command(
+            CommandType::Quiz,
+            "Carkhy",
+            badge("subscriber"),
+            &["points", "@Carol"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("quiz_points", &[("user", "Carol"), ("points", "1")])));
+    }
+
+    #[test]
+    fn ending_a_quiz_without_answers() {
+        let mut bot = quiz_bot();
+        let id = match bot.handle_event(command(
+            CommandType::Quiz,
+            "Carkhy",
+            badge("moderator"),
+            &[],
+        )) {
+            Some(ChatBotCommand::MultipleCommands(commands)) => match &commands[..] {
+                [_, ChatBotCommand::TimedCallback {
+                    event: ChatBotEvent::QuizEnd(id),
+                    ..
+                }] => *id,
+                commands => panic!("unexpected commands {:?}", commands),
+            },
+            result => panic!("unexpected result {:?}", result),
+        };
+        let result = bot.handle_event(ChatBotEvent::QuizEnd(id));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("quiz_no_answers", &[("number", "2"), ("answer", "Jupiter")])));
+        let result = bot.handle_event(command(
+            CommandType::Quiz,
+            "Carkhy",
+            badge("moderator"),
+            &["5"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("quiz_usage", &[])));
+    }
+
+    #[test]
+    fn greeting_the_chat_once_when_connected() {
+        let mut bot = ChatBot::new();
+        assert!(bot.handle_event(ChatBotEvent::Connected).is_none());
+        let mut bot = ChatBot::builder()
+            .connect_greeting(Some(Template::parse("Hi chat!").unwrap()))
+            .build();
+        let result = bot.handle_event(ChatBotEvent::Connected);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == "Hi chat!")
+        );
+        // reconnects are silent
+        assert!(bot.handle_event(ChatBotEvent::Connected).is_none());
+    }
+
+    #[test]
+    fn replying_to_mentions() {
+        let mut bot = ChatBot::builder()
+            .nickname("Botanist")
+            .commands_anywhere(Some(Permission::Everyone))
+            .mention_reply(
+                Some(Template::parse("Hi {user}!").unwrap()),
+                Duration::from_secs(300),
+            )
+            .build();
+        bot.dynamic_commands.insert(
+            "clip".to_owned(),
+            Template::parse("{user} wants a clip of {args:1}").unwrap(),
+        );
+        let result = bot.handle_raw(&chat_line("alice", "thanks @BOTANIST!"));
+        assert_eq!(replies(result), vec!["Hi alice!"]);
+        // once per chatter in the cooldown
+        let result = bot.handle_raw(&chat_line("alice", "botanist, are you there?"));
+        assert!(replies(result).is_empty());
+        let result = bot.handle_raw(&chat_line("bob", "hey botanist"));
+        assert_eq!(replies(result), vec!["Hi bob!"]);
+        for text in ["the botanists are here", "hi @mybotanist"] {
+            assert!(replies(bot.handle_raw(&chat_line("carol", text))).is_empty());
+        }
+        // commands and the bot's own messages are not answered
+        let result = bot.handle_raw(&chat_line("carol", "@botanist can you !clip that"));
+        assert_eq!(replies(result), vec!["carol wants a clip of that"]);
+        assert!(replies(bot.handle_raw(&chat_line("botanist", "I am botanist"))).is_empty());
+        assert_eq!(
+            replies(bot.handle_raw(&chat_line("carol", "botanist?"))),
+            vec!["Hi carol!"]
+        );
+        // the display name from USERSTATE is a mention as well
+        bot.handle_event(ChatBotEvent::UserState {
+            badges: HashSet::new(),
+            display_name: Some("植物学者".to_owned()),
+        });
+        assert_eq!(
+            replies(bot.handle_raw(&chat_line("dave", "@植物学者 hello"))),
+            vec!["Hi dave!"]
+        );
+    }
+
+    fn announcement_line(user: &str, text: &str) -> String {
+        format!(
+            "@badges=moderator/1;login={user};msg-id=announcement;msg-param-color=PURPLE;room-id=12345678 :tmi.twitch.tv USERNOTICE #channel123 :{text}",
+            user = user,
+            text = text
+        )
+    }
+
+    #[test]
+    fn handling_announcements() {
+        let mut bot = ChatBot::builder()
+            .nickname("botanist")
+            .commands_anywhere(Some(Permission::Everyone))
+            .build();
+        bot.dynamic_commands.insert(
+            "clip".to_owned(),
+            Template::parse("{user} wants a clip of {args:1}").unwrap(),
+        );
+        // announcements are only logged by default
+        let result = bot.handle_raw(&announcement_line("ronni", "please !clip that"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(log))
+                         if log == "[announcement purple] ronni: please !clip that"));
+
+        bot.announcement_triggers = true;
+        let result = flattened(bot.handle_raw(&announcement_line("ronni", "please !clip that")));
+        assert!(
+            matches!(&result[..], [ChatBotCommand::LogTextMessage(log), ChatBotCommand::SendMessage(reply)]
+                         if log.starts_with("[announcement") && reply == "ronni wants a clip of that")
+        );
+        // the bot's own announcements never trigger it
+        let result = bot.handle_raw(&announcement_line("botanist", "please !clip that"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    #[test]
+    fn celebrating_message_milestones() {
+        let mut usage_stats = HashMap::new();
+        usage_stats.insert(
+            "carol".to_owned(),
+            UserStats {
+                messages: 1500,
+                ..UserStats::default()
+            },
+        );
+        let milestones = MessageMilestones::new(
+            Template::parse("{user} just sent their {milestone}th message!").unwrap(),
+            vec![2, 3, 1000],
+            2,
+        )
+        .with_reached(HashMap::from([("dave".to_owned(), 3)]));
+        let mut bot = ChatBot::builder()
+            .go_live("captaincallback", None)
+            .usage_stats(usage_stats)
+            .message_milestones(Some(milestones))
+            .build();
+        let celebrations = |result: Option<ChatBotCommand>| {
+            flattened(result)
+                .into_iter()
+                .filter_map(|command| match command {
+                    ChatBotCommand::SendMessage(message) => Some(message),
+                    // stored for the channel of the message
+                    ChatBotCommand::StoreMessageMilestone {
+                        channel,
+                        user,
+                        milestone,
+                    } if channel == "channel123" => Some(format!("stored {} {}", user, milestone)),
+                    _ => None,
+                })
+                .collect::<Vec<String>>()
+        };
+
+        assert!(celebrations(bot.handle_raw(&chat_line("alice", "hi"))).is_empty());
+        assert_eq!(
+            celebrations(bot.handle_raw(&chat_line("Alice", "!slap bob"))),
+            ["stored alice 2", "Alice just sent their 2th message!"]
+        );
+        // the milestone was reached before it was configured, it is only celebrated once
+        assert_eq!(
+            celebrations(bot.handle_raw(&chat_line("carol", "hello"))),
+            [
+                "stored carol 1000",
+                "carol just sent their 1,000th message!"
+            ]
+        );
+        assert!(celebrations(bot.handle_raw(&chat_line("carol", "hello again"))).is_empty());
+        // celebrated before the bot restarted
+        for _ in 0..3 {
+            assert!(celebrations(bot.handle_raw(&chat_line("dave", "hi"))).is_empty());
+        }
+
+        // the celebrations of the stream are used up
+        assert_eq!(
+            celebrations(bot.handle_raw(&chat_line("alice", "third"))),
+            ["stored alice 3"]
+        );
+        assert!(celebrations(bot.handle_raw(&chat_line("alice", "fourth"))).is_empty());
+        bot.handle_raw(&chat_line("erin", "hi"));
+        bot.handle_event(ChatBotEvent::CheckStream);
+        bot.handle_event(stream_info(false));
+        bot.handle_event(ChatBotEvent::CheckStream);
+        bot.handle_event(stream_info(true));
+        assert_eq!(
+            celebrations(bot.handle_raw(&chat_line("erin", "hi again"))),
+            ["stored erin 2", "erin just sent their 2th message!"]
+        );
+    }
+
+    #[test]
+    fn celebrating_watch_streaks() {
+        let line = "@badges=;login=carkhy;msg-id=viewermilestone;msg-param-category=watch-streak;msg-param-value=7;room-id=12345678 :tmi.twitch.tv USERNOTICE #channel123";
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(line);
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(log))
+                         if log == "[milestone] carkhy watched 7 consecutive streams"));
+        let mut bot = ChatBot::builder()
+            .viewer_milestone_reply(Some(
+                Template::parse("{user} watched {streak} streams in a row!").unwrap(),
+            ))
+            .build();
+        assert_eq!(
+            replies(bot.handle_raw(line)),
+            vec!["carkhy watched 7 streams in a row!"]
+        );
+    }
+
+    #[test]
+    fn autoresponding_to_patterns() {
+        let autoresponder = Autoresponder::from_json(
+            r#"[{"pattern": "https?://", "response": "Links are only for subs, {user}!", "cooldown": 60}]"#,
+        )
+        .unwrap();
+        let mut bot = ChatBot::builder()
+            .nickname("botanist")
+            .autoresponder(autoresponder)
+            .build();
+        let result = bot.handle_raw(&chat_line("alice", "hello there"));
+        assert!(replies(result).is_empty());
+        let result = bot.handle_raw(&chat_line("alice", "see https://example.com"));
+        assert_eq!(replies(result), vec!["Links are only for subs, alice!"]);
+        // the pattern is cooling down
+        let result = bot.handle_raw(&chat_line("bob", "http://example.com"));
+        assert!(replies(result).is_empty());
+        bot.autoresponder = Autoresponder::from_json(
+            r#"[{"pattern": "https?://", "response": "Links are only for subs!"}]"#,
+        )
+        .unwrap();
+        let result = bot.handle_raw(&chat_line("botanist", "https://example.com"));
+        assert!(replies(result).is_empty());
+    }
+
+    #[test]
+    fn keeping_commands_when_reconnecting() {
+        let mut bot = ChatBot::builder().unknown_command_reply(true).build();
+        bot.handle_raw(":carkhy!carkhy@carkhy.tmi.twitch.tv JOIN #channel123");
+        bot.handle_raw("@badges=moderator/1 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!newcommand hello Hello there!");
+        bot.handle_event(command(
+            CommandType::Dynamic("thisisnotacommand".to_owned()),
+            "Carkhy",
+            HashSet::default(),
+            &[],
+        ));
+        assert_eq!(bot.connection.chatters.len(), 1);
+
+        bot.handle_event(ChatBotEvent::Connected);
+        assert!(bot.connection.chatters.is_empty());
+        // cooldowns are kept by default
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_none());
+        let result =
+            bot.handle_raw(":carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!hello");
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Hello there!"));
+    }
+
+    #[test]
+    fn truncating_long_command_responses() {
+        let mut bot = ChatBot::new();
+        let text = "a".repeat(2000);
+        let result = bot.handle_raw(&moderator_line(
+            "carkhy",
+            &format!("!newcommand long {}", text),
+        ));
+        let expected = bot
+            .messages
+            .text("new_command_truncated", &[("length", "500")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!long"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == text[..MAX_MESSAGE_LENGTH]));
+        // the text is not cut inside a tag, only the reply is
+        let text = format!("{} {{args:1}}", "a".repeat(MAX_MESSAGE_LENGTH - 5));
+        let result = bot.handle_raw(&moderator_line(
+            "carkhy",
+            &format!("!newcommand hug {}", text),
+        ));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("new_command_successful", &[])]
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!hug bob"));
+        assert_eq!(
+            replies(result),
+            vec![format!("{} bob", "a".repeat(MAX_MESSAGE_LENGTH - 5))]
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!hug everyone_in_the_chat"));
+        assert_eq!(
+            replies(result).pop().unwrap().chars().count(),
+            MAX_MESSAGE_LENGTH
+        );
+    }
+
+    #[test]
+    fn rejecting_invalid_command_names() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&moderator_line("carkhy", "!newcommand !hello Hello there!"));
+        let expected = bot
+            .messages
+            .text("new_command_invalid_name", &[("prefix", "!")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        assert!(bot.dynamic_commands.is_empty());
+        assert!(!valid_command_name("hel\u{a0}lo", "!"));
+        assert!(!valid_command_name("he?llo", "?"));
+        assert!(valid_command_name("hello", "!"));
+    }
+
+    #[test]
+    fn resetting_cooldowns_when_reconnecting() {
+        let mut bot = ChatBot::builder()
+            .unknown_command_reply(true)
+            .reset_cooldowns_on_connect(true)
+            .build();
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_some());
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_none());
+        bot.handle_event(ChatBotEvent::Connected);
+        assert!(bot
+            .handle_event(command(
+                CommandType::Dynamic("thisisnotacommand".to_owned()),
+                "Carkhy",
+                HashSet::default(),
+                &[]
+            ))
+            .is_some());
+    }
+
+    fn owner_bot() -> ChatBotBuilder {
+        ChatBot::builder().owners(HashSet::from(["70346833".to_owned()]))
+    }
+
+    #[test]
+    fn authorizing_admin_commands_by_user_id() {
+        let mut bot = owner_bot().build();
+        let result = bot.handle_raw("@badges=;user-id=70346833 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!say hello chat");
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "hello chat"));
+        // a user who took the name of an owner is not an owner
+        let result = bot.handle_raw("@badges=broadcaster/1;user-id=11111111 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!say hello chat");
+        assert!(
+            matches!(result, Some(ChatBotCommand::LogTextMessage(message))
+                         if message == "Unauthorized !say by carkhy (11111111)")
+        );
+        let result =
+            bot.handle_raw(":carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!shutdown");
+        assert!(
+            matches!(result, Some(ChatBotCommand::LogTextMessage(message))
+                         if message == "Unauthorized !shutdown by carkhy (unknown id)")
+        );
+    }
+
+    #[test]
+    fn running_whispered_admin_commands() {
+        let mut bot = owner_bot().build();
+        let whisper = |command: &str, user_id: &str| {
+            format!(
+                "@badges=;user-id={} :carkhy!carkhy@carkhy.tmi.twitch.tv WHISPER chatbot :!{}",
+                user_id, command
+            )
+        };
+        let result = bot.handle_raw(&whisper("shutdown", "70346833"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(_), ChatBotCommand::Shutdown]))
+        );
+        let result = bot.handle_raw(&whisper("restartconn", "70346833"));
+        assert!(matches!(result, Some(ChatBotCommand::Reconnect)));
+        let result = bot.handle_raw(&whisper("botstatus", "70346833"));
+        assert!(matches!(result, Some(ChatBotCommand::ReportStatus)));
+        let result = bot.handle_raw(&whisper("say", "70346833"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("say_usage", &[])));
+        let result = bot.handle_raw(&whisper("say #OtherChannel hello there", "70346833"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessageTo { channel, text })
+                         if channel == "otherchannel" && text == "hello there")
+        );
+        let result = bot.handle_raw(&whisper("say #otherchannel", "70346833"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("say_usage", &[])));
+        // not a channel
+        let result = bot.handle_raw(&whisper("say #1 fan", "70346833"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "#1 fan"));
+        let result = bot.handle_raw(&whisper("restartconn", "11111111"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+        // other commands are not answered in whispers
+        assert!(bot.handle_raw(&whisper("help", "70346833")).is_none());
+    }
+
+    #[test]
+    fn auditing_token_scopes() {
+        let config = crate::app_config::AppConfig::for_server("ws://localhost:6667");
+        let mut bot = owner_bot()
+            .scoped_features(crate::connect::enabled_features(
+                &config.with_native_shoutout(),
+            ))
+            .build();
+        let result = bot.handle_raw("@badges=;user-id=70346833 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!scopes");
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::TokenScopes))
+        ));
+        let granted: Vec<String> = bot
+            .scoped_features
+            .iter()
+            .map(|feature| feature.scope.to_owned())
+            .filter(|scope| {
+                scope != "moderator:manage:shoutouts" && scope != "channel:manage:raids"
+            })
+            .collect();
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::TokenScopes(Ok(
+            granted,
+        ))));
+        let expected = bot.messages.text(
+            "scopes_missing",
+            &[(
+                "missing",
+                "moderator:manage:shoutouts (TWITCH_NATIVE_SHOUTOUT), channel:manage:raids (!raid)",
+            )],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        let all: Vec<String> = bot
+            .scoped_features
+            .iter()
+            .map(|feature| feature.scope.to_owned())
+            .collect();
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::TokenScopes(Ok(
+            all,
+        ))));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("scopes_complete", &[])));
+        // only the owners may audit the token
+        let result = bot.handle_raw("@badges=broadcaster/1;user-id=11111111 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :!scopes");
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    #[test]
+    fn changing_the_bot_color() {
+        let mut bot = owner_bot().build();
+        let line = |text: &str| {
+            format!(
+                "@badges=;user-id=70346833 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :{}",
+                text
+            )
+        };
+        let result = bot.handle_raw(&line("!botcolor HotPink"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::UpdateChatColor { color })) if color == "hot_pink"
+        ));
+        let result = bot.handle_raw(&line("!botcolor purple"));
+        let expected = bot.messages.text(
+            "botcolor_invalid",
+            &[("color", "purple"), ("colors", &chat_color_names())],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::ChatColor {
+            color: "#9146FF".to_owned(),
+            result: Err(HelixError::Status(
+                400,
+                "Turbo or Prime required".to_owned(),
+            )),
+        }));
+        let expected = bot.messages.text(
+            "botcolor_rejected",
+            &[("color", "#9146FF"), ("message", "Turbo or Prime required")],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+    }
+
+    #[test]
+    fn confirming_commands_before_running_them() {
+        let mut bot = owner_bot().confirmations(&["BotColor".to_owned()]).build();
+        let line = |user_id: &str, text: &str| {
+            format!(
+                "@badges=;user-id={} :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #channel123 :{}",
+                user_id, text
+            )
+        };
+        let expected = bot.messages.text(
+            "confirm_command",
+            &[("command", "botcolor"), ("seconds", "10")],
+        );
+        let result = bot.handle_raw(&line("70346833", "!botcolor coral"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        // other arguments start over
+        let result = bot.handle_raw(&line("70346833", "!botcolor blue"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        let result = bot.handle_raw(&line("70346833", "!botcolor blue"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::UpdateChatColor { color })) if color == "blue"
+        ));
+        // users who may not use the command are not asked
+        let result = bot.handle_raw(&line("12345", "!botcolor blue"));
+        assert!(!matches!(result, Some(ChatBotCommand::SendMessage(message))
+                          if message == expected));
+    }
+
+    #[test]
+    fn rotating_the_bot_color_with_each_stream() {
+        let mut bot = ChatBot::builder()
+            .bot_colors(vec!["coral".to_owned(), "#9146FF".to_owned()])
+            .build();
+        let mut colors = Vec::new();
+        for _ in 0..3 {
+            // each stream starts long after the last one ended
+            bot.session.go_live = GoLiveState::default();
+            bot.handle_event(ChatBotEvent::CheckStream);
+            bot.handle_event(stream_info(false));
+            bot.handle_event(ChatBotEvent::CheckStream);
+            colors.extend(
+                flattened(bot.handle_event(stream_info(true)))
+                    .into_iter()
+                    .filter_map(|command| match command {
+                        ChatBotCommand::HelixRequest(HelixRequest::UpdateChatColor { color }) => {
+                            Some(color)
+                        }
+                        _ => None,
+                    }),
+            );
+        }
+        assert_eq!(colors, vec!["coral", "#9146FF", "coral"]);
+    }
+
+    #[test]
+    fn reporting_bot_status() {
+        let mut bot = owner_bot().build();
+        let result = bot.handle_event(ChatBotEvent::Status(BotStatus {
+            uptime: Duration::from_secs(((26 * 60) + 5) * 60 + 59),
+            connected: true,
+            reconnects: 0,
+            queued_messages: 3,
+            memory_kb: Some(20480),
+            suppressed_duplicates: 2,
+            latency: Some(Duration::from_millis(47)),
+            last_latency: Some(Duration::from_millis(52)),
+            helix: None,
+        }));
+        let expected = bot.messages.text(
+            "botstatus",
+            &[
+                ("uptime", "1d 2h 5m"),
+                ("connection", "connected"),
+                ("queued", "3"),
+                ("memory", "20 MB"),
+                ("duplicates", "2"),
+                ("latency", "47 ms (last 52 ms)"),
+            ],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        let result = bot.handle_event(ChatBotEvent::Status(BotStatus {
+            uptime: Duration::from_secs(60),
+            connected: true,
+            reconnects: 2,
+            queued_messages: 0,
+            memory_kb: None,
+            suppressed_duplicates: 0,
+            latency: Some(Duration::from_millis(800)),
+            last_latency: Some(Duration::from_millis(2000)),
+            helix: Some(HelixMetrics {
+                requests: 12,
+                retries: 2,
+                failed: 1,
+                ..HelixMetrics::default()
+            }),
+        }));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.contains("chat: connected, 2 reconnects | ")
+                             && message.contains("800 ms (last 2000 ms, degraded)")
+                             && message.ends_with(" | helix: 12 requests, 2 retried, 1 failed")));
+        assert_eq!(format_uptime(Duration::from_secs(59)), "0m");
+        assert_eq!(format_uptime(Duration::from_secs(3 * 3600 + 120)), "3h 2m");
+    }
+
+    fn duel_line(user: &str, command: &str) -> String {
+        format!(
+            ":{}!{}@{}.tmi.twitch.tv PRIVMSG #channel123 :{}",
+            user, user, user, command
+        )
+    }
+
+    #[test]
+    fn fighting_a_duel() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&duel_line("alice", "!duel @Bob"));
+        let expected = bot.messages.text(
+            "duel_challenge",
+            &[
+                ("challenger", "alice"),
+                ("target", "Bob"),
+                ("seconds", "60"),
+            ],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+
+        bot.rng = StdRng::seed_from_u64(42);
+        let result = bot.handle_raw(&duel_line("bob", "!accept"));
+        let mut rng = StdRng::seed_from_u64(42);
+        let (alice_roll, bob_roll) = (rng.gen_range(1..=20u32), rng.gen_range(1..=20u32));
+        let (key, winner) = match alice_roll.cmp(&bob_roll) {
+            Ordering::Greater => ("duel_result", "alice"),
+            Ordering::Less => ("duel_result", "bob"),
+            Ordering::Equal => ("duel_draw", ""),
+        };
+        let expected = bot.messages.text(
+            key,
+            &[
+                ("challenger", "alice"),
+                ("challenger_roll", &alice_roll.to_string()),
+                ("target", "bob"),
+                ("target_roll", &bob_roll.to_string()),
+                ("winner", winner),
+            ],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+
+        // the duel is over
+        let result = bot.handle_raw(&duel_line("bob", "!accept"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("duel_no_challenge", &[])));
+        let result = bot.handle_raw(&duel_line("alice", "!duel @ALICE"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("duel_self", &[])));
+    }
+
+    #[test]
+    fn rerolling_a_tied_duel() {
+        // the first seed whose first round ties and whose second round has a winner
+        let seed = (0..)
+            .find(|seed| {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                let mut roll = || rng.gen_range(1..=20u32);
+                let rolls = [roll(), roll(), roll(), roll()];
+                rolls[0] == rolls[1] && rolls[2] != rolls[3]
+            })
+            .unwrap();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let rolls: Vec<u32> = (0..4).map(|_| rng.gen_range(1..=20u32)).collect();
+
+        let mut bot = ChatBot::new();
+        bot.handle_raw(&duel_line("alice", "!duel @bob"));
+        bot.rng = StdRng::seed_from_u64(seed);
+        let result = bot.handle_raw(&duel_line("bob", "!accept"));
+        let round = |key: &str, alice_roll: u32, bob_roll: u32, winner: &str| {
+            bot.messages.text(
+                key,
+                &[
+                    ("challenger", "alice"),
+                    ("challenger_roll", &alice_roll.to_string()),
+                    ("target", "bob"),
+                    ("target_roll", &bob_roll.to_string()),
+                    ("winner", winner),
+                ],
+            )
+        };
+        let tie = round("duel_tie", rolls[0], rolls[1], "");
+        let winner = if rolls[2] > rolls[3] { "alice" } else { "bob" };
+        let decision = round("duel_result", rolls[2], rolls[3], winner);
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(first), ChatBotCommand::SendMessage(second)]
+                                     if *first == tie && *second == decision))
+        );
+    }
+
+    #[test]
+    fn accepting_an_expired_duel() {
+        let mut bot = ChatBot::new();
+        bot.session
+            .duels
+            .challenge(
+                "alice",
+                "bob",
+                Instant::now() - DUEL_EXPIRY - Duration::from_secs(1),
+            )
+            .unwrap();
+        let result = bot.handle_raw(&duel_line("bob", "!accept @alice"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("duel_expired", &[("challenger", "alice")])));
+    }
+
+    const OPEN_RAFFLE: &str = "@badges=moderator/1 :moderator!moderator@moderator.tmi.twitch.tv PRIVMSG #channel123 :!raffle open --followers --messages 2";
+
+    #[test]
+    fn entering_raffle_for_followers() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(OPEN_RAFFLE);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("raffle_opened", &[])));
+        bot.handle_raw(&chat_line("carkhy", "hello"));
+        bot.handle_raw(&chat_line("carkhy", "good luck everyone"));
+
+        let result = bot.handle_raw(&chat_line("carkhy", "!enter"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::UserAge { login }))
+                         if login == "carkhy")
+        );
+        let not_following = ChatBotEvent::HelixResponse(HelixResponse::UserAge {
+            login: "carkhy".to_owned(),
+            result: Ok(UserAge {
+                created_at: Utc::now() - chrono::Duration::days(100),
+                followed_at: None,
+            }),
+        });
+        let result = bot.handle_event(not_following);
+        let expected = bot.messages.text(
+            "raffle_not_follower",
+            &[("user", "carkhy"), ("messages", "2")],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message)] if message == &expected))
+        );
+        // the explanation is only given once
+        assert!(bot.handle_raw(&chat_line("carkhy", "!enter")).is_none());
+
+        bot.session.user_ages.clear();
+        bot.handle_raw(&chat_line("carkhy", "!enter"));
+        bot.handle_event(user_age(100, 30));
+        let result = bot.handle_raw(&OPEN_RAFFLE.replace("open --followers --messages 2", "draw"));
+        let expected = bot
+            .messages
+            .text("raffle_winner", &[("user", "carkhy"), ("entrants", "1")]);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+    }
+
+    #[test]
+    fn requiring_chat_messages_for_raffle() {
+        let mut bot = ChatBot::new();
+        bot.handle_raw(OPEN_RAFFLE);
+        bot.handle_raw(&chat_line("alice", "hi"));
+        let enter =
+            "@badges=moderator/1 :alice!alice@alice.tmi.twitch.tv PRIVMSG #channel123 :!enter";
+        let result = bot.handle_raw(enter);
+        let expected = bot
+            .messages
+            .text("raffle_too_quiet", &[("user", "alice"), ("messages", "2")]);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        assert!(bot.handle_raw(enter).is_none());
+        bot.handle_raw(&chat_line("alice", "how are you?"));
+        bot.handle_raw(enter);
+        assert_eq!(bot.session.raffle.as_ref().unwrap().entrant_count(), 1);
+        let result = bot.handle_raw(&chat_line("alice", "!raffle close"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+    }
+
+    #[test]
+    fn latching_first_chatter() {
+        let mut bot = ChatBot::builder().nickname("ChatBot").build();
+        let result = bot.handle_raw(&chat_line("alice", "!first"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("first_nobody", &[])));
+        // the bot itself does not count
+        bot.handle_raw(&chat_line("chatbot", "Hello chat!"));
+        bot.handle_raw(&chat_line("carkhy", "!help"));
+        bot.handle_raw(&chat_line("alice", "first!"));
+        bot.handle_event(ChatBotEvent::Connected);
+        bot.handle_raw(&chat_line("bob", "hi"));
+        let result = bot.handle_raw(&chat_line("alice", "!first"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("first_chatter", &[("user", "carkhy")])));
+    }
+
+    #[test]
+    fn building_a_configured_bot() {
+        let mut bot = ChatBot::builder()
+            .nickname("ChatBot")
+            .cooldown("Roll", Duration::from_secs(30))
+            .with_rng(StdRng::seed_from_u64(42))
+            .command_prefixes(CommandPrefixes::new("?", HashMap::new()))
+            .build();
+        // the messages of the bot do not make it the first chatter
+        bot.handle_raw(&chat_line("chatbot", "Hello chat!"));
+        let result = bot.handle_raw(&chat_line("alice", "?roll 2d6+3"));
+        let action = RollAction::Dice {
+            count: 2,
+            sides: 6,
+            modifier: 3,
+        };
+        let expected = expected_roll(&bot, "roll_result_modifier", action, 42);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+        assert_eq!(bot.session.first_chatter.as_deref(), Some("alice"));
+        // the cooldown is shared by all users
+        assert!(bot.handle_raw(&chat_line("bob", "?roll")).is_none());
+        // only messages with the configured prefix are commands
+        let result = bot.handle_raw(&chat_line("bob", "!help"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+        let result = bot.handle_raw(&chat_line("bob", "?help"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(_))));
+    }
+
+    #[test]
+    fn resolving_the_command_prefix_by_channel() {
+        let mut bot = ChatBot::builder()
+            .command_prefixes(CommandPrefixes::new(
+                "!",
+                HashMap::from([("channel123".to_owned(), "?".to_owned())]),
+            ))
+            .commands_anywhere(Some(Permission::Everyone))
+            .build();
+        bot.dynamic_commands.insert(
+            "lurk".to_owned(),
+            Template::parse("Enjoy the lurk!").unwrap(),
+        );
+        let other_line =
+            |user: &str, text: &str| chat_line(user, text).replace("#channel123", "#carkhy");
+        let listing = |result: Option<ChatBotCommand>| replies(result).join(" ");
+        assert!(listing(bot.handle_raw(&chat_line("alice", "?commands"))).contains("?lurk"));
+        assert!(listing(bot.handle_raw(&other_line("alice", "!commands"))).contains("!lurk"));
+        // commands in the text of a message are found with the prefix of its channel
+        assert_eq!(
+            replies(bot.handle_raw(&chat_line("alice", "going to ?lurk now"))),
+            vec!["Enjoy the lurk!"]
+        );
+        assert!(replies(bot.handle_raw(&other_line("alice", "going to ?lurk now"))).is_empty());
+        // the prefix of the channel is stripped from the command to turn off
+        let moderator = |line: String| format!("@badges=moderator/1 {}", line);
+        bot.handle_raw(&moderator(other_line("moderator", "!disable !lurk")));
+        assert!(bot.disabled_commands.contains("lurk"));
+        bot.handle_raw(&moderator(chat_line("moderator", "?enable ?lurk")));
+        assert!(!bot.disabled_commands.contains("lurk"));
+        // a new command must not contain the prefix of its channel
+        let result = bot.handle_raw(&moderator(chat_line("moderator", "?newcommand a?b Hi")));
+        assert_eq!(
+            replies(result),
+            vec![bot
+                .messages
+                .text("new_command_invalid_name", &[("prefix", "?")])]
+        );
+    }
+
+    fn moderator_line(user: &str, text: &str) -> String {
+        format!("@badges=moderator/1 {}", chat_line(user, text))
+    }
+
+    #[test]
+    fn changing_chat_modes() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("alice", "!slow 30"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+        assert_eq!(bot.connection.room, RoomState::default());
+
+        let mut change = |text: &str| {
+            let result = flattened(bot.handle_raw(&moderator_line("moderator", text)));
+            match &result[..] {
+                [ChatBotCommand::HelixRequest(HelixRequest::UpdateChatSettings(settings)), ChatBotCommand::SendMessage(confirmation)] => {
+                    (*settings, confirmation.to_owned())
+                }
+                _ => panic!("{} did not change the chat settings: {:?}", text, result),
+            }
+        };
+        assert_eq!(
+            change("!slow 45"),
+            (
+                ChatSettings {
+                    slow_mode: Some(true),
+                    slow_mode_wait_time: Some(45),
+                    ..ChatSettings::default()
+                },
+                "Slow mode is on, chatters have to wait 45 seconds between messages.".to_owned()
+            )
+        );
+        assert_eq!(
+            change("!slowoff").0,
+            ChatSettings {
+                slow_mode: Some(false),
+                ..ChatSettings::default()
+            }
+        );
+        assert_eq!(
+            change("!followersonly 1h30m"),
+            (
+                ChatSettings {
+                    follower_mode: Some(true),
+                    follower_mode_duration: Some(90),
+                    ..ChatSettings::default()
+                },
+                "Followers-only mode is on, chatters have to follow for 1h30m.".to_owned()
+            )
+        );
+        assert_eq!(
+            change("!subonly on").0,
+            ChatSettings {
+                subscriber_mode: Some(true),
+                ..ChatSettings::default()
+            }
+        );
+        assert_eq!(
+            change("!emoteonly off").0,
+            ChatSettings {
+                emote_mode: Some(false),
+                ..ChatSettings::default()
+            }
+        );
+        assert_eq!(
+            change("!uniquechat on"),
+            (
+                ChatSettings {
+                    unique_chat_mode: Some(true),
+                    ..ChatSettings::default()
+                },
+                "Unique chat mode is on, messages have to differ from the previous ones."
+                    .to_owned()
+            )
+        );
+        // the modes are known before the chat server confirms them
+        assert_eq!(
+            bot.connection.room,
+            RoomState {
+                emote_only: Some(false),
+                subs_only: Some(true),
+                slow: Some(0),
+                followers_only: Some(90),
+                unique_chat: Some(true),
+            }
+        );
+        let result = bot.handle_raw(&moderator_line("moderator", "!followersonly off"));
+        assert!(matches!(
+            &flattened(result)[0],
+            ChatBotCommand::HelixRequest(HelixRequest::UpdateChatSettings(settings))
+                if *settings == ChatSettings {
+                    follower_mode: Some(false),
+                    ..ChatSettings::default()
+                }
+        ));
+        assert_eq!(bot.connection.room.followers_only, Some(-1));
+
+        // values Twitch does not accept are not sent
+        for (text, usage) in [
+            ("!slow 2", "Usage: !slow [<seconds from 3 to 120>]"),
+            ("!slow 121", "Usage: !slow [<seconds from 3 to 120>]"),
+            (
+                "!followersonly 91d",
+                bot.messages.text("followersonly_usage", &[]).as_str(),
+            ),
+            ("!subonly", "Usage: !subonly on or !subonly off"),
+            (
+                "!uniquechat maybe",
+                "Usage: !uniquechat on or !uniquechat off",
+            ),
+        ] {
+            let result = bot.handle_raw(&moderator_line("moderator", text));
+            assert_eq!(replies(result), vec![usage], "{}", text);
+        }
+        assert_eq!(bot.connection.room.slow, Some(0));
+
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::ChatSettings(
+            Err(HelixError::Status(403, "Missing scope".to_owned())),
+        )));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("chat_settings_denied", &[])]
+        );
+    }
+
+    #[test]
+    fn listing_viewers() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("alice", "!viewers list"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+        // nobody joined yet
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers"));
+        assert_eq!(replies(result), vec!["0 viewers in the chat"]);
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers list"));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("viewers_none", &[])]
+        );
+
+        for user in ["carol", "Bob", "alice"] {
+            bot.handle_raw(&format!(
+                ":{user}!{user}@{user}.tmi.twitch.tv JOIN #channel123",
+                user = user
+            ));
+        }
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers list"));
+        assert_eq!(replies(result), vec!["Viewers (1/1): alice, Bob, carol"]);
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers list 2"));
+        assert_eq!(replies(result), vec!["There is only 1 page of viewers."]);
+
+        for number in 0..100 {
+            bot.connection
+                .chatters
+                .insert(format!("viewer_with_a_long_name{:03}", number));
+        }
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers"));
+        assert_eq!(replies(result), vec!["103 viewers in the chat"]);
+        let mut listed = Vec::new();
+        for page in 1..=6 {
+            let text = format!("!viewers list {}", page);
+            let reply = replies(bot.handle_raw(&moderator_line("moderator", &text))).remove(0);
+            assert!(reply.chars().count() <= MAX_MESSAGE_LENGTH);
+            let prefix = format!("Viewers ({}/6): ", page);
+            let names = reply.strip_prefix(&prefix).unwrap().to_owned();
+            listed.extend(names.split(", ").map(str::to_owned));
+        }
+        assert_eq!(listed.len(), 103);
+        assert_eq!(
+            listed[..4],
+            ["alice", "Bob", "carol", "viewer_with_a_long_name000"]
+        );
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers list 7"));
+        assert_eq!(replies(result), vec!["There are only 6 pages of viewers."]);
+        let result = bot.handle_raw(&moderator_line("moderator", "!viewers list zero"));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("viewers_usage", &[])]
+        );
+    }
+
+    #[test]
+    fn disabling_and_enabling_commands() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("alice", "!disable roll"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_raw(&moderator_line("carkhy", "!disable !Roll"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message), ChatBotCommand::StoreCommandDisabled { name, disabled: true }]
+                                     if message == &bot.messages.text("command_disabled", &[("command", "roll")])
+                                     && name == "roll"))
+        );
+        assert!(bot.handle_raw(&chat_line("alice", "!roll")).is_none());
+        // the commands to toggle commands stay available
+        let result = bot.handle_raw(&moderator_line("carkhy", "!disable disable"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("toggle_refused", &[])));
+        let result = bot.handle_raw(&moderator_line("carkhy", "!disable nothing"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("toggle_unknown", &[("command", "nothing")])));
+        let result = bot.handle_raw(&moderator_line("carkhy", "!enable roll"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [_, ChatBotCommand::StoreCommandDisabled { name, disabled: false }]
+                                     if name == "roll"))
+        );
+        assert!(bot.handle_raw(&chat_line("alice", "!roll")).is_some());
+        // enabling it again changes nothing to store
+        let result = bot.handle_raw(&moderator_line("carkhy", "!enable roll"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(_))));
+    }
+
+    #[test]
+    fn listing_commands_with_persisted_disabled_ones() {
+        let mut bot = ChatBot::builder()
+            .disabled_commands(HashSet::from(["lurk".to_owned()]))
+            .build();
+        let result = bot.handle_raw(&chat_line("alice", "!commands"));
+        let expected = format!(
+            "{} | {}",
+            bot.messages.text("commands_none", &[]),
+            bot.messages
+                .text("commands_disabled", &[("commands", "!lurk")])
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        bot.handle_raw(&moderator_line(
+            "carkhy",
+            "!newcommand lurk Enjoy the lurk!",
+        ));
+        bot.handle_raw(&moderator_line("carkhy", "!newcommand hello Hello there!"));
+        assert!(bot.handle_raw(&chat_line("alice", "!lurk")).is_none());
+        let result = bot.handle_raw(&chat_line("alice", "!commands"));
+        let expected = format!(
+            "{} | {}",
+            bot.messages
+                .text("commands_list", &[("commands", "!hello")]),
+            bot.messages
+                .text("commands_disabled", &[("commands", "!lurk")])
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+    }
+
+    #[test]
+    fn moderators_bypassing_cooldowns() {
+        let mut bot = ChatBot::builder()
+            .cooldown("first", Duration::from_secs(30))
+            .cooldown("roll", Duration::from_secs(30))
+            .mods_bypass_cooldown("roll", false)
+            .build();
+        assert!(bot.handle_raw(&chat_line("alice", "!first")).is_some());
+        assert!(bot.handle_raw(&chat_line("alice", "!first")).is_none());
+        assert!(bot
+            .handle_raw(&moderator_line("carkhy", "!first"))
+            .is_some());
+        assert!(bot.handle_raw(&moderator_line("carkhy", "!roll")).is_some());
+        assert!(bot.handle_raw(&moderator_line("carkhy", "!roll")).is_none());
+    }
+
+    #[test]
+    fn greeting_regulars_once() {
+        let mut bot = ChatBot::builder().regulars(&["Carkhy".to_owned()]).build();
+        let result = bot.handle_raw(&chat_line("carkhy", "hi chat"));
+        let expected = bot.messages.text("greeting", &[("user", "carkhy")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::SendMessage(message)]
+                                     if message == &expected))
+        );
+        // not even after a reconnect
+        bot.handle_event(ChatBotEvent::Connected);
+        let result = bot.handle_raw(&chat_line("carkhy", "still here"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    #[test]
+    fn batching_greetings_while_the_chat_is_busy() {
+        let regulars = ["alice", "bob", "carol", "dave"].map(str::to_owned);
+        let mut bot = ChatBot::builder()
+            .regulars(&regulars)
+            .greeting_throttle(Some(ThrottleConfig {
+                threshold: 3,
+                batch_interval: Duration::from_secs(180),
+                mode: ThrottleMode::Aggregate,
+            }))
+            .build();
+        for _ in 0..3 {
+            bot.handle_raw(&chat_line("viewer", "hype"));
+        }
+        // the fourth message within a minute makes the chat busy
+        let result = flattened(bot.handle_raw(&chat_line("alice", "hi chat")));
+        assert!(
+            matches!(&result[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::TimedCallback { duration, event: ChatBotEvent::GreetingBatchDue }]
+                         if *duration == Duration::from_secs(180))
+        );
+        let result = bot.handle_raw(&chat_line("bob", "hi chat"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+        let result = bot.handle_event(ChatBotEvent::GreetingBatchDue);
+        assert_eq!(replies(result), vec!["Welcome to all 2 new folks!"]);
+        assert!(bot.handle_event(ChatBotEvent::GreetingBatchDue).is_none());
+        let result = flattened(bot.handle_raw(&chat_line("carol", "hi chat")));
+        assert!(matches!(
+            &result[..],
+            [
+                ChatBotCommand::LogTextMessage(_),
+                ChatBotCommand::TimedCallback { .. }
+            ]
+        ));
+        // a minute later the chat is calm, the batch is welcomed right away
+        let result = bot.watch_chat_rate(Instant::now() + Duration::from_secs(61));
+        assert_eq!(replies(result), vec!["Welcome, @carol!"]);
+        assert!(bot.handle_event(ChatBotEvent::GreetingBatchDue).is_none());
+        let result = bot.handle_raw(&chat_line("dave", "hi chat"));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("greeting", &[("user", "dave")])]
+        );
+        let throttle = bot.greeting_throttle.as_ref().unwrap();
+        assert_eq!(throttle.switches(), 2);
+    }
+
+    #[test]
+    fn congratulating_new_vips() {
+        let known = HashMap::from([("22222222".to_owned(), Role::Viewer)]);
+        let mut bot = ChatBot::builder().roles(known).build();
+        let line = |badges: &str| {
+            format!(
+                "@badges={};user-id=11111111 {}",
+                badges,
+                chat_line("carkhy", "hi chat")
+            )
+        };
+        // the first role of a user is only recorded
+        let result = bot.handle_raw(&line(""));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::StoreRole { user, role: Role::Viewer }]
+                                     if user == "11111111"))
+        );
+        assert!(matches!(
+            bot.handle_raw(&line("subscriber/1")),
+            Some(ChatBotCommand::LogTextMessage(_))
+        ));
+        let expected = bot
+            .messages
+            .text("role_viewer_to_vip", &[("user", "carkhy")]);
+        let result = bot.handle_raw(&line("vip/1"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::StoreRole { role: Role::Vip, .. }, ChatBotCommand::SendMessage(message)]
+                                     if *message == expected))
+        );
+        // losing the badge is not announced
+        let result = bot.handle_raw(&line(""));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::StoreRole { role: Role::Viewer, .. }]))
+        );
+    }
+
+    #[test]
+    fn only_recording_roles_in_the_first_session() {
+        let mut bot = ChatBot::builder().roles(HashMap::new()).build();
+        let line = |badges: &str| format!("@badges={} {}", badges, chat_line("carkhy", "hi chat"));
+        bot.handle_raw(&line(""));
+        let result = bot.handle_raw(&line("vip/1"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::StoreRole { user, role: Role::Vip }]
+                                     if user == "carkhy"))
+        );
+    }
+
+    #[test]
+    fn not_greeting_other_chatters() {
+        let mut bot = ChatBot::builder().regulars(&["carkhy".to_owned()]).build();
+        let result = bot.handle_raw(&chat_line("alice", "hi chat"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    fn restrict(bot: &mut ChatBot, emote_only: bool) -> Option<ChatBotCommand> {
+        bot.handle_event(ChatBotEvent::RoomState(RoomState {
+            emote_only: Some(emote_only),
+            ..RoomState::default()
+        }))
+    }
+
+    #[test]
+    fn suppressing_timers_in_restricted_modes() {
+        let mut bot = ChatBot::builder()
+            .mode_policy(ModePolicy::new(&[ProactiveMessage::Timer], true))
+            .build();
+        let id = Uuid::new_v4();
+        bot.repeating_messages.insert(
+            "follow".to_owned(),
+            RepeatingMessage {
+                name: "follow".to_owned(),
+                text: Template::parse("Follow the channel!").unwrap(),
+                interval: Duration::from_secs(600),
+                timer_id: id,
+            },
+        );
+        let timer = || ChatBotEvent::TimedMessage("follow".to_owned(), id);
+        assert!(restrict(&mut bot, true).is_none());
+        // the timer keeps running while its message is held back
+        let result = bot.handle_event(timer());
+        assert!(matches!(result, Some(ChatBotCommand::TimedCallback { .. })));
+        let result = restrict(&mut bot, false);
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Follow the channel!"));
+        assert!(restrict(&mut bot, false).is_none());
+        let result = bot.handle_event(timer());
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(_), ChatBotCommand::TimedCallback { .. }]))
+        );
+    }
+
+    #[test]
+    fn logging_messages_instead_of_sending_them_in_dry_run_mode() {
+        let mut bot = owner_bot()
+            .go_live("captaincallback", None)
+            .dry_run(true)
+            .build();
+        let id = Uuid::new_v4();
+        bot.repeating_messages.insert(
+            "follow".to_owned(),
+            RepeatingMessage {
+                name: "follow".to_owned(),
+                text: Template::parse("Follow the channel!").unwrap(),
+                interval: Duration::from_secs(600),
+                timer_id: id,
+            },
+        );
+        let result = bot.handle_event(ChatBotEvent::TimedMessage("follow".to_owned(), id));
+        // the timer keeps running, but nothing is sent
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(message), ChatBotCommand::TimedCallback { .. }]
+                                     if message == "[DRY-RUN] #captaincallback: Follow the channel!"))
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!help"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::LogTextMessage(message))
+                         if message.starts_with("[DRY-RUN] #captaincallback: "))
+        );
+
+        // owners still get to speak
+        let result = bot.handle_raw("@badges=;user-id=70346833 :carkhy!carkhy@carkhy.tmi.twitch.tv PRIVMSG #captaincallback :!say hello chat");
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "hello chat"));
+        let result = bot.handle_raw("@badges=broadcaster/1;user-id=11111111 :captaincallback!captaincallback@captaincallback.tmi.twitch.tv PRIVMSG #captaincallback :!say hello chat");
+        assert!(
+            matches!(result, Some(ChatBotCommand::LogTextMessage(message))
+                         if message.starts_with("Unauthorized !say"))
+        );
+        let result = bot.handle_event(ChatBotEvent::Status(BotStatus {
+            uptime: Duration::from_secs(60),
+            connected: true,
+            reconnects: 0,
+            queued_messages: 0,
+            memory_kb: None,
+            suppressed_duplicates: 0,
+            latency: None,
+            last_latency: None,
+            helix: None,
+        }));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.ends_with(" | dry run: messages are only logged")));
+    }
+
+    #[test]
+    fn logging_helix_requests_with_side_effects_in_dry_run_mode() {
+        let mut bot = ChatBot::builder()
+            .go_live("captaincallback", None)
+            .dry_run(true)
+            .build();
+        let result = flattened(bot.handle_raw(&moderator_line(
+            "moderator",
+            "!prediction start \"Win?\" Yes No 60",
+        )));
+        assert!(matches!(&result[0], ChatBotCommand::LogTextMessage(message)
+                         if message.starts_with("[DRY-RUN] helix: CreatePrediction")));
+        assert!(!result
+            .iter()
+            .any(|command| matches!(command, ChatBotCommand::HelixRequest(_))));
+        // the prediction was not started, so another one may be
+        assert!(matches!(bot.session.prediction, PredictionState::Idle));
+        let result = bot.handle_raw(&moderator_line("moderator", "!slow 30"));
+        assert!(flattened(result).iter().any(|command| matches!(command,
+            ChatBotCommand::LogTextMessage(message) if message.starts_with("[DRY-RUN] helix: UpdateChatSettings"))));
+        // reading from the api changes nothing
+        let result = bot.handle_raw(&chat_line("alice", "!followers"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::FollowerCount))
+        ));
+    }
+
+    #[test]
+    fn pausing_and_resuming_timers() {
+        let mut bot = ChatBot::new();
+        let id = Uuid::new_v4();
+        bot.repeating_messages.insert(
+            "follow".to_owned(),
+            RepeatingMessage {
+                name: "follow".to_owned(),
+                text: Template::parse("Follow the channel!").unwrap(),
+                interval: Duration::from_secs(600),
+                timer_id: id,
+            },
+        );
+        let timer = || ChatBotEvent::TimedMessage("follow".to_owned(), id);
+        let broadcaster =
+            |text: &str| format!("@badges=broadcaster/1 {}", chat_line("carkhy", text));
+        let result = bot.handle_raw(&moderator_line("alice", "!timers off"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_raw(&broadcaster("!timers off"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("timers_paused", &[])));
+        // the elapsed interval only schedules the next one
+        let result = bot.handle_event(timer());
+        assert!(matches!(result, Some(ChatBotCommand::TimedCallback { .. })));
+        let result = bot.handle_raw(&broadcaster("!timers on"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("timers_resumed", &[])));
+        let result = bot.handle_event(timer());
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message), ChatBotCommand::TimedCallback { .. }]
+                                     if message == "Follow the channel!"))
+        );
+    }
+
+    #[test]
+    fn suppressing_greetings_in_restricted_modes() {
+        let mut bot = ChatBot::builder().regulars(&["carkhy".to_owned()]).build();
+        bot.handle_event(ChatBotEvent::RoomState(RoomState {
+            emote_only: None,
+            subs_only: Some(true),
+            ..RoomState::default()
+        }));
+        let result = bot.handle_raw(&chat_line("carkhy", "hi chat"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+        // the regular is greeted once the chat is open again
+        bot.handle_event(ChatBotEvent::RoomState(RoomState {
+            emote_only: None,
+            subs_only: Some(false),
+            ..RoomState::default()
+        }));
+        let result = bot.handle_raw(&chat_line("carkhy", "hi again"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::LogTextMessage(_), ChatBotCommand::SendMessage(_)]))
+        );
+    }
+
+    #[test]
+    fn replying_in_restricted_modes_only_if_allowed() {
+        let mut bot = ChatBot::new();
+        restrict(&mut bot, true);
+        // the badges of the bot are not known yet
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(_))));
+        bot.handle_event(bot_user_state(HashSet::default()));
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths"],
+        ));
+        assert!(result.is_none());
+        bot.handle_event(bot_user_state(moderator_badges()));
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            HashSet::default(),
+            &["deaths"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(_))));
+        // other commands of a reply are kept
+        let reply = ChatBotCommand::MultipleCommands(vec![
+            ChatBotCommand::SendMessage("deaths: 1".to_owned()),
+            ChatBotCommand::StoreCounter {
+                name: "deaths".to_owned(),
+                value: 1,
+            },
+        ]);
+        assert!(matches!(
+            without_messages(reply),
+            Some(ChatBotCommand::StoreCounter { .. })
+        ));
+    }
+
+    #[test]
+    fn resetting_transient_state() {
+        let now = Instant::now();
+        let mut bot = ChatBot::builder()
+            .mention_reply(
+                Some(Template::parse("Hi {user}!").unwrap()),
+                Duration::from_secs(300),
+            )
+            .autoresponder(
+                Autoresponder::from_json(
+                    r#"[{"pattern": "https?://", "response": "No links!", "cooldown": 60}]"#,
+                )
+                .unwrap(),
+            )
+            .confirmations(&["counter".to_owned()])
+            .greeting_throttle(Some(ThrottleConfig {
+                threshold: 1,
+                batch_interval: Duration::from_secs(180),
+                mode: ThrottleMode::Aggregate,
+            }))
+            .build();
+        bot.handle_event(ChatBotEvent::Join("alice".to_owned()));
+        bot.handle_raw(&chat_line("alice", "hi chat"));
+        bot.handle_raw(&chat_line("carkhy", "!roll"));
+        bot.dynamic_commands.insert(
+            "lurk".to_owned(),
+            Template::parse("{user} is lurking").unwrap(),
+        );
+        bot.counters.insert("deaths".to_owned(), 3);
+        bot.cooldowns.last_ping = Some(Instant::now());
+        bot.session.prediction = PredictionState::Starting;
+        bot.session.raffle = Some(Raffle::new(RaffleRules::default()));
+        bot.session.shoutouts.request("friend", Instant::now());
+        bot.session.shoutouts.request("other", Instant::now());
+        let mention_reply = bot.mention_reply.as_mut().unwrap();
+        assert!(mention_reply.reply("alice", now).is_some());
+        assert!(mention_reply.reply("alice", now).is_none());
+        assert!(bot.autoresponder.respond("https://x", now).is_some());
+        assert!(bot.autoresponder.respond("https://x", now).is_none());
+        assert!(!bot
+            .confirmations
+            .confirm("carkhy", "counter", "deaths", now));
+        let greeting_throttle = bot.greeting_throttle.as_mut().unwrap();
+        greeting_throttle.record_message(now);
+        greeting_throttle.record_message(now);
+        greeting_throttle.greet("bob");
+        assert!(greeting_throttle.is_busy());
+
+        let result = bot.handle_event(command(
+            CommandType::BotReset,
+            "CaptainCallback",
+            badge("moderator"),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        assert!(bot.session.raffle.is_some());
+
+        let result = bot.handle_event(command(
+            CommandType::BotReset,
+            "CaptainCallback",
+            badge("broadcaster"),
+            &[],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("botreset_done", &[])));
+        assert!(bot.connection.chatters.is_empty());
+        assert!(bot.connection.messages.is_empty());
+        assert!(bot.cooldowns.last_ping.is_none());
+        // the prediction on twitch goes on
+        assert!(matches!(bot.session.prediction, PredictionState::Starting));
+        assert!(bot.session.raffle.is_none());
+        assert!(!bot.session.shoutouts.is_waiting());
+        let mention_reply = bot.mention_reply.as_mut().unwrap();
+        assert!(mention_reply.reply("alice", now).is_some());
+        assert!(bot.autoresponder.respond("https://x", now).is_some());
+        // the confirmation starts over
+        assert!(!bot
+            .confirmations
+            .confirm("carkhy", "counter", "deaths", now));
+        let greeting_throttle = bot.greeting_throttle.as_mut().unwrap();
+        assert!(!greeting_throttle.is_busy());
+        assert!(greeting_throttle.take_batch().is_empty());
+        // persistent data is kept
+        assert!(bot.dynamic_commands.contains_key("lurk"));
+        assert_eq!(bot.counters.get("deaths"), Some(&3));
+        assert!(bot.usage_stats.contains_key("carkhy"));
+    }
+
+    fn shoutout_bot() -> ChatBot {
+        ChatBot::builder()
+            .go_live("CaptainCallback", None)
+            .native_shoutout(true)
+            .build()
+    }
+
+    #[test]
+    fn queueing_native_shoutouts_during_the_cooldown() {
+        let mut bot = shoutout_bot();
+        let result = bot.handle_event(command(
+            CommandType::Shoutout,
+            "alice",
+            moderator_badges(),
+            &["@Carkhy"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(_), ChatBotCommand::HelixRequest(HelixRequest::Shoutout { login })]
+                                     if login == "carkhy"))
+        );
+        // the cooldown is shared by all moderators
+        let result = bot.handle_event(command(
+            CommandType::Shoutout,
+            "bob",
+            moderator_badges(),
+            &["friend"],
+        ));
+        match result {
+            Some(ChatBotCommand::MultipleCommands(commands)) => match &commands[..] {
+                [ChatBotCommand::SendMessage(_), ChatBotCommand::SendMessage(deferred), ChatBotCommand::TimedCallback {
+                    duration,
+                    event: ChatBotEvent::ShoutoutDue,
+                }] => {
+                    assert!(deferred.starts_with("bob,"), "{}", deferred);
+                    assert!(*duration > Duration::from_secs(110));
+                }
+                commands => panic!("unexpected commands {:?}", commands),
+            },
+            result => panic!("unexpected result {:?}", result),
+        }
+        // only the first queued shoutout wakes the queue
+        let result = bot.handle_event(command(
+            CommandType::Shoutout,
+            "alice",
+            moderator_badges(),
+            &["other"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(_), ChatBotCommand::SendMessage(_)]))
+        );
+        let result = bot.handle_event(command(
+            CommandType::Shoutout,
+            "alice",
+            moderator_badges(),
+            &["Friend"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(_)]))
+        );
+        // the timer fired early (e.g. after the clock was adjusted)
+        assert!(bot.handle_event(ChatBotEvent::ShoutoutDue).is_none());
+    }
+
+    #[test]
+    fn rejecting_self_shoutouts() {
+        let mut bot = shoutout_bot();
+        let result = bot.handle_event(command(
+            CommandType::Shoutout,
+            "alice",
+            moderator_badges(),
+            &["@captaincallback"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("shoutout_self", &[])));
+    }
+
+    #[test]
+    fn shouting_out_in_the_chat_only() {
+        let mut bot = ChatBot::builder().go_live("CaptainCallback", None).build();
+        let result = bot.handle_event(command(
+            CommandType::Shoutout,
+            "alice",
+            moderator_badges(),
+            &["@Carkhy"],
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("shoutout", &[("user", "Carkhy")])));
+    }
+
+    #[test]
+    fn reporting_failed_native_shoutouts() {
+        let mut bot = shoutout_bot();
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Shoutout {
+            login: "carkhy".to_owned(),
+            result: Err(HelixError::Status(
+                400,
+                "The broadcaster is not streaming live".to_owned(),
+            )),
+        }));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.contains("The broadcaster is not streaming live")));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Shoutout {
+            login: "carkhy".to_owned(),
+            result: Ok(()),
+        }));
+        assert!(result.is_none());
+    }
+
+    fn broadcaster_line(text: &str) -> String {
+        format!(
+            "@badges=broadcaster/1 {}",
+            chat_line("captaincallback", text)
+        )
+    }
+
+    fn raid_response(login: &str, result: Result<(), HelixError>) -> ChatBotEvent {
+        ChatBotEvent::HelixResponse(HelixResponse::Raid {
+            login: login.to_owned(),
+            result,
+        })
+    }
+
+    #[test]
+    fn counting_down_to_a_raid() {
+        let mut bot = ChatBot::builder().go_live("CaptainCallback", None).build();
+        let result = bot.handle_raw(&moderator_line("alice", "!raid friendchannel"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+        let result = bot.handle_raw(&broadcaster_line("!raid @captaincallback"));
+        assert_eq!(replies(result), vec![bot.messages.text("raid_self", &[])]);
+        let result = bot.handle_raw(&broadcaster_line("!raid @FriendChannel"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::StartRaid { login }))
+                         if login == "friendchannel")
+        );
+        let result = bot.handle_raw(&broadcaster_line("!raid other"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.contains("friendchannel")));
+
+        let result = bot.handle_event(raid_response("friendchannel", Ok(())));
+        let timers = match result {
+            Some(ChatBotCommand::MultipleCommands(commands)) => commands
+                .into_iter()
+                .filter_map(|command| match command {
+                    ChatBotCommand::TimedCallback { duration, event } => Some((duration, event)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(
+            timers
+                .iter()
+                .map(|(duration, _)| duration.as_secs())
+                .collect::<Vec<_>>(),
+            vec![30, 60, 90]
+        );
+        // the timers fire in order of their durations
+        let messages = timers
+            .into_iter()
+            .flat_map(|(_, event)| replies(bot.handle_event(event)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "Raiding friendchannel in 60s…",
+                "Raiding friendchannel in 30s…",
+                "GO! Off to https://twitch.tv/friendchannel !",
+            ]
+        );
+        assert!(bot.connection.raid.is_none());
+    }
+
+    #[test]
+    fn canceling_the_raid_countdown() {
+        let mut bot = ChatBot::builder().go_live("CaptainCallback", None).build();
+        bot.handle_raw(&broadcaster_line("!raid friendchannel"));
+        let id = bot.connection.raid.as_ref().unwrap().id;
+        bot.handle_event(raid_response("friendchannel", Ok(())));
+        let result = bot.handle_raw(&broadcaster_line("!unraid"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::CancelRaid))
+        ));
+        assert!(bot
+            .handle_event(ChatBotEvent::RaidCountdown(id, 60))
+            .is_none());
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::RaidCanceled(
+            Ok(()),
+        )));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("raid_canceled", &[])]
+        );
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::RaidCanceled(
+            Err(HelixError::Status(404, "no pending raid".to_owned())),
+        )));
+        assert_eq!(replies(result), vec![bot.messages.text("raid_none", &[])]);
+
+        // a reconnect ends the countdown
+        bot.handle_raw(&broadcaster_line("!raid friendchannel"));
+        let id = bot.connection.raid.as_ref().unwrap().id;
+        bot.handle_event(ChatBotEvent::Connected);
+        assert!(bot
+            .handle_event(ChatBotEvent::RaidCountdown(id, 30))
+            .is_none());
+        assert!(bot
+            .handle_event(raid_response("friendchannel", Ok(())))
+            .is_none());
+    }
+
+    #[test]
+    fn telling_why_a_raid_failed() {
+        let mut bot = ChatBot::builder().go_live("CaptainCallback", None).build();
+        let mut refused = |message: &str| {
+            bot.handle_raw(&broadcaster_line("!raid friendchannel"));
+            replies(bot.handle_event(raid_response(
+                "friendchannel",
+                Err(HelixError::Status(400, message.to_owned())),
+            )))
+        };
+        assert_eq!(
+            refused("The targeted channel doesn't allow raids from this broadcaster."),
+            vec!["friendchannel does not allow raids from this channel."]
+        );
+        assert_eq!(
+            refused("The targeted channel is offline."),
+            vec!["friendchannel is offline and cannot be raided."]
+        );
+        // a refused raid can be retried right away
+        assert!(bot.connection.raid.is_none());
+    }
+
+    fn audit_entries(bot: &mut ChatBot) -> Vec<AuditEntry> {
+        flattened(bot.take_audit_entries())
+            .into_iter()
+            .map(|command| match command {
+                ChatBotCommand::Audit(entry) => entry,
+                command => panic!("{:?} is no audit entry", command),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn auditing_privileged_commands() {
+        let mut bot = ChatBot::builder().go_live("CaptainCallback", None).build();
+        let moderator = |text: &str| {
+            format!(
+                "@badges=moderator/1;user-id=1337 {}",
+                chat_line("Moderator", text)
+            )
+        };
+        // a successful chat mode change is audited when twitch confirmed it
+        bot.handle_raw(&moderator("!slow 45"));
+        assert!(audit_entries(&mut bot).is_empty());
+        bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::ChatSettings(
+            Ok(()),
+        )));
+        let entries = audit_entries(&mut bot);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (
+                entries[0].user.as_str(),
+                entries[0].user_id.as_deref(),
+                entries[0].command.as_str(),
+                entries[0].args.as_str(),
+                &entries[0].outcome
+            ),
+            (
+                "moderator",
+                Some("1337"),
+                "slow",
+                "45",
+                &AuditOutcome::Success
+            )
+        );
+
+        // so is a failed one, with the error of the api
+        bot.handle_raw(&moderator("!subonly on"));
+        bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::ChatSettings(
+            Err(HelixError::Status(403, "Missing scope".to_owned())),
+        )));
+        let entries = audit_entries(&mut bot);
+        assert_eq!(
+            entries[0].outcome,
+            AuditOutcome::Failed("403 Missing scope".to_owned())
+        );
+
+        bot.handle_raw(&broadcaster_line("!raid @FriendChannel"));
+        bot.handle_event(raid_response(
+            "friendchannel",
+            Err(HelixError::Unavailable("timed out".to_owned())),
+        ));
+        let entries = audit_entries(&mut bot);
+        assert_eq!(entries[0].target.as_deref(), Some("friendchannel"));
+        assert_eq!(
+            entries[0].outcome,
+            AuditOutcome::Failed("timed out".to_owned())
+        );
+
+        // denied attempts are audited, reading a counter is not
+        bot.handle_raw(&chat_line("alice", "!slow 30"));
+        bot.handle_raw(&chat_line("alice", "!counter deaths"));
+        bot.handle_raw(&moderator("!counter deaths +1"));
+        let entries = audit_entries(&mut bot);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].outcome, AuditOutcome::Denied);
+        assert_eq!(entries[1].target.as_deref(), Some("deaths"));
+
+        // the chat mode updates of the raid protection finish no entry
+        bot.audit.await_chat_settings();
+        bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::ChatSettings(
+            Ok(()),
+        )));
+        assert!(audit_entries(&mut bot).is_empty());
+
+        let result = bot.handle_raw(&broadcaster_line("!audit !slow"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendWhisper { user, text })
+                         if user == "captaincallback"
+                         && text.starts_with("Audit of !slow: ")
+                         && text.contains("alice !slow 30: denied | ")
+                         && text.contains("moderator !slow 45: success"))
+        );
+        let result = bot.handle_raw(&broadcaster_line("!audit Moderator 1"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendWhisper { text, .. })
+                         if text.contains("!counter deaths +1: success")
+                         && !text.contains("subonly"))
+        );
+        let result = bot.handle_raw(&broadcaster_line("!audit bob"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendWhisper { text, .. })
+                         if text == "There are no audit entries of bob.")
+        );
+        assert_eq!(
+            replies(bot.handle_raw(&moderator("!audit alice"))),
+            vec![bot.messages.text("denied", &[])]
+        );
+        // the denied !audit itself is audited
+        assert_eq!(audit_entries(&mut bot)[0].command, "audit");
+    }
+
+    fn emotes_response(sub_emotes: &[&str]) -> ChatBotEvent {
+        ChatBotEvent::HelixResponse(HelixResponse::Emotes(Ok(Emotes {
+            channel: sub_emotes
+                .iter()
+                .map(|code| ChannelEmote {
+                    code: code.to_string(),
+                    emote_type: "subscriptions".to_owned(),
+                })
+                .collect(),
+            global: vec!["Kappa".to_owned()],
+        })))
+    }
+
+    #[test]
+    fn sending_emotes_instead_of_replies_in_emote_only_mode() {
+        let mut bot = ChatBot::builder()
+            .mention_user(true)
+            .emote_only_fallback(Some("captai7Hype Kappa".to_owned()))
+            .build();
+        // the bot is no moderator, so it may only send emotes
+        bot.handle_event(bot_user_state(HashSet::new()));
+        restrict(&mut bot, true);
+        let result = bot.handle_raw(&chat_line("alice", "!help"));
+        assert_eq!(replies(result), vec!["captai7Hype Kappa"]);
+        let result = bot.handle_event(command(
+            CommandType::Counter,
+            "CaptainCallback",
+            moderator_badges(),
+            &["deaths", "+1"],
+        ));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::SendMessage(message),
+                             ChatBotCommand::StoreCounter { .. },
+                         ] if message == "captai7Hype Kappa"))
+        );
+        restrict(&mut bot, false);
+        let result = bot.handle_raw(&chat_line("alice", "!help"));
+        assert_eq!(
+            replies(result),
+            vec![format!("@alice {}", bot.messages.text("help", &[]))]
+        );
+
+        // without a fallback a bot which is no moderator keeps quiet
+        let mut bot = ChatBot::new();
+        bot.handle_event(bot_user_state(HashSet::new()));
+        restrict(&mut bot, true);
+        assert!(bot.handle_raw(&chat_line("alice", "!help")).is_none());
+    }
+
+    #[test]
+    fn listing_sub_emotes_across_messages() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("alice", "!emotes"));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("emotes_unavailable", &[])]
+        );
+        let codes: Vec<String> = (0..60).map(|n| format!("captai7Emote{:02}", n)).collect();
+        let codes: Vec<&str> = codes.iter().map(String::as_str).collect();
+        assert!(bot.handle_event(emotes_response(&codes)).is_none());
+        let messages = replies(bot.handle_raw(&chat_line("alice", "!emotes")));
+        assert_eq!(messages.len(), 2);
+        assert!(messages
+            .iter()
+            .all(|message| message.chars().count() <= MAX_MESSAGE_LENGTH));
+        assert!(messages[0].starts_with("Sub emotes: captai7Emote00 "));
+        assert!(messages[1].ends_with(" captai7Emote59"));
+        let listed = messages.join(" ");
+        assert!(codes.iter().all(|code| listed.contains(code)));
+
+        bot.handle_event(emotes_response(&[]));
+        let result = bot.handle_raw(&chat_line("alice", "!emotes"));
+        assert_eq!(replies(result), vec![bot.messages.text("emotes_none", &[])]);
+    }
+
+    #[test]
+    fn refreshing_the_emotes() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_event(ChatBotEvent::RefreshEmotes);
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::Emotes))
+        ));
+        // the emotes fetched at the start are not announced
+        assert!(bot
+            .handle_event(emotes_response(&["captai7Hype"]))
+            .is_none());
+        let result = bot.handle_raw(&chat_line("alice", "!refreshemotes"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+        let result = bot.handle_raw(&moderator_line("alice", "!refreshemotes"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::Emotes))
+        ));
+        let result = bot.handle_event(emotes_response(&["captai7Hype", "captai7Love"]));
+        assert_eq!(
+            replies(result),
+            vec!["Emotes refreshed: 2 channel emotes and 1 global emotes."]
+        );
+        assert_eq!(
+            bot.session.emote_set.as_ref().unwrap().sub_emotes(),
+            vec!["captai7Hype", "captai7Love"]
+        );
+    }
+
+    #[test]
+    fn listing_trending_phrases() {
+        let mut bot = ChatBot::new();
+        bot.bot_name = "botanist".to_owned();
+        bot.handle_event(emotes_response(&["captai7Hype"]));
+        let result = bot.handle_raw(&moderator_line("alice", "!trending"));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("trending_none", &[("minutes", "15")])]
+        );
+        for (user, text) in [
+            ("bob", "that was a clutch play"),
+            ("carol", "Clutch play!!"),
+            ("dave", "CLUTCH PLAY"),
+            ("bob", "captai7Hype captai7Hype"),
+            ("carol", "captai7Hype captai7Hype"),
+            ("dave", "!trending clutch play"),
+            ("botanist", "clutch play"),
+        ] {
+            bot.handle_raw(&chat_line(user, text));
+        }
+        let result = bot.handle_raw(&chat_line("alice", "!trending"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+        let result = bot.handle_raw(&moderator_line("alice", "!trending"));
+        assert_eq!(
+            replies(result),
+            vec!["Trending in the last 15 minutes: \"clutch play\" (3)"]
+        );
+    }
+
+    fn category(game_id: &str, game: &str) -> ChatBotEvent {
+        ChatBotEvent::HelixResponse(HelixResponse::StreamInfo(Ok(StreamInfo {
+            live: true,
+            title: "Blind run".to_owned(),
+            game: game.to_owned(),
+            game_id: game_id.to_owned(),
+            thumbnail_url: None,
+            stream_id: None,
+        })))
+    }
+
+    #[test]
+    fn counting_deaths_per_game() {
+        let mut bot = ChatBot::builder()
+            .game_deaths(HashMap::from([
+                ("512953".to_owned(), "41 Elden Ring".parse().unwrap()),
+                ("490100".to_owned(), "125 Lies of P".parse().unwrap()),
+            ]))
+            .build();
+        // the category is looked up first
+        let result = bot.handle_raw(&moderator_line("alice", "!deaths +1"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::StreamInfo))
+        ));
+        assert!(bot.handle_raw(&chat_line("bob", "!deaths")).is_none());
+        let result = bot.handle_event(category("512953", "Elden Ring"));
+        let expected = "42 deaths in Elden Ring (total across games: 167)";
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::MultipleCommands(changed),
+                             ChatBotCommand::SendMessage(shown),
+                         ] if shown == expected && matches!(&changed[..], [
+                             ChatBotCommand::SendMessage(message),
+                             ChatBotCommand::StoreGameDeaths { .. },
+                         ] if message == expected)))
+        );
+
+        // the streamer switches to a new game
+        assert!(bot.handle_event(category("1234", "Sekiro")).is_none());
+        let result = bot.handle_raw(&chat_line("bob", "!deaths"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "0 deaths in Sekiro (total across games: 167)"));
+        let result = bot.handle_raw(&moderator_line("alice", "!deaths set 2"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [
+                             ChatBotCommand::SendMessage(message),
+                             ChatBotCommand::StoreGameDeaths { game_id, game },
+                         ] if message == "2 deaths in Sekiro (total across games: 169)"
+                              && game_id == "1234" && game.deaths == 2))
+        );
+        let result = bot.handle_raw(&chat_line("bob", "!deaths game elden ring"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "42 deaths in Elden Ring"));
+        let result = bot.handle_raw(&chat_line("bob", "!deaths +1"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+
+        // just chatting has no game
+        assert!(bot.handle_event(category("", "")).is_none());
+        let result = bot.handle_raw(&chat_line("bob", "!deaths"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("deaths_no_game", &[])));
+    }
+
+    #[test]
+    fn linking_the_latest_vod() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("alice", "!vod"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::LatestVod))
+        ));
+        let title = "Speedrunning every level ".repeat(30);
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::LatestVod(Ok(
+            Some(Video {
+                title: title.to_owned(),
+                duration: Duration::from_secs(3 * 3600 + 8 * 60 + 33),
+                url: "https://www.twitch.tv/videos/335921245".to_owned(),
+            }),
+        ))));
+        let message = replies(result).pop().unwrap();
+        assert_eq!(message.chars().count(), MAX_MESSAGE_LENGTH);
+        assert!(message.starts_with("Latest VOD: Speedrunning every level"));
+        assert!(message.ends_with("… (3h 8m) https://www.twitch.tv/videos/335921245"));
+        // answered from the cache
+        assert_eq!(
+            replies(bot.handle_raw(&chat_line("bob", "!vod"))),
+            vec![message]
+        );
+
+        bot.session.latest_vod.as_mut().unwrap().fetched_at -= VIDEO_CACHE_DURATION;
+        assert!(matches!(
+            bot.handle_raw(&chat_line("bob", "!vod")),
+            Some(ChatBotCommand::HelixRequest(HelixRequest::LatestVod))
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::LatestVod(Ok(
+            None,
+        ))));
+        assert_eq!(replies(result), vec![bot.messages.text("vod_none", &[])]);
+    }
+
+    #[test]
+    fn linking_clips_of_a_time_span() {
+        let mut bot = ChatBot::new();
+        let clip = Clip {
+            title: "the raid".to_owned(),
+            creator: "Viewer".to_owned(),
+            views: 1234,
+            created_at: Utc::now(),
+            url: "https://clips.twitch.tv/SpicyTiredClipTwitchRaid".to_owned(),
+        };
+        assert!(matches!(
+            bot.handle_raw(&chat_line("alice", "!lastclip")),
+            Some(ChatBotCommand::HelixRequest(HelixRequest::Clip {
+                window: ClipWindow::Latest
+            }))
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Clip {
+            window: ClipWindow::Latest,
+            result: Ok(Some(clip.clone())),
+        }));
+        assert_eq!(
+            replies(result),
+            vec!["Latest clip: the raid by Viewer (1,234 views) https://clips.twitch.tv/SpicyTiredClipTwitchRaid"]
+        );
+        // the clips of each time span are cached on their own
+        assert!(matches!(
+            bot.handle_raw(&chat_line("alice", "!lastclip Week")),
+            Some(ChatBotCommand::HelixRequest(HelixRequest::Clip {
+                window: ClipWindow::Week
+            }))
+        ));
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Clip {
+            window: ClipWindow::Week,
+            result: Ok(Some(clip)),
+        }));
+        assert_eq!(
+            replies(result),
+            vec!["Top clip of the week: the raid by Viewer (1,234 views) https://clips.twitch.tv/SpicyTiredClipTwitchRaid"]
+        );
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::Clip {
+            window: ClipWindow::Day,
+            result: Ok(None),
+        }));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("lastclip_none", &[])]
+        );
+        assert_eq!(
+            replies(bot.handle_raw(&chat_line("alice", "!lastclip day"))),
+            vec![bot.messages.text("lastclip_none", &[])]
+        );
+        for usage in ["!lastclip year", "!lastclip week day"] {
+            assert_eq!(
+                replies(bot.handle_raw(&chat_line("alice", usage))),
+                vec![bot.messages.text("lastclip_usage", &[])]
+            );
+        }
+    }
+
+    #[test]
+    fn showing_the_config_to_the_broadcaster() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&broadcaster_line("!config"));
+        assert_eq!(
+            replies(result),
+            vec![bot.messages.text("config_unavailable", &[])]
+        );
+        let mut bot = ChatBot::builder()
+            .config_summary("channel=captaincallback, prefix=!".to_owned())
+            .disabled_commands(HashSet::from(["slap".to_owned()]))
+            .build();
+        let result = bot.handle_raw(&moderator_line("alice", "!config"));
+        assert_eq!(replies(result), vec![bot.messages.text("denied", &[])]);
+        let result = bot.handle_raw(&broadcaster_line("!config"));
+        assert_eq!(
+            replies(result),
+            vec!["Config: channel=captaincallback, prefix=! | Disabled: !slap"]
+        );
+    }
+
+    fn anywhere_bot(commands_anywhere: Option<Permission>) -> ChatBot {
+        let mut bot = ChatBot::builder()
+            .commands_anywhere(commands_anywhere)
+            .build();
+        bot.dynamic_commands.insert(
+            "clip".to_owned(),
+            Template::parse("{user} wants a clip of {args:1}").unwrap(),
+        );
+        bot
+    }
+
+    fn replies(result: Option<ChatBotCommand>) -> Vec<String> {
+        match result {
+            Some(ChatBotCommand::MultipleCommands(commands)) => commands
+                .into_iter()
+                .filter_map(|command| match command {
+                    ChatBotCommand::SendMessage(message) => Some(message),
+                    _ => None,
+                })
+                .collect(),
+            Some(ChatBotCommand::SendMessage(message)) => vec![message],
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn using_commands_inside_messages() {
+        let mut bot = anywhere_bot(Some(Permission::Everyone));
+        let result = bot.handle_raw(&chat_line("alice", "can someone do !clip, that play"));
+        assert_eq!(replies(result), vec!["alice wants a clip of that"]);
+        // unknown commands are skipped, the first known one is used
+        let result = bot.handle_raw(&chat_line("alice", "wow!clip !!! !lol !clip"));
+        assert_eq!(replies(result), vec!["alice wants a clip of "]);
+        assert!(bot.usage_stats["alice"].commands.contains_key("clip"));
+    }
+
+    #[test]
+    fn ignoring_commands_inside_messages_by_default() {
+        let mut bot = anywhere_bot(None);
+        let result = bot.handle_raw(&chat_line("alice", "can someone do !clip please"));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    #[test]
+    fn ignoring_commands_inside_links() {
+        let mut bot = anywhere_bot(Some(Permission::Everyone));
+        let result = bot.handle_raw(&chat_line(
+            "alice",
+            "look at example.com/!clip or !clip.tv/x",
+        ));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+    }
+
+    #[test]
+    fn ignoring_commands_with_side_effects_inside_messages() {
+        let mut bot = anywhere_bot(Some(Permission::Everyone));
+        let message = TextMessage {
+            text: "please don't !newcommand hi there".to_owned(),
+            user: UserInfo {
                 name: "CaptainCallback".to_owned(),
-                badges: HashSet::from([Badge {
-                    name: "moderator".to_owned(),
-                    level: 1,
-                }]),
+                id: None,
+                badges: moderator_badges(),
             },
-            kind: CommandType::NewCommand,
-            options: vec!["test2".to_string(), "testing2".to_string()],
+            room_id: None,
+            id: None,
+            first_message: false,
+        };
+        let result = bot.handle_event(ChatBotEvent::TextMessage(message));
+        assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+        assert!(!bot.dynamic_commands.contains_key("hi"));
+        // the first known command decides, later ones are not used instead
+        let result = bot.handle_raw(&chat_line("alice", "no !so for !clip"));
+        assert!(replies(result).is_empty());
+    }
+
+    fn info_bot() -> ChatBot {
+        let info = InfoConfig::from_json(&format!(
+            r#"[{{"name": "schedule", "text": "Mon, Wed, Fri at 7pm"}}, {{"name": "game"}}, {{"name": "rules", "text": "{}"}}]"#,
+            "Be nice! ".repeat(60).trim()
+        ))
+        .unwrap();
+        ChatBot::builder().info(info).build()
+    }
+
+    #[test]
+    fn addressing_info_sections() {
+        let mut bot = info_bot();
+        let result = bot.handle_raw(&chat_line("carkhy", "!info schedule"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Mon, Wed, Fri at 7pm"));
+        // unknown sections are skipped silently
+        assert!(bot
+            .handle_raw(&chat_line("carkhy", "!info discord"))
+            .is_none());
+
+        let result = bot.handle_raw(&chat_line("carkhy", "!info game"));
+        assert!(matches!(
+            result,
+            Some(ChatBotCommand::HelixRequest(HelixRequest::StreamInfo))
+        ));
+        // the go live check is not answered by the stream info of the info command
+        let result = bot.handle_event(stream_info(true));
+        let expected = bot.messages.text(
+            "info_game",
+            &[
+                ("game", "Software and Game Development"),
+                ("title", "Writing a chat bot"),
+            ],
+        );
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == expected));
+    }
+
+    #[test]
+    fn defining_terms() {
+        let glossary =
+            Glossary::from_json(r#"{"trait object": "A value of a dyn Trait."}"#).unwrap();
+        let mut bot = ChatBot::builder().glossary(glossary).build();
+        let result = bot.handle_raw(&chat_line("carkhy", "!define Trait Object"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Trait Object: A value of a dyn Trait."));
+        let result = bot.handle_raw(&chat_line("carkhy", "!define lifetime"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "No definition for 'lifetime'."));
+        let result = bot.handle_raw(&chat_line("carkhy", "!define"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == "Usage: !define <term>"));
+    }
+
+    #[test]
+    fn splitting_long_info() {
+        let mut bot = info_bot();
+        assert!(bot.handle_raw(&chat_line("carkhy", "!info")).is_some());
+        let result = bot.handle_event(ChatBotEvent::HelixResponse(HelixResponse::StreamInfo(Err(
+            HelixError::Unavailable("offline".to_owned()),
+        ))));
+        // without the stream info, the game is left out
+        let rules = "Be nice! ".repeat(60);
+        match result {
+            Some(ChatBotCommand::MultipleCommands(commands)) => {
+                let messages: Vec<&str> = commands
+                    .iter()
+                    .map(|command| match command {
+                        ChatBotCommand::SendMessage(message) => message.as_str(),
+                        command => panic!("unexpected command {:?}", command),
+                    })
+                    .collect();
+                assert!(messages.iter().all(|message| message.len() <= 500));
+                assert_eq!(messages[0], "Mon, Wed, Fri at 7pm");
+                assert_eq!(messages[1..].join(" "), rules.trim());
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn telling_the_account_age() {
+        let mut bot = ChatBot::new();
+        let result = bot.handle_raw(&chat_line("carkhy", "!accountage"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::HelixRequest(HelixRequest::UserAge { login }))
+                         if login == "carkhy")
+        );
+        // the age is requested only once
+        assert!(bot
+            .handle_raw(&chat_line("carkhy", "!accountage"))
+            .is_none());
+        let result = bot.handle_event(user_age(400, 5));
+        assert!(
+            matches!(result, Some(ChatBotCommand::MultipleCommands(commands))
+                         if matches!(&commands[..], [ChatBotCommand::SendMessage(message)]
+                                     if message.starts_with("The account of @carkhy was created 1 year")))
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!accountage @carkhy"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_raw(&moderator_line("alice", "!accountage @Carkhy"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message.starts_with("The account of @carkhy")));
+    }
+
+    #[test]
+    fn formatting_account_ages() {
+        let bot = ChatBot::new();
+        let at = |date: &str| {
+            DateTime::parse_from_rfc3339(date)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let now = at("2024-05-10T12:00:00Z");
+        let age = |created_at: &str| bot.account_age_message("carkhy", at(created_at), now);
+        assert_eq!(
+            age("2018-03-01T00:00:00Z"),
+            "The account of @carkhy was created 6 years and 2 months ago."
+        );
+        assert_eq!(
+            age("2022-05-10T00:00:00Z"),
+            "The account of @carkhy was created 2 years ago."
+        );
+        assert_eq!(
+            age("2023-05-11T00:00:00Z"),
+            "The account of @carkhy was created 11 months ago."
+        );
+        assert_eq!(
+            age("2024-05-09T00:00:00Z"),
+            "The account of @carkhy was created 1 day ago."
+        );
+    }
+
+    #[test]
+    fn telling_name_colors() {
+        let mut bot = ChatBot::new();
+        let line =
+            |color: &str, text: &str| format!("@color={} {}", color, chat_line("carkhy", text));
+        let result = bot.handle_raw(&line("#1E90FF", "!namecolor"));
+        let expected = bot.messages.text(
+            "namecolor",
+            &[
+                ("user", "carkhy"),
+                ("color", "#1E90FF"),
+                ("name", "DodgerBlue"),
+            ],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        // twitch leaves the tag empty until a color is assigned
+        bot.handle_raw(&line("", "hi chat"));
+        let result = bot.handle_raw(&moderator_line("alice", "!namecolor @Carkhy"));
+        let expected = bot.messages.text("namecolor_none", &[("user", "carkhy")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+        let result = bot.handle_raw(&chat_line("alice", "!namecolor @carkhy"));
+        assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
+                         if message == bot.messages.text("denied", &[])));
+        let result = bot.handle_raw(&moderator_line("alice", "!namecolor @bob"));
+        let expected = bot.messages.text("namecolor_unknown", &[("user", "bob")]);
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendMessage(message)) if message == expected)
+        );
+    }
+
+    // a reply of the bot with the nested commands of its parts flattened
+    fn flattened(result: Option<ChatBotCommand>) -> Vec<ChatBotCommand> {
+        match result {
+            Some(ChatBotCommand::MultipleCommands(commands)) => commands
+                .into_iter()
+                .flat_map(|command| flattened(Some(command)))
+                .collect(),
+            Some(command) => vec![command],
+            None => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn protecting_the_chat_against_a_hate_raid() {
+        let mut bot = ChatBot::builder()
+            .regulars(&["carkhy".to_owned()])
+            .commands_anywhere(Some(Permission::Everyone))
+            .raid_protection(Some(ProtectionConfig {
+                max_messages: 3,
+                window: Duration::from_secs(10),
+                duration: Duration::from_secs(600),
+                modes: ChatSettings {
+                    follower_mode: Some(true),
+                    slow_mode: Some(true),
+                    ..ChatSettings::default()
+                },
+            }))
+            .build();
+        bot.handle_event(ChatBotEvent::RoomState(RoomState {
+            slow: Some(0),
+            followers_only: Some(-1),
+            ..RoomState::default()
         }));
+        let first_message = |user: &str, id: &str, text: &str| {
+            format!("@first-msg=1;id={} {}", id, chat_line(user, text))
+        };
+        for (user, id) in [("hater1", "a1"), ("hater2", "a2"), ("hater3", "a3")] {
+            let result = bot.handle_raw(&first_message(user, id, "this streamer is bad lol"));
+            assert!(matches!(result, Some(ChatBotCommand::LogTextMessage(_))));
+        }
+        // messages of known chatters are not first messages
+        bot.handle_raw(&chat_line("alice", "this streamer is bad lol"));
+        assert!(!bot.is_protected());
+
+        let result =
+            flattened(bot.handle_raw(&first_message("hater4", "a4", "THIS streamer is bad lol!!")));
+        assert!(bot.is_protected());
+        for id in ["a1", "a2", "a3", "a4"] {
+            assert!(result.iter().any(|command| matches!(command, ChatBotCommand::LogTextMessage(message)
+                                                          if message.ends_with(&format!("(id {})", id)))));
+        }
+        assert!(result.iter().any(|command| matches!(command, ChatBotCommand::NotifyOwners(text)
+                                                      if text.contains("hater1, hater2, hater3, hater4"))));
+        let started = bot
+            .messages
+            .text("protection_started", &[("minutes", "10")]);
+        assert!(result.iter().any(
+            |command| matches!(command, ChatBotCommand::SendMessage(message) if *message == started)
+        ));
+        assert!(result.iter().any(|command| matches!(
+            command,
+            ChatBotCommand::HelixRequest(HelixRequest::UpdateChatSettings(ChatSettings {
+                follower_mode: Some(true),
+                slow_mode: Some(true),
+                ..
+            }))
+        )));
+        let id = result
+            .iter()
+            .find_map(|command| match command {
+                ChatBotCommand::TimedCallback {
+                    duration,
+                    event: ChatBotEvent::ProtectionExpired(id),
+                } if *duration == Duration::from_secs(600) => Some(*id),
+                _ => None,
+            })
+            .unwrap();
+
+        // while protected, the bot neither greets nor is triggered
+        let result = flattened(bot.handle_raw(&chat_line("carkhy", "hi !slap alice")));
+        assert!(matches!(&result[..], [ChatBotCommand::LogTextMessage(_)]));
+        let result =
+            flattened(bot.handle_raw(&first_message("hater5", "a5", "this streamer is bad")));
+        assert!(result.iter().any(
+            |command| matches!(command, ChatBotCommand::LogTextMessage(message)
+                                                      if message.ends_with("(id a5)"))
+        ));
+
+        // the protection expires and the modes are turned off again
+        let result = flattened(bot.handle_event(ChatBotEvent::ProtectionExpired(id)));
+        assert!(!bot.is_protected());
+        assert!(result.iter().any(|command| matches!(command, ChatBotCommand::SendMessage(message)
+                                                      if *message == bot.messages.text("protection_ended", &[]))));
+        assert!(result.iter().any(|command| matches!(
+            command,
+            ChatBotCommand::HelixRequest(HelixRequest::UpdateChatSettings(ChatSettings {
+                follower_mode: Some(false),
+                slow_mode: Some(false),
+                ..
+            }))
+        )));
+        let result = bot.handle_raw(&chat_line("carkhy", "hi again"));
+        assert_eq!(replies(result).len(), 1);
+    }
+
+    #[test]
+    fn lifting_the_protection_with_allclear() {
+        let mut bot = ChatBot::builder()
+            .raid_protection(Some(ProtectionConfig {
+                max_messages: 1,
+                window: Duration::from_secs(10),
+                duration: Duration::from_secs(60),
+                modes: ChatSettings::default(),
+            }))
+            .build();
+        let result = bot.handle_raw(&moderator_line("alice", "!allclear"));
+        assert_eq!(
+            replies(result),
+            [bot.messages.text("allclear_inactive", &[])]
+        );
+        for user in ["hater1", "hater2"] {
+            bot.handle_raw(&format!("@first-msg=1 {}", chat_line(user, "spam spam")));
+        }
+        assert!(bot.is_protected());
+        let result = bot.handle_raw(&chat_line("viewer", "!allclear"));
+        assert_eq!(replies(result), [bot.messages.text("denied", &[])]);
+        // no chat modes were turned on, so none are turned off
+        let result = bot.handle_raw(&moderator_line("alice", "!allclear"));
         assert!(matches!(result, Some(ChatBotCommand::SendMessage(message))
-                         if message != DENIED_MESSAGE));
-        assert!(bot.dynamic_commands.contains_key("test2"));
+                         if message == bot.messages.text("protection_ended", &[])));
+        assert!(!bot.is_protected());
+    }
+
+    #[test]
+    fn showing_the_context_of_a_user() {
+        let mut bot = ChatBot::builder()
+            .go_live("channel123", None)
+            .history_size(3)
+            .build();
+        // the lines as the connector wraps them, sent at 21:55:56
+        let receive = |bot: &mut ChatBot, line: &str| match ReceiveEvent::parse_from_message(line) {
+            Some(ReceiveEvent::ChatBotEvent(content)) => bot.handle_event(Event {
+                sent_at: "2022-01-20T21:55:56Z".parse().unwrap(),
+                channel: Some("channel123".to_owned()),
+                raw: line.to_owned(),
+                content,
+            }),
+            _ => None,
+        };
+        let message =
+            |id: &str, user: &str, text: &str| format!("@id={} {}", id, chat_line(user, text));
+        receive(&mut bot, &message("1", "spammer", "first"));
+        receive(&mut bot, &message("2", "alice", "hello"));
+        receive(&mut bot, &message("3", "spammer", "buy followers"));
+        receive(&mut bot, &message("4", "spammer", "cheap viewers"));
+        receive(
+            &mut bot,
+            "@login=spammer;target-msg-id=4 :tmi.twitch.tv CLEARMSG #channel123 :cheap viewers",
+        );
+
+        let result = bot.handle_raw(&chat_line("alice", "!context spammer"));
+        assert_eq!(replies(result), [bot.messages.text("denied", &[])]);
+        let result = bot.handle_raw(&moderator_line("carkhy", "!context @Spammer"));
+        // the first message was evicted, the last one is marked as deleted
+        let expected = bot.messages.text(
+            "context",
+            &[
+                ("user", "Spammer"),
+                (
+                    "messages",
+                    "[21:55:56 UTC] buy followers | [21:55:56 UTC] cheap viewers (deleted)",
+                ),
+            ],
+        );
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendWhisper { user, text })
+                         if user == "carkhy" && text == expected)
+        );
+
+        receive(
+            &mut bot,
+            "@ban-duration=600 :tmi.twitch.tv CLEARCHAT #channel123 :alice",
+        );
+        let result = bot.handle_raw(&moderator_line("carkhy", "!context alice"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendWhisper { text, .. })
+                         if text.ends_with("hello (deleted)"))
+        );
+        let result = bot.handle_raw(&moderator_line("carkhy", "!context nobody"));
+        assert!(
+            matches!(result, Some(ChatBotCommand::SendWhisper { text, .. })
+                         if text == bot.messages.text("context_none", &[("user", "nobody")]))
+        );
+        let result = bot.handle_raw(&moderator_line("carkhy", "!context"));
+        assert_eq!(replies(result), [bot.messages.text("context_usage", &[])]);
     }
 }
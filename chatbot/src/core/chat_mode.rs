@@ -0,0 +1,145 @@
+use crate::connect::{ChatSettings, RoomState};
+use std::ops::RangeInclusive;
+
+// the waiting times and follow durations Twitch accepts
+const SLOW_MODE_SECONDS: RangeInclusive<u64> = 3..=120;
+const FOLLOWER_MODE_MINUTES: RangeInclusive<u64> = 0..=129_600;
+// `!slow` without a time waits as long as Twitch's own `/slow`
+pub const DEFAULT_SLOW_SECONDS: u32 = 30;
+
+const UNITS: [(char, u64); 5] = [
+    ('w', 7 * 24 * 60 * 60),
+    ('d', 24 * 60 * 60),
+    ('h', 60 * 60),
+    ('m', 60),
+    ('s', 1),
+];
+
+/// Parses a duration like `90`, `10m` or `1h30m` into seconds, plain numbers count the given unit.
+fn parse_duration(text: &str, unit: u64) -> Option<u64> {
+    if let Ok(number) = text.parse::<u64>() {
+        return number.checked_mul(unit);
+    }
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for char in text.to_lowercase().chars() {
+        if char.is_ascii_digit() {
+            number.push(char);
+            continue;
+        }
+        let (_, unit) = UNITS.iter().find(|(name, _)| *name == char)?;
+        seconds = seconds.checked_add(number.parse::<u64>().ok()?.checked_mul(*unit)?)?;
+        number.clear();
+    }
+    number.is_empty().then_some(seconds)
+}
+
+/// The seconds between messages of `!slow`, e.g. `45` or `2m`, if Twitch accepts them.
+pub fn slow_mode_seconds(text: &str) -> Option<u32> {
+    parse_duration(text, 1)
+        .filter(|seconds| SLOW_MODE_SECONDS.contains(seconds))
+        .map(|seconds| seconds as u32)
+}
+
+/// The minutes of following of `!followersonly`, e.g. `10`, `1h30m` or `1w`, if Twitch accepts them.
+pub fn follower_mode_minutes(text: &str) -> Option<u32> {
+    parse_duration(text, 60)
+        .filter(|seconds| seconds % 60 == 0)
+        .map(|seconds| seconds / 60)
+        .filter(|minutes| FOLLOWER_MODE_MINUTES.contains(minutes))
+        .map(|minutes| minutes as u32)
+}
+
+/// Formats minutes of following with the largest units, e.g. `1w`, `1h30m` or `10m`.
+pub fn format_follow_duration(minutes: u32) -> String {
+    let mut rest = minutes as u64 * 60;
+    let mut duration = String::new();
+    for (name, unit) in UNITS {
+        if rest >= unit {
+            duration.push_str(&format!("{}{}", rest / unit, name));
+            rest %= unit;
+        }
+    }
+    duration
+}
+
+pub fn parse_switch(text: &str) -> Option<bool> {
+    match text.to_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// The modes of the room after the settings were changed, so that the bot knows them
+/// before the chat server confirms them.
+pub fn room_change(settings: &ChatSettings) -> RoomState {
+    RoomState {
+        emote_only: settings.emote_mode,
+        subs_only: settings.subscriber_mode,
+        slow: settings
+            .slow_mode
+            .map(|on| if on { settings.slow_seconds() } else { 0 }),
+        followers_only: settings.follower_mode.map(|on| {
+            if on {
+                settings.follower_minutes() as i32
+            } else {
+                -1
+            }
+        }),
+        unique_chat: settings.unique_chat_mode,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_chat_mode_durations() {
+        assert_eq!(slow_mode_seconds("45"), Some(45));
+        assert_eq!(slow_mode_seconds("2m"), Some(120));
+        assert_eq!(slow_mode_seconds("2"), None);
+        assert_eq!(slow_mode_seconds("121"), None);
+        assert_eq!(slow_mode_seconds("fast"), None);
+        assert_eq!(follower_mode_minutes("0"), Some(0));
+        assert_eq!(follower_mode_minutes("10"), Some(10));
+        assert_eq!(follower_mode_minutes("1h30m"), Some(90));
+        assert_eq!(follower_mode_minutes("1W"), Some(10080));
+        assert_eq!(follower_mode_minutes("90d"), Some(129_600));
+        assert_eq!(follower_mode_minutes("91d"), None);
+        assert_eq!(follower_mode_minutes("30s"), None);
+        assert_eq!(follower_mode_minutes("10x"), None);
+        assert_eq!(follower_mode_minutes("h"), None);
+        assert_eq!(follower_mode_minutes("99999999999999999999m"), None);
+        assert_eq!(format_follow_duration(90), "1h30m");
+        assert_eq!(format_follow_duration(10080), "1w");
+        assert_eq!(format_follow_duration(10), "10m");
+    }
+
+    #[test]
+    fn changing_the_room_state() {
+        let mut room = RoomState {
+            slow: Some(0),
+            followers_only: Some(10),
+            ..RoomState::default()
+        };
+        room.update(room_change(&ChatSettings {
+            slow_mode: Some(true),
+            slow_mode_wait_time: Some(45),
+            follower_mode: Some(false),
+            unique_chat_mode: Some(true),
+            ..ChatSettings::default()
+        }));
+        assert_eq!(
+            room,
+            RoomState {
+                emote_only: None,
+                subs_only: None,
+                slow: Some(45),
+                followers_only: Some(-1),
+                unique_chat: Some(true),
+            }
+        );
+    }
+}
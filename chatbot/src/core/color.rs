@@ -0,0 +1,141 @@
+/// CSS colors by name, with all the colors Twitch assigns to users without a chosen color.
+const NAMED_COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("Black", (0x00, 0x00, 0x00)),
+    ("White", (0xFF, 0xFF, 0xFF)),
+    ("Gray", (0x80, 0x80, 0x80)),
+    ("Silver", (0xC0, 0xC0, 0xC0)),
+    ("Red", (0xFF, 0x00, 0x00)),
+    ("Maroon", (0x80, 0x00, 0x00)),
+    ("Crimson", (0xDC, 0x14, 0x3C)),
+    ("FireBrick", (0xB2, 0x22, 0x22)),
+    ("Tomato", (0xFF, 0x63, 0x47)),
+    ("Coral", (0xFF, 0x7F, 0x50)),
+    ("Salmon", (0xFA, 0x80, 0x72)),
+    ("OrangeRed", (0xFF, 0x45, 0x00)),
+    ("Orange", (0xFF, 0xA5, 0x00)),
+    ("Gold", (0xFF, 0xD7, 0x00)),
+    ("GoldenRod", (0xDA, 0xA5, 0x20)),
+    ("Yellow", (0xFF, 0xFF, 0x00)),
+    ("Khaki", (0xF0, 0xE6, 0x8C)),
+    ("Olive", (0x80, 0x80, 0x00)),
+    ("YellowGreen", (0x9A, 0xCD, 0x32)),
+    ("Lime", (0x00, 0xFF, 0x00)),
+    ("Green", (0x00, 0x80, 0x00)),
+    ("SeaGreen", (0x2E, 0x8B, 0x57)),
+    ("SpringGreen", (0x00, 0xFF, 0x7F)),
+    ("Teal", (0x00, 0x80, 0x80)),
+    ("Turquoise", (0x40, 0xE0, 0xD0)),
+    ("Aqua", (0x00, 0xFF, 0xFF)),
+    ("CadetBlue", (0x5F, 0x9E, 0xA0)),
+    ("SkyBlue", (0x87, 0xCE, 0xEB)),
+    ("DodgerBlue", (0x1E, 0x90, 0xFF)),
+    ("Blue", (0x00, 0x00, 0xFF)),
+    ("Navy", (0x00, 0x00, 0x80)),
+    ("Indigo", (0x4B, 0x00, 0x82)),
+    ("BlueViolet", (0x8A, 0x2B, 0xE2)),
+    ("Purple", (0x80, 0x00, 0x80)),
+    ("Violet", (0xEE, 0x82, 0xEE)),
+    ("Orchid", (0xDA, 0x70, 0xD6)),
+    ("Fuchsia", (0xFF, 0x00, 0xFF)),
+    ("HotPink", (0xFF, 0x69, 0xB4)),
+    ("Pink", (0xFF, 0xC0, 0xCB)),
+    ("Chocolate", (0xD2, 0x69, 0x1E)),
+    ("Brown", (0xA5, 0x2A, 0x2A)),
+];
+
+/// The named chat colors every account may choose, as the Helix api expects them.
+const CHAT_COLORS: &[&str] = &[
+    "blue",
+    "blue_violet",
+    "cadet_blue",
+    "chocolate",
+    "coral",
+    "dodger_blue",
+    "firebrick",
+    "golden_rod",
+    "green",
+    "hot_pink",
+    "orange_red",
+    "red",
+    "sea_green",
+    "spring_green",
+    "yellow_green",
+];
+
+/// Parses a color like `#1E90FF` of the color tag.
+fn parse_hex(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |range| u8::from_str_radix(&hex[range], 16).ok();
+    Some((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+/// The name of the CSS color closest to the given one in RGB space, None if it is not a hex color.
+pub fn nearest_color_name(color: &str) -> Option<&'static str> {
+    let (r, g, b) = parse_hex(color)?;
+    let distance = |(other_r, other_g, other_b): (u8, u8, u8)| {
+        [(r, other_r), (g, other_g), (b, other_b)]
+            .iter()
+            .map(|(a, b)| (*a as i32 - *b as i32).pow(2))
+            .sum::<i32>()
+    };
+    NAMED_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(name, _)| *name)
+}
+
+/// The chat color for the Helix api written like `BlueViolet`, `blue_violet` or `#9146ff`,
+/// None if Twitch does not know it. Hex colors are only accepted for Turbo and Prime accounts.
+pub fn parse_chat_color(text: &str) -> Option<String> {
+    if parse_hex(text).is_some() {
+        return Some(text.to_uppercase());
+    }
+    let name = text.to_lowercase().replace(['_', '-'], "");
+    CHAT_COLORS
+        .iter()
+        .find(|color| color.replace('_', "") == name)
+        .map(|color| color.to_string())
+}
+
+/// The named chat colors, listed when an unknown color is chosen.
+pub fn chat_color_names() -> String {
+    CHAT_COLORS.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finding_the_nearest_color_name() {
+        assert_eq!(nearest_color_name("#1E90FF"), Some("DodgerBlue"));
+        assert_eq!(nearest_color_name("#ff0000"), Some("Red"));
+        assert_eq!(nearest_color_name("#FE0102"), Some("Red"));
+        assert_eq!(nearest_color_name("#101010"), Some("Black"));
+        assert_eq!(nearest_color_name("#8A2BE1"), Some("BlueViolet"));
+        assert_eq!(nearest_color_name("#7F7F80"), Some("Gray"));
+        assert_eq!(nearest_color_name("1E90FF"), None);
+        assert_eq!(nearest_color_name("#1E90F"), None);
+        assert_eq!(nearest_color_name(""), None);
+    }
+
+    #[test]
+    fn parsing_chat_colors() {
+        assert_eq!(
+            parse_chat_color("BlueViolet"),
+            Some("blue_violet".to_owned())
+        );
+        assert_eq!(
+            parse_chat_color("golden_rod"),
+            Some("golden_rod".to_owned())
+        );
+        assert_eq!(parse_chat_color("FIREBRICK"), Some("firebrick".to_owned()));
+        assert_eq!(parse_chat_color("#9146ff"), Some("#9146FF".to_owned()));
+        assert_eq!(parse_chat_color("purple"), None);
+        assert_eq!(parse_chat_color("#9146f"), None);
+        assert!(chat_color_names().starts_with("blue, blue_violet, "));
+    }
+}
@@ -1,13 +1,98 @@
 use std::time::Duration;
 
-use crate::connect::ChatBotEvent;
+use super::{
+    audit::AuditEntry, counts::StreamSnapshot, deaths::GameDeaths, dice::RollRecord,
+    goal::GoalKind, golive::GoLiveAnnouncement, roles::Role,
+};
+use crate::connect::{Alert, ChatBotEvent, HelixRequest};
 
 #[derive(Debug)]
 pub enum ChatBotCommand {
     SendMessage(String),
+    // bot wants to send a message to another channel than its own, e.g. a relayed announcement
+    SendMessageTo {
+        channel: String,
+        text: String,
+    },
+    // bot wants to whisper to a user, a failure comes back as ChatBotEvent::HelixResponse
+    SendWhisper {
+        user: String,
+        text: String,
+    },
+    // bot wants a raw IRC line to be sent as it is, e.g. a `CAP REQ`
+    SendRaw(String),
     LogTextMessage(String),
     // bot registers to be called back with the specified event
-    TimedCallback{duration: Duration, event: ChatBotEvent},
+    TimedCallback {
+        duration: Duration,
+        event: ChatBotEvent,
+    },
     // bot sends more than one command
     MultipleCommands(Vec<ChatBotCommand>),
+    // bot wants the new value of a counter to be persisted
+    StoreCounter {
+        name: String,
+        value: i64,
+    },
+    // bot wants the new target of a goal to be persisted
+    StoreGoal {
+        kind: GoalKind,
+        target: u64,
+    },
+    // bot wants the usage counters (messages and commands) of a user in a channel to be persisted
+    StoreUsage {
+        channel: String,
+        user: String,
+        counts: Vec<(String, u64)>,
+    },
+    // bot wants the new quiz points of users to be persisted
+    StoreQuizPoints(Vec<(String, u64)>),
+    // bot wants the new all-time record of `!roll` to be persisted
+    StoreRollRecord(RollRecord),
+    // bot wants the deaths in a game to be persisted, by the id of its category
+    StoreGameDeaths {
+        game_id: String,
+        game: GameDeaths,
+    },
+    // bot wants the totals at the start of the live stream to be persisted, by stream id
+    StoreStreamSnapshot {
+        stream_id: String,
+        snapshot: StreamSnapshot,
+    },
+    // bot wants a command being disabled or enabled again to be persisted
+    StoreCommandDisabled {
+        name: String,
+        disabled: bool,
+    },
+    // bot wants an entry of the audit log to be written, which no setting turns off
+    Audit(AuditEntry),
+    // bot wants the highest message milestone a user reached in a channel to be persisted
+    StoreMessageMilestone {
+        channel: String,
+        user: String,
+        milestone: u64,
+    },
+    // bot wants the role a user chatted with to be persisted, keyed by the user id
+    StoreRole {
+        user: String,
+        role: Role,
+    },
+    // bot needs a request to the twitch api, the result comes back as ChatBotEvent::HelixResponse
+    HelixRequest(HelixRequest),
+    // bot wants the round trip time to the chat server, the result comes back as ChatBotEvent::Latency
+    MeasureLatency,
+    // bot wants the last unparsed chat lines of the connector to be logged
+    LogUnparsedLines(usize),
+    // bot wants an alert to be sent to the stream overlays
+    Alert(Alert),
+    // bot wants the stream going live to be announced on discord
+    AnnounceGoLive(GoLiveAnnouncement),
+    // bot wants the owners to know about something urgent, e.g. a hate raid, which goes to discord
+    NotifyOwners(String),
+    // bot wants the status of the bot process, the result comes back as ChatBotEvent::Status
+    ReportStatus,
+    // bot wants the connection to the chat to be closed and opened again
+    Reconnect,
+    // bot wants the bot process to stop
+    Shutdown,
 }
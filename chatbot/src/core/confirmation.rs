@@ -0,0 +1,116 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+// a command waiting for its confirmation has to be repeated within this time
+pub const CONFIRMATION_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct PendingConfirmation {
+    raw_args: String,
+    asked_at: Instant,
+}
+
+/// Runs the configured (e.g. destructive) commands only when the same user repeats them
+/// with the same arguments within the window, so that a typo does not remove anything.
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    // names of the commands which need a confirmation
+    commands: HashSet<String>,
+    // by lowercase name of the user and name of the command
+    pending: HashMap<(String, String), PendingConfirmation>,
+}
+
+impl Confirmations {
+    pub fn new(commands: HashSet<String>) -> Self {
+        Self {
+            commands,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Whether the command may run now. The first use of a command needing a confirmation
+    /// returns false and starts waiting for the repetition, which then returns true.
+    pub fn confirm(
+        &mut self,
+        user_name: &str,
+        command_name: &str,
+        raw_args: &str,
+        now: Instant,
+    ) -> bool {
+        if !self.commands.contains(command_name) {
+            return true;
+        }
+        // expired confirmations are dropped here, so that they do not pile up
+        self.pending.retain(|_, pending| {
+            now.saturating_duration_since(pending.asked_at) < CONFIRMATION_WINDOW
+        });
+        let key = (user_name.to_lowercase(), command_name.to_owned());
+        match self.pending.remove(&key) {
+            Some(pending) if pending.raw_args == raw_args => true,
+            // other arguments need their own confirmation
+            _ => {
+                self.pending.insert(
+                    key,
+                    PendingConfirmation {
+                        raw_args: raw_args.to_owned(),
+                        asked_at: now,
+                    },
+                );
+                false
+            }
+        }
+    }
+
+    /// Drops the commands waiting for their confirmation.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confirmations() -> Confirmations {
+        Confirmations::new(HashSet::from(["removecommand".to_owned()]))
+    }
+
+    #[test]
+    fn confirming_within_the_window() {
+        let mut confirmations = confirmations();
+        let now = Instant::now();
+        assert!(confirmations.confirm("alice", "counter", "deaths", now));
+        assert!(!confirmations.confirm("alice", "removecommand", "hug", now));
+        // only the user who asked can confirm
+        assert!(!confirmations.confirm("bob", "removecommand", "hug", now));
+        assert!(confirmations.confirm(
+            "Alice",
+            "removecommand",
+            "hug",
+            now + Duration::from_secs(9)
+        ));
+        // a confirmed command has to be confirmed again
+        assert!(!confirmations.confirm(
+            "alice",
+            "removecommand",
+            "hug",
+            now + Duration::from_secs(9)
+        ));
+    }
+
+    #[test]
+    fn starting_over_after_the_window() {
+        let mut confirmations = confirmations();
+        let now = Instant::now();
+        assert!(!confirmations.confirm("alice", "removecommand", "hug", now));
+        assert!(!confirmations.confirm("alice", "removecommand", "hug", now + CONFIRMATION_WINDOW));
+        let later = now + CONFIRMATION_WINDOW + Duration::from_secs(1);
+        assert!(confirmations.confirm("alice", "removecommand", "hug", later));
+        // other arguments are not confirmed by the first ones
+        assert!(!confirmations.confirm("alice", "removecommand", "hug", later));
+        assert!(!confirmations.confirm("alice", "removecommand", "slap", later));
+        assert!(!confirmations.confirm("alice", "removecommand", "hug", later));
+    }
+}
@@ -0,0 +1,59 @@
+#[derive(Debug, PartialEq, Eq)]
+pub enum CounterAction {
+    Show,
+    Add(i64),
+    Set(i64),
+}
+
+impl CounterAction {
+    /// Parses the options following the counter name,
+    /// e.g. `[]`, `["+1"]`, `["-3"]` or `["set", "0"]`
+    pub fn parse(options: &[String]) -> Option<Self> {
+        match options {
+            [] => Some(CounterAction::Show),
+            [change] if change.starts_with('+') || change.starts_with('-') => {
+                change.parse().ok().map(CounterAction::Add)
+            }
+            [set, value] if set == "set" => value.parse().ok().map(CounterAction::Set),
+            _ => None,
+        }
+    }
+
+    pub fn is_mutation(&self) -> bool {
+        !matches!(self, CounterAction::Show)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::options;
+
+    #[test]
+    fn parsing_counter_actions() {
+        assert_eq!(
+            CounterAction::parse(&options(&[])),
+            Some(CounterAction::Show)
+        );
+        assert_eq!(
+            CounterAction::parse(&options(&["+1"])),
+            Some(CounterAction::Add(1))
+        );
+        assert_eq!(
+            CounterAction::parse(&options(&["-5"])),
+            Some(CounterAction::Add(-5))
+        );
+        assert_eq!(
+            CounterAction::parse(&options(&["set", "0"])),
+            Some(CounterAction::Set(0))
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_counter_actions() {
+        assert_eq!(CounterAction::parse(&options(&["1"])), None);
+        assert_eq!(CounterAction::parse(&options(&["+abc"])), None);
+        assert_eq!(CounterAction::parse(&options(&["set"])), None);
+        assert_eq!(CounterAction::parse(&options(&["set", "x"])), None);
+    }
+}
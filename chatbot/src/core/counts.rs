@@ -0,0 +1,221 @@
+use super::goal::CachedTotal;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// The totals of the channel reported by `!followers` and `!subs`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum CountKind {
+    Followers,
+    Subscribers,
+}
+
+/// The totals at the start of a stream, None until they were fetched.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct StreamSnapshot {
+    pub followers: Option<u64>,
+    pub subscribers: Option<u64>,
+}
+
+impl StreamSnapshot {
+    fn get(&self, kind: CountKind) -> Option<u64> {
+        match kind {
+            CountKind::Followers => self.followers,
+            CountKind::Subscribers => self.subscribers,
+        }
+    }
+
+    fn set(&mut self, kind: CountKind, total: u64) {
+        match kind {
+            CountKind::Followers => self.followers = Some(total),
+            CountKind::Subscribers => self.subscribers = Some(total),
+        }
+    }
+}
+
+// e.g. `1234 -` for 1234 followers and subscribers which were not fetched yet
+impl fmt::Display for StreamSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = |total: Option<u64>| total.map_or("-".to_owned(), |total| total.to_string());
+        write!(f, "{} {}", total(self.followers), total(self.subscribers))
+    }
+}
+
+impl FromStr for StreamSnapshot {
+    type Err = String;
+
+    fn from_str(snapshot: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid stream snapshot {}", snapshot);
+        let total = |total: &str| match total {
+            "-" => Ok(None),
+            total => total.parse().map(Some).map_err(|_| invalid()),
+        };
+        let (followers, subscribers) = snapshot.split_once(' ').ok_or_else(invalid)?;
+        Ok(Self {
+            followers: total(followers)?,
+            subscribers: total(subscribers)?,
+        })
+    }
+}
+
+/// The cached totals of the channel and their snapshot at the start of the live stream,
+/// which tells how they changed during the stream.
+#[derive(Debug, Default)]
+pub struct StreamCounts {
+    totals: HashMap<CountKind, CachedTotal>,
+    // fetched with the subscriber total
+    sub_points: u64,
+    // the id of the live stream with its snapshot
+    stream: Option<(String, StreamSnapshot)>,
+    // the snapshots in the store by stream id, the bot continues with one after a restart
+    stored: HashMap<String, StreamSnapshot>,
+    // the users who asked for the totals in the chat, whom the next response answers
+    pending_replies: HashMap<CountKind, Vec<String>>,
+}
+
+impl StreamCounts {
+    pub fn new(stored: HashMap<String, StreamSnapshot>) -> Self {
+        Self {
+            stored,
+            ..Self::default()
+        }
+    }
+
+    /// Notes the stream being live, returns the totals to fetch for its snapshot
+    /// if the stream is new.
+    pub fn stream_live(&mut self, stream_id: &str) -> Vec<CountKind> {
+        if self.stream.as_ref().is_some_and(|(id, _)| id == stream_id) {
+            return Vec::new();
+        }
+        let snapshot = self.stored.remove(stream_id).unwrap_or_default();
+        self.stream = Some((stream_id.to_owned(), snapshot));
+        [CountKind::Followers, CountKind::Subscribers]
+            .into_iter()
+            .filter(|kind| snapshot.get(*kind).is_none())
+            .collect()
+    }
+
+    /// The total unless it is older than the cache duration.
+    pub fn cached(&self, kind: CountKind) -> Option<u64> {
+        self.totals
+            .get(&kind)
+            .filter(|cached| cached.is_fresh())
+            .map(|cached| cached.total)
+    }
+
+    pub fn sub_points(&self) -> u64 {
+        self.sub_points
+    }
+
+    /// Caches the total, the first one of a stream becomes part of its snapshot.
+    /// Returns the stream id with the snapshot to store if it changed.
+    pub fn record(&mut self, kind: CountKind, total: u64) -> Option<(String, StreamSnapshot)> {
+        self.totals.insert(kind, CachedTotal::new(total));
+        let (stream_id, snapshot) = self.stream.as_mut()?;
+        if snapshot.get(kind).is_some() {
+            return None;
+        }
+        snapshot.set(kind, total);
+        Some((stream_id.to_owned(), *snapshot))
+    }
+
+    pub fn record_sub_points(&mut self, points: u64) {
+        self.sub_points = points;
+    }
+
+    /// How much the total changed since the start of the stream, None without a snapshot.
+    pub fn since_start(&self, kind: CountKind, total: u64) -> Option<i64> {
+        let (_, snapshot) = self.stream.as_ref()?;
+        Some(total as i64 - snapshot.get(kind)? as i64)
+    }
+
+    /// Notes a total asked for in the chat by a user, returns false if it is asked for already.
+    pub fn await_reply(&mut self, kind: CountKind, user_name: &str) -> bool {
+        let users = self.pending_replies.entry(kind).or_default();
+        let first = users.is_empty();
+        if !users.iter().any(|user| user == user_name) {
+            users.push(user_name.to_owned());
+        }
+        first
+    }
+
+    /// The users to answer with the response to the total, none if nobody asked in the chat.
+    pub fn take_reply(&mut self, kind: CountKind) -> Vec<String> {
+        self.pending_replies.remove(&kind).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::goal::GOAL_CACHE_DURATION;
+    use std::time::Instant;
+
+    #[test]
+    fn counting_since_the_stream_started() {
+        let mut counts = StreamCounts::default();
+        // offline there is nothing to compare with
+        assert_eq!(counts.record(CountKind::Followers, 1000), None);
+        assert_eq!(counts.since_start(CountKind::Followers, 1000), None);
+        assert_eq!(
+            counts.stream_live("41"),
+            vec![CountKind::Followers, CountKind::Subscribers]
+        );
+        assert!(counts.stream_live("41").is_empty());
+        let snapshot = StreamSnapshot {
+            followers: Some(1000),
+            subscribers: None,
+        };
+        assert_eq!(
+            counts.record(CountKind::Followers, 1000),
+            Some(("41".to_owned(), snapshot))
+        );
+        // later totals leave the snapshot alone
+        assert_eq!(counts.record(CountKind::Followers, 1012), None);
+        assert_eq!(counts.since_start(CountKind::Followers, 1012), Some(12));
+        assert_eq!(counts.since_start(CountKind::Followers, 997), Some(-3));
+        assert_eq!(counts.since_start(CountKind::Subscribers, 50), None);
+        assert_eq!(counts.cached(CountKind::Followers), Some(1012));
+        assert_eq!(counts.cached(CountKind::Subscribers), None);
+        // the next stream starts over
+        assert_eq!(counts.stream_live("42").len(), 2);
+        assert_eq!(counts.since_start(CountKind::Followers, 1012), None);
+    }
+
+    #[test]
+    fn continuing_with_a_stored_snapshot() {
+        let snapshot = StreamSnapshot {
+            followers: Some(1000),
+            subscribers: None,
+        };
+        let mut counts = StreamCounts::new(HashMap::from([("41".to_owned(), snapshot)]));
+        assert_eq!(counts.stream_live("41"), vec![CountKind::Subscribers]);
+        assert_eq!(counts.since_start(CountKind::Followers, 1020), Some(20));
+        assert_eq!(snapshot.to_string(), "1000 -");
+        assert_eq!("1000 -".parse(), Ok(snapshot));
+        assert_eq!(
+            "- 7".parse(),
+            Ok(StreamSnapshot {
+                followers: None,
+                subscribers: Some(7),
+            })
+        );
+        assert!("1000".parse::<StreamSnapshot>().is_err());
+        assert!("many -".parse::<StreamSnapshot>().is_err());
+    }
+
+    #[test]
+    fn caching_totals_and_replies() {
+        let mut counts = StreamCounts::default();
+        counts.record(CountKind::Subscribers, 50);
+        assert_eq!(counts.cached(CountKind::Subscribers), Some(50));
+        if let Some(cached) = counts.totals.get_mut(&CountKind::Subscribers) {
+            cached.fetched_at = Instant::now() - GOAL_CACHE_DURATION;
+        }
+        assert_eq!(counts.cached(CountKind::Subscribers), None);
+        assert!(counts.await_reply(CountKind::Followers, "alice"));
+        assert!(!counts.await_reply(CountKind::Followers, "bob"));
+        assert!(!counts.await_reply(CountKind::Followers, "alice"));
+        assert!(counts.take_reply(CountKind::Subscribers).is_empty());
+        assert_eq!(counts.take_reply(CountKind::Followers), ["alice", "bob"]);
+        assert!(counts.take_reply(CountKind::Followers).is_empty());
+    }
+}
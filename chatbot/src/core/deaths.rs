@@ -0,0 +1,188 @@
+use super::{counter::CounterAction, golive::STREAM_CHECK_INTERVAL};
+use std::{collections::HashMap, fmt, str::FromStr, time::Instant};
+
+/// The deaths counted in one game, persisted as `<deaths> <name>` by the id of the category.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GameDeaths {
+    pub name: String,
+    pub deaths: i64,
+}
+
+impl fmt::Display for GameDeaths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.deaths, self.name)
+    }
+}
+
+impl FromStr for GameDeaths {
+    type Err = String;
+
+    fn from_str(game: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid game deaths {}", game);
+        let (deaths, name) = game.split_once(' ').ok_or_else(invalid)?;
+        Ok(Self {
+            name: name.to_owned(),
+            deaths: deaths.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeathsAction {
+    // shows or changes the deaths in the current game
+    Counter(CounterAction),
+    // shows the deaths in another game, e.g. `game Elden Ring`
+    Game(String),
+}
+
+impl DeathsAction {
+    pub fn parse(options: &[String]) -> Option<Self> {
+        match options {
+            [game, name @ ..] if game == "game" && !name.is_empty() => {
+                Some(DeathsAction::Game(name.join(" ")))
+            }
+            _ => CounterAction::parse(options).map(DeathsAction::Counter),
+        }
+    }
+}
+
+/// The death counters of all games. `!deaths` counts in the game of the current category,
+/// which is switched whenever the stream info shows another category.
+#[derive(Debug, Default)]
+pub struct DeathCounters {
+    // by the id of the category
+    games: HashMap<String, GameDeaths>,
+    current: Option<String>,
+    // when the category was last looked up
+    checked_at: Option<Instant>,
+}
+
+impl DeathCounters {
+    pub fn new(games: HashMap<String, GameDeaths>) -> Self {
+        Self {
+            games,
+            ..Self::default()
+        }
+    }
+
+    /// Switches to the counter of the category, which is created at zero if the game is new.
+    /// It is persisted once deaths are counted.
+    pub fn switch(&mut self, game_id: &str, name: &str, now: Instant) {
+        self.checked_at = Some(now);
+        if game_id.is_empty() {
+            self.current = None;
+            return;
+        }
+        self.current = Some(game_id.to_owned());
+        let game = self
+            .games
+            .entry(game_id.to_owned())
+            .or_insert_with(|| GameDeaths {
+                name: name.to_owned(),
+                deaths: 0,
+            });
+        // twitch renames categories now and then
+        game.name = name.to_owned();
+    }
+
+    /// Whether the category has to be looked up again before counting,
+    /// because it was not seen for a stream check interval.
+    pub fn is_stale(&self, now: Instant) -> bool {
+        match self.checked_at {
+            Some(checked_at) => now - checked_at >= STREAM_CHECK_INTERVAL,
+            None => true,
+        }
+    }
+
+    /// The counter of the current game, None if the stream has no category.
+    pub fn current(&self) -> Option<&GameDeaths> {
+        self.games.get(self.current.as_ref()?)
+    }
+
+    /// Sets the deaths in the current game and returns its id and counter, which have to be persisted.
+    pub fn set_current(&mut self, deaths: i64) -> Option<(String, GameDeaths)> {
+        let game_id = self.current.clone()?;
+        let game = self.games.get_mut(&game_id)?;
+        game.deaths = deaths;
+        Some((game_id, game.clone()))
+    }
+
+    /// Finds the counter of a game by its name, ignoring the case.
+    pub fn find(&self, name: &str) -> Option<&GameDeaths> {
+        self.games
+            .values()
+            .find(|game| game.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The deaths across all games.
+    pub fn total(&self) -> i64 {
+        self.games
+            .values()
+            .fold(0, |total, game| total.saturating_add(game.deaths))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::options;
+
+    #[test]
+    fn parsing_deaths_actions() {
+        assert_eq!(
+            DeathsAction::parse(&options(&[])),
+            Some(DeathsAction::Counter(CounterAction::Show))
+        );
+        assert_eq!(
+            DeathsAction::parse(&options(&["set", "42"])),
+            Some(DeathsAction::Counter(CounterAction::Set(42)))
+        );
+        assert_eq!(
+            DeathsAction::parse(&options(&["game", "Elden", "Ring"])),
+            Some(DeathsAction::Game("Elden Ring".to_owned()))
+        );
+        assert_eq!(DeathsAction::parse(&options(&["game"])), None);
+        assert_eq!(DeathsAction::parse(&options(&["many"])), None);
+    }
+
+    #[test]
+    fn switching_counters_with_the_category() {
+        let now = Instant::now();
+        let mut counters = DeathCounters::new(HashMap::from([(
+            "512953".to_owned(),
+            "42 Elden Ring".parse().unwrap(),
+        )]));
+        assert!(counters.is_stale(now));
+        assert_eq!(counters.current(), None);
+        counters.switch("512953", "Elden Ring", now);
+        assert!(!counters.is_stale(now));
+        assert_eq!(counters.current().unwrap().deaths, 42);
+
+        counters.switch("490100", "Lies of P", now);
+        assert_eq!(
+            counters.current(),
+            Some(&GameDeaths {
+                name: "Lies of P".to_owned(),
+                deaths: 0,
+            })
+        );
+        assert_eq!(
+            counters.set_current(3).map(|(_, game)| game.to_string()),
+            Some("3 Lies of P".to_owned())
+        );
+        assert_eq!(counters.total(), 45);
+        assert_eq!(counters.find("elden ring").unwrap().deaths, 42);
+
+        // just chatting
+        counters.switch("", "", now);
+        assert_eq!(counters.current(), None);
+        assert_eq!(counters.set_current(1), None);
+        assert!(counters.is_stale(now + STREAM_CHECK_INTERVAL));
+    }
+
+    #[test]
+    fn parsing_invalid_game_deaths() {
+        assert!("Elden Ring".parse::<GameDeaths>().is_err());
+        assert!("many Elden Ring".parse::<GameDeaths>().is_err());
+    }
+}
@@ -0,0 +1,534 @@
+use rand::Rng;
+use std::{fmt, str::FromStr};
+
+const MAX_DICE: u32 = 20;
+const MAX_SIDES: u32 = 1000;
+// keeps the sum of the dice and the modifier far away from an overflow
+const MAX_MODIFIER: i64 = 1000;
+// rerolls of a single exploding die, so that a loaded rng cannot keep the bot rolling
+const MAX_EXPLOSIONS: usize = 10;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum RollAction {
+    // dice notation like 2d6+3
+    Dice {
+        count: u32,
+        sides: u32,
+        modifier: i64,
+    },
+    // exploding dice like 1d6!, a die showing its highest side is rolled again and added
+    Exploding {
+        count: u32,
+        sides: u32,
+        modifier: i64,
+    },
+    // Fate (Fudge) dice like 4dF, each is -1, 0 or +1
+    Fate {
+        count: u32,
+        modifier: i64,
+    },
+    // two d20, the higher one is used
+    Advantage,
+    // two d20, the lower one is used
+    Disadvantage,
+    // dice with fewer than two sides like d1 or a bare `1`, whose result is fixed
+    Degenerate {
+        count: u32,
+        sides: u32,
+        modifier: i64,
+    },
+}
+
+impl RollAction {
+    /// Parses the options of the roll command, e.g. `[]` (one d20), `["2d6+3"]` or `["adv"]`
+    pub fn parse(options: &[String]) -> Option<Self> {
+        match options {
+            [] => Some(RollAction::Dice {
+                count: 1,
+                sides: 20,
+                modifier: 0,
+            }),
+            [option] => match option.to_lowercase().as_str() {
+                "adv" | "advantage" => Some(RollAction::Advantage),
+                "dis" | "disadvantage" => Some(RollAction::Disadvantage),
+                notation => parse_notation(notation),
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether the roll competes for the all-time record, which is only kept for a single d20.
+    pub fn is_record_roll(&self) -> bool {
+        *self
+            == RollAction::Dice {
+                count: 1,
+                sides: 20,
+                modifier: 0,
+            }
+    }
+
+    /// Rolls the dice, e.g. `[17, 4]` with result 17 for advantage.
+    pub fn roll(&self, rng: &mut impl Rng) -> Roll {
+        let mut roll_dice = |count, faces: std::ops::RangeInclusive<i64>| -> Vec<i64> {
+            (0..count).map(|_| rng.gen_range(faces.clone())).collect()
+        };
+        let mut chains = Vec::new();
+        let (dice, result) = match *self {
+            RollAction::Exploding {
+                count,
+                sides,
+                modifier,
+            } => {
+                chains = (0..count)
+                    .map(|_| {
+                        let mut chain = roll_dice(1, 1..=sides as i64);
+                        while chain.last() == Some(&(sides as i64)) && chain.len() <= MAX_EXPLOSIONS
+                        {
+                            chain.extend(roll_dice(1, 1..=sides as i64));
+                        }
+                        chain
+                    })
+                    .collect();
+                let dice: Vec<i64> = chains.iter().map(|chain| chain.iter().sum()).collect();
+                let sum: i64 = dice.iter().sum();
+                (dice, sum + modifier)
+            }
+            RollAction::Dice {
+                count,
+                sides,
+                modifier,
+            } => {
+                let dice = roll_dice(count, 1..=sides as i64);
+                let sum: i64 = dice.iter().sum();
+                (dice, sum + modifier)
+            }
+            RollAction::Fate { count, modifier } => {
+                let dice = roll_dice(count, -1..=1);
+                let sum: i64 = dice.iter().sum();
+                (dice, sum + modifier)
+            }
+            RollAction::Advantage => {
+                let dice = roll_dice(2, 1..=20);
+                let higher = dice.iter().max().copied().unwrap_or_default();
+                (dice, higher)
+            }
+            RollAction::Disadvantage => {
+                let dice = roll_dice(2, 1..=20);
+                let lower = dice.iter().min().copied().unwrap_or_default();
+                (dice, lower)
+            }
+            // nothing is left to chance, a d0 shows 0
+            RollAction::Degenerate {
+                count,
+                sides,
+                modifier,
+            } => {
+                let dice = vec![sides as i64; count as usize];
+                let sum: i64 = dice.iter().sum();
+                (dice, sum + modifier)
+            }
+        };
+        Roll {
+            dice,
+            result,
+            fate: matches!(self, RollAction::Fate { .. }),
+            chains,
+        }
+    }
+}
+
+// NdS or NdF (Fate dice) with an optional +M or -M, the count may be omitted (d20),
+// a `!` after the sides (NdS!) makes the dice explode
+fn parse_notation(notation: &str) -> Option<RollAction> {
+    let (count, rest) = match notation.split_once('d') {
+        Some(dice) => dice,
+        // a bare die size, only degenerate ones are taken
+        None => {
+            let sides = notation.parse().ok().filter(|sides| *sides <= 1)?;
+            return Some(RollAction::Degenerate {
+                count: 1,
+                sides,
+                modifier: 0,
+            });
+        }
+    };
+    let count = if count.is_empty() {
+        1
+    } else {
+        count.parse().ok()?
+    };
+    let (sides, modifier) = match rest.find(['+', '-']) {
+        Some(idx) => (&rest[..idx], rest[idx..].parse().ok()?),
+        None => (rest, 0),
+    };
+    if !(-MAX_MODIFIER..=MAX_MODIFIER).contains(&modifier) {
+        return None;
+    }
+    if sides == "f" {
+        return (1..=MAX_DICE)
+            .contains(&count)
+            .then_some(RollAction::Fate { count, modifier });
+    }
+    let (sides, exploding) = match sides.strip_suffix('!') {
+        Some(sides) => (sides, true),
+        None => (sides, false),
+    };
+    let sides = sides.parse().ok()?;
+    if !(1..=MAX_DICE).contains(&count) || sides > MAX_SIDES {
+        return None;
+    }
+    if exploding {
+        // a die with one side would explode forever, and the joke is not worth a `!`
+        return (sides > 1).then_some(RollAction::Exploding {
+            count,
+            sides,
+            modifier,
+        });
+    }
+    if sides <= 1 {
+        return Some(RollAction::Degenerate {
+            count,
+            sides,
+            modifier,
+        });
+    }
+    Some(RollAction::Dice {
+        count,
+        sides,
+        modifier,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Roll {
+    pub dice: Vec<i64>,
+    pub result: i64,
+    // Fate dice are shown as symbols
+    pub fate: bool,
+    // the rolls of each exploding die, its total is in `dice`, empty for other dice
+    pub chains: Vec<Vec<i64>>,
+}
+
+impl Roll {
+    /// The rolled dice like `[17, 4]`, `[+ - 0 +]` for Fate dice or `[6 → 6 → 3, 2]` for exploding dice
+    pub fn dice_text(&self) -> String {
+        if !self.chains.is_empty() {
+            let chains: Vec<String> = self
+                .chains
+                .iter()
+                .map(|chain| {
+                    let rolls: Vec<String> = chain.iter().map(i64::to_string).collect();
+                    rolls.join(" → ")
+                })
+                .collect();
+            return format!("[{}]", chains.join(", "));
+        }
+        if self.fate {
+            let symbols: Vec<&str> = self
+                .dice
+                .iter()
+                .map(|die| match die.signum() {
+                    1 => "+",
+                    -1 => "-",
+                    _ => "0",
+                })
+                .collect();
+            return format!("[{}]", symbols.join(" "));
+        }
+        let dice: Vec<String> = self.dice.iter().map(i64::to_string).collect();
+        format!("[{}]", dice.join(", "))
+    }
+}
+
+/// The number, lowest, highest and average of the results of one kind of roll, e.g. all 2d6.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct RollSummary {
+    pub count: u64,
+    pub min: i64,
+    pub max: i64,
+    sum: i64,
+}
+
+impl RollSummary {
+    pub fn add(&mut self, result: i64) {
+        if self.count == 0 {
+            self.min = result;
+            self.max = result;
+        }
+        self.count += 1;
+        self.min = self.min.min(result);
+        self.max = self.max.max(result);
+        self.sum = self.sum.saturating_add(result);
+    }
+
+    pub fn average(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.sum as f64 / self.count as f64
+    }
+}
+
+/// The highest d20 roll since the records began, persisted as `<result> <user>`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RollRecord {
+    pub user: String,
+    pub result: i64,
+}
+
+impl fmt::Display for RollRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.result, self.user)
+    }
+}
+
+impl FromStr for RollRecord {
+    type Err = String;
+
+    fn from_str(record: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid roll record {}", record);
+        let (result, user) = record.split_once(' ').ok_or_else(invalid)?;
+        Ok(Self {
+            user: user.to_owned(),
+            result: result.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::options;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn parsing_roll_actions() {
+        assert_eq!(
+            RollAction::parse(&options(&[])),
+            Some(RollAction::Dice {
+                count: 1,
+                sides: 20,
+                modifier: 0
+            })
+        );
+        assert_eq!(
+            RollAction::parse(&options(&["2d6+3"])),
+            Some(RollAction::Dice {
+                count: 2,
+                sides: 6,
+                modifier: 3
+            })
+        );
+        assert_eq!(
+            RollAction::parse(&options(&["d8-1"])),
+            Some(RollAction::Dice {
+                count: 1,
+                sides: 8,
+                modifier: -1
+            })
+        );
+        assert_eq!(
+            RollAction::parse(&options(&["adv"])),
+            Some(RollAction::Advantage)
+        );
+        assert_eq!(
+            RollAction::parse(&options(&["Dis"])),
+            Some(RollAction::Disadvantage)
+        );
+        assert_eq!(RollAction::parse(&options(&["100d6"])), None);
+        assert_eq!(RollAction::parse(&options(&["2d1001"])), None);
+        assert_eq!(RollAction::parse(&options(&["2d6+x"])), None);
+        assert_eq!(RollAction::parse(&options(&["2d6", "adv"])), None);
+    }
+
+    #[test]
+    fn rejecting_huge_modifiers() {
+        assert_eq!(
+            RollAction::parse(&options(&["1d6+1000"])),
+            Some(RollAction::Dice {
+                count: 1,
+                sides: 6,
+                modifier: 1000
+            })
+        );
+        for notation in [
+            "1d6+9223372036854775807",
+            "1d6-9223372036854775808",
+            "1d6+1001",
+            "d6!+9223372036854775807",
+            "4dF+9223372036854775807",
+            "3d1+9223372036854775807",
+            "d0-1001",
+        ] {
+            assert_eq!(
+                RollAction::parse(&options(&[notation])),
+                None,
+                "{}",
+                notation
+            );
+        }
+    }
+
+    #[test]
+    fn rolling_dice() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let roll = RollAction::parse(&options(&["3d6+2"]))
+                .unwrap()
+                .roll(&mut rng);
+            assert_eq!(roll.dice.len(), 3);
+            assert!(roll.dice.iter().all(|die| (1..=6).contains(die)));
+            assert_eq!(roll.result, roll.dice.iter().sum::<i64>() + 2);
+        }
+    }
+
+    #[test]
+    fn rolling_degenerate_dice() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (option, expected) in [("0", 0), ("1", 1), ("d0", 0), ("3d1+2", 5), ("2d0-1", -1)] {
+            let action = RollAction::parse(&options(&[option])).unwrap();
+            assert!(
+                matches!(action, RollAction::Degenerate { .. }),
+                "{}: {:?}",
+                option,
+                action
+            );
+            assert_eq!(action.roll(&mut rng).result, expected, "{}", option);
+        }
+        assert_eq!(RollAction::parse(&options(&["6"])), None);
+        assert_eq!(RollAction::parse(&options(&["-1"])), None);
+    }
+
+    #[test]
+    fn rolling_exploding_dice() {
+        assert_eq!(
+            RollAction::parse(&options(&["2d6!+1"])),
+            Some(RollAction::Exploding {
+                count: 2,
+                sides: 6,
+                modifier: 1
+            })
+        );
+        assert_eq!(RollAction::parse(&options(&["d1!"])), None);
+        assert_eq!(RollAction::parse(&options(&["d6!!"])), None);
+        // the seed rolls a 6, which explodes once into a 3
+        let mut rng = StdRng::seed_from_u64(39);
+        let roll = RollAction::parse(&options(&["1d6!"]))
+            .unwrap()
+            .roll(&mut rng);
+        assert_eq!(roll.chains, vec![vec![6, 3]]);
+        assert_eq!(roll.dice, vec![9]);
+        assert_eq!(roll.result, 9);
+        assert_eq!(roll.dice_text(), "[6 → 3]");
+    }
+
+    #[test]
+    fn capping_explosions() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let action = RollAction::parse(&options(&["20d2!"])).unwrap();
+        let longest = (0..500)
+            .flat_map(|_| action.roll(&mut rng).chains)
+            .map(|chain| chain.len())
+            .max();
+        // a d2 explodes often enough to reach the cap in ten thousand dice
+        assert_eq!(longest, Some(MAX_EXPLOSIONS + 1));
+    }
+
+    #[test]
+    fn rolling_fate_dice() {
+        assert_eq!(
+            RollAction::parse(&options(&["4dF"])),
+            Some(RollAction::Fate {
+                count: 4,
+                modifier: 0
+            })
+        );
+        assert_eq!(
+            RollAction::parse(&options(&["df+2"])),
+            Some(RollAction::Fate {
+                count: 1,
+                modifier: 2
+            })
+        );
+        assert_eq!(RollAction::parse(&options(&["30df"])), None);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let roll = RollAction::parse(&options(&["4df"]))
+                .unwrap()
+                .roll(&mut rng);
+            assert_eq!(roll.dice.len(), 4);
+            assert!(roll.dice.iter().all(|die| (-1..=1).contains(die)));
+            assert!((-4..=4).contains(&roll.result));
+            assert_eq!(roll.result, roll.dice.iter().sum::<i64>());
+        }
+    }
+
+    #[test]
+    fn rolling_with_advantage() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let roll = RollAction::Advantage.roll(&mut rng);
+            assert_eq!(roll.dice.len(), 2);
+            assert_eq!(roll.result, roll.dice[0].max(roll.dice[1]));
+        }
+    }
+
+    #[test]
+    fn rolling_with_disadvantage() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let roll = RollAction::Disadvantage.roll(&mut rng);
+            assert_eq!(roll.dice.len(), 2);
+            assert_eq!(roll.result, roll.dice[0].min(roll.dice[1]));
+        }
+    }
+
+    #[test]
+    fn formatting_dice() {
+        let roll = Roll {
+            dice: vec![17, 4],
+            result: 17,
+            fate: false,
+            chains: Vec::new(),
+        };
+        assert_eq!(roll.dice_text(), "[17, 4]");
+        let roll = Roll {
+            dice: vec![1, -1, 0, 1],
+            result: 1,
+            fate: true,
+            chains: Vec::new(),
+        };
+        assert_eq!(roll.dice_text(), "[+ - 0 +]");
+        let roll = Roll {
+            dice: vec![15, 2],
+            result: 17,
+            fate: false,
+            chains: vec![vec![6, 6, 3], vec![2]],
+        };
+        assert_eq!(roll.dice_text(), "[6 → 6 → 3, 2]");
+    }
+
+    #[test]
+    fn persisting_roll_records() {
+        let record = RollRecord {
+            user: "Carkhy".to_owned(),
+            result: 20,
+        };
+        assert_eq!(record.to_string(), "20 Carkhy");
+        assert_eq!(record.to_string().parse(), Ok(record));
+        assert!("Carkhy".parse::<RollRecord>().is_err());
+        assert!(RollAction::parse(&[]).unwrap().is_record_roll());
+        assert!(!RollAction::Advantage.is_record_roll());
+    }
+
+    #[test]
+    fn summarizing_rolls() {
+        let mut summary = RollSummary::default();
+        assert_eq!(summary.average(), 0.0);
+        for result in [-2, 7, 4] {
+            summary.add(result);
+        }
+        assert_eq!((summary.count, summary.min, summary.max), (3, -2, 7));
+        assert_eq!(summary.average(), 3.0);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+// how long a challenged user has to accept a duel
+pub const DUEL_EXPIRY: Duration = Duration::from_secs(60);
+// a duel still tied after this many rounds ends in a draw
+pub const MAX_DUEL_ROUNDS: usize = 5;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DuelError {
+    SelfDuel,
+    NoChallenge,
+    // the challenge of the user with the given name is too old to be accepted
+    Expired(String),
+}
+
+#[derive(Debug)]
+struct Challenge {
+    challenger: String,
+    challenged_at: Instant,
+}
+
+/// Pending duel challenges, keyed by lowercase challenger and target.
+#[derive(Debug, Default)]
+pub struct Duels {
+    challenges: HashMap<(String, String), Challenge>,
+}
+
+impl Duels {
+    /// Challenges `target` to a duel, replacing an earlier challenge of the same users.
+    pub fn challenge(
+        &mut self,
+        challenger: &str,
+        target: &str,
+        now: Instant,
+    ) -> Result<(), DuelError> {
+        let key = (challenger.to_lowercase(), target.to_lowercase());
+        if key.0 == key.1 {
+            return Err(DuelError::SelfDuel);
+        }
+        // expired challenges are dropped here, so that they do not pile up
+        self.challenges
+            .retain(|_, challenge| now - challenge.challenged_at < DUEL_EXPIRY);
+        self.challenges.insert(
+            key,
+            Challenge {
+                challenger: challenger.to_owned(),
+                challenged_at: now,
+            },
+        );
+        Ok(())
+    }
+
+    /// Accepts the challenge of `challenger` to `target`, or the latest challenge to `target`
+    /// if no challenger is given. Returns the name of the challenger.
+    pub fn accept(
+        &mut self,
+        target: &str,
+        challenger: Option<&str>,
+        now: Instant,
+    ) -> Result<String, DuelError> {
+        let target = target.to_lowercase();
+        let key = match challenger {
+            Some(challenger) => (challenger.to_lowercase(), target),
+            None => self
+                .challenges
+                .iter()
+                .filter(|((_, challenged), _)| *challenged == target)
+                .max_by_key(|(_, challenge)| challenge.challenged_at)
+                .map(|(key, _)| key.clone())
+                .ok_or(DuelError::NoChallenge)?,
+        };
+        let challenge = self.challenges.remove(&key).ok_or(DuelError::NoChallenge)?;
+        if now - challenge.challenged_at >= DUEL_EXPIRY {
+            return Err(DuelError::Expired(challenge.challenger));
+        }
+        Ok(challenge.challenger)
+    }
+}
+
+/// The rolls of the challenger and the target, rolled again after a tie until one of them
+/// wins or the rounds are used up.
+pub fn duel_rounds(mut roll: impl FnMut() -> u32, max_rounds: usize) -> Vec<(u32, u32)> {
+    let mut rounds = Vec::new();
+    while rounds.len() < max_rounds {
+        let round = (roll(), roll());
+        rounds.push(round);
+        if round.0 != round.1 {
+            break;
+        }
+    }
+    rounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepting_challenges() {
+        let now = Instant::now();
+        let mut duels = Duels::default();
+        duels.challenge("Alice", "bob", now).unwrap();
+        duels
+            .challenge("Carol", "Bob", now + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(
+            duels.accept("BOB", None, now + Duration::from_secs(10)),
+            Ok("Carol".to_owned())
+        );
+        assert_eq!(
+            duels.accept("bob", Some("alice"), now + Duration::from_secs(10)),
+            Ok("Alice".to_owned())
+        );
+        assert_eq!(
+            duels.accept("bob", None, now + Duration::from_secs(10)),
+            Err(DuelError::NoChallenge)
+        );
+    }
+
+    #[test]
+    fn rejecting_self_duels() {
+        let mut duels = Duels::default();
+        assert_eq!(
+            duels.challenge("Alice", "alice", Instant::now()),
+            Err(DuelError::SelfDuel)
+        );
+    }
+
+    #[test]
+    fn rerolling_ties() {
+        let rolls = |rolls: Vec<u32>| {
+            let mut rolls = rolls.into_iter();
+            move || rolls.next().unwrap()
+        };
+        assert_eq!(duel_rounds(rolls(vec![7, 3]), 5), vec![(7, 3)]);
+        assert_eq!(
+            duel_rounds(rolls(vec![7, 7, 2, 2, 4, 19]), 5),
+            vec![(7, 7), (2, 2), (4, 19)]
+        );
+        // still tied after the last round
+        assert_eq!(
+            duel_rounds(rolls(vec![7, 7, 2, 2, 4, 19]), 2),
+            vec![(7, 7), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn expiring_challenges() {
+        let now = Instant::now();
+        let mut duels = Duels::default();
+        duels.challenge("Alice", "bob", now).unwrap();
+        assert_eq!(
+            duels.accept("bob", None, now + DUEL_EXPIRY),
+            Err(DuelError::Expired("Alice".to_owned()))
+        );
+        // an expired challenge can only be answered once
+        assert_eq!(
+            duels.accept("bob", None, now + DUEL_EXPIRY),
+            Err(DuelError::NoChallenge)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::connect::{ChannelEmote, Emotes};
+use std::collections::HashSet;
+
+// the type twitch gives the sub emotes of a channel
+const SUBSCRIPTIONS: &str = "subscriptions";
+
+/// The emotes which can be used in the channel as fetched from twitch.
+#[derive(Debug, Default)]
+pub struct EmoteSet {
+    emotes: Emotes,
+    // the codes of all emotes, emote codes are case sensitive
+    codes: HashSet<String>,
+}
+
+impl EmoteSet {
+    pub fn new(emotes: Emotes) -> Self {
+        let codes = emotes
+            .channel
+            .iter()
+            .map(|emote| emote.code.to_owned())
+            .chain(emotes.global.iter().cloned())
+            .collect();
+        Self { emotes, codes }
+    }
+
+    /// The numbers of the channel's own emotes and of the global emotes.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.emotes.channel.len(), self.emotes.global.len())
+    }
+
+    /// The codes of the channel's sub emotes in the order twitch lists them.
+    pub fn sub_emotes(&self) -> Vec<&str> {
+        self.emotes
+            .channel
+            .iter()
+            .filter(|ChannelEmote { emote_type, .. }| emote_type == SUBSCRIPTIONS)
+            .map(|emote| emote.code.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, code: &str) -> bool {
+        self.codes.contains(code)
+    }
+
+    /// The words of the text which are not known emote codes, none if it consists of emotes only.
+    pub fn unknown_codes<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.split_whitespace()
+            .filter(|word| !self.codes.contains(*word))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emote_set() -> EmoteSet {
+        let emote = |code: &str, emote_type: &str| ChannelEmote {
+            code: code.to_owned(),
+            emote_type: emote_type.to_owned(),
+        };
+        EmoteSet::new(Emotes {
+            channel: vec![
+                emote("captai7Hype", "subscriptions"),
+                emote("captai7Wave", "follower"),
+                emote("captai7Love", "subscriptions"),
+            ],
+            global: vec!["Kappa".to_owned(), "PogChamp".to_owned()],
+        })
+    }
+
+    #[test]
+    fn finding_unknown_emote_codes() {
+        let emotes = emote_set();
+        assert!(emotes
+            .unknown_codes("captai7Hype  Kappa captai7Wave")
+            .is_empty());
+        assert_eq!(
+            emotes.unknown_codes("captai7Hpye kappa PogChamp hi"),
+            vec!["captai7Hpye", "kappa", "hi"]
+        );
+        assert!(EmoteSet::default().unknown_codes("").is_empty());
+    }
+
+    #[test]
+    fn listing_sub_emotes() {
+        let emotes = emote_set();
+        assert_eq!(emotes.sub_emotes(), vec!["captai7Hype", "captai7Love"]);
+        assert_eq!(emotes.counts(), (3, 2));
+    }
+}
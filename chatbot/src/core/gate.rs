@@ -0,0 +1,198 @@
+use crate::connect::UserAge;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use std::{collections::HashMap, fs};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GateError {
+    #[error("Could not read gate config [{}]", .0)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid gate config [{}]", .0)]
+    Invalid(String),
+}
+
+/// Minimum ages a user needs to pass a gate.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Gate {
+    pub min_account_days: Option<i64>,
+    pub min_follow_minutes: Option<i64>,
+}
+
+impl Gate {
+    fn from_value(name: &str, value: &Value) -> Result<Self, GateError> {
+        let age = |key: &str| match &value[key] {
+            Value::Null => Ok(None),
+            age => age
+                .as_i64()
+                .filter(|age| *age >= 0)
+                .map(Some)
+                .ok_or_else(|| GateError::Invalid(format!("{}: {} must be a number", name, key))),
+        };
+        Ok(Self {
+            min_account_days: age("min_account_days")?,
+            min_follow_minutes: age("min_follow_minutes")?,
+        })
+    }
+
+    /// Whether a user with the given ages passes the gate at `now`.
+    /// Users who do not follow the channel only pass gates without a follow age.
+    pub fn passes(&self, age: &UserAge, now: DateTime<Utc>) -> bool {
+        let account_old_enough = self
+            .min_account_days
+            .is_none_or(|days| now - age.created_at >= Duration::days(days));
+        let following_long_enough =
+            self.min_follow_minutes
+                .is_none_or(|minutes| match age.followed_at {
+                    Some(followed_at) => now - followed_at >= Duration::minutes(minutes),
+                    None => false,
+                });
+        account_old_enough && following_long_enough
+    }
+}
+
+/// What happens to gated commands and links when the ages of a user cannot be fetched.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum UnavailablePolicy {
+    #[default]
+    Open,
+    Closed,
+}
+
+/// Gates for commands (by name) and links posted in the chat. Moderators are exempt.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct GateConfig {
+    pub commands: HashMap<String, Gate>,
+    pub links: Option<Gate>,
+    pub unavailable: UnavailablePolicy,
+}
+
+impl GateConfig {
+    /// Loads the gates from a json file like
+    /// `{"commands": {"slap": {"min_follow_minutes": 10}}, "links": {"min_account_days": 7}, "unavailable": "closed"}`
+    pub fn load(path: &str) -> Result<Self, GateError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GateError> {
+        let config: Value =
+            serde_json::from_str(json).map_err(|err| GateError::Invalid(err.to_string()))?;
+        let commands = match &config["commands"] {
+            Value::Null => HashMap::default(),
+            Value::Object(commands) => commands
+                .iter()
+                .map(|(name, gate)| {
+                    Ok((
+                        name.trim_start_matches('!').to_owned(),
+                        Gate::from_value(name, gate)?,
+                    ))
+                })
+                .collect::<Result<_, GateError>>()?,
+            _ => return Err(GateError::Invalid("commands must be an object".to_owned())),
+        };
+        let links = match &config["links"] {
+            Value::Null => None,
+            links => Some(Gate::from_value("links", links)?),
+        };
+        let unavailable = match config["unavailable"].as_str() {
+            None | Some("open") => UnavailablePolicy::Open,
+            Some("closed") => UnavailablePolicy::Closed,
+            Some(policy) => {
+                return Err(GateError::Invalid(format!(
+                    "unavailable must be open or closed, not {}",
+                    policy
+                )))
+            }
+        };
+        Ok(Self {
+            commands,
+            links,
+            unavailable,
+        })
+    }
+}
+
+/// Whether the text contains something which looks like a link, e.g. `https://...` or `example.com/...`
+pub fn contains_link(text: &str) -> bool {
+    text.split_whitespace().any(|word| {
+        let word = word.to_lowercase();
+        word.contains("://")
+            || word.starts_with("www.")
+            || word
+                .split_once('/')
+                .is_some_and(|(domain, _)| is_domain(domain))
+            || is_domain(&word)
+    })
+}
+
+// a domain needs a known looking top level domain, so that "e.g." or "1.5" are no links
+fn is_domain(word: &str) -> bool {
+    const TOP_LEVEL_DOMAINS: [&str; 12] = [
+        "com", "net", "org", "de", "tv", "gg", "io", "ly", "me", "co", "uk", "xyz",
+    ];
+    match word.rsplit_once('.') {
+        Some((name, tld)) => !name.is_empty() && TOP_LEVEL_DOMAINS.contains(&tld),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn age(account_days: i64, follow_minutes: Option<i64>) -> UserAge {
+        let now = Utc::now();
+        UserAge {
+            created_at: now - Duration::days(account_days),
+            followed_at: follow_minutes.map(|minutes| now - Duration::minutes(minutes)),
+        }
+    }
+
+    #[test]
+    fn passing_gates() {
+        let gate = Gate {
+            min_account_days: Some(7),
+            min_follow_minutes: Some(10),
+        };
+        assert!(gate.passes(&age(30, Some(60)), Utc::now()));
+        assert!(!gate.passes(&age(3, Some(60)), Utc::now()));
+        assert!(!gate.passes(&age(30, Some(5)), Utc::now()));
+        assert!(!gate.passes(&age(30, None), Utc::now()));
+        assert!(Gate::default().passes(&age(0, None), Utc::now()));
+    }
+
+    #[test]
+    fn parsing_gate_config() {
+        let config = GateConfig::from_json(
+            r#"{"commands": {"!slap": {"min_follow_minutes": 10, "min_account_days": 7}}, "links": {"min_account_days": 30}, "unavailable": "closed"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.commands.get("slap"),
+            Some(&Gate {
+                min_account_days: Some(7),
+                min_follow_minutes: Some(10),
+            })
+        );
+        assert_eq!(
+            config.links,
+            Some(Gate {
+                min_account_days: Some(30),
+                min_follow_minutes: None,
+            })
+        );
+        assert_eq!(config.unavailable, UnavailablePolicy::Closed);
+        assert!(GateConfig::from_json(r#"{"unavailable": "ajar"}"#).is_err());
+        assert!(GateConfig::from_json(r#"{"links": {"min_account_days": "7"}}"#).is_err());
+    }
+
+    #[test]
+    fn detecting_links() {
+        assert!(contains_link("look at https://example.org"));
+        assert!(contains_link("cheap viewers at www.example.xyz"));
+        assert!(contains_link("go to bit.ly/abc"));
+        assert!(contains_link("follow me on Twitch.tv"));
+        assert!(!contains_link("e.g. this is 1.5 times better..."));
+        assert!(!contains_link("no links here"));
+    }
+}
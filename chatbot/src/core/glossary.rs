@@ -0,0 +1,92 @@
+use std::{collections::HashMap, fs};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GlossaryError {
+    #[error("Could not read glossary [{}]", .0)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid glossary [{}]", .0)]
+    Invalid(String),
+}
+
+/// The terms `!define` explains, e.g. `borrow` on a Rust stream. The terms are looked up
+/// ignoring their case, apart from the custom commands.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Glossary {
+    // definitions by lowercase term
+    terms: HashMap<String, String>,
+}
+
+impl Glossary {
+    /// Loads the glossary from a toml file (`borrow = "Taking a reference ..."`) if its name ends
+    /// with `.toml`, otherwise from a json object (`{"borrow": "Taking a reference ..."}`).
+    pub fn load(path: &str) -> Result<Self, GlossaryError> {
+        let glossary = fs::read_to_string(path)?;
+        if path.ends_with(".toml") {
+            Self::from_toml(&glossary)
+        } else {
+            Self::from_json(&glossary)
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GlossaryError> {
+        let terms: HashMap<String, String> =
+            serde_json::from_str(json).map_err(|err| GlossaryError::Invalid(err.to_string()))?;
+        Ok(Self::new(terms))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, GlossaryError> {
+        let terms: HashMap<String, String> =
+            toml::from_str(toml).map_err(|err| GlossaryError::Invalid(err.to_string()))?;
+        Ok(Self::new(terms))
+    }
+
+    fn new(terms: HashMap<String, String>) -> Self {
+        Self {
+            terms: terms
+                .into_iter()
+                .map(|(term, definition)| (term.trim().to_lowercase(), definition))
+                .collect(),
+        }
+    }
+
+    pub fn define(&self, term: &str) -> Option<&str> {
+        self.terms
+            .get(&term.trim().to_lowercase())
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_glossaries() {
+        let glossary = Glossary::from_json(
+            r#"{"Borrow": "Taking a reference to a value without owning it.", "trait object": "A dyn Trait."}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            glossary.define("borrow"),
+            Some("Taking a reference to a value without owning it.")
+        );
+        assert_eq!(glossary.define("Trait Object"), Some("A dyn Trait."));
+        assert_eq!(glossary.define("lifetime"), None);
+
+        let glossary = Glossary::from_toml(
+            "borrow = \"Taking a reference.\"\n\"trait object\" = \"A dyn Trait.\"\n",
+        )
+        .unwrap();
+        assert_eq!(glossary.define("BORROW"), Some("Taking a reference."));
+        assert_eq!(glossary.define("trait object"), Some("A dyn Trait."));
+    }
+
+    #[test]
+    fn rejecting_invalid_glossaries() {
+        assert!(Glossary::from_json(r#"{"borrow": 1}"#).is_err());
+        assert!(Glossary::from_json(r#"["borrow"]"#).is_err());
+        assert!(Glossary::from_toml("borrow = 1").is_err());
+        assert!(Glossary::load("missing-glossary.json").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+// how long the totals from the helix api are reused before they are requested again
+pub const GOAL_CACHE_DURATION: Duration = Duration::from_secs(120);
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum GoalKind {
+    Followers,
+    // counted in sub points, like the sub goals of twitch
+    SubPoints,
+}
+
+impl GoalKind {
+    /// Name of the goal, used as message prefix and storage key.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GoalKind::Followers => "followgoal",
+            GoalKind::SubPoints => "subgoal",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GoalAction {
+    Show,
+    Set(u64),
+}
+
+impl GoalAction {
+    /// Parses the options of a goal command, e.g. `[]` or `["set", "1500"]`
+    pub fn parse(options: &[String]) -> Option<Self> {
+        match options {
+            [] => Some(GoalAction::Show),
+            [set, target] if set == "set" => target
+                .parse()
+                .ok()
+                .filter(|target| *target > 0)
+                .map(GoalAction::Set),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CachedTotal {
+    pub total: u64,
+    pub fetched_at: Instant,
+}
+
+impl CachedTotal {
+    pub fn new(total: u64) -> Self {
+        Self {
+            total,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.fetched_at.elapsed() < GOAL_CACHE_DURATION
+    }
+}
+
+/// Formats a number with the given separator between groups of thousands, e.g. `1,234,567`.
+pub fn format_number(number: u64, separator: &str) -> String {
+    let digits = number.to_string();
+    let mut formatted = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            formatted.push_str(separator);
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::options;
+
+    #[test]
+    fn parsing_goal_actions() {
+        assert_eq!(GoalAction::parse(&options(&[])), Some(GoalAction::Show));
+        assert_eq!(
+            GoalAction::parse(&options(&["set", "1500"])),
+            Some(GoalAction::Set(1500))
+        );
+        assert_eq!(GoalAction::parse(&options(&["set", "0"])), None);
+        assert_eq!(GoalAction::parse(&options(&["set", "-5"])), None);
+        assert_eq!(GoalAction::parse(&options(&["1500"])), None);
+    }
+
+    #[test]
+    fn formatting_numbers() {
+        assert_eq!(format_number(0, ","), "0");
+        assert_eq!(format_number(999, ","), "999");
+        assert_eq!(format_number(1234, ","), "1,234");
+        assert_eq!(format_number(123456, ","), "123,456");
+        assert_eq!(format_number(1234567, "."), "1.234.567");
+    }
+
+    #[test]
+    fn expiring_cached_totals() {
+        let mut cached = CachedTotal::new(1234);
+        assert!(cached.is_fresh());
+        cached.fetched_at = Instant::now() - GOAL_CACHE_DURATION;
+        assert!(!cached.is_fresh());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::connect::StreamInfo;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+// going offline and live again within this time is a flap and not announced again
+pub const GO_LIVE_DEBOUNCE: Duration = Duration::from_secs(600);
+// how often the bot asks the helix api whether the stream is live
+pub const STREAM_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// twitch purple, used as color of the discord embed
+const EMBED_COLOR: u32 = 0x9146ff;
+
+/// Tracks whether the stream is live, to announce it once when it goes live.
+#[derive(Debug, Default)]
+pub struct GoLiveState {
+    // None until the first check, a stream which is already live when the bot starts is not announced
+    live: Option<bool>,
+    went_offline_at: Option<Instant>,
+}
+
+impl GoLiveState {
+    /// Updates the state with the result of a check at `now`.
+    /// Returns whether the stream went live and should be announced.
+    pub fn update(&mut self, live: bool, now: Instant) -> bool {
+        let was_live = self.live.replace(live);
+        match (was_live, live) {
+            (Some(false), true) => self
+                .went_offline_at
+                .is_none_or(|went_offline_at| now - went_offline_at >= GO_LIVE_DEBOUNCE),
+            (Some(true), false) => {
+                self.went_offline_at = Some(now);
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The announcement of the stream going live, posted to the chat and to discord.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GoLiveAnnouncement {
+    pub text: String,
+    pub stream: StreamInfo,
+    pub url: String,
+}
+
+impl GoLiveAnnouncement {
+    /// The discord webhook message, the text with an embed linking to the stream.
+    pub fn discord_message(&self) -> Value {
+        let mut embed = json!({
+            "title": self.stream.title,
+            "url": self.url,
+            "color": EMBED_COLOR,
+            "fields": [{ "name": "Game", "value": self.stream.game, "inline": true }],
+        });
+        if let Some(thumbnail_url) = &self.stream.thumbnail_url {
+            embed["image"] = json!({ "url": thumbnail_url });
+        }
+        json!({ "content": self.text, "embeds": [embed] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announcing_when_going_live() {
+        let start = Instant::now();
+        let mut state = GoLiveState::default();
+        assert!(!state.update(false, start));
+        assert!(state.update(true, start + Duration::from_secs(60)));
+        assert!(!state.update(true, start + Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn not_announcing_a_stream_which_is_already_live() {
+        let mut state = GoLiveState::default();
+        assert!(!state.update(true, Instant::now()));
+    }
+
+    #[test]
+    fn debouncing_offline_online_flaps() {
+        let start = Instant::now();
+        let mut state = GoLiveState::default();
+        state.update(false, start);
+        assert!(state.update(true, start + Duration::from_secs(60)));
+        let offline_at = start + Duration::from_secs(3600);
+        assert!(!state.update(false, offline_at));
+        assert!(!state.update(true, offline_at + Duration::from_secs(300)));
+        let offline_at = offline_at + Duration::from_secs(600);
+        state.update(false, offline_at);
+        assert!(state.update(true, offline_at + GO_LIVE_DEBOUNCE));
+    }
+
+    #[test]
+    fn building_discord_messages() {
+        let announcement = GoLiveAnnouncement {
+            text: "captaincallback is live!".to_owned(),
+            stream: StreamInfo {
+                live: true,
+                title: "Writing a chat bot".to_owned(),
+                game: "Software and Game Development".to_owned(),
+                game_id: "1469308723".to_owned(),
+                thumbnail_url: Some("https://example.org/thumbnail.jpg".to_owned()),
+                stream_id: None,
+            },
+            url: "https://twitch.tv/captaincallback".to_owned(),
+        };
+        assert_eq!(
+            announcement.discord_message(),
+            json!({
+                "content": "captaincallback is live!",
+                "embeds": [{
+                    "title": "Writing a chat bot",
+                    "url": "https://twitch.tv/captaincallback",
+                    "color": 0x9146ff,
+                    "fields": [{ "name": "Game", "value": "Software and Game Development", "inline": true }],
+                    "image": { "url": "https://example.org/thumbnail.jpg" },
+                }],
+            })
+        );
+    }
+}
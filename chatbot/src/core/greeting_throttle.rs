@@ -0,0 +1,231 @@
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+// the messages per minute are counted over this window
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// What happens to greetings while the chat is busy.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ThrottleMode {
+    // the greeted users are welcomed together every batch interval
+    Aggregate,
+    // nobody is greeted until the chat calms down
+    Suppress,
+}
+
+impl FromStr for ThrottleMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim().to_lowercase().as_str() {
+            "aggregate" => Ok(ThrottleMode::Aggregate),
+            "suppress" => Ok(ThrottleMode::Suppress),
+            _ => Err(format!(
+                "unknown throttle mode {}, expected aggregate or suppress",
+                mode
+            )),
+        }
+    }
+}
+
+/// When the chat counts as busy and how greetings are sent then,
+/// e.g. more than 60 messages per minute with a batch every 3 minutes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ThrottleConfig {
+    pub threshold: usize,
+    pub batch_interval: Duration,
+    pub mode: ThrottleMode,
+}
+
+/// How a greeting is sent at the current chat rate.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GreetingDecision {
+    Individual,
+    // the user waits for the next batch, which is to be scheduled if the user is its first
+    Batched { first: bool },
+    Suppressed,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Transition {
+    Busy,
+    // the chat calmed down, the users still waiting for their batch are welcomed now
+    Calm(Vec<String>),
+}
+
+/// Counts the chat messages per minute and batches or holds back greetings while the chat is
+/// busy, e.g. after a raid. The chat calms down at half the threshold, so that a rate around
+/// the threshold does not switch back and forth.
+#[derive(Debug)]
+pub struct GreetingThrottle {
+    config: ThrottleConfig,
+    // the oldest first
+    messages: VecDeque<Instant>,
+    busy: bool,
+    batch: Vec<String>,
+    // how often the chat became busy or calm since the bot started
+    switches: u32,
+}
+
+impl GreetingThrottle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            config,
+            messages: VecDeque::new(),
+            busy: false,
+            batch: Vec::new(),
+            switches: 0,
+        }
+    }
+
+    pub fn batch_interval(&self) -> Duration {
+        self.config.batch_interval
+    }
+
+    pub fn mode(&self) -> ThrottleMode {
+        self.config.mode
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.busy
+    }
+
+    pub fn switches(&self) -> u32 {
+        self.switches
+    }
+
+    /// The messages within the last minute.
+    pub fn rate(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Counts a chat message, returns the transition if the chat became busy or calm.
+    pub fn record_message(&mut self, now: Instant) -> Option<Transition> {
+        while self
+            .messages
+            .front()
+            .is_some_and(|sent_at| now.saturating_duration_since(*sent_at) >= RATE_WINDOW)
+        {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(now);
+        if !self.busy && self.rate() > self.config.threshold {
+            self.busy = true;
+            self.switches += 1;
+            Some(Transition::Busy)
+        } else if self.busy && self.rate() <= (self.config.threshold / 2).max(1) {
+            self.busy = false;
+            self.switches += 1;
+            Some(Transition::Calm(self.take_batch()))
+        } else {
+            None
+        }
+    }
+
+    pub fn greet(&mut self, user: &str) -> GreetingDecision {
+        if !self.busy {
+            return GreetingDecision::Individual;
+        }
+        match self.config.mode {
+            ThrottleMode::Suppress => GreetingDecision::Suppressed,
+            ThrottleMode::Aggregate => {
+                self.batch.push(user.to_owned());
+                GreetingDecision::Batched {
+                    first: self.batch.len() == 1,
+                }
+            }
+        }
+    }
+
+    /// The users waiting for their batch, in the order they chatted.
+    pub fn take_batch(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.batch)
+    }
+
+    /// Forgets the counted messages and the users waiting for their batch, so that the chat is
+    /// calm again. The switches are kept, they count since the bot started.
+    pub fn reset(&mut self) {
+        self.messages.clear();
+        self.busy = false;
+        self.batch.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(mode: ThrottleMode) -> GreetingThrottle {
+        GreetingThrottle::new(ThrottleConfig {
+            threshold: 10,
+            batch_interval: Duration::from_secs(180),
+            mode,
+        })
+    }
+
+    #[test]
+    fn batching_greetings_while_the_chat_is_busy() {
+        let mut throttle = throttle(ThrottleMode::Aggregate);
+        let start = Instant::now();
+        let seconds = |seconds: u64| start + Duration::from_secs(seconds);
+        // 10 messages within a minute are still calm
+        for second in 0..10 {
+            assert_eq!(throttle.record_message(seconds(second)), None);
+        }
+        assert_eq!(throttle.greet("alice"), GreetingDecision::Individual);
+        assert_eq!(throttle.record_message(seconds(10)), Some(Transition::Busy));
+        assert_eq!(
+            throttle.greet("alice"),
+            GreetingDecision::Batched { first: true }
+        );
+        assert_eq!(
+            throttle.greet("bob"),
+            GreetingDecision::Batched { first: false }
+        );
+        assert_eq!(throttle.take_batch(), vec!["alice", "bob"]);
+        assert_eq!(
+            throttle.greet("carol"),
+            GreetingDecision::Batched { first: true }
+        );
+        // the first messages leave the window, 6 messages per minute are not calm yet
+        assert_eq!(throttle.record_message(seconds(65)), None);
+        assert!(throttle.is_busy());
+        assert_eq!(throttle.rate(), 6);
+        assert_eq!(
+            throttle.record_message(seconds(68)),
+            Some(Transition::Calm(vec!["carol".to_owned()]))
+        );
+        assert_eq!(throttle.rate(), 4);
+        assert_eq!(throttle.greet("dave"), GreetingDecision::Individual);
+        assert_eq!(throttle.switches(), 2);
+    }
+
+    #[test]
+    fn suppressing_greetings_while_the_chat_is_busy() {
+        let mut throttle = throttle(ThrottleMode::Suppress);
+        let start = Instant::now();
+        let mut transitions = Vec::new();
+        for second in 0..=10 {
+            transitions.extend(throttle.record_message(start + Duration::from_secs(second)));
+        }
+        assert_eq!(throttle.greet("alice"), GreetingDecision::Suppressed);
+        assert!(throttle.take_batch().is_empty());
+        // a quiet chat is calm with its next message
+        transitions.extend(throttle.record_message(start + Duration::from_secs(600)));
+        assert_eq!(
+            transitions,
+            vec![Transition::Busy, Transition::Calm(Vec::new())]
+        );
+        assert_eq!(throttle.greet("alice"), GreetingDecision::Individual);
+    }
+
+    #[test]
+    fn parsing_throttle_modes() {
+        assert_eq!("Aggregate".parse(), Ok(ThrottleMode::Aggregate));
+        assert_eq!(" suppress".parse(), Ok(ThrottleMode::Suppress));
+        assert!("mute".parse::<ThrottleMode>().is_err());
+    }
+}
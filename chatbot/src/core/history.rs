@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+
+// the number of chat messages remembered per channel unless another one is configured
+pub const DEFAULT_HISTORY_SIZE: usize = 500;
+
+/// A chat message remembered for the moderators, e.g. for `!context`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryEntry {
+    pub user: String,
+    pub text: String,
+    pub sent_at: DateTime<Utc>,
+    pub message_id: Option<String>,
+    // the message was deleted by a moderator or with a timeout or ban of its user,
+    // it is kept so that the moderators see what was removed
+    pub deleted: bool,
+}
+
+/// The last chat messages of each channel, the oldest are forgotten first.
+/// The buffers grow with the messages up to the size, so that a large size costs nothing
+/// in quiet channels.
+#[derive(Debug)]
+pub struct MessageHistory {
+    size: usize,
+    // by lowercase channel name without `#`
+    channels: HashMap<String, VecDeque<HistoryEntry>>,
+}
+
+impl MessageHistory {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, channel: &str, entry: HistoryEntry) {
+        if self.size == 0 {
+            return;
+        }
+        let messages = self.channels.entry(channel.to_lowercase()).or_default();
+        while messages.len() >= self.size {
+            messages.pop_front();
+        }
+        messages.push_back(entry);
+    }
+
+    /// Marks the message with the id as deleted (CLEARMSG).
+    pub fn delete_message(&mut self, channel: &str, message_id: &str) {
+        self.delete(channel, |entry| {
+            entry.message_id.as_deref() == Some(message_id)
+        });
+    }
+
+    /// Marks the messages of the user as deleted (CLEARCHAT of a timeout or ban),
+    /// or all messages of the channel if no user is given (CLEARCHAT of `/clear`).
+    pub fn delete_messages_of(&mut self, channel: &str, user: Option<&str>) {
+        self.delete(channel, |entry| {
+            user.is_none_or(|user| entry.user.eq_ignore_ascii_case(user))
+        });
+    }
+
+    fn delete(&mut self, channel: &str, matches: impl Fn(&HistoryEntry) -> bool) {
+        if let Some(messages) = self.channels.get_mut(&channel.to_lowercase()) {
+            messages
+                .iter_mut()
+                .filter(|entry| matches(entry))
+                .for_each(|entry| entry.deleted = true);
+        }
+    }
+
+    /// The last messages of the user in the channel, the oldest first.
+    pub fn last_messages_of(&self, channel: &str, user: &str, count: usize) -> Vec<&HistoryEntry> {
+        let mut messages: Vec<&HistoryEntry> = self
+            .channels
+            .get(&channel.to_lowercase())
+            .into_iter()
+            .flat_map(|messages| messages.iter().rev())
+            .filter(|entry| entry.user.eq_ignore_ascii_case(user))
+            .take(count)
+            .collect();
+        messages.reverse();
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user: &str, text: &str, id: &str) -> HistoryEntry {
+        HistoryEntry {
+            user: user.to_owned(),
+            text: text.to_owned(),
+            sent_at: Utc::now(),
+            message_id: Some(id.to_owned()),
+            deleted: false,
+        }
+    }
+
+    fn texts(entries: Vec<&HistoryEntry>) -> Vec<&str> {
+        entries.iter().map(|entry| entry.text.as_str()).collect()
+    }
+
+    #[test]
+    fn evicting_the_oldest_messages() {
+        let mut history = MessageHistory::new(3);
+        for (index, user) in ["carkhy", "alice", "carkhy", "carkhy", "alice"]
+            .iter()
+            .enumerate()
+        {
+            history.record(
+                "CaptainCallback",
+                entry(user, &format!("message {}", index), &index.to_string()),
+            );
+        }
+        history.record("other", entry("carkhy", "elsewhere", "5"));
+        assert_eq!(history.channels["captaincallback"].len(), 3);
+        // message 0 was evicted first
+        assert_eq!(
+            texts(history.last_messages_of("captaincallback", "Carkhy", 5)),
+            vec!["message 2", "message 3"]
+        );
+        assert_eq!(
+            texts(history.last_messages_of("captaincallback", "carkhy", 1)),
+            vec!["message 3"]
+        );
+        assert_eq!(
+            texts(history.last_messages_of("other", "carkhy", 5)),
+            vec!["elsewhere"]
+        );
+        assert!(history.last_messages_of("nowhere", "carkhy", 5).is_empty());
+    }
+
+    #[test]
+    fn marking_deleted_messages() {
+        let mut history = MessageHistory::new(10);
+        history.record("channel", entry("carkhy", "hello", "1"));
+        history.record("channel", entry("spammer", "buy followers", "2"));
+        history.record("channel", entry("spammer", "cheap viewers", "3"));
+        history.record("channel", entry("alice", "hi", "4"));
+
+        history.delete_message("channel", "3");
+        let deleted = |history: &MessageHistory, user: &str| -> Vec<bool> {
+            history
+                .last_messages_of("channel", user, 5)
+                .iter()
+                .map(|entry| entry.deleted)
+                .collect()
+        };
+        assert_eq!(deleted(&history, "spammer"), vec![false, true]);
+        history.delete_messages_of("channel", Some("Spammer"));
+        assert_eq!(deleted(&history, "spammer"), vec![true, true]);
+        assert_eq!(deleted(&history, "carkhy"), vec![false]);
+        history.delete_messages_of("channel", None);
+        assert_eq!(deleted(&history, "carkhy"), vec![true]);
+        assert_eq!(deleted(&history, "alice"), vec![true]);
+    }
+
+    #[test]
+    fn remembering_nothing_without_a_size() {
+        let mut history = MessageHistory::new(0);
+        history.record("channel", entry("carkhy", "hello", "1"));
+        assert!(history.last_messages_of("channel", "carkhy", 5).is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use serde_json::Value;
+use std::fs;
+use thiserror::Error;
+
+// twitch rejects longer chat messages
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+// the section filled with the current game from the twitch api
+pub const GAME_SECTION: &str = "game";
+const SEPARATOR: &str = " | ";
+
+#[derive(Debug, Error)]
+pub enum InfoError {
+    #[error("Could not read info sections [{}]", .0)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid info sections [{}]", .0)]
+    Invalid(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InfoSection {
+    pub name: String,
+    // None for the game section
+    pub text: Option<String>,
+    // disabled sections are left out of `!info`, but can still be addressed by name
+    pub enabled: bool,
+}
+
+/// The sections of `!info` in the order they are shown, e.g. schedule, socials, game and rules.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct InfoConfig {
+    pub sections: Vec<InfoSection>,
+}
+
+impl InfoConfig {
+    /// Loads the sections from a json file like
+    /// `[{"name": "schedule", "text": "Mon, Wed, Fri at 7pm"}, {"name": "game"}, {"name": "rules", "text": "Be nice!", "enabled": false}]`
+    pub fn load(path: &str) -> Result<Self, InfoError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, InfoError> {
+        let sections: Value =
+            serde_json::from_str(json).map_err(|err| InfoError::Invalid(err.to_string()))?;
+        let sections = sections
+            .as_array()
+            .ok_or_else(|| InfoError::Invalid("sections must be a list".to_owned()))?
+            .iter()
+            .map(|section| {
+                let name = section["name"]
+                    .as_str()
+                    .ok_or_else(|| InfoError::Invalid("section without name".to_owned()))?
+                    .to_lowercase();
+                let text = section["text"].as_str().map(String::from);
+                if text.is_none() && name != GAME_SECTION {
+                    return Err(InfoError::Invalid(format!("section {} has no text", name)));
+                }
+                Ok(InfoSection {
+                    name,
+                    text,
+                    enabled: section["enabled"].as_bool().unwrap_or(true),
+                })
+            })
+            .collect::<Result<_, InfoError>>()?;
+        Ok(Self { sections })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// The sections shown for `!info` (all enabled ones) or `!info <name>` (only that one).
+    /// Unknown names select no section.
+    pub fn select(&self, name: Option<&str>) -> Vec<&InfoSection> {
+        match name {
+            Some(name) => self
+                .sections
+                .iter()
+                .filter(|section| section.name == name.to_lowercase())
+                .collect(),
+            None => self
+                .sections
+                .iter()
+                .filter(|section| section.enabled)
+                .collect(),
+        }
+    }
+}
+
+/// Joins the parts into as few messages as possible, none longer than `max_length` characters.
+/// Parts which are too long on their own are split between words.
+pub fn split_message(parts: &[String], max_length: usize) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for part in parts {
+        for piece in split_part(part, max_length) {
+            let length = current.chars().count();
+            if length > 0 && length + SEPARATOR.len() + piece.chars().count() > max_length {
+                messages.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str(SEPARATOR);
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() {
+        messages.push(current);
+    }
+    messages
+}
+
+fn split_part(part: &str, max_length: usize) -> Vec<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in part.split_whitespace() {
+        let length = current.chars().count();
+        if length > 0 && length + 1 + word.chars().count() > max_length {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+        // a single word longer than a message is cut
+        while current.chars().count() > max_length {
+            let cut: String = current.chars().take(max_length).collect();
+            current = current.chars().skip(max_length).collect();
+            pieces.push(cut);
+        }
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Splits a list into pages of comma separated items, none longer than `max_length` characters
+/// unless a single item is. Unlike messages, the items are never split.
+pub fn paginate(items: &[String], max_length: usize) -> Vec<String> {
+    let mut pages: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for item in items {
+        let length = current.chars().count();
+        if length > 0 && length + 2 + item.chars().count() > max_length {
+            pages.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str(", ");
+        }
+        current.push_str(item);
+    }
+    if !current.is_empty() {
+        pages.push(current);
+    }
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTIONS: &str = r#"[
+        {"name": "schedule", "text": "Mon, Wed, Fri at 7pm"},
+        {"name": "Game"},
+        {"name": "socials", "text": "twitter.com/captaincallback"},
+        {"name": "rules", "text": "Be nice!", "enabled": false}
+    ]"#;
+
+    #[test]
+    fn selecting_sections() {
+        let config = InfoConfig::from_json(SECTIONS).unwrap();
+        let names = |sections: Vec<&InfoSection>| -> Vec<String> {
+            sections
+                .iter()
+                .map(|section| section.name.clone())
+                .collect()
+        };
+        assert_eq!(
+            names(config.select(None)),
+            vec!["schedule", "game", "socials"]
+        );
+        assert_eq!(names(config.select(Some("Rules"))), vec!["rules"]);
+        assert!(config.select(Some("discord")).is_empty());
+        assert!(InfoConfig::from_json(r#"[{"name": "rules"}]"#).is_err());
+    }
+
+    #[test]
+    fn splitting_long_messages() {
+        let parts = vec!["a".repeat(6), "b".repeat(6), "c".repeat(3)];
+        assert_eq!(split_message(&parts, 15), vec!["aaaaaa | bbbbbb", "ccc"]);
+        assert_eq!(
+            split_message(&["one two three four".to_owned()], 9),
+            vec!["one two", "three", "four"]
+        );
+        assert_eq!(
+            split_message(&["abcdefghij".to_owned()], 4),
+            vec!["abcd", "efgh", "ij"]
+        );
+        assert!(split_message(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn paginating_lists() {
+        let items: Vec<String> = ["alice", "bob", "carol", "dave"]
+            .iter()
+            .map(|item| item.to_string())
+            .collect();
+        assert_eq!(paginate(&items, 12), vec!["alice, bob", "carol, dave"]);
+        assert_eq!(paginate(&items, 100), vec!["alice, bob, carol, dave"]);
+        assert_eq!(paginate(&items[..1], 3), vec!["alice"]);
+        assert!(paginate(&[], 10).is_empty());
+    }
+}
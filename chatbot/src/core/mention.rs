@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::template::Template;
+
+// a chatter gets one reply to their mentions in this time unless another cooldown is configured
+pub const DEFAULT_MENTION_COOLDOWN: Duration = Duration::from_secs(300);
+
+/// The quip the bot replies with when a chatter mentions it, at most once per chatter in the cooldown.
+#[derive(Debug)]
+pub struct MentionReply {
+    template: Template,
+    cooldown: Duration,
+    // the last replies by lowercase name of the chatter, only while they are cooling down
+    last_replies: HashMap<String, Instant>,
+}
+
+impl MentionReply {
+    pub fn new(template: Template, cooldown: Duration) -> Self {
+        Self {
+            template,
+            cooldown,
+            last_replies: HashMap::new(),
+        }
+    }
+
+    /// The template of the reply if the chatter may get one now, which then starts their cooldown.
+    pub fn reply(&mut self, user_name: &str, now: Instant) -> Option<&Template> {
+        let cooldown = self.cooldown;
+        self.last_replies
+            .retain(|_, last_reply| now.saturating_duration_since(*last_reply) < cooldown);
+        let name = user_name.to_lowercase();
+        if self.last_replies.contains_key(&name) {
+            return None;
+        }
+        self.last_replies.insert(name, now);
+        Some(&self.template)
+    }
+
+    /// Ends the cooldowns of all chatters.
+    pub fn reset(&mut self) {
+        self.last_replies.clear();
+    }
+}
+
+/// Whether the text mentions the name as a word of its own, e.g. `@Botanist`, `botanist,` or
+/// `hi BOTANIST!`, but not `botanists` or `mybotanist`.
+pub fn mentions(text: &str, name: &str) -> bool {
+    !name.is_empty()
+        && text.split_whitespace().any(|word| {
+            word.strip_prefix('@')
+                .unwrap_or(word)
+                .trim_end_matches(|c: char| c.is_ascii_punctuation())
+                .eq_ignore_ascii_case(name)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finding_mentions() {
+        assert!(mentions("@botanist hi", "botanist"));
+        assert!(mentions("hi Botanist", "botanist"));
+        assert!(mentions("thanks @BOTANIST!!", "botanist"));
+        assert!(mentions("botanist, what is this?", "botanist"));
+        assert!(mentions("is that you botanist?!", "botanist"));
+        assert!(!mentions("the botanists are here", "botanist"));
+        assert!(!mentions("hi @mybotanist", "botanist"));
+        assert!(!mentions("botanist_fan says hi", "botanist"));
+        assert!(!mentions("@@botanist", "botanist"));
+        assert!(!mentions("hi", ""));
+    }
+
+    #[test]
+    fn cooling_down_per_chatter() {
+        let mut reply = MentionReply::new(Template::parse("hi").unwrap(), Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(reply.reply("Alice", start).is_some());
+        assert!(reply
+            .reply("alice", start + Duration::from_secs(59))
+            .is_none());
+        assert!(reply
+            .reply("bob", start + Duration::from_secs(59))
+            .is_some());
+        assert!(reply
+            .reply("alice", start + Duration::from_secs(60))
+            .is_some());
+    }
+
+    #[test]
+    fn forgetting_chatters_after_their_cooldown() {
+        let mut reply = MentionReply::new(Template::parse("hi").unwrap(), Duration::from_secs(60));
+        let start = Instant::now();
+        for user in ["alice", "bob", "carol"] {
+            reply.reply(user, start);
+        }
+        assert_eq!(reply.last_replies.len(), 3);
+        reply.reply("dave", start + Duration::from_secs(60));
+        assert_eq!(reply.last_replies.len(), 1);
+    }
+}
@@ -0,0 +1,310 @@
+use super::template::Template;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+};
+use thiserror::Error;
+use toml::{Table, Value};
+
+const FALLBACK_CATALOG: &str = include_str!("../../locales/en.toml");
+const LOCALES_DIRECTORY: &str = "./locales";
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error("Could not read message catalog [{}]", .0)]
+    Io(#[from] io::Error),
+    #[error("Could not parse message catalog [{}]", .0)]
+    Parse(#[from] toml::de::Error),
+    #[error("Invalid message catalog entry [{}]", .0)]
+    InvalidEntry(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Message {
+    Text(String),
+    // simple plural rule: "one" is used for a count of 1, "other" for everything else
+    Plural { one: String, other: String },
+}
+
+impl Message {
+    fn from_value(key: &str, value: &Value) -> Result<Self, CatalogError> {
+        match value {
+            Value::String(text) => Ok(Message::Text(text.to_owned())),
+            Value::Table(forms) => match (forms.get("one"), forms.get("other")) {
+                (Some(Value::String(one)), Some(Value::String(other))) => Ok(Message::Plural {
+                    one: one.to_owned(),
+                    other: other.to_owned(),
+                }),
+                _ => Err(CatalogError::InvalidEntry(format!(
+                    "{}: plural forms need 'one' and 'other'",
+                    key
+                ))),
+            },
+            _ => Err(CatalogError::InvalidEntry(format!(
+                "{}: must be a string or a table of plural forms",
+                key
+            ))),
+        }
+    }
+
+    fn select(&self, count: i64) -> &str {
+        match self {
+            Message::Text(text) => text,
+            Message::Plural { one, .. } if count == 1 => one,
+            Message::Plural { other, .. } => other,
+        }
+    }
+
+    fn forms(&self) -> Vec<&str> {
+        match self {
+            Message::Text(text) => vec![text],
+            Message::Plural { one, other } => vec![one, other],
+        }
+    }
+
+    fn placeholders(&self) -> BTreeSet<&str> {
+        match self {
+            Message::Text(text) => placeholders(text),
+            Message::Plural { one, other } => placeholders(one)
+                .union(&placeholders(other))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// Returns the names of all `{placeholder}`s in the given text.
+fn placeholders(text: &str) -> BTreeSet<&str> {
+    text.split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .collect()
+}
+
+fn parse_catalog(catalog: &str) -> Result<HashMap<String, Message>, CatalogError> {
+    let table: Table = catalog.parse()?;
+    table
+        .iter()
+        .map(|(key, value)| {
+            let message = Message::from_value(key, value)?;
+            // messages can use all tags of templates, so broken ones are rejected when loading
+            for form in message.forms() {
+                Template::parse(form)
+                    .map_err(|err| CatalogError::InvalidEntry(format!("{}: {}", key, err)))?;
+            }
+            Ok((key.to_owned(), message))
+        })
+        .collect()
+}
+
+/// All user facing messages of the chat bot, keyed by identifier.
+/// Messages missing in the loaded locale are taken from the embedded english catalog.
+#[derive(Debug)]
+pub struct MessageCatalog {
+    messages: HashMap<String, Message>,
+    fallback: HashMap<String, Message>,
+}
+
+impl Default for MessageCatalog {
+    fn default() -> Self {
+        let fallback = parse_catalog(FALLBACK_CATALOG).expect("Invalid fallback message catalog");
+        Self {
+            messages: fallback.clone(),
+            fallback,
+        }
+    }
+}
+
+impl MessageCatalog {
+    /// Loads the catalog of the given locale from `locales/<locale>.toml`.
+    pub fn load(locale: &str) -> Result<Self, CatalogError> {
+        let catalog = fs::read_to_string(format!("{}/{}.toml", LOCALES_DIRECTORY, locale))?;
+        Self::from_toml(&catalog)
+    }
+
+    pub fn from_toml(catalog: &str) -> Result<Self, CatalogError> {
+        Ok(Self {
+            messages: parse_catalog(catalog)?,
+            ..Self::default()
+        })
+    }
+
+    fn lookup(&self, key: &str) -> Option<&Message> {
+        self.messages.get(key).or_else(|| self.fallback.get(key))
+    }
+
+    /// Returns the message for the given key with all `{placeholder}`s replaced.
+    pub fn text(&self, key: &str, args: &[(&str, &str)]) -> String {
+        match self.lookup(key) {
+            Some(message) => fill_placeholders(message.select(0), args),
+            None => key.to_owned(),
+        }
+    }
+
+    /// Returns the message for the given key as template, e.g. for messages with random variants.
+    pub fn template(&self, key: &str) -> Template {
+        self.lookup(key)
+            .and_then(|message| Template::parse(message.select(0)).ok())
+            .unwrap_or_default()
+    }
+
+    /// Like `text`, but picks the plural form for `count` and provides it as `{count}`.
+    pub fn plural(&self, key: &str, count: i64, args: &[(&str, &str)]) -> String {
+        match self.lookup(key) {
+            Some(message) => {
+                let count_string = count.to_string();
+                let mut args = args.to_vec();
+                args.push(("count", &count_string));
+                fill_placeholders(message.select(count), &args)
+            }
+            None => key.to_owned(),
+        }
+    }
+
+    /// Compares the loaded catalog with the fallback catalog and
+    /// returns a warning for every missing, unknown or inconsistent entry.
+    pub fn validate(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut keys: Vec<&String> = self.fallback.keys().chain(self.messages.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            match (self.messages.get(key), self.fallback.get(key)) {
+                (None, Some(_)) => warnings.push(format!("Missing message '{}'", key)),
+                (Some(_), None) => warnings.push(format!("Unknown message '{}'", key)),
+                (Some(message), Some(fallback)) => {
+                    if message.placeholders() != fallback.placeholders() {
+                        warnings.push(format!(
+                            "Message '{}' has placeholders {:?} but expected {:?}",
+                            key,
+                            message.placeholders(),
+                            fallback.placeholders()
+                        ));
+                    }
+                }
+                (None, None) => (),
+            }
+        }
+        warnings
+    }
+}
+
+// in a single pass, so that a value is never filled in itself, e.g. a chat message with `{user}`
+fn fill_placeholders(text: &str, args: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let tag = &rest[start + 1..];
+        let arg = tag
+            .split_once('}')
+            .and_then(|(name, _)| args.iter().find(|(key, _)| *key == name));
+        match arg {
+            Some((name, value)) => {
+                filled.push_str(value);
+                rest = &tag[name.len() + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = tag;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTIAL_GERMAN_CATALOG: &str = r#"
+denied = "Verweigert!"
+slap = "{user} schlägt {target} mit einer großen Forelle"
+counter_value = "{name} steht bei {anzahl}"
+unknown = "Unbekannt"
+"#;
+
+    #[test]
+    fn embedded_catalogs_are_consistent() {
+        assert!(MessageCatalog::default().validate().is_empty());
+        let german = MessageCatalog::from_toml(include_str!("../../locales/de.toml")).unwrap();
+        assert!(german.validate().is_empty());
+    }
+
+    #[test]
+    fn falling_back_to_english() {
+        let catalog = MessageCatalog::from_toml(PARTIAL_GERMAN_CATALOG).unwrap();
+        assert_eq!(catalog.text("denied", &[]), "Verweigert!");
+        assert_eq!(
+            catalog.text("new_command_successful", &[]),
+            "The new command has been defined successfully."
+        );
+    }
+
+    #[test]
+    fn filling_placeholders() {
+        let catalog = MessageCatalog::from_toml(PARTIAL_GERMAN_CATALOG).unwrap();
+        assert_eq!(
+            catalog.text("slap", &[("user", "carkhy"), ("target", "chatter")]),
+            "carkhy schlägt chatter mit einer großen Forelle"
+        );
+        // values are taken as they are, also with braces
+        assert_eq!(
+            catalog.text("slap", &[("user", "{target}"), ("target", "{x} {user}")]),
+            "{target} schlägt {x} {user} mit einer großen Forelle"
+        );
+        assert_eq!(
+            catalog.text("counter_value", &[("name", "deaths")]),
+            "deaths steht bei {anzahl}"
+        );
+    }
+
+    #[test]
+    fn validating_partial_catalog() {
+        let catalog = MessageCatalog::from_toml(PARTIAL_GERMAN_CATALOG).unwrap();
+        let warnings = catalog.validate();
+        assert!(warnings.contains(&"Missing message 'help'".to_owned()));
+        assert!(warnings.contains(&"Unknown message 'unknown'".to_owned()));
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.starts_with("Message 'counter_value' has placeholders")));
+        assert!(!warnings.iter().any(|warning| warning.contains("'slap'")));
+    }
+
+    #[test]
+    fn selecting_plural_forms() {
+        let catalog = MessageCatalog::from_toml(
+            r#"
+[viewers]
+one = "{count} Zuschauer ist da"
+other = "{count} Zuschauer sind da"
+"#,
+        )
+        .unwrap();
+        assert_eq!(catalog.plural("viewers", 1, &[]), "1 Zuschauer ist da");
+        assert_eq!(catalog.plural("viewers", 0, &[]), "0 Zuschauer sind da");
+        assert_eq!(catalog.plural("viewers", 7, &[]), "7 Zuschauer sind da");
+    }
+
+    #[test]
+    fn rejecting_malformed_templates() {
+        let catalog =
+            MessageCatalog::from_toml(r#"slap = "{user} slaps {random:a trout|a herring""#);
+        assert!(
+            matches!(catalog, Err(CatalogError::InvalidEntry(entry)) if entry.starts_with("slap: Unclosed tag"))
+        );
+    }
+
+    #[test]
+    fn rejecting_incomplete_plural_forms() {
+        let catalog = MessageCatalog::from_toml(
+            r#"
+[viewers]
+one = "{count} Zuschauer ist da"
+"#,
+        );
+        assert!(matches!(catalog, Err(CatalogError::InvalidEntry(_))));
+    }
+}
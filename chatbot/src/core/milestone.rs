@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use super::template::Template;
+
+// celebrated unless another reply is configured
+pub const DEFAULT_MESSAGE_MILESTONE_REPLY: &str =
+    "{user} just sent their {milestone}th message in this channel!";
+// celebrations per stream unless another cap is configured
+pub const DEFAULT_MILESTONE_CAP: usize = 3;
+
+/// A milestone a chatter reached with their last message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Milestone {
+    Celebrated(u64),
+    // reached after the celebrations of the stream were used up, it is not celebrated later
+    Skipped(u64),
+}
+
+/// Celebrates chatters reaching a number of messages in the channel, e.g. their 1000th one.
+/// Each milestone is celebrated once per chatter, a chatter who crossed several milestones
+/// at once (e.g. because they were configured later) only gets the highest one.
+#[derive(Debug)]
+pub struct MessageMilestones {
+    template: Template,
+    // ascending
+    thresholds: Vec<u64>,
+    // the highest milestone reached by lowercase name of the chatter
+    reached: HashMap<String, u64>,
+    cap: usize,
+    celebrations: usize,
+}
+
+impl MessageMilestones {
+    pub fn new(template: Template, mut thresholds: Vec<u64>, cap: usize) -> Self {
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        Self {
+            template,
+            thresholds,
+            reached: HashMap::new(),
+            cap,
+            celebrations: 0,
+        }
+    }
+
+    /// Sets the (e.g. persisted) highest milestones reached by lowercase name of the chatter.
+    pub fn with_reached(self, reached: HashMap<String, u64>) -> Self {
+        Self { reached, ..self }
+    }
+
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+
+    /// The milestone the chatter reached with their number of messages, if it is a new one.
+    pub fn reach(&mut self, user_name: &str, messages: u64) -> Option<Milestone> {
+        let passed = self
+            .thresholds
+            .partition_point(|threshold| *threshold <= messages);
+        let milestone = *self.thresholds.get(passed.checked_sub(1)?)?;
+        let name = user_name.to_lowercase();
+        if self
+            .reached
+            .get(&name)
+            .is_some_and(|reached| *reached >= milestone)
+        {
+            return None;
+        }
+        self.reached.insert(name, milestone);
+        if self.celebrations >= self.cap {
+            return Some(Milestone::Skipped(milestone));
+        }
+        self.celebrations += 1;
+        Some(Milestone::Celebrated(milestone))
+    }
+
+    /// Starts counting the celebrations of a new stream.
+    pub fn new_stream(&mut self) {
+        self.celebrations = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn milestones(cap: usize) -> MessageMilestones {
+        MessageMilestones::new(Template::parse("{user}").unwrap(), vec![1000, 100, 10], cap)
+    }
+
+    #[test]
+    fn reaching_each_milestone_once() {
+        let mut milestones = milestones(10);
+        assert_eq!(milestones.reach("alice", 9), None);
+        assert_eq!(
+            milestones.reach("alice", 10),
+            Some(Milestone::Celebrated(10))
+        );
+        assert_eq!(milestones.reach("Alice", 10), None);
+        assert_eq!(milestones.reach("alice", 99), None);
+        assert_eq!(
+            milestones.reach("alice", 100),
+            Some(Milestone::Celebrated(100))
+        );
+        // only the highest of the milestones crossed at once
+        assert_eq!(
+            milestones.reach("bob", 1500),
+            Some(Milestone::Celebrated(1000))
+        );
+        assert_eq!(milestones.reach("bob", 1501), None);
+
+        let mut milestones = milestones.with_reached(HashMap::from([("carol".to_owned(), 100)]));
+        assert_eq!(milestones.reach("carol", 101), None);
+        assert_eq!(
+            milestones.reach("carol", 1000),
+            Some(Milestone::Celebrated(1000))
+        );
+    }
+
+    #[test]
+    fn capping_the_celebrations_of_a_stream() {
+        let mut milestones = milestones(2);
+        assert_eq!(
+            milestones.reach("alice", 10),
+            Some(Milestone::Celebrated(10))
+        );
+        assert_eq!(
+            milestones.reach("bob", 100),
+            Some(Milestone::Celebrated(100))
+        );
+        assert_eq!(milestones.reach("carol", 10), Some(Milestone::Skipped(10)));
+        assert_eq!(milestones.reach("carol", 11), None);
+        milestones.new_stream();
+        assert_eq!(
+            milestones.reach("dave", 10),
+            Some(Milestone::Celebrated(10))
+        );
+    }
+}
@@ -1,5 +1,68 @@
+mod audit;
+mod autoresponder;
+mod backup;
 mod bot;
+mod chat_mode;
+mod color;
 mod command;
+mod confirmation;
+mod counter;
+mod counts;
+mod deaths;
+mod dice;
+mod duel;
+mod emote_set;
+mod gate;
+mod glossary;
+mod goal;
+mod golive;
+mod greeting_throttle;
+mod history;
+mod info;
+mod mention;
+mod messages;
+mod milestone;
+mod mode_policy;
+mod perf;
+mod prediction;
+mod protection;
+mod quiz;
+mod raffle;
+mod raid;
+mod response;
+mod roles;
+mod shoutout;
+mod stats;
+mod store;
+mod template;
+mod trending;
+mod vod;
 
+pub use audit::{AuditLog, DEFAULT_AUDIT_LOG};
+pub use autoresponder::Autoresponder;
+pub use backup::{Backup, RestoreMode};
 pub use bot::ChatBot;
+pub use color::{chat_color_names, parse_chat_color};
 pub use command::ChatBotCommand;
+pub use gate::GateConfig;
+pub use glossary::Glossary;
+pub use goal::GoalKind;
+pub use greeting_throttle::{ThrottleConfig, ThrottleMode};
+pub use history::DEFAULT_HISTORY_SIZE;
+pub use info::InfoConfig;
+pub use mention::DEFAULT_MENTION_COOLDOWN;
+pub use messages::MessageCatalog;
+pub use milestone::{MessageMilestones, DEFAULT_MESSAGE_MILESTONE_REPLY, DEFAULT_MILESTONE_CAP};
+pub use mode_policy::{ModePolicy, ProactiveMessage};
+pub use perf::DEFAULT_SLOW_COMMAND;
+pub use protection::ProtectionConfig;
+pub use quiz::QuizQuestion;
+pub use response::{ResponseRouting, ResponseTarget};
+pub use store::{
+    load_counters, load_disabled_commands, load_game_deaths, load_goals, load_message_milestones,
+    load_quiz_points, load_roles, load_roll_record, load_stream_snapshots, load_usage_stats,
+    store_command_disabled, store_counter, store_game_deaths, store_goal, store_message_milestone,
+    store_quiz_points, store_role, store_roll_record, store_stream_snapshot, usage_totals,
+    FileStore, Store, UsageWriter, BOT_STORE_FILE,
+};
+pub use template::Template;
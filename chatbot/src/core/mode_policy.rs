@@ -0,0 +1,74 @@
+use crate::connect::RoomState;
+use std::{collections::HashSet, str::FromStr};
+
+/// Messages the bot sends on its own, without being asked by a command.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ProactiveMessage {
+    Timer,
+    Greeting,
+}
+
+impl FromStr for ProactiveMessage {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "timers" => Ok(ProactiveMessage::Timer),
+            "greetings" => Ok(ProactiveMessage::Greeting),
+            _ => Err(format!(
+                "unknown message kind {}, expected timers or greetings",
+                name
+            )),
+        }
+    }
+}
+
+/// Which proactive messages are held back while the chat is in emote-only or sub-only mode,
+/// which is usually how hostile raids are met. Replies to commands are not affected.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ModePolicy {
+    suppressed: HashSet<ProactiveMessage>,
+    // the last suppressed timer message is sent once when the chat is not restricted anymore
+    pub resend_timer: bool,
+}
+
+impl Default for ModePolicy {
+    fn default() -> Self {
+        Self::new(
+            &[ProactiveMessage::Timer, ProactiveMessage::Greeting],
+            false,
+        )
+    }
+}
+
+impl ModePolicy {
+    pub fn new(suppressed: &[ProactiveMessage], resend_timer: bool) -> Self {
+        Self {
+            suppressed: suppressed.iter().copied().collect(),
+            resend_timer,
+        }
+    }
+
+    pub fn suppresses(&self, message: ProactiveMessage, room: &RoomState) -> bool {
+        room.is_restrictive() && self.suppressed.contains(&message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppressing_configured_messages() {
+        let restricted = RoomState {
+            emote_only: Some(true),
+            ..RoomState::default()
+        };
+        let policy = ModePolicy::new(&[ProactiveMessage::Timer], false);
+        assert!(policy.suppresses(ProactiveMessage::Timer, &restricted));
+        assert!(!policy.suppresses(ProactiveMessage::Greeting, &restricted));
+        assert!(!policy.suppresses(ProactiveMessage::Timer, &RoomState::default()));
+        assert_eq!("Greetings".parse(), Ok(ProactiveMessage::Greeting));
+        assert!("triggers".parse::<ProactiveMessage>().is_err());
+    }
+}
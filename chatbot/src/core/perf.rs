@@ -0,0 +1,145 @@
+use std::{collections::HashMap, time::Duration};
+
+// handlers taking longer than this are logged unless another threshold is configured
+pub const DEFAULT_SLOW_COMMAND: Duration = Duration::from_millis(50);
+// bucket i counts the durations below 2^(i+1) microseconds, the last one all longer ones
+const BUCKETS: usize = 32;
+
+/// The durations of one command as a histogram of power of two buckets,
+/// so that recording is a counter increment without allocating.
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    buckets: [u32; BUCKETS],
+    count: u32,
+    // handlers slower than the threshold
+    slow: u32,
+}
+
+impl Histogram {
+    fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros().max(1);
+        let bucket = (u128::BITS - micros.leading_zeros() - 1) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+    }
+
+    // the upper bound of the bucket holding the percentile, e.g. 0.95 for p95
+    fn percentile(&self, percentile: f64) -> Duration {
+        let rank = (self.count as f64 * percentile).ceil().max(1.0) as u32;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(1 << (bucket + 1));
+            }
+        }
+        Duration::from_micros(1 << BUCKETS)
+    }
+}
+
+/// The summary of a command for `!perf`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CommandPerf {
+    pub name: String,
+    pub count: u32,
+    pub slow: u32,
+    pub p50: Duration,
+    pub p95: Duration,
+}
+
+/// How long the handlers of the commands took since the bot started.
+#[derive(Debug)]
+pub struct CommandTimings {
+    slow_threshold: Duration,
+    commands: HashMap<String, Histogram>,
+}
+
+impl CommandTimings {
+    pub fn new(slow_threshold: Duration) -> Self {
+        Self {
+            slow_threshold,
+            commands: HashMap::new(),
+        }
+    }
+
+    /// Records the duration of a handler, returns whether it was slower than the threshold.
+    pub fn record(&mut self, command: &str, duration: Duration) -> bool {
+        let histogram = self.commands.entry(command.to_owned()).or_default();
+        histogram.record(duration);
+        let slow = duration > self.slow_threshold;
+        if slow {
+            histogram.slow += 1;
+        }
+        slow
+    }
+
+    /// The commands by their number of uses, the most used first.
+    pub fn summary(&self) -> Vec<CommandPerf> {
+        let mut summary: Vec<CommandPerf> = self
+            .commands
+            .iter()
+            .map(|(name, histogram)| CommandPerf {
+                name: name.to_owned(),
+                count: histogram.count,
+                slow: histogram.slow,
+                p50: histogram.percentile(0.5),
+                p95: histogram.percentile(0.95),
+            })
+            .collect();
+        summary.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        summary
+    }
+}
+
+/// A duration of a handler, e.g. `512 µs` or `16 ms`.
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_millis(1) {
+        format!("{} µs", duration.as_micros())
+    } else {
+        format!("{} ms", duration.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizing_timings() {
+        let mut timings = CommandTimings::new(Duration::from_millis(10));
+        for micros in [3, 5, 6, 7, 9, 100] {
+            assert!(!timings.record("roll", Duration::from_micros(micros)));
+        }
+        assert!(timings.record("lastclip", Duration::from_millis(20)));
+        assert_eq!(
+            timings.summary(),
+            vec![
+                CommandPerf {
+                    name: "roll".to_owned(),
+                    count: 6,
+                    slow: 0,
+                    p50: Duration::from_micros(8),
+                    p95: Duration::from_micros(128),
+                },
+                CommandPerf {
+                    name: "lastclip".to_owned(),
+                    count: 1,
+                    slow: 1,
+                    p50: Duration::from_micros(32768),
+                    p95: Duration::from_micros(32768),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bucketing_extreme_durations() {
+        let mut histogram = Histogram::default();
+        histogram.record(Duration::ZERO);
+        assert_eq!(histogram.buckets[0], 1);
+        histogram.record(Duration::from_secs(100_000));
+        assert_eq!(histogram.buckets[BUCKETS - 1], 1);
+        assert_eq!(format_duration(Duration::from_micros(512)), "512 µs");
+        assert_eq!(format_duration(Duration::from_millis(16)), "16 ms");
+    }
+}
@@ -0,0 +1,105 @@
+use std::{ops::RangeInclusive, time::Duration};
+
+// limits of the helix api
+const TITLE_LENGTH: RangeInclusive<usize> = 1..=45;
+const OUTCOME_TITLE_LENGTH: RangeInclusive<usize> = 1..=25;
+const OUTCOME_COUNT: RangeInclusive<usize> = 2..=10;
+const WINDOW_SECONDS: RangeInclusive<u64> = 30..=1800;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PredictionAction {
+    Start {
+        title: String,
+        outcomes: Vec<String>,
+        window: Duration,
+    },
+    Lock,
+    // number of the winning outcome, starting at 1
+    Resolve(usize),
+}
+
+impl PredictionAction {
+    /// Parses the (quoted) options of a prediction command,
+    /// e.g. `start "Will we beat the boss?" Yes No 120`, `lock` or `resolve 1`
+    pub fn parse(options: &[String]) -> Option<Self> {
+        match options {
+            [action] if action == "lock" => Some(PredictionAction::Lock),
+            [action, outcome] if action == "resolve" => outcome
+                .parse()
+                .ok()
+                .filter(|outcome| *outcome > 0)
+                .map(PredictionAction::Resolve),
+            [action, title, outcomes @ .., window] if action == "start" => {
+                let window = window.parse().ok().filter(|w| WINDOW_SECONDS.contains(w))?;
+                let valid = TITLE_LENGTH.contains(&title.chars().count())
+                    && OUTCOME_COUNT.contains(&outcomes.len())
+                    && outcomes
+                        .iter()
+                        .all(|outcome| OUTCOME_TITLE_LENGTH.contains(&outcome.chars().count()));
+                valid.then(|| PredictionAction::Start {
+                    title: title.to_owned(),
+                    outcomes: outcomes.to_vec(),
+                    window: Duration::from_secs(window),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::options;
+
+    #[test]
+    fn parsing_prediction_start() {
+        assert_eq!(
+            PredictionAction::parse(&options(&[
+                "start",
+                "Will we beat the boss?",
+                "Yes",
+                "No",
+                "120"
+            ])),
+            Some(PredictionAction::Start {
+                title: "Will we beat the boss?".to_owned(),
+                outcomes: vec!["Yes".to_owned(), "No".to_owned()],
+                window: Duration::from_secs(120),
+            })
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_prediction_start() {
+        // only one outcome
+        assert_eq!(
+            PredictionAction::parse(&options(&["start", "Title", "Yes", "120"])),
+            None
+        );
+        // window too short
+        assert_eq!(
+            PredictionAction::parse(&options(&["start", "Title", "Yes", "No", "10"])),
+            None
+        );
+        // missing window
+        assert_eq!(
+            PredictionAction::parse(&options(&["start", "Title", "Yes", "No"])),
+            None
+        );
+    }
+
+    #[test]
+    fn parsing_prediction_lock_and_resolve() {
+        assert_eq!(
+            PredictionAction::parse(&options(&["lock"])),
+            Some(PredictionAction::Lock)
+        );
+        assert_eq!(
+            PredictionAction::parse(&options(&["resolve", "2"])),
+            Some(PredictionAction::Resolve(2))
+        );
+        assert_eq!(PredictionAction::parse(&options(&["resolve", "0"])), None);
+        assert_eq!(PredictionAction::parse(&options(&["resolve"])), None);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::connect::{ChatSettings, RoomState};
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+// share of common shingles above which two messages count as near-identical
+const SIMILARITY: f64 = 0.7;
+const SHINGLE_LENGTH: usize = 3;
+
+/// When first messages count as a hate raid and what the bot does against it,
+/// e.g. more than 5 near-identical first messages within 10 seconds.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProtectionConfig {
+    pub max_messages: usize,
+    pub window: Duration,
+    // how long the protection lasts unless it is lifted with `!allclear`
+    pub duration: Duration,
+    // the chat modes turned on while the chat is protected
+    pub modes: ChatSettings,
+}
+
+/// A first message which was part of a burst, kept for the review after a hate raid.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Offender {
+    pub user: String,
+    pub message_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Observation {
+    // nothing suspicious
+    Calm,
+    // the burst which started the protection
+    Burst(Vec<Offender>),
+    // another message like the burst while the chat is protected
+    Offending(Offender),
+}
+
+#[derive(Debug)]
+struct FirstMessage {
+    received_at: Instant,
+    offender: Offender,
+    shingles: HashSet<String>,
+}
+
+#[derive(Debug)]
+struct ActiveProtection {
+    id: Uuid,
+    // the shingles of the message which started the burst
+    sample: HashSet<String>,
+    // the modes turned on by the bot, which it turns off again
+    enabled: ChatSettings,
+}
+
+/// Watches the first messages of users for bursts of near-identical text, which is how
+/// hate raids of fresh accounts look, and keeps the chat protected for a while after one.
+#[derive(Debug)]
+pub struct RaidProtection {
+    config: ProtectionConfig,
+    recent: VecDeque<FirstMessage>,
+    active: Option<ActiveProtection>,
+}
+
+impl RaidProtection {
+    pub fn new(config: ProtectionConfig) -> Self {
+        Self {
+            config,
+            recent: VecDeque::new(),
+            active: None,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.config.duration
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Looks at the first message of a user, the burst is returned when it exceeds the limit.
+    pub fn observe(&mut self, offender: Offender, text: &str, now: Instant) -> Observation {
+        let shingles = shingles(text);
+        if let Some(active) = &self.active {
+            return if similarity(&active.sample, &shingles) >= SIMILARITY {
+                Observation::Offending(offender)
+            } else {
+                Observation::Calm
+            };
+        }
+        while self.recent.front().is_some_and(|message| {
+            now.saturating_duration_since(message.received_at) > self.config.window
+        }) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(FirstMessage {
+            received_at: now,
+            offender,
+            shingles,
+        });
+        let newest = &self.recent[self.recent.len() - 1].shingles;
+        let burst: Vec<&FirstMessage> = self
+            .recent
+            .iter()
+            .filter(|message| similarity(&message.shingles, newest) >= SIMILARITY)
+            .collect();
+        if burst.len() <= self.config.max_messages {
+            return Observation::Calm;
+        }
+        let offenders = burst
+            .iter()
+            .map(|message| message.offender.clone())
+            .collect();
+        let sample = newest.clone();
+        self.recent.clear();
+        self.active = Some(ActiveProtection {
+            id: Uuid::new_v4(),
+            sample,
+            enabled: ChatSettings::default(),
+        });
+        Observation::Burst(offenders)
+    }
+
+    /// The id of the protection started by the last burst, which its expiry refers to,
+    /// and the configured modes which are not on yet and have to be turned on.
+    pub fn protect(&mut self, room: &RoomState) -> Option<(Uuid, ChatSettings)> {
+        let active = self.active.as_mut()?;
+        let modes = &self.config.modes;
+        active.enabled = ChatSettings {
+            follower_mode: modes
+                .follower_mode
+                .filter(|_| room.followers_only.unwrap_or(-1) < 0),
+            slow_mode: modes
+                .slow_mode
+                .filter(|_| room.slow.unwrap_or_default() == 0),
+            ..ChatSettings::default()
+        };
+        Some((active.id, active.enabled))
+    }
+
+    /// Ends the protection, or only the one with the given id (e.g. when it expired).
+    /// Returns the modes which the bot turned on and has to turn off again.
+    pub fn lift(&mut self, id: Option<Uuid>) -> Option<ChatSettings> {
+        if id.is_some() && self.active.as_ref().map(|active| active.id) != id {
+            return None;
+        }
+        let enabled = self.active.take()?.enabled;
+        Some(ChatSettings {
+            follower_mode: enabled.follower_mode.map(|_| false),
+            slow_mode: enabled.slow_mode.map(|_| false),
+            ..ChatSettings::default()
+        })
+    }
+}
+
+/// The overlapping character sequences of the text, ignoring case, punctuation and spacing,
+/// so that spam with small variations (e.g. `h a t e` or `hate!!1`) is still recognized.
+fn shingles(text: &str) -> HashSet<String> {
+    let normalized: Vec<char> = text
+        .chars()
+        .filter(|char| char.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if normalized.len() <= SHINGLE_LENGTH {
+        return HashSet::from([normalized.into_iter().collect()]);
+    }
+    normalized
+        .windows(SHINGLE_LENGTH)
+        .map(|shingle| shingle.iter().collect())
+        .collect()
+}
+
+// the jaccard index of the shingles
+fn similarity(first: &HashSet<String>, second: &HashSet<String>) -> f64 {
+    let union = first.union(second).count();
+    if union == 0 {
+        return 1.0;
+    }
+    first.intersection(second).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ProtectionConfig {
+        ProtectionConfig {
+            max_messages: 3,
+            window: Duration::from_secs(10),
+            duration: Duration::from_secs(600),
+            modes: ChatSettings {
+                follower_mode: Some(true),
+                slow_mode: Some(true),
+                ..ChatSettings::default()
+            },
+        }
+    }
+
+    fn offender(user: &str) -> Offender {
+        Offender {
+            user: user.to_owned(),
+            message_id: Some(format!("id-{}", user)),
+        }
+    }
+
+    #[test]
+    fn comparing_messages() {
+        let spam = shingles("get raided by the best viewers");
+        assert!(similarity(&spam, &shingles("GET RAIDED by the best viewers!!")) > 0.99);
+        assert!(similarity(&spam, &shingles("get raided by the best viewers 2")) >= SIMILARITY);
+        assert!(similarity(&spam, &shingles("g e t  r a i d e d by the best viewers")) > 0.99);
+        assert!(similarity(&spam, &shingles("hi, first time here, love the stream")) < 0.1);
+        assert_eq!(similarity(&shingles("!!!"), &shingles("")), 1.0);
+    }
+
+    #[test]
+    fn detecting_bursts_of_first_messages() {
+        let now = Instant::now();
+        let mut protection = RaidProtection::new(config());
+        assert_eq!(
+            protection.observe(offender("old"), "spam spam spam", now),
+            Observation::Calm
+        );
+        let later = now + Duration::from_secs(11);
+        // unrelated first messages do not add up
+        assert_eq!(
+            protection.observe(offender("newbie"), "hello everyone", later),
+            Observation::Calm
+        );
+        for user in ["bot1", "bot2", "bot3"] {
+            assert_eq!(
+                protection.observe(offender(user), "Spam spam spam!", later),
+                Observation::Calm
+            );
+        }
+        assert!(!protection.is_active());
+        // the first message left the window
+        assert_eq!(
+            protection.observe(offender("bot4"), "spam spam spam 4", later),
+            Observation::Burst(vec![
+                offender("bot1"),
+                offender("bot2"),
+                offender("bot3"),
+                offender("bot4")
+            ])
+        );
+        assert!(protection.is_active());
+        assert_eq!(
+            protection.observe(offender("bot5"), "spam spam spam", later),
+            Observation::Offending(offender("bot5"))
+        );
+        assert_eq!(
+            protection.observe(offender("friend"), "what is going on?", later),
+            Observation::Calm
+        );
+    }
+
+    #[test]
+    fn turning_modes_on_and_off_again() {
+        let now = Instant::now();
+        let mut protection = RaidProtection::new(config());
+        assert_eq!(protection.protect(&RoomState::default()), None);
+        for user in ["bot1", "bot2", "bot3", "bot4"] {
+            protection.observe(offender(user), "spam", now);
+        }
+        // slow mode is on already, so it is left alone
+        let room = RoomState {
+            slow: Some(30),
+            followers_only: Some(-1),
+            ..RoomState::default()
+        };
+        let (id, enabled) = protection.protect(&room).unwrap();
+        assert_eq!(
+            enabled,
+            ChatSettings {
+                follower_mode: Some(true),
+                ..ChatSettings::default()
+            }
+        );
+        // an expiry of an earlier protection does not end this one
+        assert_eq!(protection.lift(Some(Uuid::new_v4())), None);
+        assert!(protection.is_active());
+        assert_eq!(
+            protection.lift(Some(id)),
+            Some(ChatSettings {
+                follower_mode: Some(false),
+                ..ChatSettings::default()
+            })
+        );
+        assert!(!protection.is_active());
+        assert_eq!(protection.lift(None), None);
+    }
+}
@@ -0,0 +1,209 @@
+use serde_json::Value;
+use std::{collections::HashMap, fs};
+use thiserror::Error;
+use uuid::Uuid;
+
+// viewers answer with a single digit
+const MAX_OPTIONS: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum QuizError {
+    #[error("Could not read quiz questions [{}]", .0)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid quiz questions [{}]", .0)]
+    Invalid(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct QuizQuestion {
+    pub question: String,
+    pub options: Vec<String>,
+    // number of the correct option, starting at 1 like the answers of the viewers
+    pub answer: usize,
+}
+
+impl QuizQuestion {
+    /// Loads the question bank from a json file like
+    /// `[{"question": "Which planet is the largest?", "options": ["Mars", "Jupiter", "Venus"], "answer": 2}]`
+    pub fn load(path: &str) -> Result<Vec<Self>, QuizError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Vec<Self>, QuizError> {
+        let questions: Value =
+            serde_json::from_str(json).map_err(|err| QuizError::Invalid(err.to_string()))?;
+        questions
+            .as_array()
+            .ok_or_else(|| QuizError::Invalid("questions must be a list".to_owned()))?
+            .iter()
+            .enumerate()
+            .map(|(idx, question)| Self::from_value(idx + 1, question))
+            .collect()
+    }
+
+    fn from_value(number: usize, value: &Value) -> Result<Self, QuizError> {
+        let invalid = |reason: &str| QuizError::Invalid(format!("question {}: {}", number, reason));
+        let question = value["question"]
+            .as_str()
+            .ok_or_else(|| invalid("missing question"))?;
+        let options = value["options"]
+            .as_array()
+            .and_then(|options| {
+                options
+                    .iter()
+                    .map(|option| option.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .filter(|options| (2..=MAX_OPTIONS).contains(&options.len()))
+            .ok_or_else(|| invalid("options must be 2 to 4 texts"))?;
+        let answer = value["answer"]
+            .as_u64()
+            .map(|answer| answer as usize)
+            .filter(|answer| (1..=options.len()).contains(answer))
+            .ok_or_else(|| invalid("answer must be the number of an option"))?;
+        Ok(Self {
+            question: question.to_owned(),
+            options,
+            answer,
+        })
+    }
+
+    /// The options with their numbers, e.g. `1) Mars 2) Jupiter 3) Venus`
+    pub fn options_text(&self) -> String {
+        self.options
+            .iter()
+            .enumerate()
+            .map(|(idx, option)| format!("{}) {}", idx + 1, option))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn correct_option(&self) -> &str {
+        &self.options[self.answer - 1]
+    }
+}
+
+/// A running quiz question collecting the answers of the viewers.
+#[derive(Debug)]
+pub struct QuizRound {
+    pub question: QuizQuestion,
+    // identifies the timer ending the round
+    pub id: Uuid,
+    // the first answer of each user by lowercase name
+    answers: HashMap<String, usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuizResult {
+    // lowercase names of the users who answered correctly, sorted
+    pub correct_users: Vec<String>,
+    pub answer_count: usize,
+}
+
+impl QuizResult {
+    /// Percentage of the answers which were correct, rounded down.
+    pub fn percent_correct(&self) -> usize {
+        (self.correct_users.len() * 100)
+            .checked_div(self.answer_count)
+            .unwrap_or_default()
+    }
+}
+
+impl QuizRound {
+    pub fn new(question: QuizQuestion) -> Self {
+        Self {
+            question,
+            id: Uuid::new_v4(),
+            answers: HashMap::default(),
+        }
+    }
+
+    /// Counts a chat message as answer if it is just the number of an option.
+    /// Only the first answer of a user counts. Returns whether the answer was counted.
+    pub fn answer(&mut self, user: &str, text: &str) -> bool {
+        let answer = match text.trim().parse::<usize>() {
+            Ok(answer) if (1..=self.question.options.len()).contains(&answer) => answer,
+            _ => return false,
+        };
+        let user = user.to_lowercase();
+        if self.answers.contains_key(&user) {
+            return false;
+        }
+        self.answers.insert(user, answer);
+        true
+    }
+
+    pub fn result(&self) -> QuizResult {
+        let mut correct_users: Vec<String> = self
+            .answers
+            .iter()
+            .filter(|(_, answer)| **answer == self.question.answer)
+            .map(|(user, _)| user.to_owned())
+            .collect();
+        correct_users.sort();
+        QuizResult {
+            correct_users,
+            answer_count: self.answers.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUESTIONS: &str = r#"[{"question": "Which planet is the largest?", "options": ["Mars", "Jupiter", "Venus"], "answer": 2}]"#;
+
+    #[test]
+    fn parsing_questions() {
+        let questions = QuizQuestion::from_json(QUESTIONS).unwrap();
+        assert_eq!(
+            questions,
+            vec![QuizQuestion {
+                question: "Which planet is the largest?".to_owned(),
+                options: vec!["Mars".to_owned(), "Jupiter".to_owned(), "Venus".to_owned()],
+                answer: 2,
+            }]
+        );
+        assert_eq!(questions[0].options_text(), "1) Mars 2) Jupiter 3) Venus");
+        assert_eq!(questions[0].correct_option(), "Jupiter");
+    }
+
+    #[test]
+    fn rejecting_invalid_questions() {
+        assert!(QuizQuestion::from_json(r#"{"question": "?"}"#).is_err());
+        assert!(QuizQuestion::from_json(
+            r#"[{"question": "?", "options": ["a", "b"], "answer": 3}]"#
+        )
+        .is_err());
+        assert!(QuizQuestion::from_json(
+            r#"[{"question": "?", "options": ["a", "b", "c", "d", "e"], "answer": 1}]"#
+        )
+        .is_err());
+        assert!(
+            QuizQuestion::from_json(r#"[{"question": "?", "options": ["a"], "answer": 1}]"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn counting_answers() {
+        let mut round = QuizRound::new(QuizQuestion::from_json(QUESTIONS).unwrap().remove(0));
+        assert!(round.answer("Alice", "2"));
+        assert!(round.answer("bob", " 1 "));
+        assert!(!round.answer("alice", "1"));
+        assert!(!round.answer("carol", "4"));
+        assert!(!round.answer("carol", "2 because it's big"));
+        assert!(round.answer("carol", "2"));
+        let result = round.result();
+        assert_eq!(result.correct_users, vec!["alice", "carol"]);
+        assert_eq!(result.answer_count, 3);
+        assert_eq!(result.percent_correct(), 66);
+    }
+
+    #[test]
+    fn computing_percentage_without_answers() {
+        let round = QuizRound::new(QuizQuestion::from_json(QUESTIONS).unwrap().remove(0));
+        assert_eq!(round.result().percent_correct(), 0);
+    }
+}
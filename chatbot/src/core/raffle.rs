@@ -0,0 +1,161 @@
+use rand::Rng;
+use std::collections::HashSet;
+
+/// Who may enter a raffle and how many tickets they get.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RaffleRules {
+    pub followers_only: bool,
+    // chat messages an entrant needs to have sent since the bot connected
+    pub min_messages: u32,
+    // tickets of subscribers, everyone else has one
+    pub sub_luck: u32,
+}
+
+impl Default for RaffleRules {
+    fn default() -> Self {
+        Self {
+            followers_only: false,
+            min_messages: 0,
+            sub_luck: 1,
+        }
+    }
+}
+
+impl RaffleRules {
+    /// Parses the flags of `!raffle open`, e.g. `["--followers", "--messages", "5", "--luck", "3"]`.
+    pub fn parse(flags: &[String]) -> Option<Self> {
+        let mut rules = Self::default();
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            match flag.as_str() {
+                "--followers" => rules.followers_only = true,
+                "--messages" => rules.min_messages = flags.next()?.parse().ok()?,
+                "--luck" => rules.sub_luck = flags.next()?.parse().ok().filter(|luck| *luck > 0)?,
+                _ => return None,
+            }
+        }
+        Some(rules)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    user: String,
+    tickets: u32,
+}
+
+#[derive(Debug)]
+pub struct Raffle {
+    pub rules: RaffleRules,
+    entries: Vec<Entry>,
+    // lowercase names of the entrants
+    entrants: HashSet<String>,
+    // lowercase names of the users who were told why they cannot enter
+    explained: HashSet<String>,
+}
+
+impl Raffle {
+    pub fn new(rules: RaffleRules) -> Self {
+        Self {
+            rules,
+            entries: Vec::new(),
+            entrants: HashSet::new(),
+            explained: HashSet::new(),
+        }
+    }
+
+    /// Enters the user with one ticket, or with the sub luck for subscribers.
+    /// Returns false if the user already entered.
+    pub fn enter(&mut self, user: &str, subscriber: bool) -> bool {
+        if !self.entrants.insert(user.to_lowercase()) {
+            return false;
+        }
+        let tickets = if subscriber { self.rules.sub_luck } else { 1 };
+        self.entries.push(Entry {
+            user: user.to_owned(),
+            tickets,
+        });
+        true
+    }
+
+    pub fn entrant_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the user has not been told yet why they cannot enter, which they are only told once.
+    pub fn explain_once(&mut self, user: &str) -> bool {
+        self.explained.insert(user.to_lowercase())
+    }
+
+    /// Draws the winner, every ticket is equally likely to win.
+    pub fn draw(&self, rng: &mut impl Rng) -> Option<&str> {
+        let total: u32 = self.entries.iter().map(|entry| entry.tickets).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut ticket = rng.gen_range(0..total);
+        for entry in &self.entries {
+            if ticket < entry.tickets {
+                return Some(&entry.user);
+            }
+            ticket -= entry.tickets;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn flags(flags: &[&str]) -> Vec<String> {
+        flags.iter().map(|flag| flag.to_string()).collect()
+    }
+
+    #[test]
+    fn parsing_rules() {
+        assert_eq!(RaffleRules::parse(&[]), Some(RaffleRules::default()));
+        assert_eq!(
+            RaffleRules::parse(&flags(&["--followers", "--luck", "3", "--messages", "5"])),
+            Some(RaffleRules {
+                followers_only: true,
+                min_messages: 5,
+                sub_luck: 3,
+            })
+        );
+        assert_eq!(RaffleRules::parse(&flags(&["--luck", "0"])), None);
+        assert_eq!(RaffleRules::parse(&flags(&["--messages"])), None);
+        assert_eq!(RaffleRules::parse(&flags(&["--vips"])), None);
+    }
+
+    #[test]
+    fn entering_once() {
+        let mut raffle = Raffle::new(RaffleRules::default());
+        assert!(raffle.enter("Carkhy", false));
+        assert!(!raffle.enter("carkhy", true));
+        assert_eq!(raffle.entrant_count(), 1);
+        assert!(raffle.explain_once("alice"));
+        assert!(!raffle.explain_once("Alice"));
+    }
+
+    #[test]
+    fn drawing_by_tickets() {
+        let mut raffle = Raffle::new(RaffleRules {
+            sub_luck: 3,
+            ..RaffleRules::default()
+        });
+        raffle.enter("subscriber", true);
+        raffle.enter("viewer", false);
+        raffle.enter("other_viewer", false);
+        let mut rng = StdRng::seed_from_u64(7);
+        let draws = 100_000;
+        let subscriber_wins = (0..draws)
+            .filter(|_| raffle.draw(&mut rng) == Some("subscriber"))
+            .count();
+        // 3 of 5 tickets
+        let ratio = subscriber_wins as f64 / draws as f64;
+        assert!((ratio - 0.6).abs() < 0.01, "ratio {}", ratio);
+        assert_eq!(Raffle::new(RaffleRules::default()).draw(&mut rng), None);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::connect::HelixError;
+use std::time::Duration;
+use uuid::Uuid;
+
+// twitch starts a raid 90 seconds after it was created, unless the broadcaster starts it earlier
+pub const RAID_DELAY: Duration = Duration::from_secs(90);
+// the seconds left until the raid at which the chat is told, 0 is the raid itself
+const COUNTDOWN: [u64; 3] = [60, 30, 0];
+
+/// The raid started with `!raid`, until it starts or is canceled.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Raid {
+    // lowercase login of the raided channel
+    pub target: String,
+    // the countdown timers carry it, so that the timers of a canceled raid are ignored
+    pub id: Uuid,
+}
+
+impl Raid {
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_lowercase(),
+            id: Uuid::new_v4(),
+        }
+    }
+
+    /// When the countdown messages are due after twitch accepted the raid, with the seconds
+    /// left until the raid at that time.
+    pub fn countdown(&self) -> Vec<(Duration, u64)> {
+        COUNTDOWN
+            .iter()
+            .map(|left| (RAID_DELAY.saturating_sub(Duration::from_secs(*left)), *left))
+            .collect()
+    }
+}
+
+/// Why twitch did not start a raid.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RaidRefusal {
+    // the raided channel does not allow raids (from this channel)
+    NotAllowed,
+    Offline,
+    UnknownChannel,
+    // the channel is already raiding another one
+    AlreadyRaiding,
+    Other(HelixError),
+}
+
+impl From<HelixError> for RaidRefusal {
+    // twitch tells the reasons apart only by the message of a 400
+    fn from(error: HelixError) -> Self {
+        match &error {
+            HelixError::Status(404, _) => RaidRefusal::UnknownChannel,
+            HelixError::Status(409, _) => RaidRefusal::AlreadyRaiding,
+            HelixError::Status(400, message) => {
+                let message = message.to_lowercase();
+                if message.contains("allow") || message.contains("settings") {
+                    RaidRefusal::NotAllowed
+                } else if message.contains("offline") || message.contains("live") {
+                    RaidRefusal::Offline
+                } else {
+                    RaidRefusal::Other(error)
+                }
+            }
+            _ => RaidRefusal::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting_down_to_the_raid() {
+        let raid = Raid::new("FriendChannel");
+        assert_eq!(raid.target, "friendchannel");
+        assert_eq!(
+            raid.countdown(),
+            vec![
+                (Duration::from_secs(30), 60),
+                (Duration::from_secs(60), 30),
+                (Duration::from_secs(90), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn telling_raid_refusals_apart() {
+        let status = |status, message: &str| HelixError::Status(status, message.to_owned());
+        assert_eq!(
+            RaidRefusal::from(status(
+                400,
+                "The targeted channel doesn't allow raids from this broadcaster."
+            )),
+            RaidRefusal::NotAllowed
+        );
+        assert_eq!(
+            RaidRefusal::from(status(400, "The targeted channel is offline.")),
+            RaidRefusal::Offline
+        );
+        assert_eq!(
+            RaidRefusal::from(status(404, "The targeted channel was not found.")),
+            RaidRefusal::UnknownChannel
+        );
+        assert_eq!(
+            RaidRefusal::from(status(409, "The broadcaster is already raiding.")),
+            RaidRefusal::AlreadyRaiding
+        );
+        assert_eq!(
+            RaidRefusal::from(status(400, "The broadcaster may not raid themselves.")),
+            RaidRefusal::Other(status(400, "The broadcaster may not raid themselves."))
+        );
+        assert_eq!(
+            RaidRefusal::from(HelixError::Unavailable("timeout".to_owned())),
+            RaidRefusal::Other(HelixError::Unavailable("timeout".to_owned()))
+        );
+    }
+}
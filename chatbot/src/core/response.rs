@@ -0,0 +1,88 @@
+use std::{collections::HashMap, str::FromStr};
+
+/// Where the reply to a command goes.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ResponseTarget {
+    #[default]
+    Chat,
+    // whispered to the user of the command, in the chat if whispers are unavailable
+    Whisper,
+    // only written to the log of the bot
+    Log,
+}
+
+impl FromStr for ResponseTarget {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "chat" => Ok(ResponseTarget::Chat),
+            "whisper" => Ok(ResponseTarget::Whisper),
+            "log" => Ok(ResponseTarget::Log),
+            _ => Err(format!(
+                "unknown target {}, expected chat, whisper or log",
+                name
+            )),
+        }
+    }
+}
+
+/// A reply to a command with the target it should be sent to.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Response {
+    pub text: String,
+    pub preferred_target: ResponseTarget,
+}
+
+/// The targets of the replies of commands, overridden per command name.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ResponseRouting {
+    default: ResponseTarget,
+    commands: HashMap<String, ResponseTarget>,
+}
+
+impl ResponseRouting {
+    pub fn new(default: ResponseTarget, commands: HashMap<String, ResponseTarget>) -> Self {
+        Self { default, commands }
+    }
+
+    pub fn respond(&self, command_name: &str, text: String) -> Response {
+        Response {
+            text,
+            preferred_target: self
+                .commands
+                .get(command_name)
+                .copied()
+                .unwrap_or(self.default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overriding_the_default_target() {
+        let routing = ResponseRouting::new(
+            ResponseTarget::Log,
+            HashMap::from([("stats".to_owned(), ResponseTarget::Whisper)]),
+        );
+        assert_eq!(
+            routing.respond("stats", "hi".to_owned()).preferred_target,
+            ResponseTarget::Whisper
+        );
+        assert_eq!(
+            routing.respond("roll", "hi".to_owned()).preferred_target,
+            ResponseTarget::Log
+        );
+        assert_eq!(
+            ResponseRouting::default()
+                .respond("stats", "hi".to_owned())
+                .preferred_target,
+            ResponseTarget::Chat
+        );
+        assert_eq!("Whisper".parse(), Ok(ResponseTarget::Whisper));
+        assert!("discord".parse::<ResponseTarget>().is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::connect::UserInfo;
+use std::{collections::HashMap, str::FromStr};
+
+/// The role of a user in the channel as shown by their badges, from lowest to highest.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Role {
+    Viewer,
+    Vip,
+    Moderator,
+}
+
+impl Role {
+    /// The role of the user, None for the broadcaster whose role cannot change.
+    pub fn of(user: &UserInfo) -> Option<Self> {
+        let has_badge = |name: &str| user.badges.iter().any(|badge| badge.name == name);
+        if has_badge("broadcaster") {
+            None
+        } else if has_badge("moderator") {
+            Some(Role::Moderator)
+        } else if has_badge("vip") {
+            Some(Role::Vip)
+        } else {
+            Some(Role::Viewer)
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Vip => "vip",
+            Role::Moderator => "moderator",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "viewer" => Ok(Role::Viewer),
+            "vip" => Ok(Role::Vip),
+            "moderator" => Ok(Role::Moderator),
+            _ => Err(format!("unknown role {}", name)),
+        }
+    }
+}
+
+/// A role which differs from the last known role of a user.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RoleChange {
+    // the user id, or the lowercase name of users without id
+    pub user: String,
+    // None if the user was not seen before
+    pub from: Option<Role>,
+    pub to: Role,
+}
+
+/// Remembers the last known role of each chatter to notice when it changes.
+#[derive(Debug, Default)]
+pub struct RoleTracker {
+    known: HashMap<String, Role>,
+    // false in the first session after the tracking was enabled, which only records the roles,
+    // otherwise every chatter with a badge would be congratulated
+    pub announce: bool,
+}
+
+impl RoleTracker {
+    pub fn new(known: HashMap<String, Role>) -> Self {
+        Self {
+            announce: !known.is_empty(),
+            known,
+        }
+    }
+
+    /// Records the current role of the user, returns the change if it differs from the known one.
+    pub fn observe(&mut self, user: &UserInfo) -> Option<RoleChange> {
+        let role = Role::of(user)?;
+        let key = user.id.clone().unwrap_or_else(|| user.name.to_lowercase());
+        let from = self.known.insert(key.clone(), role);
+        if from == Some(role) {
+            return None;
+        }
+        Some(RoleChange {
+            user: key,
+            from,
+            to: role,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::user;
+
+    #[test]
+    fn noticing_role_changes() {
+        let mut tracker = RoleTracker::new(HashMap::new());
+        assert!(!tracker.announce);
+        assert_eq!(
+            tracker.observe(&user(&["subscriber"])),
+            Some(RoleChange {
+                user: "carkhy".to_owned(),
+                from: None,
+                to: Role::Viewer,
+            })
+        );
+        assert_eq!(tracker.observe(&user(&[])), None);
+        assert_eq!(
+            tracker.observe(&user(&["vip", "subscriber"])),
+            Some(RoleChange {
+                user: "carkhy".to_owned(),
+                from: Some(Role::Viewer),
+                to: Role::Vip,
+            })
+        );
+        assert_eq!(tracker.observe(&user(&["broadcaster", "moderator"])), None);
+        assert!(Role::Moderator > Role::Vip);
+        assert_eq!("vip".parse(), Ok(Role::Vip));
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// twitch allows one native shoutout every two minutes in a channel
+pub const SHOUTOUT_COOLDOWN: Duration = Duration::from_secs(120);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShoutoutSlot {
+    // the native shoutout can be sent right away
+    Now,
+    // the native shoutout waits for the cooldown, `first` if the caller has to wake the queue after `wait`
+    Queued { wait: Duration, first: bool },
+    AlreadyQueued,
+}
+
+/// Native shoutouts waiting for the cooldown of the channel, shared by everyone using `!so`.
+#[derive(Debug, Default)]
+pub struct ShoutoutQueue {
+    last_sent: Option<Instant>,
+    // lowercase logins in the order of the `!so` commands
+    waiting: VecDeque<String>,
+}
+
+impl ShoutoutQueue {
+    /// Takes the slot for a native shoutout of `login`, which is sent now only if the cooldown
+    /// is over and no earlier shoutout is waiting.
+    pub fn request(&mut self, login: &str, now: Instant) -> ShoutoutSlot {
+        let login = login.to_lowercase();
+        if self.waiting.contains(&login) {
+            return ShoutoutSlot::AlreadyQueued;
+        }
+        let remaining = self.remaining(now);
+        if self.waiting.is_empty() && remaining.is_zero() {
+            self.last_sent = Some(now);
+            return ShoutoutSlot::Now;
+        }
+        self.waiting.push_back(login);
+        let earlier = self.waiting.len() as u32 - 1;
+        ShoutoutSlot::Queued {
+            wait: remaining + SHOUTOUT_COOLDOWN * earlier,
+            first: earlier == 0,
+        }
+    }
+
+    /// Takes the next waiting shoutout if the cooldown is over.
+    pub fn next_due(&mut self, now: Instant) -> Option<String> {
+        if !self.remaining(now).is_zero() {
+            return None;
+        }
+        let login = self.waiting.pop_front()?;
+        self.last_sent = Some(now);
+        Some(login)
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        !self.waiting.is_empty()
+    }
+
+    fn remaining(&self, now: Instant) -> Duration {
+        self.last_sent
+            .map(|last_sent| SHOUTOUT_COOLDOWN.saturating_sub(now - last_sent))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queueing_shoutouts_during_the_cooldown() {
+        let start = Instant::now();
+        let mut queue = ShoutoutQueue::default();
+        assert_eq!(queue.request("Alice", start), ShoutoutSlot::Now);
+        let later = start + Duration::from_secs(20);
+        assert_eq!(
+            queue.request("bob", later),
+            ShoutoutSlot::Queued {
+                wait: Duration::from_secs(100),
+                first: true
+            }
+        );
+        assert_eq!(queue.request("Bob", later), ShoutoutSlot::AlreadyQueued);
+        assert_eq!(
+            queue.request("carol", later),
+            ShoutoutSlot::Queued {
+                wait: Duration::from_secs(220),
+                first: false
+            }
+        );
+        assert_eq!(queue.next_due(later), None);
+
+        let due = start + SHOUTOUT_COOLDOWN;
+        assert_eq!(queue.next_due(due).as_deref(), Some("bob"));
+        assert!(queue.is_waiting());
+        assert_eq!(queue.next_due(due), None);
+        assert_eq!(
+            queue.next_due(due + SHOUTOUT_COOLDOWN).as_deref(),
+            Some("carol")
+        );
+        assert!(!queue.is_waiting());
+    }
+
+    #[test]
+    fn sending_shoutouts_after_the_cooldown() {
+        let start = Instant::now();
+        let mut queue = ShoutoutQueue::default();
+        assert_eq!(queue.request("alice", start), ShoutoutSlot::Now);
+        assert_eq!(
+            queue.request("bob", start + SHOUTOUT_COOLDOWN),
+            ShoutoutSlot::Now
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+// name of the counter of all chat messages of a user, commands are counted by their name
+pub const MESSAGES_COUNTER: &str = "messages";
+
+/// How often a user chatted and used each command.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct UserStats {
+    pub messages: u64,
+    pub commands: HashMap<String, u64>,
+}
+
+impl UserStats {
+    /// Sets a counter read from the store.
+    pub fn set(&mut self, counter: &str, count: u64) {
+        if counter == MESSAGES_COUNTER {
+            self.messages = count;
+        } else {
+            self.commands.insert(counter.to_owned(), count);
+        }
+    }
+
+    /// The most used command and its count.
+    /// On a tie the command which comes first alphabetically wins, so the answer is stable.
+    pub fn favorite(&self) -> Option<(&str, u64)> {
+        self.commands
+            .iter()
+            .map(|(command, count)| (command.as_str(), *count))
+            .max_by(|(command, count), (other_command, other_count)| {
+                count
+                    .cmp(other_count)
+                    .then_with(|| other_command.cmp(command))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finding_favorite_command() {
+        let mut stats = UserStats::default();
+        assert_eq!(stats.favorite(), None);
+        stats.set("slap", 37);
+        stats.set("help", 2);
+        stats.set(MESSAGES_COUNTER, 412);
+        assert_eq!(stats.favorite(), Some(("slap", 37)));
+        assert_eq!(stats.messages, 412);
+    }
+
+    #[test]
+    fn breaking_favorite_ties_alphabetically() {
+        let mut stats = UserStats::default();
+        stats.set("slap", 5);
+        stats.set("counter", 5);
+        stats.set("help", 5);
+        assert_eq!(stats.favorite(), Some(("counter", 5)));
+    }
+}
@@ -0,0 +1,479 @@
+use super::{
+    counts::StreamSnapshot,
+    deaths::GameDeaths,
+    dice::RollRecord,
+    goal::GoalKind,
+    roles::Role,
+    stats::{UserStats, MESSAGES_COUNTER},
+};
+use kv::{Bucket, Config, Error};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+};
+
+pub const BOT_STORE_FILE: &str = "./bot_store";
+pub(super) const COUNTER_BUCKET_NAME: &str = "counters";
+pub(super) const GOAL_BUCKET_NAME: &str = "goals";
+// keys of the usage statistics look like <channel>/<user>/<counter>
+pub(super) const USAGE_BUCKET_NAME: &str = "usage";
+pub(super) const QUIZ_POINTS_BUCKET_NAME: &str = "quiz_points";
+pub(super) const RECORDS_BUCKET_NAME: &str = "records";
+const ROLL_RECORD_KEY: &str = "roll";
+// commands are kept with `false` when they are enabled again
+pub(super) const DISABLED_COMMANDS_BUCKET_NAME: &str = "disabled_commands";
+// last known roles by user id
+pub(super) const ROLES_BUCKET_NAME: &str = "roles";
+// deaths by the id of the category
+pub(super) const GAME_DEATHS_BUCKET_NAME: &str = "game_deaths";
+// highest message milestone reached, keys look like <channel>/<user>
+pub(super) const MILESTONES_BUCKET_NAME: &str = "milestones";
+// the follower and subscriber totals at the start of each stream by stream id
+const STREAM_SNAPSHOTS_BUCKET_NAME: &str = "stream_snapshots";
+
+/// Where the bot persists its data, in named buckets of text keys and values.
+/// Shared with the threads writing in the background, so it has to be `Send + Sync`.
+pub trait Store: Send + Sync {
+    /// All entries of the bucket, in no particular order.
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>, Error>;
+
+    fn set(&self, bucket: &str, key: &str, value: &str) -> Result<(), Error>;
+
+    fn remove(&self, bucket: &str, key: &str) -> Result<(), Error>;
+}
+
+impl dyn Store + '_ {
+    /// Loads all entries of a bucket. Entries which cannot be read are skipped.
+    pub fn load<T: FromStr>(&self, bucket: &str) -> Result<HashMap<String, T>, Error> {
+        Ok(self
+            .entries(bucket)?
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value.parse().ok()?)))
+            .collect())
+    }
+
+    pub fn save<T: Display>(&self, bucket: &str, key: &str, value: &T) -> Result<(), Error> {
+        self.set(bucket, key, &value.to_string())
+    }
+}
+
+/// The store of the bot on disk. The database is locked while it is open, so it is opened once
+/// and the handle is shared with the threads writing in the background.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    store: kv::Store,
+}
+
+impl FileStore {
+    pub fn new(path: &str) -> Result<Self, Error> {
+        Ok(Self {
+            store: kv::Store::new(Config::new(path))?,
+        })
+    }
+
+    fn bucket<'a>(&self, name: &str) -> Result<Bucket<'a, String, String>, Error> {
+        self.store.bucket::<String, String>(Some(name))
+    }
+}
+
+impl Store for FileStore {
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>, Error> {
+        Ok(self
+            .bucket(bucket)?
+            .iter()
+            .filter_map(|item| {
+                let item = item.ok()?;
+                Some((item.key().ok()?, item.value().ok()?))
+            })
+            .collect())
+    }
+
+    // written to disk right away, the store is not flushed in the background
+    fn set(&self, bucket: &str, key: &str, value: &str) -> Result<(), Error> {
+        let bucket = self.bucket(bucket)?;
+        bucket.set(key, value.to_owned())?;
+        bucket.flush()?;
+        Ok(())
+    }
+
+    fn remove(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        let bucket = self.bucket(bucket)?;
+        bucket.remove(key)?;
+        bucket.flush()?;
+        Ok(())
+    }
+}
+
+/// A store which forgets everything when it is dropped, for tests.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    buckets: std::sync::Mutex<HashMap<String, HashMap<String, String>>>,
+}
+
+#[cfg(test)]
+impl Store for InMemoryStore {
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>, Error> {
+        let buckets = self.buckets.lock().unwrap();
+        Ok(buckets
+            .get(bucket)
+            .map(|entries| entries.clone().into_iter().collect())
+            .unwrap_or_default())
+    }
+
+    fn set(&self, bucket: &str, key: &str, value: &str) -> Result<(), Error> {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets
+            .entry(bucket.to_owned())
+            .or_default()
+            .insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn remove(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(entries) = buckets.get_mut(bucket) {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+}
+
+/// Loads all persisted counters.
+pub fn load_counters(store: &dyn Store) -> Result<HashMap<String, i64>, Error> {
+    store.load(COUNTER_BUCKET_NAME)
+}
+
+pub fn store_counter(store: &dyn Store, name: &str, value: i64) -> Result<(), Error> {
+    store.save(COUNTER_BUCKET_NAME, name, &value)
+}
+
+/// Loads all goal targets which were set with a goal command.
+pub fn load_goals(store: &dyn Store) -> Result<HashMap<GoalKind, u64>, Error> {
+    let targets = store.load(GOAL_BUCKET_NAME)?;
+    Ok([GoalKind::Followers, GoalKind::SubPoints]
+        .into_iter()
+        .filter_map(|kind| targets.get(kind.name()).map(|target| (kind, *target)))
+        .collect())
+}
+
+pub fn store_goal(store: &dyn Store, kind: GoalKind, target: u64) -> Result<(), Error> {
+    store.save(GOAL_BUCKET_NAME, kind.name(), &target)
+}
+
+/// Loads the usage statistics of all users in the given channel.
+pub fn load_usage_stats(
+    store: &dyn Store,
+    channel: &str,
+) -> Result<HashMap<String, UserStats>, Error> {
+    let counters: HashMap<String, u64> = store.load(USAGE_BUCKET_NAME)?;
+    let mut usage_stats: HashMap<String, UserStats> = HashMap::new();
+    for (key, count) in counters {
+        let mut parts = key.splitn(3, '/');
+        if let (Some(key_channel), Some(user), Some(counter)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            if key_channel == channel {
+                usage_stats
+                    .entry(user.to_owned())
+                    .or_default()
+                    .set(counter, count);
+            }
+        }
+    }
+    Ok(usage_stats)
+}
+
+/// Sums up the usage counters (messages and commands) of all users in the given channel.
+pub fn usage_totals(store: &dyn Store, channel: &str) -> Result<HashMap<String, u64>, Error> {
+    let mut totals = HashMap::new();
+    for user_stats in load_usage_stats(store, channel)?.into_values() {
+        *totals.entry(MESSAGES_COUNTER.to_owned()).or_default() += user_stats.messages;
+        for (command, count) in user_stats.commands {
+            *totals.entry(command).or_default() += count;
+        }
+    }
+    Ok(totals)
+}
+
+pub fn store_usage(
+    store: &dyn Store,
+    channel: &str,
+    user: &str,
+    counts: &[(String, u64)],
+) -> Result<(), Error> {
+    for (counter, count) in counts {
+        store.save(
+            USAGE_BUCKET_NAME,
+            &format!("{}/{}/{}", channel, user, counter),
+            count,
+        )?;
+    }
+    Ok(())
+}
+
+// the usage counters of a user in a channel, as sent by the bot
+type UsageWrite = (String, String, Vec<(String, u64)>);
+
+/// Writes the usage counters on a thread of its own, one after another, so that storing them
+/// never delays the chat and an older count never overwrites a newer one.
+/// Dropping the writer waits for the writes still queued.
+pub struct UsageWriter {
+    sender: Option<mpsc::Sender<UsageWrite>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UsageWriter {
+    pub fn new(store: Arc<dyn Store>) -> Self {
+        let (sender, receiver) = mpsc::channel::<UsageWrite>();
+        let thread = thread::spawn(move || {
+            for (channel, user, counts) in receiver {
+                if let Err(error) = store_usage(store.as_ref(), &channel, &user, &counts) {
+                    println!("Could not store usage of {}: {:?}", user, error);
+                }
+            }
+        });
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues the counters of a user in a channel to be written after the ones queued before.
+    pub fn write(&self, channel: String, user: String, counts: Vec<(String, u64)>) {
+        if let Some(sender) = &self.sender {
+            // the thread only stops when the writer is dropped
+            let _ = sender.send((channel, user, counts));
+        }
+    }
+}
+
+impl Drop for UsageWriter {
+    fn drop(&mut self) {
+        // closing the channel ends the thread once it wrote everything
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Loads the highest message milestones the users in the given channel reached.
+pub fn load_message_milestones(
+    store: &dyn Store,
+    channel: &str,
+) -> Result<HashMap<String, u64>, Error> {
+    let milestones: HashMap<String, u64> = store.load(MILESTONES_BUCKET_NAME)?;
+    Ok(milestones
+        .into_iter()
+        .filter_map(|(key, milestone)| {
+            let (key_channel, user) = key.split_once('/')?;
+            (key_channel == channel).then(|| (user.to_owned(), milestone))
+        })
+        .collect())
+}
+
+pub fn store_message_milestone(
+    store: &dyn Store,
+    channel: &str,
+    user: &str,
+    milestone: u64,
+) -> Result<(), Error> {
+    store.save(
+        MILESTONES_BUCKET_NAME,
+        &format!("{}/{}", channel, user),
+        &milestone,
+    )
+}
+
+/// Loads the quiz points of all users by lowercase name.
+pub fn load_quiz_points(store: &dyn Store) -> Result<HashMap<String, u64>, Error> {
+    store.load(QUIZ_POINTS_BUCKET_NAME)
+}
+
+pub fn store_quiz_points(store: &dyn Store, points: &[(String, u64)]) -> Result<(), Error> {
+    for (user, points) in points {
+        store.save(QUIZ_POINTS_BUCKET_NAME, user, points)?;
+    }
+    Ok(())
+}
+
+/// Loads the all-time record of `!roll`, None before the first roll.
+pub fn load_roll_record(store: &dyn Store) -> Result<Option<RollRecord>, Error> {
+    Ok(store.load(RECORDS_BUCKET_NAME)?.remove(ROLL_RECORD_KEY))
+}
+
+pub fn store_roll_record(store: &dyn Store, record: &RollRecord) -> Result<(), Error> {
+    store.save(RECORDS_BUCKET_NAME, ROLL_RECORD_KEY, record)
+}
+
+/// Loads the deaths counted with `!deaths` by the id of the category of the game.
+pub fn load_game_deaths(store: &dyn Store) -> Result<HashMap<String, GameDeaths>, Error> {
+    store.load(GAME_DEATHS_BUCKET_NAME)
+}
+
+pub fn store_game_deaths(store: &dyn Store, game_id: &str, game: &GameDeaths) -> Result<(), Error> {
+    store.save(GAME_DEATHS_BUCKET_NAME, game_id, game)
+}
+
+/// Loads the names of the commands which were disabled with `!disable`.
+pub fn load_disabled_commands(store: &dyn Store) -> Result<HashSet<String>, Error> {
+    let commands: HashMap<String, bool> = store.load(DISABLED_COMMANDS_BUCKET_NAME)?;
+    Ok(commands
+        .into_iter()
+        .filter_map(|(name, disabled)| disabled.then_some(name))
+        .collect())
+}
+
+pub fn store_command_disabled(store: &dyn Store, name: &str, disabled: bool) -> Result<(), Error> {
+    store.save(DISABLED_COMMANDS_BUCKET_NAME, name, &disabled)
+}
+
+/// Loads the last known roles of the users.
+pub fn load_roles(store: &dyn Store) -> Result<HashMap<String, Role>, Error> {
+    store.load(ROLES_BUCKET_NAME)
+}
+
+pub fn store_role(store: &dyn Store, user: &str, role: Role) -> Result<(), Error> {
+    store.save(ROLES_BUCKET_NAME, user, &role.name())
+}
+
+/// Loads the totals at the start of the streams by stream id, for `!followers` and `!subs`.
+pub fn load_stream_snapshots(store: &dyn Store) -> Result<HashMap<String, StreamSnapshot>, Error> {
+    store.load(STREAM_SNAPSHOTS_BUCKET_NAME)
+}
+
+pub fn store_stream_snapshot(
+    store: &dyn Store,
+    stream_id: &str,
+    snapshot: &StreamSnapshot,
+) -> Result<(), Error> {
+    store.save(STREAM_SNAPSHOTS_BUCKET_NAME, stream_id, snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_tripping_data_through_a_store() {
+        let store = InMemoryStore::default();
+        assert!(load_counters(&store).unwrap().is_empty());
+        store_counter(&store, "deaths", 3).unwrap();
+        store_counter(&store, "deaths", 4).unwrap();
+        store_counter(&store, "wins", -1).unwrap();
+        assert_eq!(
+            load_counters(&store).unwrap(),
+            HashMap::from([("deaths".to_owned(), 4), ("wins".to_owned(), -1)])
+        );
+
+        assert_eq!(load_roll_record(&store).unwrap(), None);
+        let record = RollRecord {
+            user: "Carkhy".to_owned(),
+            result: 20,
+        };
+        store_roll_record(&store, &record).unwrap();
+        assert_eq!(load_roll_record(&store).unwrap(), Some(record));
+
+        let game = GameDeaths {
+            name: "Elden Ring".to_owned(),
+            deaths: 42,
+        };
+        store_game_deaths(&store, "512953", &game).unwrap();
+        assert_eq!(
+            load_game_deaths(&store).unwrap(),
+            HashMap::from([("512953".to_owned(), game)])
+        );
+
+        store_command_disabled(&store, "slap", true).unwrap();
+        store_command_disabled(&store, "roll", true).unwrap();
+        store_command_disabled(&store, "roll", false).unwrap();
+        assert_eq!(
+            load_disabled_commands(&store).unwrap(),
+            HashSet::from(["slap".to_owned()])
+        );
+
+        store_role(&store, "12345", Role::Vip).unwrap();
+        assert_eq!(load_roles(&store).unwrap()["12345"], Role::Vip);
+
+        let snapshot = StreamSnapshot {
+            followers: Some(1234),
+            subscribers: None,
+        };
+        store_stream_snapshot(&store, "40952121085", &snapshot).unwrap();
+        assert_eq!(
+            load_stream_snapshots(&store).unwrap(),
+            HashMap::from([("40952121085".to_owned(), snapshot)])
+        );
+    }
+
+    #[test]
+    fn writing_to_a_file_store_concurrently() {
+        let path = std::env::temp_dir().join(format!("store-{}", uuid::Uuid::new_v4()));
+        let store = FileStore::new(path.to_str().unwrap()).unwrap();
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let writers: Vec<_> = ["deaths", "wins"]
+            .into_iter()
+            .map(|name| {
+                let store = store.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    store_counter(&store, name, 3)
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        assert_eq!(
+            load_counters(&store).unwrap(),
+            HashMap::from([("deaths".to_owned(), 3), ("wins".to_owned(), 3)])
+        );
+        drop(store);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn loading_the_usage_of_one_channel() {
+        let store = InMemoryStore::default();
+        let counts = [("messages".to_owned(), 5), ("roll".to_owned(), 2)];
+        store_usage(&store, "captaincallback", "carkhy", &counts).unwrap();
+        store_usage(&store, "otherchannel", "carkhy", &counts).unwrap();
+        let stats = load_usage_stats(&store, "captaincallback").unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats["carkhy"].messages, 5);
+        assert_eq!(
+            usage_totals(&store, "captaincallback").unwrap(),
+            HashMap::from([("messages".to_owned(), 5), ("roll".to_owned(), 2)])
+        );
+        store_message_milestone(&store, "captaincallback", "carkhy", 100).unwrap();
+        store_message_milestone(&store, "otherchannel", "carkhy", 1000).unwrap();
+        assert_eq!(
+            load_message_milestones(&store, "captaincallback").unwrap(),
+            HashMap::from([("carkhy".to_owned(), 100)])
+        );
+        // entries which cannot be read are skipped
+        store.set(COUNTER_BUCKET_NAME, "broken", "many").unwrap();
+        assert!(load_counters(&store).unwrap().is_empty());
+    }
+
+    #[test]
+    fn writing_usage_in_order() {
+        let store = Arc::new(InMemoryStore::default());
+        let writer = UsageWriter::new(store.clone());
+        for messages in 1..=100 {
+            writer.write(
+                "captaincallback".to_owned(),
+                "carkhy".to_owned(),
+                vec![("messages".to_owned(), messages)],
+            );
+        }
+        drop(writer);
+        let stats = load_usage_stats(store.as_ref(), "captaincallback").unwrap();
+        assert_eq!(stats["carkhy"].messages, 100);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::connect::UserInfo;
+use rand::Rng;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    #[error("Unclosed tag starting at character {}", .0)]
+    UnclosedTag(usize),
+    #[error("Unmatched '}}' at character {}, write '}}}}' for a literal brace", .0)]
+    UnmatchedBrace(usize),
+    #[error("Unknown tag {{{}}}", .0)]
+    UnknownTag(String),
+    #[error("{{{}}} without a matching {{if}}", .0)]
+    UnexpectedTag(String),
+    #[error("{{if {}}} without a matching {{end}}", .0)]
+    MissingEnd(String),
+}
+
+/// A message with tags which are filled in when it is sent:
+/// - `{name}` is replaced with the value of the same name, e.g. `{user}`
+/// - `{random:a|b|c}` picks one of the options, which may contain tags themselves
+/// - `{if subscriber}...{else}...{end}` depends on the badges of the user, `{else}` is optional
+/// - `{count:name}` is the value of the counter
+/// - `{args:1}` is the first option of the command
+///
+/// `{{` and `}}` are literal braces.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Node {
+    Text(String),
+    Value(String),
+    Random(Vec<Vec<Node>>),
+    If {
+        condition: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Count(String),
+    Arg(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    // the content of a tag without the braces
+    Tag(String),
+}
+
+/// What the tags of a template are filled with.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TemplateContext<'a> {
+    // the user who triggered the message, conditions are false without one
+    pub user: Option<&'a UserInfo>,
+    pub args: &'a [String],
+    pub values: &'a [(&'a str, &'a str)],
+    pub counters: Option<&'a HashMap<String, i64>>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, TemplateError> {
+        let mut tokens = tokenize(text)?.into_iter();
+        let nodes = parse_nodes(&mut tokens, None)?;
+        Ok(Self { nodes })
+    }
+
+    /// Renders the template, tags without a value (e.g. unknown names or missing options) are left empty.
+    pub fn render(&self, context: &TemplateContext, rng: &mut impl Rng) -> String {
+        let mut text = String::new();
+        render_nodes(&self.nodes, context, rng, &mut text);
+        text
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().enumerate().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' if chars.next_if(|(_, next)| *next == '{').is_some() => current.push('{'),
+            '}' if chars.next_if(|(_, next)| *next == '}').is_some() => current.push('}'),
+            '}' => return Err(TemplateError::UnmatchedBrace(i)),
+            '{' => {
+                // tags can be nested in the options of random tags
+                let mut depth = 1;
+                let mut tag = String::new();
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => (),
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    tag.push(c);
+                }
+                if depth > 0 {
+                    return Err(TemplateError::UnclosedTag(i));
+                }
+                if !current.is_empty() {
+                    tokens.push(Token::Text(std::mem::take(&mut current)));
+                }
+                tokens.push(Token::Tag(tag.trim().to_owned()));
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(Token::Text(current));
+    }
+    Ok(tokens)
+}
+
+/// Parses nodes until the end of the tokens, or until the `{end}` of the given `{if}`.
+fn parse_nodes(
+    tokens: &mut impl Iterator<Item = Token>,
+    open_if: Option<&str>,
+) -> Result<Vec<Node>, TemplateError> {
+    let mut nodes = Vec::new();
+    let mut then: Option<Vec<Node>> = None;
+    while let Some(token) = tokens.next() {
+        let tag = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+        match (tag.as_str(), open_if) {
+            ("else", Some(_)) if then.is_none() => then = Some(std::mem::take(&mut nodes)),
+            ("end", Some(condition)) => {
+                let (then, otherwise) = match then {
+                    Some(then) => (then, nodes),
+                    None => (nodes, Vec::new()),
+                };
+                return Ok(vec![Node::If {
+                    condition: condition.to_owned(),
+                    then,
+                    otherwise,
+                }]);
+            }
+            ("else" | "end", _) => return Err(TemplateError::UnexpectedTag(tag)),
+            _ => nodes.extend(parse_tag(&tag, tokens)?),
+        }
+    }
+    match open_if {
+        Some(condition) => Err(TemplateError::MissingEnd(condition.to_owned())),
+        None => Ok(nodes),
+    }
+}
+
+fn parse_tag(
+    tag: &str,
+    tokens: &mut impl Iterator<Item = Token>,
+) -> Result<Vec<Node>, TemplateError> {
+    if let Some(condition) = tag.strip_prefix("if ") {
+        return parse_nodes(tokens, Some(condition.trim()));
+    }
+    let node = match tag.split_once(':') {
+        Some(("random", options)) => Node::Random(
+            split_options(options)
+                .into_iter()
+                .map(|option| Template::parse(option).map(|template| template.nodes))
+                .collect::<Result<_, _>>()?,
+        ),
+        Some(("count", name)) if is_name(name) => Node::Count(name.to_owned()),
+        Some(("args", index)) => match index.parse() {
+            Ok(index) if index > 0 => Node::Arg(index),
+            _ => return Err(TemplateError::UnknownTag(tag.to_owned())),
+        },
+        None if is_name(tag) => Node::Value(tag.to_owned()),
+        _ => return Err(TemplateError::UnknownTag(tag.to_owned())),
+    };
+    Ok(vec![node])
+}
+
+// splits at the '|' which are not part of a nested tag
+fn split_options(options: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in options.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '|' if depth == 0 => {
+                parts.push(&options[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&options[start..]);
+    parts
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn render_nodes(nodes: &[Node], context: &TemplateContext, rng: &mut impl Rng, text: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(part) => text.push_str(part),
+            Node::Value(name) => {
+                if let Some((_, value)) = context.values.iter().find(|(key, _)| key == name) {
+                    text.push_str(value);
+                }
+            }
+            Node::Random(options) => {
+                let option = &options[rng.gen_range(0..options.len())];
+                render_nodes(option, context, rng, text);
+            }
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let nodes = if is_met(condition, context.user) {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(nodes, context, rng, text);
+            }
+            // counters which were never changed are 0, like with `!counter`
+            Node::Count(name) => {
+                let value = context
+                    .counters
+                    .and_then(|counters| counters.get(name))
+                    .copied()
+                    .unwrap_or(0);
+                text.push_str(&value.to_string());
+            }
+            Node::Arg(index) => {
+                if let Some(arg) = context.args.get(index - 1) {
+                    text.push_str(arg);
+                }
+            }
+        }
+    }
+}
+
+// conditions are badge names, moderator includes the broadcaster and subscriber the founders
+fn is_met(condition: &str, user: Option<&UserInfo>) -> bool {
+    let user = match user {
+        Some(user) => user,
+        None => return false,
+    };
+    match condition {
+        "subscriber" => user.is_subscriber(),
+        "moderator" => user.has_elevated_rights(),
+        "broadcaster" => user.is_broadcaster(),
+        badge => user
+            .badges
+            .iter()
+            .any(|user_badge| user_badge.name == badge),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::user;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn render(text: &str, context: &TemplateContext) -> String {
+        Template::parse(text)
+            .unwrap()
+            .render(context, &mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn filling_values_and_arguments() {
+        let args = vec!["@bob".to_owned(), "twice".to_owned()];
+        let context = TemplateContext {
+            args: &args,
+            values: &[("user", "carkhy")],
+            ..TemplateContext::default()
+        };
+        assert_eq!(
+            render("{user} hugs {args:1} {args:2}!", &context),
+            "carkhy hugs @bob twice!"
+        );
+        // unknown values and missing options are left empty
+        assert_eq!(render("[{target}] [{args:3}]", &context), "[] []");
+    }
+
+    #[test]
+    fn reading_counters() {
+        let counters = HashMap::from([("deaths".to_owned(), 7)]);
+        let context = TemplateContext {
+            counters: Some(&counters),
+            ..TemplateContext::default()
+        };
+        assert_eq!(
+            render("Died {count:deaths} times, won {count:wins}", &context),
+            "Died 7 times, won 0"
+        );
+    }
+
+    #[test]
+    fn checking_badges() {
+        let template = "{if subscriber}Thanks for subbing{else}Welcome{end}, {user}!";
+        let values = [("user", "carkhy")];
+        let subscriber = user(&["founder"]);
+        let viewer = user(&[]);
+        let context = |user| TemplateContext {
+            user,
+            values: &values,
+            ..TemplateContext::default()
+        };
+        assert_eq!(
+            render(template, &context(Some(&subscriber))),
+            "Thanks for subbing, carkhy!"
+        );
+        assert_eq!(
+            render(template, &context(Some(&viewer))),
+            "Welcome, carkhy!"
+        );
+        assert_eq!(render(template, &context(None)), "Welcome, carkhy!");
+        let vip = user(&["vip"]);
+        assert_eq!(render("{if vip}VIP{end}", &context(Some(&vip))), "VIP");
+        assert_eq!(render("{if vip}VIP{end}", &context(Some(&viewer))), "");
+    }
+
+    #[test]
+    fn nesting_tags() {
+        let moderator = user(&["moderator", "subscriber"]);
+        let subscriber = user(&["subscriber"]);
+        let template =
+            "{if moderator}{if subscriber}mod+sub{else}mod{end}{else}{if subscriber}sub{end}{end}";
+        let context = |user| TemplateContext {
+            user,
+            ..TemplateContext::default()
+        };
+        assert_eq!(render(template, &context(Some(&moderator))), "mod+sub");
+        assert_eq!(render(template, &context(Some(&subscriber))), "sub");
+        assert_eq!(render(template, &context(None)), "");
+        let values = [("user", "carkhy")];
+        let context = TemplateContext {
+            user: Some(&subscriber),
+            values: &values,
+            ..TemplateContext::default()
+        };
+        assert_eq!(
+            render("{random:{if subscriber}hi {user}{end}}", &context),
+            "hi carkhy"
+        );
+    }
+
+    #[test]
+    fn escaping_braces() {
+        let context = TemplateContext {
+            values: &[("user", "carkhy")],
+            ..TemplateContext::default()
+        };
+        assert_eq!(
+            render("{{user}} is {user}, }}{{", &context),
+            "{user} is carkhy, }{"
+        );
+        assert_eq!(
+            render("{random:{{a}}}", &context),
+            "{a}",
+            "escaped braces in options"
+        );
+    }
+
+    #[test]
+    fn picking_random_options() {
+        let template = Template::parse("{random:a|b|{user}}").unwrap();
+        let context = TemplateContext {
+            values: &[("user", "c")],
+            ..TemplateContext::default()
+        };
+        let mut rng = StdRng::seed_from_u64(42);
+        let picks: Vec<String> = (0..300)
+            .map(|_| template.render(&context, &mut rng))
+            .collect();
+        for option in ["a", "b", "c"] {
+            let count = picks.iter().filter(|pick| *pick == option).count();
+            assert!(
+                (70..130).contains(&count),
+                "{} picked {} times",
+                option,
+                count
+            );
+        }
+        // the same seed picks the same options
+        let mut rng = StdRng::seed_from_u64(42);
+        let again: Vec<String> = (0..300)
+            .map(|_| template.render(&context, &mut rng))
+            .collect();
+        assert_eq!(picks, again);
+        assert_eq!(render("{random:only}", &context), "only");
+        assert_eq!(render("[{random:|}]", &context), "[]");
+    }
+
+    #[test]
+    fn rejecting_malformed_templates() {
+        assert_eq!(
+            Template::parse("hi {user"),
+            Err(TemplateError::UnclosedTag(3))
+        );
+        assert_eq!(
+            Template::parse("hi user}"),
+            Err(TemplateError::UnmatchedBrace(7))
+        );
+        assert_eq!(
+            Template::parse("{if subscriber}hi"),
+            Err(TemplateError::MissingEnd("subscriber".to_owned()))
+        );
+        assert_eq!(
+            Template::parse("hi{end}"),
+            Err(TemplateError::UnexpectedTag("end".to_owned()))
+        );
+        assert_eq!(
+            Template::parse("{if vip}a{else}b{else}c{end}"),
+            Err(TemplateError::UnexpectedTag("else".to_owned()))
+        );
+        assert_eq!(
+            Template::parse("{args:0}"),
+            Err(TemplateError::UnknownTag("args:0".to_owned()))
+        );
+        assert_eq!(
+            Template::parse("{shout:hi}"),
+            Err(TemplateError::UnknownTag("shout:hi".to_owned()))
+        );
+        assert_eq!(
+            Template::parse("{random:a|{b}"),
+            Err(TemplateError::UnclosedTag(0))
+        );
+        assert_eq!(
+            Template::parse("{random:a|{if vip}b}"),
+            Err(TemplateError::MissingEnd("vip".to_owned()))
+        );
+        assert_eq!(
+            TemplateError::UnclosedTag(3).to_string(),
+            "Unclosed tag starting at character 3"
+        );
+    }
+}
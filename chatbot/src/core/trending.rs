@@ -0,0 +1,240 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+// phrases count while they were written within this time
+pub const TRENDING_WINDOW: Duration = Duration::from_secs(15 * 60);
+// the window is counted in buckets, which leave it as a whole
+const BUCKET_LENGTH: Duration = Duration::from_secs(60);
+// the phrases counted per bucket, those seen once are pruned when a bucket is full
+const MAX_BUCKET_PHRASES: usize = 5000;
+const TRENDING_PHRASES: usize = 5;
+const PHRASE_WORDS: RangeInclusive<usize> = 2..=4;
+
+// phrases of these words only (e.g. "in the") are no phrases worth counting
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "do", "for", "i", "if", "in", "is",
+    "it", "me", "my", "no", "not", "of", "on", "or", "so", "that", "the", "this", "to", "u", "was",
+    "we", "you", "das", "der", "die", "du", "ein", "eine", "es", "ich", "ist", "nicht", "und",
+    "zu",
+];
+
+#[derive(Debug)]
+struct Bucket {
+    started: Instant,
+    // by normalized phrase
+    counts: HashMap<String, u32>,
+}
+
+/// Counts the phrases of 2 to 4 words repeated in the chat, e.g. a new meme or a spam wave.
+/// Each message counts a phrase once, phrases of emotes and stopwords only are not counted.
+#[derive(Debug)]
+pub struct PhraseTracker {
+    // the oldest first
+    buckets: VecDeque<Bucket>,
+    max_bucket_phrases: usize,
+}
+
+impl Default for PhraseTracker {
+    fn default() -> Self {
+        Self::new(MAX_BUCKET_PHRASES)
+    }
+}
+
+impl PhraseTracker {
+    pub fn new(max_bucket_phrases: usize) -> Self {
+        Self {
+            buckets: VecDeque::new(),
+            max_bucket_phrases,
+        }
+    }
+
+    /// Counts the phrases of a chat message, emotes are kept as written and the other words
+    /// are lowercased without punctuation.
+    pub fn track(&mut self, text: &str, is_emote: impl Fn(&str) -> bool, now: Instant) {
+        self.expire(now);
+        let words: Vec<(String, bool)> = text
+            .split_whitespace()
+            .filter_map(|word| {
+                if is_emote(word) {
+                    return Some((word.to_owned(), true));
+                }
+                let word = normalize(word);
+                (!word.is_empty()).then_some((word, false))
+            })
+            .collect();
+        let mut phrases = HashSet::new();
+        for length in PHRASE_WORDS {
+            for phrase in words.windows(length) {
+                if phrase
+                    .iter()
+                    .all(|(word, emote)| *emote || STOPWORDS.contains(&word.as_str()))
+                {
+                    continue;
+                }
+                let phrase: Vec<&str> = phrase.iter().map(|(word, _)| word.as_str()).collect();
+                phrases.insert(phrase.join(" "));
+            }
+        }
+        if phrases.is_empty() {
+            return;
+        }
+        if self
+            .buckets
+            .back()
+            .is_none_or(|bucket| now.saturating_duration_since(bucket.started) >= BUCKET_LENGTH)
+        {
+            self.buckets.push_back(Bucket {
+                started: now,
+                counts: HashMap::new(),
+            });
+        }
+        if let Some(bucket) = self.buckets.back_mut() {
+            for phrase in phrases {
+                if bucket.counts.len() >= self.max_bucket_phrases
+                    && !bucket.counts.contains_key(&phrase)
+                {
+                    bucket.counts.retain(|_, count| *count > 1);
+                    // a bucket full of repeated phrases counts no new ones
+                    if bucket.counts.len() >= self.max_bucket_phrases {
+                        continue;
+                    }
+                }
+                *bucket.counts.entry(phrase).or_default() += 1;
+            }
+        }
+    }
+
+    /// The phrases repeated most within the window with their counts, the most frequent first.
+    /// A phrase is left out if a longer one containing it was repeated as often, so that
+    /// e.g. "gg ez" does not show up next to "gg ez clap".
+    pub fn top(&self, now: Instant) -> Vec<(String, u32)> {
+        let mut counts: HashMap<&str, u32> = HashMap::new();
+        for bucket in self
+            .buckets
+            .iter()
+            .filter(|bucket| now.saturating_duration_since(bucket.started) < TRENDING_WINDOW)
+        {
+            for (phrase, count) in &bucket.counts {
+                *counts.entry(phrase).or_default() += count;
+            }
+        }
+        let mut phrases: Vec<(&str, u32)> =
+            counts.into_iter().filter(|(_, count)| *count > 1).collect();
+        phrases.sort_by(|(a, a_count), (b, b_count)| {
+            b_count
+                .cmp(a_count)
+                .then_with(|| b.split(' ').count().cmp(&a.split(' ').count()))
+                .then_with(|| a.cmp(b))
+        });
+        let mut top: Vec<(String, u32)> = Vec::new();
+        for (phrase, count) in phrases {
+            if top.len() == TRENDING_PHRASES {
+                break;
+            }
+            let contained = top.iter().any(|(longer, longer_count)| {
+                *longer_count == count && format!(" {} ", longer).contains(&format!(" {} ", phrase))
+            });
+            if !contained {
+                top.push((phrase.to_owned(), count));
+            }
+        }
+        top
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| now.saturating_duration_since(bucket.started) >= TRENDING_WINDOW)
+        {
+            self.buckets.pop_front();
+        }
+    }
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|char| char.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_emotes(_: &str) -> bool {
+        false
+    }
+
+    #[test]
+    fn finding_the_trending_phrases() {
+        let mut tracker = PhraseTracker::default();
+        let now = Instant::now();
+        for text in [
+            "GG EZ Clap",
+            "gg, ez clap!",
+            "gg ez clap gg ez clap",
+            "what a play",
+            "What a PLAY",
+            "in the chat",
+            "in the",
+            "Kappa Kappa",
+            "Kappa Kappa",
+            "Kappa gg",
+            "Kappa GG",
+            "once only",
+        ] {
+            tracker.track(text, |word| word == "Kappa", now);
+        }
+        assert_eq!(
+            tracker.top(now + Duration::from_secs(10)),
+            vec![
+                ("gg ez clap".to_owned(), 3),
+                ("what a play".to_owned(), 2),
+                ("Kappa gg".to_owned(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn forgetting_phrases_outside_the_window() {
+        let mut tracker = PhraseTracker::default();
+        let now = Instant::now();
+        tracker.track("first meme", no_emotes, now);
+        tracker.track("first meme", no_emotes, now + Duration::from_secs(120));
+        tracker.track("second meme", no_emotes, now + TRENDING_WINDOW);
+        tracker.track("second meme", no_emotes, now + TRENDING_WINDOW);
+        assert_eq!(
+            tracker.top(now + TRENDING_WINDOW),
+            vec![("second meme".to_owned(), 2)]
+        );
+        assert_eq!(tracker.buckets.len(), 2);
+    }
+
+    #[test]
+    fn pruning_full_buckets() {
+        let mut tracker = PhraseTracker::new(3);
+        let now = Instant::now();
+        tracker.track("hello there", no_emotes, now);
+        tracker.track("hello there", no_emotes, now);
+        tracker.track("one two", no_emotes, now);
+        tracker.track("three four", no_emotes, now);
+        // the bucket is full, the phrases seen once make room
+        tracker.track("five six", no_emotes, now);
+        let counts = &tracker.buckets[0].counts;
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.get("hello there"), Some(&2));
+        assert_eq!(counts.get("five six"), Some(&1));
+        // a bucket full of repeated phrases counts no new ones
+        tracker.track("five six", no_emotes, now);
+        tracker.track("seven eight", no_emotes, now);
+        tracker.track("seven eight", no_emotes, now);
+        tracker.track("nine ten", no_emotes, now);
+        assert_eq!(tracker.buckets[0].counts.len(), 3);
+        assert!(!tracker.buckets[0].counts.contains_key("nine ten"));
+    }
+}
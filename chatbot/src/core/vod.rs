@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+// how long the latest vod and clips are reused before they are requested again
+pub const VIDEO_CACHE_DURATION: Duration = Duration::from_secs(300);
+const ELLIPSIS: char = '…';
+
+/// The answer of the api to `!vod` or `!lastclip`, None if there was nothing to link.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CachedVideo<T> {
+    pub video: Option<T>,
+    pub fetched_at: Instant,
+}
+
+impl<T> CachedVideo<T> {
+    pub fn new(video: Option<T>) -> Self {
+        Self {
+            video,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.fetched_at.elapsed() < VIDEO_CACHE_DURATION
+    }
+}
+
+/// Renders a message with the title, shortening the title with an ellipsis if the message
+/// would be longer than `max_length` characters.
+pub fn fit_title(title: &str, max_length: usize, render: impl Fn(&str) -> String) -> String {
+    let message = render(title);
+    let excess = message.chars().count().saturating_sub(max_length);
+    if excess == 0 {
+        return message;
+    }
+    let kept = title.chars().count().saturating_sub(excess + 1);
+    let mut shortened: String = title.chars().take(kept).collect();
+    shortened = shortened.trim_end().to_owned();
+    shortened.push(ELLIPSIS);
+    render(&shortened)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fitting_titles_into_messages() {
+        let render = |title: &str| format!("VOD: {} (1h) https://twitch.tv/videos/1", title);
+        assert_eq!(
+            fit_title("Short", 100, render),
+            "VOD: Short (1h) https://twitch.tv/videos/1"
+        );
+        let message = fit_title("A very long title about speedruns", 60, render);
+        assert_eq!(
+            message,
+            "VOD: A very long title abou… (1h) https://twitch.tv/videos/1"
+        );
+        assert!(message.chars().count() <= 60);
+        // the title cannot be shortened any further
+        assert_eq!(
+            fit_title("Title", 10, render),
+            "VOD: … (1h) https://twitch.tv/videos/1"
+        );
+    }
+
+    #[test]
+    fn expiring_cached_videos() {
+        let mut cached = CachedVideo::new(Some("https://twitch.tv/videos/1"));
+        assert!(cached.is_fresh());
+        cached.fetched_at = Instant::now() - VIDEO_CACHE_DURATION;
+        assert!(!cached.is_fresh());
+    }
+}
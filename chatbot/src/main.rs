@@ -1,31 +1,110 @@
 use crate::{
-    connect::ChatBotEvent,
+    connect::{
+        enabled_features, missing_scopes, AlertServer, BotStatus, ChatBotEvent, ChatLogger,
+        ControlServer, DiscordNotifier, Event, EventBatches, HelixClient, HelixError, HelixRequest,
+    },
     core::{
+        load_counters, load_disabled_commands, load_game_deaths, load_goals,
+        load_message_milestones, load_quiz_points, load_roles, load_roll_record,
+        load_stream_snapshots, load_usage_stats, store_command_disabled, store_counter,
+        store_game_deaths, store_goal, store_message_milestone, store_quiz_points, store_role,
+        store_roll_record, store_stream_snapshot, usage_totals, AuditLog, Autoresponder, Backup,
         ChatBot,
         ChatBotCommand::{self, *},
+        FileStore, GateConfig, Glossary, GoalKind, InfoConfig, MessageCatalog, MessageMilestones,
+        ModePolicy, QuizQuestion, ResponseRouting, RestoreMode, Store, Template, UsageWriter,
+        BOT_STORE_FILE,
     },
 };
 use app_config::AppConfig;
-use connect::TwitchChatConnector;
-use std::sync::mpsc;
+use chrono::{DateTime, Utc};
+use connect::{SeenMessages, TwitchChatConnector};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::{error::Error, sync::mpsc::Sender};
 use thread_timer::ThreadTimer;
 
 pub mod app_config;
 mod connect;
 mod core;
+mod setup;
+#[cfg(test)]
+mod test_util;
+
+// how long queued messages (e.g. the goodbye) get to be sent before the bot stops
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// Commands which need the main loop, because they are async or replace the connector.
+enum LoopTask {
+    Helix(HelixRequest),
+    ReportStatus,
+    Reconnect,
+    Shutdown,
+}
+
+/// The optional receivers of the bot's output besides the chat.
+#[derive(Clone, Copy)]
+struct Outputs<'a> {
+    alert_server: Option<&'a AlertServer>,
+    discord_notifier: Option<&'a DiscordNotifier>,
+    chat_logger: Option<&'a ChatLogger>,
+    audit_log: &'a AuditLog,
+    usage_writer: &'a UsageWriter,
+}
 
+/// Reads the resident memory of the bot process, only available on linux.
+fn resident_memory_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|memory| memory.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+/// Executes a command of the bot, `sent_at` is the time of the event it answers.
 fn process_command(
     command: ChatBotCommand,
+    sent_at: DateTime<Utc>,
     connector: &TwitchChatConnector,
-    bot_event_sender: Sender<ChatBotEvent>,
+    outputs: Outputs,
+    store: &Arc<dyn Store>,
+    bot_event_sender: Sender<Event>,
+    loop_tasks: &mut Vec<LoopTask>,
 ) -> Result<(), Box<dyn Error>> {
     match command {
         SendMessage(message) => {
             println!("Sending this message : {}", &message);
             connector.send_message(&message)?;
         }
-        LogTextMessage(message) => println!("{}", message),
+        SendMessageTo { channel, text } => {
+            println!("Sending this message to #{} : {}", channel, &text);
+            connector.send_message_to(&channel, &text)?;
+        }
+        SendRaw(line) => {
+            println!("Sending this raw line : {}", &line);
+            connector.send_raw(&line)?;
+        }
+        SendWhisper { user, text } => {
+            println!("Whispering to {} : {}", user, &text);
+            loop_tasks.push(LoopTask::Helix(HelixRequest::Whisper { login: user, text }));
+        }
+        LogTextMessage(message) => {
+            println!("{}", message);
+            if let Some(chat_logger) = outputs.chat_logger {
+                if let Err(error) = chat_logger.log(sent_at, &message) {
+                    println!("Could not log chat message: {:?}", error);
+                }
+            }
+        }
+        // written whether or not the chat is logged
+        Audit(entry) => {
+            if let Err(error) = outputs.audit_log.append(&entry) {
+                println!("Could not write audit entry {:?}: {:?}", entry, error);
+            }
+        }
         TimedCallback { duration, event } => {
             // This timer spawns a thread per invokation, that's bad
             // More serious timers were not a good fit (afaik)
@@ -33,32 +112,563 @@ fn process_command(
             // one or 2 threads for all timers
             let timer = ThreadTimer::new();
             let _ = timer.start(duration, move || {
-                let _ = bot_event_sender.send(event);
+                let _ = bot_event_sender.send(event.into());
             });
         }
+        StoreCounter { name, value } => {
+            if let Err(error) = store_counter(store.as_ref(), &name, value) {
+                println!("Could not store counter {}: {:?}", name, error);
+            }
+        }
+        StoreGoal { kind, target } => {
+            if let Err(error) = store_goal(store.as_ref(), kind, target) {
+                println!("Could not store {} target: {:?}", kind.name(), error);
+            }
+        }
+        // statistics are written in the background, they must never delay the chat
+        StoreUsage {
+            channel,
+            user,
+            counts,
+        } => outputs.usage_writer.write(channel, user, counts),
+        StoreMessageMilestone {
+            channel,
+            user,
+            milestone,
+        } => {
+            if let Err(error) = store_message_milestone(store.as_ref(), &channel, &user, milestone)
+            {
+                println!("Could not store the milestone of {}: {:?}", user, error);
+            }
+        }
+        StoreQuizPoints(points) => {
+            if let Err(error) = store_quiz_points(store.as_ref(), &points) {
+                println!("Could not store quiz points: {:?}", error);
+            }
+        }
+        StoreRollRecord(record) => {
+            if let Err(error) = store_roll_record(store.as_ref(), &record) {
+                println!("Could not store roll record: {:?}", error);
+            }
+        }
+        StoreGameDeaths { game_id, game } => {
+            if let Err(error) = store_game_deaths(store.as_ref(), &game_id, &game) {
+                println!("Could not store the deaths in {}: {:?}", game.name, error);
+            }
+        }
+        StoreStreamSnapshot {
+            stream_id,
+            snapshot,
+        } => {
+            if let Err(error) = store_stream_snapshot(store.as_ref(), &stream_id, &snapshot) {
+                println!(
+                    "Could not store the snapshot of stream {}: {:?}",
+                    stream_id, error
+                );
+            }
+        }
+        StoreCommandDisabled { name, disabled } => {
+            if let Err(error) = store_command_disabled(store.as_ref(), &name, disabled) {
+                println!("Could not store whether !{} is disabled: {:?}", name, error);
+            }
+        }
+        StoreRole { user, role } => {
+            if let Err(error) = store_role(store.as_ref(), &user, role) {
+                println!("Could not store the role of {}: {:?}", user, error);
+            }
+        }
+        // helix requests are async, so they are executed by the main loop
+        ChatBotCommand::HelixRequest(request) => loop_tasks.push(LoopTask::Helix(request)),
+        ReportStatus => loop_tasks.push(LoopTask::ReportStatus),
+        Reconnect => loop_tasks.push(LoopTask::Reconnect),
+        Shutdown => loop_tasks.push(LoopTask::Shutdown),
+        // the connector sends the result back as ChatBotEvent::Latency
+        MeasureLatency => {
+            if let Err(error) = connector.measure_latency() {
+                println!("Could not measure latency: {:?}", error);
+                bot_event_sender.send(ChatBotEvent::Latency(None).into())?;
+            }
+        }
+        LogUnparsedLines(count) => {
+            let (entries, total) = connector.unparsed_lines(count);
+            println!(
+                "{} unparsed chat lines since connecting, the last ones:",
+                total
+            );
+            for entry in entries {
+                println!("{} {}", entry.sent_at.to_rfc3339(), entry.line);
+            }
+        }
+        // without overlays the bot is not configured to send alerts
+        Alert(alert) => {
+            if let Some(alert_server) = outputs.alert_server {
+                alert_server.broadcast(&alert);
+            }
+        }
+        AnnounceGoLive(announcement) => {
+            if let Some(discord_notifier) = outputs.discord_notifier.cloned() {
+                tokio::spawn(async move {
+                    if let Err(error) = discord_notifier.send(&announcement.discord_message()).await
+                    {
+                        println!("Could not announce going live on discord: {:?}", error);
+                    }
+                });
+            }
+        }
+        NotifyOwners(text) => {
+            println!("{}", text);
+            if let Some(discord_notifier) = outputs.discord_notifier.cloned() {
+                tokio::spawn(async move {
+                    if let Err(error) = discord_notifier.send(&json!({ "content": text })).await {
+                        println!("Could not notify the owners on discord: {:?}", error);
+                    }
+                });
+            }
+        }
         MultipleCommands(new_commands) => {
             for command in new_commands {
-                process_command(command, connector, bot_event_sender.clone())?;
+                process_command(
+                    command,
+                    sent_at,
+                    connector,
+                    outputs,
+                    store,
+                    bot_event_sender.clone(),
+                    loop_tasks,
+                )?;
             }
         }
     }
     Ok(())
 }
 
+/// The value after an option of the command line, e.g. the file of `--out backup.json`.
+/// Sends a request to the Helix api and hands the response to the bot.
+async fn execute_helix(
+    helix_client: Option<Arc<HelixClient>>,
+    request: HelixRequest,
+    bot_event_sender: Sender<Event>,
+) {
+    let response = match helix_client {
+        Some(helix_client) => helix_client.execute(request).await,
+        None => request.failed(HelixError::Unavailable(
+            "Helix api is not available".to_owned(),
+        )),
+    };
+    // the loop only stops when the bot shuts down
+    let _ = bot_event_sender.send(ChatBotEvent::HelixResponse(response).into());
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == name)?;
+    args.get(position + 1).map(String::as_str)
+}
+
+/// `backup --out <file>` writes the data of the store to a backup file.
+fn backup(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = option_value(args, "--out").ok_or("Usage: backup --out <file>")?;
+    Backup::create(&FileStore::new(BOT_STORE_FILE)?)?.save(path)?;
+    println!("Backed up the store to {}", path);
+    Ok(())
+}
+
+/// `restore --in <file> [--merge|--replace]` writes a backup file to the store, merging by default.
+fn restore(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "Usage: restore --in <file> [--merge|--replace]";
+    let path = option_value(args, "--in").ok_or(usage)?;
+    let mode = match (
+        args.iter().any(|arg| arg == "--merge"),
+        args.iter().any(|arg| arg == "--replace"),
+    ) {
+        (true, true) => return Err(usage.into()),
+        (_, true) => RestoreMode::Replace,
+        _ => RestoreMode::Merge,
+    };
+    // the whole backup is validated before the store is touched
+    let backup = Backup::load(path)?;
+    let report = backup.restore(&FileStore::new(BOT_STORE_FILE)?, mode)?;
+    for entry in &report.added {
+        println!("Added {}", entry);
+    }
+    for entry in &report.changed {
+        println!("Changed {}", entry);
+    }
+    for entry in &report.removed {
+        println!("Removed {}", entry);
+    }
+    println!(
+        "Restored {}: {} added, {} changed, {} removed",
+        path,
+        report.added.len(),
+        report.changed.len(),
+        report.removed.len()
+    );
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let app_config = AppConfig::new()?;
+    // backups only need the store, the bot does not connect, setup connects once
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("backup") => return backup(&args[1..]),
+        Some("restore") => return restore(&args[1..]),
+        Some("setup") => return Ok(setup::run().await?),
+        _ => {}
+    }
+
+    let app_config = &AppConfig::new()?;
 
     let (tx, rx) = mpsc::channel();
 
-    let connector = TwitchChatConnector::new(&app_config, tx.clone()).await;
-    connector.send_message("Hello, world!")?;
+    let messages = match app_config.locale() {
+        Some(locale) => MessageCatalog::load(locale)?,
+        None => MessageCatalog::default(),
+    };
+    for warning in messages.validate() {
+        println!("Message catalog warning: {}", warning);
+    }
+
+    if app_config.dry_run() {
+        println!(
+            "Dry run: messages to #{} are logged instead of sent, except those of owner commands",
+            app_config.channel_name()
+        );
+    }
+
+    let started_at = Instant::now();
+    let seen_messages = Arc::new(Mutex::new(SeenMessages::new(
+        app_config.seen_messages(),
+        app_config.seen_messages_retention(),
+    )));
+    let mut connector =
+        TwitchChatConnector::new(app_config, tx.clone(), seen_messages.clone()).await?;
+    println!("Joined #{}", connector.channels().join(", #"));
+
+    if let (Some(port), Some(secret)) = (app_config.control_port(), app_config.control_secret()) {
+        let control_server =
+            ControlServer::bind(port, secret, app_config.channel_name(), tx.clone()).await?;
+        tokio::spawn(control_server.run());
+    }
+
+    let alert_server = app_config.alert_port().map(AlertServer::bind).transpose()?;
+    let discord_notifier = app_config.discord_webhook().map(DiscordNotifier::new);
+    let chat_logger = app_config
+        .chat_log()
+        .map(|chat_log| {
+            ChatLogger::new(
+                chat_log,
+                app_config.chat_log_format(),
+                app_config.chat_log_timezone(),
+            )
+        })
+        .transpose()?;
+
+    let audit_log = AuditLog::new(app_config.audit_log());
+    // a broken audit log must not keep the bot from moderating, it is only appended to
+    let audit_trail = audit_log.load().unwrap_or_else(|error| {
+        println!("Could not load the audit log: {:?}", error);
+        Vec::new()
+    });
+
+    let helix_client = match HelixClient::new(app_config).await {
+        Ok(helix_client) => Some(helix_client),
+        Err(error) => {
+            println!("Helix api is not available: {:?}", error);
+            None
+        }
+    };
+    let scoped_features = enabled_features(app_config);
+    if let Some(helix_client) = &helix_client {
+        match helix_client.scopes().await {
+            Ok(scopes) => {
+                for feature in missing_scopes(&scoped_features, &scopes) {
+                    println!("{}", feature.warning());
+                }
+            }
+            Err(error) => println!(
+                "Could not check the scopes of the access token: {:?}",
+                error
+            ),
+        }
+    }
+
+    // the Helix requests run in tasks of their own, a throttled or retried one must not stop the
+    // chat; the requests with side effects are sent one after another in the order the bot made
+    // them (e.g. a prediction is locked only after it was created), reading ones run on their own
+    let helix_client = helix_client.map(Arc::new);
+    let (helix_sequence, mut helix_sequence_receiver) = tokio::sync::mpsc::unbounded_channel();
+    {
+        let helix_client = helix_client.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while let Some(request) = helix_sequence_receiver.recv().await {
+                execute_helix(helix_client.clone(), request, tx.clone()).await;
+            }
+        });
+    }
+
+    let store: Arc<dyn Store> = Arc::new(FileStore::new(BOT_STORE_FILE)?);
+    let usage_writer = UsageWriter::new(store.clone());
+
+    // targets set with a goal command replace the configured ones
+    let mut goals: HashMap<GoalKind, u64> = [
+        (GoalKind::Followers, app_config.follow_goal()),
+        (GoalKind::SubPoints, app_config.sub_goal()),
+    ]
+    .into_iter()
+    .filter_map(|(kind, target)| Some((kind, target?)))
+    .collect();
+    goals.extend(load_goals(store.as_ref())?);
+
+    let usage_stats = load_usage_stats(store.as_ref(), app_config.channel_name())?;
+    let mut totals: Vec<(String, u64)> = usage_totals(store.as_ref(), app_config.channel_name())?
+        .into_iter()
+        .collect();
+    totals.sort_by(|(_, count), (_, other_count)| other_count.cmp(count));
+    println!(
+        "Usage statistics loaded, most used: {:?}",
+        &totals[..totals.len().min(5)]
+    );
+
+    let gates = match app_config.gates_file() {
+        Some(gates_file) => GateConfig::load(gates_file)?,
+        None => GateConfig::default(),
+    };
+
+    let info = match app_config.info_file() {
+        Some(info_file) => InfoConfig::load(info_file)?,
+        None => InfoConfig::default(),
+    };
+
+    let glossary = match app_config.glossary_file() {
+        Some(glossary_file) => Glossary::load(glossary_file)?,
+        None => Glossary::default(),
+    };
+
+    let autoresponder = match app_config.autoresponses_file() {
+        Some(autoresponses_file) => Autoresponder::load(autoresponses_file)?,
+        None => Autoresponder::default(),
+    };
+
+    // a broken go live message is reported now, not when the stream goes live
+    let go_live_template = app_config
+        .go_live_message()
+        .map(Template::parse)
+        .transpose()?;
+    let connect_greeting = app_config
+        .connect_greeting()
+        .map(Template::parse)
+        .transpose()?;
+    let mention_reply = app_config
+        .mention_reply()
+        .map(Template::parse)
+        .transpose()?;
+    let viewer_milestone_reply = app_config
+        .viewer_milestone_reply()
+        .map(Template::parse)
+        .transpose()?;
+
+    let message_milestones = match app_config.message_milestones() {
+        [] => None,
+        thresholds => Some(
+            MessageMilestones::new(
+                Template::parse(app_config.message_milestone_reply())?,
+                thresholds.to_vec(),
+                app_config.milestone_cap(),
+            )
+            .with_reached(load_message_milestones(
+                store.as_ref(),
+                app_config.channel_name(),
+            )?),
+        ),
+    };
+
+    let quiz_questions = match app_config.quiz_file() {
+        Some(quiz_file) => QuizQuestion::load(quiz_file)?,
+        None => Vec::new(),
+    };
 
-    let mut chat_bot = ChatBot::new();
-    while let Ok(message) = rx.recv() {
-        if let Some(bot_command) = chat_bot.handle_event(message) {
-            process_command(bot_command, &connector, tx.clone())?;
+    let mut chat_bot_builder = ChatBot::builder()
+        .counters(load_counters(store.as_ref())?)
+        .nickname(app_config.bot_user_name())
+        .command_prefixes(app_config.command_prefixes().clone());
+    for (command_name, cooldown) in app_config.command_cooldowns() {
+        chat_bot_builder = chat_bot_builder.cooldown(command_name, *cooldown);
+    }
+    for command_name in app_config.command_cooldowns_for_mods() {
+        chat_bot_builder = chat_bot_builder.mods_bypass_cooldown(command_name, false);
+    }
+    chat_bot_builder = chat_bot_builder
+        .messages(messages)
+        .goals(goals)
+        .mention_user(app_config.mention_user())
+        .emotes(app_config.emotes().clone())
+        .usage_stats(usage_stats)
+        .gates(gates)
+        .unknown_command_reply(app_config.unknown_command_reply())
+        .reset_cooldowns_on_connect(app_config.reset_cooldowns_on_connect())
+        .go_live(app_config.channel_name(), go_live_template)
+        .connect_greeting(connect_greeting)
+        .mention_reply(mention_reply, app_config.mention_cooldown())
+        .announcement_triggers(app_config.announcement_triggers())
+        .viewer_milestone_reply(viewer_milestone_reply)
+        .emote_only_fallback(app_config.emote_only_fallback().map(str::to_owned))
+        .config_summary(app_config.redacted_summary())
+        .game_deaths(load_game_deaths(store.as_ref())?)
+        .dry_run(app_config.dry_run())
+        .raid_protection(app_config.raid_protection().cloned())
+        .greeting_throttle(app_config.greeting_throttle().cloned())
+        .history_size(app_config.history_size())
+        .slow_command(app_config.slow_command())
+        .quiz(quiz_questions, load_quiz_points(store.as_ref())?)
+        .roll_record(load_roll_record(store.as_ref())?)
+        .stream_snapshots(load_stream_snapshots(store.as_ref())?)
+        .disabled_commands(load_disabled_commands(store.as_ref())?)
+        .confirmations(app_config.confirm_commands())
+        .audit_trail(audit_trail)
+        .info(info)
+        .glossary(glossary)
+        .autoresponder(autoresponder)
+        .message_milestones(message_milestones)
+        .owners(app_config.owners().iter().cloned().collect())
+        .regulars(app_config.regulars())
+        .native_shoutout(app_config.native_shoutout())
+        .scoped_features(scoped_features)
+        .bot_colors(app_config.bot_colors().to_vec())
+        .commands_anywhere(
+            app_config
+                .commands_anywhere()
+                .then_some(app_config.commands_anywhere_max_permission()),
+        )
+        .response_routing(ResponseRouting::new(
+            app_config.respond_via(),
+            app_config.respond_via_commands().clone(),
+        ))
+        .mode_policy(ModePolicy::new(
+            app_config.restricted_suppress(),
+            app_config.restricted_resend_timer(),
+        ));
+    if alert_server.is_some() {
+        chat_bot_builder = chat_bot_builder.alerts(
+            app_config.alert_events().iter().cloned().collect(),
+            app_config.alert_min_bits(),
+        );
+    }
+    if app_config.role_changes() {
+        chat_bot_builder = chat_bot_builder.roles(load_roles(store.as_ref())?);
+    }
+    let mut chat_bot = chat_bot_builder.build();
+    if app_config.go_live_check() {
+        tx.send(ChatBotEvent::CheckStream.into())?;
+    }
+    tx.send(ChatBotEvent::RefreshEmotes.into())?;
+    let outputs = Outputs {
+        alert_server: alert_server.as_ref(),
+        discord_notifier: discord_notifier.as_ref(),
+        chat_logger: chat_logger.as_ref(),
+        audit_log: &audit_log,
+        usage_writer: &usage_writer,
+    };
+    let mut loop_tasks = Vec::new();
+    let mut event_batches = EventBatches::new(rx, app_config.event_batch_size());
+    while let Some(batch) = event_batches.next_batch() {
+        if event_batches.backlog() > 0 {
+            println!("{} events wait for the next batch", event_batches.backlog());
+        }
+        let dropped = event_batches.take_dropped();
+        if dropped > 0 {
+            println!("Dropped {} chat events, the backlog was full", dropped);
+        }
+        for event in batch {
+            // events of the bot itself (e.g. of its timers) did not come from the chat
+            if !event.raw.is_empty() {
+                println!(
+                    "{} #{}: {}",
+                    event.sent_at.to_rfc3339(),
+                    event.channel.as_deref().unwrap_or("-"),
+                    event.raw
+                );
+            }
+            let sent_at = event.sent_at;
+            if let Some(bot_command) = chat_bot.handle_event(event) {
+                process_command(
+                    bot_command,
+                    sent_at,
+                    &connector,
+                    outputs,
+                    &store,
+                    tx.clone(),
+                    &mut loop_tasks,
+                )?;
+            }
+            // audited even if the reply was dropped, e.g. in a restricted chat mode
+            if let Some(audit_entries) = chat_bot.take_audit_entries() {
+                process_command(
+                    audit_entries,
+                    sent_at,
+                    &connector,
+                    outputs,
+                    &store,
+                    tx.clone(),
+                    &mut loop_tasks,
+                )?;
+            }
+            if let Some(store_usage) = chat_bot.take_pending_usage() {
+                process_command(
+                    store_usage,
+                    sent_at,
+                    &connector,
+                    outputs,
+                    &store,
+                    tx.clone(),
+                    &mut loop_tasks,
+                )?;
+            }
+            for task in loop_tasks.drain(..) {
+                match task {
+                    LoopTask::Helix(request) if request.has_side_effects() => {
+                        // the task only stops when the bot shuts down
+                        let _ = helix_sequence.send(request);
+                    }
+                    LoopTask::Helix(request) => {
+                        tokio::spawn(execute_helix(helix_client.clone(), request, tx.clone()));
+                    }
+                    LoopTask::ReportStatus => tx.send(
+                        ChatBotEvent::Status(BotStatus {
+                            uptime: started_at.elapsed(),
+                            connected: connector.is_connected(),
+                            reconnects: connector.reconnect_count(),
+                            queued_messages: connector.queued_messages(),
+                            memory_kb: resident_memory_kb(),
+                            suppressed_duplicates: connector.suppressed_duplicates(),
+                            latency: connector.latency(),
+                            last_latency: connector.last_latency(),
+                            helix: helix_client.as_deref().map(HelixClient::metrics),
+                        })
+                        .into(),
+                    )?,
+                    LoopTask::Reconnect => {
+                        println!("Reconnecting to the chat");
+                        connector = connector.reconnect().await?;
+                    }
+                    LoopTask::Shutdown => {
+                        println!("Shutting down");
+                        let shutdown_at = Instant::now();
+                        while connector.queued_messages() > 0
+                            && shutdown_at.elapsed() < SHUTDOWN_GRACE
+                        {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                        }
+                        connector.close()?;
+                        return Ok(());
+                    }
+                }
+            }
         }
+        // the tasks of the bot (e.g. the Helix requests and the control interface) get their
+        // turn before the next batch
+        tokio::task::yield_now().await;
     }
     Ok(())
 }
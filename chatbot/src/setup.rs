@@ -0,0 +1,241 @@
+use crate::{
+    app_config::{AppConfig, AppConfigError},
+    connect::{
+        authorization_scopes, http_client, poll_device_tokens, request_device_code,
+        restrict_permissions, store_authorization, ConnectorError, SeenMessages,
+        TwitchChatConnector, ID_URL,
+    },
+};
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+// the file the configuration is written to, which the bot reads at startup
+const ENV_FILE: &str = ".env";
+const TEST_MESSAGE: &str = "Hello chat, the bot is set up!";
+// how long the test message gets to be sent
+const SEND_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum SetupError {
+    #[error("Setup cancelled, {} was left as it was", ENV_FILE)]
+    Cancelled,
+    #[error("Could not read the answer [{}]", .0)]
+    Io(#[from] io::Error),
+    #[error("Could not authorize the bot [{}]", .0)]
+    Authorization(#[from] ConnectorError),
+    #[error("The written configuration is invalid [{}]", .0)]
+    Config(#[from] AppConfigError),
+}
+
+/// What happens to a configuration which exists already.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Existing {
+    Overwrite,
+    // the answers replace their variables, the other variables are kept
+    Merge,
+}
+
+/// The value of a variable in the lines of an env file, e.g. `TWITCH_CHANNEL=carkhy`.
+fn env_value<'a>(env: &'a str, name: &str) -> Option<&'a str> {
+    env.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(name)?.strip_prefix('=')?;
+        Some(value.trim().trim_matches('"'))
+    })
+}
+
+/// The env file with the given variables, whose earlier values are replaced where they are.
+fn merge_env(env: &str, values: &[(&str, String)]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in env.lines() {
+        let replaced = values.iter().find(|(name, _)| {
+            line.trim()
+                .strip_prefix(name)
+                .is_some_and(|rest| rest.starts_with('='))
+        });
+        lines.push(match replaced {
+            Some((name, value)) => format!("{}={}", name, value),
+            None => line.to_owned(),
+        });
+    }
+    for (name, value) in values {
+        if env_value(env, name).is_none() {
+            lines.push(format!("{}={}", name, value));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Asks a question on the terminal, an empty answer takes the default.
+fn ask(input: &mut impl BufRead, question: &str, default: Option<&str>) -> io::Result<String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+            _ => print!("{}: ", question),
+        }
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        match (answer.trim(), default) {
+            ("", Some(default)) if !default.is_empty() => return Ok(default.to_owned()),
+            ("", _) => continue,
+            (answer, _) => return Ok(answer.to_owned()),
+        }
+    }
+}
+
+fn ask_existing(input: &mut impl BufRead) -> Result<Existing, SetupError> {
+    loop {
+        let question = format!(
+            "{} exists already, (m)erge the answers into it, (o)verwrite it or (c)ancel?",
+            ENV_FILE
+        );
+        match ask(input, &question, Some("m"))?.to_lowercase().as_str() {
+            "m" | "merge" => return Ok(Existing::Merge),
+            "o" | "overwrite" => return Ok(Existing::Overwrite),
+            "c" | "cancel" => return Err(SetupError::Cancelled),
+            _ => continue,
+        }
+    }
+}
+
+fn write_env(env: &str) -> io::Result<()> {
+    fs::write(ENV_FILE, env)?;
+    // the client secret must not be readable by other users
+    restrict_permissions(ENV_FILE, 0o600)
+}
+
+/// `setup` asks for the settings the bot needs, authorizes it with the device code flow,
+/// writes the settings to `.env` and sends a test message to the chat.
+pub async fn run() -> Result<(), SetupError> {
+    let mut input = io::stdin().lock();
+    let existing = fs::read_to_string(ENV_FILE).ok();
+    let mode = match existing {
+        Some(_) => ask_existing(&mut input)?,
+        None => Existing::Overwrite,
+    };
+    let env = existing.as_deref().unwrap_or_default();
+    let bot_name = ask(
+        &mut input,
+        "Name of the bot's twitch account",
+        env_value(env, "TWITCH_CHAT_USER"),
+    )?;
+    let channel = ask(
+        &mut input,
+        "Channel the bot chats in",
+        env_value(env, "TWITCH_CHANNEL"),
+    )?
+    .to_lowercase();
+    let client_id = ask(
+        &mut input,
+        "Client id of the twitch application",
+        env_value(env, "TWITCH_AUTH_CLIENT_ID"),
+    )?;
+    let client_secret = ask(
+        &mut input,
+        "Client secret of the twitch application",
+        env_value(env, "TWITCH_AUTH_CLIENT_SECRET"),
+    )?;
+    let default_scopes = authorization_scopes().join(" ");
+    let scopes: Vec<String> = ask(
+        &mut input,
+        "Scopes of the access token, separated by spaces",
+        Some(&default_scopes),
+    )?
+    .split_whitespace()
+    .map(str::to_owned)
+    .collect();
+    drop(input);
+
+    let client = http_client(None).map_err(ConnectorError::from)?;
+    let device = request_device_code(&client, ID_URL, &client_id, &scopes).await?;
+    println!(
+        "Log in as {} at {} and enter the code {} within {} minutes",
+        bot_name,
+        device.verification_uri,
+        device.user_code,
+        device.expires_in.as_secs() / 60
+    );
+    let (access_token, refresh_token) =
+        poll_device_tokens(&client, ID_URL, &client_id, &scopes, &device).await?;
+    store_authorization(&access_token, &refresh_token, &scopes)?;
+    println!("The bot is authorized");
+
+    let values = [
+        ("TWITCH_CHAT_USER", bot_name),
+        ("TWITCH_CHANNEL", channel),
+        ("TWITCH_AUTH_CLIENT_ID", client_id),
+        ("TWITCH_AUTH_CLIENT_SECRET", client_secret),
+    ];
+    write_env(&match mode {
+        Existing::Merge => merge_env(env, &values),
+        Existing::Overwrite => merge_env("", &values),
+    })?;
+    println!("Wrote the configuration to {}", ENV_FILE);
+
+    // the variables of the process take precedence over the file
+    for (name, value) in &values {
+        std::env::set_var(name, value);
+    }
+    let app_config = AppConfig::new()?;
+    let (tx, _rx) = mpsc::channel();
+    let seen_messages = Arc::new(Mutex::new(SeenMessages::new(
+        app_config.seen_messages(),
+        app_config.seen_messages_retention(),
+    )));
+    let connector = TwitchChatConnector::new(&app_config, tx, seen_messages).await?;
+    connector.send_message(TEST_MESSAGE)?;
+    let sent_at = Instant::now();
+    while connector.queued_messages() > 0 && sent_at.elapsed() < SEND_GRACE {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    connector.close()?;
+    println!(
+        "Sent a test message to #{}, run the bot without `setup` now",
+        app_config.channel_name()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merging_env_files() {
+        let env = "# the bot\nTWITCH_CHANNEL=\"carkhy\"\nTWITCH_BOT_LOCALE=de\n";
+        assert_eq!(env_value(env, "TWITCH_CHANNEL"), Some("carkhy"));
+        assert_eq!(env_value(env, "TWITCH_CHAT"), None);
+        let values = [
+            ("TWITCH_CHANNEL", "captaincallback".to_owned()),
+            ("TWITCH_CHAT_USER", "botanist".to_owned()),
+        ];
+        assert_eq!(
+            merge_env(env, &values),
+            "# the bot\nTWITCH_CHANNEL=captaincallback\nTWITCH_BOT_LOCALE=de\n\
+             TWITCH_CHAT_USER=botanist\n"
+        );
+        assert_eq!(
+            merge_env("", &values),
+            "TWITCH_CHANNEL=captaincallback\nTWITCH_CHAT_USER=botanist\n"
+        );
+    }
+
+    #[test]
+    fn asking_with_defaults() {
+        let mut input = io::Cursor::new("\n\ncarkhy\n");
+        assert_eq!(
+            ask(&mut input, "Channel", Some("botanist")).unwrap(),
+            "botanist"
+        );
+        // without a default the question is repeated
+        assert_eq!(ask(&mut input, "Channel", None).unwrap(), "carkhy");
+        assert!(ask(&mut input, "Channel", None).is_err());
+    }
+}
@@ -0,0 +1,21 @@
+use crate::connect::{Badge, UserInfo};
+
+/// The options of a command, as they are split from a chat message.
+pub fn options(options: &[&str]) -> Vec<String> {
+    options.iter().map(|option| option.to_string()).collect()
+}
+
+/// A chatter with the given badges.
+pub fn user(badges: &[&str]) -> UserInfo {
+    UserInfo {
+        name: "Carkhy".to_owned(),
+        id: None,
+        badges: badges
+            .iter()
+            .map(|name| Badge {
+                name: name.to_string(),
+                level: 1,
+            })
+            .collect(),
+    }
+}